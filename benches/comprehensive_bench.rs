@@ -1,64 +1,25 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
 use glifzip::{compress, decompress, CompressionConfig};
-use std::fs::{File, create_dir_all};
-use std::io::Write;
-use std::time::Instant;
+use glifzip::testing::data::{generate, DataKind, DEFAULT_SEED};
 
 /// Generate random uncompressible data
 fn generate_random_data(size: usize) -> Vec<u8> {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hash, Hasher};
-
-    let mut data = Vec::with_capacity(size);
-    let state = RandomState::new();
-
-    for i in 0..size {
-        let mut hasher = state.build_hasher();
-        i.hash(&mut hasher);
-        data.push((hasher.finish() % 256) as u8);
-    }
-
-    data
+    generate(DataKind::Random, size, DEFAULT_SEED)
 }
 
 /// Generate highly compressible text data
 fn generate_compressible_text(size: usize) -> Vec<u8> {
-    let pattern = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. ";
-    let mut data = Vec::with_capacity(size);
-
-    while data.len() < size {
-        data.extend_from_slice(pattern);
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Text, size, DEFAULT_SEED)
 }
 
 /// Generate source code-like data
 fn generate_source_code(size: usize) -> Vec<u8> {
-    let code_patterns = [
-        b"fn main() {\n    println!(\"Hello, world!\");\n}\n",
-        b"pub struct MyStruct {\n    field1: u32,\n    field2: String,\n}\n",
-        b"impl MyTrait for MyStruct {\n    fn method(&self) -> bool {\n        true\n    }\n}\n",
-        b"use std::collections::HashMap;\n",
-        b"#[derive(Debug, Clone)]\n",
-    ];
-
-    let mut data = Vec::with_capacity(size);
-    let mut idx = 0;
-
-    while data.len() < size {
-        data.extend_from_slice(code_patterns[idx % code_patterns.len()]);
-        idx += 1;
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Code, size, DEFAULT_SEED)
 }
 
 /// Generate repeated zeros (highly compressible)
 fn generate_zeros(size: usize) -> Vec<u8> {
-    vec![0u8; size]
+    generate(DataKind::Zeros, size, DEFAULT_SEED)
 }
 
 /// Benchmark compression throughput with different data types
@@ -1,76 +1,38 @@
-/// Comprehensive Performance Benchmark Suite for GLifzip
-///
-/// This benchmark suite measures:
-/// - Compression/decompression throughput (GB/s per core)
-/// - Multi-core scaling (1, 2, 4, 8, 16 cores)
-/// - Compression ratios by data type
-/// - Comparison with ZIP baseline
-///
-/// Outputs:
-/// - CSV results for analysis
-/// - Performance reports
-/// - Raw data for visualization
+//! Comprehensive Performance Benchmark Suite for GLifzip
+//!
+//! This benchmark suite measures:
+//! - Compression/decompression throughput (GB/s per core)
+//! - Multi-core scaling (1, 2, 4, 8, 16 cores)
+//! - Compression ratios by data type
+//! - Comparison with ZIP baseline
+//!
+//! Outputs:
+//! - CSV results for analysis
+//! - Performance reports
+//! - Raw data for visualization
 
 use std::fs::{File, create_dir_all};
 use std::io::Write as IoWrite;
 use std::time::Instant;
 use std::path::Path;
 use glifzip::{compress, decompress, CompressionConfig};
+use glifzip::testing::data::{generate, DataKind, DEFAULT_SEED};
 
-// Data generation functions
+// Data generation, shared with `glifzip gen-testdata`
 fn generate_random_data(size: usize) -> Vec<u8> {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hash, Hasher};
-
-    let mut data = Vec::with_capacity(size);
-    let state = RandomState::new();
-
-    for i in 0..size {
-        let mut hasher = state.build_hasher();
-        i.hash(&mut hasher);
-        data.push((hasher.finish() % 256) as u8);
-    }
-
-    data
+    generate(DataKind::Random, size, DEFAULT_SEED)
 }
 
 fn generate_compressible_text(size: usize) -> Vec<u8> {
-    let pattern = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. \
-                     Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. \
-                     Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris. ";
-    let mut data = Vec::with_capacity(size);
-
-    while data.len() < size {
-        data.extend_from_slice(pattern);
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Text, size, DEFAULT_SEED)
 }
 
 fn generate_source_code(size: usize) -> Vec<u8> {
-    let code_patterns = [
-        b"fn main() {\n    println!(\"Hello, world!\");\n}\n\n".as_slice(),
-        b"pub struct MyStruct {\n    field1: u32,\n    field2: String,\n}\n\n".as_slice(),
-        b"impl MyTrait for MyStruct {\n    fn method(&self) -> bool {\n        true\n    }\n}\n\n".as_slice(),
-        b"use std::collections::HashMap;\nuse std::io::{Read, Write};\n\n".as_slice(),
-        b"#[derive(Debug, Clone, PartialEq)]\npub enum MyEnum {\n    Variant1,\n    Variant2(u32),\n}\n\n".as_slice(),
-    ];
-
-    let mut data = Vec::with_capacity(size);
-    let mut idx = 0;
-
-    while data.len() < size {
-        data.extend_from_slice(code_patterns[idx % code_patterns.len()]);
-        idx += 1;
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Code, size, DEFAULT_SEED)
 }
 
 fn generate_zeros(size: usize) -> Vec<u8> {
-    vec![0u8; size]
+    generate(DataKind::Zeros, size, DEFAULT_SEED)
 }
 
 #[derive(Debug, Clone)]
@@ -335,7 +297,7 @@ fn generate_summary_report(results: &[BenchmarkResult]) {
     writeln!(file, "GLifzip Performance Benchmark Report").unwrap();
     writeln!(file, "=====================================").unwrap();
     writeln!(file, "Generated: {}", chrono::Local::now()).unwrap();
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
 
     // Throughput summary
     writeln!(file, "THROUGHPUT BENCHMARKS (1 GB datasets)").unwrap();
@@ -349,7 +311,7 @@ fn generate_summary_report(results: &[BenchmarkResult]) {
         if let Some(ratio) = result.compression_ratio {
             writeln!(file, "  Compression Ratio: {:.2}%", ratio).unwrap();
         }
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
     }
 
     // Scaling summary
@@ -366,7 +328,7 @@ fn generate_summary_report(results: &[BenchmarkResult]) {
             writeln!(file, "{} core(s):", threads).unwrap();
             writeln!(file, "  Compression: {:.2} GB/s", comp.throughput_gbps).unwrap();
             writeln!(file, "  Decompression: {:.2} GB/s", decomp.throughput_gbps).unwrap();
-            writeln!(file, "").unwrap();
+            writeln!(file).unwrap();
         }
     }
 
@@ -380,7 +342,7 @@ fn generate_summary_report(results: &[BenchmarkResult]) {
         }
     }
 
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
     writeln!(file, "Report saved to: {:?}", path).unwrap();
 
     println!("\nPerformance report generated: {:?}", path);
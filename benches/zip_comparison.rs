@@ -1,62 +1,26 @@
-/// ZIP Baseline Comparison Benchmark
-///
-/// Compares GLifzip performance against standard ZIP compression
-/// (using the `zip` crate which is the Rust equivalent of Windows/macOS ZIP)
+//! ZIP Baseline Comparison Benchmark
+//!
+//! Compares GLifzip performance against standard ZIP compression
+//! (using the `zip` crate which is the Rust equivalent of Windows/macOS ZIP)
 
 use std::fs::{File, create_dir_all};
 use std::io::Write as IoWrite;
 use std::time::Instant;
 use std::path::Path;
 use glifzip::{compress as glifzip_compress, decompress as glifzip_decompress, CompressionConfig};
+use glifzip::testing::data::{generate, DataKind, DEFAULT_SEED};
 
-// Data generation (same as performance_suite)
+// Data generation (same as performance_suite and `glifzip gen-testdata`)
 fn generate_random_data(size: usize) -> Vec<u8> {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hash, Hasher};
-
-    let mut data = Vec::with_capacity(size);
-    let state = RandomState::new();
-
-    for i in 0..size {
-        let mut hasher = state.build_hasher();
-        i.hash(&mut hasher);
-        data.push((hasher.finish() % 256) as u8);
-    }
-
-    data
+    generate(DataKind::Random, size, DEFAULT_SEED)
 }
 
 fn generate_compressible_text(size: usize) -> Vec<u8> {
-    let pattern = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. \
-                     Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. \
-                     Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris. ";
-    let mut data = Vec::with_capacity(size);
-
-    while data.len() < size {
-        data.extend_from_slice(pattern);
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Text, size, DEFAULT_SEED)
 }
 
 fn generate_source_code(size: usize) -> Vec<u8> {
-    let code_patterns = [
-        b"fn main() {\n    println!(\"Hello, world!\");\n}\n\n".as_slice(),
-        b"pub struct MyStruct {\n    field1: u32,\n    field2: String,\n}\n\n".as_slice(),
-        b"impl MyTrait for MyStruct {\n    fn method(&self) -> bool {\n        true\n    }\n}\n\n".as_slice(),
-    ];
-
-    let mut data = Vec::with_capacity(size);
-    let mut idx = 0;
-
-    while data.len() < size {
-        data.extend_from_slice(code_patterns[idx % code_patterns.len()]);
-        idx += 1;
-    }
-
-    data.truncate(size);
-    data
+    generate(DataKind::Code, size, DEFAULT_SEED)
 }
 
 #[derive(Debug)]
@@ -205,7 +169,7 @@ fn run_comparison(data_type: &str, data: &[u8]) -> ComparisonResult {
         zip_decompress_ms: zip_decomp_ms,
         zip_compress_mbps: zip_comp_mbps,
         zip_decompress_mbps: zip_decomp_mbps,
-        zip_ratio: zip_ratio,
+        zip_ratio,
         speedup_compression: speedup_comp,
         speedup_decompression: speedup_decomp,
     }
@@ -235,12 +199,12 @@ fn generate_comparison_report(results: &[ComparisonResult]) {
     writeln!(file, "GLifzip vs ZIP Baseline Comparison").unwrap();
     writeln!(file, "====================================").unwrap();
     writeln!(file, "Generated: {}", chrono::Local::now()).unwrap();
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
 
     for result in results {
         writeln!(file, "Data Type: {}", result.data_type).unwrap();
         writeln!(file, "Data Size: {:.2} MB", result.data_size_mb).unwrap();
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
 
         writeln!(file, "COMPRESSION:").unwrap();
         writeln!(file, "  GLifzip: {:.2} ms ({:.2} GB/s) - Ratio: {:.2}%",
@@ -248,7 +212,7 @@ fn generate_comparison_report(results: &[ComparisonResult]) {
         writeln!(file, "  ZIP:     {:.2} ms ({:.2} MB/s) - Ratio: {:.2}%",
                  result.zip_compress_ms, result.zip_compress_mbps, result.zip_ratio).unwrap();
         writeln!(file, "  Speedup: {:.2}x FASTER", result.speedup_compression).unwrap();
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
 
         writeln!(file, "DECOMPRESSION:").unwrap();
         writeln!(file, "  GLifzip: {:.2} ms ({:.2} GB/s)",
@@ -256,9 +220,9 @@ fn generate_comparison_report(results: &[ComparisonResult]) {
         writeln!(file, "  ZIP:     {:.2} ms ({:.2} MB/s)",
                  result.zip_decompress_ms, result.zip_decompress_mbps).unwrap();
         writeln!(file, "  Speedup: {:.2}x FASTER", result.speedup_decompression).unwrap();
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
         writeln!(file, "---").unwrap();
-        writeln!(file, "").unwrap();
+        writeln!(file).unwrap();
     }
 
     println!("Comparison report generated: {:?}", path);
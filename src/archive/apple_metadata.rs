@@ -8,9 +8,10 @@
 //! - Bundle bits
 
 use std::collections::HashMap;
-use std::io::{Result, Error, ErrorKind};
 use serde::{Deserialize, Serialize};
 
+use crate::error::Result;
+
 /// macOS-specific metadata for archived files
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppleMetadata {
@@ -125,14 +126,12 @@ impl AppleMetadata {
 
     /// Serialize metadata to bytes
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self)
-            .map_err(|e| Error::new(ErrorKind::Other, e))
+        Ok(serde_json::to_vec(self)?)
     }
 
     /// Deserialize metadata from bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        serde_json::from_slice(bytes)
-            .map_err(|e| Error::new(ErrorKind::Other, e))
+        Ok(serde_json::from_slice(bytes)?)
     }
 }
 
@@ -0,0 +1,168 @@
+//! Hash backfill for archives created without per-file hashes
+//!
+//! `create --no-hash` records regular files as unhashed to save time.
+//! Backfilling decompresses the file data once, hashes every unhashed
+//! file, and rewrites the manifest and sidecar (adding the tree hash and
+//! content address) along with the manifest hash in the header. The compressed stream itself is copied unchanged.
+
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+use crate::archive::directory_compressor::{annotate_sidecar, tree_hashes};
+use crate::archive::ArchiveManifest;
+use crate::error::{GlifError, Result};
+use crate::format::{GlifHeader, GlifSidecar, MAX_SIDECAR_SIZE};
+use crate::verification::{calculate_sha256, hex_encode};
+
+/// Add hashes to the unhashed files of a directory archive
+///
+/// Returns the rewritten archive and the number of files hashed; an
+/// archive with nothing to backfill is returned as is.
+pub fn backfill_hashes(archive_data: &[u8], threads: usize) -> Result<(Vec<u8>, usize)> {
+    let mut cursor = Cursor::new(archive_data);
+    let mut manifest = ArchiveManifest::read(&mut cursor).map_err(|e| GlifError::InvalidInput(
+        format!("Not a directory archive: {}", e)
+    ))?;
+    if manifest.unhashed_count() == 0 {
+        return Ok((archive_data.to_vec(), 0));
+    }
+
+    let stream = &archive_data[cursor.position() as usize..];
+
+    // Decompression checks the payload hash, so the new hashes describe the original data
+    let file_data = crate::decompress(stream, threads)?;
+    let mut hashed = 0;
+    for entry in manifest.entries.iter_mut().filter(|e| !e.is_hashed()) {
+        let contents = entry.data(&file_data)?;
+        entry.sha256 = hex_encode(&calculate_sha256(contents));
+        hashed += 1;
+    }
+
+    let mut cursor = Cursor::new(stream);
+    let mut header = GlifHeader::read(&mut cursor)?;
+    let mut sidecar = GlifSidecar::read(&mut cursor, header.sidecar_size)?;
+    let compressed_data = &stream[cursor.position() as usize..];
+
+    annotate_sidecar(&mut sidecar, &manifest, tree_hashes(&manifest)?);
+    let sidecar_json = sidecar.to_json()?;
+    header.sidecar_size = u32::try_from(sidecar_json.len()).ok().filter(|&size| size <= MAX_SIDECAR_SIZE).ok_or_else(|| GlifError::Malformed(
+        format!("Sidecar would grow to {} bytes, over the format limit", sidecar_json.len())
+    ))?;
+
+    let mut result = Vec::with_capacity(archive_data.len() + hashed * 64);
+    manifest.write(&mut result)?;
+    header.set_manifest_hash(&calculate_sha256(&result));
+    header.write(&mut result)?;
+    result.write_all(sidecar_json.as_bytes())?;
+    result.write_all(compressed_data)?;
+
+    Ok((result, hashed))
+}
+
+/// Backfill hashes in an archive file, replacing it only once the new archive is written
+pub fn backfill_file(path: &Path, threads: usize) -> Result<usize> {
+    let (archive, hashed) = backfill_hashes(&fs::read(path)?, threads)?;
+    if hashed == 0 {
+        return Ok(0);
+    }
+
+    let mut partial = path.as_os_str().to_os_string();
+    partial.push(format!(".{}.partial", std::process::id()));
+    let written = fs::write(&partial, archive).and_then(|_| fs::rename(&partial, path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(e.into());
+    }
+    Ok(hashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use crate::archive::file_entry::UNHASHED;
+    use crate::archive::DirectoryCompressor;
+    use crate::testing::TreeBuilder;
+    use crate::DirectoryCompressionConfig;
+    use std::path::PathBuf;
+
+    fn archive(tree: &crate::testing::MemoryFs, hash_files: bool) -> Vec<u8> {
+        crate::testing::archive_with(tree, DirectoryCompressionConfig::default().with_hash_files(hash_files))
+    }
+
+    fn sidecar(archive: &[u8]) -> GlifSidecar {
+        crate::GlifHeader::peek(archive).unwrap().sidecar.unwrap()
+    }
+
+    #[test]
+    fn test_backfill_matches_hashed_archive() {
+        let tree = TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "beta")
+            .symlink("link", "a.txt")
+            .build();
+        let hashed = archive(&tree, true);
+        let unhashed = archive(&tree, false);
+
+        let (manifest, data) = DirectoryCompressor::read_archive(&unhashed, 1).unwrap();
+        assert_eq!(manifest.unhashed_count(), 2);
+        assert!(manifest.entries.iter().any(|e| e.sha256 == UNHASHED));
+        assert_eq!(manifest.find_entry(&PathBuf::from("a.txt")).unwrap().data(&data).unwrap(), b"alpha");
+        assert!(sidecar(&unhashed).payload.tree_hash.is_none());
+        assert!(manifest.content_address().is_err());
+
+        let (backfilled, count) = backfill_hashes(&unhashed, 1).unwrap();
+        assert_eq!(count, 2);
+        let (manifest, data) = DirectoryCompressor::read_archive(&backfilled, 1).unwrap();
+        assert_eq!(manifest.unhashed_count(), 0);
+        for entry in &manifest.entries {
+            entry.data(&data).unwrap();
+        }
+        let info = crate::GlifHeader::peek(&backfilled).unwrap();
+        let offset = info.header_offset().unwrap() as usize;
+        crate::verify_archive(&backfilled[offset..]).unwrap();
+        crate::archive::check_manifest_hash(&backfilled[..offset], info.header.as_ref().unwrap()).unwrap();
+
+        let expected = sidecar(&hashed);
+        let actual = sidecar(&backfilled);
+        assert_eq!(actual.payload.tree_hash, expected.payload.tree_hash);
+        assert_eq!(actual.payload.content_address, expected.payload.content_address);
+
+        // Nothing left to do the second time
+        assert_eq!(backfill_hashes(&backfilled, 1).unwrap(), (backfilled, 0));
+    }
+
+    #[test]
+    fn test_backfill_v1_archive() {
+        let tree = TreeBuilder::new().file("a.txt", "alpha").build();
+        let unhashed = archive(&tree, false);
+
+        // Rewrite the header as v1.1, which has no TLV section for the manifest hash
+        let mut cursor = Cursor::new(unhashed.as_slice());
+        let manifest = ArchiveManifest::read(&mut cursor).unwrap();
+        let mut header = GlifHeader::read(&mut cursor).unwrap();
+        header.format_version = crate::format::header::FRAMED_GLIF_VERSION;
+        header.fields.clear();
+        let mut v1 = Vec::new();
+        header.write(&mut v1).unwrap();
+        std::io::copy(&mut cursor, &mut v1).unwrap();
+        let mut old = Vec::new();
+        manifest.write_with_stream(&mut v1.as_slice(), &mut old).unwrap();
+
+        let (backfilled, count) = backfill_hashes(&old, 1).unwrap();
+        assert_eq!(count, 1);
+        let info = crate::GlifHeader::peek(&backfilled).unwrap();
+        let header = info.header.as_ref().unwrap();
+        assert_eq!(header.format_version, crate::format::header::FRAMED_GLIF_VERSION);
+        assert_eq!(header.manifest_hash().unwrap(), None);
+        let (manifest, data) = DirectoryCompressor::read_archive(&backfilled, 1).unwrap();
+        assert_eq!(manifest.find_entry(&PathBuf::from("a.txt")).unwrap().data(&data).unwrap(), b"alpha");
+    }
+
+    #[test]
+    fn test_backfill_rejects_single_file_archives() {
+        let archive = crate::compress(b"single file", &crate::CompressionConfig::default()).unwrap();
+        assert_eq!(backfill_hashes(&archive, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
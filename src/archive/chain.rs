@@ -0,0 +1,299 @@
+//! Chains of incremental archives
+//!
+//! A chain is a full archive followed by incrementals, each of which may
+//! reference file contents in the archives before it (see
+//! `archive::external`). `verify_chain` checks that the chain is whole:
+//! every reference names an earlier archive in it, and that archive holds
+//! a file with the referenced hash and size. This reads only manifests;
+//! `glifzip verify` checks the archives' contents. `flatten_chain` then
+//! materializes the last archive into one that needs no bases.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::archive::external::{archive_id, BaseArchives};
+use crate::archive::file_entry::FileType;
+use crate::archive::{DirectoryCompressor, FileEntry, GlifArchive};
+use crate::error::{GlifError, Result};
+
+/// One archive of a checked chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainLink {
+    pub path: PathBuf,
+
+    /// The id references to this archive use
+    pub archive_id: String,
+
+    pub entries: usize,
+
+    /// Entries whose contents are in earlier archives
+    pub references: usize,
+}
+
+/// What breaks a chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainProblem {
+    /// The same archive appears twice
+    Duplicate { archive: PathBuf, first: PathBuf },
+
+    /// A reference names an archive that is not in the chain
+    MissingBase { archive: PathBuf, entry: PathBuf, archive_id: String },
+
+    /// A reference names the archive itself or one after it
+    OutOfOrder { archive: PathBuf, entry: PathBuf, base: PathBuf },
+
+    /// The referenced archive has no file with the referenced hash
+    MissingEntry { archive: PathBuf, entry: PathBuf, base: PathBuf, entry_hash: String },
+
+    /// The referenced file's size differs from the referencing entry's
+    SizeMismatch { archive: PathBuf, entry: PathBuf, base: PathBuf, expected: u64, actual: u64 },
+}
+
+impl fmt::Display for ChainProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainProblem::Duplicate { archive, first } => {
+                write!(f, "{} is the same archive as {}", archive.display(), first.display())
+            }
+            ChainProblem::MissingBase { archive, entry, archive_id } => write!(
+                f, "{}: {} references {}, which is not in the chain", archive.display(), entry.display(), archive_id
+            ),
+            ChainProblem::OutOfOrder { archive, entry, base } => write!(
+                f, "{}: {} references {}, which does not come before it", archive.display(), entry.display(), base.display()
+            ),
+            ChainProblem::MissingEntry { archive, entry, base, entry_hash } => write!(
+                f, "{}: {} references hash {}, which {} does not hold", archive.display(), entry.display(), entry_hash, base.display()
+            ),
+            ChainProblem::SizeMismatch { archive, entry, base, expected, actual } => write!(
+                f, "{}: {} is {} bytes but its contents in {} are {} bytes",
+                archive.display(), entry.display(), expected, base.display(), actual
+            ),
+        }
+    }
+}
+
+/// Result of checking a chain, oldest archive first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainReport {
+    pub links: Vec<ChainLink>,
+    pub problems: Vec<ChainProblem>,
+}
+
+impl ChainReport {
+    /// Whether every reference resolves within the chain
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check the chain of directory archives at `paths`, oldest first
+///
+/// Problems with the chain are collected in the report; archives that
+/// cannot be opened are errors.
+pub fn verify_chain(paths: &[PathBuf]) -> Result<ChainReport> {
+    if paths.is_empty() {
+        return Err(GlifError::InvalidInput("A chain needs at least one archive".to_string()));
+    }
+
+    let mut report = ChainReport::default();
+    let mut archives = Vec::with_capacity(paths.len());
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for path in paths {
+        let archive = open(path)?;
+        let id = archive_id(archive.header());
+        match positions.get(&id) {
+            Some(&first) => report.problems.push(ChainProblem::Duplicate {
+                archive: path.clone(),
+                first: paths[first].clone(),
+            }),
+            None => {
+                positions.insert(id.clone(), archives.len());
+            }
+        }
+        archives.push((archive, id));
+    }
+
+    let by_hash: Vec<HashMap<&str, &FileEntry>> = archives.iter()
+        .map(|(archive, _)| archive.entries().iter().rev()
+            .filter(|entry| entry.file_type == FileType::Regular && entry.is_hashed())
+            .map(|entry| (entry.sha256.as_str(), entry))
+            .collect())
+        .collect();
+
+    for (index, (archive, id)) in archives.iter().enumerate() {
+        let path = &paths[index];
+        let mut references = 0;
+        for entry in archive.entries() {
+            let Some(reference) = &entry.external else { continue };
+            references += 1;
+
+            let problem = match positions.get(&reference.archive_id) {
+                None => Some(ChainProblem::MissingBase {
+                    archive: path.clone(),
+                    entry: entry.path.clone(),
+                    archive_id: reference.archive_id.clone(),
+                }),
+                Some(&base) if base >= index => Some(ChainProblem::OutOfOrder {
+                    archive: path.clone(),
+                    entry: entry.path.clone(),
+                    base: paths[base].clone(),
+                }),
+                Some(&base) => match by_hash[base].get(reference.entry_hash.as_str()) {
+                    None => Some(ChainProblem::MissingEntry {
+                        archive: path.clone(),
+                        entry: entry.path.clone(),
+                        base: paths[base].clone(),
+                        entry_hash: reference.entry_hash.clone(),
+                    }),
+                    Some(target) if target.size != entry.size => Some(ChainProblem::SizeMismatch {
+                        archive: path.clone(),
+                        entry: entry.path.clone(),
+                        base: paths[base].clone(),
+                        expected: entry.size,
+                        actual: target.size,
+                    }),
+                    // A target that is itself a reference was checked with its own archive
+                    Some(_) => None,
+                },
+            };
+            report.problems.extend(problem);
+        }
+
+        report.links.push(ChainLink {
+            path: path.clone(),
+            archive_id: id.clone(),
+            entries: archive.entries().len(),
+            references,
+        });
+    }
+
+    Ok(report)
+}
+
+/// The last archive of the chain at `paths` with every reference replaced
+/// by the contents it points to, as a standalone directory archive
+///
+/// Entry metadata, order and hashes are kept; contents are checked
+/// against their hashes as they are read.
+pub fn flatten_chain(paths: &[PathBuf], config: DirectoryCompressionConfig) -> Result<Vec<u8>> {
+    let report = verify_chain(paths)?;
+    if let Some(problem) = report.problems.first() {
+        return Err(GlifError::InvalidInput(format!(
+            "The chain is broken ({} problem(s)): {}", report.problems.len(), problem
+        )));
+    }
+
+    let (tip, earlier) = paths.split_last().expect("verify_chain rejects empty chains");
+    let mut archive = open(tip)?;
+    let mut bases = BaseArchives::open(earlier)?;
+    let mut manifest = archive.manifest().clone();
+    let mut file_data = Vec::new();
+
+    for entry in &mut manifest.entries {
+        if entry.file_type != FileType::Regular {
+            continue;
+        }
+        let data = match entry.external.take() {
+            Some(reference) => bases.read(&reference)?,
+            None => archive.read_entry(&entry.path)?,
+        };
+        if data.len() as u64 != entry.size {
+            return Err(GlifError::SizeMismatch { expected: entry.size, actual: data.len() as u64 });
+        }
+        entry.data_offset = file_data.len() as u64;
+        file_data.extend_from_slice(&data);
+    }
+
+    DirectoryCompressor::new(config)?.build_archive(&manifest, &file_data)
+}
+
+fn open(path: &Path) -> Result<GlifArchive<std::fs::File>> {
+    GlifArchive::open(path).map_err(|e| GlifError::InvalidInput(
+        format!("{} is not a readable directory archive: {}", path.display(), e)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{archive_writer as writer, reference_to};
+
+    /// A full archive and two incrementals, each referencing the one before
+    fn chain(dir: &Path, big: &[u8]) -> Vec<PathBuf> {
+        let full = dir.join("full.glif");
+        let mut base = writer();
+        base.add_dir("docs").unwrap();
+        base.add_bytes("docs/big.bin", big).unwrap();
+        base.finalize_to_file(&full).unwrap();
+
+        let entry = FileEntry::regular(PathBuf::from("docs/big.bin"), 0o100644, 0, 0);
+        let first = dir.join("first.glif");
+        let mut incremental = writer();
+        incremental.add_dir("docs").unwrap();
+        incremental.add_external(entry.clone(), big.len() as u64, reference_to(&full, big)).unwrap();
+        incremental.finalize_to_file(&first).unwrap();
+
+        let second = dir.join("second.glif");
+        let mut incremental = writer();
+        incremental.add_dir("docs").unwrap();
+        incremental.add_external(entry, big.len() as u64, reference_to(&first, big)).unwrap();
+        incremental.add_bytes("docs/new.txt", b"new").unwrap();
+        incremental.finalize_to_file(&second).unwrap();
+
+        vec![full, first, second]
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let temp = tempfile::tempdir().unwrap();
+        let paths = chain(temp.path(), &b"base contents ".repeat(1000));
+
+        let report = verify_chain(&paths).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+        let references: Vec<usize> = report.links.iter().map(|link| link.references).collect();
+        assert_eq!(references, [0, 1, 1]);
+
+        // Out of order, incomplete and repeated chains
+        let swapped = vec![paths[1].clone(), paths[0].clone(), paths[2].clone()];
+        assert!(matches!(verify_chain(&swapped).unwrap().problems[..], [ChainProblem::OutOfOrder { .. }]));
+        let gap = vec![paths[0].clone(), paths[2].clone()];
+        assert!(matches!(verify_chain(&gap).unwrap().problems[..], [ChainProblem::MissingBase { .. }]));
+        let repeated = vec![paths[0].clone(), paths[0].clone()];
+        assert!(matches!(verify_chain(&repeated).unwrap().problems[..], [ChainProblem::Duplicate { .. }]));
+
+        let mut other = writer();
+        other.add_bytes("other.txt", b"other").unwrap();
+        let other_path = temp.path().join("other.glif");
+        other.finalize_to_file(&other_path).unwrap();
+        let mut dangling = writer();
+        let entry = FileEntry::regular(PathBuf::from("big.bin"), 0o100644, 0, 0);
+        dangling.add_external(entry, 4, reference_to(&other_path, b"gone")).unwrap();
+        let dangling_path = temp.path().join("dangling.glif");
+        dangling.finalize_to_file(&dangling_path).unwrap();
+        let problems = verify_chain(&[other_path, dangling_path]).unwrap().problems;
+        assert!(matches!(problems[..], [ChainProblem::MissingEntry { .. }]));
+        assert!(problems[0].to_string().contains("does not hold"));
+    }
+
+    #[test]
+    fn test_flatten_chain() {
+        let temp = tempfile::tempdir().unwrap();
+        let big = b"base contents ".repeat(1000);
+        let paths = chain(temp.path(), &big);
+
+        let flat = temp.path().join("flat.glif");
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        std::fs::write(&flat, flatten_chain(&paths, config.clone()).unwrap()).unwrap();
+
+        let mut archive = GlifArchive::open(&flat).unwrap();
+        assert!(archive.entries().iter().all(|entry| entry.external.is_none()));
+        assert_eq!(archive.entries().len(), 3);
+        assert_eq!(archive.read_entry("docs/big.bin").unwrap(), big);
+        assert_eq!(archive.read_entry("docs/new.txt").unwrap(), b"new");
+        assert_eq!(verify_chain(&[flat]).unwrap().links[0].references, 0);
+
+        assert!(flatten_chain(&paths[1..], config).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Content addresses for directory trees
+//!
+//! A Merkle hash in the style of git tree objects: every directory hashes
+//! its children's names, kinds and hashes, so the root hash changes if and
+//! only if the tree's contents do. Only the executable bit is kept from
+//! file modes; owners and times are ignored. The same tree yields the same
+//! address whether it is hashed on disk or from an archive's manifest.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Component, Path};
+
+use sha2::{Digest, Sha256};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{FileEntry, FileSource};
+use crate::error::{GlifError, Result};
+use crate::encoding::hex_decode_array;
+use crate::verification::{calculate_sha256, hex_encode};
+
+/// Prefix of formatted content addresses
+pub const CONTENT_ADDRESS_PREFIX: &str = "sha256:";
+
+/// Tree node kinds, encoded like git tree entry modes
+const MODE_FILE: &[u8] = b"100644";
+const MODE_EXECUTABLE: &[u8] = b"100755";
+const MODE_SYMLINK: &[u8] = b"120000";
+const MODE_DIRECTORY: &[u8] = b"40000";
+
+enum Node {
+    Leaf { mode: &'static [u8], hash: [u8; 32] },
+    Directory(BTreeMap<OsString, Node>),
+}
+
+impl Node {
+    fn hash(&self) -> ([u8; 32], &'static [u8]) {
+        match self {
+            Node::Leaf { mode, hash } => (*hash, mode),
+            Node::Directory(children) => {
+                let mut hasher = Sha256::new();
+                for (name, child) in children {
+                    let (hash, mode) = child.hash();
+                    hasher.update(mode);
+                    hasher.update(b" ");
+                    hasher.update(name.as_encoded_bytes());
+                    hasher.update([0]);
+                    hasher.update(hash);
+                }
+                (hasher.finalize().into(), MODE_DIRECTORY)
+            }
+        }
+    }
+}
+
+/// Content address of the tree described by `entries`
+///
+/// Regular files must carry their `sha256`. Parent directories missing from
+/// `entries` are implied, as they are on extraction.
+pub fn content_address(entries: &[FileEntry]) -> Result<[u8; 32]> {
+    let mut root = BTreeMap::new();
+
+    for entry in entries {
+        let node = match entry.file_type {
+            FileType::Directory => Node::Directory(BTreeMap::new()),
+            FileType::Symlink => {
+                let target = entry.symlink_target.as_deref().unwrap_or(Path::new(""));
+                Node::Leaf { mode: MODE_SYMLINK, hash: calculate_sha256(target.as_os_str().as_encoded_bytes()) }
+            }
+            FileType::Regular if !entry.is_hashed() => return Err(GlifError::InvalidInput(
+                format!("{} was archived without a hash; run `glifzip hash-backfill` first", entry.path.display())
+            )),
+            FileType::Regular => {
+                let mode = if entry.mode & 0o111 != 0 { MODE_EXECUTABLE } else { MODE_FILE };
+                let hash = hex_decode_array(&entry.sha256).map_err(|e| GlifError::Malformed(
+                    format!("{}: invalid file hash: {}", entry.path.display(), e)
+                ))?;
+                Node::Leaf { mode, hash }
+            }
+        };
+        insert(&mut root, &entry.path, node)?;
+    }
+
+    Ok(Node::Directory(root).hash().0)
+}
+
+/// Content address of every entry a source lists, without building an archive
+pub fn content_address_of<S: FileSource>(source: &S) -> Result<[u8; 32]> {
+    let entries = source.paths()?
+        .iter()
+        .map(|path| {
+            let mut entry = source.entry(path)?;
+            if entry.file_type == FileType::Regular && entry.sha256.is_empty() {
+                entry.sha256 = hex_encode(&calculate_sha256(&source.read_file(path)?));
+            }
+            Ok(entry)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    content_address(&entries)
+}
+
+/// Format a content address for display and the sidecar
+pub fn format_content_address(hash: &[u8; 32]) -> String {
+    format!("{}{}", CONTENT_ADDRESS_PREFIX, hex_encode(hash))
+}
+
+fn insert(root: &mut BTreeMap<OsString, Node>, path: &Path, node: Node) -> Result<()> {
+    let invalid = || GlifError::Malformed(
+        format!("{}: entry path must be relative and normalized", path.display())
+    );
+
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => names.push(name.to_os_string()),
+            Component::CurDir => {}
+            _ => return Err(invalid()),
+        }
+    }
+    let name = names.pop().ok_or_else(invalid)?;
+
+    let mut directory = root;
+    for parent in names {
+        let child = directory.entry(parent).or_insert_with(|| Node::Directory(BTreeMap::new()));
+        directory = match child {
+            Node::Directory(children) => children,
+            Node::Leaf { .. } => return Err(invalid()),
+        };
+    }
+
+    match (directory.get_mut(&name), node) {
+        // A directory listed after its children keeps them
+        (Some(Node::Directory(_)), Node::Directory(_)) => {}
+        (_, node) => {
+            directory.insert(name, node);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TreeBuilder;
+
+    #[test]
+    fn test_address_depends_only_on_contents() {
+        let tree = TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "beta")
+            .symlink("link", "a.txt")
+            .build();
+        let same = TreeBuilder::new()
+            .file("src/b.txt", "beta")
+            .mode("src/b.txt", 0o600)
+            .symlink("link", "a.txt")
+            .file("a.txt", "alpha")
+            .build();
+
+        assert_eq!(content_address_of(&tree).unwrap(), content_address_of(&same).unwrap());
+    }
+
+    #[test]
+    fn test_address_changes_with_contents() {
+        let base = || TreeBuilder::new().file("a.txt", "alpha").file("src/b.txt", "beta");
+        let address = content_address_of(&base().build()).unwrap();
+
+        for changed in [
+            base().file("src/b.txt", "BETA").build(),
+            base().mode("a.txt", 0o755).build(),
+            base().dir("empty").build(),
+            base().symlink("link", "a.txt").build(),
+        ] {
+            assert_ne!(content_address_of(&changed).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn test_implied_parents_match_listed_parents() {
+        let mut file = FileEntry::directory("dir/file".into(), 0o644, 0, 0);
+        file.file_type = FileType::Regular;
+        file.sha256 = hex_encode(&calculate_sha256(b"x"));
+        let parent = FileEntry::directory("dir".into(), 0o755, 0, 0);
+
+        let implied = content_address(std::slice::from_ref(&file)).unwrap();
+        assert_eq!(content_address(&[parent.clone(), file.clone()]).unwrap(), implied);
+        assert_eq!(content_address(&[file, parent]).unwrap(), implied);
+    }
+
+    #[test]
+    fn test_rejects_unnormalized_paths() {
+        let entry = FileEntry::directory("../escape".into(), 0o755, 0, 0);
+        assert!(content_address(&[entry]).is_err());
+    }
+}
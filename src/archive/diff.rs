@@ -0,0 +1,487 @@
+//! Differences between two directory archives
+//!
+//! `glifzip compare` matches entries of two manifests by path and reports
+//! added, removed and modified entries with their size deltas, as text or
+//! as a standalone HTML page for sharing backup change summaries.
+//! Contents are compared by SHA256; where either side was archived without
+//! hashes, a changed modification time stands in for changed contents.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::archive::file_entry::FileType;
+use crate::progress::color::{column_width, pad, paint_if, Style};
+use crate::{ArchiveManifest, FileEntry};
+
+/// How an entry differs between the two archives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+        }
+    }
+}
+
+/// One entry that differs between the two archives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+
+    /// Content size in each archive (0 for directories); `None` where the entry is absent
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+
+    /// What differs in a modified entry: `type`, `content`, `size`, `mode`, `owner`, `target` or `mtime`
+    pub reasons: Vec<&'static str>,
+}
+
+impl EntryChange {
+    /// Change in content size, new minus old
+    pub fn size_delta(&self) -> i64 {
+        self.new_size.unwrap_or(0) as i64 - self.old_size.unwrap_or(0) as i64
+    }
+}
+
+/// Differences between an old and a new manifest, sorted by path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    pub changes: Vec<EntryChange>,
+
+    /// Entries present and identical in both archives
+    pub unchanged: usize,
+}
+
+impl ManifestDiff {
+    /// Number of changes of `kind`
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|change| change.kind == kind).count()
+    }
+
+    /// Change in total content size, new minus old
+    pub fn size_delta(&self) -> i64 {
+        self.changes.iter().map(EntryChange::size_delta).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Compare the entries of two manifests by path
+pub fn diff_manifests(old: &ArchiveManifest, new: &ArchiveManifest) -> ManifestDiff {
+    let old_entries: HashMap<&Path, &FileEntry> = old.entries.iter().map(|e| (e.path.as_path(), e)).collect();
+    let new_entries: HashMap<&Path, &FileEntry> = new.entries.iter().map(|e| (e.path.as_path(), e)).collect();
+    let mut diff = ManifestDiff::default();
+
+    for entry in &new.entries {
+        match old_entries.get(entry.path.as_path()) {
+            None => diff.changes.push(EntryChange {
+                path: entry.path.clone(),
+                kind: ChangeKind::Added,
+                old_size: None,
+                new_size: Some(content_size(entry)),
+                reasons: Vec::new(),
+            }),
+            Some(old_entry) => {
+                let reasons = differences(old_entry, entry);
+                if reasons.is_empty() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changes.push(EntryChange {
+                        path: entry.path.clone(),
+                        kind: ChangeKind::Modified,
+                        old_size: Some(content_size(old_entry)),
+                        new_size: Some(content_size(entry)),
+                        reasons,
+                    });
+                }
+            }
+        }
+    }
+
+    for entry in old.entries.iter().filter(|e| !new_entries.contains_key(e.path.as_path())) {
+        diff.changes.push(EntryChange {
+            path: entry.path.clone(),
+            kind: ChangeKind::Removed,
+            old_size: Some(content_size(entry)),
+            new_size: None,
+            reasons: Vec::new(),
+        });
+    }
+
+    diff.changes.sort_by(|a, b| a.path.cmp(&b.path));
+    diff
+}
+
+/// Size of an entry's contents; directory sizes are filesystem metadata, not contents
+fn content_size(entry: &FileEntry) -> u64 {
+    match entry.file_type {
+        FileType::Directory => 0,
+        _ => entry.size,
+    }
+}
+
+fn differences(old: &FileEntry, new: &FileEntry) -> Vec<&'static str> {
+    if old.file_type != new.file_type {
+        return vec!["type"];
+    }
+
+    let mut reasons = Vec::new();
+    if old.file_type == FileType::Regular {
+        if old.is_hashed() && new.is_hashed() {
+            if old.sha256 != new.sha256 {
+                reasons.push("content");
+            }
+        } else if old.mtime != new.mtime {
+            reasons.push("mtime");
+        }
+        if old.size != new.size {
+            reasons.push("size");
+        }
+    }
+    if old.mode != new.mode {
+        reasons.push("mode");
+    }
+    if (old.uid, old.gid) != (new.uid, new.gid) {
+        reasons.push("owner");
+    }
+    if old.symlink_target != new.symlink_target {
+        reasons.push("target");
+    }
+    reasons
+}
+
+/// Output format of `glifzip compare`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Text,
+    Html,
+}
+
+impl DiffFormat {
+    pub const ALL: [DiffFormat; 2] = [DiffFormat::Text, DiffFormat::Html];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffFormat::Text => "text",
+            DiffFormat::Html => "html",
+        }
+    }
+}
+
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        DiffFormat::ALL.into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown compare format '{}' (expected text or html)", s))
+    }
+}
+
+/// One line per change (`+` added, `-` removed, `~` modified) and a summary line
+pub fn render_text(diff: &ManifestDiff) -> String {
+    let mut text = String::new();
+    for change in &diff.changes {
+        let marker = match change.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Modified => '~',
+        };
+        let _ = write!(text, "{} {} ({})", marker, change.path.display(), format_delta(change.size_delta()));
+        if !change.reasons.is_empty() {
+            let _ = write!(text, " [{}]", change.reasons.join(", "));
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "{}", summary(diff));
+    text
+}
+
+/// Text report for the terminal: markers and paths colored by change kind
+/// (if `colors`), size deltas aligned in a column, reasons dimmed
+pub fn render_console(diff: &ManifestDiff, colors: bool) -> String {
+    let deltas: Vec<String> = diff.changes.iter().map(|change| format_delta(change.size_delta())).collect();
+    let width = column_width(&deltas);
+    let mut text = String::new();
+    for (change, delta) in diff.changes.iter().zip(&deltas) {
+        let (marker, style) = match change.kind {
+            ChangeKind::Added => ('+', Style::Ok),
+            ChangeKind::Removed => ('-', Style::Error),
+            ChangeKind::Modified => ('~', Style::Warning),
+        };
+        let _ = write!(
+            text,
+            "{} {}  {}",
+            paint_if(colors, marker, style),
+            paint_if(colors, pad(delta, width, true), Style::Dim),
+            paint_if(colors, change.path.display(), style)
+        );
+        if !change.reasons.is_empty() {
+            let _ = write!(text, "  {}", paint_if(colors, change.reasons.join(", "), Style::Dim));
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "{}", paint_if(colors, summary(diff), Style::Bold));
+    text
+}
+
+fn summary(diff: &ManifestDiff) -> String {
+    format!(
+        "{} added, {} removed, {} modified, {} unchanged; size {}",
+        diff.count(ChangeKind::Added),
+        diff.count(ChangeKind::Removed),
+        diff.count(ChangeKind::Modified),
+        diff.unchanged,
+        format_delta(diff.size_delta())
+    )
+}
+
+const HTML_STYLE: &str = "\
+body{font-family:-apple-system,'Segoe UI',sans-serif;margin:2em;color:#222}
+h1{font-size:1.4em}.labels{color:#666}
+.cards{display:flex;gap:1em;margin:1em 0}
+.card{border:1px solid #ddd;border-radius:6px;padding:.6em 1em;min-width:7em}
+.card b{display:block;font-size:1.5em}
+ul.tree,ul.tree ul{list-style:none;padding-left:1.2em}
+summary{cursor:pointer}
+.added{color:#1a7f37}.removed{color:#cf222e}.modified{color:#9a6700}
+.delta{color:#666;margin-left:.5em}
+table{border-collapse:collapse;margin-top:1em}
+th,td{border-bottom:1px solid #eee;padding:.3em .8em;text-align:left}
+td.num{text-align:right;font-variant-numeric:tabular-nums}
+";
+
+/// A standalone HTML page: summary counts, a tree of changed paths and a table of every change
+pub fn render_html(diff: &ManifestDiff, old_label: &str, new_label: &str) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>glifzip compare</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+        HTML_STYLE
+    );
+    let _ = writeln!(html, "<h1>Archive changes</h1>\n<p class=\"labels\">{} &rarr; {}</p>", escape(old_label), escape(new_label));
+
+    html.push_str("<div class=\"cards\">\n");
+    for (label, value, class) in [
+        ("Added", diff.count(ChangeKind::Added).to_string(), "added"),
+        ("Removed", diff.count(ChangeKind::Removed).to_string(), "removed"),
+        ("Modified", diff.count(ChangeKind::Modified).to_string(), "modified"),
+        ("Unchanged", diff.unchanged.to_string(), ""),
+        ("Size change", format_delta(diff.size_delta()), ""),
+    ] {
+        let _ = writeln!(html, "<div class=\"card\"><b class=\"{}\">{}</b>{}</div>", class, escape(&value), label);
+    }
+    html.push_str("</div>\n");
+
+    if diff.is_empty() {
+        html.push_str("<p>The archives hold the same entries.</p>\n</body>\n</html>\n");
+        return html;
+    }
+
+    html.push_str("<h2>Tree</h2>\n<ul class=\"tree\">\n");
+    let mut tree = TreeNode::default();
+    for change in &diff.changes {
+        tree.insert(change);
+    }
+    tree.render(&mut html);
+    html.push_str("</ul>\n");
+
+    html.push_str("<h2>Changes</h2>\n<table>\n<tr><th>Path</th><th>Change</th><th>Old size</th><th>New size</th><th>Delta</th><th>Details</th></tr>\n");
+    for change in &diff.changes {
+        let size = |size: Option<u64>| size.map_or(String::new(), format_size);
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"{kind}\">{kind}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            escape(&change.path.to_string_lossy()),
+            size(change.old_size),
+            size(change.new_size),
+            format_delta(change.size_delta()),
+            change.reasons.join(", "),
+            kind = change.kind.name(),
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Changed paths grouped by directory for the HTML tree view
+#[derive(Default)]
+struct TreeNode<'a> {
+    change: Option<&'a EntryChange>,
+    children: BTreeMap<String, TreeNode<'a>>,
+}
+
+impl<'a> TreeNode<'a> {
+    fn insert(&mut self, change: &'a EntryChange) {
+        let node = change.path.components().fold(self, |node, component| {
+            node.children.entry(component.as_os_str().to_string_lossy().into_owned()).or_default()
+        });
+        node.change = Some(change);
+    }
+
+    /// Total size delta of this node's change and its descendants
+    fn size_delta(&self) -> i64 {
+        self.change.map_or(0, EntryChange::size_delta) + self.children.values().map(TreeNode::size_delta).sum::<i64>()
+    }
+
+    fn render(&self, html: &mut String) {
+        for (name, child) in &self.children {
+            let class = child.change.map_or("", |change| change.kind.name());
+            let suffix = if child.children.is_empty() { "" } else { "/" };
+            let label = format!(
+                "<span class=\"{}\">{}{}</span><span class=\"delta\">{}</span>",
+                class,
+                escape(name),
+                suffix,
+                format_delta(child.size_delta())
+            );
+            if child.children.is_empty() {
+                let _ = writeln!(html, "<li>{}</li>", label);
+            } else {
+                let _ = writeln!(html, "<li><details open><summary>{}</summary>\n<ul>", label);
+                child.render(html);
+                html.push_str("</ul></details></li>\n");
+            }
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(delta.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(path: &str, file_type: FileType, size: u64, sha256: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            file_type,
+            size,
+            mode: 0o644,
+            uid: 501,
+            gid: 20,
+            mtime: Utc::now(),
+            atime: Utc::now(),
+            symlink_target: None,
+            data_offset: 0,
+            sha256: sha256.to_string(),
+            xattrs: Vec::new(),
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
+        }
+    }
+
+    fn manifests() -> (ArchiveManifest, ArchiveManifest) {
+        let mut old = ArchiveManifest::new(PathBuf::from("/data"));
+        let mut new = ArchiveManifest::new(PathBuf::from("/data"));
+        for manifest in [&mut old, &mut new] {
+            manifest.add_entry(entry("docs", FileType::Directory, 4096, ""));
+            manifest.add_entry(entry("docs/same.txt", FileType::Regular, 10, "aa"));
+        }
+        old.add_entry(entry("docs/edited.txt", FileType::Regular, 100, "bb"));
+        new.add_entry(entry("docs/edited.txt", FileType::Regular, 2148, "cc"));
+        old.add_entry(entry("gone.bin", FileType::Regular, 50, "dd"));
+        new.add_entry(FileEntry { mode: 0o600, ..entry("docs/new <1>.txt", FileType::Regular, 7, "ee") });
+        (old, new)
+    }
+
+    #[test]
+    fn test_diff_manifests() {
+        let (old, new) = manifests();
+        let diff = diff_manifests(&old, &new);
+
+        assert_eq!(diff.unchanged, 2);
+        let paths: Vec<_> = diff.changes.iter().map(|c| (c.path.to_str().unwrap(), c.kind)).collect();
+        assert_eq!(paths, [
+            ("docs/edited.txt", ChangeKind::Modified),
+            ("docs/new <1>.txt", ChangeKind::Added),
+            ("gone.bin", ChangeKind::Removed),
+        ]);
+        assert_eq!(diff.changes[0].reasons, ["content", "size"]);
+        assert_eq!(diff.size_delta(), 2048 + 7 - 50);
+
+        assert!(diff_manifests(&old, &old).is_empty());
+        let text = render_text(&diff);
+        assert!(text.contains("~ docs/edited.txt (+2.0 KiB) [content, size]"));
+        assert!(text.ends_with("1 added, 1 removed, 1 modified, 2 unchanged; size +2.0 KiB\n"));
+
+        let console = render_console(&diff, false);
+        assert_eq!(console.lines().take(3).collect::<Vec<_>>(), [
+            "~ +2.0 KiB  docs/edited.txt  content, size",
+            "+     +7 B  docs/new <1>.txt",
+            "-    -50 B  gone.bin",
+        ]);
+        assert!(render_console(&diff, true).contains("\x1b[31m-\x1b[0m"));
+    }
+
+    #[test]
+    fn test_unhashed_files_compare_mtime() {
+        let old_entry = entry("a", FileType::Regular, 1, crate::archive::file_entry::UNHASHED);
+        let touched = FileEntry { mtime: old_entry.mtime + chrono::Duration::seconds(1), ..old_entry.clone() };
+        assert_eq!(differences(&old_entry, &old_entry), Vec::<&str>::new());
+        assert_eq!(differences(&old_entry, &touched), ["mtime"]);
+
+        let link = FileEntry { symlink_target: Some(PathBuf::from("b")), ..entry("a", FileType::Symlink, 1, "") };
+        assert_eq!(differences(&old_entry, &link), ["type"]);
+    }
+
+    #[test]
+    fn test_render_html() {
+        let (old, new) = manifests();
+        let html = render_html(&diff_manifests(&old, &new), "mon.glif", "tue <new>.glif");
+
+        assert!(html.starts_with("<!DOCTYPE html>") && html.ends_with("</html>\n"));
+        assert!(html.contains("tue &lt;new&gt;.glif"));
+        assert!(html.contains("<span class=\"added\">new &lt;1&gt;.txt</span>"));
+        // The docs directory isn't changed itself but carries its children's delta
+        assert!(html.contains("<summary><span class=\"\">docs/</span><span class=\"delta\">+2.0 KiB</span></summary>"));
+        assert_eq!(html.matches("<tr>").count(), 4);
+
+        assert!(render_html(&ManifestDiff::default(), "a", "b").contains("same entries"));
+        assert_eq!("html".parse(), Ok(DiffFormat::Html));
+    }
+}
@@ -1,16 +1,35 @@
 use std::fs;
-use std::io::{Result, Error, ErrorKind, Write};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
 use glob::Pattern;
-use indicatif::{ProgressBar, ProgressStyle};
-
-use crate::archive::{ArchiveManifest, FileEntry};
-use crate::archive::file_entry::FileType;
-use crate::CompressionConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::mime;
+use crate::archive::sanitize::NamePolicy;
+use crate::archive::{check_manifest_hash, ArchiveManifest, FileEntry, ManifestFormat};
+use crate::archive::report::{EntryOutcome, EntryStatus, ExtractionReport};
+use crate::archive::file_entry::{FileType, UNHASHED};
+use crate::archive::content_address::{content_address_of, format_content_address};
+use crate::archive::filesystem::{is_unsupported, DiskSink, DiskSource, FileSource, FileSystemSink, NullSink, UnsupportedPolicy};
+use crate::archive::external::BaseArchives;
+use crate::archive::journal::{Journal, JournalSource};
+use crate::archive::salvage::gap_bytes;
+use crate::archive::timestamps::{self, TimestampPolicy};
+use crate::cache::chunks::{compress_with_cache, ChunkCache};
+use crate::compression::{estimate_compressed_size, ESTIMATE_SAMPLE_SIZE};
+use crate::error::{GlifError, Result};
+use crate::format::GlifHeader;
+use crate::{CompressionConfig, DecompressionConfig, GlifEncoder, GlifSidecar, VerifyPolicy};
+use crate::verification::hex_encode;
 
 /// Configuration for directory compression
-#[derive(Debug, Clone)]
+///
+/// Serializes as a JSON object with the compression settings under
+/// `compression`; fields missing when deserializing take their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DirectoryCompressionConfig {
     /// Base compression configuration
     pub compression: CompressionConfig,
@@ -29,8 +48,50 @@ pub struct DirectoryCompressionConfig {
 
     /// Show progress bars
     pub show_progress: bool,
+
+    /// Skip cache directories (CACHEDIR.TAG and well-known cache names)
+    pub exclude_caches: bool,
+
+    /// Skip items excluded from Time Machine backups (macOS only)
+    pub respect_tm_excludes: bool,
+
+    /// Keep going past read errors: zero-fill unreadable ranges and skip
+    /// unreadable paths, recording both in the manifest
+    pub salvage: bool,
+
+    /// Hash each file for the manifest; when off, files are recorded as
+    /// unhashed and `backfill_hashes` can add the hashes later
+    pub hash_files: bool,
+
+    /// Reuse compressed chunks of unchanged large files from earlier runs
+    pub chunk_cache: Option<ChunkCache>,
+
+    /// Journal of an earlier run: files unchanged since then are copied
+    /// from its archive instead of being read and hashed
+    pub since: Option<PathBuf>,
+
+    /// Write a journal of the new archive here, for the next run's `since`
+    pub journal: Option<PathBuf>,
+
+    /// What to do with timestamps before 1970 or in the future
+    pub timestamp_policy: TimestampPolicy,
+
+    /// Record timestamps in whole seconds
+    pub whole_second_timestamps: bool,
+
+    /// Encoding of the manifest
+    pub manifest_format: ManifestFormat,
+
+    /// Compress the manifest with zstd
+    pub compress_manifest: bool,
 }
 
+/// Signature that must begin a CACHEDIR.TAG file (https://bford.info/cachedir/)
+pub const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Directory names skipped when `exclude_caches` is enabled
+pub const CACHE_DIRECTORY_NAMES: &[&str] = &[".cache", "target", "node_modules"];
+
 impl Default for DirectoryCompressionConfig {
     fn default() -> Self {
         Self {
@@ -40,6 +101,17 @@ impl Default for DirectoryCompressionConfig {
             preserve_metadata: true,
             verbose: false,
             show_progress: true,
+            exclude_caches: false,
+            respect_tm_excludes: false,
+            salvage: false,
+            hash_files: true,
+            chunk_cache: None,
+            since: None,
+            journal: None,
+            timestamp_policy: TimestampPolicy::Keep,
+            whole_second_timestamps: false,
+            manifest_format: ManifestFormat::Json,
+            compress_manifest: true,
         }
     }
 }
@@ -52,6 +124,13 @@ impl DirectoryCompressionConfig {
         }
     }
 
+    /// Read a configuration from the JSON file at `path`, validating its compression settings
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let config: Self = crate::config::read_json_file(path.as_ref())?;
+        config.compression.validate()?;
+        Ok(config)
+    }
+
     pub fn with_exclude_patterns(mut self, patterns: Vec<String>) -> Self {
         self.exclude_patterns = patterns;
         self
@@ -71,6 +150,284 @@ impl DirectoryCompressionConfig {
         self.follow_symlinks = follow;
         self
     }
+
+    pub fn with_exclude_caches(mut self, exclude_caches: bool) -> Self {
+        self.exclude_caches = exclude_caches;
+        self
+    }
+
+    pub fn with_respect_tm_excludes(mut self, respect: bool) -> Self {
+        self.respect_tm_excludes = respect;
+        self
+    }
+
+    pub fn with_salvage(mut self, salvage: bool) -> Self {
+        self.salvage = salvage;
+        self
+    }
+
+    pub fn with_hash_files(mut self, hash_files: bool) -> Self {
+        self.hash_files = hash_files;
+        self
+    }
+
+    /// Copy chunks of unchanged files from `cache` instead of recompressing
+    /// them, storing chunks of new files for the next run
+    pub fn with_chunk_cache(mut self, cache: ChunkCache) -> Self {
+        self.chunk_cache = Some(cache);
+        self
+    }
+
+    /// Take files unchanged since the journal at `path` was written from
+    /// its archive; a missing journal means a full run
+    pub fn with_since(mut self, path: PathBuf) -> Self {
+        self.since = Some(path);
+        self
+    }
+
+    /// Write a journal of the archive to `path` once it is created
+    pub fn with_journal(mut self, path: PathBuf) -> Self {
+        self.journal = Some(path);
+        self
+    }
+
+    /// Keep, clamp or refuse timestamps before 1970 or in the future
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = policy;
+        self
+    }
+
+    /// Drop the sub-second part of timestamps
+    pub fn with_whole_second_timestamps(mut self, whole_seconds: bool) -> Self {
+        self.whole_second_timestamps = whole_seconds;
+        self
+    }
+
+    /// Write the manifest as JSON or compact CBOR
+    pub fn with_manifest_format(mut self, format: ManifestFormat) -> Self {
+        self.manifest_format = format;
+        self
+    }
+
+    /// Compress the manifest with zstd, or store it as is for readers before 1.2.0
+    pub fn with_compressed_manifest(mut self, compress: bool) -> Self {
+        self.compress_manifest = compress;
+        self
+    }
+}
+
+/// Options for extracting a directory archive
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// Worker threads (0: auto-detect)
+    pub threads: usize,
+
+    /// Show verbose output
+    pub verbose: bool,
+
+    /// Show progress bars
+    pub show_progress: bool,
+
+    /// Which SHA256 hashes to check (all by default); the per-file hashes
+    /// go with the payload hash, and the header and sidecar are validated
+    /// either way
+    pub verify: VerifyPolicy,
+
+    /// Chunk buffers held at once while decompressing (0: two per thread)
+    pub max_in_flight: usize,
+
+    /// Memory ceiling in bytes above which decompression uses one thread (0: none)
+    pub max_memory: u64,
+
+    /// Archives holding the contents of entries that reference other archives
+    pub bases: Vec<PathBuf>,
+
+    /// MIME types (or `type/*` patterns) of the regular files to extract;
+    /// empty extracts everything
+    pub include_types: Vec<String>,
+
+    /// What to do when the target can't create symlinks or set permissions
+    pub unsupported: UnsupportedPolicy,
+
+    /// What to do with entry names Windows can't store
+    pub names: NamePolicy,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            verbose: false,
+            show_progress: true,
+            verify: VerifyPolicy::Full,
+            max_in_flight: 0,
+            max_memory: 0,
+            bases: Vec::new(),
+            include_types: Vec::new(),
+            unsupported: UnsupportedPolicy::Fail,
+            names: NamePolicy::Keep,
+        }
+    }
+}
+
+impl ExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+
+    /// Skip hash checks for trusted local archives where speed matters
+    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
+        self.verify = if verify_hashes { VerifyPolicy::Full } else { VerifyPolicy::Trusted };
+        self
+    }
+
+    /// Choose which hashes are checked
+    pub fn with_verify(mut self, verify: VerifyPolicy) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Bound the chunk buffers held at once, for memory-constrained devices
+    pub fn with_max_in_flight(mut self, budget: usize) -> Self {
+        self.max_in_flight = budget;
+        self
+    }
+
+    /// Fall back to one thread when decompressing would need more than `bytes`
+    pub fn with_max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// Resolve references to other archives against the directory archives at `bases`,
+    /// including any older archives those reference in turn
+    pub fn with_bases(mut self, bases: Vec<PathBuf>) -> Self {
+        self.bases = bases;
+        self
+    }
+
+    /// Extract only regular files whose type matches one of `patterns`
+    /// (see `mime::matches`); directories are still created and symlinks
+    /// are skipped
+    pub fn with_include_types(mut self, patterns: Vec<String>) -> Self {
+        self.include_types = patterns;
+        self
+    }
+
+    /// Degrade instead of failing on targets without symlinks or
+    /// permissions, such as FAT, exFAT and SMB shares
+    pub fn with_unsupported(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported = policy;
+        self
+    }
+
+    /// Rewrite or refuse entry names Windows can't store, for extracting
+    /// Unix archives onto Windows, FAT, exFAT or SMB targets
+    pub fn with_names(mut self, policy: NamePolicy) -> Self {
+        self.names = policy;
+        self
+    }
+
+    /// Whether the type filter lets `entry` through; files archived
+    /// without a type are typed from `decompressed_data`
+    fn includes_type(&self, entry: &FileEntry, decompressed_data: &[u8]) -> bool {
+        if self.include_types.is_empty() || entry.file_type == FileType::Directory {
+            return true;
+        }
+        if entry.file_type != FileType::Regular {
+            return false;
+        }
+
+        let mime_type = match &entry.mime_type {
+            Some(mime_type) => Some(mime_type.as_str()),
+            None => entry.data_unverified(decompressed_data).ok().and_then(mime::detect),
+        };
+        mime_type.is_some_and(|mime_type| self.include_types.iter().any(|pattern| mime::matches(mime_type, pattern)))
+    }
+
+    /// Decompression settings these options imply
+    pub fn decompression_config(&self) -> DecompressionConfig {
+        DecompressionConfig::new()
+            .with_threads(self.threads)
+            .with_verify(self.verify)
+            .with_max_in_flight(self.max_in_flight)
+            .with_max_memory(self.max_memory)
+    }
+}
+
+/// Check whether a directory is a cache directory
+/// Matches directories tagged with a valid CACHEDIR.TAG, the built-in cache
+/// names, and `Library/Caches` on macOS
+pub fn is_cache_directory(path: &Path) -> bool {
+    if !path.is_dir() {
+        return false;
+    }
+
+    let is_builtin = path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| CACHE_DIRECTORY_NAMES.contains(&name))
+        .unwrap_or(false);
+
+    if is_builtin || path.ends_with("Library/Caches") {
+        return true;
+    }
+
+    has_cachedir_tag(path)
+}
+
+fn has_cachedir_tag(directory: &Path) -> bool {
+    use std::io::Read;
+
+    let mut signature = [0u8; CACHEDIR_TAG_SIGNATURE.len()];
+    fs::File::open(directory.join("CACHEDIR.TAG"))
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .map(|_| signature == CACHEDIR_TAG_SIGNATURE)
+        .unwrap_or(false)
+}
+
+/// Tree hash and content address of a manifest, formatted for the sidecar
+///
+/// None while any file is unhashed, since neither can be computed yet.
+pub(crate) fn tree_hashes(manifest: &ArchiveManifest) -> Result<Option<(String, String)>> {
+    if manifest.unhashed_count() > 0 {
+        return Ok(None);
+    }
+    let tree_hash = format!("sha256:{}", hex_encode(&manifest.tree_hash()));
+    Ok(Some((tree_hash, format_content_address(&manifest.content_address()?))))
+}
+
+/// Record a manifest's counts and hashes in a directory archive's sidecar
+pub(crate) fn annotate_sidecar(sidecar: &mut GlifSidecar, manifest: &ArchiveManifest, tree_hashes: Option<(String, String)>) {
+    sidecar.payload.files = Some(manifest.count(FileType::Regular) as u64);
+    sidecar.payload.directories = Some(manifest.count(FileType::Directory) as u64);
+    let (tree_hash, content_address) = tree_hashes.unzip();
+    sidecar.payload.tree_hash = tree_hash;
+    sidecar.payload.content_address = content_address;
+}
+
+/// Record each regular file's compressed size in `manifest`, from the chunk
+/// table in the header at the start of its compressed data; archives
+/// without one leave the sizes unrecorded
+fn record_compressed_sizes<R: Read>(manifest: &mut ArchiveManifest, compressed: &mut R) -> Result<()> {
+    if let Some(table) = GlifHeader::read(compressed)?.chunk_table()? {
+        manifest.record_compressed_sizes(&table);
+    }
+    Ok(())
 }
 
 /// DirectoryCompressor handles recursive directory compression
@@ -86,7 +443,7 @@ impl DirectoryCompressor {
         let mut compiled_patterns = Vec::new();
         for pattern_str in &config.exclude_patterns {
             let pattern = Pattern::new(pattern_str)
-                .map_err(|e| Error::new(ErrorKind::InvalidInput,
+                .map_err(|e| GlifError::InvalidInput(
                     format!("Invalid exclude pattern '{}': {}", pattern_str, e)))?;
             compiled_patterns.push(pattern);
         }
@@ -110,22 +467,48 @@ impl DirectoryCompressor {
 
     /// Collect all files in a directory
     pub fn collect_files<P: AsRef<Path>>(&self, directory: P) -> Result<Vec<PathBuf>> {
-        let directory = directory.as_ref();
+        self.walk(directory.as_ref()).map(|(files, _)| files)
+    }
+
+    /// Collect all files in a directory, plus the paths that could not be
+    /// read in salvage mode (relative to the directory)
+    fn walk(&self, directory: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
         if !directory.is_dir() {
             return Err(Error::new(
                 ErrorKind::NotFound,
                 format!("{} is not a directory", directory.display())
-            ));
+            ).into());
         }
 
         let mut files = Vec::new();
+        let mut skipped = Vec::new();
         let walker = WalkDir::new(directory)
             .follow_links(self.config.follow_symlinks)
             .into_iter()
-            .filter_entry(|e| !self.should_exclude(e.path()));
+            .filter_entry(|e| {
+                if self.should_exclude(e.path()) {
+                    return false;
+                }
+                if self.config.respect_tm_excludes && crate::platform::is_backup_excluded(e.path()) {
+                    return false;
+                }
+                // Never treat the directory being archived as a cache
+                !(self.config.exclude_caches && e.depth() > 0 && is_cache_directory(e.path()))
+            });
 
         for entry in walker {
-            let entry = entry.map_err(|e| Error::new(ErrorKind::Other, e))?;
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if self.config.salvage => {
+                    if self.config.verbose {
+                        println!("  Skipped: {}", e);
+                    }
+                    let path = e.path().unwrap_or(directory);
+                    skipped.push(path.strip_prefix(directory).unwrap_or(path).to_path_buf());
+                    continue;
+                }
+                Err(e) => return Err(Error::other(e).into()),
+            };
             let path = entry.path();
 
             // Skip the base directory itself
@@ -143,81 +526,198 @@ impl DirectoryCompressor {
         // Sort for deterministic ordering
         files.sort();
 
-        Ok(files)
+        Ok((files, skipped))
     }
 
     /// Create a manifest from a directory
     pub fn create_manifest<P: AsRef<Path>>(&self, directory: P) -> Result<(ArchiveManifest, Vec<u8>)> {
         let directory = directory.as_ref();
-        let files = self.collect_files(directory)?;
+        let (files, skipped) = self.walk(directory)?;
 
         if self.config.verbose {
             println!("Collected {} files", files.len());
         }
 
-        let mut manifest = ArchiveManifest::new(directory.to_path_buf());
-        let mut file_data = Vec::new();
-        let mut current_offset = 0u64;
+        let (mut manifest, file_data) = self.with_disk_source(directory, files, |source| {
+            self.create_manifest_from(source, directory.to_path_buf())
+        })?;
+        manifest.skipped.splice(0..0, skipped);
+        Ok((manifest, file_data))
+    }
 
-        // Setup progress bar
-        let progress = if self.config.show_progress {
-            let pb = ProgressBar::new(files.len() as u64);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-                    .unwrap()
-                    .progress_chars("#>-")
-            );
-            Some(pb)
-        } else {
-            None
+    /// Run `f` on the files collected from `directory`, taking those
+    /// unchanged since the `since` journal from its archive
+    fn with_disk_source<T, F>(&self, directory: &Path, files: Vec<PathBuf>, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn FileSource) -> Result<T>,
+    {
+        let source = DiskSource::new(directory.to_path_buf(), files);
+        let journal = match &self.config.since {
+            Some(path) => Journal::load(path)?,
+            None => None,
+        };
+        let Some(journal) = journal else {
+            return f(&source);
         };
 
-        for file_path in &files {
-            let relative_path = file_path.strip_prefix(directory)
-                .map_err(|e| Error::new(ErrorKind::Other, e))?
-                .to_path_buf();
+        let previous = journal.archive.clone();
+        let source = JournalSource::new(source, journal)?;
+        let result = f(&source)?;
+        if self.config.verbose {
+            let stats = source.stats();
+            println!("Journal: {} unchanged files ({} bytes) copied from {}",
+                stats.unchanged_files, stats.unchanged_bytes, previous.display());
+        }
+        Ok(result)
+    }
 
-            if let Some(ref pb) = progress {
-                pb.set_message(format!("{}", relative_path.display()));
-            }
+    /// Content address of a directory, honouring the exclusion rules, without archiving it
+    pub fn content_address<P: AsRef<Path>>(&self, directory: P) -> Result<[u8; 32]> {
+        let directory = directory.as_ref();
+        let files = self.collect_files(directory)?;
+        content_address_of(&DiskSource::new(directory.to_path_buf(), files))
+    }
+
+    /// Create a manifest from any file source
+    ///
+    /// Every path the source lists is archived; exclusion rules only apply
+    /// when collecting files from disk.
+    pub fn create_manifest_from<S: FileSource + ?Sized>(
+        &self,
+        source: &S,
+        base_directory: PathBuf,
+    ) -> Result<(ArchiveManifest, Vec<u8>)> {
+        let mut file_data = Vec::new();
+        let manifest = self.write_entries(source, base_directory, &mut file_data)?;
+        Ok((manifest, file_data))
+    }
 
-            let metadata = fs::symlink_metadata(file_path)?;
+    /// Create a manifest from a file source, writing the concatenated
+    /// contents of its regular files to `file_data` as they are read
+    fn write_entries<S: FileSource + ?Sized, W: Write>(
+        &self,
+        source: &S,
+        base_directory: PathBuf,
+        file_data: &mut W,
+    ) -> Result<ArchiveManifest> {
+        let paths = source.paths()?;
 
-            let entry = if metadata.is_symlink() {
-                // Handle symlink
-                FileEntry::from_path(file_path, relative_path.clone(), current_offset)?
-            } else if metadata.is_dir() {
-                // Handle directory
-                FileEntry::from_path(file_path, relative_path.clone(), current_offset)?
-            } else {
-                // Handle regular file
-                let file_contents = fs::read(file_path)?;
-                let entry = FileEntry::from_path(file_path, relative_path.clone(), current_offset)?;
+        let mut manifest = ArchiveManifest::new(base_directory);
+        let mut current_offset = 0u64;
 
-                // Append file data
-                file_data.extend_from_slice(&file_contents);
-                current_offset += file_contents.len() as u64;
+        let mut progress = crate::progress::item_progress(paths.len() as u64, self.config.show_progress);
 
-                entry
+        for relative_path in &paths {
+            progress.start_item(&relative_path.display().to_string());
+
+            // Salvaged files are read whole, so a read error skips them before anything is written
+            let entry = if self.config.salvage {
+                self.read_entry(source, relative_path).and_then(|(entry, file_contents)| {
+                    file_data.write_all(&file_contents)?;
+                    Ok((entry, file_contents.len() as u64))
+                })
+            } else {
+                Ok(self.stream_entry(source, relative_path, file_data)?)
             };
 
-            if self.config.verbose {
-                println!("  Added: {} ({} bytes)", relative_path.display(), entry.size);
+            match entry {
+                Ok((mut entry, written)) => {
+                    timestamps::normalize_entry(&mut entry, self.config.timestamp_policy, self.config.whole_second_timestamps)?;
+                    entry.data_offset = current_offset;
+                    current_offset += written;
+
+                    if self.config.verbose {
+                        println!("  Added: {} ({} bytes)", relative_path.display(), entry.size);
+                        if !entry.gaps.is_empty() {
+                            println!("  Damaged: {} ({} bytes unreadable, stored as zeros)",
+                                relative_path.display(), gap_bytes(&entry.gaps));
+                        }
+                    }
+
+                    manifest.add_entry(entry);
+                }
+                Err(e) if self.config.salvage => {
+                    if self.config.verbose {
+                        println!("  Skipped: {}: {}", relative_path.display(), e);
+                    }
+                    manifest.skipped.push(relative_path.clone());
+                }
+                Err(e) => return Err(e),
             }
 
-            manifest.add_entry(entry);
+            progress.finish_item();
+        }
+
+        progress.finish();
 
-            if let Some(ref pb) = progress {
-                pb.inc(1);
-            }
+        Ok(manifest)
+    }
+
+    /// Read an entry's metadata and, for regular files, its contents
+    fn read_entry<S: FileSource + ?Sized>(&self, source: &S, relative_path: &Path) -> Result<(FileEntry, Vec<u8>)> {
+        let mut entry = source.entry(relative_path)?;
+        if entry.file_type != FileType::Regular {
+            return Ok((entry, Vec::new()));
+        }
+
+        let (file_contents, gaps) = if self.config.salvage {
+            source.read_file_salvaged(relative_path)?
+        } else {
+            (source.read_file(relative_path)?, Vec::new())
+        };
+
+        // A salvaged file is hashed as stored, zeros included, so it extracts cleanly
+        if !self.config.hash_files {
+            entry.sha256 = UNHASHED.to_string();
+        } else if entry.sha256.is_empty() || !gaps.is_empty() {
+            entry.sha256 = hex_encode(&crate::verification::calculate_sha256(&file_contents));
         }
+        entry.size = file_contents.len() as u64;
+        entry.gaps = gaps;
+        entry.mime_type = mime::detect(&file_contents).map(str::to_string);
+        entry.compressed_size = Some(estimate_compressed_size(&file_contents, entry.size));
+        Ok((entry, file_contents))
+    }
 
-        if let Some(pb) = progress {
-            pb.finish_with_message("Done");
+    /// Read an entry's metadata and copy a regular file's contents to
+    /// `file_data`, returning the entry and the number of bytes copied
+    fn stream_entry<S: FileSource + ?Sized, W: Write>(&self, source: &S, relative_path: &Path, file_data: &mut W) -> Result<(FileEntry, u64)> {
+        let mut entry = source.entry(relative_path)?;
+        if entry.file_type != FileType::Regular {
+            return Ok((entry, 0));
         }
 
-        Ok((manifest, file_data))
+        let mut hasher = (self.config.hash_files && entry.sha256.is_empty()).then(Sha256::new);
+        let mut reader = source.open_file(relative_path)?;
+        let mut buffer = vec![0u8; 1024 * 1024];
+        let mut head = Vec::with_capacity(ESTIMATE_SAMPLE_SIZE);
+        let mut size = 0u64;
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&buffer[..read]);
+            }
+            if head.len() < ESTIMATE_SAMPLE_SIZE {
+                head.extend_from_slice(&buffer[..read.min(ESTIMATE_SAMPLE_SIZE - head.len())]);
+            }
+            file_data.write_all(&buffer[..read])?;
+            size += read as u64;
+        }
+
+        if !self.config.hash_files {
+            entry.sha256 = UNHASHED.to_string();
+        } else if let Some(hasher) = hasher {
+            entry.sha256 = hex_encode(&hasher.finalize());
+        }
+        entry.size = size;
+        entry.mime_type = mime::detect(&head).map(str::to_string);
+        entry.compressed_size = Some(estimate_compressed_size(&head, size));
+        Ok((entry, size))
     }
 
     /// Compress a directory into a GLIF archive
@@ -233,37 +733,136 @@ impl DirectoryCompressor {
             println!("Compressing directory: {}", directory.display());
         }
 
-        // Create manifest and collect file data
-        let (manifest, file_data) = self.create_manifest(directory)?;
+        if self.config.compression.max_memory.is_some() {
+            if self.config.chunk_cache.is_some() {
+                return Err(Error::new(ErrorKind::InvalidInput, "a chunk cache cannot be used with a memory budget").into());
+            }
+            self.compress_directory_streaming(directory, output_path)?;
+        } else {
+            // Create manifest and collect file data
+            let (manifest, file_data) = self.create_manifest(directory)?;
+            let archive = self.build_archive(&manifest, &file_data)?;
+
+            // Write the final archive
+            fs::write(output_path, archive)?;
+        }
+
+        if let Some(journal) = &self.config.journal {
+            Journal::for_archive(output_path)?.save(journal)?;
+        }
 
+        if self.config.verbose {
+            println!("Archive created: {}", output_path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Compress a directory within `max_memory`, streaming file contents
+    /// through a `GlifEncoder`
+    ///
+    /// The manifest precedes the compressed data but is only complete once
+    /// every file has been read, so the compressed data is held in a
+    /// temporary file next to the output until then.
+    fn compress_directory_streaming(&self, directory: &Path, output_path: &Path) -> Result<()> {
+        let (files, skipped) = self.walk(directory)?;
+        if self.config.verbose {
+            println!("Collected {} files", files.len());
+        }
+
+        let spill_dir = crate::stream::spill_dir_for(output_path);
+        let compressed = BufWriter::new(crate::stream::spill::anonymous_file(&spill_dir)?);
+        let mut encoder = GlifEncoder::new(compressed, &self.config.compression)?.with_spill_dir(&spill_dir);
+
+        let mut manifest = self.with_disk_source(directory, files, |source| {
+            self.write_entries(source, directory.to_path_buf(), &mut encoder)
+        })?;
+        manifest.skipped.splice(0..0, skipped);
+        self.print_totals(&manifest);
+
+        let tree_hashes = tree_hashes(&manifest)?;
+        let mut compressed = encoder
+            .finish_annotated(|sidecar| annotate_sidecar(sidecar, &manifest, tree_hashes))?
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        let compressed_size = compressed.stream_position()?;
+        self.print_compressed_size(&manifest, compressed_size);
+
+        compressed.seek(SeekFrom::Start(0))?;
+        record_compressed_sizes(&mut manifest, &mut compressed)?;
+        compressed.seek(SeekFrom::Start(0))?;
+        manifest.format = self.config.manifest_format;
+        manifest.compressed = self.config.compress_manifest;
+        let mut output = BufWriter::new(fs::File::create(output_path)?);
+        manifest.write_with_stream(&mut BufReader::new(compressed), &mut output)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    fn print_totals(&self, manifest: &ArchiveManifest) {
         if self.config.verbose {
             println!("Total files: {}", manifest.file_count);
             println!("Total size: {} bytes", manifest.total_size);
         }
+    }
 
-        // Compress the concatenated file data
-        let compressed_data = crate::compress(&file_data, &self.config.compression)?;
-
+    fn print_compressed_size(&self, manifest: &ArchiveManifest, compressed_size: u64) {
         if self.config.verbose {
-            println!("Compressed size: {} bytes", compressed_data.len());
-            println!("Compression ratio: {:.2}%",
-                manifest.compression_ratio(compressed_data.len() as u64));
+            println!("Compressed size: {} bytes", compressed_size);
+            println!("Compression ratio: {:.2}%", manifest.compression_ratio(compressed_size));
         }
+    }
 
-        // Write the final archive
-        let mut output = fs::File::create(output_path)?;
+    /// Compress a file source into directory archive bytes
+    pub fn compress_source<S: FileSource>(&self, source: &S, base_directory: PathBuf) -> Result<Vec<u8>> {
+        let (manifest, file_data) = self.create_manifest_from(source, base_directory)?;
+        self.build_archive(&manifest, &file_data)
+    }
 
-        // Write manifest
-        manifest.write(&mut output)?;
+    /// Serialize the manifest followed by the compressed file data
+    pub(crate) fn build_archive(&self, manifest: &ArchiveManifest, file_data: &[u8]) -> Result<Vec<u8>> {
+        self.print_totals(manifest);
 
-        // Write compressed data
-        output.write_all(&compressed_data)?;
+        // Compress the concatenated file data, recording the tree hash in the sidecar
+        let tree_hashes = tree_hashes(manifest)?;
+        let annotate = |sidecar: &mut GlifSidecar| annotate_sidecar(sidecar, manifest, tree_hashes);
+        let compressed_data = match &self.config.chunk_cache {
+            Some(cache) => self.compress_cached(manifest, file_data, cache, annotate)?,
+            None => crate::compress_annotated(file_data, &self.config.compression, annotate)?.0,
+        };
+        self.print_compressed_size(manifest, compressed_data.len() as u64);
+
+        let mut manifest = manifest.clone();
+        manifest.format = self.config.manifest_format;
+        manifest.compressed = self.config.compress_manifest;
+        record_compressed_sizes(&mut manifest, &mut compressed_data.as_slice())?;
+        let mut archive = Vec::with_capacity(compressed_data.len());
 
+        // Write the manifest, then the compressed data with the manifest's hash in its header
+        manifest.write_with_stream(&mut compressed_data.as_slice(), &mut archive)?;
+
+        Ok(archive)
+    }
+
+    /// Compress the file data, taking chunks of unchanged files from `cache`
+    fn compress_cached<F>(&self, manifest: &ArchiveManifest, file_data: &[u8], cache: &ChunkCache, annotate: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&mut GlifSidecar),
+    {
+        let config = &self.config.compression;
+        config.validate()?;
+        let threads = crate::compression::resolve_threads(config.threads);
+
+        let workers = crate::compression::chunks_within_budget(threads, config.max_in_flight);
+        let (framed, stats) = config.install(|| compress_with_cache(manifest, file_data, config, workers, cache))?;
         if self.config.verbose {
-            println!("Archive created: {}", output_path.display());
+            println!("Chunk cache: {} hits ({} bytes reused), {} misses", stats.hits, stats.bytes_reused, stats.misses);
         }
+        cache.evict()?;
 
-        Ok(())
+        let payload_hash = crate::calculate_sha256(file_data);
+        let (archive, _) = crate::assemble_archive(file_data.len() as u64, payload_hash, framed, config, threads, annotate)?;
+        Ok(archive)
     }
 
     /// Extract a directory archive
@@ -274,125 +873,310 @@ impl DirectoryCompressor {
         verbose: bool,
         show_progress: bool,
     ) -> Result<()> {
+        Self::extract_directory_with_report(input_path, output_directory, threads, verbose, show_progress)?
+            .into_result()
+    }
+
+    /// Extract a directory archive, recording the outcome of every entry
+    ///
+    /// Entries that fail are recorded and extraction continues with the
+    /// rest; metadata that cannot be restored is recorded as a warning.
+    /// Errors that prevent reading the archive are returned directly.
+    pub fn extract_directory_with_report<P: AsRef<Path>, Q: AsRef<Path>>(
+        input_path: P,
+        output_directory: Q,
+        threads: usize,
+        verbose: bool,
+        show_progress: bool,
+    ) -> Result<ExtractionReport> {
+        let options = ExtractOptions::new()
+            .with_threads(threads)
+            .with_verbose(verbose)
+            .with_progress(show_progress);
+        Self::extract_directory_with_options(input_path, output_directory, &options)
+    }
+
+    /// Extract a directory archive with explicit options, recording the outcome of every entry
+    pub fn extract_directory_with_options<P: AsRef<Path>, Q: AsRef<Path>>(
+        input_path: P,
+        output_directory: Q,
+        options: &ExtractOptions,
+    ) -> Result<ExtractionReport> {
         let input_path = input_path.as_ref();
         let output_directory = output_directory.as_ref();
 
-        if verbose {
+        if options.verbose {
             println!("Extracting archive: {}", input_path.display());
         }
 
-        // Read the archive
-        let archive_data = fs::read(input_path)?;
-        let mut cursor = std::io::Cursor::new(&archive_data);
+        // Read the archive, decoding ASCII armor if present
+        let archive_data = crate::armor::read_archive_file(input_path)?;
 
-        // Read manifest
-        let manifest = ArchiveManifest::read(&mut cursor)?;
+        let report = ExtractionReport::new(input_path, output_directory);
+        let mut sink = DiskSink::new(output_directory.to_path_buf());
+        let report = Self::extract_into(&archive_data, &mut sink, report, options)?;
 
-        if verbose {
-            println!("Files in archive: {}", manifest.file_count);
-            println!("Total size: {} bytes", manifest.total_size);
+        if options.verbose {
+            println!("Extraction complete: {}", output_directory.display());
         }
 
-        // Read compressed data
+        Ok(report)
+    }
+
+    /// Run a full extraction of a directory archive without writing anything
+    ///
+    /// Decompresses the archive, checks every file against its hash (unless
+    /// `options` turn that off) and validates each entry's path and metadata
+    /// through a `NullSink`. The report's output is `/dev/null`.
+    pub fn extract_directory_to_null<P: AsRef<Path>>(input_path: P, options: &ExtractOptions) -> Result<ExtractionReport> {
+        let input_path = input_path.as_ref();
+        let archive_data = crate::armor::read_archive_file(input_path)?;
+        let report = ExtractionReport::new(input_path, Path::new("/dev/null"));
+        Self::extract_into(&archive_data, &mut NullSink::new(), report, options)
+    }
+
+    /// Extract directory archive bytes into any filesystem sink
+    pub fn extract_to_sink<K: FileSystemSink>(
+        archive_data: &[u8],
+        sink: &mut K,
+        threads: usize,
+    ) -> Result<ExtractionReport> {
+        let report = ExtractionReport::new(Path::new(""), Path::new(""));
+        let options = ExtractOptions::new().with_threads(threads).with_progress(false);
+        Self::extract_into(archive_data, sink, report, &options)
+    }
+
+    /// Read a directory archive's manifest and decompress its file data
+    ///
+    /// Each regular file's contents are at `entry.data_offset` in the data;
+    /// use `FileEntry::data` to slice and verify them.
+    pub fn read_archive(archive_data: &[u8], threads: usize) -> Result<(ArchiveManifest, Vec<u8>)> {
+        Self::read_archive_with(archive_data, &DecompressionConfig::new().with_threads(threads))
+    }
+
+    /// Read a directory archive's manifest and decompress its file data with explicit settings
+    pub fn read_archive_with(archive_data: &[u8], config: &DecompressionConfig) -> Result<(ArchiveManifest, Vec<u8>)> {
+        let mut cursor = std::io::Cursor::new(archive_data);
+        let (manifest, prefix) = ArchiveManifest::read_prefix(&mut cursor)?;
+
         let compressed_data_start = cursor.position() as usize;
         let compressed_data = &archive_data[compressed_data_start..];
+        check_manifest_hash(&prefix, &GlifHeader::read(&mut &compressed_data[..])?)?;
+
+        // Decompress, naming the entries affected by any corruption
+        let decompressed_data = crate::decompress_with(compressed_data, config).map_err(|e| match e {
+            GlifError::Corrupt(mut corruption) => {
+                corruption.data_offset += compressed_data_start as u64;
+                corruption.find_affected_entries(&manifest.entries);
+                GlifError::Corrupt(corruption)
+            }
+            e => e,
+        })?;
 
-        // Decompress
-        let decompressed_data = crate::decompress(compressed_data, threads)?;
+        Ok((manifest, decompressed_data))
+    }
+
+    /// Extract directory archive bytes into `sink` with explicit options,
+    /// adding each entry's outcome to `report`
+    ///
+    /// For archives that aren't files of their own, such as one member of
+    /// a multi-member file (see `format::members`).
+    pub fn extract_into<K: FileSystemSink>(
+        archive_data: &[u8],
+        sink: &mut K,
+        mut report: ExtractionReport,
+        options: &ExtractOptions,
+    ) -> Result<ExtractionReport> {
+        let (manifest, decompressed_data) = Self::read_archive_with(archive_data, &options.decompression_config())?;
+        let verbose = options.verbose;
 
         if verbose {
+            println!("Files in archive: {}", manifest.file_count);
+            println!("Total size: {} bytes", manifest.total_size);
             println!("Decompressed {} bytes", decompressed_data.len());
         }
 
-        // Setup progress bar
-        let progress = if show_progress {
-            let pb = ProgressBar::new(manifest.file_count as u64);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-                    .unwrap()
-                    .progress_chars("#>-")
-            );
-            Some(pb)
+        let mut bases = if manifest.has_external_entries() {
+            BaseArchives::open(&options.bases)?
         } else {
-            None
+            BaseArchives::new()
         };
 
+        let mut progress = crate::progress::item_progress(manifest.file_count as u64, options.show_progress);
+
+        // Set once a permission change fails where restoring everything
+        // else works, so the rest of the entries skip permissions
+        let mut modes_unsupported = false;
+
         // Extract files
+        // Paths written so far, to catch names that collide once rewritten
+        let mut written = std::collections::HashSet::new();
+
         for entry in &manifest.entries {
-            let target_path = output_directory.join(&entry.path);
+            progress.start_item(&entry.path.display().to_string());
+
+            let mut outcome = EntryOutcome {
+                path: entry.path.clone(),
+                status: EntryStatus::Extracted,
+                renamed_to: None,
+                reason: None,
+                warnings: Vec::new(),
+            };
 
-            if let Some(ref pb) = progress {
-                pb.set_message(format!("{}", entry.path.display()));
+            if !options.includes_type(entry, &decompressed_data) {
+                outcome.status = EntryStatus::Skipped;
+                outcome.reason = Some("not an included type".to_string());
+                report.record(outcome);
+                progress.finish_item();
+                continue;
             }
 
-            match entry.file_type {
-                FileType::Directory => {
-                    fs::create_dir_all(&target_path)?;
+            let renamed;
+            let entry = match options.names.apply(&entry.path) {
+                Ok(None) => entry,
+                Ok(Some(path)) => {
                     if verbose {
-                        println!("  Created directory: {}", target_path.display());
+                        println!("  Renamed: {} -> {}", entry.path.display(), path.display());
                     }
+                    outcome.renamed_to = Some(path.clone());
+                    renamed = FileEntry { path, ..entry.clone() };
+                    &renamed
                 }
-                FileType::Symlink => {
-                    if let Some(ref target) = entry.symlink_target {
-                        if let Some(parent) = target_path.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-                        std::os::unix::fs::symlink(target, &target_path)?;
-                        if verbose {
-                            println!("  Created symlink: {} -> {}",
-                                target_path.display(), target.display());
-                        }
-                    }
+                Err(reason) => {
+                    outcome.status = EntryStatus::Failed;
+                    outcome.reason = Some(reason);
+                    report.record(outcome);
+                    progress.finish_item();
+                    continue;
                 }
-                FileType::Regular => {
-                    // Extract file data
-                    let start = entry.data_offset as usize;
-                    let end = start + entry.size as usize;
-
-                    if end > decompressed_data.len() {
-                        return Err(Error::new(
-                            ErrorKind::InvalidData,
-                            format!("File data out of bounds for {}", entry.path.display())
+            };
+            if options.names != NamePolicy::Keep && !written.insert(entry.path.clone()) && entry.file_type != FileType::Directory {
+                outcome.status = EntryStatus::Failed;
+                outcome.reason = Some(format!("{} is already taken by another entry", entry.path.display()));
+                report.record(outcome);
+                progress.finish_item();
+                continue;
+            }
+            let target_path = report.output.join(&entry.path);
+
+            match Self::extract_entry(entry, sink, &target_path, &decompressed_data, &mut bases, options, &mut outcome) {
+                Ok(()) if outcome.status == EntryStatus::Skipped => {}
+                Ok(()) => {
+                    if !entry.gaps.is_empty() {
+                        outcome.warnings.push(format!(
+                            "{} bytes could not be read when archived and were restored as zeros",
+                            gap_bytes(&entry.gaps)
                         ));
                     }
 
-                    let file_data = &decompressed_data[start..end];
-
-                    // Verify integrity
-                    entry.verify_integrity(file_data)?;
-
-                    // Create parent directories
-                    if let Some(parent) = target_path.parent() {
-                        fs::create_dir_all(parent)?;
+                    // Restore metadata
+                    if entry.file_type != FileType::Symlink {
+                        let restored = if modes_unsupported {
+                            sink.restore_metadata_except_mode(entry)
+                        } else {
+                            match sink.restore_metadata(entry) {
+                                Err(e) if options.unsupported.degrades() && is_unsupported(&e) => {
+                                    let restored = sink.restore_metadata_except_mode(entry);
+                                    if restored.is_ok() {
+                                        modes_unsupported = true;
+                                        if verbose {
+                                            println!("  Permissions are not supported here; skipping them");
+                                        }
+                                    }
+                                    restored
+                                }
+                                restored => restored,
+                            }
+                        };
+                        if let Err(e) = restored {
+                            outcome.warnings.push(format!("could not restore metadata: {}", e));
+                        }
                     }
-
-                    // Write file
-                    fs::write(&target_path, file_data)?;
-
+                }
+                Err(e) => {
                     if verbose {
-                        println!("  Extracted: {} ({} bytes)",
-                            target_path.display(), entry.size);
+                        println!("  Failed: {}: {}", entry.path.display(), e);
                     }
+                    outcome.status = EntryStatus::Failed;
+                    outcome.reason = Some(e.to_string());
                 }
             }
 
-            // Restore metadata
-            if entry.file_type != FileType::Symlink {
-                entry.restore_metadata(&target_path)?;
-            }
+            report.record(outcome);
+            progress.finish_item();
+        }
+
+        progress.finish();
 
-            if let Some(ref pb) = progress {
-                pb.inc(1);
+        Ok(report)
+    }
+
+    /// Write a single entry to the sink, marking `outcome` if the entry
+    /// was skipped or written differently
+    fn extract_entry<K: FileSystemSink>(
+        entry: &FileEntry,
+        sink: &mut K,
+        target_path: &Path,
+        decompressed_data: &[u8],
+        bases: &mut BaseArchives,
+        options: &ExtractOptions,
+        outcome: &mut EntryOutcome,
+    ) -> Result<()> {
+        let verbose = options.verbose;
+        match entry.file_type {
+            FileType::Directory => {
+                sink.create_dir(&entry.path)?;
+                if verbose {
+                    println!("  Created directory: {}", target_path.display());
+                }
             }
-        }
+            FileType::Symlink => {
+                let Some(ref target) = entry.symlink_target else {
+                    outcome.status = EntryStatus::Skipped;
+                    outcome.reason = Some("symlink has no target".to_string());
+                    return Ok(());
+                };
+                match sink.symlink(&entry.path, target) {
+                    Err(e) if options.unsupported.degrades() && is_unsupported(&e) => {
+                        if options.unsupported == UnsupportedPolicy::Skip {
+                            outcome.status = EntryStatus::Skipped;
+                            outcome.reason = Some(format!("symlinks are not supported here ({})", e));
+                            return Ok(());
+                        }
+                        sink.write_file(&entry.path, target.as_os_str().as_encoded_bytes())?;
+                        outcome.warnings.push(format!(
+                            "symlinks are not supported here; written as a text file holding {}", target.display()
+                        ));
+                    }
+                    result => result?,
+                }
+                if verbose {
+                    println!("  Created symlink: {} -> {}",
+                        target_path.display(), target.display());
+                }
+            }
+            FileType::Regular => {
+                let external;
+                let file_data = match &entry.external {
+                    Some(reference) => {
+                        external = bases.read(reference)?;
+                        if external.len() as u64 != entry.size {
+                            return Err(GlifError::SizeMismatch { expected: entry.size, actual: external.len() as u64 });
+                        }
+                        &external[..]
+                    }
+                    None if options.verify.checks_payload() => entry.data(decompressed_data)?,
+                    None => entry.data_unverified(decompressed_data)?,
+                };
 
-        if let Some(pb) = progress {
-            pb.finish_with_message("Done");
-        }
+                // Write file
+                sink.write_file(&entry.path, file_data)?;
 
-        if verbose {
-            println!("Extraction complete: {}", output_directory.display());
+                if verbose {
+                    println!("  Extracted: {} ({} bytes)",
+                        target_path.display(), entry.size);
+                }
+            }
         }
 
         Ok(())
@@ -402,6 +1186,7 @@ impl DirectoryCompressor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compression::memory::chunk_compression_memory;
     use tempfile::TempDir;
     use std::fs::File;
     use std::io::Write;
@@ -461,6 +1246,55 @@ mod tests {
         assert_eq!(files.len(), 3); // subdir + file1.txt + subdir/file3.txt
     }
 
+    #[test]
+    fn test_collect_files_exclude_caches() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+
+        fs::create_dir_all(base.join("node_modules/pkg")).unwrap();
+        fs::create_dir(base.join("tagged")).unwrap();
+        fs::create_dir(base.join("fake_tag")).unwrap();
+        File::create(base.join("file1.txt")).unwrap();
+        File::create(base.join("node_modules/pkg/index.js")).unwrap();
+        fs::write(base.join("tagged/CACHEDIR.TAG"), b"Signature: 8a477f597d28d172789f06886806bc55\n").unwrap();
+        fs::write(base.join("fake_tag/CACHEDIR.TAG"), b"not a cache").unwrap();
+
+        let config = DirectoryCompressionConfig::default().with_exclude_caches(true);
+        let compressor = DirectoryCompressor::new(config).unwrap();
+
+        let files = compressor.collect_files(base).unwrap();
+        // file1.txt + fake_tag + fake_tag/CACHEDIR.TAG
+        assert_eq!(files.len(), 3);
+        assert!(!files.iter().any(|f| f.starts_with(base.join("node_modules"))));
+        assert!(!files.iter().any(|f| f.starts_with(base.join("tagged"))));
+
+        // Caches are kept unless explicitly excluded
+        let compressor = DirectoryCompressor::new(DirectoryCompressionConfig::default()).unwrap();
+        assert_eq!(compressor.collect_files(base).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_cbor_manifest_roundtrip() {
+        let tree = crate::testing::TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "beta")
+            .symlink("link", "a.txt")
+            .build();
+        let config = DirectoryCompressionConfig::default().with_progress(false).with_manifest_format(ManifestFormat::Cbor);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+        assert_eq!(archive[8], crate::archive::MANIFEST_FORMAT_ZSTD);
+
+        let info = crate::GlifHeader::peek(&archive).unwrap();
+        let manifest = info.manifest.as_ref().unwrap();
+        assert!(manifest.compressed && manifest.format == ManifestFormat::Cbor);
+        crate::verify_archive(&archive[info.header_offset().unwrap() as usize..]).unwrap();
+
+        let mut sink = crate::testing::MemoryFs::new();
+        DirectoryCompressor::extract_to_sink(&archive, &mut sink, 1).unwrap();
+        assert!(sink.differences(&tree).is_empty());
+    }
+
     #[test]
     fn test_compress_extract_roundtrip() {
         let temp_dir = TempDir::new().unwrap();
@@ -506,4 +1340,514 @@ mod tests {
         let content2 = fs::read_to_string(extract_dir.join("subdir/file2.txt")).unwrap();
         assert_eq!(content2, "Test data");
     }
+
+    #[test]
+    fn test_memory_budget_streams_same_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(source_dir.join("subdir")).unwrap();
+        fs::write(source_dir.join("file1.txt"), b"streamed entry ".repeat(10_000)).unwrap();
+        fs::write(source_dir.join("subdir/file2.txt"), b"Test data").unwrap();
+        fs::write(source_dir.join("empty"), b"").unwrap();
+
+        let mut config = DirectoryCompressionConfig::default()
+            .with_verbose(false)
+            .with_progress(false);
+        let whole = temp_dir.path().join("whole.glif");
+        DirectoryCompressor::new(config.clone()).unwrap().compress_directory(&source_dir, &whole).unwrap();
+
+        config.compression.max_memory = Some(chunk_compression_memory(config.compression.level, config.compression.chunk_size));
+        let bounded = temp_dir.path().join("bounded.glif");
+        DirectoryCompressor::new(config).unwrap().compress_directory(&source_dir, &bounded).unwrap();
+
+        // The manifests differ only in timestamps
+        let (whole_manifest, whole_data) = DirectoryCompressor::read_archive(&fs::read(&whole).unwrap(), 2).unwrap();
+        let (bounded_manifest, bounded_data) = DirectoryCompressor::read_archive(&fs::read(&bounded).unwrap(), 2).unwrap();
+        assert_eq!(bounded_data, whole_data);
+        assert_eq!(bounded_manifest.total_size, whole_manifest.total_size);
+        assert_eq!(bounded_manifest.entries.len(), whole_manifest.entries.len());
+
+        let extract_dir = temp_dir.path().join("extract");
+        DirectoryCompressor::extract_directory(&bounded, &extract_dir, 2, false, false).unwrap();
+        assert_eq!(
+            fs::read(extract_dir.join("file1.txt")).unwrap(),
+            fs::read(source_dir.join("file1.txt")).unwrap()
+        );
+        assert_eq!(fs::read(extract_dir.join("empty")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_mime_types_and_type_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(source_dir.join("img")).unwrap();
+        fs::write(source_dir.join("img/logo.png"), b"\x89PNG\r\n\x1a\n not really a png").unwrap();
+        fs::write(source_dir.join("notes.txt"), b"plain notes\n").unwrap();
+        fs::write(source_dir.join("empty"), b"").unwrap();
+
+        let mut config = DirectoryCompressionConfig::default().with_progress(false);
+        let whole = temp_dir.path().join("whole.glif");
+        DirectoryCompressor::new(config.clone()).unwrap().compress_directory(&source_dir, &whole).unwrap();
+        config.compression.max_memory = Some(chunk_compression_memory(config.compression.level, config.compression.chunk_size));
+        let bounded = temp_dir.path().join("bounded.glif");
+        DirectoryCompressor::new(config).unwrap().compress_directory(&source_dir, &bounded).unwrap();
+
+        // Read and streamed files are typed alike
+        for archive in [&whole, &bounded] {
+            let (manifest, _) = DirectoryCompressor::read_archive(&fs::read(archive).unwrap(), 1).unwrap();
+            let types: Vec<_> = manifest.entries.iter().map(|e| (e.path.to_str().unwrap(), e.mime_type.as_deref())).collect();
+            assert!(types.contains(&("img/logo.png", Some("image/png"))));
+            assert!(types.contains(&("notes.txt", Some("text/plain"))));
+            assert!(types.contains(&("empty", None)));
+            assert!(types.contains(&("img", None)));
+        }
+
+        let extract_dir = temp_dir.path().join("extract");
+        let options = ExtractOptions::new().with_progress(false).with_include_types(vec!["image/*".to_string()]);
+        let report = DirectoryCompressor::extract_directory_with_options(&whole, &extract_dir, &options).unwrap();
+        assert_eq!((report.extracted, report.skipped), (2, 2));
+        assert!(extract_dir.join("img/logo.png").exists());
+        assert!(!extract_dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_records_compressed_sizes() {
+        use crate::testing::data::{generate, DataKind};
+
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("sub/zeros.bin"), generate(DataKind::Zeros, 3 * 1024 * 1024, 1)).unwrap();
+        fs::write(source_dir.join("random.bin"), generate(DataKind::Random, 1024 * 1024, 2)).unwrap();
+        fs::write(source_dir.join("empty"), b"").unwrap();
+
+        let mut config = DirectoryCompressionConfig::default().with_progress(false);
+        let whole = temp_dir.path().join("whole.glif");
+        DirectoryCompressor::new(config.clone()).unwrap().compress_directory(&source_dir, &whole).unwrap();
+        config.compression.max_memory = Some(chunk_compression_memory(config.compression.level, config.compression.chunk_size));
+        let bounded = temp_dir.path().join("bounded.glif");
+        DirectoryCompressor::new(config).unwrap().compress_directory(&source_dir, &bounded).unwrap();
+
+        for archive in [&whole, &bounded] {
+            let (manifest, _) = DirectoryCompressor::read_archive(&fs::read(archive).unwrap(), 1).unwrap();
+            let find = |path: &str| manifest.find_entry(&PathBuf::from(path)).unwrap();
+            assert!(find("sub/zeros.bin").compression_ratio().unwrap() < 1.0);
+            assert!(find("random.bin").compression_ratio().unwrap() > 99.0);
+            assert_eq!(find("empty").compressed_size, Some(0));
+            assert_eq!(find("empty").compression_ratio(), None);
+            assert_eq!(find("sub").compressed_size, None);
+        }
+    }
+
+    #[test]
+    fn test_timestamp_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+        let file = source_dir.join("from-2200.txt");
+        fs::write(&file, b"bogus clock").unwrap();
+        let future = filetime::FileTime::from_unix_time(7_258_118_400, 500);
+        filetime::set_file_times(&file, future, future).unwrap();
+
+        let archive_path = temp_dir.path().join("archive.glif");
+        let mtime_with = |policy, whole_seconds| {
+            let config = DirectoryCompressionConfig::default().with_progress(false)
+                .with_timestamp_policy(policy)
+                .with_whole_second_timestamps(whole_seconds);
+            DirectoryCompressor::new(config).unwrap().compress_directory(&source_dir, &archive_path)?;
+            let (manifest, _) = DirectoryCompressor::read_archive(&fs::read(&archive_path).unwrap(), 1).unwrap();
+            Ok::<_, GlifError>(manifest.find_entry(&PathBuf::from("from-2200.txt")).unwrap().mtime)
+        };
+
+        let kept = mtime_with(TimestampPolicy::Keep, false).unwrap();
+        assert_eq!((kept.timestamp(), kept.timestamp_subsec_nanos()), (7_258_118_400, 500));
+        assert_eq!(mtime_with(TimestampPolicy::Keep, true).unwrap().timestamp_subsec_nanos(), 0);
+        assert!(mtime_with(TimestampPolicy::Clamp, false).unwrap() <= chrono::Utc::now());
+        let err = mtime_with(TimestampPolicy::Error, false).unwrap_err();
+        assert!(err.to_string().contains("in the future"), "{}", err);
+    }
+
+    #[test]
+    fn test_corrupt_archive_names_affected_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let archive_path = temp_dir.path().join("test.glif");
+
+        fs::create_dir(&source_dir).unwrap();
+        fs::write(source_dir.join("damaged.txt"), b"Some file content").unwrap();
+
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        DirectoryCompressor::new(config).unwrap()
+            .compress_directory(&source_dir, &archive_path).unwrap();
+
+        let mut archive = fs::read(&archive_path).unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 0xff;
+        fs::write(&archive_path, archive).unwrap();
+
+        let err = DirectoryCompressor::extract_directory(
+            &archive_path,
+            temp_dir.path().join("extract"),
+            1,
+            false,
+            false,
+        ).unwrap_err();
+
+        let corruption = err.corruption().unwrap();
+        assert_eq!(corruption.affected_entries, vec![PathBuf::from("damaged.txt")]);
+    }
+
+    #[test]
+    fn test_extract_report_records_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let extract_dir = temp_dir.path().join("extract");
+        let archive_path = temp_dir.path().join("test.glif");
+
+        fs::create_dir(&source_dir).unwrap();
+        fs::write(source_dir.join("blocked.txt"), b"Cannot be written").unwrap();
+        fs::write(source_dir.join("ok.txt"), b"Written fine").unwrap();
+
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        DirectoryCompressor::new(config).unwrap()
+            .compress_directory(&source_dir, &archive_path).unwrap();
+
+        // A directory in the way makes one entry fail without stopping the rest
+        fs::create_dir_all(extract_dir.join("blocked.txt")).unwrap();
+
+        let report = DirectoryCompressor::extract_directory_with_report(
+            &archive_path,
+            &extract_dir,
+            1,
+            false,
+            false,
+        ).unwrap();
+
+        assert_eq!((report.extracted, report.failed), (1, 1));
+        assert_eq!(fs::read_to_string(extract_dir.join("ok.txt")).unwrap(), "Written fine");
+
+        let failed = report.entries.iter().find(|e| e.status == EntryStatus::Failed).unwrap();
+        assert_eq!(failed.path, PathBuf::from("blocked.txt"));
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn test_extract_to_null_checks_without_writing() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let archive_path = temp_dir.path().join("test.glif");
+
+        fs::create_dir_all(source_dir.join("docs")).unwrap();
+        fs::write(source_dir.join("docs/a.txt"), b"alpha").unwrap();
+        fs::write(source_dir.join("b.txt"), b"bravo").unwrap();
+
+        let compressor = DirectoryCompressor::new(DirectoryCompressionConfig::default().with_progress(false)).unwrap();
+        compressor.compress_directory(&source_dir, &archive_path).unwrap();
+
+        let options = ExtractOptions::new().with_progress(false);
+        let report = DirectoryCompressor::extract_directory_to_null(&archive_path, &options).unwrap();
+        assert_eq!((report.extracted, report.failed), (3, 0));
+        assert_eq!(report.output, PathBuf::from("/dev/null"));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+        // A damaged file fails its entry
+        let archive_data = fs::read(&archive_path).unwrap();
+        let (mut manifest, data) = DirectoryCompressor::read_archive(&archive_data, 1).unwrap();
+        let damaged = manifest.entries.iter_mut().find(|e| e.path == Path::new("b.txt")).unwrap();
+        damaged.sha256 = crate::hex_encode(&[0; 32]);
+        fs::write(&archive_path, compressor.build_archive(&manifest, &data).unwrap()).unwrap();
+
+        let report = DirectoryCompressor::extract_directory_to_null(&archive_path, &options).unwrap();
+        assert_eq!((report.extracted, report.failed), (2, 1));
+
+        // So would paths leaving the output directory, as they do when extracting to disk
+        let mut sink = NullSink::new();
+        sink.write_file(Path::new("docs/a.txt"), b"alpha").unwrap();
+        assert_eq!(sink.bytes(), 5);
+        assert!(sink.write_file(Path::new("../escaped"), b"").is_err());
+        assert!(sink.create_dir(Path::new("/etc")).is_err());
+        let mut disk = DiskSink::new(temp_dir.path().join("out"));
+        assert!(disk.write_file(Path::new("../escaped"), b"").is_err());
+        assert!(disk.create_dir(Path::new("/etc")).is_err());
+        assert!(disk.symlink(Path::new("../link"), Path::new("b.txt")).is_err());
+        assert!(!temp_dir.path().join("escaped").exists() && !temp_dir.path().join("link").exists());
+    }
+
+    /// An in-memory target that refuses symlinks and permission changes, like FAT
+    struct FatSink {
+        files: crate::testing::MemoryFs,
+        mode_attempts: usize,
+    }
+
+    impl FileSystemSink for FatSink {
+        fn create_dir(&mut self, path: &Path) -> std::io::Result<()> {
+            self.files.create_dir(path)
+        }
+
+        fn write_file(&mut self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+            self.files.write_file(path, data)
+        }
+
+        fn symlink(&mut self, _path: &Path, _target: &Path) -> std::io::Result<()> {
+            Err(Error::from(ErrorKind::PermissionDenied))
+        }
+
+        fn restore_metadata(&mut self, _entry: &FileEntry) -> std::io::Result<()> {
+            self.mode_attempts += 1;
+            Err(Error::from(ErrorKind::PermissionDenied))
+        }
+
+        fn restore_metadata_except_mode(&mut self, entry: &FileEntry) -> std::io::Result<()> {
+            self.files.restore_metadata(entry)
+        }
+    }
+
+    #[test]
+    fn test_unsupported_policies() {
+        use crate::testing::TreeBuilder;
+
+        let tree = TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "beta")
+            .symlink("link", "a.txt")
+            .build();
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+
+        let extract = |policy| {
+            let mut sink = FatSink { files: crate::testing::MemoryFs::new(), mode_attempts: 0 };
+            let options = ExtractOptions::new().with_progress(false).with_unsupported(policy);
+            let report = ExtractionReport::new(Path::new(""), Path::new(""));
+            let report = DirectoryCompressor::extract_into(&archive, &mut sink, report, &options).unwrap();
+            (report, sink)
+        };
+
+        // By default the symlink fails and every file warns about its permissions
+        let (report, sink) = extract(UnsupportedPolicy::Fail);
+        assert_eq!(report.failed, 1);
+        assert!(report.entries.iter().filter(|e| e.path != Path::new("link")).all(|e| !e.warnings.is_empty()));
+        assert!(sink.files.get("link").is_none());
+
+        // Degrading writes the link's target as text, and gives up on permissions after the first refusal
+        let (report, sink) = extract(UnsupportedPolicy::Text);
+        assert!(report.is_complete());
+        let link = report.entries.iter().find(|e| e.path == Path::new("link")).unwrap();
+        assert_eq!(link.status, EntryStatus::Extracted);
+        assert_eq!(link.warnings.len(), 1);
+        assert!(report.entries.iter().filter(|e| e.path != Path::new("link")).all(|e| e.warnings.is_empty()));
+        assert_eq!(sink.files.file("link"), Some(&b"a.txt"[..]));
+        assert_eq!(sink.files.file("src/b.txt"), Some(&b"beta"[..]));
+        assert_eq!(sink.mode_attempts, 1);
+
+        let (report, sink) = extract(UnsupportedPolicy::Skip);
+        assert!(report.is_complete());
+        assert_eq!(report.skipped, 1);
+        assert!(sink.files.get("link").is_none());
+
+        assert_eq!("text".parse(), Ok(UnsupportedPolicy::Text));
+        assert!("ignore".parse::<UnsupportedPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_name_policies() {
+        use crate::testing::{MemoryFs, TreeBuilder};
+
+        let tree = TreeBuilder::new()
+            .file("aux/report: final.txt", "report")
+            .file("a:b", "colon")
+            .file("a_b", "underscore")
+            .file("ok.txt", "fine")
+            .build();
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+
+        let extract = |policy| {
+            let mut sink = MemoryFs::new();
+            let options = ExtractOptions::new().with_progress(false).with_names(policy);
+            let report = ExtractionReport::new(Path::new(""), Path::new(""));
+            let report = DirectoryCompressor::extract_into(&archive, &mut sink, report, &options).unwrap();
+            (report, sink)
+        };
+
+        let (report, sink) = extract(NamePolicy::Keep);
+        assert!(report.is_complete());
+        assert_eq!(report.renamed().count(), 0);
+        assert!(sink.file("aux/report: final.txt").is_some());
+
+        // Rewritten names are reported, and a rewrite can't overwrite another entry
+        let (report, sink) = extract(NamePolicy::Replace);
+        let renamed: Vec<_> = report.renamed().collect();
+        assert!(renamed.contains(&(Path::new("aux"), Path::new("aux_"))));
+        assert!(renamed.contains(&(Path::new("aux/report: final.txt"), Path::new("aux_/report_ final.txt"))));
+        assert_eq!(sink.file("aux_/report_ final.txt"), Some(&b"report"[..]));
+        assert_eq!(sink.file("ok.txt"), Some(&b"fine"[..]));
+        assert_eq!(report.failed, 1);
+        assert_eq!(sink.file("a_b").map(|data| data.len()), Some(5));
+
+        let (report, sink) = extract(NamePolicy::Error);
+        assert_eq!((report.extracted, report.failed), (2, 3));
+        assert!(sink.file("ok.txt").is_some());
+    }
+
+    fn archived_sidecar(tree: &crate::testing::MemoryFs) -> crate::GlifSidecar {
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(tree, PathBuf::from("tree")).unwrap();
+
+        let mut cursor = std::io::Cursor::new(&archive);
+        ArchiveManifest::read(&mut cursor).unwrap();
+        let header = crate::GlifHeader::read(&mut cursor).unwrap();
+        crate::GlifSidecar::read(&mut cursor, header.sidecar_size).unwrap()
+    }
+
+    #[test]
+    fn test_identical_trees_share_tree_hash() {
+        use crate::testing::TreeBuilder;
+
+        let tree = TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "beta")
+            .symlink("link", "a.txt")
+            .build();
+        let same_contents = TreeBuilder::new()
+            .file("src/b.txt", "beta")
+            .mode("src/b.txt", 0o600)
+            .file("a.txt", "alpha")
+            .symlink("link", "a.txt")
+            .build();
+        let edited = TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("src/b.txt", "BETA")
+            .symlink("link", "a.txt")
+            .build();
+
+        let sidecar = archived_sidecar(&tree);
+        assert!(sidecar.payload.tree_hash.as_deref().unwrap().starts_with("sha256:"));
+        assert_eq!((sidecar.payload.files, sidecar.payload.directories), (Some(2), Some(1)));
+
+        assert_eq!(sidecar.payload.tree_hash, archived_sidecar(&same_contents).payload.tree_hash);
+        assert_ne!(sidecar.payload.tree_hash, archived_sidecar(&edited).payload.tree_hash);
+
+        let address = crate::archive::content_address_of(&tree).unwrap();
+        assert_eq!(sidecar.payload.content_address, Some(format_content_address(&address)));
+    }
+
+    /// Tree whose `damaged.bin` has an unreadable range and whose `gone.txt` cannot be read at all
+    struct FailingDisk(crate::testing::MemoryFs);
+
+    impl FileSource for FailingDisk {
+        fn paths(&self) -> std::io::Result<Vec<PathBuf>> {
+            FileSource::paths(&self.0)
+        }
+
+        fn entry(&self, path: &Path) -> std::io::Result<FileEntry> {
+            self.0.entry(path)
+        }
+
+        fn read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+            match path.to_str() {
+                Some("damaged.bin") | Some("gone.txt") => Err(Error::other("Input/output error")),
+                _ => self.0.read_file(path),
+            }
+        }
+
+        fn read_file_salvaged(&self, path: &Path) -> std::io::Result<(Vec<u8>, Vec<crate::archive::ReadGap>)> {
+            match path.to_str() {
+                Some("damaged.bin") => {
+                    let mut data = self.0.read_file(path)?;
+                    data[4096..8192].fill(0);
+                    Ok((data, vec![crate::archive::ReadGap { offset: 4096, length: 4096 }]))
+                }
+                _ => Ok((self.read_file(path)?, Vec::new())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_salvage_records_gaps_and_skipped_paths() {
+        let tree = FailingDisk(crate::testing::TreeBuilder::new()
+            .file("damaged.bin", vec![7u8; 16384])
+            .file("gone.txt", "lost")
+            .file("ok.txt", "fine")
+            .build());
+
+        let strict = DirectoryCompressionConfig::default().with_progress(false);
+        assert!(DirectoryCompressor::new(strict).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).is_err());
+
+        let config = DirectoryCompressionConfig::default().with_progress(false).with_salvage(true);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+
+        let (manifest, data) = DirectoryCompressor::read_archive(&archive, 1).unwrap();
+        assert_eq!(manifest.skipped, vec![PathBuf::from("gone.txt")]);
+        let damaged: Vec<_> = manifest.damaged_entries().collect();
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].gaps, vec![crate::archive::ReadGap { offset: 4096, length: 4096 }]);
+
+        // The zero-filled contents verify, so the rest of the file is recoverable
+        let contents = damaged[0].data(&data).unwrap();
+        assert_eq!(contents.len(), 16384);
+        assert!(contents[4096..8192].iter().all(|&b| b == 0));
+
+        let mut sink = crate::testing::MemoryFs::new();
+        let report = DirectoryCompressor::extract_to_sink(&archive, &mut sink, 1).unwrap();
+        assert_eq!(report.failed, 0);
+        let outcome = report.entries.iter().find(|e| e.path == Path::new("damaged.bin")).unwrap();
+        assert!(outcome.warnings[0].contains("4096 bytes"));
+        assert_eq!(sink.file("ok.txt"), Some(&b"fine"[..]));
+    }
+
+    #[test]
+    fn test_extract_without_hash_checks() {
+        let tree = crate::testing::TreeBuilder::new()
+            .file("a.txt", "alpha")
+            .file("b.txt", "beta")
+            .build();
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+
+        // A file hash that no longer matches the file, in a manifest whose own hash is updated
+        let mut cursor = std::io::Cursor::new(&archive);
+        let mut manifest = ArchiveManifest::read(&mut cursor).unwrap();
+        manifest.entries.iter_mut().find(|e| e.path == Path::new("a.txt")).unwrap().sha256 = "00".repeat(32);
+        let mut tampered = Vec::new();
+        manifest.write_with_stream(&mut &archive[cursor.position() as usize..], &mut tampered).unwrap();
+
+        let report = DirectoryCompressor::extract_to_sink(&tampered, &mut crate::testing::MemoryFs::new(), 1).unwrap();
+        assert_eq!(report.failed, 1);
+
+        let options = ExtractOptions::new().with_threads(1).with_progress(false).with_verify_hashes(false);
+        let mut sink = crate::testing::MemoryFs::new();
+        let report = DirectoryCompressor::extract_into(&tampered, &mut sink, ExtractionReport::new(Path::new(""), Path::new("")), &options).unwrap();
+        assert_eq!((report.extracted, report.failed), (2, 0));
+        assert_eq!(sink.file("a.txt"), Some(&b"alpha"[..]));
+    }
+
+    #[test]
+    fn test_rejects_manifest_not_matching_header() {
+        let tree = crate::testing::TreeBuilder::new().file("a.txt", "alpha").build();
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+        assert!(ArchiveManifest::read_checked(&mut archive.as_slice()).is_ok());
+
+        // A manifest rewritten in front of the original stream
+        let mut cursor = std::io::Cursor::new(&archive);
+        let mut manifest = ArchiveManifest::read(&mut cursor).unwrap();
+        manifest.entries[0].size = 1;
+        let mut tampered = Vec::new();
+        manifest.write(&mut tampered).unwrap();
+        tampered.extend_from_slice(&archive[cursor.position() as usize..]);
+
+        let is_mismatch = |result: Result<()>| matches!(result, Err(GlifError::ManifestHashMismatch { .. }));
+        assert!(is_mismatch(ArchiveManifest::read_checked(&mut tampered.as_slice()).map(drop)));
+        assert!(is_mismatch(DirectoryCompressor::read_archive(&tampered, 1).map(drop)));
+        assert!(is_mismatch(DirectoryCompressor::extract_to_sink(&tampered, &mut crate::testing::MemoryFs::new(), 1).map(drop)));
+        assert!(is_mismatch(crate::GlifArchive::new(std::io::Cursor::new(&tampered)).map(drop)));
+        assert!(is_mismatch(crate::archive::EntryReader::new(tampered.as_slice()).map(drop)));
+    }
 }
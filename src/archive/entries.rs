@@ -0,0 +1,301 @@
+//! Entry-by-entry reading of directory archives
+//!
+//! `EntryReader` reads a directory archive from any `Read` source (a file,
+//! pipe or socket) and yields its entries in manifest order, each with a
+//! reader over its contents. The payload is decompressed as it is read,
+//! so neither the archive nor the files have to fit in memory, unlike
+//! `DirectoryCompressor::read_archive`; unlike `GlifArchive`, the source
+//! need not be seekable.
+//!
+//! A file's contents are checked against its manifest hash as they are
+//! read: the read reaching the end of the file fails with `InvalidData` on
+//! a mismatch. Contents left unread are skipped when the next entry is
+//! requested, and the archive and payload hashes are checked after the
+//! last entry.
+
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{check_manifest_hash, ArchiveManifest, FileEntry};
+use crate::error::{GlifError, Result};
+use crate::verification::hex_encode;
+use crate::{DecompressionConfig, GlifDecoder};
+
+/// Reads the entries of a directory archive in order
+pub struct EntryReader<R: Read> {
+    manifest: ArchiveManifest,
+    stream: RefCell<PayloadStream<R>>,
+}
+
+/// The decompressed payload and how much of it has been consumed
+struct PayloadStream<R: Read> {
+    decoder: GlifDecoder<R>,
+    position: u64,
+}
+
+impl<R: Read> EntryReader<R> {
+    /// Read the manifest, header and sidecar of the directory archive in `reader`
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_config(reader, &DecompressionConfig::new().with_threads(1))
+    }
+
+    /// Read the manifest, header and sidecar with explicit decompression settings
+    pub fn with_config(mut reader: R, config: &DecompressionConfig) -> Result<Self> {
+        let (manifest, prefix) = ArchiveManifest::read_prefix(&mut reader).map_err(|e| GlifError::InvalidInput(
+            format!("Not a directory archive: {}", e)
+        ))?;
+        let decoder = GlifDecoder::with_config(reader, config)?;
+        check_manifest_hash(&prefix, decoder.header())?;
+        Ok(Self {
+            manifest,
+            stream: RefCell::new(PayloadStream { decoder, position: 0 }),
+        })
+    }
+
+    pub fn manifest(&self) -> &ArchiveManifest {
+        &self.manifest
+    }
+
+    /// Iterate over the entries; the iterator ends after checking the archive hashes
+    pub fn entries(&mut self) -> Entries<'_, R> {
+        Entries {
+            manifest: &self.manifest,
+            stream: &self.stream,
+            next: 0,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over the entries of an `EntryReader`
+pub struct Entries<'a, R: Read> {
+    manifest: &'a ArchiveManifest,
+    stream: &'a RefCell<PayloadStream<R>>,
+    next: usize,
+    done: bool,
+}
+
+impl<'a, R: Read> Entries<'a, R> {
+    fn open(&self, entry: &'a FileEntry) -> Result<Entry<'a, R>> {
+        let size = content_size(entry);
+        if size > 0 {
+            let mut stream = self.stream.borrow_mut();
+            // Entries are stored in manifest order, so a file never starts before the previous one ends
+            let skip = entry.data_offset.checked_sub(stream.position).ok_or_else(|| GlifError::Malformed(
+                format!("{} starts at payload offset {}, before the previous entry ends", entry.path.display(), entry.data_offset)
+            ))?;
+            let skipped = io::copy(&mut (&mut stream.decoder).take(skip), &mut io::sink())?;
+            stream.position += skipped;
+            if skipped < skip {
+                return Err(truncated(entry));
+            }
+        }
+
+        let hashed = entry.file_type == FileType::Regular && entry.is_hashed() && entry.external.is_none();
+        Ok(Entry {
+            entry,
+            stream: self.stream,
+            read: 0,
+            hasher: hashed.then(Sha256::new),
+        })
+    }
+
+    /// Read the rest of the payload, which checks the archive and payload hashes
+    fn finish(&self) -> Result<()> {
+        io::copy(&mut self.stream.borrow_mut().decoder, &mut io::sink())?;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read> Iterator for Entries<'a, R> {
+    type Item = Result<Entry<'a, R>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.manifest.entries.get(self.next) {
+            Some(entry) => {
+                self.next += 1;
+                self.open(entry).map(Some)
+            }
+            None => self.finish().map(|()| None),
+        };
+        match result {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// An entry and a reader over its contents (empty for directories and symlinks)
+///
+/// Must be read before the next entry is requested; reading it afterwards fails.
+pub struct Entry<'a, R: Read> {
+    entry: &'a FileEntry,
+    stream: &'a RefCell<PayloadStream<R>>,
+    read: u64,
+    hasher: Option<Sha256>,
+}
+
+impl<R: Read> Entry<'_, R> {
+    /// Metadata of the entry
+    pub fn entry(&self) -> &FileEntry {
+        self.entry
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.entry.path
+    }
+}
+
+impl<R: Read> Read for Entry<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = content_size(self.entry) - self.read;
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut stream = self.stream.borrow_mut();
+        if stream.position != self.entry.data_offset + self.read {
+            return Err(io::Error::other(format!(
+                "{} was read after a later entry was requested",
+                self.entry.path.display()
+            )));
+        }
+
+        let len = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+        let read = stream.decoder.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(truncated(self.entry).into());
+        }
+        stream.position += read as u64;
+        self.read += read as u64;
+
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        if self.read == content_size(self.entry) {
+            if let Some(hasher) = self.hasher.take() {
+                let actual = hex_encode(&hasher.finalize());
+                if actual != self.entry.sha256 {
+                    return Err(GlifError::EntryHashMismatch {
+                        path: self.entry.path.clone(),
+                        expected: self.entry.sha256.clone(),
+                        actual,
+                    }.into());
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Bytes of payload an entry occupies
+fn content_size(entry: &FileEntry) -> u64 {
+    entry.stored_size()
+}
+
+fn truncated(entry: &FileEntry) -> GlifError {
+    GlifError::Truncated(format!("Payload ends within {}", entry.path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::archive::{ArchiveWriter, DirectoryCompressor};
+    use crate::compression::MIN_CHUNK_SIZE;
+
+    fn config() -> DirectoryCompressionConfig {
+        let compression = crate::CompressionConfig::builder().chunk_size(MIN_CHUNK_SIZE).build().unwrap();
+        DirectoryCompressionConfig::new(compression).with_progress(false)
+    }
+
+    #[test]
+    fn test_entries_stream_contents() {
+        let big: Vec<u8> = (0..3 * MIN_CHUNK_SIZE).map(|i| (i % 253) as u8).collect();
+        let mut writer = ArchiveWriter::new(config()).unwrap();
+        writer.add_dir("docs").unwrap();
+        writer.add_bytes("docs/a.txt", b"alpha").unwrap();
+        writer.add_bytes("big.bin", &big).unwrap();
+        writer.add_symlink("link", "docs/a.txt").unwrap();
+        writer.add_bytes("empty", b"").unwrap();
+        writer.add_bytes("z.txt", b"zulu").unwrap();
+        let archive = writer.finalize().unwrap();
+
+        let mut reader = EntryReader::new(&archive[..]).unwrap();
+        assert_eq!(reader.manifest().entries.len(), 6);
+        let mut seen = Vec::new();
+        for entry in reader.entries() {
+            let mut entry = entry.unwrap();
+            let mut contents = Vec::new();
+            if entry.path() == Path::new("big.bin") {
+                // Read part of it; the rest is skipped
+                (&mut entry).take(1000).read_to_end(&mut contents).unwrap();
+                assert_eq!(contents, big[..1000]);
+            } else {
+                entry.read_to_end(&mut contents).unwrap();
+                seen.push((entry.path().to_str().unwrap().to_string(), contents));
+            }
+        }
+        assert_eq!(seen, [
+            ("docs".to_string(), Vec::new()),
+            ("docs/a.txt".to_string(), b"alpha".to_vec()),
+            ("link".to_string(), Vec::new()),
+            ("empty".to_string(), Vec::new()),
+            ("z.txt".to_string(), b"zulu".to_vec()),
+        ]);
+
+        // An entry can't be read once a later one was requested
+        let mut reader = EntryReader::new(&archive[..]).unwrap();
+        let mut entries = reader.entries();
+        let mut first_file = entries.nth(1).unwrap().unwrap();
+        let _big = entries.next().unwrap().unwrap();
+        let mut later = entries.next().unwrap().unwrap();
+        assert_eq!(later.read(&mut [0; 4]).unwrap(), 0);
+        assert!(first_file.read(&mut [0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_entries_detect_damage() {
+        let mut writer = ArchiveWriter::new(config()).unwrap();
+        writer.add_bytes("a.txt", b"alpha").unwrap();
+        writer.add_bytes("b.txt", b"bravo").unwrap();
+        let archive = writer.finalize().unwrap();
+        let mut manifest = ArchiveManifest::read(&mut &archive[..]).unwrap();
+        manifest.entries[1].sha256 = hex_encode(&[0; 32]);
+
+        let compressor = DirectoryCompressor::new(config()).unwrap();
+        let tampered = compressor.build_archive(&manifest, b"alphabravo").unwrap();
+        let mut reader = EntryReader::new(&tampered[..]).unwrap();
+        let mut results = Vec::new();
+        for entry in reader.entries() {
+            let mut contents = Vec::new();
+            results.push(entry.unwrap().read_to_end(&mut contents).map(|_| contents));
+        }
+        assert_eq!(results[0].as_ref().unwrap(), b"alpha");
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Damage to the compressed stream fails the iteration
+        let mut damaged = archive.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 0xFF;
+        let mut reader = EntryReader::new(&damaged[..]).unwrap();
+        assert!(reader.entries().any(|entry| entry.is_err()));
+
+        assert!(EntryReader::new(&b"not an archive"[..]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! References to file data held by other archives
+//!
+//! A regular file entry may carry an `ExternalRef` instead of contents: its
+//! data is not in this archive's payload but in a base archive, where it is
+//! found by content hash. Archives are identified by their archive hash
+//! (`archive_id`), so a reference cannot resolve to a different archive
+//! that happens to sit at the same path.
+//!
+//! Bases may themselves reference older bases. `BaseArchives` follows such
+//! chains, so an incremental archive extracts given every archive it
+//! depends on. Resolved contents are checked against the referenced hash.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::GlifArchive;
+use crate::error::{GlifError, Result};
+use crate::format::GlifHeader;
+use crate::verification::hex_encode;
+
+/// Longest chain of references followed before giving up (a cycle, most likely)
+pub const MAX_REFERENCE_DEPTH: usize = 64;
+
+/// Contents of a file stored in another archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalRef {
+    /// `archive_id` of the archive holding the contents
+    pub archive_id: String,
+
+    /// Hex SHA256 of the contents
+    pub entry_hash: String,
+}
+
+impl ExternalRef {
+    pub fn new(archive_id: String, entry_hash: String) -> Result<Self> {
+        if entry_hash.len() != 64 || !entry_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(GlifError::InvalidInput(format!("'{}' is not a hex SHA256", entry_hash)));
+        }
+        Ok(Self { archive_id, entry_hash: entry_hash.to_ascii_lowercase() })
+    }
+}
+
+/// Identifier other archives use to reference an archive: its archive hash as "sha256:<hex>"
+pub fn archive_id(header: &GlifHeader) -> String {
+    format!("sha256:{}", hex_encode(&header.archive_hash))
+}
+
+/// Base archives that references are resolved against, by `archive_id`
+#[derive(Default)]
+pub struct BaseArchives {
+    archives: HashMap<String, Base>,
+}
+
+struct Base {
+    archive: GlifArchive<File>,
+    path: PathBuf,
+    /// Path of the first regular file with each hash
+    by_hash: HashMap<String, PathBuf>,
+}
+
+impl BaseArchives {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the directory archives at `paths`
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        let mut bases = Self::new();
+        for path in paths {
+            bases.add(path)?;
+        }
+        Ok(bases)
+    }
+
+    /// Open the directory archive at `path` as a base; returns its `archive_id`
+    pub fn add(&mut self, path: &Path) -> Result<String> {
+        let archive = GlifArchive::open(path)?;
+        let id = archive_id(archive.header());
+        let by_hash = archive.entries().iter().rev()
+            .filter(|entry| entry.file_type == FileType::Regular && entry.is_hashed())
+            .map(|entry| (entry.sha256.clone(), entry.path.clone()))
+            .collect();
+        self.archives.insert(id.clone(), Base { archive, path: path.to_path_buf(), by_hash });
+        Ok(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archives.is_empty()
+    }
+
+    /// Contents a reference points to, following references in the bases
+    pub fn read(&mut self, reference: &ExternalRef) -> Result<Vec<u8>> {
+        let mut reference = reference.clone();
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            let base = self.archives.get_mut(&reference.archive_id).ok_or_else(|| Error::new(
+                ErrorKind::NotFound,
+                format!("base archive {} is needed but was not given", reference.archive_id)
+            ))?;
+            let path = base.by_hash.get(&reference.entry_hash).ok_or_else(|| GlifError::Malformed(format!(
+                "base archive {} has no file with hash {}", base.path.display(), reference.entry_hash
+            )))?;
+
+            let entry = base.archive.entry(path).expect("indexed entries exist");
+            match &entry.external {
+                Some(next) => reference = next.clone(),
+                // The base checks the contents against the entry's hash, which is the referenced one
+                None => return base.archive.read_entry(path),
+            }
+        }
+        Err(GlifError::Malformed(format!(
+            "reference to {} is more than {} archives deep", reference.entry_hash, MAX_REFERENCE_DEPTH
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{DirectoryCompressor, ExtractOptions, FileEntry};
+    use crate::testing::{archive_writer as writer, reference_to};
+
+    #[test]
+    fn test_reference_chain_extracts() {
+        let temp = tempfile::tempdir().unwrap();
+        let big = b"large unchanged file ".repeat(5000);
+
+        let full = temp.path().join("full.glif");
+        let mut base = writer();
+        base.add_bytes("big.bin", &big).unwrap();
+        base.finalize_to_file(&full).unwrap();
+
+        // Each incremental references the one before it
+        let first = temp.path().join("first.glif");
+        let mut incremental = writer();
+        let entry = FileEntry::regular(PathBuf::from("big.bin"), 0o100644, 0, 0);
+        incremental.add_external(entry.clone(), big.len() as u64, reference_to(&full, &big)).unwrap();
+        incremental.add_bytes("new.txt", b"new").unwrap();
+        incremental.finalize_to_file(&first).unwrap();
+
+        // Readers that can't resolve references refuse the incremental by its required features
+        use crate::format::features::FEATURE_DEDUP;
+        assert_eq!(GlifArchive::open(&full).unwrap().header().required_features & FEATURE_DEDUP, 0);
+        assert_ne!(GlifArchive::open(&first).unwrap().header().required_features & FEATURE_DEDUP, 0);
+
+        let second = temp.path().join("second.glif");
+        let mut incremental = writer();
+        incremental.add_external(entry, big.len() as u64, reference_to(&first, &big)).unwrap();
+        incremental.finalize_to_file(&second).unwrap();
+
+        let out = temp.path().join("out");
+        let options = ExtractOptions::new().with_progress(false).with_bases(vec![first.clone(), full.clone()]);
+        let report = DirectoryCompressor::extract_directory_with_options(&second, &out, &options).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(std::fs::read(out.join("big.bin")).unwrap(), big);
+
+        // Without the whole chain the entry fails, naming the missing base
+        let options = ExtractOptions::new().with_progress(false).with_bases(vec![first.clone()]);
+        let report = DirectoryCompressor::extract_directory_with_options(&second, temp.path().join("partial"), &options).unwrap();
+        assert!(!report.is_complete());
+
+        // Reading the entry directly points at the base instead of returning payload bytes
+        assert!(GlifArchive::open(&second).unwrap().read_entry("big.bin").is_err());
+    }
+
+    #[test]
+    fn test_reference_hash_is_validated() {
+        assert!(ExternalRef::new("sha256:00".to_string(), "not a hash".to_string()).is_err());
+        let reference = ExternalRef::new("sha256:00".to_string(), "AB".repeat(32)).unwrap();
+        assert_eq!(reference.entry_hash, "ab".repeat(32));
+    }
+}
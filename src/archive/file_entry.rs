@@ -1,10 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::{Result, Error, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use chrono::{DateTime, Utc};
 
+use crate::archive::external::ExternalRef;
+use crate::archive::salvage::ReadGap;
+use crate::archive::timestamps::restorable;
+use crate::error::{GlifError, Result};
+
+/// `sha256` of a regular file archived without hashing (`create --no-hash`)
+pub const UNHASHED: &str = "unhashed";
+
 /// Represents file type in the archive
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
@@ -48,6 +54,28 @@ pub struct FileEntry {
 
     /// SHA256 hash of the file contents (empty for directories)
     pub sha256: String,
+
+    /// Extended attributes, sorted by name (captured where the platform supports them)
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "crate::encoding::xattrs")]
+    pub xattrs: Vec<(String, Vec<u8>)>,
+
+    /// Ranges that could not be read when archiving in salvage mode; stored as zeros
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gaps: Vec<ReadGap>,
+
+    /// The base archive holding a regular file's contents, which are then not in this archive's payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<ExternalRef>,
+
+    /// MIME type of a regular file, detected from its contents when archived (see `archive::mime`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+
+    /// Compressed bytes attributed to a regular file's contents: its share of
+    /// the compressed chunks holding them (see
+    /// `ArchiveManifest::record_compressed_sizes`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compressed_size: Option<u64>,
 }
 
 impl FileEntry {
@@ -57,6 +85,25 @@ impl FileEntry {
         relative_path: PathBuf,
         data_offset: u64,
     ) -> Result<Self> {
+        let path_ref = path.as_ref();
+        let mut entry = Self::from_metadata(path_ref, relative_path)?;
+        entry.data_offset = data_offset;
+
+        // Calculate SHA256 for regular files
+        if entry.file_type == FileType::Regular {
+            let data = fs::read(path_ref)?;
+            let hash = crate::verification::calculate_sha256(&data);
+            entry.sha256 = crate::verification::hex_encode(&hash);
+            entry.mime_type = crate::archive::mime::detect(&data).map(str::to_string);
+        }
+
+        Ok(entry)
+    }
+
+    /// Create a FileEntry from a path's metadata without reading its contents
+    ///
+    /// `sha256` is left empty for regular files.
+    pub fn from_metadata<P: AsRef<Path>>(path: P, relative_path: PathBuf) -> Result<Self> {
         let path_ref = path.as_ref();
         let metadata = fs::symlink_metadata(path_ref)?;
 
@@ -75,9 +122,7 @@ impl FileEntry {
         };
 
         // Get Unix-specific metadata
-        let mode = metadata.permissions().mode();
-        let uid = metadata.uid();
-        let gid = metadata.gid();
+        let (mode, uid, gid) = crate::platform::unix_metadata(&metadata);
 
         // Convert modified time
         let mtime = metadata.modified()?;
@@ -87,14 +132,12 @@ impl FileEntry {
         let atime = metadata.accessed()?;
         let atime = DateTime::from(atime);
 
-        // Calculate SHA256 for regular files
-        let sha256 = if file_type == FileType::Regular {
-            let data = fs::read(path_ref)?;
-            let hash = crate::verification::calculate_sha256(&data);
-            crate::verification::hex_encode(&hash)
+        let mut xattrs = if file_type == FileType::Symlink {
+            Vec::new()
         } else {
-            String::new()
+            crate::platform::get_file_attributes(path_ref).unwrap_or_default()
         };
+        xattrs.sort();
 
         Ok(Self {
             path: relative_path,
@@ -106,11 +149,24 @@ impl FileEntry {
             mtime,
             atime,
             symlink_target,
-            data_offset,
-            sha256,
+            data_offset: 0,
+            sha256: String::new(),
+            xattrs,
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
         })
     }
 
+    /// Create an empty regular file entry; size and hash are set when its contents are added
+    pub fn regular(relative_path: PathBuf, mode: u32, uid: u32, gid: u32) -> Self {
+        Self {
+            file_type: FileType::Regular,
+            ..Self::directory(relative_path, mode, uid, gid)
+        }
+    }
+
     /// Create a directory entry
     pub fn directory(relative_path: PathBuf, mode: u32, uid: u32, gid: u32) -> Self {
         let now = Utc::now();
@@ -126,6 +182,11 @@ impl FileEntry {
             symlink_target: None,
             data_offset: 0,
             sha256: String::new(),
+            xattrs: Vec::new(),
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
         }
     }
 
@@ -150,6 +211,11 @@ impl FileEntry {
             symlink_target: Some(target),
             data_offset: 0,
             sha256: String::new(),
+            xattrs: Vec::new(),
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
         }
     }
 
@@ -158,19 +224,32 @@ impl FileEntry {
         let path_ref = path.as_ref();
 
         // Set permissions
-        let permissions = fs::Permissions::from_mode(self.mode);
-        fs::set_permissions(path_ref, permissions)?;
-
-        // Set modification and access times
-        let mtime = filetime::FileTime::from_unix_time(
-            self.mtime.timestamp(),
-            self.mtime.timestamp_subsec_nanos(),
-        );
-        let atime = filetime::FileTime::from_unix_time(
-            self.atime.timestamp(),
-            self.atime.timestamp_subsec_nanos(),
-        );
-        filetime::set_file_times(path_ref, atime, mtime)?;
+        crate::platform::set_mode(path_ref, self.mode)?;
+
+        self.restore_metadata_except_mode(path_ref)
+    }
+
+    /// Restore file metadata other than permissions, for filesystems
+    /// without Unix modes (FAT, exFAT, many SMB shares)
+    pub fn restore_metadata_except_mode<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path_ref = path.as_ref();
+
+        // Set modification and access times, retrying times the filesystem
+        // can't store clamped to a range it likely can
+        let set_times = |atime: DateTime<Utc>, mtime: DateTime<Utc>| {
+            let file_time = |time: DateTime<Utc>| filetime::FileTime::from_unix_time(time.timestamp(), time.timestamp_subsec_nanos());
+            filetime::set_file_times(path_ref, file_time(atime), file_time(mtime))
+        };
+        let (atime, mtime) = (restorable(self.atime), restorable(self.mtime));
+        let mut times = set_times(self.atime, self.mtime);
+        if times.is_err() && (atime != self.atime || mtime != self.mtime) {
+            times = set_times(atime, mtime);
+        }
+
+        if !self.xattrs.is_empty() {
+            crate::platform::set_file_attributes(path_ref, &self.xattrs)?;
+        }
+        times?;
 
         // Note: Setting uid/gid requires elevated privileges
         // We skip this for now but could add a --preserve-ownership flag
@@ -178,9 +257,53 @@ impl FileEntry {
         Ok(())
     }
 
+    /// This file's contents within a decompressed data blob, verified against its hash
+    pub fn data<'a>(&self, blob: &'a [u8]) -> Result<&'a [u8]> {
+        let data = self.data_unverified(blob)?;
+        self.verify_integrity(data)?;
+        Ok(data)
+    }
+
+    /// This file's contents within a decompressed data blob, without checking its hash
+    pub fn data_unverified<'a>(&self, blob: &'a [u8]) -> Result<&'a [u8]> {
+        if let Some(reference) = &self.external {
+            return Err(GlifError::InvalidInput(format!(
+                "{} is stored in base archive {}", self.path.display(), reference.archive_id
+            )));
+        }
+        let start = self.data_offset as usize;
+        start.checked_add(self.size as usize)
+            .and_then(|end| blob.get(start..end))
+            .ok_or_else(|| GlifError::Malformed(
+                format!("File data out of bounds for {}", self.path.display())
+            ))
+    }
+
+    /// Bytes of this archive's payload the entry's contents take up
+    pub fn stored_size(&self) -> u64 {
+        match self.file_type {
+            FileType::Regular if self.external.is_none() => self.size,
+            _ => 0,
+        }
+    }
+
+    /// Whether the entry has a hash to verify against (only unhashed regular files do not)
+    pub fn is_hashed(&self) -> bool {
+        self.file_type != FileType::Regular || self.sha256 != UNHASHED
+    }
+
+    /// Compressed size as a percentage of the file size, if recorded and the
+    /// file is not empty
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let compressed_size = self.compressed_size?;
+        (self.size > 0).then(|| compressed_size as f64 / self.size as f64 * 100.0)
+    }
+
     /// Validate file integrity by comparing SHA256
+    ///
+    /// Unhashed files pass; the archive's payload hash still covers their data.
     pub fn verify_integrity(&self, data: &[u8]) -> Result<()> {
-        if self.file_type != FileType::Regular {
+        if self.file_type != FileType::Regular || !self.is_hashed() {
             return Ok(());
         }
 
@@ -188,15 +311,11 @@ impl FileEntry {
         let calculated_hex = crate::verification::hex_encode(&calculated_hash);
 
         if calculated_hex != self.sha256 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                format!(
-                    "File integrity check failed for {}: expected {}, got {}",
-                    self.path.display(),
-                    self.sha256,
-                    calculated_hex
-                )
-            ));
+            return Err(GlifError::EntryHashMismatch {
+                path: self.path.clone(),
+                expected: self.sha256.clone(),
+                actual: calculated_hex,
+            });
         }
 
         Ok(())
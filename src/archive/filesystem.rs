@@ -0,0 +1,278 @@
+//! Filesystem abstraction for directory archives
+//!
+//! Archiving reads a tree through a `FileSource` and extraction writes it
+//! through a `FileSystemSink`. The disk implementations are used by the
+//! CLI; `NullSink` checks entries without writing them, and
+//! `crate::testing` provides an in-memory implementation of both.
+//!
+//! Some targets can't hold everything an archive records: FAT and exFAT
+//! have no symlinks or permissions, and SMB shares often refuse both.
+//! `UnsupportedPolicy` decides how extraction degrades on them.
+
+use std::fmt;
+use std::fs;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::archive::FileEntry;
+use crate::archive::salvage::{salvage_file, ReadGap};
+
+/// A tree of files that can be archived
+pub trait FileSource {
+    /// Paths of all entries below the root, relative to it, in archive order
+    fn paths(&self) -> Result<Vec<PathBuf>>;
+
+    /// Metadata for an entry; `sha256` may be left empty for regular files
+    fn entry(&self, path: &Path) -> Result<FileEntry>;
+
+    /// Contents of a regular file
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Reader over the contents of a regular file, for sources that can
+    /// stream files rather than read them whole
+    fn open_file(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Cursor::new(self.read_file(path)?)))
+    }
+
+    /// Contents of a regular file with unreadable ranges zero-filled and reported
+    ///
+    /// Sources that cannot fail part-way through a file read it whole.
+    fn read_file_salvaged(&self, path: &Path) -> Result<(Vec<u8>, Vec<ReadGap>)> {
+        Ok((self.read_file(path)?, Vec::new()))
+    }
+}
+
+/// A destination that archive entries are extracted into
+pub trait FileSystemSink {
+    /// Create a directory and any missing parents
+    fn create_dir(&mut self, path: &Path) -> Result<()>;
+
+    /// Write a regular file, creating missing parent directories
+    fn write_file(&mut self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Create a symbolic link, creating missing parent directories
+    fn symlink(&mut self, path: &Path, target: &Path) -> Result<()>;
+
+    /// Apply an entry's permissions, times and extended attributes
+    fn restore_metadata(&mut self, entry: &FileEntry) -> Result<()>;
+
+    /// Apply an entry's times and extended attributes, leaving its
+    /// permissions as created; sinks without permissions of their own
+    /// restore everything
+    fn restore_metadata_except_mode(&mut self, entry: &FileEntry) -> Result<()> {
+        self.restore_metadata(entry)
+    }
+}
+
+/// How extraction copes with a target that can't create symlinks or set
+/// permissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnsupportedPolicy {
+    /// Report symlinks as failed and permissions as not restored
+    #[default]
+    Fail,
+    /// Write each symlink as a text file holding its target, and skip
+    /// permissions quietly
+    Text,
+    /// Skip symlinks, reporting why, and skip permissions quietly
+    Skip,
+}
+
+impl UnsupportedPolicy {
+    pub const ALL: [UnsupportedPolicy; 3] = [UnsupportedPolicy::Fail, UnsupportedPolicy::Text, UnsupportedPolicy::Skip];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnsupportedPolicy::Fail => "fail",
+            UnsupportedPolicy::Text => "text",
+            UnsupportedPolicy::Skip => "skip",
+        }
+    }
+
+    /// Whether extraction carries on without what the target lacks
+    pub fn degrades(&self) -> bool {
+        *self != UnsupportedPolicy::Fail
+    }
+}
+
+impl fmt::Display for UnsupportedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for UnsupportedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        UnsupportedPolicy::ALL.into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("unknown unsupported-feature policy '{}' (expected fail, text or skip)", s))
+    }
+}
+
+/// Whether `error` means the target doesn't support the operation, as
+/// symlink(2) and chmod(2) report it on FAT, exFAT and SMB (EPERM,
+/// EOPNOTSUPP, ENOSYS)
+pub fn is_unsupported(error: &Error) -> bool {
+    matches!(error.kind(), ErrorKind::Unsupported | ErrorKind::PermissionDenied)
+}
+
+/// Files on disk below a root directory
+#[derive(Debug, Clone)]
+pub struct DiskSource {
+    root: PathBuf,
+    paths: Vec<PathBuf>,
+}
+
+impl DiskSource {
+    /// Source for an already collected list of paths below `root`
+    pub fn new(root: PathBuf, paths: Vec<PathBuf>) -> Self {
+        Self { root, paths }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl FileSource for DiskSource {
+    fn paths(&self) -> Result<Vec<PathBuf>> {
+        self.paths.iter()
+            .map(|path| path.strip_prefix(&self.root)
+                .map(Path::to_path_buf)
+                .map_err(std::io::Error::other))
+            .collect()
+    }
+
+    fn entry(&self, path: &Path) -> Result<FileEntry> {
+        Ok(FileEntry::from_metadata(self.root.join(path), path.to_path_buf())?)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        fs::read(self.root.join(path))
+    }
+
+    fn open_file(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(self.root.join(path))?))
+    }
+
+    fn read_file_salvaged(&self, path: &Path) -> Result<(Vec<u8>, Vec<ReadGap>)> {
+        salvage_file(&self.root.join(path))
+    }
+}
+
+/// Check that `path` is relative and doesn't leave the directory it is joined to
+fn check_entry_path(path: &Path) -> Result<()> {
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("{} escapes the output directory", path.display())));
+    }
+    Ok(())
+}
+
+/// Extracts entries below a directory on disk
+///
+/// Paths are checked like `NullSink` checks them, so nothing is written
+/// outside the directory.
+#[derive(Debug, Clone)]
+pub struct DiskSink {
+    root: PathBuf,
+}
+
+impl DiskSink {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl FileSystemSink for DiskSink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        check_entry_path(path)?;
+        fs::create_dir_all(self.root.join(path))
+    }
+
+    fn write_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        check_entry_path(path)?;
+        let target = self.root.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(target)?;
+        // Reserving the space up front reduces fragmentation; it is only an optimization
+        let _ = crate::platform::preallocate(&file, data.len() as u64);
+        file.write_all(data)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> Result<()> {
+        check_entry_path(path)?;
+        let link = self.root.join(path);
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        crate::platform::symlink(target, &link)
+    }
+
+    fn restore_metadata(&mut self, entry: &FileEntry) -> Result<()> {
+        check_entry_path(&entry.path)?;
+        Ok(entry.restore_metadata(self.root.join(&entry.path))?)
+    }
+
+    fn restore_metadata_except_mode(&mut self, entry: &FileEntry) -> Result<()> {
+        check_entry_path(&entry.path)?;
+        Ok(entry.restore_metadata_except_mode(self.root.join(&entry.path))?)
+    }
+}
+
+/// Discards extracted entries after checking that they could be written
+///
+/// Paths must stay inside the output directory, symlinks need a target
+/// and metadata must be restorable. Runs the full extraction pipeline,
+/// decompression and hash checks included, on hosts where writing the
+/// contents isn't feasible (`glifzip extract --to-null`).
+#[derive(Debug, Clone, Default)]
+pub struct NullSink {
+    bytes: u64,
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes of file contents discarded so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl FileSystemSink for NullSink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        check_entry_path(path)
+    }
+
+    fn write_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        check_entry_path(path)?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> Result<()> {
+        check_entry_path(path)?;
+        if target.as_os_str().is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "symlink target is empty"));
+        }
+        Ok(())
+    }
+
+    fn restore_metadata(&mut self, entry: &FileEntry) -> Result<()> {
+        // Permission bits plus the file type bits `from_metadata` records
+        if entry.mode & !0o177777 != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, format!("invalid mode {:o}", entry.mode)));
+        }
+        if let Some((name, _)) = entry.xattrs.iter().find(|(name, _)| name.is_empty() || name.contains('\0')) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("invalid extended attribute name {:?}", name)));
+        }
+        Ok(())
+    }
+}
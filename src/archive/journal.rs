@@ -0,0 +1,306 @@
+//! Change journals for incremental backups
+//!
+//! After a directory archive is created, a journal can record the size,
+//! modification time and hash of every regular file in it, plus where the
+//! archive lives. The next run reads the journal back: a file whose size
+//! and modification time still match is neither read from disk nor
+//! hashed, and its contents are copied out of the previous archive
+//! instead (checked against the recorded hash there). Reading the source
+//! tree therefore costs in proportion to what changed, while every archive
+//! stays self-contained.
+//!
+//! Like rsync's quick check, this trusts size and mtime: a file rewritten
+//! with its old size and mtime restored is taken from the previous archive.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::filesystem::FileSource;
+use crate::archive::salvage::ReadGap;
+use crate::archive::{ArchiveManifest, FileEntry, GlifArchive};
+use crate::error::{GlifError, Result};
+
+/// Version of the journal format
+pub const JOURNAL_VERSION: u32 = 1;
+
+/// Snapshot of the regular files in a directory archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    /// Version of the journal format
+    pub version: u32,
+
+    /// When the journal was written
+    pub created_at: DateTime<Utc>,
+
+    /// The archive the files can be copied from, as an absolute path
+    pub archive: PathBuf,
+
+    /// Every hashed, undamaged regular file stored in the archive
+    pub entries: Vec<JournalEntry>,
+}
+
+/// One regular file as it was archived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Path relative to the archived directory
+    pub path: PathBuf,
+
+    pub size: u64,
+
+    pub mtime: DateTime<Utc>,
+
+    /// Hex SHA256 of the contents
+    pub sha256: String,
+}
+
+impl Journal {
+    /// Journal of the directory archive at `archive`, read from its manifest
+    pub fn for_archive(archive: &Path) -> Result<Self> {
+        let manifest = ArchiveManifest::read_checked(&mut BufReader::new(File::open(archive)?)).map_err(|e| {
+            GlifError::InvalidInput(format!("{} is not a directory archive: {}", archive.display(), e))
+        })?;
+        Ok(Self::from_manifest(&manifest, fs::canonicalize(archive)?))
+    }
+
+    /// Journal of a manifest whose archive is at `archive`
+    pub fn from_manifest(manifest: &ArchiveManifest, archive: PathBuf) -> Self {
+        // Salvaged files were stored with zeros in place of what could not be read
+        let entries = manifest.entries.iter()
+            .filter(|entry| entry.file_type == FileType::Regular && entry.external.is_none())
+            .filter(|entry| entry.is_hashed() && entry.gaps.is_empty())
+            .map(|entry| JournalEntry {
+                path: entry.path.clone(),
+                size: entry.size,
+                mtime: entry.mtime,
+                sha256: entry.sha256.clone(),
+            })
+            .collect();
+
+        Self {
+            version: JOURNAL_VERSION,
+            created_at: Utc::now(),
+            archive,
+            entries,
+        }
+    }
+
+    /// Write the journal as JSON to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        Ok(fs::write(path, json)?)
+    }
+
+    /// Read the journal at `path`, or None if there is none yet (the first run)
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let json = match fs::read(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let journal: Self = serde_json::from_slice(&json).map_err(|e| {
+            GlifError::InvalidInput(format!("{}: invalid journal: {}", path.display(), e))
+        })?;
+        if journal.version > JOURNAL_VERSION {
+            return Err(GlifError::InvalidInput(format!(
+                "{}: journal version {} is newer than this glifzip supports ({})",
+                path.display(), journal.version, JOURNAL_VERSION
+            )));
+        }
+        Ok(Some(journal))
+    }
+}
+
+/// Files taken from the previous archive during one run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JournalStats {
+    pub unchanged_files: usize,
+    pub unchanged_bytes: u64,
+}
+
+/// A `FileSource` that takes files unchanged since a journal was written
+/// from the journal's archive instead of from `inner`
+///
+/// Metadata always comes from `inner`. A regular file is unchanged when
+/// its size and mtime match the journal and the previous archive still
+/// holds it with the journal's hash; its entry then carries that hash, so
+/// archiving does not hash it again.
+pub struct JournalSource<S> {
+    inner: S,
+    entries: HashMap<PathBuf, JournalEntry>,
+    previous: RefCell<GlifArchive<File>>,
+    unchanged: RefCell<HashSet<PathBuf>>,
+    stats: Cell<JournalStats>,
+}
+
+impl<S: FileSource> JournalSource<S> {
+    /// Read `inner` against `journal`, opening the archive it names
+    pub fn new(inner: S, journal: Journal) -> Result<Self> {
+        let previous = GlifArchive::open(&journal.archive).map_err(|e| GlifError::InvalidInput(
+            format!("Cannot open the previous archive {} named by the journal: {}", journal.archive.display(), e)
+        ))?;
+        let entries = journal.entries.into_iter().map(|entry| (entry.path.clone(), entry)).collect();
+
+        Ok(Self {
+            inner,
+            entries,
+            previous: RefCell::new(previous),
+            unchanged: RefCell::new(HashSet::new()),
+            stats: Cell::new(JournalStats::default()),
+        })
+    }
+
+    /// Files taken from the previous archive so far
+    pub fn stats(&self) -> JournalStats {
+        self.stats.get()
+    }
+
+    /// The journal's record of `entry`, if the file hasn't changed since
+    fn unchanged_record(&self, entry: &FileEntry) -> Option<&JournalEntry> {
+        let record = self.entries.get(&entry.path)?;
+        // Archives with whole-second timestamps record mtimes truncated
+        let same_mtime = record.mtime == entry.mtime || record.mtime == entry.mtime.trunc_subsecs(0);
+        if entry.file_type != FileType::Regular || record.size != entry.size || !same_mtime {
+            return None;
+        }
+        let previous = self.previous.borrow();
+        let archived = previous.entry(&record.path)?;
+        let stored = archived.file_type == FileType::Regular && archived.external.is_none();
+        (stored && archived.size == record.size && archived.sha256 == record.sha256)
+            .then_some(record)
+    }
+
+    /// Contents of an unchanged file from the previous archive, verified against its hash
+    fn read_previous(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let data = self.previous.borrow_mut().read_entry(path)?;
+        let mut stats = self.stats.get();
+        stats.unchanged_files += 1;
+        stats.unchanged_bytes += data.len() as u64;
+        self.stats.set(stats);
+        Ok(data)
+    }
+}
+
+impl<S: FileSource> FileSource for JournalSource<S> {
+    fn paths(&self) -> std::io::Result<Vec<PathBuf>> {
+        self.inner.paths()
+    }
+
+    fn entry(&self, path: &Path) -> std::io::Result<FileEntry> {
+        let mut entry = self.inner.entry(path)?;
+        let mut unchanged = self.unchanged.borrow_mut();
+        match self.unchanged_record(&entry) {
+            Some(record) => {
+                entry.sha256 = record.sha256.clone();
+                unchanged.insert(path.to_path_buf());
+            }
+            None => {
+                unchanged.remove(path);
+            }
+        }
+        Ok(entry)
+    }
+
+    fn read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        if self.unchanged.borrow().contains(path) {
+            return self.read_previous(path);
+        }
+        self.inner.read_file(path)
+    }
+
+    fn open_file(&self, path: &Path) -> std::io::Result<Box<dyn Read + '_>> {
+        if self.unchanged.borrow().contains(path) {
+            return Ok(Box::new(Cursor::new(self.read_previous(path)?)));
+        }
+        self.inner.open_file(path)
+    }
+
+    fn read_file_salvaged(&self, path: &Path) -> std::io::Result<(Vec<u8>, Vec<ReadGap>)> {
+        if self.unchanged.borrow().contains(path) {
+            return Ok((self.read_previous(path)?, Vec::new()));
+        }
+        self.inner.read_file_salvaged(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::archive::{DirectoryCompressor, DiskSource};
+
+    /// A source that fails reads of the paths in `unreadable`, to prove they are not read
+    struct Guarded {
+        inner: DiskSource,
+        unreadable: Vec<PathBuf>,
+    }
+
+    impl FileSource for Guarded {
+        fn paths(&self) -> std::io::Result<Vec<PathBuf>> {
+            self.inner.paths()
+        }
+
+        fn entry(&self, path: &Path) -> std::io::Result<FileEntry> {
+            self.inner.entry(path)
+        }
+
+        fn read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+            if self.unreadable.iter().any(|p| p == path) {
+                return Err(std::io::Error::other(format!("{} was read", path.display())));
+            }
+            self.inner.read_file(path)
+        }
+    }
+
+    #[test]
+    fn test_unchanged_files_come_from_previous_archive() {
+        let temp = tempfile::tempdir().unwrap();
+        let tree = temp.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("same.txt"), b"unchanged contents".repeat(100)).unwrap();
+        fs::write(tree.join("sub/edited.txt"), b"before").unwrap();
+
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let compressor = DirectoryCompressor::new(config).unwrap();
+        let first = temp.path().join("first.glif");
+        compressor.compress_directory(&tree, &first).unwrap();
+        let journal_path = temp.path().join("journal.json");
+        Journal::for_archive(&first).unwrap().save(&journal_path).unwrap();
+
+        fs::write(tree.join("sub/edited.txt"), b"after, and longer").unwrap();
+        fs::write(tree.join("new.txt"), b"new").unwrap();
+
+        let journal = Journal::load(&journal_path).unwrap().unwrap();
+        assert_eq!(journal.archive, fs::canonicalize(&first).unwrap());
+        assert_eq!(journal.entries.len(), 2);
+
+        let files = compressor.collect_files(&tree).unwrap();
+        let guarded = Guarded {
+            inner: DiskSource::new(tree.clone(), files.clone()),
+            unreadable: vec![PathBuf::from("same.txt")],
+        };
+        let source = JournalSource::new(guarded, journal).unwrap();
+        let (manifest, file_data) = compressor.create_manifest_from(&source, tree.clone()).unwrap();
+        assert_eq!(source.stats(), JournalStats { unchanged_files: 1, unchanged_bytes: 1800 });
+
+        // The result is what a full run produces
+        let (full, full_data) = compressor.create_manifest_from(&DiskSource::new(tree.clone(), files), tree.clone()).unwrap();
+        assert_eq!(file_data, full_data);
+        assert_eq!(manifest.tree_hash(), full.tree_hash());
+    }
+
+    #[test]
+    fn test_missing_journal_is_first_run() {
+        let temp = tempfile::tempdir().unwrap();
+        assert!(Journal::load(&temp.path().join("none.json")).unwrap().is_none());
+
+        fs::write(temp.path().join("bad.json"), b"{").unwrap();
+        assert!(Journal::load(&temp.path().join("bad.json")).is_err());
+    }
+}
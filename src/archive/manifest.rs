@@ -1,7 +1,79 @@
 use serde::{Deserialize, Serialize};
-use std::io::{Result, Error, ErrorKind, Write, Read};
+use std::fmt;
+use std::io::{Write, Read};
+use std::str::FromStr;
 use std::path::PathBuf;
+use sha2::{Digest, Sha256};
 use crate::archive::FileEntry;
+use crate::archive::file_entry::FileType;
+use crate::error::{GlifError, Result};
+use crate::encoding::hex_encode;
+use crate::format::features::FEATURE_DEDUP;
+use crate::format::{ChunkTable, GlifHeader};
+
+/// Largest manifest accepted when reading a directory archive (100 MB)
+pub const MAX_MANIFEST_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Byte starting a CBOR-encoded manifest; JSON manifests start with `{`
+/// and have no format byte
+pub const MANIFEST_FORMAT_CBOR: u8 = 0x01;
+
+/// Byte starting a zstd-compressed manifest, followed by the uncompressed
+/// and compressed sizes (big-endian u64 each) and a zstd frame holding
+/// the manifest in one of the other encodings
+pub const MANIFEST_FORMAT_ZSTD: u8 = 0x02;
+
+/// Length of a compressed manifest's format byte and sizes
+const COMPRESSED_PREFIX_SIZE: usize = 17;
+
+/// Zstd level manifests are compressed at; they are small next to the
+/// payload, so a high level costs little
+const MANIFEST_COMPRESSION_LEVEL: i32 = 9;
+
+/// How a manifest is encoded on disk
+///
+/// JSON grows to tens of megabytes for trees of hundreds of thousands of
+/// files; CBOR holds the same fields in about half the space and parses
+/// faster. Either needs glifzip 1.2.0 or later to read once compressed,
+/// which manifests are by default, so uncompressed JSON (set with
+/// `DirectoryCompressionConfig::with_compressed_manifest(false)`) is the
+/// choice for archives older versions must read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestFormat {
+    /// Pretty-printed JSON
+    #[default]
+    Json,
+    /// CBOR (RFC 8949) after the `MANIFEST_FORMAT_CBOR` byte
+    Cbor,
+}
+
+impl ManifestFormat {
+    pub const ALL: [ManifestFormat; 2] = [ManifestFormat::Json, ManifestFormat::Cbor];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "json",
+            ManifestFormat::Cbor => "cbor",
+        }
+    }
+}
+
+impl fmt::Display for ManifestFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ManifestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ManifestFormat::ALL.into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown manifest format '{}' (expected json or cbor)", s))
+    }
+}
 
 /// Manifest entry - simplified reference to a file in the archive
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +106,18 @@ pub struct ArchiveManifest {
 
     /// Base directory that was archived
     pub base_directory: PathBuf,
+
+    /// Paths left out in salvage mode because they could not be read at all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<PathBuf>,
+
+    /// Encoding the manifest is written in, and was read from
+    #[serde(skip)]
+    pub format: ManifestFormat,
+
+    /// Whether the manifest is written zstd-compressed, and was read so
+    #[serde(skip)]
+    pub compressed: bool,
 }
 
 impl ArchiveManifest {
@@ -51,6 +135,9 @@ impl ArchiveManifest {
             created_at: chrono::Utc::now().to_rfc3339(),
             creator: hostname,
             base_directory,
+            skipped: Vec::new(),
+            format: ManifestFormat::Json,
+            compressed: true,
         }
     }
 
@@ -63,49 +150,171 @@ impl ArchiveManifest {
 
     /// Serialize manifest to JSON bytes
     pub fn to_json(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec_pretty(self)
-            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        Ok(serde_json::to_vec_pretty(self)?)
     }
 
     /// Deserialize manifest from JSON bytes
     pub fn from_json(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data)
-            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    /// Serialize manifest in its `format`, compressed if `compressed`
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let data = match self.format {
+            ManifestFormat::Json => self.to_json()?,
+            ManifestFormat::Cbor => {
+                let mut data = vec![MANIFEST_FORMAT_CBOR];
+                ciborium::into_writer(self, &mut data)
+                    .map_err(|e| GlifError::InvalidInput(format!("Cannot encode manifest as CBOR: {}", e)))?;
+                data
+            }
+        };
+        if !self.compressed {
+            return Ok(data);
+        }
+
+        let frame = crate::compression::compress_zstd(&data, MANIFEST_COMPRESSION_LEVEL)?;
+        let mut compressed = Vec::with_capacity(COMPRESSED_PREFIX_SIZE + frame.len());
+        compressed.push(MANIFEST_FORMAT_ZSTD);
+        compressed.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        compressed.extend_from_slice(&(frame.len() as u64).to_be_bytes());
+        compressed.extend_from_slice(&frame);
+        Ok(compressed)
+    }
+
+    /// Deserialize manifest from bytes in any format, told apart by the first byte
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        match data.first() {
+            Some(&MANIFEST_FORMAT_ZSTD) => {
+                let (uncompressed_size, frame) = Self::compressed_frame(data)?;
+                let inner = zstd::bulk::decompress(frame, uncompressed_size).map_err(GlifError::zstd)?;
+                if inner.len() != uncompressed_size {
+                    return Err(GlifError::SizeMismatch { expected: uncompressed_size as u64, actual: inner.len() as u64 });
+                }
+                if inner.first() == Some(&MANIFEST_FORMAT_ZSTD) {
+                    return Err(GlifError::Malformed("Compressed manifest holds another compressed manifest".to_string()));
+                }
+                let mut manifest = Self::decode(&inner)?;
+                manifest.compressed = true;
+                Ok(manifest)
+            }
+            Some(&MANIFEST_FORMAT_CBOR) => {
+                let mut manifest: Self = ciborium::from_reader(&data[1..])
+                    .map_err(|e| GlifError::Malformed(format!("Invalid CBOR manifest: {}", e)))?;
+                manifest.format = ManifestFormat::Cbor;
+                Ok(manifest)
+            }
+            _ => {
+                let mut manifest = Self::from_json(data)?;
+                manifest.compressed = false;
+                Ok(manifest)
+            }
+        }
+    }
+
+    /// Uncompressed size and zstd frame of a compressed manifest, checked
+    /// against the sizes recorded before them
+    fn compressed_frame(data: &[u8]) -> Result<(usize, &[u8])> {
+        let prefix = data.get(..COMPRESSED_PREFIX_SIZE).ok_or_else(|| GlifError::Truncated(
+            "Compressed manifest ends inside its sizes".to_string()
+        ))?;
+        let uncompressed_size = u64::from_be_bytes(prefix[1..9].try_into().unwrap_or_default());
+        let compressed_size = u64::from_be_bytes(prefix[9..17].try_into().unwrap_or_default());
+        if uncompressed_size > MAX_MANIFEST_SIZE {
+            return Err(GlifError::Malformed(format!("Manifest too large: {} bytes uncompressed", uncompressed_size)));
+        }
+        let frame = &data[COMPRESSED_PREFIX_SIZE..];
+        if frame.len() as u64 != compressed_size {
+            return Err(GlifError::SizeMismatch { expected: compressed_size, actual: frame.len() as u64 });
+        }
+        Ok((uncompressed_size as usize, frame))
     }
 
     /// Write manifest to a writer
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
-        let json = self.to_json()?;
+        let data = self.encode()?;
 
         // Write manifest size as 8-byte big-endian
-        let size = json.len() as u64;
+        let size = data.len() as u64;
         writer.write_all(&size.to_be_bytes())?;
 
         // Write manifest data
-        writer.write_all(&json)?;
+        writer.write_all(&data)?;
 
         Ok(())
     }
 
     /// Read manifest from a reader
+    ///
+    /// The manifest is not checked against the hash in the header after
+    /// it; see `read_checked` and `read_prefix`.
     pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        Self::read_prefix(reader).map(|(manifest, _)| manifest)
+    }
+
+    /// Read manifest from a reader along with the bytes it was read from,
+    /// for `check_manifest_hash` once the header after it is read
+    pub fn read_prefix<R: Read>(reader: &mut R) -> Result<(Self, Vec<u8>)> {
         // Read manifest size
-        let mut size_buf = [0u8; 8];
-        reader.read_exact(&mut size_buf)?;
-        let size = u64::from_be_bytes(size_buf);
-
-        if size > 100 * 1024 * 1024 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                format!("Manifest too large: {} bytes", size)
-            ));
+        let mut prefix = vec![0u8; 8];
+        reader.read_exact(&mut prefix)?;
+        let size = u64::from_be_bytes(prefix[..8].try_into().unwrap_or_default());
+
+        if size > MAX_MANIFEST_SIZE {
+            return Err(GlifError::Malformed(format!("Manifest too large: {} bytes", size)));
         }
 
         // Read manifest data
-        let mut json = vec![0u8; size as usize];
-        reader.read_exact(&mut json)?;
+        prefix.resize(8 + size as usize, 0);
+        reader.read_exact(&mut prefix[8..])?;
+
+        let manifest = Self::decode(&prefix[8..])?;
+        Ok((manifest, prefix))
+    }
+
+    /// Read manifest from a reader and the header after it, checking the
+    /// manifest against the hash the header records
+    pub fn read_checked<R: Read>(reader: &mut R) -> Result<Self> {
+        let (manifest, prefix) = Self::read_prefix(reader)?;
+        check_manifest_hash(&prefix, &GlifHeader::read(reader)?)?;
+        Ok(manifest)
+    }
+
+    /// Write the manifest followed by the GLIF stream read from `glif`,
+    /// recording the manifest's hash in the stream's header
+    ///
+    /// Only the header is rewritten. A v1.x header has no TLV section to
+    /// hold the hash and is copied as it was. Entries referring to base
+    /// archives mark the header as requiring `FEATURE_DEDUP`, which a v1.x
+    /// header can't hold.
+    pub fn write_with_stream<R: Read, W: Write>(&self, glif: &mut R, writer: &mut W) -> Result<()> {
+        let mut manifest = Vec::new();
+        self.write(&mut manifest)?;
+        let mut header = GlifHeader::read(glif)?;
+        header.set_manifest_hash(&Sha256::digest(&manifest).into());
+        if self.has_external_entries() {
+            header.require(FEATURE_DEDUP);
+        }
 
-        Self::from_json(&json)
+        writer.write_all(&manifest)?;
+        header.write(writer)?;
+        std::io::copy(glif, writer)?;
+        Ok(())
+    }
+
+    /// Entries with ranges that could not be read when archiving in salvage mode
+    pub fn damaged_entries(&self) -> impl Iterator<Item = &FileEntry> {
+        self.entries.iter().filter(|e| !e.gaps.is_empty())
+    }
+
+    /// Whether any entry's contents are in a base archive (see `ExternalRef`)
+    pub fn has_external_entries(&self) -> bool {
+        self.entries.iter().any(|e| e.external.is_some())
+    }
+
+    /// Number of regular files archived without a hash
+    pub fn unhashed_count(&self) -> usize {
+        self.entries.iter().filter(|e| !e.is_hashed()).count()
     }
 
     /// Find an entry by path
@@ -135,6 +344,59 @@ impl ArchiveManifest {
             .collect()
     }
 
+    /// Aggregate hash of the tree's contents
+    ///
+    /// SHA256 over every entry in path order: its type, path, and file hash
+    /// or symlink target. Metadata (modes, times, owners) and the order
+    /// files were archived in are ignored, so archives of identical trees
+    /// share a tree hash.
+    pub fn tree_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for entry in self.sorted_entries() {
+            let (kind, digest): (&[u8], &[u8]) = match entry.file_type {
+                FileType::Regular => (b"f", entry.sha256.as_bytes()),
+                FileType::Directory => (b"d", b""),
+                FileType::Symlink => (b"l", entry.symlink_target.as_deref().map_or(&[][..], |t| t.as_os_str().as_encoded_bytes())),
+            };
+            hasher.update(kind);
+            hasher.update([0]);
+            hasher.update(entry.path.as_os_str().as_encoded_bytes());
+            hasher.update([0]);
+            hasher.update(digest);
+            hasher.update([0]);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Merkle content address of the tree (see `archive::content_address`)
+    pub fn content_address(&self) -> Result<[u8; 32]> {
+        crate::archive::content_address(&self.entries)
+    }
+
+    /// Number of entries of the given type
+    pub fn count(&self, file_type: FileType) -> usize {
+        self.entries.iter().filter(|e| e.file_type == file_type).count()
+    }
+
+    /// Record each regular file's share of the compressed chunks in
+    /// `FileEntry::compressed_size`
+    ///
+    /// A chunk holding several files is shared out by the compressed sizes
+    /// estimated for them when they were read (see
+    /// `compression::estimate_compressed_size`), or by their sizes where
+    /// there is no estimate.
+    pub fn record_compressed_sizes(&mut self, table: &ChunkTable) {
+        let stored = |entry: &FileEntry| entry.file_type == FileType::Regular && entry.external.is_none();
+        let ranges: Vec<_> = self.entries.iter()
+            .filter(|entry| stored(entry))
+            .map(|entry| (entry.data_offset, entry.size, entry.compressed_size.unwrap_or(entry.size)))
+            .collect();
+        let shares = table.compressed_shares(&ranges);
+        for (entry, share) in self.entries.iter_mut().filter(|entry| stored(entry)).zip(shares) {
+            entry.compressed_size = Some(share);
+        }
+    }
+
     /// Calculate compression ratio
     pub fn compression_ratio(&self, compressed_size: u64) -> f64 {
         if self.total_size == 0 {
@@ -144,6 +406,19 @@ impl ArchiveManifest {
     }
 }
 
+/// Check `prefix`, the bytes of a directory archive before its header,
+/// against the manifest hash `header` records
+///
+/// Archives written before glifzip 1.2.0 record no hash and pass.
+pub fn check_manifest_hash(prefix: &[u8], header: &GlifHeader) -> Result<()> {
+    let Some(expected) = header.manifest_hash()? else { return Ok(()) };
+    let actual: [u8; 32] = Sha256::digest(prefix).into();
+    if actual != expected {
+        return Err(GlifError::ManifestHashMismatch { expected: hex_encode(&expected), actual: hex_encode(&actual) });
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,6 +472,69 @@ mod tests {
         assert_eq!(read_manifest.base_directory, manifest.base_directory);
     }
 
+    #[test]
+    fn test_cbor_manifest() {
+        let mut manifest = ArchiveManifest::new(PathBuf::from("/test"));
+        for i in 0..200 {
+            manifest.add_entry(FileEntry::directory(PathBuf::from(format!("dir{}", i)), 0o755, 1000, 1000));
+        }
+        manifest.add_entry(FileEntry::symlink(PathBuf::from("latest"), PathBuf::from("dir0"), 0o777, 1000, 1000));
+        manifest.compressed = false;
+        let json = manifest.encode().unwrap();
+        assert_eq!(json[0], b'{');
+
+        manifest.format = ManifestFormat::Cbor;
+        let mut buffer = Vec::new();
+        manifest.write(&mut buffer).unwrap();
+        assert_eq!(buffer[8], MANIFEST_FORMAT_CBOR);
+        assert!(buffer.len() < json.len() * 2 / 3);
+
+        let read_manifest = ArchiveManifest::read(&mut std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(read_manifest.format, ManifestFormat::Cbor);
+        assert_eq!(read_manifest.entries.len(), 201);
+        assert_eq!(read_manifest.tree_hash(), manifest.tree_hash());
+        assert_eq!(ArchiveManifest::decode(&json).unwrap().format, ManifestFormat::Json);
+
+        assert!(matches!(ArchiveManifest::decode(&buffer[8..buffer.len() - 1]), Err(GlifError::Malformed(_))));
+        assert_eq!("cbor".parse(), Ok(ManifestFormat::Cbor));
+        assert!("msgpack".parse::<ManifestFormat>().is_err());
+    }
+
+    #[test]
+    fn test_compressed_manifest() {
+        let mut manifest = ArchiveManifest::new(PathBuf::from("/test"));
+        for i in 0..200 {
+            manifest.add_entry(FileEntry::directory(PathBuf::from(format!("dir{}", i)), 0o755, 1000, 1000));
+        }
+        let compressed = manifest.encode().unwrap();
+        assert_eq!(compressed[0], MANIFEST_FORMAT_ZSTD);
+        manifest.compressed = false;
+        let json = manifest.encode().unwrap();
+        assert_eq!(u64::from_be_bytes(compressed[1..9].try_into().unwrap()), json.len() as u64);
+        assert_eq!(u64::from_be_bytes(compressed[9..17].try_into().unwrap()), compressed.len() as u64 - 17);
+        assert!(compressed.len() < json.len() / 10);
+
+        // Compression is kept through a read and applies to either encoding
+        let read_manifest = ArchiveManifest::decode(&compressed).unwrap();
+        assert!(read_manifest.compressed);
+        assert_eq!(read_manifest.format, ManifestFormat::Json);
+        assert_eq!(read_manifest.tree_hash(), manifest.tree_hash());
+        assert!(!ArchiveManifest::decode(&json).unwrap().compressed);
+        manifest.compressed = true;
+        manifest.format = ManifestFormat::Cbor;
+        let cbor = ArchiveManifest::decode(&manifest.encode().unwrap()).unwrap();
+        assert!(cbor.compressed && cbor.format == ManifestFormat::Cbor);
+
+        // Sizes that don't match the data, or claim more than a manifest may hold, are refused
+        assert!(matches!(ArchiveManifest::decode(&compressed[..compressed.len() - 1]), Err(GlifError::SizeMismatch { .. })));
+        assert!(matches!(ArchiveManifest::decode(&compressed[..10]), Err(GlifError::Truncated(_))));
+        let mut inflated = compressed.clone();
+        inflated[1..9].copy_from_slice(&(MAX_MANIFEST_SIZE + 1).to_be_bytes());
+        assert!(matches!(ArchiveManifest::decode(&inflated), Err(GlifError::Malformed(_))));
+        inflated[1..9].copy_from_slice(&(json.len() as u64 - 1).to_be_bytes());
+        assert!(ArchiveManifest::decode(&inflated).is_err());
+    }
+
     #[test]
     fn test_manifest_find_entry() {
         let mut manifest = ArchiveManifest::new(PathBuf::from("/test"));
@@ -218,4 +556,20 @@ mod tests {
         let ratio = manifest.compression_ratio(500);
         assert_eq!(ratio, 50.0);
     }
+
+    #[test]
+    fn test_tree_hash_ignores_order_and_metadata() {
+        let mut first = ArchiveManifest::new(PathBuf::from("/a"));
+        first.add_entry(FileEntry::directory(PathBuf::from("src"), 0o755, 1000, 1000));
+        first.add_entry(FileEntry::directory(PathBuf::from("docs"), 0o755, 1000, 1000));
+
+        let mut second = ArchiveManifest::new(PathBuf::from("/b"));
+        second.add_entry(FileEntry::directory(PathBuf::from("docs"), 0o700, 0, 0));
+        second.add_entry(FileEntry::directory(PathBuf::from("src"), 0o700, 0, 0));
+        assert_eq!(first.tree_hash(), second.tree_hash());
+        assert_eq!(first.count(FileType::Directory), 2);
+
+        second.add_entry(FileEntry::symlink(PathBuf::from("latest"), PathBuf::from("src"), 0o777, 1000, 1000));
+        assert_ne!(first.tree_hash(), second.tree_hash());
+    }
 }
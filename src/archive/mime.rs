@@ -0,0 +1,154 @@
+//! File type detection
+//!
+//! Regular files are typed by their first bytes when archived, and the
+//! MIME type is recorded in `FileEntry::mime_type` for listings and type
+//! filters on extraction. Binary formats are recognized by their magic
+//! numbers; anything else that decodes as UTF-8 without control
+//! characters is text.
+
+/// Bytes from the start of a file that detection looks at
+pub const SNIFF_BYTES: usize = 512;
+
+/// MIME type of files that are neither a known format nor text
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Magic numbers: offset, bytes and MIME type
+const MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (0, b"icns", "image/icns"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"%!PS", "application/postscript"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (257, b"ustar", "application/x-tar"),
+    (0, crate::format::MAGIC_NUMBER, "application/x-glif"),
+    (0, b"\x7fELF", "application/x-elf"),
+    (0, b"\xfe\xed\xfa\xce", "application/x-mach-binary"),
+    (0, b"\xfe\xed\xfa\xcf", "application/x-mach-binary"),
+    (0, b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\x00asm", "application/wasm"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"OggS", "audio/ogg"),
+];
+
+/// MIME type of a file starting with `head`, or None for an empty file
+pub fn detect(head: &[u8]) -> Option<&'static str> {
+    if head.is_empty() {
+        return None;
+    }
+
+    let head = &head[..head.len().min(SNIFF_BYTES)];
+    if let Some(&(_, _, mime)) = MAGIC.iter().find(|(offset, magic, _)| head.get(*offset..).is_some_and(|rest| rest.starts_with(magic))) {
+        return Some(mime);
+    }
+
+    if head.starts_with(b"RIFF") && head.len() >= 12 {
+        match &head[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    if head.get(4..8) == Some(b"ftyp") {
+        return Some(match head.get(8..12) {
+            Some(b"qt  ") => "video/quicktime",
+            Some(b"heic" | b"heix" | b"mif1") => "image/heic",
+            Some(b"M4A ") => "audio/mp4",
+            _ => "video/mp4",
+        });
+    }
+
+    let Some(text) = as_text(head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head)) else {
+        return Some(OCTET_STREAM);
+    };
+    let start = text.trim_start().chars().take(16).collect::<String>().to_ascii_lowercase();
+    Some(if start.starts_with("<svg") || (start.starts_with("<?xml") && text.contains("<svg")) {
+        "image/svg+xml"
+    } else if start.starts_with("<?xml") {
+        "application/xml"
+    } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "text/html"
+    } else {
+        "text/plain"
+    })
+}
+
+/// `head`, the start of a file, as text if it is UTF-8 (possibly cut
+/// mid-character at the end) without control characters other than tabs,
+/// line breaks and form feeds
+pub fn as_text(head: &[u8]) -> Option<&str> {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c')) {
+        return None;
+    }
+    Some(text)
+}
+
+/// Whether `mime` matches `pattern`: a MIME type, `type/*` or `*`
+pub fn matches(mime: &str, pattern: &str) -> bool {
+    if pattern == "*" || pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split('/').next().is_some_and(|prefix| prefix.eq_ignore_ascii_case(kind)),
+        None => mime.eq_ignore_ascii_case(pattern),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let mut tar = vec![0u8; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        let cases: [(&[u8], Option<&str>); 11] = [
+            (b"", None),
+            (b"\x89PNG\r\n\x1a\n\x00\x00", Some("image/png")),
+            (b"RIFF\x00\x00\x00\x00WEBPVP8 ", Some("image/webp")),
+            (b"\x00\x00\x00\x18ftypisom", Some("video/mp4")),
+            (&tar, Some("application/x-tar")),
+            (b"\x7fELF\x02\x01", Some("application/x-elf")),
+            (b"port = 8080\n", Some("text/plain")),
+            ("\u{feff}caf\u{e9}".as_bytes(), Some("text/plain")),
+            (b"  <!DOCTYPE html><html>", Some("text/html")),
+            (b"<?xml version=\"1.0\"?>\n<svg xmlns=", Some("image/svg+xml")),
+            (b"\x00\x01\x02\x03", Some(OCTET_STREAM)),
+        ];
+        for (head, expected) in cases {
+            assert_eq!(detect(head), expected, "{:?}", head);
+        }
+
+        // Text cut mid-character is still text
+        assert_eq!(detect(&"é".repeat(SNIFF_BYTES).into_bytes()), Some("text/plain"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("image/png", "image/*"));
+        assert!(matches("image/png", "IMAGE/PNG"));
+        assert!(matches("text/plain", "*"));
+        assert!(!matches("text/plain", "image/*"));
+        assert!(!matches("image/png", "image/jpeg"));
+        assert!(!matches("imagery/png", "image/*"));
+    }
+}
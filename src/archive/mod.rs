@@ -2,8 +2,37 @@ pub mod manifest;
 pub mod file_entry;
 pub mod directory_compressor;
 pub mod apple_metadata;
+pub mod report;
+pub mod filesystem;
+pub mod content_address;
+pub mod salvage;
+pub mod backfill;
+pub mod reader;
+pub mod writer;
+pub mod diff;
+pub mod entries;
+pub mod journal;
+pub mod external;
+pub mod chain;
+pub mod mime;
+pub mod timestamps;
+pub mod sanitize;
 
-pub use manifest::{ArchiveManifest, ManifestEntry};
+pub use manifest::{check_manifest_hash, ArchiveManifest, ManifestEntry, ManifestFormat, MANIFEST_FORMAT_CBOR, MANIFEST_FORMAT_ZSTD, MAX_MANIFEST_SIZE};
 pub use file_entry::FileEntry;
-pub use directory_compressor::DirectoryCompressor;
+pub use directory_compressor::{DirectoryCompressor, ExtractOptions};
 pub use apple_metadata::AppleMetadata;
+pub use report::{EntryOutcome, EntryStatus, ExtractionReport};
+pub use filesystem::{DiskSink, DiskSource, FileSource, FileSystemSink, NullSink, UnsupportedPolicy};
+pub use salvage::{ReadGap, salvage_file};
+pub use backfill::{backfill_hashes, backfill_file};
+pub use reader::GlifArchive;
+pub use writer::ArchiveWriter;
+pub use diff::{diff_manifests, ManifestDiff};
+pub use entries::{Entries, Entry, EntryReader};
+pub use external::{archive_id, BaseArchives, ExternalRef};
+pub use chain::{flatten_chain, verify_chain, ChainLink, ChainProblem, ChainReport};
+pub use journal::{Journal, JournalEntry, JournalSource};
+pub use content_address::{content_address, content_address_of, format_content_address};
+pub use timestamps::TimestampPolicy;
+pub use sanitize::NamePolicy;
@@ -0,0 +1,357 @@
+//! Random access to directory archives
+//!
+//! `GlifArchive` parses the manifest once and reads the GLIF stream after
+//! it through a `RangeDecoder`, so a single entry is read by decompressing
+//! only the chunks its data lies in (see `stream::range`).
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{check_manifest_hash, mime, ArchiveManifest, FileEntry};
+use crate::error::{GlifError, Result};
+use crate::format::{GlifHeader, GlifSidecar};
+use crate::stream::range::{self, RangeDecoder};
+
+/// Largest file `GlifArchive::preview_entry` previews
+pub const MAX_PREVIEW_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Bytes read from the start of a file to preview it
+pub const PREVIEW_BYTES: u64 = 256;
+
+/// Characters of a preview line kept before it is cut short
+pub const PREVIEW_LINE_CHARS: usize = 80;
+
+/// The first non-blank line of `head`, the start of a file, if it is text
+/// (see `mime::as_text`)
+pub fn text_preview(head: &[u8]) -> Option<String> {
+    let line = mime::as_text(head)?.lines().map(str::trim_end).find(|line| !line.is_empty())?;
+    let mut preview: String = line.chars().take(PREVIEW_LINE_CHARS).collect();
+    if preview.len() < line.len() {
+        preview.push_str("...");
+    }
+    Some(preview)
+}
+
+/// A directory archive opened for reading individual entries
+pub struct GlifArchive<R> {
+    manifest: ArchiveManifest,
+    /// Index of the first entry with each path
+    index: HashMap<PathBuf, usize>,
+    /// The GLIF stream after the manifest
+    payload: RangeDecoder<R>,
+}
+
+impl GlifArchive<File> {
+    /// Open a directory archive file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(range::open_unarmored(path.as_ref())?)
+    }
+}
+
+impl<R: Read + Seek> GlifArchive<R> {
+    /// Parse the manifest, header and sidecar of the directory archive in
+    /// `source` and index its compressed chunks
+    pub fn new(mut source: R) -> Result<Self> {
+        let (manifest, prefix) = ArchiveManifest::read_prefix(&mut source).map_err(|e| GlifError::InvalidInput(
+            format!("Not a directory archive: {}", e)
+        ))?;
+        let payload = RangeDecoder::new(source)?;
+        check_manifest_hash(&prefix, payload.header())?;
+
+        let mut index = HashMap::with_capacity(manifest.entries.len());
+        for (position, entry) in manifest.entries.iter().enumerate() {
+            index.entry(entry.path.clone()).or_insert(position);
+        }
+
+        Ok(Self { manifest, index, payload })
+    }
+
+    pub fn manifest(&self) -> &ArchiveManifest {
+        &self.manifest
+    }
+
+    pub fn header(&self) -> &GlifHeader {
+        self.payload.header()
+    }
+
+    pub fn sidecar(&self) -> &GlifSidecar {
+        self.payload.sidecar()
+    }
+
+    /// All entries, in archive order
+    pub fn entries(&self) -> &[FileEntry] {
+        &self.manifest.entries
+    }
+
+    /// The entry at `path`, relative to the archived directory
+    pub fn entry<P: AsRef<Path>>(&self, path: P) -> Option<&FileEntry> {
+        self.index.get(path.as_ref()).map(|&position| &self.manifest.entries[position])
+    }
+
+    /// Contents of the regular file at `path`, verified against its hash
+    pub fn read_entry<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>> {
+        let entry = self.stored_entry(path.as_ref())?;
+        let data = self.read_payload(entry.data_offset, entry.size)?;
+        entry.verify_integrity(&data)?;
+        Ok(data)
+    }
+
+    /// Up to the first `len` bytes of the regular file at `path`
+    ///
+    /// Only the chunks holding them are decompressed. The entry's hash
+    /// covers the whole file, so unlike `read_entry` they are not verified.
+    pub fn read_entry_head<P: AsRef<Path>>(&mut self, path: P, len: u64) -> Result<Vec<u8>> {
+        let entry = self.stored_entry(path.as_ref())?;
+        self.read_payload(entry.data_offset, entry.size.min(len))
+    }
+
+    /// `len` bytes of the regular file at `path`, starting `offset` bytes in
+    ///
+    /// Only the chunks holding them are decompressed, and like
+    /// `read_entry_head` they are not verified against the entry's hash.
+    pub fn read_entry_range<P: AsRef<Path>>(&mut self, path: P, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let entry = self.stored_entry(path.as_ref())?;
+        if offset.checked_add(len).is_none_or(|end| end > entry.size) {
+            return Err(GlifError::InvalidInput(format!(
+                "Range {}+{} is outside {} ({} bytes)", offset, len, entry.path.display(), entry.size
+            )));
+        }
+        self.read_payload(entry.data_offset + offset, len)
+    }
+
+    /// `len` bytes of the payload, the concatenated contents of the
+    /// archived files, starting at `offset`
+    ///
+    /// Only the chunks holding them are decompressed; see
+    /// `RangeDecoder::read_range`.
+    pub fn read_range(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.payload.read_range(offset, len)
+    }
+
+    /// First line of the regular file at `path`, if it is a small text file
+    ///
+    /// Files over `MAX_PREVIEW_FILE_SIZE` and those stored in base archives
+    /// are not previewed.
+    pub fn preview_entry<P: AsRef<Path>>(&mut self, path: P) -> Result<Option<String>> {
+        let entry = self.regular_entry(path.as_ref())?;
+        if entry.external.is_some() || entry.size > MAX_PREVIEW_FILE_SIZE {
+            return Ok(None);
+        }
+        Ok(text_preview(&self.read_entry_head(path, PREVIEW_BYTES)?))
+    }
+
+    /// Extract the entry at `path` to `destination`, restoring its metadata
+    pub fn extract_entry<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, path: P, destination: Q) -> Result<()> {
+        let entry = self.find(path.as_ref())?.clone();
+        let destination = destination.as_ref();
+        if let Some(parent) = destination.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        match entry.file_type {
+            FileType::Directory => fs::create_dir_all(destination)?,
+            FileType::Symlink => {
+                let target = entry.symlink_target.as_ref().ok_or_else(|| GlifError::Malformed(
+                    format!("Symlink {} has no target", entry.path.display())
+                ))?;
+                return Ok(crate::platform::symlink(target, destination)?);
+            }
+            FileType::Regular => {
+                let data = self.read_entry(&entry.path)?;
+                let mut file = File::create(destination)?;
+                let _ = crate::platform::preallocate(&file, data.len() as u64);
+                std::io::Write::write_all(&mut file, &data)?;
+            }
+        }
+
+        entry.restore_metadata(destination)
+    }
+
+    fn find(&self, path: &Path) -> Result<&FileEntry> {
+        self.entry(path).ok_or_else(|| Error::new(
+            ErrorKind::NotFound,
+            format!("{} is not in the archive", path.display())
+        ).into())
+    }
+
+    /// The regular file at `path`, if its data is in this archive
+    fn stored_entry(&self, path: &Path) -> Result<FileEntry> {
+        let entry = self.regular_entry(path)?;
+        if let Some(reference) = &entry.external {
+            return Err(GlifError::InvalidInput(format!(
+                "{} is stored in base archive {}; read it through BaseArchives", entry.path.display(), reference.archive_id
+            )));
+        }
+        Ok(entry.clone())
+    }
+
+    fn regular_entry(&self, path: &Path) -> Result<&FileEntry> {
+        let entry = self.find(path)?;
+        if entry.file_type != FileType::Regular {
+            return Err(GlifError::InvalidInput(format!("{} is not a regular file", path.display())));
+        }
+        Ok(entry)
+    }
+
+    /// Read `len` payload bytes starting at `offset`
+    fn read_payload(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let payload_size = self.header().payload_size;
+        if offset.checked_add(len).is_none_or(|end| end > payload_size) {
+            return Err(GlifError::Malformed(
+                format!("File data at {}+{} is outside the {}-byte payload", offset, len, payload_size)
+            ));
+        }
+        self.payload.read_range(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::archive::DirectoryCompressor;
+    use crate::compression::{self, framing};
+    use crate::stream::range::Layout;
+    use crate::CompressionConfig;
+
+    fn source_tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("docs/empty")).unwrap();
+        fs::write(dir.path().join("a.txt"), b"first file").unwrap();
+        fs::write(dir.path().join("docs/b.bin"), (0..20_000u32).map(|i| (i % 241) as u8).collect::<Vec<_>>()).unwrap();
+        fs::write(dir.path().join("docs/c.txt"), b"third file").unwrap();
+        std::os::unix::fs::symlink("a.txt", dir.path().join("link")).unwrap();
+        dir
+    }
+
+    /// A directory archive whose payload is framed in `chunk_size` chunks
+    fn chunked_archive(dir: &Path, chunk_size: usize, use_lz4: bool) -> (ArchiveManifest, Vec<u8>, Vec<u8>) {
+        let compressor = DirectoryCompressor::new(DirectoryCompressionConfig::default()).unwrap();
+        let (manifest, file_data) = compressor.create_manifest(dir).unwrap();
+
+        let compressed = framing::compress_chunked(&file_data, chunk_size, 1, |chunk| compression::compress_zstd(chunk, 3)).unwrap();
+        let config = CompressionConfig::builder().threads(1).use_lz4_decompression(use_lz4).build().unwrap();
+        let hash = crate::calculate_sha256(&file_data);
+        let (stream, _) = crate::assemble_archive(file_data.len() as u64, hash, compressed, &config, 1, |_| {}).unwrap();
+
+        let mut archive = Vec::new();
+        manifest.write(&mut archive).unwrap();
+        archive.extend_from_slice(&stream);
+        (manifest, file_data, archive)
+    }
+
+    #[test]
+    fn test_read_entries_across_chunks() {
+        let dir = source_tree();
+
+        for use_lz4 in [false, true] {
+            let (manifest, file_data, archive) = chunked_archive(dir.path(), 1000, use_lz4);
+
+            let mut reader = GlifArchive::new(Cursor::new(archive)).unwrap();
+            assert_eq!(reader.entries().len(), manifest.entries.len());
+            match &reader.payload.layout {
+                Layout::Zstd(zstd) | Layout::Lz4 { zstd, .. } => assert!(zstd.chunks.len() > 20),
+                Layout::Whole(_) => panic!("framed archive was not indexed"),
+            }
+
+            for entry in manifest.entries.iter().filter(|e| e.file_type == FileType::Regular) {
+                assert_eq!(reader.read_entry(&entry.path).unwrap(), entry.data(&file_data).unwrap());
+            }
+
+            assert_eq!(reader.read_entry("missing").unwrap_err().kind(), ErrorKind::NotFound);
+            assert_eq!(reader.read_entry("docs").unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_open_and_extract_entries() {
+        let dir = source_tree();
+        let archive_path = dir.path().join("../reader_test.glif");
+        let compressor = DirectoryCompressor::new(DirectoryCompressionConfig::default()).unwrap();
+        compressor.compress_directory(dir.path(), &archive_path).unwrap();
+
+        let mut reader = GlifArchive::open(&archive_path).unwrap();
+        assert_eq!(reader.read_entry("docs/c.txt").unwrap(), b"third file");
+
+        let out = TempDir::new().unwrap();
+        reader.extract_entry("docs/b.bin", out.path().join("nested/b.bin")).unwrap();
+        reader.extract_entry("docs/empty", out.path().join("empty")).unwrap();
+        reader.extract_entry("link", out.path().join("link")).unwrap();
+
+        assert_eq!(fs::read(out.path().join("nested/b.bin")).unwrap(), fs::read(dir.path().join("docs/b.bin")).unwrap());
+        assert!(out.path().join("empty").is_dir());
+        assert_eq!(fs::read_link(out.path().join("link")).unwrap(), Path::new("a.txt"));
+
+        let _ = fs::remove_file(&archive_path);
+    }
+
+    #[test]
+    fn test_entry_heads_and_previews() {
+        let dir = source_tree();
+        let (_, _, archive) = chunked_archive(dir.path(), 1000, false);
+        let mut reader = GlifArchive::new(Cursor::new(archive)).unwrap();
+
+        assert_eq!(reader.read_entry_head("a.txt", 5).unwrap(), b"first");
+        assert_eq!(reader.read_entry_head("a.txt", 100).unwrap(), b"first file");
+        assert_eq!(reader.read_entry_head("docs/b.bin", 3).unwrap(), [0, 1, 2]);
+        assert_eq!(reader.preview_entry("docs/c.txt").unwrap().as_deref(), Some("third file"));
+        assert_eq!(reader.preview_entry("docs/b.bin").unwrap(), None);
+        assert!(reader.preview_entry("docs").is_err());
+
+        assert_eq!(text_preview(b"\n \r\n  key = value\r\nmore").as_deref(), Some("  key = value"));
+        assert_eq!(text_preview("héllo".as_bytes()[..2].as_ref()).as_deref(), Some("h"));
+        assert_eq!(text_preview(&"x".repeat(100).into_bytes()).unwrap().len(), PREVIEW_LINE_CHARS + 3);
+        assert_eq!(text_preview(b"\x7fELF\x02"), None);
+        assert_eq!(text_preview(b"\xff\xfe"), None);
+        assert_eq!(text_preview(b"\n\n"), None);
+    }
+
+    #[test]
+    fn test_read_ranges() {
+        let dir = source_tree();
+        let bin = fs::read(dir.path().join("docs/b.bin")).unwrap();
+
+        for use_lz4 in [false, true] {
+            let (_, file_data, archive) = chunked_archive(dir.path(), 1000, use_lz4);
+            let mut reader = GlifArchive::new(Cursor::new(archive)).unwrap();
+
+            // Ranges spanning chunks and entries
+            for (offset, len) in [(0, 5), (990, 2020), (5, file_data.len() as u64 - 5), (file_data.len() as u64, 0)] {
+                assert_eq!(reader.read_range(offset, len).unwrap(), file_data[offset as usize..(offset + len) as usize]);
+            }
+            assert_eq!(reader.read_range(file_data.len() as u64 - 1, 2).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+            assert_eq!(reader.read_entry_range("docs/b.bin", 1500, 3000).unwrap(), bin[1500..4500]);
+            assert_eq!(reader.read_entry_range("a.txt", 6, 4).unwrap(), b"file");
+            assert_eq!(reader.read_entry_range("a.txt", 6, 5).unwrap_err().kind(), ErrorKind::InvalidInput);
+            assert_eq!(reader.read_entry_range("docs", 0, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_unframed_archives_read_whole_payload() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/v1.1.0/tree-lz4.glif");
+        let archive = fs::read(path).unwrap();
+        let (manifest, file_data) = DirectoryCompressor::read_archive(&archive, 1).unwrap();
+
+        let mut reader = GlifArchive::new(Cursor::new(archive)).unwrap();
+        assert!(matches!(reader.payload.layout, Layout::Whole(None)));
+        for entry in manifest.entries.iter().filter(|e| e.file_type == FileType::Regular) {
+            assert_eq!(reader.read_entry(&entry.path).unwrap(), entry.data(&file_data).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_truncated_archive_is_rejected() {
+        let dir = source_tree();
+        let (_, _, archive) = chunked_archive(dir.path(), 1000, true);
+        let truncated = archive[..archive.len() - 10].to_vec();
+        assert!(GlifArchive::new(Cursor::new(truncated)).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Extraction reports
+//!
+//! Records the outcome of every entry during extraction so automated
+//! pipelines can confirm that an archive was extracted completely.
+
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error::{GlifError, Result};
+
+/// Outcome of extracting a single entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Extracted,
+    Skipped,
+    Failed,
+}
+
+/// Per-entry record in an extraction report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryOutcome {
+    /// Path of the entry within the archive
+    pub path: PathBuf,
+
+    /// What happened to the entry
+    pub status: EntryStatus,
+
+    /// Where the entry was written when its name had to be rewritten
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub renamed_to: Option<PathBuf>,
+
+    /// Why the entry was skipped or failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// Non-fatal problems, e.g. metadata that could not be restored
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub warnings: Vec<String>,
+}
+
+/// Summary of an extraction run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionReport {
+    /// Archive that was extracted
+    pub archive: PathBuf,
+
+    /// Destination file or directory
+    pub output: PathBuf,
+
+    /// Number of entries extracted
+    pub extracted: usize,
+
+    /// Number of entries skipped
+    pub skipped: usize,
+
+    /// Number of entries that failed
+    pub failed: usize,
+
+    /// Number of warnings across all entries
+    pub warnings: usize,
+
+    /// Error that stopped extraction before entries were processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Outcome of every entry, in archive order
+    pub entries: Vec<EntryOutcome>,
+}
+
+impl ExtractionReport {
+    pub fn new(archive: &Path, output: &Path) -> Self {
+        Self {
+            archive: archive.to_path_buf(),
+            output: output.to_path_buf(),
+            extracted: 0,
+            skipped: 0,
+            failed: 0,
+            warnings: 0,
+            error: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Report for an extraction that failed before any entry was processed
+    pub fn failed(archive: &Path, output: &Path, error: &GlifError) -> Self {
+        let mut report = Self::new(archive, output);
+        report.error = Some(error.to_string());
+        report
+    }
+
+    /// Record an entry outcome
+    pub fn record(&mut self, outcome: EntryOutcome) {
+        match outcome.status {
+            EntryStatus::Extracted => self.extracted += 1,
+            EntryStatus::Skipped => self.skipped += 1,
+            EntryStatus::Failed => self.failed += 1,
+        }
+        self.warnings += outcome.warnings.len();
+        self.entries.push(outcome);
+    }
+
+    /// Whether every entry was extracted or deliberately skipped
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none() && self.failed == 0
+    }
+
+    /// Entries written under a rewritten name, as (path in the archive,
+    /// path written)
+    pub fn renamed(&self) -> impl Iterator<Item = (&Path, &Path)> {
+        self.entries.iter()
+            .filter_map(|e| e.renamed_to.as_deref().map(|to| (e.path.as_path(), to)))
+    }
+
+    /// Convert failures into an error, as returned by plain extraction
+    pub fn into_result(self) -> Result<()> {
+        if let Some(error) = self.error {
+            return Err(Error::other(error).into());
+        }
+
+        match self.entries.iter().find(|e| e.status == EntryStatus::Failed) {
+            Some(first) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} of {} entries failed to extract; first failure: {}: {}",
+                    self.failed,
+                    self.entries.len(),
+                    first.path.display(),
+                    first.reason.as_deref().unwrap_or("unknown error")
+                )
+            ).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the report as pretty-printed JSON
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        Ok(std::fs::write(path, json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_counts_and_result() {
+        let mut report = ExtractionReport::new(Path::new("a.glif"), Path::new("out"));
+        report.record(EntryOutcome {
+            path: PathBuf::from("ok.txt"),
+            status: EntryStatus::Extracted,
+            renamed_to: None,
+            reason: None,
+            warnings: vec!["could not restore times".to_string()],
+        });
+        assert!(report.is_complete());
+
+        report.record(EntryOutcome {
+            path: PathBuf::from("bad.txt"),
+            status: EntryStatus::Failed,
+            renamed_to: None,
+            reason: Some("integrity check failed".to_string()),
+            warnings: Vec::new(),
+        });
+
+        assert_eq!((report.extracted, report.failed, report.warnings), (1, 1, 1));
+        assert!(!report.is_complete());
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"status\":\"failed\""));
+
+        let err = report.into_result().unwrap_err();
+        assert!(err.to_string().contains("bad.txt: integrity check failed"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Salvage reads for failing disks
+//!
+//! Reads a file extent by extent, retrying a failed extent block by block,
+//! and zero-fills whatever still cannot be read. The unreadable ranges are
+//! returned as gaps so they can be recorded in the manifest instead of
+//! aborting the whole archive at the first I/O error.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes read at a time while salvaging (64 KiB)
+pub const SALVAGE_EXTENT_SIZE: usize = 64 * 1024;
+
+/// Granularity at which a failed extent is retried (4 KiB, a typical sector group)
+pub const SALVAGE_BLOCK_SIZE: usize = 4 * 1024;
+
+/// A byte range of a file that could not be read and was zero-filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadGap {
+    /// Offset of the range within the file
+    pub offset: u64,
+
+    /// Length of the range in bytes
+    pub length: u64,
+}
+
+/// Read `size` bytes from `reader`, zero-filling ranges that fail to read
+///
+/// Reading stops early at end of file, e.g. if the file shrank since its
+/// size was taken.
+pub fn salvage_read<R: Read + Seek>(reader: &mut R, size: u64) -> (Vec<u8>, Vec<ReadGap>) {
+    let mut data = Vec::with_capacity(size as usize);
+    let mut gaps: Vec<ReadGap> = Vec::new();
+
+    while (data.len() as u64) < size {
+        let offset = data.len() as u64;
+        let extent = SALVAGE_EXTENT_SIZE.min((size - offset) as usize);
+
+        let read = match read_range(reader, offset, extent) {
+            Ok(read) => read,
+            Err(_) => salvage_extent(reader, offset, extent, &mut gaps),
+        };
+        if read.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&read);
+    }
+
+    (data, gaps)
+}
+
+/// Read a file from disk, zero-filling unreadable ranges
+///
+/// Fails only if the file cannot be opened or its size cannot be read.
+pub fn salvage_file(path: &Path) -> Result<(Vec<u8>, Vec<ReadGap>)> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    Ok(salvage_read(&mut file, size))
+}
+
+/// Total bytes covered by a list of gaps
+pub fn gap_bytes(gaps: &[ReadGap]) -> u64 {
+    gaps.iter().map(|gap| gap.length).sum()
+}
+
+/// Retry a failed extent block by block, zero-filling the blocks that still fail
+fn salvage_extent<R: Read + Seek>(reader: &mut R, offset: u64, extent: usize, gaps: &mut Vec<ReadGap>) -> Vec<u8> {
+    let mut data = Vec::with_capacity(extent);
+
+    while data.len() < extent {
+        let block_offset = offset + data.len() as u64;
+        let block = SALVAGE_BLOCK_SIZE.min(extent - data.len());
+
+        match read_range(reader, block_offset, block) {
+            Ok(read) if read.is_empty() => break,
+            Ok(read) => data.extend_from_slice(&read),
+            Err(_) => {
+                data.resize(data.len() + block, 0);
+                add_gap(gaps, block_offset, block as u64);
+            }
+        }
+    }
+
+    data
+}
+
+/// Read up to `length` bytes at `offset`; shorter only at end of file
+fn read_range<R: Read + Seek>(reader: &mut R, offset: u64, length: usize) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let mut buffer = vec![0u8; length];
+    let mut filled = 0;
+    while filled < length {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    buffer.truncate(filled);
+    Ok(buffer)
+}
+
+/// Record a gap, merging it with the previous one when they touch
+fn add_gap(gaps: &mut Vec<ReadGap>, offset: u64, length: u64) {
+    match gaps.last_mut() {
+        Some(last) if last.offset + last.length == offset => last.length += length,
+        _ => gaps.push(ReadGap { offset, length }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Error};
+
+    /// Reader whose reads fail when they touch a bad range
+    struct FailingReader {
+        inner: Cursor<Vec<u8>>,
+        bad: std::ops::Range<u64>,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let start = self.inner.position();
+            let end = start + buf.len() as u64;
+            if start < self.bad.end && self.bad.start < end {
+                return Err(Error::other("Input/output error"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_salvage_read_zero_fills_bad_blocks() {
+        let original: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8 + 1).collect();
+        // Spans two blocks inside the second extent
+        let bad = 70_000..75_000u64;
+        let mut reader = FailingReader { inner: Cursor::new(original.clone()), bad: bad.clone() };
+
+        let (data, gaps) = salvage_read(&mut reader, original.len() as u64);
+        assert_eq!(data.len(), original.len());
+        assert_eq!(gaps, vec![ReadGap { offset: 69_632, length: 8_192 }]);
+        assert_eq!(gap_bytes(&gaps), 8_192);
+
+        let gap = 69_632..77_824;
+        assert!(data[gap.clone()].iter().all(|&b| b == 0));
+        assert_eq!(data[..gap.start], original[..gap.start]);
+        assert_eq!(data[gap.end..], original[gap.end..]);
+    }
+
+    #[test]
+    fn test_salvage_read_clean_and_short_files() {
+        let original = b"readable".repeat(10_000);
+        let mut reader = Cursor::new(original.clone());
+        assert_eq!(salvage_read(&mut reader, original.len() as u64), (original.clone(), Vec::new()));
+
+        // The file shrank after its size was taken
+        let mut reader = Cursor::new(original.clone());
+        let (data, gaps) = salvage_read(&mut reader, original.len() as u64 + 100_000);
+        assert_eq!(data, original);
+        assert!(gaps.is_empty());
+    }
+}
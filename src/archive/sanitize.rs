@@ -0,0 +1,145 @@
+//! Entry names Windows can't store
+//!
+//! Names that are fine on Unix can be invalid on Windows and on the FAT,
+//! exFAT and SMB filesystems that follow its rules: they may contain
+//! `< > : " \ | ? *` or control characters, end in a dot or space, or be
+//! a reserved device name such as `CON` or `nul.txt`. A `NamePolicy`
+//! decides whether extraction keeps such names, rewrites them or refuses
+//! them; rewritten names are listed in the extraction report.
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// Characters Windows doesn't allow in file names, besides control characters
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What to do with entry names Windows can't store when extracting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamePolicy {
+    /// Write them as they are
+    #[default]
+    Keep,
+    /// Replace invalid characters with `_`, drop trailing dots and spaces,
+    /// and add `_` to reserved names
+    Replace,
+    /// Fail the entry
+    Error,
+}
+
+impl NamePolicy {
+    pub const ALL: [NamePolicy; 3] = [NamePolicy::Keep, NamePolicy::Replace, NamePolicy::Error];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NamePolicy::Keep => "keep",
+            NamePolicy::Replace => "replace",
+            NamePolicy::Error => "error",
+        }
+    }
+
+    /// Where to write an entry at `path`: `Ok(None)` to write it as
+    /// archived, or the rewritten path; the error names the first invalid
+    /// component
+    pub fn apply(&self, path: &Path) -> std::result::Result<Option<PathBuf>, String> {
+        if *self == NamePolicy::Keep {
+            return Ok(None);
+        }
+        let mut sanitized = PathBuf::new();
+        let mut changed = false;
+        for component in path.components() {
+            let Component::Normal(name) = component else {
+                sanitized.push(component);
+                continue;
+            };
+            let name = name.to_string_lossy();
+            match sanitize_name(&name) {
+                Some(_) if *self == NamePolicy::Error => {
+                    return Err(format!("{:?} is not a valid Windows file name", name));
+                }
+                Some(replacement) => {
+                    sanitized.push(replacement);
+                    changed = true;
+                }
+                None => sanitized.push(name.as_ref()),
+            }
+        }
+        Ok(changed.then_some(sanitized))
+    }
+}
+
+impl fmt::Display for NamePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NamePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        NamePolicy::ALL.into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("unknown name policy '{}' (expected keep, replace or error)", s))
+    }
+}
+
+/// Whether Windows reserves `name` for a device; the part before the first
+/// dot decides, so `nul.tar.gz` is reserved too
+pub fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// A name Windows can store in place of `name`, or `None` if it already can
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let mut sanitized: String = name.chars()
+        .map(|c| if c.is_control() || INVALID_CHARS.contains(&c) { '_' } else { c })
+        .collect();
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+    if is_reserved(&sanitized) {
+        let stem_len = sanitized.find('.').unwrap_or(sanitized.len());
+        sanitized.insert(stem_len, '_');
+    }
+    (sanitized != name).then_some(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("notes.txt"), None);
+        assert_eq!(sanitize_name("C:"), Some("C_".to_string()));
+        assert_eq!(sanitize_name("what?*.txt"), Some("what__.txt".to_string()));
+        assert_eq!(sanitize_name("draft. . "), Some("draft".to_string()));
+        assert_eq!(sanitize_name("..."), Some("_".to_string()));
+        assert_eq!(sanitize_name("CON"), Some("CON_".to_string()));
+        assert_eq!(sanitize_name("nul.tar.gz"), Some("nul_.tar.gz".to_string()));
+        assert_eq!(sanitize_name("console"), None);
+        assert_eq!(sanitize_name("tab\there"), Some("tab_here".to_string()));
+    }
+
+    #[test]
+    fn test_policies() {
+        let path = Path::new("docs/aux/report: final.txt");
+        assert_eq!(NamePolicy::Keep.apply(path), Ok(None));
+        assert_eq!(NamePolicy::Replace.apply(path), Ok(Some(PathBuf::from("docs/aux_/report_ final.txt"))));
+        assert!(NamePolicy::Error.apply(path).unwrap_err().contains("\"aux\""));
+        assert_eq!(NamePolicy::Error.apply(Path::new("docs/a.txt")), Ok(None));
+
+        assert_eq!("replace".parse(), Ok(NamePolicy::Replace));
+        assert!("strip".parse::<NamePolicy>().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Normalization of file timestamps
+//!
+//! Files dated before 1970 or in the future (often far in it, such as 2106
+//! from a wrapped 32-bit clock) are usually bogus, and they break formats
+//! and filesystems with a narrower range than the manifest's. When
+//! archiving, a `TimestampPolicy` decides whether such times are kept,
+//! clamped or refused, and times can be cut to whole seconds. When
+//! extracting, times the filesystem rejects are retried clamped by
+//! `restorable`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::FileEntry;
+use crate::error::{GlifError, Result};
+
+/// Latest time extraction falls back to, the end of unsigned 32-bit Unix
+/// time (2106-02-07T06:28:15Z)
+pub const MAX_RESTORABLE_SECS: i64 = u32::MAX as i64;
+
+/// What to do with timestamps before 1970 or in the future when archiving
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampPolicy {
+    /// Record them as they are
+    #[default]
+    Keep,
+    /// Record the nearest time in range: the epoch, or the time the file was archived
+    Clamp,
+    /// Fail to archive the file
+    Error,
+}
+
+impl TimestampPolicy {
+    pub const ALL: [TimestampPolicy; 3] = [TimestampPolicy::Keep, TimestampPolicy::Clamp, TimestampPolicy::Error];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimestampPolicy::Keep => "keep",
+            TimestampPolicy::Clamp => "clamp",
+            TimestampPolicy::Error => "error",
+        }
+    }
+
+    /// `time`, checked against the range from the epoch to `now`; the
+    /// error describes a time out of range
+    pub fn apply(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> std::result::Result<DateTime<Utc>, String> {
+        let in_range = time.clamp(DateTime::UNIX_EPOCH, now);
+        match self {
+            TimestampPolicy::Keep => Ok(time),
+            TimestampPolicy::Clamp => Ok(in_range),
+            TimestampPolicy::Error if in_range == time => Ok(time),
+            TimestampPolicy::Error => Err(format!(
+                "{} is {}", time.to_rfc3339(), if time < in_range { "before 1970" } else { "in the future" }
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TimestampPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TimestampPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        TimestampPolicy::ALL.into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("unknown timestamp policy '{}' (expected keep, clamp or error)", s))
+    }
+}
+
+/// Apply `policy` to an entry's modification and access times, truncating
+/// them to whole seconds if `whole_seconds`
+pub fn normalize_entry(entry: &mut FileEntry, policy: TimestampPolicy, whole_seconds: bool) -> Result<()> {
+    let now = Utc::now();
+    for (name, time) in [("modification", &mut entry.mtime), ("access", &mut entry.atime)] {
+        *time = policy.apply(*time, now).map_err(|e| GlifError::InvalidInput(
+            format!("{}: {} time {}", entry.path.display(), name, e)
+        ))?;
+        if whole_seconds {
+            *time = time.trunc_subsecs(0);
+        }
+    }
+    Ok(())
+}
+
+/// `time` clamped to what filesystems generally store: the epoch to
+/// `MAX_RESTORABLE_SECS`
+pub fn restorable(time: DateTime<Utc>) -> DateTime<Utc> {
+    let latest = DateTime::from_timestamp(MAX_RESTORABLE_SECS, 0).unwrap_or(DateTime::<Utc>::MAX_UTC);
+    time.clamp(DateTime::UNIX_EPOCH, latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_policies() {
+        let now = Utc.with_ymd_and_hms(2026, 5, 1, 12, 0, 0).unwrap();
+        let past = Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 59).unwrap();
+        let future = Utc.with_ymd_and_hms(2106, 2, 7, 6, 28, 16).unwrap();
+        let normal = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        for time in [past, future, normal] {
+            assert_eq!(TimestampPolicy::Keep.apply(time, now), Ok(time));
+        }
+        assert_eq!(TimestampPolicy::Clamp.apply(past, now), Ok(DateTime::UNIX_EPOCH));
+        assert_eq!(TimestampPolicy::Clamp.apply(future, now), Ok(now));
+        assert_eq!(TimestampPolicy::Clamp.apply(normal, now), Ok(normal));
+        assert!(TimestampPolicy::Error.apply(past, now).unwrap_err().contains("before 1970"));
+        assert!(TimestampPolicy::Error.apply(future, now).unwrap_err().contains("in the future"));
+        assert_eq!(TimestampPolicy::Error.apply(normal, now), Ok(normal));
+
+        assert_eq!(restorable(past), DateTime::UNIX_EPOCH);
+        assert_eq!(restorable(future).timestamp(), MAX_RESTORABLE_SECS);
+        assert_eq!(restorable(normal), normal);
+
+        assert_eq!("clamp".parse(), Ok(TimestampPolicy::Clamp));
+        assert!("round".parse::<TimestampPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_normalize_entry() {
+        let mut entry = FileEntry::regular(PathBuf::from("a.txt"), 0o644, 0, 0);
+        entry.mtime = Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap();
+        entry.atime = Utc.with_ymd_and_hms(2200, 1, 1, 0, 0, 0).unwrap();
+
+        let mut clamped = entry.clone();
+        normalize_entry(&mut clamped, TimestampPolicy::Clamp, true).unwrap();
+        assert_eq!(clamped.mtime, Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        assert!(clamped.atime <= Utc::now());
+
+        let err = normalize_entry(&mut entry, TimestampPolicy::Error, false).unwrap_err();
+        assert!(err.to_string().contains("a.txt: access time"));
+    }
+}
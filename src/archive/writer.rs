@@ -0,0 +1,275 @@
+//! Programmatic construction of directory archives
+//!
+//! `ArchiveWriter` builds the same archives as `DirectoryCompressor`, but
+//! from entries added one at a time (files on disk, in-memory bytes,
+//! directories and symlinks) rather than by walking a tree. Entries keep
+//! the order they were added in.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::archive::mime;
+use crate::archive::timestamps::{self, TimestampPolicy};
+use crate::archive::file_entry::{FileType, UNHASHED};
+use crate::archive::{ArchiveManifest, DirectoryCompressor, ExternalRef, FileEntry};
+use crate::compression::estimate_compressed_size;
+use crate::error::{GlifError, Result};
+use crate::verification::{calculate_sha256, hex_encode};
+
+/// Mode of regular files added from bytes
+pub const DEFAULT_FILE_MODE: u32 = 0o100644;
+
+/// Mode of directories added with `add_dir`
+pub const DEFAULT_DIR_MODE: u32 = 0o40755;
+
+/// Mode of symlinks added with `add_symlink`
+pub const DEFAULT_SYMLINK_MODE: u32 = 0o120777;
+
+/// Builds a directory archive entry by entry
+pub struct ArchiveWriter {
+    compressor: DirectoryCompressor,
+    hash_files: bool,
+    timestamp_policy: TimestampPolicy,
+    whole_second_timestamps: bool,
+    manifest: ArchiveManifest,
+    file_data: Vec<u8>,
+    paths: HashSet<PathBuf>,
+}
+
+impl ArchiveWriter {
+    /// Start an empty archive; exclusion and walking options in `config` do not apply
+    pub fn new(config: DirectoryCompressionConfig) -> Result<Self> {
+        let (hash_files, timestamp_policy, whole_second_timestamps) = (config.hash_files, config.timestamp_policy, config.whole_second_timestamps);
+        Ok(Self {
+            compressor: DirectoryCompressor::new(config)?,
+            hash_files,
+            timestamp_policy,
+            whole_second_timestamps,
+            manifest: ArchiveManifest::new(PathBuf::new()),
+            file_data: Vec::new(),
+            paths: HashSet::new(),
+        })
+    }
+
+    /// Record `base_directory` in the manifest as the archived directory
+    pub fn with_base_directory(mut self, base_directory: PathBuf) -> Self {
+        self.manifest.base_directory = base_directory;
+        self
+    }
+
+    /// Add the file at `source` as `path`, with its metadata
+    ///
+    /// A directory or symlink at `source` is added as such; directories
+    /// are not recursed into.
+    pub fn add_file<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, path: P, source: Q) -> Result<()> {
+        let source = source.as_ref();
+        let entry = FileEntry::from_metadata(source, path.as_ref().to_path_buf())?;
+        let data = match entry.file_type {
+            FileType::Regular => fs::read(source)?,
+            _ => Vec::new(),
+        };
+        self.add_entry(entry, &data)
+    }
+
+    /// Add a regular file holding `data` as `path`
+    pub fn add_bytes<P: AsRef<Path>>(&mut self, path: P, data: &[u8]) -> Result<()> {
+        self.add_entry(FileEntry::regular(path.as_ref().to_path_buf(), DEFAULT_FILE_MODE, 0, 0), data)
+    }
+
+    /// Add a directory as `path`
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.add_entry(FileEntry::directory(path.as_ref().to_path_buf(), DEFAULT_DIR_MODE, 0, 0), &[])
+    }
+
+    /// Add a symlink at `path` pointing to `target`
+    pub fn add_symlink<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, path: P, target: Q) -> Result<()> {
+        let entry = FileEntry::symlink(
+            path.as_ref().to_path_buf(),
+            target.as_ref().to_path_buf(),
+            DEFAULT_SYMLINK_MODE,
+            0,
+            0,
+        );
+        self.add_entry(entry, &[])
+    }
+
+    /// Add an entry with explicit metadata; `data` is the contents of a
+    /// regular file and must be empty for other types
+    ///
+    /// The entry's size, data offset and hash are filled in here.
+    pub fn add_entry(&mut self, mut entry: FileEntry, data: &[u8]) -> Result<()> {
+        entry.path = archive_path(&entry.path)?;
+        if entry.file_type != FileType::Regular && !data.is_empty() {
+            return Err(GlifError::InvalidInput(
+                format!("{} is not a regular file but was given contents", entry.path.display())
+            ));
+        }
+        if entry.file_type == FileType::Symlink && entry.symlink_target.is_none() {
+            return Err(GlifError::InvalidInput(format!("Symlink {} has no target", entry.path.display())));
+        }
+        timestamps::normalize_entry(&mut entry, self.timestamp_policy, self.whole_second_timestamps)?;
+        if !self.paths.insert(entry.path.clone()) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is already in the archive", entry.path.display())
+            ).into());
+        }
+
+        entry.size = data.len() as u64;
+        entry.data_offset = self.file_data.len() as u64;
+        entry.gaps = Vec::new();
+        entry.external = None;
+        entry.sha256 = match entry.file_type {
+            FileType::Regular if self.hash_files => hex_encode(&calculate_sha256(data)),
+            FileType::Regular => UNHASHED.to_string(),
+            _ => String::new(),
+        };
+        (entry.mime_type, entry.compressed_size) = match entry.file_type {
+            FileType::Regular => (mime::detect(data).map(str::to_string), Some(estimate_compressed_size(data, entry.size))),
+            _ => (None, None),
+        };
+
+        self.file_data.extend_from_slice(data);
+        self.manifest.add_entry(entry);
+        Ok(())
+    }
+
+    /// Add a regular file of `size` bytes whose contents are in the base archive `reference` names
+    ///
+    /// Extraction then needs that base (see `ExtractOptions::with_bases`).
+    /// The entry's hash is the referenced one.
+    pub fn add_external(&mut self, mut entry: FileEntry, size: u64, reference: ExternalRef) -> Result<()> {
+        if entry.file_type != FileType::Regular {
+            return Err(GlifError::InvalidInput(
+                format!("{} is not a regular file and cannot reference a base archive", entry.path.display())
+            ));
+        }
+        entry.path = archive_path(&entry.path)?;
+        if !self.paths.insert(entry.path.clone()) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is already in the archive", entry.path.display())
+            ).into());
+        }
+
+        entry.size = size;
+        entry.data_offset = self.file_data.len() as u64;
+        entry.gaps = Vec::new();
+        entry.sha256 = reference.entry_hash.clone();
+        entry.external = Some(reference);
+        self.manifest.add_entry(entry);
+        Ok(())
+    }
+
+    /// Number of entries added so far
+    pub fn len(&self) -> usize {
+        self.manifest.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifest.entries.is_empty()
+    }
+
+    /// Compress the entries into directory archive bytes
+    pub fn finalize(self) -> Result<Vec<u8>> {
+        self.compressor.build_archive(&self.manifest, &self.file_data)
+    }
+
+    /// Compress the entries and write the archive to `output_path`
+    pub fn finalize_to_file<P: AsRef<Path>>(self, output_path: P) -> Result<()> {
+        Ok(fs::write(output_path, self.finalize()?)?)
+    }
+}
+
+/// Check that an entry path stays inside the archive, dropping `.` components
+fn archive_path(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return Err(GlifError::InvalidInput(
+                format!("{} is not a relative path inside the archive", path.display())
+            )),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        return Err(GlifError::InvalidInput("Empty entry path".to_string()));
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    use crate::archive::GlifArchive;
+
+    fn writer() -> ArchiveWriter {
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        ArchiveWriter::new(config).unwrap()
+    }
+
+    #[test]
+    fn test_written_archive_extracts() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.txt");
+        fs::write(&source, b"from disk").unwrap();
+
+        let mut writer = writer();
+        writer.add_dir("config").unwrap();
+        writer.add_bytes("config/app.toml", b"name = \"glif\"\n").unwrap();
+        writer.add_file("notes/source.txt", &source).unwrap();
+        writer.add_symlink("latest", "config/app.toml").unwrap();
+        assert_eq!(writer.len(), 4);
+
+        let archive_path = temp.path().join("built.glif");
+        writer.finalize_to_file(&archive_path).unwrap();
+
+        let output = temp.path().join("out");
+        DirectoryCompressor::extract_directory(&archive_path, &output, 1, false, false).unwrap();
+        assert_eq!(fs::read(output.join("config/app.toml")).unwrap(), b"name = \"glif\"\n");
+        assert_eq!(fs::read(output.join("notes/source.txt")).unwrap(), b"from disk");
+        assert_eq!(fs::read_link(output.join("latest")).unwrap(), Path::new("config/app.toml"));
+
+        let mut reader = GlifArchive::open(&archive_path).unwrap();
+        let paths: Vec<&Path> = reader.entries().iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(paths, ["config", "config/app.toml", "notes/source.txt", "latest"].map(Path::new));
+        assert_eq!(reader.read_entry("notes/source.txt").unwrap(), b"from disk");
+    }
+
+    #[test]
+    fn test_invalid_entries_are_rejected() {
+        let mut writer = writer();
+        writer.add_bytes("./a.txt", b"a").unwrap();
+
+        assert_eq!(writer.add_bytes("a.txt", b"again").unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(writer.add_bytes("/etc/passwd", b"").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(writer.add_dir("../outside").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(writer.add_dir("").unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        let entry = FileEntry::directory(PathBuf::from("dir"), DEFAULT_DIR_MODE, 0, 0);
+        assert_eq!(writer.add_entry(entry, b"data").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(writer.len(), 1);
+    }
+
+    #[test]
+    fn test_matches_directory_compressor_manifest() {
+        let mut writer = writer();
+        writer.add_bytes("one", b"first").unwrap();
+        writer.add_bytes("two", b"second").unwrap();
+        let archive = writer.finalize().unwrap();
+
+        let (manifest, data) = DirectoryCompressor::read_archive(&archive, 1).unwrap();
+        assert_eq!(data, b"firstsecond");
+        assert_eq!(manifest.file_count, 2);
+        assert_eq!(manifest.total_size, 11);
+        assert_eq!(manifest.entries[1].data_offset, 5);
+        assert_eq!(manifest.entries[1].data(&data).unwrap(), b"second");
+    }
+}
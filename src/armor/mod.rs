@@ -0,0 +1,207 @@
+//! ASCII-armored archives
+//!
+//! Wraps small archives in PEM-style text so they survive email and chat:
+//!
+//! ```text
+//! -----BEGIN GLIF ARCHIVE-----
+//! Version: glifzip 1.2.0
+//! Size: 1234
+//!
+//! R0xJRgEB... 3fa0
+//! ...
+//! -----END GLIF ARCHIVE-----
+//! ```
+//!
+//! Each body line holds up to 48 bytes as base64 followed by a 4-digit hex
+//! checksum of the line and its position, so a mangled, dropped or
+//! reordered line is reported by number rather than as a failed archive
+//! hash. `Size` catches truncation.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::Path;
+
+use crate::format::sidecar::GLIFZIP_VERSION;
+use crate::encoding::{base64_decode, base64_encode, hex_encode};
+use crate::verification::calculate_sha256;
+
+/// First line of an armored archive
+pub const ARMOR_BEGIN: &str = "-----BEGIN GLIF ARCHIVE-----";
+
+/// Last line of an armored archive
+pub const ARMOR_END: &str = "-----END GLIF ARCHIVE-----";
+
+/// Largest archive that will be armored (1 MiB)
+pub const MAX_ARMOR_ARCHIVE_SIZE: usize = 1024 * 1024;
+
+/// Archive bytes per body line (64 base64 characters)
+pub const ARMOR_LINE_BYTES: usize = 48;
+
+/// Encode an archive as armored text
+pub fn armor(archive: &[u8]) -> Result<String> {
+    if archive.len() > MAX_ARMOR_ARCHIVE_SIZE {
+        return Err(too_large(archive.len() as u64));
+    }
+
+    let mut text = format!("{}\nVersion: glifzip {}\nSize: {}\n\n", ARMOR_BEGIN, GLIFZIP_VERSION, archive.len());
+    for (index, chunk) in archive.chunks(ARMOR_LINE_BYTES).enumerate() {
+        let line = base64_encode(chunk);
+        text.push_str(&format!("{} {}\n", line, line_checksum(index, &line)));
+    }
+    text.push_str(ARMOR_END);
+    text.push('\n');
+    Ok(text)
+}
+
+/// Decode armored text back into archive bytes
+///
+/// Text before the BEGIN line and after the END line is ignored, as are
+/// line endings and indentation added by mail and chat clients.
+pub fn dearmor(text: &str) -> Result<Vec<u8>> {
+    let mut lines = text.lines().map(str::trim).skip_while(|line| *line != ARMOR_BEGIN);
+    if lines.next().is_none() {
+        return Err(invalid(format!("missing \"{}\" line", ARMOR_BEGIN)));
+    }
+
+    let mut size = None;
+    for header in lines.by_ref().take_while(|line| !line.is_empty()) {
+        let (name, value) = header.split_once(':').ok_or_else(|| invalid(format!("malformed header \"{}\"", header)))?;
+        if name.trim().eq_ignore_ascii_case("size") {
+            let value: u64 = value.trim().parse().map_err(|_| invalid(format!("invalid size \"{}\"", value.trim())))?;
+            if value > MAX_ARMOR_ARCHIVE_SIZE as u64 {
+                return Err(too_large(value));
+            }
+            size = Some(value as usize);
+        }
+    }
+    let size = size.ok_or_else(|| invalid("missing Size header"))?;
+
+    let mut archive = Vec::with_capacity(size);
+    let mut ended = false;
+    for (index, line) in lines.by_ref().enumerate() {
+        if line == ARMOR_END {
+            ended = true;
+            break;
+        }
+
+        let (data, checksum) = line.rsplit_once(' ').unwrap_or((line, ""));
+        if checksum != line_checksum(index, data) {
+            return Err(invalid(format!("line {} of the body is corrupt or out of order", index + 1)));
+        }
+        let decoded = base64_decode(data).map_err(|e| invalid(format!("line {} of the body: {}", index + 1, e)))?;
+        if archive.len() + decoded.len() > size {
+            return Err(invalid(format!("body is longer than the {} bytes in its Size header", size)));
+        }
+        archive.extend_from_slice(&decoded);
+    }
+
+    if !ended {
+        return Err(invalid(format!("missing \"{}\" line; the text may be truncated", ARMOR_END)));
+    }
+    if archive.len() != size {
+        return Err(invalid(format!("body holds {} of {} bytes; lines may be missing", archive.len(), size)));
+    }
+    Ok(archive)
+}
+
+/// Whether `data` looks like armored text rather than a binary archive
+pub fn is_armored(data: &[u8]) -> bool {
+    std::str::from_utf8(data).is_ok_and(|text| text.lines().any(|line| line.trim() == ARMOR_BEGIN))
+}
+
+/// Read an archive file, decoding it first if it is armored
+pub fn read_archive_file(path: &Path) -> Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    // Read-ahead is only a hint; the read succeeds without it
+    let _ = crate::platform::advise_sequential_read(&file);
+    let mut data = Vec::with_capacity(file.metadata().map(|m| m.len() as usize).unwrap_or(0));
+    file.read_to_end(&mut data)?;
+    if !is_armored(&data) {
+        return Ok(data);
+    }
+    dearmor(&String::from_utf8_lossy(&data)).map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Replace an archive file with its armored form
+pub fn armor_file(path: &Path) -> Result<()> {
+    let text = armor(&fs::read(path)?)?;
+    fs::write(path, text)
+}
+
+fn line_checksum(index: usize, line: &str) -> String {
+    let mut input = (index as u64).to_be_bytes().to_vec();
+    input.extend_from_slice(line.as_bytes());
+    hex_encode(&calculate_sha256(&input)[..2])
+}
+
+fn too_large(size: u64) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Archive is {} bytes, over the {} byte limit for armored text; send the binary archive instead",
+            size,
+            MAX_ARMOR_ARCHIVE_SIZE
+        )
+    )
+}
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid armored archive: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompressionConfig;
+
+    fn sample_archive() -> Vec<u8> {
+        crate::compress(&b"armored payload ".repeat(200), &CompressionConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_armor_roundtrip() {
+        let archive = sample_archive();
+        let text = armor(&archive).unwrap();
+
+        assert!(text.starts_with(ARMOR_BEGIN));
+        assert!(text.lines().all(|line| line.len() <= 69));
+        assert!(is_armored(text.as_bytes()));
+        assert!(!is_armored(&archive));
+        assert_eq!(dearmor(&text).unwrap(), archive);
+
+        // Quoted in an email with CRLF line endings and surrounding prose
+        let emailed = format!("Hi,\r\n\r\nhere it is:\r\n\r\n{}\r\nCheers\r\n", text.replace('\n', "\r\n  "));
+        assert_eq!(dearmor(&emailed).unwrap(), archive);
+        assert_eq!(crate::decompress(&dearmor(&emailed).unwrap(), 1).unwrap(), b"armored payload ".repeat(200));
+    }
+
+    #[test]
+    fn test_dearmor_reports_damaged_lines() {
+        let text = armor(&sample_archive()).unwrap();
+        let mut lines: Vec<&str> = text.lines().collect();
+        let body = lines.iter().position(|line| line.is_empty()).unwrap() + 1;
+
+        let mut swapped = lines.clone();
+        swapped.swap(body, body + 1);
+        let err = dearmor(&swapped.join("\n")).unwrap_err();
+        assert!(err.to_string().contains("line 1 of the body"), "{}", err);
+
+        let edited = lines[body + 1].replacen(|c: char| c.is_ascii_alphabetic(), "0", 1);
+        lines[body + 1] = &edited;
+        let err = dearmor(&lines.join("\n")).unwrap_err();
+        assert!(err.to_string().contains("line 2 of the body"), "{}", err);
+
+        let truncated: String = text.lines().take(body + 2).collect::<Vec<_>>().join("\n");
+        assert!(dearmor(&truncated).unwrap_err().to_string().contains("truncated"));
+        assert!(dearmor("no armor here").is_err());
+    }
+
+    #[test]
+    fn test_size_guards() {
+        let err = armor(&vec![0u8; MAX_ARMOR_ARCHIVE_SIZE + 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let oversized = format!("{}\nSize: {}\n\n{}\n", ARMOR_BEGIN, MAX_ARMOR_ARCHIVE_SIZE + 1, ARMOR_END);
+        assert_eq!(dearmor(&oversized).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
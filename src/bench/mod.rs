@@ -0,0 +1,325 @@
+//! Benchmarks on the user's own machine
+//!
+//! Times glifzip compression and decompression of a buffer and, for
+//! comparison, the system's `gzip`, `zstd` and `zip` binaries on the same
+//! data. External tools read their input from a temporary file and write
+//! compressed output to one; decompressed output is counted and discarded
+//! so no tool pays for disk writes glifzip does not make. Tools that are
+//! not installed are skipped.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::CompressionConfig;
+
+/// Result of compressing and decompressing the input with one tool
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    /// Tool and settings, e.g. `glifzip (level 8, lz4)` or `gzip (level 6)`
+    pub tool: String,
+    pub input_bytes: u64,
+    pub compressed_bytes: u64,
+    pub compress_time: Duration,
+    pub decompress_time: Duration,
+}
+
+impl BenchResult {
+    /// `compressed_bytes / input_bytes` (lower is better)
+    pub fn ratio(&self) -> f64 {
+        if self.input_bytes > 0 {
+            self.compressed_bytes as f64 / self.input_bytes as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Input bytes compressed per second
+    pub fn compress_throughput(&self) -> f64 {
+        throughput(self.input_bytes, self.compress_time)
+    }
+
+    /// Output bytes decompressed per second
+    pub fn decompress_throughput(&self) -> f64 {
+        throughput(self.input_bytes, self.decompress_time)
+    }
+}
+
+fn throughput(bytes: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        bytes as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+/// A system compressor to compare against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalTool {
+    Gzip,
+    Zstd,
+    Zip,
+}
+
+impl ExternalTool {
+    pub const ALL: [ExternalTool; 3] = [ExternalTool::Gzip, ExternalTool::Zstd, ExternalTool::Zip];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExternalTool::Gzip => "gzip",
+            ExternalTool::Zstd => "zstd",
+            ExternalTool::Zip => "zip",
+        }
+    }
+
+    /// Default level of the tool, used for the comparison
+    pub fn default_level(&self) -> i32 {
+        match self {
+            ExternalTool::Gzip | ExternalTool::Zip => 6,
+            ExternalTool::Zstd => 3,
+        }
+    }
+
+    /// Whether the tool (and `unzip`, for zip) can be run
+    pub fn is_available(&self) -> bool {
+        let found = |program: &str| {
+            Command::new(program)
+                .arg(if program == "unzip" { "-v" } else { "--version" })
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok()
+        };
+        match self {
+            ExternalTool::Zip => found("zip") && found("unzip"),
+            tool => found(tool.name()),
+        }
+    }
+
+    fn compress_command(&self, input: &Path, output: &Path, threads: usize) -> Command {
+        let level = format!("-{}", self.default_level());
+        let mut command = Command::new(self.name());
+        match self {
+            ExternalTool::Gzip => {
+                command.args(["-c", &level]).arg(input);
+            }
+            ExternalTool::Zstd => {
+                command.args(["-c", "-q", &level, &format!("-T{}", threads)]).arg(input);
+            }
+            ExternalTool::Zip => {
+                command.args(["-q", "-j", &level]).arg(output).arg(input);
+            }
+        }
+        command
+    }
+
+    fn decompress_command(&self, compressed: &Path) -> Command {
+        let program = if *self == ExternalTool::Zip { "unzip" } else { self.name() };
+        let mut command = Command::new(program);
+        match self {
+            ExternalTool::Gzip => command.arg("-dc"),
+            ExternalTool::Zstd => command.args(["-dc", "-q"]),
+            ExternalTool::Zip => command.arg("-p"),
+        };
+        command.arg(compressed);
+        command
+    }
+
+    /// Compress and decompress the file at `input` (of `input_bytes`
+    /// bytes), keeping the compressed copy in `work_dir`
+    pub fn bench(&self, input: &Path, input_bytes: u64, threads: usize, work_dir: &Path) -> Result<BenchResult> {
+        let compressed = work_dir.join(format!("bench.{}", self.name()));
+        let _ = fs::remove_file(&compressed);
+
+        let mut command = self.compress_command(input, &compressed, threads);
+        if *self != ExternalTool::Zip {
+            command.stdout(File::create(&compressed)?);
+        }
+        let started = Instant::now();
+        run(command.stderr(Stdio::piped()), self.name())?;
+        let compress_time = started.elapsed();
+        let compressed_bytes = fs::metadata(&compressed)?.len();
+
+        let started = Instant::now();
+        let mut child = self.decompress_command(&compressed)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let decompressed = match child.stdout.take() {
+            Some(mut stdout) => io::copy(&mut stdout, &mut io::sink())?,
+            None => 0,
+        };
+        let status = child.wait()?;
+        let decompress_time = started.elapsed();
+        if !status.success() {
+            return Err(Error::other(format!("{} decompression failed ({})", self.name(), status)));
+        }
+        if decompressed != input_bytes {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} decompressed {} bytes, expected {}", self.name(), decompressed, input_bytes)
+            ));
+        }
+
+        Ok(BenchResult {
+            tool: format!("{} (level {})", self.name(), self.default_level()),
+            input_bytes,
+            compressed_bytes,
+            compress_time,
+            decompress_time,
+        })
+    }
+}
+
+fn run(command: &mut Command, name: &str) -> Result<()> {
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!(
+            "{} failed ({}): {}",
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Compress and decompress `data` in memory with glifzip
+pub fn bench_glifzip(data: &[u8], config: &CompressionConfig) -> Result<BenchResult> {
+    let started = Instant::now();
+    let archive = crate::compress(data, config)?;
+    let compress_time = started.elapsed();
+
+    let started = Instant::now();
+    let restored = crate::decompress(&archive, config.threads)?;
+    let decompress_time = started.elapsed();
+    if restored != data {
+        return Err(Error::new(ErrorKind::InvalidData, "glifzip round trip changed the data"));
+    }
+
+    let mode = if config.use_lz4_decompression { "lz4" } else { "zstd" };
+    Ok(BenchResult {
+        tool: format!("glifzip (level {}, {})", config.level, mode),
+        input_bytes: data.len() as u64,
+        compressed_bytes: archive.len() as u64,
+        compress_time,
+        decompress_time,
+    })
+}
+
+/// Benchmark glifzip and then every available external tool on `data`
+///
+/// `on_result` sees each tool as it finishes, with the error if it failed.
+/// Tools that are not installed are left out.
+pub fn compare(
+    data: &[u8],
+    config: &CompressionConfig,
+    tools: &[ExternalTool],
+    mut on_result: impl FnMut(&str, &Result<BenchResult>),
+) -> Result<Vec<BenchResult>> {
+    let mut results = Vec::new();
+    let mut record = |name: &str, result: Result<BenchResult>| {
+        on_result(name, &result);
+        if let Ok(result) = result {
+            results.push(result);
+        }
+    };
+
+    record("glifzip", bench_glifzip(data, config));
+
+    let available: Vec<ExternalTool> = tools.iter().copied().filter(ExternalTool::is_available).collect();
+    if !available.is_empty() {
+        crate::testing::with_temp_dir("bench", |dir| {
+            let input = dir.join("input");
+            fs::write(&input, data)?;
+            for tool in &available {
+                record(tool.name(), tool.bench(&input, data.len() as u64, config.threads, dir));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(results)
+}
+
+/// Render results as a table
+///
+/// The speedup columns give how many times faster the first row (glifzip
+/// in `compare`) compressed and decompressed than each tool.
+pub fn render_table(results: &[BenchResult]) -> String {
+    let mut table = format!(
+        "{:<24} {:>14} {:>8} {:>14} {:>14} {:>10} {:>10}\n",
+        "Tool", "Compressed", "Ratio", "Compress", "Decompress", "C speedup", "D speedup"
+    );
+    let baseline = results.first();
+    for result in results {
+        let relative = |speed: f64, base: Option<f64>| match base {
+            Some(base) if speed > 0.0 => format!("{:.2}x", base / speed),
+            _ => "-".to_string(),
+        };
+        let _ = writeln!(
+            table,
+            "{:<24} {:>14} {:>7.2}% {:>9.1} MB/s {:>9.1} MB/s {:>10} {:>10}",
+            result.tool,
+            result.compressed_bytes,
+            result.ratio() * 100.0,
+            result.compress_throughput() / 1e6,
+            result.decompress_throughput() / 1e6,
+            relative(result.compress_throughput(), baseline.map(BenchResult::compress_throughput)),
+            relative(result.decompress_throughput(), baseline.map(BenchResult::decompress_throughput)),
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_glifzip() {
+        let data = crate::testing::data::generate(crate::testing::data::DataKind::Code, 1 << 20, 1);
+        let result = bench_glifzip(&data, &CompressionConfig::new(3, 2)).unwrap();
+        assert_eq!(result.tool, "glifzip (level 3, lz4)");
+        assert_eq!(result.input_bytes, 1 << 20);
+        assert!(result.ratio() < 0.1);
+    }
+
+    #[test]
+    fn test_compare_skips_missing_tools() {
+        let data = b"glifzip bench ".repeat(1000);
+        let mut seen = Vec::new();
+        let results = compare(&data, &CompressionConfig::new(3, 1), &ExternalTool::ALL, |name, result| {
+            assert!(result.is_ok(), "{}: {:?}", name, result);
+            seen.push(name.to_string());
+        }).unwrap();
+
+        assert_eq!(seen.len(), results.len());
+        assert_eq!(seen[0], "glifzip");
+        for (name, result) in seen.iter().zip(&results).skip(1) {
+            assert!(result.tool.starts_with(name.as_str()));
+            assert_eq!(result.input_bytes, data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_render_table() {
+        let row = |tool: &str, seconds| BenchResult {
+            tool: tool.to_string(),
+            input_bytes: 1_000_000,
+            compressed_bytes: 250_000,
+            compress_time: Duration::from_secs(seconds),
+            decompress_time: Duration::from_secs(seconds),
+        };
+        let table = render_table(&[row("glifzip (level 8, lz4)", 1), row("gzip (level 6)", 4)]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("25.00%") && lines[1].contains("1.0 MB/s") && lines[1].contains("1.00x"));
+        assert!(lines[2].starts_with("gzip (level 6)") && lines[2].contains("4.00x"));
+    }
+}
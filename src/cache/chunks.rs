@@ -0,0 +1,372 @@
+//! Cache of compressed file chunks for repeated archiving
+//!
+//! When the same tree is archived again and again (nightly backups, CI),
+//! most files have not changed since the last run. With a chunk cache, each
+//! large file's contents are compressed into chunks of their own and the
+//! framed result is stored under the file's SHA256, the compression level
+//! and the codec. Later runs copy the chunks of unchanged files from the
+//! cache instead of recompressing them.
+//!
+//! Entries are framed Zstd streams preceded by their own SHA256, so a
+//! damaged entry is detected and treated as a miss. As in `ArchiveCache`,
+//! an entry's modification time records when it was last used and the
+//! least recently used entries are evicted once the cache outgrows its
+//! size limit.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{touch, ArchiveCache};
+use crate::archive::file_entry::UNHASHED;
+use crate::compression::{compress_zstd, framing};
+use crate::{ArchiveManifest, CompressionConfig};
+
+/// Default size limit of a chunk cache (10 GiB)
+pub const DEFAULT_MAX_CHUNK_CACHE_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Files smaller than this are compressed together with their neighbours
+/// and never cached (1 MiB)
+pub const MIN_CACHED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Extension of cached chunk entries
+pub const CHUNK_CACHE_EXTENSION: &str = "glcf";
+
+/// Compressed chunks of files, keyed by file hash, level and codec
+///
+/// Serializes as `{"root": ..., "max_size": ...}`; `max_size` is optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkCache {
+    root: PathBuf,
+    #[serde(default = "default_max_size")]
+    max_size: u64,
+}
+
+fn default_max_size() -> u64 {
+    DEFAULT_MAX_CHUNK_CACHE_SIZE
+}
+
+/// Hits and misses of one archiving run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Uncompressed bytes whose chunks came from the cache
+    pub bytes_reused: u64,
+}
+
+impl ChunkCache {
+    /// Cache stored in `root` with the default size limit
+    pub fn new(root: PathBuf) -> Self {
+        Self { root, max_size: DEFAULT_MAX_CHUNK_CACHE_SIZE }
+    }
+
+    /// `chunks` under the default `ArchiveCache` root
+    pub fn default_root() -> Result<PathBuf> {
+        Ok(ArchiveCache::default_root()?.join("chunks"))
+    }
+
+    /// Evict least recently used entries once the cache exceeds `max_size` bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Key of the chunks of a file with hex SHA256 `file_hash`, compressed
+    /// with Zstd at `level` in `chunk_size` pieces
+    pub fn key(file_hash: &str, level: i32, chunk_size: usize) -> String {
+        format!("{}-zstd-{}-{}", file_hash, level, chunk_size)
+    }
+
+    /// The framed stream stored under `key`, marking it as used
+    ///
+    /// A damaged entry is removed and reported as a miss.
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path_for(key)?;
+        let entry = match fs::read(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        match entry.split_at_checked(32) {
+            Some((hash, framed)) if Sha256::digest(framed).as_slice() == hash => {
+                touch(&path)?;
+                Ok(Some(framed.to_vec()))
+            }
+            _ => {
+                fs::remove_file(&path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Store a framed stream under `key`
+    pub fn put(&self, key: &str, framed: &[u8]) -> Result<()> {
+        let path = self.path_for(key)?;
+        fs::create_dir_all(&self.root)?;
+
+        // Write under a temporary name so readers never see a partial entry
+        let partial = self.root.join(format!(".{}.{}.partial", key, std::process::id()));
+        let mut entry = Sha256::digest(framed).to_vec();
+        entry.extend_from_slice(framed);
+        let written = fs::write(&partial, entry).and_then(|_| fs::rename(&partial, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        written
+    }
+
+    /// Entries with their sizes, least recently used first
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let read_dir = match fs::read_dir(&self.root) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            if path.extension().is_some_and(|e| e == CHUNK_CACHE_EXTENSION) {
+                let metadata = fs::metadata(&path)?;
+                entries.push((path, metadata.len(), metadata.modified()?));
+            }
+        }
+        entries.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0)));
+        Ok(entries)
+    }
+
+    /// Total size of all entries
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, size, _)| size).sum())
+    }
+
+    /// Remove least recently used entries until the cache fits its size
+    /// limit, returning how many were removed
+    pub fn evict(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+
+        let mut removed = 0;
+        for (path, size, _) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= size;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid chunk cache key '{}'", key)));
+        }
+        Ok(self.root.join(format!("{}.{}", key, CHUNK_CACHE_EXTENSION)))
+    }
+}
+
+/// Where a run of the payload's compressed chunks comes from
+enum Part {
+    /// A cache hit: the file's framed chunks
+    Cached(Vec<u8>),
+    /// Chunks compressed in this run, cached under `key` if given
+    Compressed { pieces: Range<usize>, key: Option<String> },
+}
+
+/// Compress a directory payload into a framed Zstd stream, reusing cached
+/// chunks of unchanged files
+///
+/// Regular files of at least `MIN_CACHED_FILE_SIZE` with a recorded hash
+/// start a new chunk and end one, so their chunks depend only on their
+/// contents; the data between them is chunked as usual. The stream is
+/// the same whether chunks come from the cache or not.
+pub(crate) fn compress_with_cache(
+    manifest: &ArchiveManifest,
+    file_data: &[u8],
+    config: &CompressionConfig,
+    threads: usize,
+    cache: &ChunkCache,
+) -> Result<(Vec<u8>, ChunkCacheStats)> {
+    let (level, chunk_size) = (config.level, config.chunk_size);
+    let mut stats = ChunkCacheStats::default();
+    if file_data.is_empty() {
+        let framed = framing::compress_chunked(file_data, chunk_size, threads, |chunk| compress_zstd(chunk, level))?;
+        return Ok((framed, stats));
+    }
+
+    let mut pieces: Vec<&[u8]> = Vec::new();
+    let mut parts = Vec::new();
+    for (range, file_hash) in segments(manifest, file_data.len()) {
+        let key = file_hash.map(|hash| ChunkCache::key(hash, level, chunk_size));
+        if let Some(key) = &key {
+            match cache.get(key)? {
+                Some(framed) if frames_cover(&framed, range.len()) => {
+                    stats.hits += 1;
+                    stats.bytes_reused += range.len() as u64;
+                    parts.push(Part::Cached(framed));
+                    continue;
+                }
+                _ => stats.misses += 1,
+            }
+        }
+
+        let first = pieces.len();
+        pieces.extend(file_data[range].chunks(chunk_size));
+        parts.push(Part::Compressed { pieces: first..pieces.len(), key });
+    }
+
+    let mut compressed = framing::compress_chunks(&pieces, threads, |chunk| compress_zstd(chunk, level))?.into_iter();
+    let mut sizes = Vec::new();
+    let mut chunks = Vec::new();
+    for part in parts {
+        match part {
+            Part::Cached(framed) => {
+                for chunk in framing::read_frames(&framed)? {
+                    sizes.push(chunk.uncompressed_size.unwrap_or_default());
+                    chunks.push(chunk.data.to_vec());
+                }
+            }
+            Part::Compressed { pieces: range, key } => {
+                let part_sizes: Vec<usize> = pieces[range.clone()].iter().map(|piece| piece.len()).collect();
+                let part_chunks: Vec<Vec<u8>> = compressed.by_ref().take(range.len()).collect();
+                if let Some(key) = key {
+                    cache.put(&key, &framing::frame_chunks(&part_sizes, &part_chunks))?;
+                }
+                sizes.extend(part_sizes);
+                chunks.extend(part_chunks);
+            }
+        }
+    }
+
+    Ok((framing::frame_chunks(&sizes, &chunks), stats))
+}
+
+/// Split the payload into cacheable files (with their hash) and the runs between them
+fn segments(manifest: &ArchiveManifest, payload_len: usize) -> Vec<(Range<usize>, Option<&str>)> {
+    let mut cacheable: Vec<_> = manifest.entries.iter()
+        .filter(|entry| entry.stored_size() >= MIN_CACHED_FILE_SIZE)
+        .filter(|entry| !entry.sha256.is_empty() && entry.sha256 != UNHASHED)
+        .collect();
+    cacheable.sort_by_key(|entry| entry.data_offset);
+
+    let mut segments = Vec::new();
+    let mut position = 0;
+    for entry in cacheable {
+        let start = entry.data_offset as usize;
+        let end = start + entry.size as usize;
+        if start > position {
+            segments.push((position..start, None));
+        }
+        segments.push((start..end, Some(entry.sha256.as_str())));
+        position = end;
+    }
+    if position < payload_len {
+        segments.push((position..payload_len, None));
+    }
+    segments
+}
+
+/// Whether a cached entry is a framed stream of exactly `len` uncompressed bytes
+fn frames_cover(framed: &[u8], len: usize) -> bool {
+    framing::read_frames(framed).is_ok_and(|chunks| {
+        chunks.iter().map(|chunk| chunk.uncompressed_size.unwrap_or_default()).sum::<usize>() == len
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_put_get_and_evict() {
+        let temp = TempDir::new().unwrap();
+        let cache = ChunkCache::new(temp.path().join("chunks")).with_max_size(100);
+        let key = ChunkCache::key("ab12", 8, 1 << 20);
+        assert_eq!(key, "ab12-zstd-8-1048576");
+        assert_eq!(cache.get(&key).unwrap(), None);
+
+        cache.put(&key, &[1; 60]).unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(vec![1; 60]));
+        assert_eq!(cache.size().unwrap(), 92);
+
+        // A second entry pushes the cache over its limit; the older one goes
+        let old = cache.path_for(&key).unwrap();
+        fs::File::options().write(true).open(&old).unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(100)).unwrap();
+        cache.put("cd34-zstd-8-1048576", &[2; 60]).unwrap();
+        assert_eq!(cache.evict().unwrap(), 1);
+        assert!(!old.exists());
+
+        assert!(cache.get("../escape").is_err());
+    }
+
+    #[test]
+    fn test_unchanged_files_come_from_cache() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir_all(source.join("logs")).unwrap();
+        let large = crate::testing::data::generate(crate::testing::data::DataKind::Code, 3 << 20, 1);
+        fs::write(source.join("logs/large.log"), &large).unwrap();
+        fs::write(source.join("small.txt"), b"small").unwrap();
+
+        let cache = ChunkCache::new(temp.path().join("chunks"));
+        let config = CompressionConfig::builder().threads(2).chunk_size(1 << 20).build().unwrap();
+        let compressor = crate::DirectoryCompressor::new(
+            crate::DirectoryCompressionConfig::new(config.clone()).with_progress(false)
+        ).unwrap();
+
+        let (manifest, file_data) = compressor.create_manifest(&source).unwrap();
+        let (first, stats) = compress_with_cache(&manifest, &file_data, &config, 2, &cache).unwrap();
+        assert_eq!(stats, ChunkCacheStats { hits: 0, misses: 1, bytes_reused: 0 });
+        let (second, stats) = compress_with_cache(&manifest, &file_data, &config, 2, &cache).unwrap();
+        assert_eq!(stats, ChunkCacheStats { hits: 1, misses: 0, bytes_reused: large.len() as u64 });
+        assert_eq!(second, first);
+        assert_eq!(crate::compression::decompress_zstd_multithreaded(&second, 2).unwrap(), file_data);
+
+        // A changed file misses; a different level is cached separately
+        fs::write(source.join("logs/large.log"), &large[1..]).unwrap();
+        let (manifest, file_data) = compressor.create_manifest(&source).unwrap();
+        let (_, stats) = compress_with_cache(&manifest, &file_data, &config, 2, &cache).unwrap();
+        assert_eq!(stats.misses, 1);
+        let fast = CompressionConfig { level: 1, ..config };
+        let (_, stats) = compress_with_cache(&manifest, &file_data, &fast, 2, &cache).unwrap();
+        assert_eq!(stats.misses, 1);
+
+        // Archives built through the cache extract as usual
+        let archive = temp.path().join("cached.glif");
+        crate::DirectoryCompressor::new(
+            crate::DirectoryCompressionConfig::new(fast).with_progress(false).with_chunk_cache(cache)
+        ).unwrap().compress_directory(&source, &archive).unwrap();
+        crate::DirectoryCompressor::extract_directory(&archive, temp.path().join("out"), 2, false, false).unwrap();
+        assert_eq!(fs::read(temp.path().join("out/logs/large.log")).unwrap(), &large[1..]);
+    }
+
+    #[test]
+    fn test_damaged_entry_is_a_miss() {
+        let temp = TempDir::new().unwrap();
+        let cache = ChunkCache::new(temp.path().to_path_buf());
+        cache.put("key", b"framed chunks").unwrap();
+
+        let path = cache.path_for("key").unwrap();
+        let mut entry = fs::read(&path).unwrap();
+        *entry.last_mut().unwrap() ^= 1;
+        fs::write(&path, entry).unwrap();
+
+        assert_eq!(cache.get("key").unwrap(), None);
+        assert!(!path.exists());
+    }
+}
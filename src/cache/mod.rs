@@ -0,0 +1,291 @@
+//! Local archive cache for build outputs
+//!
+//! Stores directory archives under a cache root keyed by tree hash (by
+//! default the directory's content address), as used for CI build caches.
+//! An entry's modification time records when it was last stored or
+//! restored; once the cache grows past its size limit the least recently
+//! used entries are evicted first.
+
+pub mod chunks;
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::archive::content_address::CONTENT_ADDRESS_PREFIX;
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::verification::hex_encode;
+use crate::DirectoryCompressor;
+
+/// Default size limit of a cache root (10 GiB)
+pub const DEFAULT_MAX_CACHE_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Extension of cached archives
+pub const CACHE_EXTENSION: &str = "glif";
+
+/// Environment variable overriding the default cache root
+pub const CACHE_DIR_ENV: &str = "GLIFZIP_CACHE_DIR";
+
+/// A cached archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub key: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// When the entry was last stored or restored
+    pub last_used: SystemTime,
+}
+
+/// Archives stored under a cache root, keyed by tree hash
+#[derive(Debug, Clone)]
+pub struct ArchiveCache {
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl ArchiveCache {
+    /// Cache stored in `root` with the default size limit
+    pub fn new(root: PathBuf) -> Self {
+        Self { root, max_size: DEFAULT_MAX_CACHE_SIZE }
+    }
+
+    /// Cache in `$GLIFZIP_CACHE_DIR`, `$XDG_CACHE_HOME/glifzip` or `~/.cache/glifzip`
+    pub fn default_root() -> Result<PathBuf> {
+        if let Some(dir) = std::env::var_os(CACHE_DIR_ENV) {
+            return Ok(PathBuf::from(dir));
+        }
+        let cache = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))?,
+        };
+        Ok(cache.join("glifzip"))
+    }
+
+    /// Evict least recently used entries once the cache exceeds `max_size` bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Archive `directory` under `key`, or under its content address if no key is given
+    ///
+    /// An entry already stored under the key is kept and marked as used.
+    /// Returns the stored entry after evicting older entries over the size limit.
+    pub fn put(&self, directory: &Path, key: Option<&str>, config: DirectoryCompressionConfig) -> Result<CacheEntry> {
+        let compressor = DirectoryCompressor::new(config)?;
+        let key = match key {
+            Some(key) => normalize_key(key)?,
+            None => hex_encode(&compressor.content_address(directory)?),
+        };
+
+        fs::create_dir_all(&self.root)?;
+        let path = self.path_for(&key);
+        if path.exists() {
+            touch(&path)?;
+        } else {
+            // Write under a temporary name so readers never see a partial archive
+            let partial = self.root.join(format!(".{}.{}.partial", key, std::process::id()));
+            let written = compressor.compress_directory(directory, &partial)
+                .map_err(Error::from)
+                .and_then(|_| fs::rename(&partial, &path));
+            if let Err(e) = written {
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+        }
+
+        self.evict(Some(&key))?;
+        self.entry(&key)?.ok_or_else(|| not_cached(&key))
+    }
+
+    /// Extract the archive stored under `key` into `output`, marking it as used
+    ///
+    /// Fails with `NotFound` on a cache miss.
+    pub fn get(&self, key: &str, output: &Path, threads: usize) -> Result<CacheEntry> {
+        let key = normalize_key(key)?;
+        let entry = self.entry(&key)?.ok_or_else(|| not_cached(&key))?;
+
+        touch(&entry.path)?;
+        DirectoryCompressor::extract_directory(&entry.path, output, threads, false, false)?;
+        Ok(entry)
+    }
+
+    /// Entry stored under `key`, if any
+    pub fn entry(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let key = normalize_key(key)?;
+        let path = self.path_for(&key);
+        match fs::metadata(&path) {
+            Ok(metadata) => Ok(Some(CacheEntry { key, path, size: metadata.len(), last_used: metadata.modified()? })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// All entries, least recently used first
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let read_dir = match fs::read_dir(&self.root) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            let key = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(key) if !key.starts_with('.') && path.extension().is_some_and(|e| e == CACHE_EXTENSION) => key.to_string(),
+                _ => continue,
+            };
+            let metadata = fs::metadata(&path)?;
+            entries.push(CacheEntry { key, path, size: metadata.len(), last_used: metadata.modified()? });
+        }
+
+        entries.sort_by(|a, b| a.last_used.cmp(&b.last_used).then_with(|| a.key.cmp(&b.key)));
+        Ok(entries)
+    }
+
+    /// Total size of all entries
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|e| e.size).sum())
+    }
+
+    /// Remove least recently used entries until the cache fits its size limit
+    ///
+    /// The entry under `keep` is never removed. Returns the removed entries.
+    pub fn evict(&self, keep: Option<&str>) -> Result<Vec<CacheEntry>> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|e| e.size).sum();
+
+        let mut removed = Vec::new();
+        for entry in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if Some(entry.key.as_str()) == keep {
+                continue;
+            }
+            fs::remove_file(&entry.path)?;
+            total -= entry.size;
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", key, CACHE_EXTENSION))
+    }
+}
+
+/// Strip a `sha256:` prefix and check that a key is safe to use as a file name
+fn normalize_key(key: &str) -> Result<String> {
+    let key = key.strip_prefix(CONTENT_ADDRESS_PREFIX).unwrap_or(key);
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !valid {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid cache key '{}': use letters, digits, '-', '_' and '.'", key)
+        ));
+    }
+    Ok(key.to_string())
+}
+
+fn touch(path: &Path) -> Result<()> {
+    fs::File::options().write(true).open(path)?.set_modified(SystemTime::now())
+}
+
+fn not_cached(key: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("No cache entry for key '{}'", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn config() -> DirectoryCompressionConfig {
+        DirectoryCompressionConfig::default().with_progress(false)
+    }
+
+    fn source(temp: &TempDir, name: &str, contents: &str) -> PathBuf {
+        let dir = temp.path().join(name);
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("out/artifact.txt"), contents).unwrap();
+        dir
+    }
+
+    fn set_last_used(entry: &CacheEntry, seconds_ago: u64) {
+        fs::File::options().write(true).open(&entry.path).unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(seconds_ago)).unwrap();
+    }
+
+    #[test]
+    fn test_put_and_get_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp.path().join("cache"));
+        let dir = source(&temp, "build", "compiled");
+
+        let entry = cache.put(&dir, None, config()).unwrap();
+        let address = DirectoryCompressor::new(config()).unwrap().content_address(&dir).unwrap();
+        assert_eq!(entry.key, hex_encode(&address));
+
+        let restored = temp.path().join("restored");
+        cache.get(&format!("sha256:{}", entry.key), &restored, 1).unwrap();
+        assert_eq!(fs::read_to_string(restored.join("out/artifact.txt")).unwrap(), "compiled");
+
+        let miss = cache.get("missing", &restored, 1).unwrap_err();
+        assert_eq!(miss.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let temp = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp.path().join("cache"));
+
+        let old = cache.put(&source(&temp, "a", "first"), Some("old"), config()).unwrap();
+        let used = cache.put(&source(&temp, "b", "second"), Some("used"), config()).unwrap();
+        set_last_used(&old, 200);
+        set_last_used(&used, 100);
+
+        // Restoring an entry makes it the most recently used
+        cache.get("old", &temp.path().join("restored"), 1).unwrap();
+
+        // Room for two entries but not three
+        let cache = cache.with_max_size(old.size + used.size + old.size / 2);
+        cache.put(&source(&temp, "c", "third"), Some("new"), config()).unwrap();
+
+        let keys: Vec<String> = cache.entries().unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&"old".to_string()) && keys.contains(&"new".to_string()));
+    }
+
+    #[test]
+    fn test_new_entry_is_kept_even_if_over_limit() {
+        let temp = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp.path().join("cache")).with_max_size(1);
+
+        cache.put(&source(&temp, "a", "first"), Some("a"), config()).unwrap();
+        cache.put(&source(&temp, "b", "second"), Some("b"), config()).unwrap();
+
+        let keys: Vec<String> = cache.entries().unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_keys() {
+        for key in ["", "../escape", "a/b", ".hidden", "sha256:"] {
+            assert!(normalize_key(key).is_err(), "{:?}", key);
+        }
+        assert_eq!(normalize_key("sha256:abc123").unwrap(), "abc123");
+    }
+}
@@ -0,0 +1,98 @@
+//! Clipboard transfer of small files
+//!
+//! Compresses a file into a GLIF archive and encodes it as base64 text that
+//! can be pasted across machines, e.g. over a screen-sharing session. The
+//! text is wrapped into short lines; whitespace is ignored when decoding,
+//! and the archive's hashes catch anything mangled in transit.
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::encoding::{base64_decode, base64_encode};
+
+use crate::CompressionConfig;
+
+/// Largest archive that will be placed on the clipboard (1 MiB)
+pub const MAX_CLIP_ARCHIVE_SIZE: usize = 1024 * 1024;
+
+/// Width of the base64 lines in clipboard text
+pub const CLIP_LINE_WIDTH: usize = 76;
+
+/// Compress `data` and encode the archive as clipboard text
+pub fn encode_clip(data: &[u8], config: &CompressionConfig) -> Result<String> {
+    let archive = crate::compress(data, config)?;
+    if archive.len() > MAX_CLIP_ARCHIVE_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Compressed archive is {} bytes, over the {} byte clipboard limit; use `glifzip create` instead",
+                archive.len(),
+                MAX_CLIP_ARCHIVE_SIZE
+            )
+        ));
+    }
+
+    let encoded = base64_encode(&archive);
+    let mut text = String::with_capacity(encoded.len() + encoded.len() / CLIP_LINE_WIDTH + 1);
+    for line in encoded.as_bytes().chunks(CLIP_LINE_WIDTH) {
+        // base64 output is ASCII, so every chunk is valid UTF-8
+        text.push_str(std::str::from_utf8(line).unwrap_or_default());
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// Decode clipboard text produced by `encode_clip` and decompress it
+pub fn decode_clip(text: &str, threads: usize) -> Result<Vec<u8>> {
+    let encoded: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if encoded.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "Clipboard is empty"));
+    }
+
+    let archive = base64_decode(&encoded).map_err(|e| Error::new(
+        ErrorKind::InvalidData,
+        format!("Clipboard does not hold a glifzip payload: {}", e)
+    ))?;
+    Ok(crate::decompress(&archive, threads)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_roundtrip() {
+        let data = b"[user]\n\tname = glyph\n".repeat(20);
+        let text = encode_clip(&data, &CompressionConfig::default()).unwrap();
+
+        assert!(text.lines().all(|line| line.len() <= CLIP_LINE_WIDTH));
+        assert_eq!(decode_clip(&text, 1).unwrap(), data);
+
+        // Pasting through chat or terminal tools often rewraps or indents text
+        let mangled = text.replace('\n', "\r\n  ");
+        assert_eq!(decode_clip(&mangled, 1).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decode_rejects_other_text() {
+        assert_eq!(decode_clip("  \n", 1).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(decode_clip("not base64!", 1).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(decode_clip(&base64_encode(b"plain text, not an archive"), 1).is_err());
+    }
+
+    #[test]
+    fn test_encode_rejects_large_payloads() {
+        // Pseudo-random bytes do not compress below the limit
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let data: Vec<u8> = (0..MAX_CLIP_ARCHIVE_SIZE + 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let err = encode_clip(&data, &CompressionConfig::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,115 @@
+//! Falling back to one thread under memory pressure
+//!
+//! Background services shouldn't abort because parallel (de)compression
+//! wants more memory than the machine can spare. Decompression drops to a
+//! single thread up front when its estimate exceeds the configured ceiling
+//! (`DecompressionConfig::max_memory`) or the memory available, and both
+//! directions retry on a single thread when a multithreaded attempt fails
+//! to allocate memory or start its workers; decompression retries by
+//! streaming, so only one chunk is held at a time. The output is the same
+//! either way; every downgrade is passed to the hook installed with
+//! `set_downgrade_hook`, so applications decide whether to report it.
+
+use std::io::ErrorKind;
+use std::sync::RwLock;
+
+use crate::error::GlifError;
+
+/// Whether `error` means memory or threads ran out, so a single-threaded retry may succeed
+pub fn is_memory_pressure(error: &GlifError) -> bool {
+    let GlifError::Io(e) = error else {
+        return false;
+    };
+    if e.kind() == ErrorKind::OutOfMemory {
+        return true;
+    }
+
+    // Zstd reports allocation failures and rayon failed thread spawns as plain messages
+    let message = e.to_string().to_lowercase();
+    ["allocation error", "out of memory", "cannot allocate memory", "resource temporarily unavailable"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Threads to use so that `required(threads)` bytes fit in `ceiling`: `threads`, or 1 if they don't
+///
+/// A single thread is returned even if it doesn't fit either; callers that
+/// must refuse check that separately.
+pub fn threads_within(threads: usize, ceiling: Option<u64>, required: impl Fn(usize) -> u64, operation: &str) -> usize {
+    match ceiling {
+        Some(ceiling) if threads > 1 && required(threads) > ceiling => {
+            report_downgrade(operation, &format!(
+                "~{} MiB needed with {} threads exceeds the {} MiB available",
+                required(threads).div_ceil(1024 * 1024),
+                threads,
+                ceiling / (1024 * 1024)
+            ));
+            1
+        }
+        _ => threads,
+    }
+}
+
+/// Receives the operation ("compression" or "decompression") and the
+/// reason whenever one falls back to a single thread
+pub type DowngradeHook = fn(operation: &str, reason: &str);
+
+static DOWNGRADE_HOOK: RwLock<Option<DowngradeHook>> = RwLock::new(None);
+
+/// Pass every later fallback to a single thread to `hook`; `None` (the default) ignores them
+pub fn set_downgrade_hook(hook: Option<DowngradeHook>) {
+    *DOWNGRADE_HOOK.write().unwrap_or_else(|e| e.into_inner()) = hook;
+}
+
+/// Report that `operation` continues on one thread because of `reason`
+pub fn report_downgrade(operation: &str, reason: &str) {
+    if let Some(hook) = *DOWNGRADE_HOOK.read().unwrap_or_else(|e| e.into_inner()) {
+        hook(operation, reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pressure_detection() {
+        let oom = GlifError::Io(std::io::Error::new(ErrorKind::OutOfMemory, "no memory"));
+        let zstd = GlifError::Io(std::io::Error::other("Allocation error : not enough memory"));
+        let spawn = GlifError::Io(std::io::Error::other("Resource temporarily unavailable (os error 11)"));
+        assert!(is_memory_pressure(&oom) && is_memory_pressure(&zstd) && is_memory_pressure(&spawn));
+        assert!(!is_memory_pressure(&GlifError::InvalidMagic));
+        assert!(!is_memory_pressure(&GlifError::Io(std::io::Error::other("Unknown frame descriptor"))));
+
+        static REPORTED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        set_downgrade_hook(Some(|operation, reason| {
+            if operation == "test" {
+                REPORTED.lock().unwrap().push(reason.to_string());
+            }
+        }));
+
+        let required = |threads: usize| (threads as u64 * 100) << 20;
+        assert_eq!(threads_within(8, Some(1 << 30), required, "test"), 8);
+        assert!(REPORTED.lock().unwrap().is_empty());
+        assert_eq!(threads_within(16, Some(1 << 30), required, "test"), 1);
+        assert_eq!(*REPORTED.lock().unwrap(), ["~1600 MiB needed with 16 threads exceeds the 1024 MiB available"]);
+        assert_eq!(threads_within(16, None, required, "test"), 16);
+        assert_eq!(threads_within(1, Some(0), required, "test"), 1);
+    }
+
+    #[test]
+    fn test_memory_ceiling_decompresses_on_one_thread() {
+        use crate::compression::MIN_CHUNK_SIZE;
+        use crate::{CompressionConfig, DecompressionConfig};
+
+        let data: Vec<u8> = (0..3 * MIN_CHUNK_SIZE).map(|i| (i % 241) as u8).collect();
+        let config = CompressionConfig::builder().threads(4).chunk_size(MIN_CHUNK_SIZE).build().unwrap();
+        let archive = crate::compress(&data, &config).unwrap();
+
+        // A ceiling nothing fits under still decompresses, just without parallelism
+        let config = DecompressionConfig::new().with_threads(4).with_max_memory(1);
+        assert_eq!(config.max_memory, Some(1));
+        assert_eq!(crate::decompress_with(&archive, &config).unwrap(), data);
+        assert_eq!(DecompressionConfig::new().with_max_memory(0).max_memory, None);
+    }
+}
@@ -0,0 +1,755 @@
+//! Chunk framing for compressed streams
+//!
+//! Data is compressed chunk by chunk and stored as:
+//!
+//! ```text
+//! magic "GLCF" | chunk count (u32 BE)
+//! per chunk: uncompressed size (u64 BE) | compressed size (u64 BE) | data
+//! ```
+//!
+//! Readers validate the whole table before decompressing anything and
+//! report inconsistencies as a `FramingError` instead of guessing.
+//! Since format v1.1 every stream is framed, even a single (or empty)
+//! chunk. Format v1.0 archives stored small data unframed and used
+//! pre-magic layouts for chunked data; these are still read, but only
+//! when their headers are exactly consistent.
+
+use std::fmt;
+use std::collections::VecDeque;
+use std::io::Read;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use super::CHUNK_SIZE;
+use crate::error::{GlifError, Result};
+
+/// Magic bytes that start a framed stream
+pub const FRAME_MAGIC: &[u8; 4] = b"GLCF";
+
+/// Size of the magic and chunk count
+pub const FRAME_HEADER_SIZE: usize = 8;
+
+/// Size of each chunk's uncompressed and compressed sizes
+pub const CHUNK_HEADER_SIZE: usize = 16;
+
+/// Largest uncompressed chunk a reader accepts (guards allocations)
+pub const MAX_FRAME_CHUNK_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Magic number of a Zstd frame, as stored (little-endian)
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Error in the chunk framing of a compressed stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramingError {
+    /// A framed stream with no chunks
+    NoChunks,
+
+    /// The stream ends inside a chunk header or chunk data
+    Truncated { chunk: usize, offset: usize, needed: usize, available: usize },
+
+    /// A chunk claims more uncompressed data than readers accept
+    ChunkTooLarge { chunk: usize, size: u64 },
+
+    /// Bytes left over after the last chunk
+    TrailingData { bytes: usize },
+
+    /// A chunk decompressed to a different size than its header records
+    SizeMismatch { chunk: usize, expected: usize, actual: usize },
+
+    /// A legacy chunked stream whose header is inconsistent
+    InvalidLegacyHeader { reason: &'static str },
+
+    /// Data from a format that always frames chunks lacks the framing magic
+    NotFramed,
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::NoChunks => write!(f, "chunk framing declares no chunks"),
+            FramingError::Truncated { chunk, offset, needed, available } => write!(
+                f,
+                "chunk {} is truncated: needs {} bytes at offset {}, but only {} remain",
+                chunk, needed, offset, available
+            ),
+            FramingError::ChunkTooLarge { chunk, size } => write!(
+                f,
+                "chunk {} claims {} uncompressed bytes (maximum {})",
+                chunk, size, MAX_FRAME_CHUNK_SIZE
+            ),
+            FramingError::TrailingData { bytes } => {
+                write!(f, "{} unexpected bytes after the last chunk", bytes)
+            }
+            FramingError::SizeMismatch { chunk, expected, actual } => write!(
+                f,
+                "chunk {} decompressed to {} bytes, expected {}",
+                chunk, actual, expected
+            ),
+            FramingError::InvalidLegacyHeader { reason } => {
+                write!(f, "invalid legacy chunk header: {}", reason)
+            }
+            FramingError::NotFramed => write!(f, "compressed stream is missing its chunk framing"),
+        }
+    }
+}
+
+impl std::error::Error for FramingError {}
+
+impl From<FramingError> for std::io::Error {
+    fn from(e: FramingError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// One compressed chunk of a framed stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// Uncompressed size, if the framing records it
+    pub uncompressed_size: Option<usize>,
+    pub data: &'a [u8],
+}
+
+/// Whether `data` starts with the framing magic
+pub fn is_framed(data: &[u8]) -> bool {
+    data.starts_with(FRAME_MAGIC)
+}
+
+/// Chunk count recorded after the framing magic, if `data` is framed
+pub fn framed_chunk_count(data: &[u8]) -> Option<usize> {
+    let count = data.strip_prefix(FRAME_MAGIC.as_slice())?.get(..4)?;
+    Some(u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize)
+}
+
+/// Number of chunks `compress_chunked` splits `len` bytes into
+pub fn chunk_count(len: usize, chunk_size: usize) -> usize {
+    len.div_ceil(chunk_size.max(1)).max(1)
+}
+
+/// Compress `data` in `chunk_size` pieces on `threads` workers and frame the result
+///
+/// Empty data is framed as a single empty chunk.
+pub fn compress_chunked<F>(data: &[u8], chunk_size: usize, threads: usize, compress: F) -> Result<Vec<u8>>
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>> + Sync,
+{
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(chunk_size.max(1)).collect()
+    };
+
+    let compressed_chunks = compress_chunks(&chunks, threads, compress)?;
+    let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+    Ok(frame_chunks(&sizes, &compressed_chunks))
+}
+
+/// Compress each chunk on `threads` workers, keeping their order
+pub fn compress_chunks<F>(chunks: &[&[u8]], threads: usize, compress: F) -> Result<Vec<Vec<u8>>>
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>> + Sync,
+{
+    if threads <= 1 || chunks.len() <= 1 {
+        return chunks.iter().map(|chunk| compress(chunk)).collect();
+    }
+
+    ChunkPool::new(threads)?.map(chunks, |chunk| compress(chunk))
+}
+
+/// Workers that process chunks in parallel
+///
+/// Called from a rayon worker thread (such as within a pool installed by
+/// `CompressionConfig::thread_pool`), chunks run on that pool instead of a
+/// new one, so glifzip doesn't oversubscribe an application's threads.
+/// Without the `parallel` feature (e.g. on `wasm32-unknown-unknown`, which
+/// has no threads) chunks are processed in order on the calling thread.
+struct ChunkPool {
+    /// None to use the pool of the current worker thread
+    #[cfg(feature = "parallel")]
+    pool: Option<rayon::ThreadPool>,
+}
+
+impl ChunkPool {
+    #[cfg(feature = "parallel")]
+    fn new(threads: usize) -> Result<Self> {
+        if rayon::current_thread_index().is_some() {
+            return Ok(Self { pool: None });
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(std::io::Error::other)?;
+        Ok(Self { pool: Some(pool) })
+    }
+
+    /// Run `f` on the pool
+    #[cfg(feature = "parallel")]
+    fn install<U: Send>(&self, f: impl FnOnce() -> U + Send) -> U {
+        match &self.pool {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn new(_threads: usize) -> Result<Self> {
+        Ok(Self {})
+    }
+
+    /// Apply `f` to every item, keeping their order and stopping at the first error
+    #[cfg(feature = "parallel")]
+    fn map<T, U, F>(&self, items: &[T], f: F) -> Result<Vec<U>>
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(&T) -> Result<U> + Sync + Send,
+    {
+        self.install(|| items.par_iter().map(f).collect())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn map<T, U, F>(&self, items: &[T], f: F) -> Result<Vec<U>>
+    where
+        F: Fn(&T) -> Result<U>,
+    {
+        items.iter().map(f).collect()
+    }
+
+    /// Like `map`, but `f` may modify the items
+    #[cfg(feature = "parallel")]
+    fn map_mut<T, U, F>(&self, items: &mut [T], f: F) -> Result<Vec<U>>
+    where
+        T: Send,
+        U: Send,
+        F: Fn(&mut T) -> Result<U> + Sync + Send,
+    {
+        self.install(|| items.par_iter_mut().map(f).collect())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn map_mut<T, U, F>(&self, items: &mut [T], f: F) -> Result<Vec<U>>
+    where
+        F: Fn(&mut T) -> Result<U>,
+    {
+        items.iter_mut().map(f).collect()
+    }
+}
+
+/// Frame compressed chunks, given the uncompressed size of each
+pub fn frame_chunks(uncompressed_sizes: &[usize], compressed_chunks: &[Vec<u8>]) -> Vec<u8> {
+    let framed_size = FRAME_HEADER_SIZE
+        + compressed_chunks.iter().map(|c| CHUNK_HEADER_SIZE + c.len()).sum::<usize>();
+    let mut result = Vec::with_capacity(framed_size);
+
+    result.extend_from_slice(FRAME_MAGIC);
+    result.extend_from_slice(&(compressed_chunks.len() as u32).to_be_bytes());
+
+    for (size, compressed) in uncompressed_sizes.iter().zip(compressed_chunks) {
+        result.extend_from_slice(&(*size as u64).to_be_bytes());
+        result.extend_from_slice(&(compressed.len() as u64).to_be_bytes());
+        result.extend_from_slice(compressed);
+    }
+
+    result
+}
+
+/// Parse a framed stream, validating every chunk header
+pub fn read_frames(data: &[u8]) -> std::result::Result<Vec<Chunk<'_>>, FramingError> {
+    let mut reader = ChunkReader::new(data, FRAME_MAGIC.len());
+    let count = reader.u32(0)? as usize;
+    if count == 0 {
+        return Err(FramingError::NoChunks);
+    }
+
+    let mut chunks = Vec::with_capacity(count.min(data.len() / CHUNK_HEADER_SIZE));
+    for chunk in 0..count {
+        let uncompressed_size = reader.u64(chunk)?;
+        if uncompressed_size > MAX_FRAME_CHUNK_SIZE as u64 {
+            return Err(FramingError::ChunkTooLarge { chunk, size: uncompressed_size });
+        }
+        let compressed_size = reader.u64(chunk)?;
+        chunks.push(Chunk {
+            uncompressed_size: Some(uncompressed_size as usize),
+            data: reader.bytes(chunk, compressed_size)?,
+        });
+    }
+
+    reader.finish()?;
+    Ok(chunks)
+}
+
+/// Parse the chunked LZ4 layout written by glifzip 1.1.0 and earlier
+///
+/// Returns `None` unless the header is exactly consistent, in which case
+/// the data is a single unframed LZ4 block.
+pub fn read_legacy_lz4(data: &[u8]) -> Option<Vec<Chunk<'_>>> {
+    let mut reader = ChunkReader::new(data, 0);
+    let count = reader.u32(0).ok()? as usize;
+    let chunk_size = reader.u64(0).ok()? as usize;
+    let total_size = reader.u64(0).ok()? as usize;
+
+    if count < 2 || chunk_size != CHUNK_SIZE || total_size.div_ceil(chunk_size) != count {
+        return None;
+    }
+
+    let mut chunks = Vec::with_capacity(count);
+    for chunk in 0..count {
+        let compressed_size = reader.u64(chunk).ok()?;
+        let uncompressed_size = if chunk == count - 1 {
+            total_size - chunk * chunk_size
+        } else {
+            chunk_size
+        };
+        chunks.push(Chunk {
+            uncompressed_size: Some(uncompressed_size),
+            data: reader.bytes(chunk, compressed_size).ok()?,
+        });
+    }
+
+    reader.finish().ok()?;
+    Some(chunks)
+}
+
+/// Parse the chunked Zstd layout written by glifzip 1.1.0 and earlier
+pub fn read_legacy_zstd(data: &[u8]) -> std::result::Result<Vec<Chunk<'_>>, FramingError> {
+    let mut reader = ChunkReader::new(data, 0);
+    let count = reader.u32(0)? as usize;
+    if count == 0 {
+        return Err(FramingError::InvalidLegacyHeader { reason: "no chunks" });
+    }
+
+    let mut chunks = Vec::with_capacity(count.min(data.len() / 8));
+    for chunk in 0..count {
+        let compressed_size = reader.u64(chunk)?;
+        let chunk_data = reader.bytes(chunk, compressed_size)?;
+        if !chunk_data.starts_with(&ZSTD_MAGIC) {
+            return Err(FramingError::InvalidLegacyHeader { reason: "chunk is not a Zstd frame" });
+        }
+        chunks.push(Chunk { uncompressed_size: None, data: chunk_data });
+    }
+
+    reader.finish()?;
+    Ok(chunks)
+}
+
+/// Decompress chunks on up to `threads` workers and concatenate them in order
+///
+/// `decompress` receives each chunk's recorded uncompressed size; the
+/// output of every chunk with a recorded size is checked against it.
+pub fn decompress_chunks<F>(chunks: &[Chunk<'_>], threads: usize, decompress: F) -> Result<Vec<u8>>
+where
+    F: Fn(&[u8], Option<usize>) -> Result<Vec<u8>> + Sync,
+{
+    let decompress_chunk = |&(index, chunk): &(usize, &Chunk<'_>)| -> Result<Vec<u8>> {
+        let output = decompress(chunk.data, chunk.uncompressed_size)?;
+        match chunk.uncompressed_size {
+            Some(expected) if expected != output.len() => Err(FramingError::SizeMismatch {
+                chunk: index,
+                expected,
+                actual: output.len(),
+            }.into()),
+            _ => Ok(output),
+        }
+    };
+
+    let indexed: Vec<(usize, &Chunk<'_>)> = chunks.iter().enumerate().collect();
+    let decompressed_chunks: Vec<Vec<u8>> = if threads <= 1 || chunks.len() <= 1 {
+        indexed.iter().map(decompress_chunk).collect::<Result<_>>()?
+    } else {
+        ChunkPool::new(threads)?.map(&indexed, decompress_chunk)?
+    };
+
+    let total_size = decompressed_chunks.iter().map(Vec::len).sum();
+    let mut result = Vec::with_capacity(total_size);
+    for chunk in decompressed_chunks {
+        result.extend_from_slice(&chunk);
+    }
+
+    Ok(result)
+}
+
+/// Decompress chunks straight into `out`, each into its own slice of it
+///
+/// Every chunk must record its uncompressed size, and the sizes must add up
+/// to `out.len()`. `decompress` writes one chunk into the slice it is given
+/// and returns how many bytes it wrote.
+pub fn decompress_chunks_into<F>(chunks: &[Chunk<'_>], out: &mut [u8], threads: usize, decompress: F) -> Result<()>
+where
+    F: Fn(&[u8], &mut [u8]) -> Result<usize> + Sync,
+{
+    let mut total_size = 0usize;
+    for chunk in chunks {
+        let size = chunk.uncompressed_size
+            .ok_or_else(|| GlifError::InvalidInput("Chunk sizes are not recorded in this stream".to_string()))?;
+        total_size = total_size.saturating_add(size);
+    }
+    if total_size != out.len() {
+        return Err(GlifError::SizeMismatch { expected: out.len() as u64, actual: total_size as u64 });
+    }
+
+    let mut slices = Vec::with_capacity(chunks.len());
+    let mut rest = out;
+    for (index, chunk) in chunks.iter().enumerate() {
+        let (slice, tail) = rest.split_at_mut(chunk.uncompressed_size.unwrap_or(0));
+        slices.push((index, chunk.data, slice));
+        rest = tail;
+    }
+
+    let decompress_chunk = |(index, data, slice): &mut (usize, &[u8], &mut [u8])| -> Result<()> {
+        let written = decompress(data, slice)?;
+        if written != slice.len() {
+            return Err(FramingError::SizeMismatch { chunk: *index, expected: slice.len(), actual: written }.into());
+        }
+        Ok(())
+    };
+
+    if threads <= 1 || chunks.len() <= 1 {
+        slices.iter_mut().try_for_each(decompress_chunk)
+    } else {
+        ChunkPool::new(threads)?.map_mut(&mut slices, decompress_chunk).map(drop)
+    }
+}
+
+/// Reads a framed stream from `R`, decompressing a batch of chunks at a time
+///
+/// Applies the same checks as `read_frames` and `decompress_chunks`, but
+/// holds only the current batch, so streams of any size can be read. A batch
+/// is one chunk unless `with_threads` allows more.
+pub struct FrameReader<R> {
+    inner: R,
+    decompress: fn(&[u8], Option<usize>) -> Result<Vec<u8>>,
+    threads: usize,
+    pool: Option<ChunkPool>,
+    chunks_left: Option<usize>,
+    chunk: usize,
+    offset: usize,
+    ready: VecDeque<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Read the framed stream in `inner`, which must end with the stream
+    pub fn new(inner: R, decompress: fn(&[u8], Option<usize>) -> Result<Vec<u8>>) -> Self {
+        Self {
+            inner,
+            decompress,
+            threads: 1,
+            pool: None,
+            chunks_left: None,
+            chunk: 0,
+            offset: 0,
+            ready: VecDeque::new(),
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Decompress up to `threads` chunks at once, holding that many in memory
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The reader the framed stream comes from
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Mutable access to the reader the framed stream comes from
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Decompress the next batch of chunks; false once all chunks are read
+    fn next_batch(&mut self) -> Result<bool> {
+        let chunks_left = match self.chunks_left {
+            Some(chunks_left) => chunks_left,
+            None => {
+                let header = self.read_bytes(FRAME_HEADER_SIZE as u64)?;
+                if !is_framed(&header) {
+                    return Err(FramingError::NotFramed.into());
+                }
+                match u32::from_be_bytes([header[4], header[5], header[6], header[7]]) {
+                    0 => return Err(FramingError::NoChunks.into()),
+                    count => count as usize,
+                }
+            }
+        };
+
+        if chunks_left == 0 {
+            let trailing = std::io::copy(&mut self.inner, &mut std::io::sink())?;
+            if trailing > 0 {
+                return Err(FramingError::TrailingData { bytes: trailing as usize }.into());
+            }
+            return Ok(false);
+        }
+
+        let mut batch = Vec::new();
+        for _ in 0..self.threads.min(chunks_left) {
+            let index = self.chunk;
+            let header = self.read_bytes(CHUNK_HEADER_SIZE as u64)?;
+            let mut size = [0u8; 8];
+            size.copy_from_slice(&header[..8]);
+            let uncompressed_size = u64::from_be_bytes(size);
+            if uncompressed_size > MAX_FRAME_CHUNK_SIZE as u64 {
+                return Err(FramingError::ChunkTooLarge { chunk: index, size: uncompressed_size }.into());
+            }
+            size.copy_from_slice(&header[8..]);
+            let compressed = self.read_bytes(u64::from_be_bytes(size))?;
+            batch.push((index, uncompressed_size as usize, compressed));
+            self.chunk += 1;
+        }
+
+        let decompress = self.decompress;
+        let decompress_chunk = |(index, expected, compressed): &(usize, usize, Vec<u8>)| -> Result<Vec<u8>> {
+            let output = decompress(compressed, Some(*expected))?;
+            if output.len() != *expected {
+                return Err(FramingError::SizeMismatch { chunk: *index, expected: *expected, actual: output.len() }.into());
+            }
+            Ok(output)
+        };
+
+        let outputs: Vec<Vec<u8>> = if batch.len() == 1 {
+            vec![decompress_chunk(&batch[0])?]
+        } else {
+            if self.pool.is_none() {
+                self.pool = Some(ChunkPool::new(self.threads)?);
+            }
+            let pool = self.pool.as_ref().expect("pool was just built");
+            pool.map(&batch, decompress_chunk)?
+        };
+
+        self.chunks_left = Some(chunks_left - batch.len());
+        self.ready.extend(outputs);
+        Ok(true)
+    }
+
+    /// Read exactly `len` bytes, reporting a short read as truncation
+    fn read_bytes(&mut self, len: u64) -> Result<Vec<u8>> {
+        // Grows with the data actually read, so a bogus size can't force a huge allocation
+        let mut bytes = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < len {
+            return Err(FramingError::Truncated {
+                chunk: self.chunk,
+                offset: self.offset,
+                needed: len.min(usize::MAX as u64) as usize,
+                available: bytes.len(),
+            }.into());
+        }
+
+        self.offset += bytes.len();
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Read for FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.buffer.len() {
+            match self.ready.pop_front() {
+                Some(chunk) => {
+                    self.buffer = chunk;
+                    self.position = 0;
+                }
+                None if !self.next_batch()? => return Ok(0),
+                None => {}
+            }
+        }
+
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Bounds-checked big-endian reader over chunk headers
+struct ChunkReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ChunkReader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    fn bytes(&mut self, chunk: usize, len: u64) -> std::result::Result<&'a [u8], FramingError> {
+        let available = self.data.len().saturating_sub(self.offset);
+        if len > available as u64 {
+            return Err(FramingError::Truncated {
+                chunk,
+                offset: self.offset,
+                needed: len.min(usize::MAX as u64) as usize,
+                available,
+            });
+        }
+
+        let bytes = &self.data[self.offset..self.offset + len as usize];
+        self.offset += len as usize;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self, chunk: usize) -> std::result::Result<u32, FramingError> {
+        let bytes = self.bytes(chunk, 4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self, chunk: usize) -> std::result::Result<u64, FramingError> {
+        let bytes = self.bytes(chunk, 8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(bytes);
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn finish(self) -> std::result::Result<(), FramingError> {
+        match self.data.len() - self.offset {
+            0 => Ok(()),
+            bytes => Err(FramingError::TrailingData { bytes }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity_frames(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        compress_chunked(data, chunk_size, 2, |chunk| Ok(chunk.to_vec())).unwrap()
+    }
+
+    fn copy(chunk: &[u8], _size: Option<usize>) -> Result<Vec<u8>> {
+        Ok(chunk.to_vec())
+    }
+
+    #[test]
+    fn test_frame_roundtrip_across_boundaries() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+
+        for chunk_size in [1, 7, 999, 1000, 1001] {
+            let framed = identity_frames(&data, chunk_size);
+            assert!(is_framed(&framed));
+
+            let chunks = read_frames(&framed).unwrap();
+            assert_eq!(chunks.len(), chunk_count(data.len(), chunk_size));
+
+            for threads in [1, 3] {
+                assert_eq!(decompress_chunks(&chunks, threads, copy).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_data_is_one_empty_chunk() {
+        let framed = identity_frames(b"", 4);
+        let chunks = read_frames(&framed).unwrap();
+        assert_eq!(chunks, vec![Chunk { uncompressed_size: Some(0), data: b"" }]);
+        assert_eq!(chunk_count(0, 4), 1);
+        assert!(decompress_chunks(&chunks, 1, copy).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_and_trailing_frames_are_rejected() {
+        let framed = identity_frames(b"abcdefgh", 3);
+
+        for len in [5, FRAME_HEADER_SIZE + 4, framed.len() - 1] {
+            assert!(matches!(read_frames(&framed[..len]), Err(FramingError::Truncated { .. })));
+        }
+
+        let mut trailing = framed.clone();
+        trailing.push(0);
+        assert_eq!(read_frames(&trailing), Err(FramingError::TrailingData { bytes: 1 }));
+
+        let mut empty = FRAME_MAGIC.to_vec();
+        empty.extend_from_slice(&0u32.to_be_bytes());
+        assert_eq!(read_frames(&empty), Err(FramingError::NoChunks));
+    }
+
+    #[test]
+    fn test_frame_reader_matches_decompress_chunks() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+
+        for chunk_size in [1, 7, 1000] {
+            let framed = identity_frames(&data, chunk_size);
+            for threads in [1, 3] {
+                let mut output = Vec::new();
+                FrameReader::new(&framed[..], copy).with_threads(threads).read_to_end(&mut output).unwrap();
+                assert_eq!(output, data);
+            }
+        }
+
+        let mut output = Vec::new();
+        FrameReader::new(&identity_frames(b"", 4)[..], copy).read_to_end(&mut output).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_frame_reader_rejects_damaged_streams() {
+        let framed = identity_frames(b"abcdefgh", 3);
+        let framing_error = |data: &[u8]| {
+            let err = FrameReader::new(data, copy).read_to_end(&mut Vec::new()).unwrap_err();
+            err.get_ref().and_then(|e| e.downcast_ref::<FramingError>()).cloned()
+        };
+
+        assert!(matches!(framing_error(&framed[..framed.len() - 1]), Some(FramingError::Truncated { chunk: 2, .. })));
+        assert_eq!(framing_error(&framed[4..]), Some(FramingError::NotFramed));
+
+        let mut trailing = framed.clone();
+        trailing.extend_from_slice(b"xy");
+        assert_eq!(framing_error(&trailing), Some(FramingError::TrailingData { bytes: 2 }));
+
+        // Chunks are numbered across batches
+        let err = FrameReader::new(&framed[..framed.len() - 1], copy).with_threads(2)
+            .read_to_end(&mut Vec::new()).unwrap_err();
+        let framing_error = err.get_ref().and_then(|e| e.downcast_ref::<FramingError>());
+        assert!(matches!(framing_error, Some(FramingError::Truncated { chunk: 2, .. })));
+
+        let short = |chunk: &[u8], _: Option<usize>| Ok(chunk[1..].to_vec());
+        let err = FrameReader::new(&framed[..], short).with_threads(2).read_to_end(&mut Vec::new()).unwrap_err();
+        let framing_error = err.get_ref().and_then(|e| e.downcast_ref::<FramingError>());
+        assert!(matches!(framing_error, Some(FramingError::SizeMismatch { chunk: 0, expected: 3, actual: 2 })));
+    }
+
+    #[test]
+    fn test_size_mismatch_is_reported() {
+        let framed = identity_frames(b"abcdefgh", 4);
+        let chunks = read_frames(&framed).unwrap();
+
+        let err = decompress_chunks(&chunks, 1, |chunk, _| Ok(chunk[1..].to_vec())).unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::GlifError::Framing(FramingError::SizeMismatch { chunk: 0, expected: 4, actual: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_oversized_chunk_is_rejected() {
+        let mut framed = FRAME_MAGIC.to_vec();
+        framed.extend_from_slice(&1u32.to_be_bytes());
+        framed.extend_from_slice(&(MAX_FRAME_CHUNK_SIZE as u64 + 1).to_be_bytes());
+        framed.extend_from_slice(&0u64.to_be_bytes());
+
+        assert!(matches!(read_frames(&framed), Err(FramingError::ChunkTooLarge { chunk: 0, .. })));
+    }
+
+    #[test]
+    fn test_legacy_lz4_header_must_be_consistent() {
+        let mut legacy = 2u32.to_be_bytes().to_vec();
+        legacy.extend_from_slice(&(CHUNK_SIZE as u64).to_be_bytes());
+        legacy.extend_from_slice(&(CHUNK_SIZE as u64 + 10).to_be_bytes());
+        for chunk in [&b"first"[..], b"second"] {
+            legacy.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
+            legacy.extend_from_slice(chunk);
+        }
+
+        let chunks = read_legacy_lz4(&legacy).unwrap();
+        assert_eq!(chunks[0].uncompressed_size, Some(CHUNK_SIZE));
+        assert_eq!(chunks[1].uncompressed_size, Some(10));
+        assert_eq!(chunks[1].data, b"second");
+
+        // Any inconsistency means the data is a plain LZ4 block
+        assert!(read_legacy_lz4(&legacy[..legacy.len() - 1]).is_none());
+        assert!(read_legacy_lz4(b"not a chunk header at all").is_none());
+    }
+}
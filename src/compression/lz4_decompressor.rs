@@ -1,150 +1,72 @@
-use rayon::prelude::*;
-use std::io::Result;
+use crate::error::{GlifError, Result};
 
-use super::CHUNK_SIZE;
+use super::{framing, CHUNK_SIZE, resolve_threads};
+
+/// Largest ratio of decompressed to compressed size an LZ4 block can reach
+const LZ4_MAX_EXPANSION: usize = 255;
+
+/// Smallest buffer tried when a block's size is unknown
+const MIN_LZ4_BUFFER: usize = 64 * 1024;
 
 pub fn compress_lz4(data: &[u8]) -> Result<Vec<u8>> {
-    lz4::block::compress(data, None, false)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    Ok(lz4::block::compress(data, None, false)?)
 }
 
+/// Decompress an LZ4 block
+///
+/// Framed streams record each chunk's exact size. Without one (format v1.0
+/// single blocks) the buffer starts at four times the input and doubles
+/// until the block fits, bounded by LZ4's maximum expansion ratio.
 pub fn decompress_lz4(data: &[u8], uncompressed_size: Option<usize>) -> Result<Vec<u8>> {
-    let size = uncompressed_size.unwrap_or_else(|| {
-        // Use a larger estimate for safety - LZ4 can compress very well
-        // so decompressed size could be much larger than compressed size
-        std::cmp::max(data.len() * 100, 1024 * 1024 * 1024) // At least 1GB buffer
-    });
-    lz4::block::decompress(data, Some(size as i32))
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-}
-
-pub fn compress_lz4_multithreaded(data: &[u8], threads: usize) -> Result<Vec<u8>> {
-    if data.len() <= CHUNK_SIZE || threads <= 1 {
-        return compress_lz4(data);
+    if let Some(size) = uncompressed_size {
+        return decompress_lz4_block(data, size);
     }
 
-    // Build thread pool
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    // Split data into chunks
-    let chunks: Vec<&[u8]> = data.chunks(CHUNK_SIZE).collect();
-
-    // Compress chunks in parallel
-    let compressed_chunks: Result<Vec<Vec<u8>>> = pool.install(|| {
-        chunks
-            .par_iter()
-            .map(|chunk| compress_lz4(chunk))
-            .collect()
-    });
-
-    let compressed_chunks = compressed_chunks?;
-
-    // Build the final compressed data with chunk metadata
-    let mut result = Vec::new();
-
-    // Write number of chunks (4 bytes, big-endian)
-    result.extend_from_slice(&(compressed_chunks.len() as u32).to_be_bytes());
+    let limit = data.len()
+        .saturating_mul(LZ4_MAX_EXPANSION)
+        .clamp(MIN_LZ4_BUFFER, i32::MAX as usize);
+    let mut capacity = data.len().saturating_mul(4).clamp(MIN_LZ4_BUFFER, limit);
 
-    // Write original chunk size (for decompression sizing)
-    result.extend_from_slice(&(CHUNK_SIZE as u64).to_be_bytes());
+    loop {
+        match decompress_lz4_block(data, capacity) {
+            Ok(decompressed) => return Ok(decompressed),
+            Err(_) if capacity < limit => capacity = capacity.saturating_mul(2).min(limit),
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-    // Write total uncompressed size
-    result.extend_from_slice(&(data.len() as u64).to_be_bytes());
+fn decompress_lz4_block(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    let size = i32::try_from(size).map_err(|_| GlifError::Malformed(
+        format!("LZ4 block size {} exceeds the block format limit", size)
+    ))?;
+    lz4::block::decompress(data, Some(size)).map_err(GlifError::lz4)
+}
 
-    // Write each chunk with its size
-    for chunk in &compressed_chunks {
-        // Write chunk size (8 bytes, big-endian)
-        result.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
-        // Write chunk data
-        result.extend_from_slice(chunk);
-    }
+/// Compress into a chunk-framed stream, using up to `threads` workers
+pub fn compress_lz4_multithreaded(data: &[u8], threads: usize) -> Result<Vec<u8>> {
+    compress_lz4_chunked(data, CHUNK_SIZE, threads)
+}
 
-    Ok(result)
+/// Compress into a chunk-framed stream of `chunk_size` chunks
+pub fn compress_lz4_chunked(data: &[u8], chunk_size: usize, threads: usize) -> Result<Vec<u8>> {
+    let threads = resolve_threads(threads);
+    framing::compress_chunked(data, chunk_size, threads, compress_lz4)
 }
 
+/// Decompress a chunk-framed stream, or a format v1.0 single block or chunked stream
 pub fn decompress_lz4_multithreaded(data: &[u8], threads: usize) -> Result<Vec<u8>> {
-    if threads <= 1 || data.len() < 20 {
-        return decompress_lz4(data, None);
-    }
+    let threads = resolve_threads(threads);
 
-    // Try to read chunk metadata
-    let num_chunks = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
-
-    if num_chunks == 0 {
+    let chunks = if framing::is_framed(data) {
+        framing::read_frames(data)?
+    } else if let Some(chunks) = framing::read_legacy_lz4(data) {
+        chunks
+    } else {
         return decompress_lz4(data, None);
-    }
-
-    let chunk_size = u64::from_be_bytes([
-        data[4], data[5], data[6], data[7],
-        data[8], data[9], data[10], data[11],
-    ]) as usize;
-
-    let total_size = u64::from_be_bytes([
-        data[12], data[13], data[14], data[15],
-        data[16], data[17], data[18], data[19],
-    ]) as usize;
-
-    // Parse chunk metadata
-    let mut offset = 20;
-    let mut chunk_infos = Vec::new();
-
-    for i in 0..num_chunks {
-        if offset + 8 > data.len() {
-            return decompress_lz4(data, None);
-        }
+    };
 
-        let compressed_size = u64::from_be_bytes([
-            data[offset], data[offset + 1], data[offset + 2], data[offset + 3],
-            data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7],
-        ]) as usize;
-        offset += 8;
-
-        // Check for overflow before addition
-        if compressed_size > data.len() || offset > data.len() - compressed_size {
-            return decompress_lz4(data, None);
-        }
-
-        // Calculate uncompressed size for this chunk (last chunk might be smaller)
-        let uncompressed_size = if i == num_chunks - 1 {
-            total_size - (i * chunk_size)
-        } else {
-            chunk_size
-        };
-
-        chunk_infos.push((offset, compressed_size, uncompressed_size));
-        offset += compressed_size;
-    }
-
-    // Build thread pool
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    // Decompress chunks in parallel
-    let decompressed_chunks: Result<Vec<Vec<u8>>> = pool.install(|| {
-        chunk_infos
-            .par_iter()
-            .map(|(offset, compressed_size, uncompressed_size)| {
-                let chunk = &data[*offset..*offset + *compressed_size];
-                decompress_lz4(chunk, Some(*uncompressed_size))
-            })
-            .collect()
-    });
-
-    let decompressed_chunks = decompressed_chunks?;
-
-    // Merge decompressed chunks
-    let mut result = Vec::with_capacity(total_size);
-
-    for chunk in decompressed_chunks {
-        result.extend_from_slice(&chunk);
-    }
-
-    Ok(result)
+    framing::decompress_chunks(&chunks, threads, decompress_lz4)
 }
 
 #[cfg(test)]
@@ -159,6 +81,26 @@ mod tests {
         assert_eq!(data.as_slice(), decompressed.as_slice());
     }
 
+    #[test]
+    fn test_unknown_size_grows_buffer() {
+        // Compresses far beyond the initial 4x buffer
+        let data = vec![7u8; 4 * 1024 * 1024];
+        let compressed = compress_lz4(&data).unwrap();
+        assert!(compressed.len() * 4 < data.len());
+
+        assert_eq!(decompress_lz4(&compressed, None).unwrap(), data);
+        assert!(decompress_lz4(b"not lz4 data", None).is_err());
+    }
+
+    #[test]
+    fn test_framed_lz4_empty_and_small() {
+        for data in [&b""[..], b"small"] {
+            let compressed = compress_lz4_multithreaded(data, 1).unwrap();
+            assert!(framing::is_framed(&compressed));
+            assert_eq!(decompress_lz4_multithreaded(&compressed, 1).unwrap(), data);
+        }
+    }
+
     #[test]
     fn test_multithreaded_lz4_roundtrip() {
         // Create large enough data to trigger multithreading
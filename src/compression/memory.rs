@@ -0,0 +1,135 @@
+//! Memory estimates for Zstd compression and decompression
+//!
+//! Ultra levels (20-22) use windows of up to 128 MB per worker, so both
+//! sides of the pipeline can need several GB of RAM. The estimates here
+//! mirror Zstd's per-level parameter tables for large inputs.
+
+use super::CHUNK_SIZE;
+
+/// First level that needs Zstd's "ultra" window sizes
+pub const ULTRA_LEVEL_THRESHOLD: i32 = 20;
+
+/// Largest window log used by any supported level (128 MB)
+pub const MAX_WINDOW_LOG: u32 = 27;
+
+/// (window_log, chain_log, hash_log) used by Zstd for inputs over 256 KB
+fn level_parameters(level: i32) -> (u32, u32, u32) {
+    match level {
+        i32::MIN..=1 => (19, 12, 13),
+        2 => (20, 15, 16),
+        3 => (21, 16, 17),
+        4 => (21, 18, 18),
+        5 | 6 => (21, 18, 19),
+        7 | 8 => (21, 19, 20),
+        9..=12 => (21, 20, 21),
+        13..=15 => (22, 21, 22),
+        16 => (22, 22, 22),
+        17 | 18 => (23, 23, 22),
+        19 => (23, 24, 22),
+        20 => (25, 25, 23),
+        21 => (26, 26, 24),
+        _ => (27, 27, 25),
+    }
+}
+
+/// Whether a level is in the ultra range
+pub fn is_ultra_level(level: i32) -> bool {
+    level >= ULTRA_LEVEL_THRESHOLD
+}
+
+/// Window log Zstd uses for a level, capped to the size of a single chunk
+pub fn window_log(level: i32, input_len: usize) -> u32 {
+    let (window_log, _, _) = level_parameters(level);
+    let chunk_len = input_len.clamp(1, CHUNK_SIZE) as u64;
+    // Zstd never uses a window larger than the input (minimum 1 KB)
+    let input_log = (64 - (chunk_len - 1).leading_zeros()).max(10);
+    window_log.min(input_log)
+}
+
+/// Estimated memory requirements for an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Zstd window size in bytes
+    pub window_size: u64,
+
+    /// Bytes needed to compress (input, output and per-worker contexts)
+    pub compression: u64,
+
+    /// Bytes needed to decompress (output and per-worker windows)
+    pub decompression: u64,
+}
+
+impl MemoryEstimate {
+    /// Estimate memory for compressing `input_len` bytes at `level` with `threads` workers
+    pub fn new(level: i32, input_len: usize, threads: usize) -> Self {
+        let window_size = 1u64 << window_log(level, input_len);
+        let workers = workers(input_len, threads);
+        let context = compression_context(level, input_len);
+
+        Self {
+            window_size,
+            compression: input_len as u64 * 2 + context * workers,
+            decompression: decompression_memory(input_len as u64, window_size, threads),
+        }
+    }
+}
+
+/// Bytes one worker's Zstd context needs: its window plus match tables
+fn compression_context(level: i32, input_len: usize) -> u64 {
+    let window_log = window_log(level, input_len);
+    let (_, chain_log, hash_log) = level_parameters(level);
+    (1u64 << window_log) + (4u64 << chain_log.min(window_log + 1)) + (4u64 << hash_log.min(window_log + 1))
+}
+
+/// Bytes needed per `chunk_size` chunk being compressed at `level`: the
+/// chunk, its compressed output and a worker's context
+///
+/// Streaming compression under a memory budget keeps as many chunks in
+/// flight as fit.
+pub fn chunk_compression_memory(level: i32, chunk_size: usize) -> u64 {
+    chunk_size as u64 * 2 + compression_context(level, chunk_size)
+}
+
+/// Estimate memory for decompressing a payload of `payload_size` bytes
+pub fn decompression_memory(payload_size: u64, window_size: u64, threads: usize) -> u64 {
+    let workers = workers(payload_size as usize, threads);
+    payload_size + window_size * workers
+}
+
+fn workers(input_len: usize, threads: usize) -> u64 {
+    let chunks = input_len.div_ceil(CHUNK_SIZE).max(1);
+    chunks.min(threads.max(1)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_log_by_level() {
+        let large = 1024 * 1024 * 1024;
+        assert_eq!(window_log(3, large), 21);
+        assert_eq!(window_log(22, large), MAX_WINDOW_LOG);
+        assert_eq!(window_log(-5, large), 19);
+
+        // Small inputs never get a window larger than themselves
+        assert_eq!(window_log(22, 4096), 12);
+        assert_eq!(window_log(22, 10), 10);
+    }
+
+    #[test]
+    fn test_ultra_estimate_exceeds_default() {
+        let size = 512 * 1024 * 1024;
+        let ultra = MemoryEstimate::new(22, size, 4);
+        let default = MemoryEstimate::new(8, size, 4);
+
+        assert_eq!(ultra.window_size, 128 * 1024 * 1024);
+        assert!(ultra.compression > default.compression);
+        assert!(ultra.decompression > default.decompression);
+        assert!(chunk_compression_memory(22, CHUNK_SIZE) > chunk_compression_memory(8, CHUNK_SIZE));
+        assert!(chunk_compression_memory(1, CHUNK_SIZE) > 2 * CHUNK_SIZE as u64);
+        assert!(chunk_compression_memory(8, 16 * 1024 * 1024) < chunk_compression_memory(8, CHUNK_SIZE));
+        assert!(is_ultra_level(20));
+        assert!(!is_ultra_level(19));
+    }
+}
@@ -1,11 +1,69 @@
 pub mod zstd_compressor;
 pub mod lz4_decompressor;
+pub mod memory;
+pub mod framing;
+pub mod report;
+pub mod fallback;
+pub mod raw;
 
-pub use zstd_compressor::{compress_zstd, compress_zstd_multithreaded, decompress_zstd, decompress_zstd_multithreaded};
-pub use lz4_decompressor::{compress_lz4, compress_lz4_multithreaded, decompress_lz4, decompress_lz4_multithreaded};
+pub use zstd_compressor::{compress_zstd, compress_zstd_chunked, compress_zstd_multithreaded, decompress_zstd, decompress_zstd_into, decompress_zstd_multithreaded};
+pub use lz4_decompressor::{compress_lz4, compress_lz4_chunked, compress_lz4_multithreaded, decompress_lz4, decompress_lz4_multithreaded};
+pub use memory::{MemoryEstimate, is_ultra_level};
+pub use framing::FramingError;
+pub use report::{Codec, CompressionReport, StageTimings};
+pub(crate) use report::Stopwatch;
 
 // Chunk size for multi-threaded processing (128 MB)
 pub const CHUNK_SIZE: usize = 128 * 1024 * 1024;
 
+// Smallest chunk size accepted by configuration validation (1 MB)
+pub const MIN_CHUNK_SIZE: usize = 1024 * 1024;
+
 // Default compression level (balanced)
 pub const DEFAULT_COMPRESSION_LEVEL: i32 = 8;
+
+// Supported Zstd compression level range (negative levels trade ratio for speed)
+pub const MIN_COMPRESSION_LEVEL: i32 = -131072;
+pub const MAX_COMPRESSION_LEVEL: i32 = 22;
+
+// Upper bound on worker threads accepted by configuration validation
+pub const MAX_THREADS: usize = 1024;
+
+// Smallest in-flight chunk budget: one chunk's input and output buffers
+pub const MIN_IN_FLIGHT: usize = 2;
+
+// Bytes from the start of a file compressed to estimate how well it compresses (64 KB)
+pub const ESTIMATE_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Estimated compressed size of `size` bytes starting with `sample`, from
+/// compressing up to `ESTIMATE_SAMPLE_SIZE` bytes of it at level 1
+pub fn estimate_compressed_size(sample: &[u8], size: u64) -> u64 {
+    let sample = &sample[..sample.len().min(ESTIMATE_SAMPLE_SIZE)];
+    if sample.is_empty() {
+        return 0;
+    }
+    let compressed = compress_zstd(sample, 1).map_or(sample.len(), |compressed| compressed.len());
+    (size as f64 * compressed as f64 / sample.len() as f64).round() as u64
+}
+
+/// Resolve a requested thread count, treating 0 as "auto-detect"
+pub fn resolve_threads(threads: usize) -> usize {
+    if threads == 0 {
+        crate::num_cpus::get()
+    } else {
+        threads
+    }
+}
+
+/// Chunks processed at once on `threads` workers within a budget of
+/// `max_in_flight` chunk buffers
+///
+/// A chunk being compressed or decompressed holds two buffers, its input
+/// and its output, so the budget allows half as many chunks. Without a
+/// budget every thread gets a chunk (a budget of two per thread).
+pub fn chunks_within_budget(threads: usize, max_in_flight: Option<usize>) -> usize {
+    match max_in_flight {
+        Some(budget) => threads.min(budget / 2).max(1),
+        None => threads.max(1),
+    }
+}
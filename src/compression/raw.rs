@@ -0,0 +1,171 @@
+//! Plain `.zst` and `.lz4` streams, without a GLIF header
+//!
+//! For systems that only understand zstd, `compress_zstd_stream` writes a
+//! standard `.zst` file: each chunk is compressed on its own worker into
+//! a frame recording its size and checksum, and the frames are simply
+//! concatenated, which `zstd -d` reads as one stream. Going the other
+//! way, `decompress_raw` reads `.zst` files (splitting them into frames
+//! that decompress in parallel) and LZ4 frame files (`.lz4`).
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::error::{GlifError, Result};
+
+use super::framing::{self, Chunk, ZSTD_MAGIC};
+use super::zstd_compressor::frame_content_size;
+use super::{compress_zstd, decompress_zstd};
+
+/// Magic number starting an LZ4 frame
+pub const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+
+/// Format of a stream without a GLIF header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    /// One or more zstd frames (`.zst`)
+    Zstd,
+    /// An LZ4 frame (`.lz4`)
+    Lz4,
+}
+
+impl RawFormat {
+    /// Format of the stream starting with `prefix`, if it is one
+    pub fn detect(prefix: &[u8]) -> Option<RawFormat> {
+        if prefix.starts_with(&ZSTD_MAGIC) {
+            Some(RawFormat::Zstd)
+        } else if prefix.starts_with(&LZ4_FRAME_MAGIC) {
+            Some(RawFormat::Lz4)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RawFormat::Zstd => "zstd",
+            RawFormat::Lz4 => "LZ4",
+        }
+    }
+}
+
+impl fmt::Display for RawFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Compress everything `reader` yields into a `.zst` stream on `writer`,
+/// `chunks_in_flight` chunks of `chunk_size` at a time on `threads`
+/// workers; returns the bytes read
+///
+/// Empty input still writes one (empty) frame, so the output is always a
+/// valid `.zst` file.
+pub fn compress_zstd_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    level: i32,
+    chunk_size: usize,
+    chunks_in_flight: usize,
+    threads: usize,
+) -> Result<u64> {
+    let chunk_size = chunk_size.max(1);
+    let in_flight = chunks_in_flight.max(1);
+    let mut total = 0u64;
+    let mut done = false;
+    while !done {
+        let mut batch = Vec::with_capacity(in_flight);
+        while batch.len() < in_flight && !done {
+            let mut chunk = Vec::new();
+            reader.by_ref().take(chunk_size as u64).read_to_end(&mut chunk)?;
+            done = chunk.len() < chunk_size;
+            if !chunk.is_empty() || (total == 0 && batch.is_empty()) {
+                batch.push(chunk);
+            }
+        }
+
+        let chunks: Vec<&[u8]> = batch.iter().map(Vec::as_slice).collect();
+        for frame in framing::compress_chunks(&chunks, threads, |chunk| compress_zstd(chunk, level))? {
+            writer.write_all(&frame)?;
+        }
+        total += chunks.iter().map(|chunk| chunk.len() as u64).sum::<u64>();
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Split a `.zst` stream into its frames, with the content size of each
+/// frame that records one
+pub fn zstd_frames(mut data: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while !data.is_empty() {
+        let len = zstd::zstd_safe::find_frame_compressed_size(data).map_err(|_| GlifError::Malformed(
+            format!("Invalid or truncated zstd frame at offset {}", offset)
+        ))?;
+        let (frame, rest) = data.split_at(len);
+        frames.push(Chunk { uncompressed_size: frame_content_size(frame), data: frame });
+        data = rest;
+        offset += len;
+    }
+    Ok(frames)
+}
+
+/// Decompress a `.zst` or `.lz4` stream, zstd frames on up to `threads` workers
+pub fn decompress_raw(data: &[u8], threads: usize) -> Result<Vec<u8>> {
+    match RawFormat::detect(data) {
+        Some(RawFormat::Zstd) => {
+            let frames = zstd_frames(data)?;
+            framing::decompress_chunks(&frames, super::resolve_threads(threads), |frame, _| decompress_zstd(frame))
+        }
+        Some(RawFormat::Lz4) => {
+            let mut decoder = lz4::Decoder::new(data).map_err(GlifError::lz4)?;
+            let mut output = Vec::new();
+            decoder.read_to_end(&mut output).map_err(GlifError::lz4)?;
+            decoder.finish().1.map_err(GlifError::lz4)?;
+            Ok(output)
+        }
+        None => Err(GlifError::InvalidInput("Not a zstd or LZ4 stream".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zstd_stream_roundtrip() {
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i| (i % 251) as u8).collect();
+        let mut zst = Vec::new();
+        let read = compress_zstd_stream(&data[..], &mut zst, 3, 1024 * 1024, 2, 2).unwrap();
+        assert_eq!(read, data.len() as u64);
+
+        // A plain zstd decoder reads the concatenated frames as one stream
+        assert_eq!(RawFormat::detect(&zst), Some(RawFormat::Zstd));
+        assert_eq!(zstd::decode_all(&zst[..]).unwrap(), data);
+        let frames = zstd_frames(&zst).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[3].uncompressed_size, Some(17));
+        assert_eq!(decompress_raw(&zst, 4).unwrap(), data);
+
+        // Empty input is one empty frame
+        let mut empty = Vec::new();
+        compress_zstd_stream(&[][..], &mut empty, 3, 1024, 1, 1).unwrap();
+        assert_eq!(zstd_frames(&empty).unwrap().len(), 1);
+        assert!(decompress_raw(&empty, 1).unwrap().is_empty());
+
+        assert!(decompress_raw(&zst[..zst.len() - 1], 1).is_err());
+    }
+
+    #[test]
+    fn test_lz4_frame() {
+        let data = b"an lz4 frame written by another tool ".repeat(1000);
+        let mut encoder = lz4::EncoderBuilder::new().build(Vec::new()).unwrap();
+        encoder.write_all(&data).unwrap();
+        let (lz4, result) = encoder.finish();
+        result.unwrap();
+
+        assert_eq!(RawFormat::detect(&lz4), Some(RawFormat::Lz4));
+        assert_eq!(decompress_raw(&lz4, 1).unwrap(), data);
+        assert!(decompress_raw(b"GLIF01", 1).is_err());
+    }
+}
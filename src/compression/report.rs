@@ -0,0 +1,215 @@
+//! Compression reports
+//!
+//! Sizes, timing and codec details of a single `compress_with_report` call,
+//! so embedders don't have to measure them separately.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Codecs applied to an archive's data, in compression order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// Zstd only (decompression mode 1)
+    Zstd,
+    /// Zstd wrapped in an LZ4 layer for faster decompression (mode 0)
+    ZstdLz4,
+}
+
+impl Codec {
+    /// Codec for a header's decompression mode
+    pub fn from_decompression_mode(mode: u32) -> Self {
+        if mode == 0 { Codec::ZstdLz4 } else { Codec::Zstd }
+    }
+
+    /// Decompression mode recorded in the header for this codec
+    pub fn decompression_mode(self) -> u32 {
+        match self {
+            Codec::ZstdLz4 => 0,
+            Codec::Zstd => 1,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::Zstd => write!(f, "zstd"),
+            Codec::ZstdLz4 => write!(f, "zstd+lz4"),
+        }
+    }
+}
+
+/// Summary of one compression run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionReport {
+    /// Size of the uncompressed input
+    pub input_bytes: u64,
+
+    /// Size of the complete archive, including header and sidecar
+    pub output_bytes: u64,
+
+    /// `output_bytes / input_bytes`, or 0 for empty input (lower is better)
+    pub ratio: f64,
+
+    /// Wall-clock time spent compressing
+    pub elapsed: Duration,
+
+    /// Number of chunks the input was split into
+    pub chunks: usize,
+
+    /// Codecs applied to the data
+    pub codec: Codec,
+
+    /// Time spent in each stage of `elapsed`
+    #[serde(default)]
+    pub stages: StageTimings,
+}
+
+/// Time spent in each stage of a compression run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageTimings {
+    /// Hashing the uncompressed input
+    pub payload_hash: Duration,
+
+    /// Compressing the chunks with Zstd
+    pub zstd: Duration,
+
+    /// Compressing the Zstd stream with LZ4 (zero for `Codec::Zstd`)
+    pub lz4: Duration,
+
+    /// Hashing the archive data and writing the header and sidecar
+    pub assemble: Duration,
+}
+
+impl StageTimings {
+    /// Sum of all stages
+    pub fn total(&self) -> Duration {
+        self.payload_hash + self.zstd + self.lz4 + self.assemble
+    }
+}
+
+/// Times consecutive stages
+///
+/// wasm32-unknown-unknown has no monotonic clock, so every reading is zero there.
+pub(crate) struct Stopwatch {
+    started: Option<Instant>,
+    lap: Option<Instant>,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        let now = (!cfg!(all(target_arch = "wasm32", target_os = "unknown"))).then(Instant::now);
+        Self { started: now, lap: now }
+    }
+
+    /// Time since the previous lap, or since the start for the first
+    pub(crate) fn lap(&mut self) -> Duration {
+        let Some(lap) = self.lap else { return Duration::ZERO };
+        let now = Instant::now();
+        self.lap = Some(now);
+        now - lap
+    }
+
+    /// Time since the start
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started.map(|started| started.elapsed()).unwrap_or_default()
+    }
+}
+
+impl CompressionReport {
+    pub fn new(input_bytes: u64, output_bytes: u64, elapsed: Duration, chunks: usize, codec: Codec) -> Self {
+        let ratio = if input_bytes > 0 {
+            output_bytes as f64 / input_bytes as f64
+        } else {
+            0.0
+        };
+
+        Self { input_bytes, output_bytes, ratio, elapsed, chunks, codec, stages: StageTimings::default() }
+    }
+
+    /// Attach per-stage timings
+    pub fn with_stages(mut self, stages: StageTimings) -> Self {
+        self.stages = stages;
+        self
+    }
+
+    /// Input bytes compressed per second
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.input_bytes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Input bytes compressed per second of Zstd compression alone
+    pub fn zstd_throughput(&self) -> f64 {
+        let seconds = self.stages.zstd.as_secs_f64();
+        if seconds > 0.0 {
+            self.input_bytes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} bytes ({:.2}%) in {:.3}s, {} chunk(s), {}",
+            self.input_bytes,
+            self.output_bytes,
+            self.ratio * 100.0,
+            self.elapsed.as_secs_f64(),
+            self.chunks,
+            self.codec
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_and_throughput() {
+        let report = CompressionReport::new(1000, 250, Duration::from_secs(2), 1, Codec::Zstd);
+        assert_eq!(report.ratio, 0.25);
+        assert_eq!(report.throughput(), 500.0);
+        assert_eq!(report.to_string(), "1000 -> 250 bytes (25.00%) in 2.000s, 1 chunk(s), zstd");
+
+        let empty = CompressionReport::new(0, 200, Duration::ZERO, 1, Codec::ZstdLz4);
+        assert_eq!(empty.ratio, 0.0);
+        assert_eq!(empty.throughput(), 0.0);
+    }
+
+    #[test]
+    fn test_stage_timings() {
+        let stages = StageTimings {
+            payload_hash: Duration::from_millis(100),
+            zstd: Duration::from_millis(500),
+            lz4: Duration::from_millis(300),
+            assemble: Duration::from_millis(100),
+        };
+        let report = CompressionReport::new(1000, 250, Duration::from_secs(1), 1, Codec::ZstdLz4).with_stages(stages);
+        assert_eq!(report.stages.total(), Duration::from_secs(1));
+        assert_eq!(report.zstd_throughput(), 2000.0);
+
+        // Reports serialized before stages were recorded still load
+        let mut json = serde_json::to_value(&report).unwrap();
+        json.as_object_mut().unwrap().remove("stages");
+        let loaded: CompressionReport = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.stages, StageTimings::default());
+    }
+
+    #[test]
+    fn test_codec_modes() {
+        for codec in [Codec::Zstd, Codec::ZstdLz4] {
+            assert_eq!(Codec::from_decompression_mode(codec.decompression_mode()), codec);
+        }
+    }
+}
@@ -1,126 +1,79 @@
-use rayon::prelude::*;
-use std::io::Result;
+use zstd::stream::raw::CParameter;
 
-use super::CHUNK_SIZE;
+use crate::error::{GlifError, Result};
 
-pub fn compress_zstd(data: &[u8], level: i32) -> Result<Vec<u8>> {
-    zstd::encode_all(data, level)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-}
+use super::{framing, CHUNK_SIZE, resolve_threads};
+use super::memory::{is_ultra_level, window_log};
 
-pub fn compress_zstd_multithreaded(data: &[u8], level: i32, threads: usize) -> Result<Vec<u8>> {
-    if data.len() <= CHUNK_SIZE || threads <= 1 {
-        return compress_zstd(data, level);
-    }
+/// Largest uncompressed-to-compressed ratio a frame's recorded content size
+/// is trusted up to when sizing the output buffer; zstd tops out near
+/// 32768:1, so a damaged size can't demand much more than that
+const MAX_FRAME_RATIO: u64 = 1 << 16;
 
-    // Build thread pool with specified number of threads
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    // Split data into chunks
-    let chunks: Vec<&[u8]> = data.chunks(CHUNK_SIZE).collect();
-
-    // Compress chunks in parallel
-    let compressed_chunks: Result<Vec<Vec<u8>>> = pool.install(|| {
-        chunks
-            .par_iter()
-            .map(|chunk| compress_zstd(chunk, level))
-            .collect()
-    });
-
-    let compressed_chunks = compressed_chunks?;
-
-    // Build the final compressed data with chunk metadata
-    let mut result = Vec::new();
-
-    // Write number of chunks (4 bytes, big-endian)
-    result.extend_from_slice(&(compressed_chunks.len() as u32).to_be_bytes());
-
-    // Write each chunk with its size
-    for chunk in &compressed_chunks {
-        // Write chunk size (8 bytes, big-endian)
-        result.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
-        // Write chunk data
-        result.extend_from_slice(chunk);
+/// Compress `data` into one frame that records its content size and ends
+/// with zstd's own checksum, so standalone zstd tools can size and check
+/// frames taken out of an archive
+pub fn compress_zstd(data: &[u8], level: i32) -> Result<Vec<u8>> {
+    let mut compressor = zstd::bulk::Compressor::new(level)?;
+    compressor.set_parameter(CParameter::ContentSizeFlag(true))?;
+    compressor.set_parameter(CParameter::ChecksumFlag(true))?;
+
+    // Ultra levels: pin the window explicitly so it never exceeds the chunk
+    // size and decoders can rely on the window recorded in the sidecar
+    if is_ultra_level(level) {
+        compressor.set_parameter(CParameter::WindowLog(window_log(level, data.len())))?;
     }
-
-    Ok(result)
+    Ok(compressor.compress(data)?)
 }
 
-pub fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>> {
-    zstd::decode_all(data)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+/// Content size recorded in the header of `data`, if `data` is a single
+/// frame recording a plausible one
+pub fn frame_content_size(data: &[u8]) -> Option<usize> {
+    let single_frame = zstd::zstd_safe::find_frame_compressed_size(data).is_ok_and(|len| len == data.len());
+    let size = zstd::zstd_safe::get_frame_content_size(data).ok().flatten()?;
+    (single_frame && size <= (data.len() as u64).saturating_mul(MAX_FRAME_RATIO))
+        .then(|| usize::try_from(size).ok())
+        .flatten()
 }
 
-pub fn decompress_zstd_multithreaded(data: &[u8], threads: usize) -> Result<Vec<u8>> {
-    if threads <= 1 {
-        return decompress_zstd(data);
-    }
-
-    // Read number of chunks
-    if data.len() < 4 {
-        return decompress_zstd(data);
-    }
+/// Compress into a chunk-framed stream, using up to `threads` workers
+pub fn compress_zstd_multithreaded(data: &[u8], level: i32, threads: usize) -> Result<Vec<u8>> {
+    compress_zstd_chunked(data, level, CHUNK_SIZE, threads)
+}
 
-    let num_chunks = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+/// Compress into a chunk-framed stream of `chunk_size` chunks
+pub fn compress_zstd_chunked(data: &[u8], level: i32, chunk_size: usize, threads: usize) -> Result<Vec<u8>> {
+    let threads = resolve_threads(threads);
+    framing::compress_chunked(data, chunk_size, threads, |chunk| compress_zstd(chunk, level))
+}
 
-    if num_chunks == 0 {
-        return decompress_zstd(data);
+/// Decompress one or more frames, into a buffer of exactly the recorded
+/// size when the data is a single frame that records it
+pub fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>> {
+    match frame_content_size(data) {
+        Some(size) => zstd::bulk::decompress(data, size).map_err(GlifError::zstd),
+        None => zstd::decode_all(data).map_err(GlifError::zstd),
     }
+}
 
-    // Parse chunk metadata
-    let mut offset = 4;
-    let mut chunk_infos = Vec::new();
-
-    for _ in 0..num_chunks {
-        if offset + 8 > data.len() {
-            return decompress_zstd(data);
-        }
-
-        let chunk_size = u64::from_be_bytes([
-            data[offset], data[offset + 1], data[offset + 2], data[offset + 3],
-            data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7],
-        ]) as usize;
-        offset += 8;
-
-        if offset + chunk_size > data.len() {
-            return decompress_zstd(data);
-        }
+/// Decompress one frame into `out`, which must be large enough; returns the bytes written
+pub fn decompress_zstd_into(data: &[u8], out: &mut [u8]) -> Result<usize> {
+    zstd::bulk::decompress_to_buffer(data, out).map_err(GlifError::zstd)
+}
 
-        chunk_infos.push((offset, chunk_size));
-        offset += chunk_size;
-    }
+/// Decompress a chunk-framed stream, or a format v1.0 single frame or chunked stream
+pub fn decompress_zstd_multithreaded(data: &[u8], threads: usize) -> Result<Vec<u8>> {
+    let threads = resolve_threads(threads);
 
-    // Build thread pool
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    // Decompress chunks in parallel
-    let decompressed_chunks: Result<Vec<Vec<u8>>> = pool.install(|| {
-        chunk_infos
-            .par_iter()
-            .map(|(offset, size)| {
-                let chunk = &data[*offset..*offset + *size];
-                decompress_zstd(chunk)
-            })
-            .collect()
-    });
-
-    let decompressed_chunks = decompressed_chunks?;
-
-    // Merge decompressed chunks
-    let total_size: usize = decompressed_chunks.iter().map(|c| c.len()).sum();
-    let mut result = Vec::with_capacity(total_size);
-
-    for chunk in decompressed_chunks {
-        result.extend_from_slice(&chunk);
-    }
+    let chunks = if framing::is_framed(data) {
+        framing::read_frames(data)?
+    } else if data.starts_with(&framing::ZSTD_MAGIC) {
+        return decompress_zstd(data);
+    } else {
+        framing::read_legacy_zstd(data)?
+    };
 
-    Ok(result)
+    framing::decompress_chunks(&chunks, threads, |chunk, _| decompress_zstd(chunk))
 }
 
 #[cfg(test)]
@@ -135,6 +88,28 @@ mod tests {
         assert_eq!(data.as_slice(), decompressed.as_slice());
     }
 
+    #[test]
+    fn test_frames_record_size_and_checksum() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 97) as u8).collect();
+        let compressed = compress_zstd(&data, 8).unwrap();
+        assert_eq!(frame_content_size(&compressed), Some(data.len()));
+        // Frame header descriptor: Content_Checksum_flag
+        assert_ne!(compressed[4] & 0x04, 0);
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), data);
+
+        // Damage past the header shows up in zstd's own checksum
+        let mut damaged = compressed.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 0xff;
+        assert!(decompress_zstd(&damaged).is_err());
+
+        // Concatenated frames decompress without a single recorded size
+        let mut frames = compressed.clone();
+        frames.extend_from_slice(&compress_zstd(b"tail", 3).unwrap());
+        assert_eq!(frame_content_size(&frames), None);
+        assert_eq!(decompress_zstd(&frames).unwrap().len(), data.len() + 4);
+    }
+
     #[test]
     fn test_multithreaded_compression_roundtrip() {
         // Create large enough data to trigger multithreading
@@ -147,6 +122,14 @@ mod tests {
         assert_eq!(data, decompressed);
     }
 
+    #[test]
+    fn test_ultra_level_roundtrip() {
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let compressed = compress_zstd(&data, 22).unwrap();
+        assert_eq!(decompress_zstd(&compressed).unwrap(), data);
+        assert_eq!(compressed, compress_zstd(&data, 22).unwrap());
+    }
+
     #[test]
     fn test_deterministic_compression() {
         let data = vec![42u8; 10_000];
@@ -0,0 +1,826 @@
+//! Compression configuration and validation
+
+use std::fmt;
+use std::path::Path;
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compression::{CHUNK_SIZE, DEFAULT_COMPRESSION_LEVEL, MAX_COMPRESSION_LEVEL, MAX_THREADS, MIN_CHUNK_SIZE, MIN_COMPRESSION_LEVEL, MIN_IN_FLIGHT, MemoryEstimate, chunks_within_budget, resolve_threads};
+use crate::compression::framing::MAX_FRAME_CHUNK_SIZE;
+use crate::compression::memory::chunk_compression_memory;
+use crate::num_cpus;
+use crate::format::IconInfo;
+
+/// Configuration for compression
+///
+/// Serializes as a JSON object; fields missing when deserializing take
+/// their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub level: i32,
+    /// Worker threads; 0 means auto-detect
+    pub threads: usize,
+    pub use_lz4_decompression: bool,
+    pub deterministic: bool,
+    /// Mark the archive as holding sensitive data in its sidecar
+    pub sensitive: bool,
+    /// Peak memory for streaming compression (`compress_file`, directories
+    /// and `GlifEncoder`); `None` buffers the whole payload
+    pub max_memory: Option<u64>,
+    /// Chunk buffers held at once by the parallel chunk pipeline, for
+    /// memory-constrained devices; `None` allows two per thread
+    pub max_in_flight: Option<usize>,
+    /// Bytes of input per independently compressed chunk
+    pub chunk_size: usize,
+    /// Preview image stored in the sidecar; not saved with profiles
+    #[serde(skip)]
+    pub icon: Option<IconInfo>,
+    /// Pool chunks are compressed on instead of one built per call, so an
+    /// application's own rayon pool bounds glifzip's parallelism too
+    #[cfg(feature = "parallel")]
+    #[serde(skip)]
+    pub thread_pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_COMPRESSION_LEVEL,
+            threads: num_cpus::get(),
+            use_lz4_decompression: true,
+            deterministic: true,
+            sensitive: false,
+            max_memory: None,
+            max_in_flight: None,
+            chunk_size: CHUNK_SIZE,
+            icon: None,
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn new(level: i32, threads: usize) -> Self {
+        Self {
+            level,
+            threads,
+            ..Self::default()
+        }
+    }
+
+    pub fn fast() -> Self {
+        Self {
+            level: 3,
+            ..Self::default()
+        }
+    }
+
+    pub fn balanced() -> Self {
+        Self::default()
+    }
+
+    pub fn high_compression() -> Self {
+        Self {
+            level: 16,
+            use_lz4_decompression: false,
+            ..Self::default()
+        }
+    }
+
+    /// Estimate memory needed to compress and later decompress `input_len` bytes
+    pub fn memory_estimate(&self, input_len: usize) -> MemoryEstimate {
+        MemoryEstimate::new(self.level, input_len, self.effective_threads())
+    }
+
+    /// Thread count actually used, resolving 0 to the number of available cores
+    pub fn effective_threads(&self) -> usize {
+        resolve_threads(self.threads)
+    }
+
+    /// Run `f` on `thread_pool`, if one was given, so chunk work started
+    /// within it shares that pool
+    pub(crate) fn install<T: Send>(&self, f: impl FnOnce() -> T + Send) -> T {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.thread_pool {
+            return pool.install(f);
+        }
+        f()
+    }
+
+    /// Chunks compressed at once: one per thread, reduced to what fits in
+    /// `max_in_flight` and, when streaming, `max_memory`
+    pub fn chunks_in_flight(&self) -> usize {
+        let threads = chunks_within_budget(self.effective_threads(), self.max_in_flight);
+        match self.max_memory {
+            Some(budget) => threads.min((budget / chunk_compression_memory(self.level, self.chunk_size)) as usize).max(1),
+            None => threads,
+        }
+    }
+
+    /// Read and validate a configuration from the JSON file at `path`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let config: Self = read_json_file(path.as_ref())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Start building a validated configuration
+    pub fn builder() -> CompressionConfigBuilder {
+        CompressionConfigBuilder::default()
+    }
+
+    /// `level` as a typed level, if it is a valid one
+    pub fn compression_level(&self) -> Result<CompressionLevel, ConfigError> {
+        CompressionLevel::new(self.level)
+    }
+
+    /// Check that the configuration can be used for compression
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.compression_level()?;
+
+        if self.threads > MAX_THREADS {
+            return Err(ConfigError::TooManyThreads {
+                threads: self.threads,
+                max: MAX_THREADS,
+            });
+        }
+
+        if !(MIN_CHUNK_SIZE..=MAX_FRAME_CHUNK_SIZE).contains(&self.chunk_size) {
+            return Err(ConfigError::ChunkSizeOutOfRange {
+                chunk_size: self.chunk_size,
+                min: MIN_CHUNK_SIZE,
+                max: MAX_FRAME_CHUNK_SIZE,
+            });
+        }
+
+        if let Some(budget) = self.max_in_flight {
+            if budget < MIN_IN_FLIGHT {
+                return Err(ConfigError::InFlightBudgetTooSmall { budget, min: MIN_IN_FLIGHT });
+            }
+        }
+
+        if let Some(budget) = self.max_memory {
+            let required = chunk_compression_memory(self.level, self.chunk_size);
+            if budget < required {
+                return Err(ConfigError::MemoryBudgetTooSmall { budget, required });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A Zstd compression level
+///
+/// Negative levels are Zstd's fast mode, trading ratio for speed; headers
+/// and sidecars store levels as signed integers, so these round-trip.
+/// `CompressionConfig::level` stays an `i32` so serialized configs keep
+/// their format; `CompressionConfigBuilder::compression_level` takes this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionLevel {
+    /// Fast mode with the given acceleration, i.e. level `-acceleration`
+    /// (1 to 131072; higher is faster)
+    Fastest(u32),
+    /// A standard level, 1 to 21
+    Level(i32),
+    /// Level 22, the highest (an ultra level, see `is_ultra_level`)
+    Max,
+}
+
+impl CompressionLevel {
+    /// The level for a raw Zstd level, rejecting 0 and levels out of range
+    pub fn new(level: i32) -> Result<Self, ConfigError> {
+        match level {
+            0 => Err(ConfigError::ZeroLevel),
+            MIN_COMPRESSION_LEVEL..=-1 => Ok(CompressionLevel::Fastest(level.unsigned_abs())),
+            MAX_COMPRESSION_LEVEL => Ok(CompressionLevel::Max),
+            1..MAX_COMPRESSION_LEVEL => Ok(CompressionLevel::Level(level)),
+            _ => Err(ConfigError::LevelOutOfRange {
+                level,
+                min: MIN_COMPRESSION_LEVEL,
+                max: MAX_COMPRESSION_LEVEL,
+            }),
+        }
+    }
+
+    /// The nearest valid level to `level`; 0 becomes level 1
+    pub fn clamped(level: i32) -> Self {
+        match level {
+            0 => CompressionLevel::Level(1),
+            _ => Self::new(level.clamp(MIN_COMPRESSION_LEVEL, MAX_COMPRESSION_LEVEL)).expect("clamped levels are valid"),
+        }
+    }
+
+    /// The raw Zstd level, clamping variants built with out-of-range values
+    pub fn value(&self) -> i32 {
+        match *self {
+            CompressionLevel::Fastest(acceleration) => {
+                -(acceleration.clamp(1, MIN_COMPRESSION_LEVEL.unsigned_abs()) as i32)
+            }
+            CompressionLevel::Level(level) => level.clamp(1, MAX_COMPRESSION_LEVEL),
+            CompressionLevel::Max => MAX_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        CompressionLevel::Level(DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+impl From<CompressionLevel> for i32 {
+    fn from(level: CompressionLevel) -> i32 {
+        level.value()
+    }
+}
+
+impl TryFrom<i32> for CompressionLevel {
+    type Error = ConfigError;
+
+    fn try_from(level: i32) -> Result<Self, ConfigError> {
+        Self::new(level)
+    }
+}
+
+impl fmt::Display for CompressionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionLevel::Max => f.write_str("max"),
+            level => write!(f, "{}", level.value()),
+        }
+    }
+}
+
+impl std::str::FromStr for CompressionLevel {
+    type Err = String;
+
+    /// A level number, negative for fast mode, or `max`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "max" {
+            return Ok(CompressionLevel::Max);
+        }
+        let level = s.parse::<i32>()
+            .map_err(|_| format!("invalid compression level '{}' (expected a number or max)", s))?;
+        Self::new(level).map_err(|e| e.to_string())
+    }
+}
+
+/// Which SHA256 hashes decompression checks
+///
+/// The archive hash covers the compressed data and catches damage to the
+/// file; the payload hash covers the decompressed data, along with the
+/// per-file hashes of directory archives. Each is a full pass over its
+/// data, so latency-critical consumers of trusted archives can skip one
+/// or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerifyPolicy {
+    /// Both hashes
+    #[default]
+    Full,
+    /// Only the archive hash, checked before decompressing
+    Archive,
+    /// Only the payload (and per-file) hashes
+    Payload,
+    /// No hashes
+    Trusted,
+}
+
+impl VerifyPolicy {
+    pub const ALL: [VerifyPolicy; 4] = [VerifyPolicy::Full, VerifyPolicy::Archive, VerifyPolicy::Payload, VerifyPolicy::Trusted];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VerifyPolicy::Full => "full",
+            VerifyPolicy::Archive => "archive",
+            VerifyPolicy::Payload => "payload",
+            VerifyPolicy::Trusted => "none",
+        }
+    }
+
+    /// Whether the archive hash of the compressed data is checked
+    pub fn checks_archive(&self) -> bool {
+        matches!(self, VerifyPolicy::Full | VerifyPolicy::Archive)
+    }
+
+    /// Whether the payload hash, and per-file hashes, are checked
+    pub fn checks_payload(&self) -> bool {
+        matches!(self, VerifyPolicy::Full | VerifyPolicy::Payload)
+    }
+}
+
+impl fmt::Display for VerifyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for VerifyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        VerifyPolicy::ALL.into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("unknown verify policy '{}' (expected full, archive, payload or none)", s))
+    }
+}
+
+/// Configuration for decompression
+#[derive(Debug, Clone)]
+pub struct DecompressionConfig {
+    /// Worker threads; `None` uses available parallelism
+    pub threads: Option<usize>,
+
+    /// Which SHA256 hashes to check (both by default); the header and
+    /// sidecar are validated either way
+    pub verify: VerifyPolicy,
+
+    /// Chunk buffers held at once by the parallel chunk pipeline; `None`
+    /// allows two per thread
+    pub max_in_flight: Option<usize>,
+
+    /// Memory ceiling in bytes; parallel decompression expected to need
+    /// more falls back to a single thread
+    pub max_memory: Option<u64>,
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        Self {
+            threads: None,
+            verify: VerifyPolicy::Full,
+            max_in_flight: None,
+            max_memory: None,
+        }
+    }
+}
+
+impl DecompressionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the worker thread count; 0 means auto-detect
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = if threads == 0 { None } else { Some(threads) };
+        self
+    }
+
+    /// Skip hash checks for trusted archives where speed matters
+    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
+        self.verify = if verify_hashes { VerifyPolicy::Full } else { VerifyPolicy::Trusted };
+        self
+    }
+
+    /// Choose which hashes are checked
+    pub fn with_verify(mut self, verify: VerifyPolicy) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Hold at most `budget` chunk buffers at once (at least 2; 0 removes the limit)
+    pub fn with_max_in_flight(mut self, budget: usize) -> Self {
+        self.max_in_flight = if budget == 0 { None } else { Some(budget.max(MIN_IN_FLIGHT)) };
+        self
+    }
+
+    /// Decompress on a single thread when more threads would need over `bytes` (0 removes the ceiling)
+    pub fn with_max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = if bytes == 0 { None } else { Some(bytes) };
+        self
+    }
+
+    /// Chunks decompressed at once when streaming: one per thread, reduced
+    /// to what fits in `max_in_flight`
+    pub fn chunks_in_flight(&self) -> usize {
+        chunks_within_budget(resolve_threads(self.threads.unwrap_or(0)), self.max_in_flight)
+    }
+
+    /// Threads to use for a payload of `chunks` chunks, capped by that
+    /// count since chunks are the unit of parallel work, and by `max_in_flight`
+    pub fn threads_for(&self, chunks: usize) -> usize {
+        self.chunks_in_flight().min(chunks.max(1))
+    }
+}
+
+/// Deserialize the JSON file at `path`, naming the file in errors
+pub(crate) fn read_json_file<T: serde::de::DeserializeOwned>(path: &Path) -> std::io::Result<T> {
+    let data = std::fs::read(path)?;
+    serde_json::from_slice(&data).map_err(|e| std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{}: invalid configuration: {}", path.display(), e)
+    ))
+}
+
+/// Error returned when a compression configuration is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Compression level outside the supported range
+    LevelOutOfRange { level: i32, min: i32, max: i32 },
+
+    /// Level 0, which Zstd would silently map to its default level
+    ZeroLevel,
+
+    /// More worker threads than the supported maximum
+    TooManyThreads { threads: usize, max: usize },
+
+    /// A memory budget too small to compress a single chunk
+    MemoryBudgetTooSmall { budget: u64, required: u64 },
+
+    /// Chunk size outside the supported range
+    ChunkSizeOutOfRange { chunk_size: usize, min: usize, max: usize },
+
+    /// In-flight chunk budget too small for one chunk's input and output
+    InFlightBudgetTooSmall { budget: usize, min: usize },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::LevelOutOfRange { level, min, max } => {
+                write!(f, "compression level {} out of range {}..={}", level, min, max)
+            }
+            ConfigError::TooManyThreads { threads, max } => {
+                write!(f, "thread count {} exceeds maximum of {}", threads, max)
+            }
+            ConfigError::MemoryBudgetTooSmall { budget, required } => {
+                write!(f, "memory budget of {} bytes is below the {} bytes needed to compress one chunk at this level", budget, required)
+            }
+            ConfigError::ChunkSizeOutOfRange { chunk_size, min, max } => {
+                write!(f, "chunk size {} out of range {}..={}", chunk_size, min, max)
+            }
+            ConfigError::InFlightBudgetTooSmall { budget, min } => {
+                write!(f, "in-flight chunk budget {} is below the minimum of {} (one chunk's input and output)", budget, min)
+            }
+            ConfigError::ZeroLevel => {
+                write!(f, "compression level 0 is not allowed; use 1-22, or a negative level for fast mode")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for std::io::Error {
+    fn from(e: ConfigError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// Builder for [`CompressionConfig`] that validates on `build()`
+#[derive(Debug, Clone)]
+pub struct CompressionConfigBuilder {
+    level: i32,
+    threads: usize,
+    use_lz4_decompression: bool,
+    deterministic: bool,
+    sensitive: bool,
+    max_memory: Option<u64>,
+    max_in_flight: Option<usize>,
+    chunk_size: usize,
+    icon: Option<IconInfo>,
+    #[cfg(feature = "parallel")]
+    thread_pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Default for CompressionConfigBuilder {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_COMPRESSION_LEVEL,
+            threads: 0,
+            use_lz4_decompression: true,
+            deterministic: true,
+            sensitive: false,
+            max_memory: None,
+            max_in_flight: None,
+            chunk_size: CHUNK_SIZE,
+            icon: None,
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
+        }
+    }
+}
+
+impl CompressionConfigBuilder {
+    /// Zstd compression level (1-22, or negative for fast mode)
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Typed compression level, in place of `level`
+    pub fn compression_level(mut self, level: CompressionLevel) -> Self {
+        self.level = level.value();
+        self
+    }
+
+    /// Worker threads; 0 means auto-detect
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn use_lz4_decompression(mut self, enabled: bool) -> Self {
+        self.use_lz4_decompression = enabled;
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Flag the archive as containing sensitive data
+    pub fn sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// Bound peak memory of streaming compression to `bytes`
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Hold at most `budget` chunk buffers at once (default two per thread)
+    ///
+    /// Each chunk being compressed holds its input and its output, so a
+    /// budget of 4 compresses two chunks at a time whatever the thread count.
+    pub fn max_in_flight(mut self, budget: usize) -> Self {
+        self.max_in_flight = Some(budget);
+        self
+    }
+
+    /// Bytes of input per independently compressed chunk (default 128 MiB)
+    ///
+    /// Smaller chunks spread small payloads across more threads at some
+    /// cost in ratio.
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes;
+        self
+    }
+
+    /// Embed `icon` in the sidecar as the archive's preview image
+    pub fn icon(mut self, icon: IconInfo) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Compress chunks on `pool` rather than on a pool built per call
+    ///
+    /// With threads left at 0 (auto), the thread count is the pool's size.
+    #[cfg(feature = "parallel")]
+    pub fn thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Validate and build the configuration
+    pub fn build(self) -> Result<CompressionConfig, ConfigError> {
+        #[cfg(feature = "parallel")]
+        let threads = match &self.thread_pool {
+            Some(pool) if self.threads == 0 => pool.current_num_threads(),
+            _ => resolve_threads(self.threads),
+        };
+        #[cfg(not(feature = "parallel"))]
+        let threads = resolve_threads(self.threads);
+
+        let config = CompressionConfig {
+            level: self.level,
+            threads,
+            use_lz4_decompression: self.use_lz4_decompression,
+            deterministic: self.deterministic,
+            sensitive: self.sensitive,
+            max_memory: self.max_memory,
+            max_in_flight: self.max_in_flight,
+            chunk_size: self.chunk_size,
+            icon: self.icon,
+            #[cfg(feature = "parallel")]
+            thread_pool: self.thread_pool,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let config = CompressionConfig::builder().build().unwrap();
+        assert_eq!(config.level, DEFAULT_COMPRESSION_LEVEL);
+        assert!(config.threads >= 1);
+        assert!(config.use_lz4_decompression);
+        assert!(config.deterministic);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_level() {
+        let err = CompressionConfig::builder().level(25).build().unwrap_err();
+        assert_eq!(err, ConfigError::LevelOutOfRange { level: 25, min: -131072, max: 22 });
+        assert_eq!(err.to_string(), "compression level 25 out of range -131072..=22");
+
+        assert_eq!(CompressionConfig::builder().level(0).build().unwrap_err(), ConfigError::ZeroLevel);
+        assert!(CompressionConfig::builder().level(-131073).build().is_err());
+        assert!(CompressionConfig::builder().level(22).build().is_ok());
+    }
+
+    #[test]
+    fn test_compression_level() {
+        assert_eq!(CompressionLevel::new(-5).unwrap(), CompressionLevel::Fastest(5));
+        assert_eq!(CompressionLevel::new(22).unwrap(), CompressionLevel::Max);
+        assert_eq!(CompressionLevel::new(0).unwrap_err(), ConfigError::ZeroLevel);
+        assert!(CompressionLevel::new(23).is_err());
+
+        assert_eq!(CompressionLevel::clamped(40), CompressionLevel::Max);
+        assert_eq!(CompressionLevel::clamped(0), CompressionLevel::Level(1));
+        assert_eq!(CompressionLevel::clamped(i32::MIN).value(), MIN_COMPRESSION_LEVEL);
+        assert_eq!(CompressionLevel::Level(99).value(), 22);
+        assert_eq!(CompressionLevel::Fastest(0).value(), -1);
+
+        for text in ["max", "-5", "8"] {
+            assert_eq!(text.parse::<CompressionLevel>().unwrap().to_string(), text);
+        }
+        assert!("fast".parse::<CompressionLevel>().is_err());
+
+        let config = CompressionConfig::builder().compression_level(CompressionLevel::Fastest(3)).build().unwrap();
+        assert_eq!(config.level, -3);
+        assert_eq!(config.compression_level().unwrap(), CompressionLevel::Fastest(3));
+    }
+
+    #[test]
+    fn test_negative_level_roundtrip() {
+        let config = CompressionConfig::builder().level(-5).build().unwrap();
+        let data = b"fast mode fast mode fast mode fast mode".repeat(100);
+
+        let archive = crate::compress(&data, &config).unwrap();
+        let sidecar = crate::verify_archive(&archive).unwrap();
+        assert_eq!(sidecar.archive.compression_level, -5);
+        assert_eq!(crate::decompress(&archive, 1).unwrap(), data);
+    }
+
+    #[test]
+    fn test_builder_zero_threads_is_auto() {
+        let config = CompressionConfig::builder().threads(0).build().unwrap();
+        assert_eq!(config.threads, num_cpus::get());
+
+        let config = CompressionConfig::builder().threads(3).build().unwrap();
+        assert_eq!(config.threads, 3);
+    }
+
+    #[test]
+    fn test_zero_threads_is_auto_in_api() {
+        let config = CompressionConfig::new(3, 0);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.effective_threads(), num_cpus::get());
+
+        let data = b"zero threads means auto".repeat(100);
+        let archive = crate::compress(&data, &config).unwrap();
+        let sidecar = crate::verify_archive(&archive).unwrap();
+        assert_eq!(sidecar.archive.threads as usize, num_cpus::get());
+        assert_eq!(crate::decompress(&archive, 0).unwrap(), data);
+    }
+
+    #[test]
+    fn test_too_many_threads_rejected() {
+        let err = CompressionConfig::builder().threads(MAX_THREADS + 1).build().unwrap_err();
+        assert_eq!(err, ConfigError::TooManyThreads { threads: MAX_THREADS + 1, max: MAX_THREADS });
+    }
+
+    #[test]
+    fn test_memory_budget_limits_chunks_in_flight() {
+        let per_chunk = chunk_compression_memory(8, CHUNK_SIZE);
+        let config = CompressionConfig::builder().threads(8).max_memory(3 * per_chunk + 1).build().unwrap();
+        assert_eq!(config.chunks_in_flight(), 3);
+
+        let config = CompressionConfig::builder().threads(2).max_memory(100 * per_chunk).build().unwrap();
+        assert_eq!(config.chunks_in_flight(), 2);
+        assert_eq!(CompressionConfig::new(8, 4).chunks_in_flight(), 4);
+
+        let err = CompressionConfig::builder().max_memory(per_chunk - 1).build().unwrap_err();
+        assert_eq!(err, ConfigError::MemoryBudgetTooSmall { budget: per_chunk - 1, required: per_chunk });
+    }
+
+    #[test]
+    fn test_in_flight_budget_limits_parallel_chunks() {
+        let config = CompressionConfig::builder().threads(8).max_in_flight(5).build().unwrap();
+        assert_eq!(config.chunks_in_flight(), 2);
+        assert_eq!(CompressionConfig::builder().threads(2).max_in_flight(64).build().unwrap().chunks_in_flight(), 2);
+
+        // The tighter of the chunk and memory budgets wins
+        let per_chunk = chunk_compression_memory(8, CHUNK_SIZE);
+        let config = CompressionConfig::builder().threads(8).max_in_flight(8).max_memory(3 * per_chunk).build().unwrap();
+        assert_eq!(config.chunks_in_flight(), 3);
+
+        let err = CompressionConfig::builder().max_in_flight(1).build().unwrap_err();
+        assert_eq!(err, ConfigError::InFlightBudgetTooSmall { budget: 1, min: MIN_IN_FLIGHT });
+
+        let config = DecompressionConfig::new().with_threads(8).with_max_in_flight(6);
+        assert_eq!(config.chunks_in_flight(), 3);
+        assert_eq!(config.threads_for(100), 3);
+        assert_eq!(config.threads_for(1), 1);
+        assert_eq!(DecompressionConfig::new().with_max_in_flight(1).max_in_flight, Some(MIN_IN_FLIGHT));
+        assert_eq!(DecompressionConfig::new().with_threads(8).with_max_in_flight(0).chunks_in_flight(), 8);
+
+        // Output doesn't depend on the budget
+        let data: Vec<u8> = (0..5 * MIN_CHUNK_SIZE / 2).map(|i| (i % 251) as u8).collect();
+        let tight = CompressionConfig::builder().threads(4).chunk_size(MIN_CHUNK_SIZE).max_in_flight(2).build().unwrap();
+        let loose = CompressionConfig { max_in_flight: None, ..tight.clone() };
+        let archive = crate::compress(&data, &tight).unwrap();
+        assert_eq!(archive, crate::compress(&data, &loose).unwrap());
+
+        let config = DecompressionConfig::new().with_threads(4).with_max_in_flight(2);
+        assert_eq!(crate::decompress_with(&archive, &config).unwrap(), data);
+        let mut streamed = Vec::new();
+        std::io::Read::read_to_end(&mut crate::GlifDecoder::with_config(&archive[..], &config).unwrap(), &mut streamed).unwrap();
+        assert_eq!(streamed, data);
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let config = CompressionConfig::builder().threads(4).chunk_size(MIN_CHUNK_SIZE).build().unwrap();
+        let data: Vec<u8> = (0..3 * MIN_CHUNK_SIZE + 10).map(|i| (i % 251) as u8).collect();
+        let (archive, report) = crate::compress_with_report(&data, &config).unwrap();
+        assert_eq!(report.chunks, 4);
+        assert_eq!(crate::decompress(&archive, 2).unwrap(), data);
+
+        // Smaller chunks also fit more of them in a memory budget
+        let budget = 2 * chunk_compression_memory(8, CHUNK_SIZE);
+        let config = CompressionConfig::builder().threads(8).chunk_size(16 << 20).max_memory(budget).build().unwrap();
+        assert_eq!(config.chunks_in_flight(), 8);
+
+        let err = CompressionConfig::builder().chunk_size(4096).build().unwrap_err();
+        assert_eq!(err, ConfigError::ChunkSizeOutOfRange { chunk_size: 4096, min: MIN_CHUNK_SIZE, max: MAX_FRAME_CHUNK_SIZE });
+        assert!(CompressionConfig::builder().chunk_size(MAX_FRAME_CHUNK_SIZE + 1).build().is_err());
+    }
+
+    #[test]
+    fn test_from_file() {
+        crate::testing::with_temp_dir("config", |dir| {
+            let path = dir.join("config.json");
+            let config = CompressionConfig::builder().level(19).threads(2).max_memory(1 << 30).build()?;
+            std::fs::write(&path, serde_json::to_vec(&config)?)?;
+            let loaded = CompressionConfig::from_file(&path)?;
+            assert_eq!((loaded.level, loaded.threads, loaded.max_memory), (19, 2, Some(1 << 30)));
+
+            // Missing fields take their defaults; invalid settings are rejected
+            std::fs::write(&path, br#"{"level": 3}"#)?;
+            assert_eq!(CompressionConfig::from_file(&path)?.chunk_size, CHUNK_SIZE);
+            std::fs::write(&path, br#"{"level": 0}"#)?;
+            assert_eq!(CompressionConfig::from_file(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+            std::fs::write(&path, br#"{"level": "high"}"#)?;
+            assert_eq!(CompressionConfig::from_file(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_decompression_threads_capped_by_chunks() {
+        let config = DecompressionConfig::new().with_threads(8);
+        assert_eq!(config.threads_for(0), 1);
+        assert_eq!(config.threads_for(1), 1);
+        assert_eq!(config.threads_for(3), 3);
+        assert_eq!(config.threads_for(100), 8);
+
+        let auto = DecompressionConfig::new();
+        assert_eq!(auto.threads, None);
+        assert_eq!(auto.threads_for(100), num_cpus::get().min(100));
+
+        // Archives with small chunks are counted by their chunks, not their size
+        let data: Vec<u8> = (0..5 * MIN_CHUNK_SIZE / 2).map(|i| (i % 251) as u8).collect();
+        let compression = CompressionConfig::builder().threads(4).chunk_size(MIN_CHUNK_SIZE).build().unwrap();
+        let archive = crate::compress(&data, &compression).unwrap();
+        let header = crate::GlifHeader::read(&mut &archive[..]).unwrap();
+        let stream = &archive[header.size() + header.sidecar_size as usize..];
+        assert_eq!(crate::payload_chunk_count(&header, stream), 3);
+        assert_eq!(config.threads_for(crate::payload_chunk_count(&header, stream)), 3);
+        assert_eq!(crate::decompress_with(&archive, &config).unwrap(), data);
+    }
+
+    #[test]
+    fn test_compress_rejects_invalid_config() {
+        let config = CompressionConfig::new(30, 1);
+        let err = crate::compress(b"data", &config).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_caller_thread_pool() {
+        let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+        let config = CompressionConfig::builder().chunk_size(1 << 20).thread_pool(pool).build().unwrap();
+        assert_eq!(config.threads, 2);
+
+        let data = b"shared pool ".repeat(300_000);
+        let archive = crate::compress(&data, &config).unwrap();
+        let own = CompressionConfig::builder().chunk_size(1 << 20).threads(2).build().unwrap();
+        assert_eq!(archive, crate::compress(&data, &own).unwrap());
+        assert_eq!(crate::decompress(&archive, 2).unwrap(), data);
+    }
+}
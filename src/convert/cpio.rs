@@ -0,0 +1,234 @@
+//! cpio "newc" streams for initramfs images
+//!
+//! Writes a directory archive as an SVR4 `newc` cpio stream (the format
+//! the Linux kernel unpacks into its initial root filesystem), optionally
+//! compressed as a single Zstd frame. Modes, owners and modification times
+//! come from the manifest; inode numbers are assigned in path order so the
+//! output is deterministic.
+
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::archive::file_entry::FileType;
+use crate::compression::compress_zstd;
+use crate::DirectoryCompressor;
+
+/// Magic number starting every `newc` header
+pub const NEWC_MAGIC: &[u8] = b"070701";
+
+/// Name of the entry terminating a cpio stream
+pub const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// Size of a `newc` header: the magic and 13 eight-digit hex fields
+pub const NEWC_HEADER_SIZE: usize = 110;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Options for `to_cpio`
+#[derive(Debug, Clone, Default)]
+pub struct CpioOptions {
+    /// Compress the stream as one Zstd frame at this level
+    pub zstd_level: Option<i32>,
+
+    /// Record every entry as owned by root (uid and gid 0), as initramfs images expect
+    pub root_owner: bool,
+}
+
+impl CpioOptions {
+    pub fn with_zstd(mut self, level: i32) -> Self {
+        self.zstd_level = Some(level);
+        self
+    }
+
+    pub fn with_root_owner(mut self, root_owner: bool) -> Self {
+        self.root_owner = root_owner;
+        self
+    }
+}
+
+/// Convert a directory archive into a `newc` cpio stream
+///
+/// Parent directories missing from the manifest are added with mode 0755
+/// so the kernel can create every path in order.
+pub fn to_cpio(archive_data: &[u8], threads: usize, options: &CpioOptions) -> Result<Vec<u8>> {
+    let (manifest, blob) = DirectoryCompressor::read_archive(archive_data, threads)?;
+
+    let listed: BTreeSet<&Path> = manifest.entries.iter().map(|e| e.path.as_path()).collect();
+    let mut writer = NewcWriter::default();
+
+    for entry in manifest.sorted_entries() {
+        for parent in entry.path.ancestors().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            if !parent.as_os_str().is_empty() && !listed.contains(parent) && writer.implied.insert(parent.to_path_buf()) {
+                writer.append(parent, S_IFDIR | 0o755, 0, 0, 0, &[])?;
+            }
+        }
+
+        let (uid, gid) = if options.root_owner { (0, 0) } else { (entry.uid, entry.gid) };
+        let mtime = entry.mtime.timestamp().clamp(0, u32::MAX as i64) as u32;
+        let permissions = entry.mode & 0o7777;
+
+        match entry.file_type {
+            FileType::Directory => {
+                writer.append(&entry.path, S_IFDIR | permissions, uid, gid, mtime, &[])?;
+            }
+            FileType::Symlink => {
+                let target = entry.symlink_target.as_deref().ok_or_else(|| Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Symlink {} has no target", entry.path.display())
+                ))?;
+                writer.append(&entry.path, S_IFLNK | 0o777, uid, gid, mtime, target.as_os_str().as_encoded_bytes())?;
+            }
+            FileType::Regular => {
+                writer.append(&entry.path, S_IFREG | permissions, uid, gid, mtime, entry.data(&blob)?)?;
+            }
+        }
+    }
+
+    let cpio = writer.finish();
+    match options.zstd_level {
+        Some(level) => Ok(compress_zstd(&cpio, level)?),
+        None => Ok(cpio),
+    }
+}
+
+#[derive(Default)]
+struct NewcWriter {
+    output: Vec<u8>,
+    next_inode: u32,
+    implied: BTreeSet<PathBuf>,
+}
+
+impl NewcWriter {
+    fn append(&mut self, path: &Path, mode: u32, uid: u32, gid: u32, mtime: u32, data: &[u8]) -> Result<()> {
+        let name = path.as_os_str().as_encoded_bytes();
+        let size = u32::try_from(data.len()).map_err(|_| Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is too large for a newc cpio entry (4 GB limit)", path.display())
+        ))?;
+
+        self.next_inode += 1;
+        let nlink = if mode & S_IFMT == S_IFDIR { 2 } else { 1 };
+        self.header(self.next_inode, mode, uid, gid, nlink, mtime, size, name);
+        self.output.extend_from_slice(data);
+        self.pad();
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn header(&mut self, inode: u32, mode: u32, uid: u32, gid: u32, nlink: u32, mtime: u32, size: u32, name: &[u8]) {
+        self.output.extend_from_slice(NEWC_MAGIC);
+        // ino, mode, uid, gid, nlink, mtime, filesize, devmajor, devminor,
+        // rdevmajor, rdevminor, namesize (with NUL), check
+        let name_size = name.len() as u32 + 1;
+        for field in [inode, mode, uid, gid, nlink, mtime, size, 0, 0, 0, 0, name_size, 0] {
+            self.output.extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+        self.output.extend_from_slice(name);
+        self.output.push(0);
+        self.pad();
+    }
+
+    fn pad(&mut self) {
+        let padded = self.output.len().next_multiple_of(4);
+        self.output.resize(padded, 0);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.header(0, 0, 0, 0, 1, 0, 0, TRAILER_NAME.as_bytes());
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{archive, MemoryFs, TreeBuilder};
+
+    #[derive(Debug, PartialEq)]
+    struct Parsed {
+        name: String,
+        mode: u32,
+        uid: u32,
+        data: Vec<u8>,
+    }
+
+    fn parse(mut cpio: &[u8]) -> Vec<Parsed> {
+        let start_len = cpio.len();
+        let field = |header: &[u8], i: usize| {
+            u32::from_str_radix(std::str::from_utf8(&header[6 + i * 8..14 + i * 8]).unwrap(), 16).unwrap()
+        };
+        let align = |offset: usize| offset.div_ceil(4) * 4;
+
+        let mut entries = Vec::new();
+        loop {
+            assert!(cpio.starts_with(NEWC_MAGIC));
+            let offset = start_len - cpio.len();
+            let (mode, uid, size, name_size) = (field(cpio, 1), field(cpio, 2), field(cpio, 6) as usize, field(cpio, 11) as usize);
+
+            let name = String::from_utf8(cpio[NEWC_HEADER_SIZE..NEWC_HEADER_SIZE + name_size - 1].to_vec()).unwrap();
+            let data_start = align(offset + NEWC_HEADER_SIZE + name_size) - offset;
+            let data = cpio[data_start..data_start + size].to_vec();
+            cpio = &cpio[align(offset + data_start + size) - offset..];
+
+            if name == TRAILER_NAME {
+                assert!(cpio.is_empty());
+                return entries;
+            }
+            entries.push(Parsed { name, mode, uid, data });
+        }
+    }
+
+    #[test]
+    fn test_newc_layout() {
+        let tree = TreeBuilder::new()
+            .file("init", "#!/bin/sh\n")
+            .mode("init", 0o755)
+            .file("etc/hostname", "glyphos\n")
+            .symlink("bin", "usr/bin")
+            .build();
+
+        let entries = parse(&to_cpio(&archive(&tree), 1, &CpioOptions::default()).unwrap());
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["bin", "etc", "etc/hostname", "init"]);
+
+        assert_eq!(entries[0].mode, S_IFLNK | 0o777);
+        assert_eq!(entries[0].data, b"usr/bin");
+        assert_eq!(entries[1].mode, S_IFDIR | 0o755);
+        assert_eq!(entries[2].data, b"glyphos\n");
+        assert_eq!(entries[3].mode, S_IFREG | 0o755);
+    }
+
+    #[test]
+    fn test_missing_parents_and_root_owner() {
+        let archive_data = archive(&TreeBuilder::new().file("lib/modules/a.ko", "module").build());
+
+        // Drop the listed parents from the manifest to check they are implied
+        let (mut manifest, blob) = DirectoryCompressor::read_archive(&archive_data, 1).unwrap();
+        manifest.entries.retain(|e| e.file_type == FileType::Regular);
+        let mut rebuilt = Vec::new();
+        manifest.write(&mut rebuilt).unwrap();
+        rebuilt.extend(crate::compress(&blob, &crate::CompressionConfig::default()).unwrap());
+
+        let options = CpioOptions::default().with_root_owner(true);
+        let entries = parse(&to_cpio(&rebuilt, 1, &options).unwrap());
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["lib", "lib/modules", "lib/modules/a.ko"]);
+        assert!(entries.iter().all(|e| e.uid == 0));
+
+        assert!(parse(&to_cpio(&archive(&MemoryFs::new()), 1, &options).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_zstd_compressed_stream() {
+        let tree = TreeBuilder::new().file("init", "#!/bin/sh\n").build();
+        let archive_data = archive(&tree);
+
+        let plain = to_cpio(&archive_data, 1, &CpioOptions::default()).unwrap();
+        let compressed = to_cpio(&archive_data, 1, &CpioOptions::default().with_zstd(19)).unwrap();
+        assert_eq!(crate::compression::decompress_zstd(&compressed).unwrap(), plain);
+    }
+}
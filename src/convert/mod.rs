@@ -0,0 +1,9 @@
+//! Conversions between directory archives and other formats
+
+pub mod cpio;
+pub mod oci;
+pub mod table;
+
+pub use cpio::{to_cpio, CpioOptions};
+pub use oci::{from_oci_layer, read_oci_layer, to_oci_layer, LayerTree, OciLayer};
+pub use table::{export_manifest, TableFormat};
@@ -0,0 +1,383 @@
+//! OCI image layers
+//!
+//! Converts directory archives to and from gzip-compressed OCI layer
+//! tarballs. Layers may delete files from lower layers with whiteout
+//! entries (`.wh.<name>`, or `.wh..wh..opq` for a whole directory). A
+//! directory archive is always a complete tree, so exported layers never
+//! contain whiteouts, and imported layers must not contain any unless the
+//! caller chooses to drop them.
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::DateTime;
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
+use tar::{Builder, EntryType, Header};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{FileEntry, FileSource};
+use crate::verification::{calculate_sha256, hex_encode};
+use crate::DirectoryCompressor;
+
+/// Media type of the layers written by `to_oci_layer`
+pub const OCI_LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+/// File name prefix marking a whiteout entry
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Whiteout entry hiding every lower-layer entry of its directory
+pub const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// PAX header key prefix for extended attributes
+const XATTR_PAX_PREFIX: &str = "SCHILY.xattr.";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// A layer tarball and the digests an image manifest and config refer to it by
+#[derive(Debug, Clone)]
+pub struct OciLayer {
+    /// gzip-compressed tar data
+    pub data: Vec<u8>,
+
+    /// `sha256:` digest of `data`, used in the image manifest
+    pub digest: String,
+
+    /// `sha256:` digest of the uncompressed tar, used in the image config
+    pub diff_id: String,
+}
+
+/// Convert a directory archive into an OCI image layer
+///
+/// Entries keep their modes, owners, modification times and extended
+/// attributes (as `SCHILY.xattr` PAX records). Output is deterministic.
+pub fn to_oci_layer(archive_data: &[u8], threads: usize) -> Result<OciLayer> {
+    let (manifest, blob) = DirectoryCompressor::read_archive(archive_data, threads)?;
+
+    let mut builder = Builder::new(Vec::new());
+    for entry in manifest.sorted_entries() {
+        check_not_whiteout(&entry.path)?;
+
+        if !entry.xattrs.is_empty() {
+            let keys: Vec<String> = entry.xattrs.iter().map(|(name, _)| format!("{}{}", XATTR_PAX_PREFIX, name)).collect();
+            builder.append_pax_extensions(keys.iter().map(String::as_str).zip(entry.xattrs.iter().map(|(_, value)| value.as_slice())))?;
+        }
+
+        let mut header = Header::new_gnu();
+        header.set_mode(entry.mode & 0o7777);
+        header.set_uid(entry.uid as u64);
+        header.set_gid(entry.gid as u64);
+        header.set_mtime(entry.mtime.timestamp().max(0) as u64);
+
+        match entry.file_type {
+            FileType::Directory => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, &entry.path, std::io::empty())?;
+            }
+            FileType::Symlink => {
+                let target = entry.symlink_target.as_deref().ok_or_else(|| Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Symlink {} has no target", entry.path.display())
+                ))?;
+                header.set_entry_type(EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.path, target)?;
+            }
+            FileType::Regular => {
+                let data = entry.data(&blob)?;
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, &entry.path, data)?;
+            }
+        }
+    }
+    let tar = builder.into_inner()?;
+
+    let mut encoder = GzBuilder::new().mtime(0).write(Vec::new(), Compression::default());
+    std::io::Write::write_all(&mut encoder, &tar)?;
+    let data = encoder.finish()?;
+
+    Ok(OciLayer {
+        digest: format!("sha256:{}", hex_encode(&calculate_sha256(&data))),
+        diff_id: format!("sha256:{}", hex_encode(&calculate_sha256(&tar))),
+        data,
+    })
+}
+
+/// Files read from an OCI layer, ready to be archived with `compress_source`
+#[derive(Debug, Clone, Default)]
+pub struct LayerTree {
+    entries: BTreeMap<PathBuf, (FileEntry, Vec<u8>)>,
+
+    /// Paths deleted by whiteouts that were dropped while reading
+    pub whiteouts: Vec<PathBuf>,
+}
+
+impl LayerTree {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl FileSource for LayerTree {
+    fn paths(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.entries.keys().cloned().collect())
+    }
+
+    fn entry(&self, path: &Path) -> Result<FileEntry> {
+        self.entries.get(path)
+            .map(|(entry, _)| entry.clone())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} not found in layer", path.display())))
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        self.entries.get(path)
+            .map(|(_, data)| data.clone())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} not found in layer", path.display())))
+    }
+}
+
+/// Read an OCI layer (gzip-compressed or plain tar)
+///
+/// Whiteouts are an error unless `drop_whiteouts` is set, in which case the
+/// paths they delete are listed in `LayerTree::whiteouts`. Hard links become
+/// copies of their target; device nodes and FIFOs are rejected.
+pub fn read_oci_layer(data: &[u8], drop_whiteouts: bool) -> Result<LayerTree> {
+    if data.starts_with(GZIP_MAGIC) {
+        read_layer_tar(GzDecoder::new(data), drop_whiteouts)
+    } else {
+        read_layer_tar(data, drop_whiteouts)
+    }
+}
+
+fn read_layer_tar<R: Read>(reader: R, drop_whiteouts: bool) -> Result<LayerTree> {
+    let mut archive = tar::Archive::new(reader);
+    let mut tree = LayerTree::default();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = layer_path(&entry.path()?)?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+
+        if let Some(deleted) = whiteout_target(&path) {
+            if !drop_whiteouts {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Layer contains whiteout {}; it modifies a lower layer and is not a complete tree", path.display())
+                ));
+            }
+            tree.whiteouts.push(deleted);
+            continue;
+        }
+
+        let mut xattrs = Vec::new();
+        if let Some(extensions) = entry.pax_extensions()? {
+            for extension in extensions {
+                let extension = extension?;
+                if let Some(name) = extension.key().ok().and_then(|key| key.strip_prefix(XATTR_PAX_PREFIX)) {
+                    xattrs.push((name.to_string(), extension.value_bytes().to_vec()));
+                }
+            }
+        }
+        xattrs.sort();
+
+        let header = entry.header();
+        let (file_type, type_bits) = match header.entry_type() {
+            EntryType::Regular | EntryType::Continuous | EntryType::Link => (FileType::Regular, 0o100000),
+            EntryType::Directory => (FileType::Directory, 0o040000),
+            EntryType::Symlink => (FileType::Symlink, 0o120000),
+            other => return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: unsupported tar entry type {:?}", path.display(), other)
+            )),
+        };
+        let mtime = DateTime::from_timestamp(header.mtime()? as i64, 0).unwrap_or_default();
+
+        let mut file_entry = FileEntry {
+            path: path.clone(),
+            file_type,
+            size: 0,
+            mode: type_bits | (header.mode()? & 0o7777),
+            uid: header.uid()? as u32,
+            gid: header.gid()? as u32,
+            mtime,
+            atime: mtime,
+            symlink_target: None,
+            data_offset: 0,
+            sha256: String::new(),
+            xattrs,
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
+        };
+
+        let mut contents = Vec::new();
+        match header.entry_type() {
+            EntryType::Symlink => {
+                file_entry.symlink_target = entry.link_name()?.map(|target| target.into_owned());
+            }
+            EntryType::Link => {
+                let target = entry.link_name()?
+                    .map(|target| layer_path(&target))
+                    .transpose()?
+                    .unwrap_or_default();
+                contents = match tree.entries.get(&target) {
+                    Some((linked, data)) if linked.file_type == FileType::Regular => data.clone(),
+                    _ => return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{}: hard link target {} is not an earlier regular file", path.display(), target.display())
+                    )),
+                };
+            }
+            _ => {
+                entry.read_to_end(&mut contents)?;
+            }
+        }
+        file_entry.size = contents.len() as u64;
+
+        tree.entries.insert(path, (file_entry, contents));
+    }
+
+    Ok(tree)
+}
+
+/// Convert an OCI layer into a directory archive
+pub fn from_oci_layer(data: &[u8], compressor: &DirectoryCompressor, drop_whiteouts: bool) -> Result<(Vec<u8>, LayerTree)> {
+    let tree = read_oci_layer(data, drop_whiteouts)?;
+    let archive = compressor.compress_source(&tree, PathBuf::from("layer"))?;
+    Ok((archive, tree))
+}
+
+/// Normalize a path inside a layer, rejecting absolute paths and `..`
+fn layer_path(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Layer path {} escapes the layer root", path.display())
+            )),
+        }
+    }
+    Ok(normalized)
+}
+
+/// Path a whiteout entry deletes, or `None` if `path` is not a whiteout
+fn whiteout_target(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let parent = path.parent().unwrap_or(Path::new(""));
+    if name == OPAQUE_WHITEOUT {
+        Some(parent.to_path_buf())
+    } else {
+        name.strip_prefix(WHITEOUT_PREFIX).map(|deleted| parent.join(deleted))
+    }
+}
+
+fn check_not_whiteout(path: &Path) -> Result<()> {
+    let whiteout = path.components().any(|component| match component {
+        Component::Normal(name) => name.to_str().is_some_and(|name| name.starts_with(WHITEOUT_PREFIX)),
+        _ => false,
+    });
+
+    if whiteout {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} would be read as a whiteout in an OCI layer", path.display())
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::testing::{archive, MemoryFs, TreeBuilder};
+
+    fn compressor() -> DirectoryCompressor {
+        DirectoryCompressor::new(DirectoryCompressionConfig::default().with_progress(false)).unwrap()
+    }
+
+    fn extract(archive: &[u8]) -> MemoryFs {
+        let mut extracted = MemoryFs::new();
+        DirectoryCompressor::extract_to_sink(archive, &mut extracted, 1).unwrap().into_result().unwrap();
+        extracted
+    }
+
+    #[test]
+    fn test_layer_roundtrip() {
+        let tree = TreeBuilder::new()
+            .file("usr/bin/tool", "#!/bin/sh\n")
+            .mode("usr/bin/tool", 0o755)
+            .file("etc/config", "key = value\n")
+            .xattr("etc/config", "user.origin", "glyphos")
+            .dir("var/empty")
+            .symlink("bin", "usr/bin")
+            .build();
+
+        let layer = to_oci_layer(&archive(&tree), 1).unwrap();
+        assert!(layer.data.starts_with(GZIP_MAGIC));
+        assert_eq!(layer.digest, format!("sha256:{}", hex_encode(&calculate_sha256(&layer.data))));
+        assert_eq!(to_oci_layer(&archive(&tree), 1).unwrap().data, layer.data);
+
+        let (restored, read) = from_oci_layer(&layer.data, &compressor(), false).unwrap();
+        assert!(read.whiteouts.is_empty());
+        assert_eq!(extract(&restored), tree);
+    }
+
+    #[test]
+    fn test_export_rejects_whiteout_names() {
+        let tree = TreeBuilder::new().file("dir/.wh.file", "looks like a whiteout").build();
+        let err = to_oci_layer(&archive(&tree), 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    fn header(entry_type: EntryType, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(0o644);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(0);
+        header.set_size(size);
+        header
+    }
+
+    #[test]
+    fn test_import_whiteouts() {
+        let mut builder = Builder::new(Vec::new());
+        for (path, data) in [("app/keep.txt", &b"kept"[..]), ("app/.wh.removed.txt", b""), ("cache/.wh..wh..opq", b"")] {
+            builder.append_data(&mut header(EntryType::Regular, data.len() as u64), path, data).unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+
+        assert_eq!(read_oci_layer(&tar, false).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let tree = read_oci_layer(&tar, true).unwrap();
+        assert_eq!(tree.paths().unwrap(), vec![PathBuf::from("app/keep.txt")]);
+        assert_eq!(tree.whiteouts, vec![PathBuf::from("app/removed.txt"), PathBuf::from("cache")]);
+    }
+
+    #[test]
+    fn test_import_resolves_hard_links() {
+        let mut builder = Builder::new(Vec::new());
+        builder.append_data(&mut header(EntryType::Regular, 4), "./original", &b"data"[..]).unwrap();
+        builder.append_link(&mut header(EntryType::Link, 0), "./copy", "./original").unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let tree = read_oci_layer(&tar, false).unwrap();
+        assert_eq!(tree.read_file(Path::new("copy")).unwrap(), b"data");
+        assert_eq!(tree.entry(Path::new("copy")).unwrap().file_type, FileType::Regular);
+    }
+}
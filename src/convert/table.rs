@@ -0,0 +1,418 @@
+//! Manifest export as CSV or Parquet tables
+//!
+//! `glifzip list --format csv|parquet` writes one row per manifest entry so
+//! archive composition can be analyzed in data tools. Both formats share
+//! the columns in `COLUMNS`; CSV follows RFC 4180 with RFC 3339 timestamps,
+//! and Parquet files hold a single uncompressed row group of required,
+//! plain-encoded columns with timestamps in microseconds since the epoch.
+
+use std::fmt;
+use std::io::{Result, Write};
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::archive::file_entry::FileType;
+use crate::{ArchiveManifest, FileEntry};
+
+/// Magic bytes at the start and end of a Parquet file
+pub const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+
+/// Columns of an exported manifest, in order
+pub const COLUMNS: [(&str, ColumnKind); 13] = [
+    ("path", ColumnKind::Text),
+    ("type", ColumnKind::Text),
+    ("size", ColumnKind::Integer),
+    ("mode", ColumnKind::Integer),
+    ("uid", ColumnKind::Integer),
+    ("gid", ColumnKind::Integer),
+    ("mtime", ColumnKind::Timestamp),
+    ("atime", ColumnKind::Timestamp),
+    ("symlink_target", ColumnKind::Text),
+    ("data_offset", ColumnKind::Integer),
+    ("sha256", ColumnKind::Text),
+    ("xattr_count", ColumnKind::Integer),
+    ("compressed_size", ColumnKind::Integer),
+];
+
+/// Value type of an exported column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Text,
+    Integer,
+    Timestamp,
+}
+
+/// Output format of `export_manifest`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Parquet,
+}
+
+impl TableFormat {
+    pub const ALL: [TableFormat; 2] = [TableFormat::Csv, TableFormat::Parquet];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl fmt::Display for TableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        TableFormat::ALL.into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown table format '{}' (expected csv or parquet)", s))
+    }
+}
+
+/// Write every manifest entry to `writer` as a table in `format`
+pub fn export_manifest<W: Write>(manifest: &ArchiveManifest, format: TableFormat, mut writer: W) -> Result<()> {
+    match format {
+        TableFormat::Csv => write_csv(manifest, &mut writer),
+        TableFormat::Parquet => writer.write_all(&to_parquet(manifest)),
+    }?;
+    writer.flush()
+}
+
+enum Value {
+    Text(String),
+    Integer(i64),
+    Timestamp(DateTime<Utc>),
+}
+
+/// Values of an entry in `COLUMNS` order
+fn row(entry: &FileEntry) -> [Value; 13] {
+    let file_type = match entry.file_type {
+        FileType::Regular => "regular",
+        FileType::Directory => "directory",
+        FileType::Symlink => "symlink",
+    };
+    let symlink_target = entry.symlink_target.as_ref()
+        .map(|target| target.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    [
+        Value::Text(entry.path.to_string_lossy().into_owned()),
+        Value::Text(file_type.to_string()),
+        Value::Integer(entry.size as i64),
+        Value::Integer(entry.mode as i64),
+        Value::Integer(entry.uid as i64),
+        Value::Integer(entry.gid as i64),
+        Value::Timestamp(entry.mtime),
+        Value::Timestamp(entry.atime),
+        Value::Text(symlink_target),
+        Value::Integer(entry.data_offset as i64),
+        Value::Text(entry.sha256.clone()),
+        Value::Integer(entry.xattrs.len() as i64),
+        Value::Integer(entry.compressed_size.unwrap_or_default() as i64),
+    ]
+}
+
+/// Write the manifest as CSV with a header row
+pub fn write_csv<W: Write>(manifest: &ArchiveManifest, writer: &mut W) -> Result<()> {
+    let header: Vec<&str> = COLUMNS.iter().map(|(name, _)| *name).collect();
+    writeln!(writer, "{}", header.join(","))?;
+
+    for entry in &manifest.entries {
+        let fields: Vec<String> = row(entry).into_iter()
+            .map(|value| match value {
+                Value::Text(text) => csv_field(&text),
+                Value::Integer(n) => n.to_string(),
+                Value::Timestamp(time) => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            })
+            .collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a field holding a delimiter, quote or line break, doubling its quotes
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// Parquet enum values used below (parquet.thrift)
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_REQUIRED: i32 = 0;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MICROS: i32 = 10;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+/// Encode the manifest as a Parquet file
+pub fn to_parquet(manifest: &ArchiveManifest) -> Vec<u8> {
+    let rows: Vec<[Value; 13]> = manifest.entries.iter().map(row).collect();
+    let num_rows = rows.len() as i64;
+
+    let mut output = PARQUET_MAGIC.to_vec();
+    let mut column_chunks = Vec::new();
+    let mut row_group_size = 0;
+
+    for (column, (name, kind)) in COLUMNS.iter().enumerate() {
+        // A single plain-encoded data page; required columns have no level data
+        let mut page = Vec::new();
+        for values in &rows {
+            match &values[column] {
+                Value::Text(text) => {
+                    page.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                    page.extend_from_slice(text.as_bytes());
+                }
+                Value::Integer(n) => page.extend_from_slice(&n.to_le_bytes()),
+                Value::Timestamp(time) => page.extend_from_slice(&time.timestamp_micros().to_le_bytes()),
+            }
+        }
+
+        let page_header = Thrift::Struct(vec![
+            (1, Thrift::I32(PAGE_DATA)),
+            (2, Thrift::I32(page.len() as i32)),
+            (3, Thrift::I32(page.len() as i32)),
+            (5, Thrift::Struct(vec![
+                (1, Thrift::I32(num_rows as i32)),
+                (2, Thrift::I32(ENCODING_PLAIN)),
+                (3, Thrift::I32(ENCODING_RLE)),
+                (4, Thrift::I32(ENCODING_RLE)),
+            ])),
+        ]).encode();
+
+        let page_offset = output.len() as i64;
+        let chunk_size = (page_header.len() + page.len()) as i64;
+        output.extend_from_slice(&page_header);
+        output.extend_from_slice(&page);
+        row_group_size += chunk_size;
+
+        column_chunks.push(Thrift::Struct(vec![
+            (2, Thrift::I64(page_offset)),
+            (3, Thrift::Struct(vec![
+                (1, Thrift::I32(physical_type(*kind))),
+                (2, Thrift::List(vec![Thrift::I32(ENCODING_PLAIN), Thrift::I32(ENCODING_RLE)])),
+                (3, Thrift::List(vec![Thrift::Binary(name.as_bytes().to_vec())])),
+                (4, Thrift::I32(CODEC_UNCOMPRESSED)),
+                (5, Thrift::I64(num_rows)),
+                (6, Thrift::I64(chunk_size)),
+                (7, Thrift::I64(chunk_size)),
+                (9, Thrift::I64(page_offset)),
+            ])),
+        ]));
+    }
+
+    let mut schema = vec![Thrift::Struct(vec![
+        (4, Thrift::Binary(b"schema".to_vec())),
+        (5, Thrift::I32(COLUMNS.len() as i32)),
+    ])];
+    for (name, kind) in COLUMNS {
+        let mut element = vec![
+            (1, Thrift::I32(physical_type(kind))),
+            (3, Thrift::I32(REPETITION_REQUIRED)),
+            (4, Thrift::Binary(name.as_bytes().to_vec())),
+        ];
+        match kind {
+            ColumnKind::Text => element.push((6, Thrift::I32(CONVERTED_UTF8))),
+            ColumnKind::Timestamp => element.push((6, Thrift::I32(CONVERTED_TIMESTAMP_MICROS))),
+            ColumnKind::Integer => {}
+        }
+        schema.push(Thrift::Struct(element));
+    }
+
+    let metadata = Thrift::Struct(vec![
+        (1, Thrift::I32(1)),
+        (2, Thrift::List(schema)),
+        (3, Thrift::I64(num_rows)),
+        (4, Thrift::List(vec![Thrift::Struct(vec![
+            (1, Thrift::List(column_chunks)),
+            (2, Thrift::I64(row_group_size)),
+            (3, Thrift::I64(num_rows)),
+        ])])),
+        (6, Thrift::Binary(format!("glifzip version {}", env!("CARGO_PKG_VERSION")).into_bytes())),
+    ]).encode();
+
+    output.extend_from_slice(&metadata);
+    output.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    output.extend_from_slice(PARQUET_MAGIC);
+    output
+}
+
+fn physical_type(kind: ColumnKind) -> i32 {
+    match kind {
+        ColumnKind::Text => TYPE_BYTE_ARRAY,
+        ColumnKind::Integer | ColumnKind::Timestamp => TYPE_INT64,
+    }
+}
+
+/// Thrift values, written with the compact protocol Parquet metadata uses
+enum Thrift {
+    I32(i32),
+    I64(i64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    /// Fields in ascending id order
+    Struct(Vec<(i16, Thrift)>),
+}
+
+impl Thrift {
+    fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write(&mut output);
+        output
+    }
+
+    /// Compact protocol type id
+    fn type_id(&self) -> u8 {
+        match self {
+            Thrift::I32(_) => 5,
+            Thrift::I64(_) => 6,
+            Thrift::Binary(_) => 8,
+            Thrift::List(_) => 9,
+            Thrift::Struct(_) => 12,
+        }
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        match self {
+            Thrift::I32(n) => write_varint(output, zigzag(*n as i64)),
+            Thrift::I64(n) => write_varint(output, zigzag(*n)),
+            Thrift::Binary(bytes) => {
+                write_varint(output, bytes.len() as u64);
+                output.extend_from_slice(bytes);
+            }
+            Thrift::List(items) => {
+                // Lists here are never empty, and hold a single type
+                let element_type = items.first().map_or(12, Thrift::type_id);
+                if items.len() < 15 {
+                    output.push(((items.len() as u8) << 4) | element_type);
+                } else {
+                    output.push(0xF0 | element_type);
+                    write_varint(output, items.len() as u64);
+                }
+                for item in items {
+                    item.write(output);
+                }
+            }
+            Thrift::Struct(fields) => {
+                let mut last_id = 0;
+                for (id, value) in fields {
+                    let delta = id - last_id;
+                    if (1..=15).contains(&delta) {
+                        output.push(((delta as u8) << 4) | value.type_id());
+                    } else {
+                        output.push(value.type_id());
+                        write_varint(output, zigzag(*id as i64));
+                    }
+                    value.write(output);
+                    last_id = *id;
+                }
+                output.push(0);
+            }
+        }
+    }
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn write_varint(output: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        output.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    output.push(n as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn manifest() -> ArchiveManifest {
+        let mut manifest = ArchiveManifest::new(PathBuf::from("/data"));
+        let mtime = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        let entry = |path: &str, file_type, size| FileEntry {
+            path: PathBuf::from(path),
+            file_type,
+            size,
+            mode: 0o644,
+            uid: 501,
+            gid: 20,
+            mtime,
+            atime: mtime,
+            symlink_target: None,
+            data_offset: 0,
+            sha256: String::new(),
+            xattrs: Vec::new(),
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
+        };
+        manifest.add_entry(entry("docs", FileType::Directory, 0));
+        manifest.add_entry(FileEntry { sha256: "ab".repeat(32), ..entry("docs/a, \"b\".txt", FileType::Regular, 12) });
+        manifest.add_entry(FileEntry { symlink_target: Some(PathBuf::from("docs")), ..entry("link", FileType::Symlink, 0) });
+        manifest
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let mut csv = Vec::new();
+        export_manifest(&manifest(), TableFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "path,type,size,mode,uid,gid,mtime,atime,symlink_target,data_offset,sha256,xattr_count,compressed_size");
+        assert_eq!(lines[1], "docs,directory,0,420,501,20,2026-01-02T03:04:05Z,2026-01-02T03:04:05Z,,0,,0,0");
+        assert!(lines[2].starts_with("\"docs/a, \"\"b\"\".txt\",regular,12,"));
+        assert!(lines[3].starts_with("link,symlink,0,") && lines[3].contains(",docs,"));
+    }
+
+    #[test]
+    fn test_parquet_layout() {
+        let parquet = to_parquet(&manifest());
+        assert_eq!(&parquet[..4], PARQUET_MAGIC);
+        assert_eq!(&parquet[parquet.len() - 4..], PARQUET_MAGIC);
+
+        let footer_len = u32::from_le_bytes(parquet[parquet.len() - 8..parquet.len() - 4].try_into().unwrap()) as usize;
+        let footer = &parquet[parquet.len() - 8 - footer_len..parquet.len() - 8];
+        // FileMetaData opens with version 1, then the schema list of 14 elements
+        assert_eq!(&footer[..3], &[0x15, 0x02, 0x19]);
+        assert_eq!(footer[3], (14 << 4) | 12);
+
+        // The path column's plain values follow its page header
+        let mut paths = Vec::new();
+        for path in ["docs", "docs/a, \"b\".txt", "link"] {
+            paths.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            paths.extend_from_slice(path.as_bytes());
+        }
+        assert!(parquet.windows(paths.len()).any(|window| window == paths));
+
+        assert_eq!("parquet".parse(), Ok(TableFormat::Parquet));
+        assert!("xlsx".parse::<TableFormat>().is_err());
+    }
+
+    #[test]
+    fn test_thrift_compact_encoding() {
+        assert_eq!(Thrift::I32(-1).encode(), [1]);
+        assert_eq!(Thrift::I64(300).encode(), [0xD8, 0x04]);
+        // A field id jump past 15 uses the long form
+        let encoded = Thrift::Struct(vec![(1, Thrift::I32(1)), (20, Thrift::Binary(b"x".to_vec()))]).encode();
+        assert_eq!(encoded, [0x15, 0x02, 0x08, 0x28, 0x01, b'x', 0x00]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Text encodings of binary data
+//!
+//! Hashes are written as lowercase hex. Binary data kept in text, such as
+//! armored archives, clipboard payloads, sidecar icons and the values of
+//! extended attributes in manifests, is standard base64 with padding.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::error::{GlifError, Result};
+
+/// Lowercase hex of `bytes`
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes of the hex string `s`, in either case; its length must be even
+pub fn hex_decode(s: &str) -> Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return Err(GlifError::InvalidInput(format!("Invalid hex string length: {} (expected an even length)", s.len())));
+    }
+    s.as_bytes()
+        .chunks_exact(2)
+        .map(|pair| match (hex_digit(pair[0]), hex_digit(pair[1])) {
+            (Some(high), Some(low)) => Ok(high << 4 | low),
+            _ => Err(GlifError::InvalidInput(format!("Invalid hex string: {:?} is not a hex byte", String::from_utf8_lossy(pair)))),
+        })
+        .collect()
+}
+
+/// Bytes of the hex string `s`, which must encode exactly `N` of them
+pub fn hex_decode_array<const N: usize>(s: &str) -> Result<[u8; N]> {
+    if s.len() != 2 * N {
+        return Err(GlifError::InvalidInput(format!("Invalid hex string length: {} (expected {})", s.len(), 2 * N)));
+    }
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(&hex_decode(s)?);
+    Ok(bytes)
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Standard base64 of `bytes`, with padding
+pub fn base64_encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+/// Bytes of the standard base64 string `s`
+pub fn base64_decode(s: &str) -> Result<Vec<u8>> {
+    STANDARD.decode(s).map_err(|e| GlifError::InvalidInput(format!("Invalid base64: {}", e)))
+}
+
+/// Serde helpers for a list of extended attributes, writing each value as
+/// a base64 string
+///
+/// Values written by earlier versions as arrays of numbers are read too.
+pub mod xattrs {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Base64(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(xattrs: &[(String, Vec<u8>)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(xattrs.iter().map(|(name, value)| (name, super::base64_encode(value))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, Vec<u8>)>, D::Error> {
+        Vec::<(String, Value)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::Base64(text) => super::base64_decode(&text)
+                    .map(|value| (name, value))
+                    .map_err(|e| D::Error::custom(format!("extended attribute value: {}", e))),
+                Value::Bytes(value) => Ok((name, value)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex_encode(&[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(hex_decode("00AB7f").unwrap(), vec![0x00, 0xab, 0x7f]);
+        assert!(hex_decode("").unwrap().is_empty());
+        assert!(hex_decode("abc").is_err());
+        assert!(hex_decode("zz").is_err());
+        assert!(hex_decode("é0").is_err());
+
+        let digest = [0x5au8; 32];
+        assert_eq!(hex_decode_array::<32>(&hex_encode(&digest)).unwrap(), digest);
+        assert!(hex_decode_array::<32>("5a5a").unwrap_err().to_string().contains("expected 64"));
+    }
+
+    #[test]
+    fn test_base64() {
+        for data in [&b""[..], b"f", b"fo", b"foo", &[0xff, 0x00, 0xfe, 0x01]] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+        }
+        assert_eq!(base64_encode(b"glif"), "Z2xpZg==");
+        assert!(base64_decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_xattrs_serde() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Entry {
+            #[serde(with = "xattrs")]
+            xattrs: Vec<(String, Vec<u8>)>,
+        }
+
+        let entry = Entry { xattrs: vec![("user.tag".to_string(), b"red".to_vec())] };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(json, r#"{"xattrs":[["user.tag","cmVk"]]}"#);
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap().xattrs, entry.xattrs);
+
+        // Arrays of numbers, as earlier versions wrote, still read
+        let old = serde_json::from_str::<Entry>(r#"{"xattrs":[["user.tag",[114,101,100]]]}"#).unwrap();
+        assert_eq!(old.xattrs, entry.xattrs);
+        assert!(serde_json::from_str::<Entry>(r#"{"xattrs":[["user.tag","???"]]}"#).is_err());
+    }
+}
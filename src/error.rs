@@ -0,0 +1,266 @@
+//! Error type for archive reading, writing and verification
+//!
+//! `GlifError` separates damaged or invalid archives from I/O failures
+//! without matching on messages. It converts to and from `std::io::Error`
+//! in both directions, so it crosses `Read`/`Write` implementations and
+//! `std::io::Result` code with `?` and comes back out with its variant
+//! intact.
+
+use std::io;
+use std::path::PathBuf;
+
+use crate::compression::FramingError;
+use crate::config::ConfigError;
+use crate::format::header::{format_version, MIN_GLIF_VERSION};
+use crate::format::sidecar::GLIFZIP_VERSION;
+use crate::verification::CorruptionError;
+
+/// Result type of the archive modules
+pub type Result<T> = std::result::Result<T, GlifError>;
+
+/// Error raised while reading, writing or verifying an archive
+#[derive(Debug, thiserror::Error)]
+pub enum GlifError {
+    /// Failure of the underlying reader, writer or filesystem
+    #[error(transparent)]
+    Io(io::Error),
+
+    /// The data does not start with the GLIF magic number
+    #[error("Invalid GLIF magic number")]
+    InvalidMagic,
+
+    /// The header format version is outside the range this build reads;
+    /// `max_supported` is the newest version it knows, and every minor
+    /// revision of that major version is read too
+    #[error(
+        "Unsupported GLIF format v{} (this glifzip {} reads v{} to v{}.x): {}",
+        format_version(*.found),
+        GLIFZIP_VERSION,
+        format_version(MIN_GLIF_VERSION),
+        .max_supported >> 8,
+        version_hint(*.found, *.max_supported)
+    )]
+    UnsupportedVersion { found: u32, max_supported: u32 },
+
+    /// The sidecar asks for a newer glifzip than this one
+    #[error(
+        "Archive requires glifzip >= {} (created by glifzip {}), but this is glifzip {}; please upgrade",
+        .required,
+        .created_by,
+        GLIFZIP_VERSION
+    )]
+    ReaderTooOld { required: String, created_by: String },
+
+    /// The header marks features this build can't read as required
+    #[error(
+        "Archive requires {}, which this glifzip {} does not support; please upgrade",
+        .features.join(", "),
+        GLIFZIP_VERSION
+    )]
+    UnsupportedFeatures { features: Vec<String> },
+
+    /// The header checksum does not match the header fields
+    #[error("Header checksum mismatch")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    /// The payload SHA256 hash does not match the header
+    #[error("SHA256 hash mismatch. Expected: {expected}, Got: {actual}")]
+    HashMismatch { expected: String, actual: String },
+
+    /// A directory archive entry does not match its recorded SHA256 hash
+    #[error("File integrity check failed for {}: expected {}, got {}", .path.display(), .expected, .actual)]
+    EntryHashMismatch { path: PathBuf, expected: String, actual: String },
+
+    /// A directory archive's manifest does not match the SHA256 hash in
+    /// its header
+    #[error("Manifest hash mismatch: expected {expected}, got {actual}")]
+    ManifestHashMismatch { expected: String, actual: String },
+
+    /// The archive data hash does not match; carries the damaged regions
+    #[error(transparent)]
+    Corrupt(Box<CorruptionError>),
+
+    /// The decompressed data has a different size than recorded
+    #[error("Decompressed size mismatch: expected {expected}, got {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    /// Invalid chunk framing in the compressed stream
+    #[error(transparent)]
+    Framing(#[from] FramingError),
+
+    /// A compressed chunk could not be decoded
+    #[error("{codec} decompression failed: {source}")]
+    Decode { codec: &'static str, source: io::Error },
+
+    /// Invalid JSON in a sidecar, manifest or report
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The archive ends before a structure it declares
+    #[error("{0}")]
+    Truncated(String),
+
+    /// Any other structurally invalid archive data
+    #[error("{0}")]
+    Malformed(String),
+
+    /// An invalid compression configuration
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    /// An invalid argument, such as an entry path outside the archive
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+impl GlifError {
+    /// The `std::io::ErrorKind` this error maps to
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            GlifError::Io(e) => e.kind(),
+            GlifError::UnsupportedVersion { .. } | GlifError::ReaderTooOld { .. } | GlifError::UnsupportedFeatures { .. } => io::ErrorKind::Unsupported,
+            GlifError::Truncated(_) => io::ErrorKind::UnexpectedEof,
+            GlifError::Config(_) | GlifError::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+
+    /// Whether the archive itself is damaged or invalid, as opposed to an
+    /// I/O failure, an unsupported version or a caller error
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            GlifError::Io(_)
+            | GlifError::UnsupportedVersion { .. }
+            | GlifError::ReaderTooOld { .. }
+            | GlifError::UnsupportedFeatures { .. }
+            | GlifError::Config(_)
+            | GlifError::InvalidInput(_) => false,
+            GlifError::InvalidMagic
+            | GlifError::ChecksumMismatch { .. }
+            | GlifError::HashMismatch { .. }
+            | GlifError::EntryHashMismatch { .. }
+            | GlifError::ManifestHashMismatch { .. }
+            | GlifError::Corrupt(_)
+            | GlifError::SizeMismatch { .. }
+            | GlifError::Framing(_)
+            | GlifError::Decode { .. }
+            | GlifError::Json(_)
+            | GlifError::Truncated(_)
+            | GlifError::Malformed(_) => true,
+        }
+    }
+
+    /// The localized corruption report, for an archive hash mismatch
+    pub fn corruption(&self) -> Option<&CorruptionError> {
+        match self {
+            GlifError::Corrupt(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Wrap a Zstd decoding failure
+    pub(crate) fn zstd(source: io::Error) -> Self {
+        GlifError::Decode { codec: "Zstd", source }
+    }
+
+    /// Wrap an LZ4 decoding failure
+    pub(crate) fn lz4(source: io::Error) -> Self {
+        GlifError::Decode { codec: "LZ4", source }
+    }
+}
+
+impl From<CorruptionError> for GlifError {
+    fn from(error: CorruptionError) -> Self {
+        GlifError::Corrupt(Box::new(error))
+    }
+}
+
+fn version_hint(found: u32, max_supported: u32) -> &'static str {
+    if found > max_supported {
+        "the archive was created by a newer glifzip; please upgrade"
+    } else {
+        "the archive predates this format"
+    }
+}
+
+/// Take the error of type `T` carried by `error`, or give `error` back
+fn take_inner<T: std::error::Error + Send + Sync + 'static>(error: io::Error) -> std::result::Result<T, io::Error> {
+    if !error.get_ref().is_some_and(|e| e.is::<T>()) {
+        return Err(error);
+    }
+    let kind = error.kind();
+    match error.into_inner().map(|e| e.downcast::<T>()) {
+        Some(Ok(inner)) => Ok(*inner),
+        Some(Err(inner)) => Err(io::Error::new(kind, inner)),
+        None => Err(kind.into()),
+    }
+}
+
+impl From<io::Error> for GlifError {
+    /// Recover the typed error from an `io::Error` that carries one
+    fn from(error: io::Error) -> Self {
+        let error = match take_inner::<GlifError>(error) {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let error = match take_inner::<CorruptionError>(error) {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let error = match take_inner::<FramingError>(error) {
+            Ok(e) => return GlifError::Framing(e),
+            Err(e) => e,
+        };
+        match take_inner::<ConfigError>(error) {
+            Ok(e) => GlifError::Config(e),
+            Err(e) => GlifError::Io(e),
+        }
+    }
+}
+
+impl From<GlifError> for io::Error {
+    /// Carry the error inside an `io::Error` of the matching kind
+    ///
+    /// Corruption, framing and configuration errors are carried as
+    /// themselves, so `CorruptionError::from_io_error` keeps working.
+    fn from(error: GlifError) -> Self {
+        match error {
+            GlifError::Io(e) => e,
+            GlifError::Corrupt(e) => e.into_io_error(),
+            GlifError::Framing(e) => e.into(),
+            GlifError::Config(e) => e.into(),
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::GLIF_VERSION;
+
+    #[test]
+    fn test_round_trip_through_io_error() {
+        let error = GlifError::HashMismatch { expected: "aa".to_string(), actual: "bb".to_string() };
+        let io_error = io::Error::from(error);
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(GlifError::from(io_error), GlifError::HashMismatch { .. }));
+
+        let io_error = io::Error::from(GlifError::Framing(FramingError::NoChunks));
+        assert!(matches!(GlifError::from(io_error), GlifError::Framing(FramingError::NoChunks)));
+
+        let plain = GlifError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(plain, GlifError::Io(_)));
+        assert_eq!(plain.kind(), io::ErrorKind::NotFound);
+        assert!(!plain.is_corruption());
+    }
+
+    #[test]
+    fn test_classification() {
+        assert!(GlifError::InvalidMagic.is_corruption());
+        assert!(GlifError::Truncated("short".to_string()).is_corruption());
+        assert!(!GlifError::UnsupportedVersion { found: 0xff00, max_supported: GLIF_VERSION }.is_corruption());
+        assert_eq!(GlifError::UnsupportedVersion { found: 0xff00, max_supported: GLIF_VERSION }.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(GlifError::InvalidInput("bad".to_string()).kind(), io::ErrorKind::InvalidInput);
+    }
+}
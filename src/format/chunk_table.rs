@@ -0,0 +1,237 @@
+//! Chunk tables for random access
+//!
+//! A framed stream records each chunk's sizes just ahead of its data, so
+//! finding the chunk that holds a payload offset means walking the chunk
+//! headers from the start, and in LZ4 mode decompressing the LZ4 chunks
+//! the Zstd chunk headers sit in. A v2 header can instead carry the
+//! location of every chunk in a `TLV_CHUNK_TABLE` field, so readers seek
+//! straight to the chunks covering a byte range.
+//!
+//! The field holds the number of Zstd chunks and of LZ4 chunks (0 outside
+//! LZ4 mode) as big-endian u32s, then each chunk's uncompressed and
+//! compressed size as big-endian u32s, Zstd chunks first. Offsets follow
+//! from the sizes and the framing. Archives whose table would not fit in
+//! the header are written without one, and readers walk the framing.
+
+use crate::compression::framing::{self, CHUNK_HEADER_SIZE, FRAME_HEADER_SIZE, MAX_FRAME_CHUNK_SIZE};
+use crate::error::{GlifError, Result};
+
+/// Bytes each chunk takes in an encoded table
+const ENTRY_SIZE: usize = 8;
+
+/// Where a compressed chunk lives and which decompressed bytes it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLocation {
+    /// Offset of its decompressed bytes within the stream's output
+    pub output_offset: u64,
+    pub uncompressed_size: u64,
+    /// Offset of its compressed bytes within the framed stream
+    pub stream_offset: u64,
+    pub compressed_size: u64,
+}
+
+impl ChunkLocation {
+    /// Locate the chunks of a framed stream from their uncompressed and
+    /// compressed sizes, in order
+    pub fn from_sizes<I: IntoIterator<Item = (u64, u64)>>(sizes: I) -> Vec<Self> {
+        let mut output_offset = 0;
+        let mut position = FRAME_HEADER_SIZE as u64;
+        sizes.into_iter().map(|(uncompressed_size, compressed_size)| {
+            let chunk = Self {
+                output_offset,
+                uncompressed_size,
+                stream_offset: position + CHUNK_HEADER_SIZE as u64,
+                compressed_size,
+            };
+            output_offset += uncompressed_size;
+            position = chunk.stream_offset + compressed_size;
+            chunk
+        }).collect()
+    }
+}
+
+/// Total decompressed size of the stream holding `chunks`
+pub fn output_size(chunks: &[ChunkLocation]) -> u64 {
+    chunks.last().map_or(0, |c| c.output_offset + c.uncompressed_size)
+}
+
+/// Size of the framed stream holding `chunks`
+pub fn framed_size(chunks: &[ChunkLocation]) -> u64 {
+    chunks.last().map_or(FRAME_HEADER_SIZE as u64, |c| c.stream_offset + c.compressed_size)
+}
+
+/// Locations of the Zstd chunks of an archive and, in LZ4 mode, of the
+/// LZ4 chunks framing the Zstd stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkTable {
+    /// Zstd chunks, located within the framed Zstd stream
+    pub zstd: Vec<ChunkLocation>,
+    /// LZ4 chunks, located within the archive data; empty outside LZ4 mode
+    pub lz4: Vec<ChunkLocation>,
+}
+
+impl ChunkTable {
+    /// Table of the framed Zstd stream `zstd`, wrapped in the framed LZ4
+    /// stream `lz4` in LZ4 mode
+    pub fn from_frames(zstd: &[u8], lz4: Option<&[u8]>) -> Result<Self> {
+        let locate = |data: &[u8]| -> Result<Vec<ChunkLocation>> {
+            let chunks = framing::read_frames(data)?;
+            Ok(ChunkLocation::from_sizes(chunks.iter().map(|chunk| {
+                (chunk.uncompressed_size.unwrap_or_default() as u64, chunk.data.len() as u64)
+            })))
+        };
+        Ok(Self {
+            zstd: locate(zstd)?,
+            lz4: lz4.map(locate).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// The encoded table, or None if a chunk is too large to record
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        let mut data = Vec::with_capacity(8 + ENTRY_SIZE * (self.zstd.len() + self.lz4.len()));
+        data.extend_from_slice(&u32::try_from(self.zstd.len()).ok()?.to_be_bytes());
+        data.extend_from_slice(&u32::try_from(self.lz4.len()).ok()?.to_be_bytes());
+        for chunk in self.zstd.iter().chain(&self.lz4) {
+            data.extend_from_slice(&u32::try_from(chunk.uncompressed_size).ok()?.to_be_bytes());
+            data.extend_from_slice(&u32::try_from(chunk.compressed_size).ok()?.to_be_bytes());
+        }
+        Some(data)
+    }
+
+    /// Parse an encoded table
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let malformed = |reason: &str| GlifError::Malformed(format!("Invalid chunk table: {}", reason));
+        let u32_at = |offset: usize| u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as u64;
+
+        if data.len() < 8 {
+            return Err(malformed("too short"));
+        }
+        let (zstd_count, lz4_count) = (u32_at(0) as usize, u32_at(4) as usize);
+        if zstd_count == 0 {
+            return Err(malformed("no Zstd chunks"));
+        }
+        // Counts come from the file, so size them without overflowing usize on 32-bit targets
+        let chunks = zstd_count as u64 + lz4_count as u64;
+        if data.len() as u64 != 8 + ENTRY_SIZE as u64 * chunks {
+            return Err(malformed(&format!("{} bytes do not hold {} chunks", data.len(), chunks)));
+        }
+
+        let chunks = chunks as usize;
+        let mut sizes = Vec::with_capacity(chunks);
+        for entry in 0..chunks {
+            let offset = 8 + entry * ENTRY_SIZE;
+            let uncompressed_size = u32_at(offset);
+            if uncompressed_size > MAX_FRAME_CHUNK_SIZE as u64 {
+                return Err(malformed(&format!("chunk {} is {} bytes", entry, uncompressed_size)));
+            }
+            sizes.push((uncompressed_size, u32_at(offset + 4)));
+        }
+
+        let lz4 = sizes.split_off(zstd_count);
+        Ok(Self {
+            zstd: ChunkLocation::from_sizes(sizes),
+            lz4: ChunkLocation::from_sizes(lz4),
+        })
+    }
+
+    /// Compressed bytes attributable to each of the payload ranges
+    /// `(offset, len, weight)`: every Zstd chunk's compressed size is shared
+    /// out between the ranges it holds bytes of, in proportion to their
+    /// weight times the fraction of the range in the chunk, and scaled by
+    /// the LZ4 layer's ratio in LZ4 mode
+    pub fn compressed_shares(&self, ranges: &[(u64, u64, u64)]) -> Vec<u64> {
+        // Each range's weighted overlap with the chunks it spans
+        let overlaps = |&(offset, len, weight): &(u64, u64, u64)| {
+            let end = offset + len;
+            let first = self.zstd.partition_point(|c| c.output_offset + c.uncompressed_size <= offset);
+            self.zstd[first..].iter().enumerate()
+                .take_while(move |(_, c)| c.output_offset < end)
+                .map(move |(index, c)| {
+                    let overlap = end.min(c.output_offset + c.uncompressed_size) - offset.max(c.output_offset);
+                    (first + index, weight as f64 * overlap as f64 / len as f64)
+                })
+        };
+
+        let mut chunk_weights = vec![0.0; self.zstd.len()];
+        for (chunk, weight) in ranges.iter().filter(|range| range.1 > 0).flat_map(overlaps) {
+            chunk_weights[chunk] += weight;
+        }
+
+        let scale = if self.lz4.is_empty() {
+            1.0
+        } else {
+            framed_size(&self.lz4) as f64 / framed_size(&self.zstd) as f64
+        };
+        ranges.iter().map(|range| {
+            if range.1 == 0 {
+                return 0;
+            }
+            let share: f64 = overlaps(range)
+                .filter(|&(chunk, weight)| weight > 0.0 && chunk_weights[chunk] > 0.0)
+                .map(|(chunk, weight)| self.zstd[chunk].compressed_size as f64 * weight / chunk_weights[chunk])
+                .sum();
+            (share * scale).round() as u64
+        }).collect()
+    }
+
+    /// Whether the table matches the sizes a header records
+    pub fn matches(&self, archive_size: u64, payload_size: u64) -> bool {
+        let zstd_size = framed_size(&self.zstd);
+        let framed = if self.lz4.is_empty() {
+            zstd_size == archive_size
+        } else {
+            output_size(&self.lz4) == zstd_size && framed_size(&self.lz4) == archive_size
+        };
+        framed && output_size(&self.zstd) == payload_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression;
+
+    #[test]
+    fn test_chunk_table_roundtrip() {
+        let data: Vec<u8> = (0..3 * compression::MIN_CHUNK_SIZE as u32 / 2).map(|i| (i % 253) as u8).collect();
+        let zstd = framing::compress_chunked(&data, compression::MIN_CHUNK_SIZE, 1, |chunk| compression::compress_zstd(chunk, 3)).unwrap();
+        let lz4 = framing::compress_chunked(&zstd, 1000, 1, compression::compress_lz4).unwrap();
+
+        let table = ChunkTable::from_frames(&zstd, Some(&lz4)).unwrap();
+        assert_eq!(table.zstd.len(), 2);
+        assert_eq!(table.zstd[1].output_offset, compression::MIN_CHUNK_SIZE as u64);
+        let chunks = framing::read_frames(&zstd).unwrap();
+        let start = table.zstd[1].stream_offset as usize;
+        assert_eq!(&zstd[start..start + table.zstd[1].compressed_size as usize], chunks[1].data);
+        assert!(table.matches(lz4.len() as u64, data.len() as u64));
+        assert!(!table.matches(lz4.len() as u64 + 1, data.len() as u64));
+
+        let encoded = table.encode().unwrap();
+        assert_eq!(encoded.len(), 8 + ENTRY_SIZE * (table.zstd.len() + table.lz4.len()));
+        assert_eq!(ChunkTable::decode(&encoded).unwrap(), table);
+
+        let zstd_only = ChunkTable::from_frames(&zstd, None).unwrap();
+        assert!(zstd_only.lz4.is_empty());
+        assert!(zstd_only.matches(zstd.len() as u64, data.len() as u64));
+
+        // Shares follow the weights within a chunk and add up to the compressed chunks
+        let split = compression::MIN_CHUNK_SIZE as u64 / 2;
+        let total: u64 = zstd_only.zstd.iter().map(|c| c.compressed_size).sum();
+        let shares = zstd_only.compressed_shares(&[(0, split, 1), (split, data.len() as u64 - split, 3), (split, 0, 1)]);
+        assert!((shares[0] + shares[1]).abs_diff(total) <= 1);
+        assert!(shares[0] < shares[1] / 3);
+        assert_eq!(shares[2], 0);
+
+        assert!(ChunkTable::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(ChunkTable::decode(&[0; 8]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_counts_that_overflow() {
+        // 0xFFFF_FFFF + 2 chunks wraps to one 8-byte entry in 32-bit arithmetic
+        let mut crafted = vec![0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 2];
+        crafted.extend_from_slice(&[0; ENTRY_SIZE]);
+        let err = ChunkTable::decode(&crafted).unwrap_err();
+        assert!(matches!(err, GlifError::Malformed(_)), "{}", err);
+    }
+}
@@ -0,0 +1,67 @@
+//! Features a reader must support to read an archive
+//!
+//! A v2 header can carry a `TLV_REQUIRED_FEATURES` field: a big-endian
+//! u64 with one bit per capability the archive depends on. Unknown TLV
+//! fields are otherwise ignored, which is right for hints like the chunk
+//! table but not for changes to how the data must be read; a reader that
+//! finds a bit outside `SUPPORTED_FEATURES` refuses the archive by
+//! naming the feature, rather than decoding garbage or reporting hash
+//! mismatches.
+
+/// The payload is encrypted with the parameters in `TLV_ENCRYPTION`
+pub const FEATURE_ENCRYPTION: u64 = 1 << 0;
+
+/// Chunks can only be located through the `TLV_CHUNK_TABLE` field
+pub const FEATURE_CHUNK_INDEX: u64 = 1 << 1;
+
+/// The manifest has entries whose contents are in base archives (see
+/// `ExternalRef`), set whenever an archive has one
+pub const FEATURE_DEDUP: u64 = 1 << 2;
+
+/// The sidecar size is in a `TLV_SIDECAR_SIZE` field, with `u16::MAX`
+/// in the fixed field
+pub const FEATURE_WIDE_SIDECAR: u64 = 1 << 3;
+
+/// Features this version of glifzip can read
+pub const SUPPORTED_FEATURES: u64 = FEATURE_DEDUP | FEATURE_WIDE_SIDECAR;
+
+/// Names of the features that have one, by bit
+const FEATURE_NAMES: &[(u64, &str)] = &[
+    (FEATURE_ENCRYPTION, "encryption"),
+    (FEATURE_CHUNK_INDEX, "chunk-index"),
+    (FEATURE_DEDUP, "dedup"),
+    (FEATURE_WIDE_SIDECAR, "wide-sidecar"),
+];
+
+/// Names of the features set in `features`, in bit order; bits added
+/// after this version are named by number
+pub fn feature_names(features: u64) -> Vec<String> {
+    (0..64)
+        .map(|bit| 1u64 << bit)
+        .filter(|flag| features & flag != 0)
+        .map(|flag| match FEATURE_NAMES.iter().find(|(known, _)| *known == flag) {
+            Some((_, name)) => name.to_string(),
+            None => format!("feature bit {}", flag.trailing_zeros()),
+        })
+        .collect()
+}
+
+/// Features set in `features` that this version can't read
+pub fn unsupported(features: u64) -> u64 {
+    features & !SUPPORTED_FEATURES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_names() {
+        assert!(feature_names(0).is_empty());
+        assert_eq!(feature_names(FEATURE_DEDUP | FEATURE_ENCRYPTION), vec!["encryption", "dedup"]);
+        assert_eq!(feature_names(1 << 40), vec!["feature bit 40"]);
+
+        assert_eq!(unsupported(FEATURE_WIDE_SIDECAR | FEATURE_DEDUP), 0);
+        assert_eq!(unsupported(FEATURE_WIDE_SIDECAR | FEATURE_CHUNK_INDEX), FEATURE_CHUNK_INDEX);
+    }
+}
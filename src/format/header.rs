@@ -1,32 +1,106 @@
-use std::io::{Read, Write, Result, Error, ErrorKind};
+use std::io::{Read, Write};
+
+use super::chunk_table::ChunkTable;
+use super::features::{self, FEATURE_WIDE_SIDECAR};
+use super::GlifSidecar;
+use crate::error::{GlifError, Result};
+use crate::archive::{ArchiveManifest, MAX_MANIFEST_SIZE};
 
 pub const MAGIC_NUMBER: &[u8; 6] = b"GLIF01";
-pub const GLIF_VERSION: u32 = 0x00000100; // v1.0
-pub const HEADER_SIZE: usize = 116;
+pub const GLIF_VERSION: u32 = 0x00000200; // v2.0: TLV header fields
+pub const MIN_GLIF_VERSION: u32 = 0x00000100; // v1.0: oldest readable format
+pub const FRAMED_GLIF_VERSION: u32 = 0x00000101; // first version that always frames chunks
+pub const TLV_GLIF_VERSION: u32 = 0x00000200; // first version with a TLV section
+pub const HEADER_SIZE: usize = 116; // fixed fields; v2 headers append the TLV section
+
+/// Length of the TLV section's size, which starts the section
+pub const TLV_SECTION_PREFIX_SIZE: usize = 4;
+
+/// Largest TLV section accepted, so a damaged size cannot make readers
+/// allocate without bound
+pub const MAX_TLV_SECTION_SIZE: usize = 64 * 1024;
+
+/// Type of a TLV carrying encryption parameters
+pub const TLV_ENCRYPTION: u16 = 0x0001;
+
+/// Type of a TLV naming the codecs used, beyond `decompression_mode`
+pub const TLV_CODEC_IDS: u16 = 0x0002;
+
+/// Type of a TLV locating every compressed chunk (see `format::chunk_table`)
+pub const TLV_CHUNK_TABLE: u16 = 0x0003;
+
+/// Type of a TLV holding the sidecar size as a big-endian u32, written
+/// when the sidecar is too large for the fixed u16 field, which then holds
+/// `u16::MAX`
+pub const TLV_SIDECAR_SIZE: u16 = 0x0004;
+
+/// Type of a TLV holding the features a reader must support as a
+/// big-endian u64 bitfield (see `format::features`)
+pub const TLV_REQUIRED_FEATURES: u16 = 0x0005;
+
+/// Type of a TLV holding the SHA256 of everything before the header in a
+/// directory archive: the manifest's size and its encoded bytes
+pub const TLV_MANIFEST_HASH: u16 = 0x0006;
+
+/// Largest sidecar accepted, so a damaged size cannot make readers
+/// allocate without bound
+pub const MAX_SIDECAR_SIZE: u32 = 16 * 1024 * 1024;
+
+/// An optional type-length-value header field
+///
+/// On disk a field is its type and value length (big-endian u16 each)
+/// followed by the value. Readers keep fields of types they don't know
+/// and otherwise ignore them, so new fields don't break older v2 readers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderField {
+    pub field_type: u16,
+    pub value: Vec<u8>,
+}
+
+impl HeaderField {
+    pub fn new(field_type: u16, value: Vec<u8>) -> Self {
+        Self { field_type, value }
+    }
+
+    fn encoded_size(&self) -> usize {
+        4 + self.value.len()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct GlifHeader {
+    pub format_version: u32,
     pub payload_size: u64,
     pub archive_size: u64,
     pub payload_hash: [u8; 32],
     pub archive_hash: [u8; 32],
-    pub compression_level: u32,
+    pub compression_level: i32, // negative values are Zstd fast levels
     pub decompression_mode: u32, // 0=LZ4, 1=Zstd
     pub cores_used: u32,
     pub timestamp: u64,
-    pub sidecar_size: u16,
+    /// Sizes over `u16::MAX` need a v2 header, which records them in a
+    /// `TLV_SIDECAR_SIZE` field
+    pub sidecar_size: u32,
+    /// Features a reader must support, recorded in a
+    /// `TLV_REQUIRED_FEATURES` field; writing adds those the header
+    /// itself needs
+    pub required_features: u64,
+    /// Optional fields of a v2 header, in order; the sidecar size and
+    /// required features are not among them
+    pub fields: Vec<HeaderField>,
 }
 
 impl GlifHeader {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         payload_size: u64,
         archive_size: u64,
         payload_hash: [u8; 32],
         archive_hash: [u8; 32],
-        compression_level: u32,
+        compression_level: i32,
         decompression_mode: u32,
         cores_used: u32,
-        sidecar_size: u16,
+        sidecar_size: u32,
     ) -> Self {
         Self::new_with_timestamp(
             payload_size,
@@ -41,25 +115,22 @@ impl GlifHeader {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_timestamp(
         payload_size: u64,
         archive_size: u64,
         payload_hash: [u8; 32],
         archive_hash: [u8; 32],
-        compression_level: u32,
+        compression_level: i32,
         decompression_mode: u32,
         cores_used: u32,
-        sidecar_size: u16,
+        sidecar_size: u32,
         timestamp: Option<u64>,
     ) -> Self {
-        let timestamp = timestamp.unwrap_or_else(|| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        });
+        let timestamp = timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp().max(0) as u64);
 
         Self {
+            format_version: GLIF_VERSION,
             payload_size,
             archive_size,
             payload_hash,
@@ -69,7 +140,189 @@ impl GlifHeader {
             cores_used,
             timestamp,
             sidecar_size,
+            required_features: 0,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Size of the header on disk, including any TLV section
+    pub fn size(&self) -> usize {
+        if self.has_tlv_section() {
+            let sidecar_size_field = if self.has_wide_sidecar() { 8 } else { 0 };
+            let features_field = if self.features_to_write() != 0 { 12 } else { 0 };
+            HEADER_SIZE + TLV_SECTION_PREFIX_SIZE + sidecar_size_field + features_field + self.fields.iter().map(HeaderField::encoded_size).sum::<usize>()
+        } else {
+            HEADER_SIZE
+        }
+    }
+
+    /// Value of the first field of `field_type`
+    pub fn field(&self, field_type: u16) -> Option<&[u8]> {
+        self.fields.iter().find(|field| field.field_type == field_type).map(|field| field.value.as_slice())
+    }
+
+    /// Set the field of `field_type`, replacing any already present
+    pub fn set_field(&mut self, field_type: u16, value: Vec<u8>) {
+        self.fields.retain(|field| field.field_type != field_type);
+        self.fields.push(HeaderField::new(field_type, value));
+    }
+
+    /// Chunk table recorded in a `TLV_CHUNK_TABLE` field, checked against
+    /// the archive and payload sizes
+    pub fn chunk_table(&self) -> Result<Option<ChunkTable>> {
+        let Some(field) = self.field(TLV_CHUNK_TABLE) else { return Ok(None) };
+        let table = ChunkTable::decode(field)?;
+        if !table.matches(self.archive_size, self.payload_size) {
+            return Err(GlifError::Malformed("The chunk table does not match the archive's sizes".to_string()));
+        }
+        Ok(Some(table))
+    }
+
+    /// Record `table` in a `TLV_CHUNK_TABLE` field if it fits in the TLV
+    /// section; returns whether it was recorded
+    pub fn set_chunk_table(&mut self, table: &ChunkTable) -> bool {
+        if !self.has_tlv_section() {
+            return false;
+        }
+        let Some(value) = table.encode() else { return false };
+        let section_size = self.size() - HEADER_SIZE - TLV_SECTION_PREFIX_SIZE;
+        if section_size + 4 + value.len() > MAX_TLV_SECTION_SIZE {
+            return false;
         }
+        self.set_field(TLV_CHUNK_TABLE, value);
+        true
+    }
+
+    /// SHA256 of the directory archive manifest recorded in a
+    /// `TLV_MANIFEST_HASH` field
+    pub fn manifest_hash(&self) -> Result<Option<[u8; 32]>> {
+        let Some(field) = self.field(TLV_MANIFEST_HASH) else { return Ok(None) };
+        let hash = field.try_into().map_err(|_| GlifError::Malformed(
+            format!("Manifest hash field is {} bytes, not 32", field.len())
+        ))?;
+        Ok(Some(hash))
+    }
+
+    /// Record `hash` in a `TLV_MANIFEST_HASH` field if the header has a
+    /// TLV section with room for it; returns whether it was recorded
+    pub fn set_manifest_hash(&mut self, hash: &[u8; 32]) -> bool {
+        if !self.has_tlv_section() {
+            return false;
+        }
+        let section_size = self.size() - HEADER_SIZE - TLV_SECTION_PREFIX_SIZE;
+        let replaced = self.field(TLV_MANIFEST_HASH).map_or(0, |field| 4 + field.len());
+        if section_size - replaced + 4 + hash.len() > MAX_TLV_SECTION_SIZE {
+            return false;
+        }
+        self.set_field(TLV_MANIFEST_HASH, hash.to_vec());
+        true
+    }
+
+    /// Whether the header ends with a TLV section
+    pub fn has_tlv_section(&self) -> bool {
+        self.format_version >= TLV_GLIF_VERSION
+    }
+
+    /// Whether the sidecar size needs a `TLV_SIDECAR_SIZE` field
+    fn has_wide_sidecar(&self) -> bool {
+        self.sidecar_size > u16::MAX as u32
+    }
+
+    /// Mark `feature` (one of the `format::features` flags) as required to read the archive
+    pub fn require(&mut self, feature: u64) {
+        self.required_features |= feature;
+    }
+
+    /// Required features as written, including those the header itself needs
+    fn features_to_write(&self) -> u64 {
+        let wide_sidecar = if self.has_wide_sidecar() { FEATURE_WIDE_SIDECAR } else { 0 };
+        self.required_features | wide_sidecar
+    }
+
+    /// Header bytes needed to know the header's full size, from its first bytes
+    ///
+    /// A lower bound until the TLV section's size has been read. Errors
+    /// when the available bytes are not the start of a supported header.
+    pub fn encoded_size(prefix: &[u8]) -> Result<usize> {
+        if prefix.len() < HEADER_SIZE {
+            return Ok(HEADER_SIZE);
+        }
+        if &prefix[..MAGIC_NUMBER.len()] != MAGIC_NUMBER {
+            return Err(GlifError::InvalidMagic);
+        }
+        let version = u32::from_be_bytes(prefix[6..10].try_into().unwrap_or_default());
+        if !is_supported_version(version) {
+            return Err(GlifError::UnsupportedVersion { found: version, max_supported: GLIF_VERSION });
+        }
+        if version < TLV_GLIF_VERSION {
+            return Ok(HEADER_SIZE);
+        }
+
+        let section_end = HEADER_SIZE + TLV_SECTION_PREFIX_SIZE;
+        if prefix.len() < section_end {
+            return Ok(section_end);
+        }
+        let section_size = u32::from_be_bytes(prefix[HEADER_SIZE..section_end].try_into().unwrap_or_default()) as usize;
+        if section_size > MAX_TLV_SECTION_SIZE {
+            return Err(GlifError::Malformed(format!("Header TLV section of {} bytes is too large", section_size)));
+        }
+        Ok(section_end + section_size)
+    }
+
+    /// The TLV section after its size, as written
+    fn encode_fields(&self) -> Result<Vec<u8>> {
+        if self.sidecar_size > MAX_SIDECAR_SIZE || (self.has_wide_sidecar() && !self.has_tlv_section()) {
+            return Err(GlifError::InvalidInput(format!(
+                "Sidecar is {} bytes, more than a v{} header can record", self.sidecar_size, format_version(self.format_version)
+            )));
+        }
+
+        let required_features = self.features_to_write();
+        if required_features != 0 && !self.has_tlv_section() {
+            return Err(GlifError::InvalidInput(format!(
+                "A v{} header can't record required features", format_version(self.format_version)
+            )));
+        }
+
+        let mut section = Vec::new();
+        if required_features != 0 {
+            section.extend_from_slice(&TLV_REQUIRED_FEATURES.to_be_bytes());
+            section.extend_from_slice(&8u16.to_be_bytes());
+            section.extend_from_slice(&required_features.to_be_bytes());
+        }
+        if self.has_wide_sidecar() {
+            section.extend_from_slice(&TLV_SIDECAR_SIZE.to_be_bytes());
+            section.extend_from_slice(&4u16.to_be_bytes());
+            section.extend_from_slice(&self.sidecar_size.to_be_bytes());
+        }
+        for field in self.fields.iter().filter(|field| ![TLV_SIDECAR_SIZE, TLV_REQUIRED_FEATURES].contains(&field.field_type)) {
+            let len = u16::try_from(field.value.len()).map_err(|_| GlifError::InvalidInput(
+                format!("Header field {:#06x} is {} bytes, more than a TLV can hold", field.field_type, field.value.len())
+            ))?;
+            section.extend_from_slice(&field.field_type.to_be_bytes());
+            section.extend_from_slice(&len.to_be_bytes());
+            section.extend_from_slice(&field.value);
+        }
+        if section.len() > MAX_TLV_SECTION_SIZE {
+            return Err(GlifError::InvalidInput(format!("Header TLV section of {} bytes is too large", section.len())));
+        }
+        Ok(section)
+    }
+
+    fn decode_fields(mut section: &[u8]) -> Result<Vec<HeaderField>> {
+        let mut fields = Vec::new();
+        while !section.is_empty() {
+            let (field_type, len) = match section {
+                [t0, t1, l0, l1, ..] => (u16::from_be_bytes([*t0, *t1]), u16::from_be_bytes([*l0, *l1]) as usize),
+                _ => return Err(GlifError::Malformed("Header TLV section ends inside a field".to_string())),
+            };
+            let value = section.get(4..4 + len).ok_or_else(|| GlifError::Malformed(
+                format!("Header field {:#06x} runs past the end of the TLV section", field_type)
+            ))?;
+            fields.push(HeaderField::new(field_type, value.to_vec()));
+            section = &section[4 + len..];
+        }
+        Ok(fields)
     }
 
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
@@ -77,7 +330,7 @@ impl GlifHeader {
         writer.write_all(MAGIC_NUMBER)?;
 
         // Version (4 bytes, big-endian)
-        writer.write_all(&GLIF_VERSION.to_be_bytes())?;
+        writer.write_all(&self.format_version.to_be_bytes())?;
 
         // Payload size (8 bytes, big-endian)
         writer.write_all(&self.payload_size.to_be_bytes())?;
@@ -91,7 +344,7 @@ impl GlifHeader {
         // Archive hash (32 bytes)
         writer.write_all(&self.archive_hash)?;
 
-        // Compression level (4 bytes, big-endian)
+        // Compression level (4 bytes, big-endian two's complement)
         writer.write_all(&self.compression_level.to_be_bytes())?;
 
         // Decompression mode (4 bytes, big-endian)
@@ -104,11 +357,18 @@ impl GlifHeader {
         writer.write_all(&self.timestamp.to_be_bytes())?;
 
         // Calculate and write Adler-32 checksum (4 bytes)
-        let checksum = self.calculate_checksum();
+        let section = self.encode_fields()?;
+        let checksum = self.calculate_checksum(&section);
         writer.write_all(&checksum.to_be_bytes())?;
 
-        // Sidecar size (2 bytes, big-endian)
-        writer.write_all(&self.sidecar_size.to_be_bytes())?;
+        // Sidecar size (2 bytes, big-endian; u16::MAX when a TLV holds it)
+        writer.write_all(&u16::try_from(self.sidecar_size).unwrap_or(u16::MAX).to_be_bytes())?;
+
+        // TLV section size (4 bytes, big-endian) and fields (v2)
+        if self.has_tlv_section() {
+            writer.write_all(&(section.len() as u32).to_be_bytes())?;
+            writer.write_all(&section)?;
+        }
 
         Ok(())
     }
@@ -118,15 +378,15 @@ impl GlifHeader {
         let mut magic = [0u8; 6];
         reader.read_exact(&mut magic)?;
         if &magic != MAGIC_NUMBER {
-            return Err(Error::new(ErrorKind::InvalidData, "Invalid GLIF magic number"));
+            return Err(GlifError::InvalidMagic);
         }
 
         // Read version
         let mut version_bytes = [0u8; 4];
         reader.read_exact(&mut version_bytes)?;
         let version = u32::from_be_bytes(version_bytes);
-        if version != GLIF_VERSION {
-            return Err(Error::new(ErrorKind::InvalidData, "Unsupported GLIF version"));
+        if !is_supported_version(version) {
+            return Err(GlifError::UnsupportedVersion { found: version, max_supported: GLIF_VERSION });
         }
 
         // Read payload size
@@ -150,7 +410,7 @@ impl GlifHeader {
         // Read compression level
         let mut compression_level_bytes = [0u8; 4];
         reader.read_exact(&mut compression_level_bytes)?;
-        let compression_level = u32::from_be_bytes(compression_level_bytes);
+        let compression_level = i32::from_be_bytes(compression_level_bytes);
 
         // Read decompression mode
         let mut decompression_mode_bytes = [0u8; 4];
@@ -175,9 +435,25 @@ impl GlifHeader {
         // Read sidecar size
         let mut sidecar_size_bytes = [0u8; 2];
         reader.read_exact(&mut sidecar_size_bytes)?;
-        let sidecar_size = u16::from_be_bytes(sidecar_size_bytes);
+        let sidecar_size = u16::from_be_bytes(sidecar_size_bytes) as u32;
+
+        // Read the TLV section
+        let section = if version >= TLV_GLIF_VERSION {
+            let mut section_size_bytes = [0u8; 4];
+            reader.read_exact(&mut section_size_bytes)?;
+            let section_size = u32::from_be_bytes(section_size_bytes) as usize;
+            if section_size > MAX_TLV_SECTION_SIZE {
+                return Err(GlifError::Malformed(format!("Header TLV section of {} bytes is too large", section_size)));
+            }
+            let mut section = vec![0u8; section_size];
+            reader.read_exact(&mut section)?;
+            section
+        } else {
+            Vec::new()
+        };
 
-        let header = Self {
+        let mut header = Self {
+            format_version: version,
             payload_size,
             archive_size,
             payload_hash,
@@ -187,18 +463,44 @@ impl GlifHeader {
             cores_used,
             timestamp,
             sidecar_size,
+            required_features: 0,
+            fields: Vec::new(),
         };
 
-        // Verify checksum
-        let calculated_checksum = header.calculate_checksum();
+        // Verify checksum before trusting the fields
+        let calculated_checksum = header.calculate_checksum(&section);
         if calculated_checksum != stored_checksum {
-            return Err(Error::new(ErrorKind::InvalidData, "Header checksum mismatch"));
+            return Err(GlifError::ChecksumMismatch { expected: stored_checksum, actual: calculated_checksum });
+        }
+        header.fields = Self::decode_fields(&section)?;
+        // Refuse features this build can't read before interpreting anything they may change
+        if let Some(position) = header.fields.iter().position(|field| field.field_type == TLV_REQUIRED_FEATURES) {
+            let field = header.fields.remove(position);
+            let bits = <[u8; 8]>::try_from(field.value.as_slice()).map_err(|_| GlifError::Malformed(
+                format!("Required features field is {} bytes", field.value.len())
+            ))?;
+            header.required_features = u64::from_be_bytes(bits);
+        }
+        let unsupported = features::unsupported(header.required_features);
+        if unsupported != 0 {
+            return Err(GlifError::UnsupportedFeatures { features: features::feature_names(unsupported) });
+        }
+        if let Some(position) = header.fields.iter().position(|field| field.field_type == TLV_SIDECAR_SIZE) {
+            let field = header.fields.remove(position);
+            let size = <[u8; 4]>::try_from(field.value.as_slice()).map_err(|_| GlifError::Malformed(
+                format!("Sidecar size field is {} bytes", field.value.len())
+            ))?;
+            header.sidecar_size = u32::from_be_bytes(size);
+        }
+        if header.sidecar_size > MAX_SIDECAR_SIZE {
+            return Err(GlifError::Malformed(format!("Sidecar of {} bytes is too large", header.sidecar_size)));
         }
 
         Ok(header)
     }
 
-    fn calculate_checksum(&self) -> u32 {
+    /// Adler-32 of the fixed fields and, in v2 headers, the TLV section
+    fn calculate_checksum(&self, section: &[u8]) -> u32 {
         let mut data = Vec::new();
         data.extend_from_slice(&self.payload_size.to_be_bytes());
         data.extend_from_slice(&self.archive_size.to_be_bytes());
@@ -208,15 +510,143 @@ impl GlifHeader {
         data.extend_from_slice(&self.decompression_mode.to_be_bytes());
         data.extend_from_slice(&self.cores_used.to_be_bytes());
         data.extend_from_slice(&self.timestamp.to_be_bytes());
+        if self.has_tlv_section() {
+            data.extend_from_slice(&(section.len() as u32).to_be_bytes());
+            data.extend_from_slice(section);
+        }
 
         adler::adler32_slice(&data)
     }
+
+    /// Whether the archive data is always chunk-framed
+    pub fn is_framed(&self) -> bool {
+        self.format_version >= FRAMED_GLIF_VERSION
+    }
+}
+
+/// What can be learned from the first bytes of an archive
+///
+/// Returned by [`GlifHeader::peek`]. Parts are filled in as they become
+/// available: the manifest of a directory archive, then the header, then
+/// the sidecar.
+#[derive(Debug, Clone, Default)]
+pub struct PeekInfo {
+    /// Size of the manifest that precedes the GLIF stream of a directory archive
+    pub manifest_size: Option<u64>,
+    pub manifest: Option<ArchiveManifest>,
+    pub header: Option<GlifHeader>,
+    pub sidecar: Option<GlifSidecar>,
+    /// More bytes needed before the next missing part can be parsed (0 once complete)
+    pub bytes_needed: u64,
+}
+
+impl PeekInfo {
+    /// Whether the header and sidecar (and any manifest) have been read
+    pub fn is_complete(&self) -> bool {
+        self.sidecar.is_some()
+    }
+
+    /// Offset of the GLIF header: after the manifest of a directory archive
+    pub fn header_offset(&self) -> Option<u64> {
+        match self.manifest_size {
+            Some(size) => Some(DIRECTORY_PREFIX_SIZE as u64 + size),
+            None => self.header.as_ref().map(|_| 0),
+        }
+    }
+
+    /// Prefix length needed to read everything `peek` reports, once known
+    pub fn info_size(&self) -> Option<u64> {
+        let header = self.header.as_ref()?;
+        Some(self.header_offset()? + header.size() as u64 + header.sidecar_size as u64)
+    }
+
+    /// Size of the complete archive file, once the header has been read
+    pub fn expected_size(&self) -> Option<u64> {
+        Some(self.info_size()? + self.header.as_ref()?.archive_size)
+    }
+
+    fn needs(mut self, needed: usize, available: usize) -> Self {
+        self.bytes_needed = needed.saturating_sub(available) as u64;
+        self
+    }
+}
+
+/// Length of the big-endian manifest size starting a directory archive
+const DIRECTORY_PREFIX_SIZE: usize = 8;
+
+impl GlifHeader {
+    /// Read what is available from the first bytes of an archive
+    ///
+    /// Works on a partially downloaded file: parts that are not yet complete
+    /// are left out and `bytes_needed` says how much more to fetch before
+    /// calling again. Errors only when the available bytes are invalid.
+    pub fn peek(prefix: &[u8]) -> Result<PeekInfo> {
+        let info = PeekInfo::default();
+
+        // A file archive starts with the magic number; a directory archive
+        // with its manifest size, whose high bytes are zero
+        let magic_len = prefix.len().min(MAGIC_NUMBER.len());
+        let header_offset = if prefix[..magic_len] == MAGIC_NUMBER[..magic_len] {
+            0
+        } else {
+            if prefix.len() < DIRECTORY_PREFIX_SIZE {
+                return Ok(info.needs(DIRECTORY_PREFIX_SIZE, prefix.len()));
+            }
+            let manifest_size = u64::from_be_bytes(prefix[..DIRECTORY_PREFIX_SIZE].try_into().unwrap_or_default());
+            if manifest_size > MAX_MANIFEST_SIZE {
+                return Err(GlifError::Malformed("Not a GLIF archive: invalid magic number or manifest size".to_string()));
+            }
+
+            let header_offset = DIRECTORY_PREFIX_SIZE + manifest_size as usize;
+            let info = PeekInfo { manifest_size: Some(manifest_size), ..info };
+            if prefix.len() < header_offset {
+                return Ok(info.needs(header_offset, prefix.len()));
+            }
+            let manifest = ArchiveManifest::decode(&prefix[DIRECTORY_PREFIX_SIZE..header_offset])?;
+            return Self::peek_stream(prefix, header_offset, PeekInfo { manifest: Some(manifest), ..info });
+        };
+        Self::peek_stream(prefix, header_offset, info)
+    }
+
+    /// Peek at the header and sidecar of the GLIF stream at `offset`
+    fn peek_stream(prefix: &[u8], offset: usize, info: PeekInfo) -> Result<PeekInfo> {
+        let header_end = offset + Self::encoded_size(&prefix[offset..])?;
+        if prefix.len() < header_end {
+            return Ok(info.needs(header_end, prefix.len()));
+        }
+        let header = Self::read(&mut &prefix[offset..header_end])?;
+
+        let sidecar_end = header_end + header.sidecar_size as usize;
+        let info = PeekInfo { header: Some(header), ..info };
+        if prefix.len() < sidecar_end {
+            return Ok(info.needs(sidecar_end, prefix.len()));
+        }
+        let sidecar = GlifSidecar::from_json(
+            std::str::from_utf8(&prefix[header_end..sidecar_end]).map_err(|e| GlifError::Malformed(format!("Sidecar is not UTF-8: {}", e)))?
+        )?;
+        Ok(PeekInfo { sidecar: Some(sidecar), bytes_needed: 0, ..info })
+    }
+}
+
+/// Whether this build reads headers of format `version`: any minor
+/// revision of a major version from `MIN_GLIF_VERSION`'s to `GLIF_VERSION`'s
+///
+/// Minor revisions keep the layout of their major version and only add
+/// fields older readers skip (TLV fields, from v2 on), so a newer minor
+/// revision reads like the newest one this build knows.
+pub fn is_supported_version(version: u32) -> bool {
+    version >= MIN_GLIF_VERSION && version >> 8 <= GLIF_VERSION >> 8
+}
+
+/// Render a header format version (`major << 8 | minor`) as "major.minor"
+pub fn format_version(version: u32) -> String {
+    format!("{}.{}", version >> 8, version & 0xff)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use std::io::{Cursor, ErrorKind};
 
     #[test]
     fn test_header_roundtrip() {
@@ -245,5 +675,256 @@ mod tests {
         assert_eq!(header.decompression_mode, read_header.decompression_mode);
         assert_eq!(header.cores_used, read_header.cores_used);
         assert_eq!(header.sidecar_size, read_header.sidecar_size);
+        assert_eq!(read_header.format_version, GLIF_VERSION);
+    }
+
+    #[test]
+    fn test_reads_older_format_version() {
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.format_version = MIN_GLIF_VERSION;
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+
+        let read_header = GlifHeader::read(&mut Cursor::new(buffer)).unwrap();
+        assert_eq!(read_header.format_version, MIN_GLIF_VERSION);
+        assert!(!read_header.is_framed());
+    }
+
+    #[test]
+    fn test_v1_header_records_no_chunk_table() {
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.format_version = FRAMED_GLIF_VERSION;
+        let table = ChunkTable { zstd: Vec::new(), lz4: Vec::new() };
+        assert!(!header.set_chunk_table(&table));
+        assert_eq!(header.size(), HEADER_SIZE);
+    }
+
+    #[test]
+    fn test_newer_format_version_error() {
+        let header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        buffer[6..10].copy_from_slice(&0x00000300u32.to_be_bytes());
+
+        let err = GlifHeader::read(&mut Cursor::new(buffer)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(err.to_string().contains("v3.0"));
+        assert!(err.to_string().contains("please upgrade"));
+    }
+
+    #[test]
+    fn test_reads_newer_minor_version() {
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.format_version = GLIF_VERSION + 3;
+        header.set_field(0x7f01, b"a field from the future".to_vec());
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+
+        // Unknown fields are kept but otherwise ignored
+        let read_header = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(read_header.format_version, GLIF_VERSION + 3);
+        assert_eq!(read_header.payload_size, 100);
+        assert_eq!(read_header.field(0x7f01), Some(&b"a field from the future"[..]));
+        assert_eq!(GlifHeader::encoded_size(&buffer).unwrap(), buffer.len());
+
+        // Every 1.x revision reads, but nothing before 1.0 or after the current major
+        assert!(is_supported_version(0x000001ff));
+        assert!(!is_supported_version(0x000000ff));
+        assert!(!is_supported_version(0x00010200));
+        buffer[6..10].copy_from_slice(&0x00000300u32.to_be_bytes());
+        let err = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap_err();
+        assert!(matches!(err, GlifError::UnsupportedVersion { found: 0x300, max_supported: GLIF_VERSION }));
+    }
+
+    #[test]
+    fn test_header_negative_level_roundtrip() {
+        let header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], -5, 0, 1, 10);
+
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+
+        let read_header = GlifHeader::read(&mut Cursor::new(buffer)).unwrap();
+        assert_eq!(read_header.compression_level, -5);
+    }
+
+    #[test]
+    fn test_header_fields_roundtrip() {
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.set_field(TLV_CODEC_IDS, b"zstd".to_vec());
+        header.set_field(0x7f00, b"from a newer writer".to_vec());
+        header.set_field(TLV_CODEC_IDS, b"zstd,lz4".to_vec());
+
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), header.size());
+        assert_eq!(GlifHeader::encoded_size(&buffer).unwrap(), buffer.len());
+
+        // Unknown fields are kept but don't stop the header from being read
+        let read_header = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(read_header.fields, header.fields);
+        assert_eq!(read_header.field(TLV_CODEC_IDS), Some(&b"zstd,lz4"[..]));
+        assert_eq!(read_header.field(0x7f00), Some(&b"from a newer writer"[..]));
+        assert_eq!(read_header.field(TLV_ENCRYPTION), None);
+
+        // The checksum covers the fields
+        let last = buffer.len() - 1;
+        buffer[last] ^= 0xff;
+        assert!(GlifHeader::read(&mut Cursor::new(&buffer)).is_err());
+
+        // A field running past the section is rejected
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.set_field(TLV_CODEC_IDS, b"zstd".to_vec());
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        buffer[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&6u32.to_be_bytes());
+        buffer.truncate(HEADER_SIZE + 4 + 6);
+        assert!(GlifHeader::decode_fields(&buffer[HEADER_SIZE + 4..]).is_err());
+
+        // v1 headers have no TLV section
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 10);
+        header.format_version = FRAMED_GLIF_VERSION;
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), HEADER_SIZE);
+        assert!(GlifHeader::read(&mut Cursor::new(&buffer)).unwrap().fields.is_empty());
+    }
+
+    #[test]
+    fn test_wide_sidecar_size_roundtrip() {
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 100_000);
+        header.set_field(TLV_CODEC_IDS, b"zstd".to_vec());
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), header.size());
+        assert_eq!(&buffer[HEADER_SIZE - 2..HEADER_SIZE], &u16::MAX.to_be_bytes());
+
+        // The size lives in a TLV field but isn't listed among the fields
+        let read_header = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(read_header.sidecar_size, 100_000);
+        assert_eq!(read_header.fields, header.fields);
+        assert_eq!(read_header.size(), buffer.len());
+
+        // Small sizes stay in the fixed field
+        header.sidecar_size = u16::MAX as u32;
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        assert_eq!(GlifHeader::read(&mut Cursor::new(&buffer)).unwrap().sidecar_size, u16::MAX as u32);
+        assert_eq!(buffer.len(), HEADER_SIZE + TLV_SECTION_PREFIX_SIZE + 8);
+
+        // v1 headers can't record wide sizes, and no header records oversized sidecars
+        header.sidecar_size = 100_000;
+        header.format_version = FRAMED_GLIF_VERSION;
+        assert!(header.write(&mut Vec::new()).is_err());
+        header.format_version = GLIF_VERSION;
+        header.sidecar_size = MAX_SIDECAR_SIZE + 1;
+        assert!(header.write(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_required_features() {
+        use crate::format::features::{FEATURE_CHUNK_INDEX, FEATURE_DEDUP, FEATURE_ENCRYPTION};
+
+        // Wide sidecars mark themselves as required
+        let mut header = GlifHeader::new(100, 50, [1u8; 32], [2u8; 32], 8, 0, 1, 100_000);
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), header.size());
+        let read_header = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(read_header.required_features, FEATURE_WIDE_SIDECAR);
+        assert!(read_header.fields.is_empty());
+
+        // Features this build can't read are refused by name, before the sidecar size is trusted
+        header.require(FEATURE_ENCRYPTION | FEATURE_CHUNK_INDEX | FEATURE_DEDUP | 1 << 40);
+        let mut buffer = Vec::new();
+        header.write(&mut buffer).unwrap();
+        let err = GlifHeader::read(&mut Cursor::new(&buffer)).unwrap_err();
+        assert!(matches!(&err, GlifError::UnsupportedFeatures { features } if features == &["encryption", "chunk-index", "feature bit 40"]));
+        assert!(err.to_string().contains("requires encryption, chunk-index, feature bit 40"));
+        assert!(!err.is_corruption());
+
+        // Plain headers don't carry the field, and v1 headers can't
+        header.sidecar_size = 100;
+        header.required_features = 0;
+        assert_eq!(header.size(), HEADER_SIZE + TLV_SECTION_PREFIX_SIZE);
+        header.format_version = FRAMED_GLIF_VERSION;
+        header.require(FEATURE_DEDUP);
+        assert!(header.write(&mut Vec::new()).is_err());
+    }
+
+    /// Peek at every prefix of `archive`, checking progress is monotonic
+    fn peek_prefixes(archive: &[u8]) -> PeekInfo {
+        let mut len = 0;
+        loop {
+            let info = GlifHeader::peek(&archive[..len]).unwrap();
+            if info.is_complete() {
+                assert_eq!(info.info_size(), Some(len as u64));
+                return info;
+            }
+            assert!(info.bytes_needed > 0);
+            len += info.bytes_needed as usize;
+        }
+    }
+
+    #[test]
+    fn test_peek_file_archive() {
+        let archive = crate::compress(&b"streamed in".repeat(1000), &crate::CompressionConfig::default()).unwrap();
+
+        let partial = GlifHeader::peek(&archive[..3]).unwrap();
+        assert_eq!(partial.bytes_needed, HEADER_SIZE as u64 - 3);
+        assert!(partial.header.is_none());
+
+        // The fixed fields are followed by the TLV section's size
+        let fixed_only = GlifHeader::peek(&archive[..HEADER_SIZE]).unwrap();
+        assert!(fixed_only.header.is_none());
+        assert_eq!(fixed_only.bytes_needed, TLV_SECTION_PREFIX_SIZE as u64);
+
+        // The TLV section holds the chunk table
+        let header_size = GlifHeader::encoded_size(&archive).unwrap();
+        assert!(header_size > HEADER_SIZE + TLV_SECTION_PREFIX_SIZE);
+        let header_only = GlifHeader::peek(&archive[..header_size + 1]).unwrap();
+        let header = header_only.header.as_ref().unwrap();
+        assert_eq!(header.size(), header_size);
+        assert_eq!(header.payload_size, 11000);
+        assert_eq!(header_only.bytes_needed, header.sidecar_size as u64 - 1);
+
+        let info = peek_prefixes(&archive);
+        assert!(info.manifest.is_none());
+        assert_eq!(info.header_offset(), Some(0));
+        assert_eq!(info.expected_size(), Some(archive.len() as u64));
+        assert_eq!(info.sidecar.unwrap().payload.size, 11000);
+        assert!(GlifHeader::peek(&archive).unwrap().is_complete());
+    }
+
+    #[test]
+    fn test_peek_directory_archive() {
+        use crate::archive::directory_compressor::DirectoryCompressionConfig;
+        use crate::testing::TreeBuilder;
+
+        let tree = TreeBuilder::new().file("a.txt", "alpha").file("b/c.txt", "gamma").build();
+        let archive = crate::DirectoryCompressor::new(DirectoryCompressionConfig::default().with_progress(false)).unwrap()
+            .compress_source(&tree, std::path::PathBuf::from("tree")).unwrap();
+
+        assert_eq!(GlifHeader::peek(&archive[..0]).unwrap().bytes_needed, HEADER_SIZE as u64);
+        assert_eq!(GlifHeader::peek(&archive[..5]).unwrap().bytes_needed, 3);
+
+        let sized = GlifHeader::peek(&archive[..8]).unwrap();
+        let manifest_size = sized.manifest_size.unwrap();
+        assert_eq!(sized.bytes_needed, manifest_size);
+
+        let info = peek_prefixes(&archive);
+        assert_eq!(info.manifest.as_ref().unwrap().entries.len(), 3);
+        assert_eq!(info.header_offset(), Some(8 + manifest_size));
+        assert_eq!(info.expected_size(), Some(archive.len() as u64));
+        assert_eq!(info.sidecar.unwrap().payload.files, Some(2));
+    }
+
+    #[test]
+    fn test_peek_rejects_invalid_prefix() {
+        assert_eq!(GlifHeader::peek(b"PK\x03\x04 not glif").unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let mut archive = crate::compress(b"data", &crate::CompressionConfig::default()).unwrap();
+        let header_size = GlifHeader::encoded_size(&archive).unwrap();
+        archive[20] ^= 0xff;
+        assert!(GlifHeader::peek(&archive[..header_size]).is_err());
     }
 }
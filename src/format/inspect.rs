@@ -0,0 +1,187 @@
+//! Metadata-only inspection of archive files
+//!
+//! `inspect` reads just the header, sidecar and, for directory archives,
+//! the manifest at the start of a file, so learning an archive's sizes,
+//! creator or entry counts doesn't cost reading or hashing its payload the
+//! way `verify_archive` does. Nothing is verified beyond the structure of
+//! what is read.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{ArchiveManifest, MAX_MANIFEST_SIZE};
+use crate::error::{GlifError, Result};
+use crate::format::{GlifHeader, GlifSidecar, PeekInfo, MAGIC_NUMBER};
+
+/// What the first bytes of an archive file say about it
+#[derive(Debug, Clone)]
+pub struct GlifInfo {
+    pub header: GlifHeader,
+    pub sidecar: GlifSidecar,
+    /// Summary of the manifest of a directory archive
+    pub manifest: Option<ManifestSummary>,
+    /// Bytes read to learn all this
+    pub info_size: u64,
+    /// Size of the file on disk
+    pub file_size: u64,
+}
+
+impl GlifInfo {
+    pub fn is_directory(&self) -> bool {
+        self.manifest.is_some()
+    }
+
+    /// Size the complete archive file should have
+    pub fn expected_size(&self) -> u64 {
+        self.info_size + self.header.archive_size
+    }
+
+    /// Whether the file is at least as long as its header declares, e.g. a finished download
+    pub fn is_complete(&self) -> bool {
+        self.file_size >= self.expected_size()
+    }
+}
+
+/// Counts and totals of a directory archive's manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestSummary {
+    pub files: usize,
+    pub directories: usize,
+    pub symlinks: usize,
+    /// Bytes of file contents
+    pub total_size: u64,
+    /// Files archived without a content hash
+    pub unhashed: usize,
+    /// Paths left out because they could not be read
+    pub skipped: usize,
+    pub base_directory: PathBuf,
+    pub created_at: String,
+    /// Size of the manifest JSON
+    pub manifest_size: u64,
+}
+
+impl ManifestSummary {
+    pub fn new(manifest: &ArchiveManifest, manifest_size: u64) -> Self {
+        Self {
+            files: manifest.count(FileType::Regular),
+            directories: manifest.count(FileType::Directory),
+            symlinks: manifest.count(FileType::Symlink),
+            total_size: manifest.total_size,
+            unhashed: manifest.unhashed_count(),
+            skipped: manifest.skipped.len(),
+            base_directory: manifest.base_directory.clone(),
+            created_at: manifest.created_at.clone(),
+            manifest_size,
+        }
+    }
+}
+
+/// Read only as much of `reader` as `GlifHeader::peek` needs
+///
+/// Stops once the header, sidecar and any manifest are read, or at the end
+/// of `reader`, in which case the result is incomplete.
+pub fn peek_reader<R: Read>(mut reader: R) -> Result<PeekInfo> {
+    let mut prefix = Vec::new();
+    loop {
+        let info = GlifHeader::peek(&prefix)?;
+        if info.is_complete() {
+            return Ok(info);
+        }
+        if (&mut reader).take(info.bytes_needed).read_to_end(&mut prefix)? == 0 {
+            return Ok(info);
+        }
+    }
+}
+
+/// Header fields, sidecar and manifest summary of the archive at `path`, from its first bytes
+pub fn inspect<P: AsRef<Path>>(path: P) -> Result<GlifInfo> {
+    let file = File::open(path.as_ref())?;
+    let file_size = file.metadata()?.len();
+    let info = peek_reader(std::io::BufReader::new(file))?;
+
+    let info_size = info.info_size();
+    let manifest = info.manifest.as_ref().zip(info.manifest_size)
+        .map(|(manifest, size)| ManifestSummary::new(manifest, size));
+    match (info.header, info.sidecar, info_size) {
+        (Some(header), Some(sidecar), Some(info_size)) => Ok(GlifInfo { header, sidecar, manifest, info_size, file_size }),
+        _ => Err(GlifError::Truncated(format!(
+            "{} ends before its archive info ({} more bytes needed)",
+            path.as_ref().display(),
+            info.bytes_needed
+        ))),
+    }
+}
+
+/// Sidecar of the archive at `path`, reading only its header and sidecar
+///
+/// Cheaper than `inspect` for directory archives, whose manifest is
+/// skipped rather than read and parsed; nothing is hashed.
+pub fn read_sidecar_file<P: AsRef<Path>>(path: P) -> Result<GlifSidecar> {
+    let mut reader = BufReader::new(File::open(path.as_ref())?);
+    let mut prefix = [0u8; 8];
+    reader.read_exact(&mut prefix)?;
+    if prefix.starts_with(MAGIC_NUMBER) {
+        reader.seek(SeekFrom::Start(0))?;
+    } else {
+        // A directory archive: skip the manifest its size leads
+        let manifest_size = u64::from_be_bytes(prefix);
+        if manifest_size > MAX_MANIFEST_SIZE {
+            return Err(GlifError::Malformed("Not a GLIF archive: invalid magic number or manifest size".to_string()));
+        }
+        reader.seek_relative(manifest_size as i64)?;
+    }
+    let header = GlifHeader::read(&mut reader)?;
+    GlifSidecar::read(&mut reader, header.sidecar_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::{ArchiveWriter, CompressionConfig};
+
+    #[test]
+    fn test_inspect() {
+        crate::testing::with_temp_dir("inspect", |dir| {
+            let file_archive = dir.join("file.glif");
+            let data = vec![7u8; 100_000];
+            std::fs::write(&file_archive, crate::compress(&data, &CompressionConfig::default())?)?;
+            let info = inspect(&file_archive)?;
+            assert!(!info.is_directory() && info.is_complete());
+            assert_eq!(info.header.payload_size, 100_000);
+            assert_eq!(info.sidecar.payload.size, 100_000);
+            assert_eq!(info.expected_size(), info.file_size);
+
+            let mut writer = ArchiveWriter::new(DirectoryCompressionConfig::default().with_progress(false))?;
+            writer.add_dir("docs")?;
+            writer.add_bytes("docs/a.txt", b"alpha")?;
+            writer.add_symlink("link", "docs/a.txt")?;
+            let dir_archive = dir.join("dir.glif");
+            writer.finalize_to_file(&dir_archive)?;
+            let info = inspect(&dir_archive)?;
+            assert_eq!(read_sidecar_file(&dir_archive)?.payload.hash, info.sidecar.payload.hash);
+            assert_eq!(read_sidecar_file(&file_archive)?.payload.size, 100_000);
+            let summary = info.manifest.as_ref().unwrap();
+            assert_eq!((summary.files, summary.directories, summary.symlinks), (1, 1, 1));
+            assert_eq!((summary.unhashed, summary.skipped), (0, 0));
+            assert!(info.is_complete() && info.info_size < info.file_size);
+
+            // A partial download still has its info, but is incomplete
+            let archive = std::fs::read(&dir_archive)?;
+            std::fs::write(&dir_archive, &archive[..info.info_size as usize + 1])?;
+            let partial = inspect(&dir_archive)?;
+            assert!(!partial.is_complete());
+            assert_eq!(partial.expected_size(), archive.len() as u64);
+
+            std::fs::write(&dir_archive, &archive[..info.info_size as usize - 1])?;
+            assert!(matches!(inspect(&dir_archive), Err(GlifError::Truncated(_))));
+            assert!(read_sidecar_file(&dir_archive).is_err());
+            std::fs::write(&dir_archive, b"not an archive at all")?;
+            assert!(inspect(&dir_archive).is_err());
+            assert!(read_sidecar_file(&dir_archive).is_err());
+            Ok(())
+        }).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+//! Files holding several archives, one after another
+//!
+//! Each member of a multi-member file is a complete archive, single-file
+//! or directory, with its own header and sidecar, so a member sliced out
+//! of the file reads like any other archive. Appending a member writes it
+//! after the last one without touching the compressed data already there.
+//! Members are named by `metadata.member_name` in their sidecar; unnamed
+//! ones go by their position, `#0` for the first.
+
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{GlifError, Result};
+use crate::format::{peek_reader, GlifHeader, GlifSidecar, MAX_SIDECAR_SIZE};
+
+/// Where one archive sits in a multi-member file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub index: usize,
+    /// Offset of the member's first byte in the file
+    pub offset: u64,
+    /// Size of the whole member: any manifest, header, sidecar and data
+    pub size: u64,
+    pub name: Option<String>,
+    pub is_directory: bool,
+    pub payload_size: u64,
+}
+
+impl Member {
+    /// The member's name, or `#index` if it has none
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("#{}", self.index))
+    }
+}
+
+/// Every member of the file read by `reader`, in order; a plain archive
+/// is a file with one member
+///
+/// Only each member's manifest, header and sidecar are read.
+pub fn list_members<R: Read + Seek>(reader: &mut R) -> Result<Vec<Member>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut members = Vec::new();
+    let mut offset = 0;
+    while offset < len || members.is_empty() {
+        reader.seek(SeekFrom::Start(offset))?;
+        let info = peek_reader(&mut *reader)?;
+        let (Some(size), Some(header), Some(sidecar)) = (info.expected_size(), &info.header, &info.sidecar) else {
+            return Err(GlifError::Truncated(format!("Member {} at offset {} ends before its archive info", members.len(), offset)));
+        };
+        if offset + size > len {
+            return Err(GlifError::Truncated(format!(
+                "Member {} at offset {} needs {} bytes but the file ends after {}", members.len(), offset, size, len - offset
+            )));
+        }
+        members.push(Member {
+            index: members.len(),
+            offset,
+            size,
+            name: sidecar.metadata.member_name.clone(),
+            is_directory: info.manifest.is_some(),
+            payload_size: header.payload_size,
+        });
+        offset += size;
+    }
+    Ok(members)
+}
+
+/// The member called `label`: its name, or `#index`
+pub fn find_member<'a>(members: &'a [Member], label: &str) -> Result<&'a Member> {
+    members.iter().find(|member| member.label() == label).ok_or_else(|| GlifError::InvalidInput(format!(
+        "No member {} (the file holds {})", label, members.iter().map(Member::label).collect::<Vec<_>>().join(", ")
+    )))
+}
+
+/// The bytes of `member` within the whole file's `data`, a complete archive
+pub fn member_data<'a>(data: &'a [u8], member: &Member) -> Result<&'a [u8]> {
+    usize::try_from(member.offset).ok()
+        .zip(usize::try_from(member.offset + member.size).ok())
+        .and_then(|(start, end)| data.get(start..end))
+        .ok_or_else(|| GlifError::Truncated(format!("Member {} runs past the end of the file", member.label())))
+}
+
+/// Read the bytes of `member` from the file read by `reader`
+pub fn read_member<R: Read + Seek>(reader: &mut R, member: &Member) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(member.offset))?;
+    let mut data = Vec::new();
+    reader.take(member.size).read_to_end(&mut data)?;
+    if (data.len() as u64) < member.size {
+        return Err(GlifError::Truncated(format!("Member {} runs past the end of the file", member.label())));
+    }
+    Ok(data)
+}
+
+/// `archive` with its sidecar naming it `name` as a member; only the
+/// header and sidecar are rewritten
+pub fn with_member_name(archive: &[u8], name: &str) -> Result<Vec<u8>> {
+    let info = GlifHeader::peek(archive)?;
+    let (Some(header_offset), Some(info_size)) = (info.header_offset(), info.info_size()) else {
+        return Err(GlifError::Truncated("Archive ends before its header and sidecar".to_string()));
+    };
+
+    let mut cursor = Cursor::new(&archive[header_offset as usize..]);
+    let mut header = GlifHeader::read(&mut cursor)?;
+    let mut sidecar = GlifSidecar::read(&mut cursor, header.sidecar_size)?;
+    sidecar.metadata.member_name = Some(name.to_string());
+    let sidecar_json = sidecar.to_json()?;
+    header.sidecar_size = u32::try_from(sidecar_json.len()).ok().filter(|&size| size <= MAX_SIDECAR_SIZE).ok_or_else(|| GlifError::InvalidInput(
+        format!("Sidecar is {} bytes, more than the header can record", sidecar_json.len())
+    ))?;
+
+    let mut renamed = archive[..header_offset as usize].to_vec();
+    header.write(&mut renamed)?;
+    renamed.extend_from_slice(sidecar_json.as_bytes());
+    renamed.extend_from_slice(&archive[info_size as usize..]);
+    Ok(renamed)
+}
+
+/// Append the single archive `archive` to the file at `path` as a new
+/// member, named `name` if given (otherwise it keeps any name it has)
+///
+/// The file must already hold valid members, and names must be unique.
+pub fn append_member<P: AsRef<Path>>(path: P, archive: &[u8], name: Option<&str>) -> Result<Member> {
+    let added = list_members(&mut Cursor::new(archive))?;
+    if added.len() != 1 {
+        return Err(GlifError::InvalidInput(format!("Only single archives can be appended, not a file of {} members", added.len())));
+    }
+    let renamed;
+    let archive = match name {
+        Some(name) => {
+            renamed = with_member_name(archive, name)?;
+            &renamed[..]
+        }
+        None => archive,
+    };
+
+    let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+    let members = list_members(&mut file)?;
+    let mut member = list_members(&mut Cursor::new(archive))?.remove(0);
+    member.index = members.len();
+    if let Some(taken) = members.iter().find(|existing| existing.name.is_some() && existing.name == member.name) {
+        return Err(GlifError::InvalidInput(format!("The file already has a member named {}", taken.label())));
+    }
+
+    member.offset = members.last().map_or(0, |last| last.offset + last.size);
+    file.write_all(archive)?;
+    file.flush()?;
+    Ok(member)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::testing::TreeBuilder;
+    use crate::{compress, decompress, CompressionConfig, DirectoryCompressor};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_append_and_extract_members() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("datasets.glif");
+        let config = CompressionConfig::new(3, 1);
+        std::fs::write(&path, compress(b"first dataset", &config).unwrap()).unwrap();
+
+        let tree = TreeBuilder::new().file("a.txt", "alpha").build();
+        let directory = DirectoryCompressor::new(DirectoryCompressionConfig::default().with_progress(false)).unwrap()
+            .compress_source(&tree, PathBuf::from("tree")).unwrap();
+        let before = std::fs::read(&path).unwrap();
+        append_member(&path, &directory, Some("tree")).unwrap();
+        let added = append_member(&path, &compress(b"third dataset", &config).unwrap(), Some("third")).unwrap();
+        assert_eq!(added.index, 2);
+
+        // Existing members are left as they were
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(&before));
+
+        let members = list_members(&mut Cursor::new(&data)).unwrap();
+        let labels: Vec<_> = members.iter().map(Member::label).collect();
+        assert_eq!(labels, ["#0", "tree", "third"]);
+        assert!(members[1].is_directory);
+        assert_eq!(members[2], added);
+
+        let third = member_data(&data, find_member(&members, "third").unwrap()).unwrap();
+        assert_eq!(decompress(third, 1).unwrap(), b"third dataset");
+        let first = read_member(&mut Cursor::new(&data), find_member(&members, "#0").unwrap()).unwrap();
+        assert_eq!(decompress(&first, 1).unwrap(), b"first dataset");
+        let mut sink = crate::testing::MemoryFs::new();
+        DirectoryCompressor::extract_to_sink(member_data(&data, &members[1]).unwrap(), &mut sink, 1).unwrap();
+        assert_eq!(sink.file("a.txt"), Some(&b"alpha"[..]));
+
+        // Names are unique, and only whole members are appended
+        assert!(append_member(&path, &compress(b"again", &config).unwrap(), Some("tree")).is_err());
+        assert!(append_member(&path, &data, None).is_err());
+        assert!(find_member(&members, "fourth").unwrap_err().to_string().contains("#0, tree, third"));
+        assert!(list_members(&mut Cursor::new(&data[..data.len() - 1])).is_err());
+    }
+}
@@ -1,5 +1,12 @@
 pub mod header;
+pub mod chunk_table;
 pub mod sidecar;
+pub mod inspect;
+pub mod features;
+pub mod members;
 
-pub use header::{GlifHeader, MAGIC_NUMBER, GLIF_VERSION};
-pub use sidecar::GlifSidecar;
+pub use header::{GlifHeader, HeaderField, PeekInfo, MAGIC_NUMBER, GLIF_VERSION, MIN_GLIF_VERSION, MAX_SIDECAR_SIZE};
+pub use sidecar::{GlifSidecar, HashAlgorithm, IconInfo, GLIFZIP_VERSION, MAX_ICON_SIZE, MIN_READER_VERSION};
+pub use chunk_table::{ChunkLocation, ChunkTable};
+pub use inspect::{inspect, peek_reader, read_sidecar_file, GlifInfo, ManifestSummary};
+pub use members::{append_member, list_members, Member};
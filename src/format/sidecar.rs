@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write, Result};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::encoding::hex_encode;
+use crate::error::{GlifError, Result};
+use crate::format::{GlifHeader, MAX_SIDECAR_SIZE};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlifSidecar {
@@ -8,6 +13,13 @@ pub struct GlifSidecar {
     pub archive: ArchiveInfo,
     pub cryptography: CryptographyInfo,
     pub metadata: MetadataInfo,
+    /// Preview image shown for the archive file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<IconInfo>,
+    /// Top-level sections this version doesn't know, such as those added
+    /// by newer writers or other tools, kept so they survive a rewrite
+    #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +31,12 @@ pub struct PayloadInfo {
     pub files: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub directories: Option<u64>,
+    /// Aggregate hash of a directory archive's tree (see `ArchiveManifest::tree_hash`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_hash: Option<String>,
+    /// Merkle content address of a directory archive's tree (see `archive::content_address`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_address: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,8 +45,83 @@ pub struct ArchiveInfo {
     pub hash: String,
     pub compressed_with: String,
     pub decompressed_with: String,
-    pub compression_level: u32,
+    pub compression_level: i32,
     pub threads: u32,
+    /// Zstd window size in bytes, used to pre-check memory before extraction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_size: Option<u64>,
+    /// Estimated bytes needed to decompress with the creating thread count
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompression_memory: Option<u64>,
+    /// Size of the integrity blocks hashed in `block_hashes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity_block_size: Option<u64>,
+    /// Short per-block hashes of the archive data, used to localize corruption
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hashes: Option<Vec<String>>,
+}
+
+impl PayloadInfo {
+    /// Algorithm of `hash`
+    pub fn algorithm(&self) -> Result<HashAlgorithm> {
+        Ok(parse_hash("payload", &self.hash)?.0)
+    }
+
+    /// Digest in `hash`, checked to be a well-formed SHA256 hash
+    pub fn hash_bytes(&self) -> Result<[u8; 32]> {
+        Ok(parse_hash("payload", &self.hash)?.1)
+    }
+}
+
+impl ArchiveInfo {
+    /// Algorithm of `hash`
+    pub fn algorithm(&self) -> Result<HashAlgorithm> {
+        Ok(parse_hash("archive", &self.hash)?.0)
+    }
+
+    /// Digest in `hash`, checked to be a well-formed SHA256 hash
+    pub fn hash_bytes(&self) -> Result<[u8; 32]> {
+        Ok(parse_hash("archive", &self.hash)?.1)
+    }
+}
+
+/// Algorithm named by the prefix of a sidecar hash, as in "sha256:<hex>"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Split the sidecar hash `value` of the `what` data into its algorithm
+/// and digest
+///
+/// A malformed hash is `Malformed`; one in an algorithm this version
+/// doesn't know is `UnsupportedFeatures`, as reading the archive would
+/// need a newer glifzip.
+fn parse_hash(what: &str, value: &str) -> Result<(HashAlgorithm, [u8; 32])> {
+    let (algorithm, hex) = value.split_once(':').ok_or_else(|| GlifError::Malformed(
+        format!("Sidecar {} hash {:?} has no algorithm prefix", what, value)
+    ))?;
+    if algorithm != HashAlgorithm::Sha256.name() {
+        return Err(GlifError::UnsupportedFeatures { features: vec![format!("{} hashes", algorithm)] });
+    }
+    let digest = crate::encoding::hex_decode_array(hex).map_err(|e| GlifError::Malformed(
+        format!("Sidecar {} hash is not a SHA256 digest: {}", what, e)
+    ))?;
+    Ok((HashAlgorithm::Sha256, digest))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,15 +140,99 @@ pub struct MetadataInfo {
     pub source_platform: String,
     pub source_architecture: String,
     pub deterministic: bool,
+    /// Version of glifzip that created the archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glifzip_version: Option<String>,
+    /// Oldest glifzip version able to extract the archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_reader_version: Option<String>,
+    /// The payload holds sensitive data that should stay on encrypted storage
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    /// Name of the archive as a member of a multi-member file (see `format::members`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member_name: Option<String>,
+}
+
+/// Largest icon that can be embedded, keeping the sidecar small enough for
+/// readers to parse up front once base64-encoded
+pub const MAX_ICON_SIZE: usize = 32 * 1024;
+
+/// Small PNG, JPEG or ICNS image embedded in the sidecar
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IconInfo {
+    /// MIME type of the image, e.g. "image/png"
+    pub media_type: String,
+    /// Base64 of the image file
+    pub data: String,
+}
+
+impl IconInfo {
+    /// Icon of the image in `bytes`, whose type is detected from its signature
+    pub fn new(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() > MAX_ICON_SIZE {
+            return Err(GlifError::InvalidInput(format!(
+                "Icon is {} bytes; at most {} can be embedded", bytes.len(), MAX_ICON_SIZE
+            )));
+        }
+        let media_type = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            "image/png"
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            "image/jpeg"
+        } else if bytes.starts_with(b"icns") {
+            "image/icns"
+        } else {
+            return Err(GlifError::InvalidInput("Icon is not a PNG, JPEG or ICNS image".to_string()));
+        };
+        Ok(Self { media_type: media_type.to_string(), data: crate::encoding::base64_encode(bytes) })
+    }
+
+    /// Icon of the image file at `path`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::new(&std::fs::read(path)?).map_err(|e| GlifError::InvalidInput(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The image file
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        crate::encoding::base64_decode(&self.data).map_err(|e| GlifError::Malformed(format!("Icon is not valid base64: {}", e)))
+    }
+
+    /// File extension for the image type
+    pub fn extension(&self) -> &'static str {
+        match self.media_type.as_str() {
+            "image/png" => "png",
+            "image/jpeg" => "jpg",
+            "image/icns" => "icns",
+            _ => "bin",
+        }
+    }
+}
+
+/// Version of this glifzip build
+pub const GLIFZIP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Oldest glifzip release able to read archives written by this build
+/// (1.2.0 introduced chunk-framed streams, format v1.1, and header TLV fields, v2.0)
+pub const MIN_READER_VERSION: &str = "1.2.0";
+
+/// Parse a "major.minor.patch" version, ignoring any pre-release suffix
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let core = version.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
 }
 
 impl GlifSidecar {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         payload_size: u64,
         archive_size: u64,
         payload_hash: &[u8; 32],
         archive_hash: &[u8; 32],
-        compression_level: u32,
+        compression_level: i32,
         threads: u32,
         decompression_mode: u32,
     ) -> Self {
@@ -71,12 +248,13 @@ impl GlifSidecar {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_timestamp(
         payload_size: u64,
         archive_size: u64,
         payload_hash: &[u8; 32],
         archive_hash: &[u8; 32],
-        compression_level: u32,
+        compression_level: i32,
         threads: u32,
         decompression_mode: u32,
         timestamp: Option<String>,
@@ -108,6 +286,8 @@ impl GlifSidecar {
                 compression_ratio,
                 files: None,
                 directories: None,
+                tree_hash: None,
+                content_address: None,
             },
             archive: ArchiveInfo {
                 size: archive_size,
@@ -116,6 +296,10 @@ impl GlifSidecar {
                 decompressed_with,
                 compression_level,
                 threads,
+                window_size: None,
+                decompression_memory: None,
+                integrity_block_size: None,
+                block_hashes: None,
             },
             cryptography: CryptographyInfo {
                 algorithm: "sha256".to_string(),
@@ -129,18 +313,60 @@ impl GlifSidecar {
                 source_platform: platform,
                 source_architecture: arch,
                 deterministic: true,
+                glifzip_version: Some(GLIFZIP_VERSION.to_string()),
+                min_reader_version: Some(MIN_READER_VERSION.to_string()),
+                sensitive: false,
+                member_name: None,
             },
+            icon: None,
+            extensions: serde_json::Map::new(),
         }
     }
 
+    /// Check that this build of glifzip is new enough to read the archive
+    pub fn check_reader_compatibility(&self) -> Result<()> {
+        let Some(ref required) = self.metadata.min_reader_version else {
+            return Ok(());
+        };
+
+        match (parse_version(required), parse_version(GLIFZIP_VERSION)) {
+            (Some(required_version), Some(current)) if required_version > current => {
+                Err(GlifError::ReaderTooOld {
+                    required: required.clone(),
+                    created_by: self.metadata.glifzip_version.clone().unwrap_or_else(|| "unknown".to_string()),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the sidecar's hashes are well-formed and in an algorithm
+    /// this version can check, before any data is read
+    pub fn check_hashes(&self) -> Result<()> {
+        self.payload.hash_bytes()?;
+        self.archive.hash_bytes()?;
+        Ok(())
+    }
+
     pub fn to_json(&self) -> Result<String> {
-        serde_json::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        Ok(serde_json::to_string_pretty(self)?)
     }
 
     pub fn from_json(json: &str) -> Result<Self> {
-        serde_json::from_str(json)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Sidecar of the archive, single-file or directory, whose first bytes
+    /// are `prefix`; nothing is hashed or decompressed
+    ///
+    /// `prefix` must reach the end of the sidecar, which for directory
+    /// archives comes after the manifest; `GlifHeader::peek` tells how much
+    /// that is.
+    pub fn from_archive_prefix(prefix: &[u8]) -> Result<Self> {
+        let info = GlifHeader::peek(prefix)?;
+        info.sidecar.ok_or_else(|| GlifError::Truncated(format!(
+            "Archive prefix ends before the sidecar ({} more bytes needed)", info.bytes_needed
+        )))
     }
 
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
@@ -149,22 +375,23 @@ impl GlifSidecar {
         Ok(())
     }
 
-    pub fn read<R: Read>(reader: &mut R, size: u16) -> Result<Self> {
+    pub fn read<R: Read>(reader: &mut R, size: u32) -> Result<Self> {
+        if size > MAX_SIDECAR_SIZE {
+            return Err(GlifError::Malformed(format!("Sidecar of {} bytes is too large", size)));
+        }
         let mut buffer = vec![0u8; size as usize];
         reader.read_exact(&mut buffer)?;
         let json = String::from_utf8(buffer)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            .map_err(|e| GlifError::Malformed(format!("Sidecar is not UTF-8: {}", e)))?;
         Self::from_json(&json)
     }
 }
 
-fn hex_encode(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect()
-}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::verification::calculate_sha256;
 
     #[test]
     fn test_sidecar_json_roundtrip() {
@@ -178,5 +405,88 @@ mod tests {
         assert_eq!(sidecar.payload.size, parsed.payload.size);
         assert_eq!(sidecar.archive.size, parsed.archive.size);
         assert_eq!(sidecar.archive.compression_level, parsed.archive.compression_level);
+        assert_eq!(parsed.metadata.glifzip_version.as_deref(), Some(GLIFZIP_VERSION));
+    }
+
+    #[test]
+    fn test_sidecar_from_archive_prefix() {
+        let archive = crate::compress(b"indexed", &crate::CompressionConfig::new(3, 1)).unwrap();
+        let sidecar = GlifSidecar::from_archive_prefix(&archive).unwrap();
+        assert_eq!(sidecar.payload.size, 7);
+        assert!(sidecar.extensions.is_empty());
+        assert!(matches!(GlifSidecar::from_archive_prefix(&archive[..120]), Err(GlifError::Truncated(_))));
+        assert!(GlifSidecar::from_archive_prefix(b"not an archive").is_err());
+
+        // Sections from newer writers are kept through a rewrite
+        let mut json: serde_json::Value = serde_json::from_str(&sidecar.to_json().unwrap()).unwrap();
+        json["provenance"] = serde_json::json!({ "builder": "ci", "run": 42 });
+        let parsed = GlifSidecar::from_json(&json.to_string()).unwrap();
+        assert_eq!(parsed.extensions["provenance"]["run"], 42);
+        assert!(parsed.to_json().unwrap().contains("\"builder\": \"ci\""));
+    }
+
+    #[test]
+    fn test_hash_accessors() {
+        let payload_hash = calculate_sha256(b"payload");
+        let archive_hash = calculate_sha256(b"archive");
+        let mut sidecar = GlifSidecar::new(7, 7, &payload_hash, &archive_hash, 3, 1, 1);
+        assert_eq!(sidecar.payload.algorithm().unwrap(), HashAlgorithm::Sha256);
+        assert_eq!(sidecar.payload.hash_bytes().unwrap(), payload_hash);
+        assert_eq!(sidecar.archive.hash_bytes().unwrap(), archive_hash);
+
+        // The digest survives a trip through JSON
+        let parsed = GlifSidecar::from_json(&sidecar.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.archive.hash_bytes().unwrap(), archive_hash);
+        parsed.check_hashes().unwrap();
+
+        sidecar.payload.hash = format!("blake3:{}", "ab".repeat(32));
+        let err = sidecar.payload.hash_bytes().unwrap_err();
+        assert!(matches!(err, GlifError::UnsupportedFeatures { .. }) && err.to_string().contains("blake3 hashes"));
+        assert!(sidecar.check_hashes().is_err());
+        for malformed in ["ab".repeat(32), "sha256:abc".to_string(), format!("sha256:{}", "zz".repeat(32))] {
+            sidecar.archive.hash = malformed;
+            assert!(matches!(sidecar.archive.hash_bytes(), Err(GlifError::Malformed(_))));
+        }
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1.1.0"), Some((1, 1, 0)));
+        assert_eq!(parse_version("v2.0"), Some((2, 0, 0)));
+        assert_eq!(parse_version("1.2.3-beta.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("abc"), None);
+    }
+
+    #[test]
+    fn test_reader_compatibility() {
+        let mut sidecar = GlifSidecar::new(1, 1, &[0u8; 32], &[0u8; 32], 8, 1, 0);
+        assert!(sidecar.check_reader_compatibility().is_ok());
+
+        sidecar.metadata.min_reader_version = Some("99.0.0".to_string());
+        let err = sidecar.check_reader_compatibility().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("requires glifzip >= 99.0.0"));
+
+        // Archives from before version tracking are always accepted
+        sidecar.metadata.min_reader_version = None;
+        assert!(sidecar.check_reader_compatibility().is_ok());
+    }
+
+    #[test]
+    fn test_icon() {
+        let png = [b"\x89PNG\r\n\x1a\n".as_slice(), &[0u8; 100]].concat();
+        let icon = IconInfo::new(&png).unwrap();
+        assert_eq!((icon.media_type.as_str(), icon.extension()), ("image/png", "png"));
+        assert_eq!(icon.bytes().unwrap(), png);
+
+        assert!(IconInfo::new(b"GIF89a").is_err());
+        assert!(IconInfo::new(&[png.clone(), vec![0; MAX_ICON_SIZE]].concat()).is_err());
+
+        // The icon is embedded in and read back from an archive's sidecar
+        let config = crate::CompressionConfig::builder().icon(icon.clone()).build().unwrap();
+        let archive = crate::compress(b"with an icon", &config).unwrap();
+        assert_eq!(crate::verify_archive(&archive).unwrap().icon, Some(icon));
+        let plain = crate::compress(b"with an icon", &crate::CompressionConfig::default()).unwrap();
+        assert!(crate::verify_archive(&plain).unwrap().icon.is_none());
     }
 }
@@ -0,0 +1,229 @@
+//! Read-only filesystem images
+//!
+//! An alternative to directory archives for trees that are mounted rather
+//! than extracted. File contents are stored as one logical stream split
+//! into fixed-size blocks that are compressed independently, so any byte
+//! range can be read by decompressing only the blocks it covers. Metadata
+//! lives in uncompressed fixed-size tables:
+//!
+//! ```text
+//! superblock | data blocks | block table | inode table | directory table | xattr table | string table
+//! ```
+//!
+//! Inodes are numbered breadth-first from the root (inode 0), and each
+//! directory's entries are a contiguous, name-sorted run of the directory
+//! table, so path lookups are binary searches. All integers are big-endian.
+//! Images contain no timestamps of their own: the same tree always yields
+//! the same image.
+
+pub mod reader;
+pub mod writer;
+
+pub use reader::Image;
+pub use writer::{archive_to_image, write_image, ImageOptions};
+
+use std::io::{Error, ErrorKind};
+
+/// Magic number at the start of every image
+pub const IMAGE_MAGIC: &[u8; 4] = b"GLIM";
+
+/// Image format version written by this build
+pub const IMAGE_VERSION: u32 = 1;
+
+/// Extension for image files
+pub const IMAGE_EXTENSION: &str = "glifimg";
+
+/// Default uncompressed size of a data block (128 KB)
+pub const DEFAULT_BLOCK_SIZE: u32 = 128 * 1024;
+
+/// Smallest and largest supported block sizes
+pub const MIN_BLOCK_SIZE: u32 = 4 * 1024;
+pub const MAX_BLOCK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Inode number of the root directory
+pub const ROOT_INODE: u32 = 0;
+
+pub const SUPERBLOCK_SIZE: usize = 88;
+pub const BLOCK_ENTRY_SIZE: usize = 16;
+pub const INODE_SIZE: usize = 56;
+pub const DIR_ENTRY_SIZE: usize = 16;
+pub const XATTR_ENTRY_SIZE: usize = 16;
+
+/// Block table flag: the block is stored uncompressed
+pub const BLOCK_RAW: u32 = 1;
+
+/// Fixed header locating every table in the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superblock {
+    pub version: u32,
+    pub block_size: u32,
+    pub inode_count: u32,
+    pub block_count: u32,
+    pub dir_entry_count: u32,
+    pub xattr_count: u32,
+    /// Uncompressed size of all file contents
+    pub data_size: u64,
+    pub block_table_offset: u64,
+    pub inode_table_offset: u64,
+    pub directory_table_offset: u64,
+    pub xattr_table_offset: u64,
+    pub string_table_offset: u64,
+    pub string_table_size: u64,
+}
+
+impl Superblock {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SUPERBLOCK_SIZE);
+        out.extend_from_slice(IMAGE_MAGIC);
+        for field in [self.version, self.block_size, self.inode_count, self.block_count, self.dir_entry_count, self.xattr_count] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        for field in [
+            self.data_size,
+            self.block_table_offset,
+            self.inode_table_offset,
+            self.directory_table_offset,
+            self.xattr_table_offset,
+            self.string_table_offset,
+            self.string_table_size,
+        ] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        out.extend_from_slice(&[0; 4]);
+        out
+    }
+
+    fn decode(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < SUPERBLOCK_SIZE || &data[..4] != IMAGE_MAGIC {
+            return Err(corrupt("not a GLIF image"));
+        }
+        let superblock = Self {
+            version: be_u32(data, 4),
+            block_size: be_u32(data, 8),
+            inode_count: be_u32(data, 12),
+            block_count: be_u32(data, 16),
+            dir_entry_count: be_u32(data, 20),
+            xattr_count: be_u32(data, 24),
+            data_size: be_u64(data, 28),
+            block_table_offset: be_u64(data, 36),
+            inode_table_offset: be_u64(data, 44),
+            directory_table_offset: be_u64(data, 52),
+            xattr_table_offset: be_u64(data, 60),
+            string_table_offset: be_u64(data, 68),
+            string_table_size: be_u64(data, 76),
+        };
+
+        if superblock.version != IMAGE_VERSION {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unsupported image version {} (this build reads version {})", superblock.version, IMAGE_VERSION)
+            ));
+        }
+        if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&superblock.block_size) {
+            return Err(corrupt(format!("invalid block size {}", superblock.block_size)));
+        }
+        Ok(superblock)
+    }
+}
+
+/// What an inode describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeKind {
+    /// File contents at `data_offset` in the logical data stream
+    File { size: u64, data_offset: u64 },
+    /// `entry_count` directory table entries starting at `first_entry`
+    Directory { first_entry: u32, entry_count: u32 },
+    /// Link target in the string table
+    Symlink { target_offset: u64, target_len: u32 },
+}
+
+/// Metadata of one file, directory or symlink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inode {
+    pub kind: InodeKind,
+    /// Permission bits and file type, as in `st_mode`
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: i64,
+    pub parent: u32,
+    pub first_xattr: u32,
+    pub xattr_count: u32,
+}
+
+const KIND_FILE: u8 = 0;
+const KIND_DIRECTORY: u8 = 1;
+const KIND_SYMLINK: u8 = 2;
+
+impl Inode {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (kind, a, b) = match self.kind {
+            InodeKind::File { size, data_offset } => (KIND_FILE, size, data_offset),
+            InodeKind::Directory { first_entry, entry_count } => (KIND_DIRECTORY, first_entry as u64, entry_count as u64),
+            InodeKind::Symlink { target_offset, target_len } => (KIND_SYMLINK, target_offset, target_len as u64),
+        };
+        out.extend_from_slice(&[kind, 0, 0, 0]);
+        for field in [self.mode, self.uid, self.gid] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        out.extend_from_slice(&self.mtime.to_be_bytes());
+        out.extend_from_slice(&a.to_be_bytes());
+        out.extend_from_slice(&b.to_be_bytes());
+        for field in [self.parent, self.first_xattr, self.xattr_count, 0] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+    }
+
+    fn decode(data: &[u8]) -> std::io::Result<Self> {
+        let (a, b) = (be_u64(data, 24), be_u64(data, 32));
+        let kind = match data[0] {
+            KIND_FILE => InodeKind::File { size: a, data_offset: b },
+            KIND_DIRECTORY => InodeKind::Directory {
+                first_entry: u32::try_from(a).map_err(|_| corrupt("directory entry index out of range"))?,
+                entry_count: u32::try_from(b).map_err(|_| corrupt("directory entry count out of range"))?,
+            },
+            KIND_SYMLINK => InodeKind::Symlink {
+                target_offset: a,
+                target_len: u32::try_from(b).map_err(|_| corrupt("symlink target too long"))?,
+            },
+            other => return Err(corrupt(format!("unknown inode kind {}", other))),
+        };
+
+        Ok(Self {
+            kind,
+            mode: be_u32(data, 4),
+            uid: be_u32(data, 8),
+            gid: be_u32(data, 12),
+            mtime: be_u64(data, 16) as i64,
+            parent: be_u32(data, 40),
+            first_xattr: be_u32(data, 44),
+            xattr_count: be_u32(data, 48),
+        })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, InodeKind::Directory { .. })
+    }
+
+    /// Size reported to `stat`: content length for files, target length for symlinks
+    pub fn size(&self) -> u64 {
+        match self.kind {
+            InodeKind::File { size, .. } => size,
+            InodeKind::Directory { .. } => 0,
+            InodeKind::Symlink { target_len, .. } => target_len as u64,
+        }
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn be_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+fn corrupt(message: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Corrupt image: {}", message))
+}
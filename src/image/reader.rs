@@ -0,0 +1,372 @@
+//! Image reader
+//!
+//! Random access to an image held in memory (or mapped from disk): path
+//! lookups, directory listings and byte-range reads that decompress only
+//! the blocks they touch.
+
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::DateTime;
+
+use super::{
+    corrupt, be_u32, be_u64, Inode, InodeKind, Superblock, BLOCK_ENTRY_SIZE, BLOCK_RAW, DIR_ENTRY_SIZE,
+    INODE_SIZE, ROOT_INODE, SUPERBLOCK_SIZE, XATTR_ENTRY_SIZE,
+};
+use crate::archive::file_entry::FileType;
+use crate::archive::{FileEntry, FileSystemSink};
+use crate::compression::decompress_zstd;
+
+/// A parsed image
+#[derive(Debug, Clone, Copy)]
+pub struct Image<'a> {
+    data: &'a [u8],
+    superblock: Superblock,
+    blocks: &'a [u8],
+    inodes: &'a [u8],
+    directory: &'a [u8],
+    xattrs: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Image<'a> {
+    /// Parse an image, checking that every table lies within `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let superblock = Superblock::decode(data)?;
+
+        let table = |offset: u64, count: u64, size: usize, name: &str| -> Result<&'a [u8]> {
+            let start = usize::try_from(offset).map_err(|_| corrupt(format!("{} offset out of range", name)))?;
+            let len = (count as usize).checked_mul(size).ok_or_else(|| corrupt(format!("{} too large", name)))?;
+            start.checked_add(len)
+                .filter(|_| start >= SUPERBLOCK_SIZE)
+                .and_then(|end| data.get(start..end))
+                .ok_or_else(|| corrupt(format!("{} extends past the end of the image", name)))
+        };
+
+        let image = Self {
+            data,
+            superblock,
+            blocks: table(superblock.block_table_offset, superblock.block_count as u64, BLOCK_ENTRY_SIZE, "block table")?,
+            inodes: table(superblock.inode_table_offset, superblock.inode_count as u64, INODE_SIZE, "inode table")?,
+            directory: table(superblock.directory_table_offset, superblock.dir_entry_count as u64, DIR_ENTRY_SIZE, "directory table")?,
+            xattrs: table(superblock.xattr_table_offset, superblock.xattr_count as u64, XATTR_ENTRY_SIZE, "xattr table")?,
+            strings: table(superblock.string_table_offset, superblock.string_table_size, 1, "string table")?,
+        };
+
+        let expected_blocks = superblock.data_size.div_ceil(superblock.block_size as u64);
+        if expected_blocks != superblock.block_count as u64 {
+            return Err(corrupt(format!("{} blocks for {} bytes of data", superblock.block_count, superblock.data_size)));
+        }
+        if superblock.inode_count == 0 || !image.inode(ROOT_INODE)?.is_dir() {
+            return Err(corrupt("missing root directory"));
+        }
+        Ok(image)
+    }
+
+    pub fn superblock(&self) -> &Superblock {
+        &self.superblock
+    }
+
+    /// Metadata of inode `ino`
+    pub fn inode(&self, ino: u32) -> Result<Inode> {
+        let start = (ino as usize).checked_mul(INODE_SIZE)
+            .filter(|_| ino < self.superblock.inode_count)
+            .ok_or_else(|| corrupt(format!("inode {} out of range", ino)))?;
+        Inode::decode(&self.inodes[start..start + INODE_SIZE])
+    }
+
+    /// Entries of a directory, sorted by name
+    pub fn read_dir(&self, ino: u32) -> Result<Vec<(&'a OsStr, u32)>> {
+        let (first, count) = self.dir_range(ino)?;
+        (first..first + count).map(|i| self.dir_entry(i)).collect()
+    }
+
+    /// Look up `name` in directory `parent`
+    pub fn lookup(&self, parent: u32, name: &OsStr) -> Result<Option<u32>> {
+        let (first, count) = self.dir_range(parent)?;
+        let (mut low, mut high) = (first, first + count);
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (entry_name, ino) = self.dir_entry(mid)?;
+            match entry_name.as_encoded_bytes().cmp(name.as_encoded_bytes()) {
+                std::cmp::Ordering::Equal => return Ok(Some(ino)),
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Inode at a path relative to the root, without following symlinks
+    pub fn resolve(&self, path: &Path) -> Result<Option<u32>> {
+        let mut ino = ROOT_INODE;
+        for component in path.components() {
+            ino = match component {
+                Component::Normal(name) => {
+                    if !self.inode(ino)?.is_dir() {
+                        return Ok(None);
+                    }
+                    match self.lookup(ino, name)? {
+                        Some(child) => child,
+                        None => return Ok(None),
+                    }
+                }
+                Component::CurDir | Component::RootDir => ino,
+                Component::ParentDir => self.inode(ino)?.parent,
+                Component::Prefix(_) => return Ok(None),
+            };
+        }
+        Ok(Some(ino))
+    }
+
+    /// Read up to `len` bytes of a file starting at `offset`
+    pub fn read(&self, ino: u32, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let InodeKind::File { size, data_offset } = self.inode(ino)?.kind else {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("inode {} is not a regular file", ino)));
+        };
+        if data_offset.checked_add(size).is_none_or(|end| end > self.superblock.data_size) {
+            return Err(corrupt(format!("inode {} data out of range", ino)));
+        }
+
+        let start = data_offset + offset.min(size);
+        let end = data_offset + offset.saturating_add(len as u64).min(size);
+        let block_size = self.superblock.block_size as u64;
+
+        let mut out = Vec::with_capacity((end - start) as usize);
+        let mut position = start;
+        while position < end {
+            let index = position / block_size;
+            let block = self.block(index as u32)?;
+            let block_start = index * block_size;
+            let from = (position - block_start) as usize;
+            let to = ((end - block_start) as usize).min(block.len());
+            if from >= to {
+                return Err(corrupt(format!("block {} is shorter than expected", index)));
+            }
+            out.extend_from_slice(&block[from..to]);
+            position = block_start + to as u64;
+        }
+        Ok(out)
+    }
+
+    /// Target of a symlink
+    pub fn read_link(&self, ino: u32) -> Result<&'a Path> {
+        let InodeKind::Symlink { target_offset, target_len } = self.inode(ino)?.kind else {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("inode {} is not a symlink", ino)));
+        };
+        Ok(Path::new(crate::platform::os_str_from_bytes(self.string(target_offset, target_len)?)?))
+    }
+
+    /// Extended attributes of an inode, sorted by name
+    pub fn xattrs(&self, ino: u32) -> Result<Vec<(String, Vec<u8>)>> {
+        let inode = self.inode(ino)?;
+        (inode.first_xattr..inode.first_xattr.saturating_add(inode.xattr_count))
+            .map(|i| {
+                let start = (i as usize).checked_mul(XATTR_ENTRY_SIZE)
+                    .filter(|_| i < self.superblock.xattr_count)
+                    .ok_or_else(|| corrupt(format!("xattr {} out of range", i)))?;
+                let entry = &self.xattrs[start..start + XATTR_ENTRY_SIZE];
+                let name = self.string(be_u32(entry, 0) as u64, be_u32(entry, 4))?;
+                let value = self.string(be_u32(entry, 8) as u64, be_u32(entry, 12))?;
+                let name = String::from_utf8(name.to_vec()).map_err(|_| corrupt("xattr name is not UTF-8"))?;
+                Ok((name, value.to_vec()))
+            })
+            .collect()
+    }
+
+    /// Write every entry of the image into a sink, parents before children
+    pub fn extract_to_sink<K: FileSystemSink>(&self, sink: &mut K) -> Result<()> {
+        let mut pending = vec![(ROOT_INODE, PathBuf::new())];
+        while let Some((dir, dir_path)) = pending.pop() {
+            for (name, ino) in self.read_dir(dir)? {
+                let path = dir_path.join(name);
+                let entry = self.file_entry(ino, path.clone())?;
+                match entry.file_type {
+                    FileType::Directory => {
+                        sink.create_dir(&path)?;
+                        pending.push((ino, path));
+                    }
+                    FileType::Regular => sink.write_file(&path, &self.read(ino, 0, entry.size as usize)?)?,
+                    FileType::Symlink => {
+                        sink.symlink(&path, self.read_link(ino)?)?;
+                        continue;
+                    }
+                }
+                sink.restore_metadata(&entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Manifest-style entry for an inode
+    pub fn file_entry(&self, ino: u32, path: PathBuf) -> Result<FileEntry> {
+        let inode = self.inode(ino)?;
+        let file_type = match inode.kind {
+            InodeKind::File { .. } => FileType::Regular,
+            InodeKind::Directory { .. } => FileType::Directory,
+            InodeKind::Symlink { .. } => FileType::Symlink,
+        };
+        let mtime = DateTime::from_timestamp(inode.mtime, 0).unwrap_or_default();
+
+        Ok(FileEntry {
+            path,
+            file_type,
+            size: if file_type == FileType::Regular { inode.size() } else { 0 },
+            mode: inode.mode,
+            uid: inode.uid,
+            gid: inode.gid,
+            mtime,
+            atime: mtime,
+            symlink_target: match file_type {
+                FileType::Symlink => Some(self.read_link(ino)?.to_path_buf()),
+                _ => None,
+            },
+            data_offset: 0,
+            sha256: String::new(),
+            xattrs: self.xattrs(ino)?,
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
+        })
+    }
+
+    fn dir_range(&self, ino: u32) -> Result<(u32, u32)> {
+        match self.inode(ino)?.kind {
+            InodeKind::Directory { first_entry, entry_count } => {
+                if first_entry.checked_add(entry_count).is_none_or(|end| end > self.superblock.dir_entry_count) {
+                    return Err(corrupt(format!("directory {} entries out of range", ino)));
+                }
+                Ok((first_entry, entry_count))
+            }
+            _ => Err(Error::new(ErrorKind::NotADirectory, format!("inode {} is not a directory", ino))),
+        }
+    }
+
+    fn dir_entry(&self, index: u32) -> Result<(&'a OsStr, u32)> {
+        let start = index as usize * DIR_ENTRY_SIZE;
+        let entry = &self.directory[start..start + DIR_ENTRY_SIZE];
+        let name = self.string(be_u32(entry, 0) as u64, be_u32(entry, 4))?;
+        Ok((crate::platform::os_str_from_bytes(name)?, be_u32(entry, 8)))
+    }
+
+    fn string(&self, offset: u64, len: u32) -> Result<&'a [u8]> {
+        usize::try_from(offset).ok()
+            .and_then(|start| self.strings.get(start..start.checked_add(len as usize)?))
+            .ok_or_else(|| corrupt("string out of range"))
+    }
+
+    fn block(&self, index: u32) -> Result<Vec<u8>> {
+        let start = index as usize * BLOCK_ENTRY_SIZE;
+        let entry = self.blocks.get(start..start + BLOCK_ENTRY_SIZE)
+            .ok_or_else(|| corrupt(format!("block {} out of range", index)))?;
+        let (offset, size, flags) = (be_u64(entry, 0), be_u32(entry, 8), be_u32(entry, 12));
+
+        let stored = usize::try_from(offset).ok()
+            .and_then(|start| self.data.get(start..start.checked_add(size as usize)?))
+            .ok_or_else(|| corrupt(format!("block {} extends past the end of the image", index)))?;
+
+        let block = if flags & BLOCK_RAW != 0 {
+            stored.to_vec()
+        } else {
+            decompress_zstd(stored).map_err(|e| corrupt(format!("block {}: {}", index, e)))?
+        };
+        if block.len() > self.superblock.block_size as usize {
+            return Err(corrupt(format!("block {} is larger than the block size", index)));
+        }
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{archive_to_image, ImageOptions, MIN_BLOCK_SIZE};
+    use crate::testing::{archive, MemoryFs, TreeBuilder};
+
+    fn sample_tree() -> MemoryFs {
+        let large: Vec<u8> = (0..3 * MIN_BLOCK_SIZE as usize + 123).map(|i| (i * 7 % 251) as u8).collect();
+        TreeBuilder::new()
+            .file("bin/tool", "#!/bin/sh\n")
+            .mode("bin/tool", 0o755)
+            .file("share/data.bin", large)
+            .file("share/empty", "")
+            .dir("var/empty")
+            .symlink("lib", "usr/lib")
+            .xattr("bin/tool", "user.origin", "build")
+            .build()
+    }
+
+    fn small_blocks() -> ImageOptions {
+        ImageOptions::default().with_block_size(MIN_BLOCK_SIZE)
+    }
+
+    #[test]
+    fn test_image_roundtrip() {
+        let tree = sample_tree();
+        let data = archive_to_image(&archive(&tree), 1, &small_blocks()).unwrap();
+        let image = Image::parse(&data).unwrap();
+
+        let mut extracted = MemoryFs::new();
+        image.extract_to_sink(&mut extracted).unwrap();
+        assert_eq!(extracted, tree);
+        assert_eq!(image.xattrs(image.resolve(Path::new("bin/tool")).unwrap().unwrap()).unwrap(),
+            vec![("user.origin".to_string(), b"build".to_vec())]);
+    }
+
+    #[test]
+    fn test_lookup_and_partial_reads() {
+        let tree = sample_tree();
+        let data = archive_to_image(&archive(&tree), 1, &small_blocks()).unwrap();
+        let image = Image::parse(&data).unwrap();
+
+        let names: Vec<&OsStr> = image.read_dir(ROOT_INODE).unwrap().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["bin", "lib", "share", "var"]);
+        assert_eq!(image.resolve(Path::new("share/missing")).unwrap(), None);
+        assert_eq!(image.resolve(Path::new("bin/tool/x")).unwrap(), None);
+        assert_eq!(image.resolve(Path::new("share/../bin")).unwrap(), image.lookup(ROOT_INODE, OsStr::new("bin")).unwrap());
+
+        let link = image.resolve(Path::new("lib")).unwrap().unwrap();
+        assert_eq!(image.read_link(link).unwrap(), Path::new("usr/lib"));
+
+        // Ranges straddling block boundaries, running past the end, and empty files
+        let large = tree.file("share/data.bin").unwrap();
+        let ino = image.resolve(Path::new("share/data.bin")).unwrap().unwrap();
+        for (offset, len) in [(0, 10), (MIN_BLOCK_SIZE as u64 - 5, 10), (100, 2 * MIN_BLOCK_SIZE as usize), (large.len() as u64 - 3, 100)] {
+            let end = (offset as usize + len).min(large.len());
+            assert_eq!(image.read(ino, offset, len).unwrap(), &large[offset as usize..end]);
+        }
+        assert!(image.read(ino, large.len() as u64 + 10, 5).unwrap().is_empty());
+
+        let empty = image.resolve(Path::new("share/empty")).unwrap().unwrap();
+        assert!(image.read(empty, 0, 100).unwrap().is_empty());
+        assert!(image.read(ROOT_INODE, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_image_is_deterministic() {
+        let tree = sample_tree();
+        let first = archive_to_image(&archive(&tree), 1, &ImageOptions::default()).unwrap();
+        let second = archive_to_image(&archive(&tree), 4, &ImageOptions::default()).unwrap();
+        assert_eq!(first, second);
+
+        assert!(archive_to_image(&archive(&tree), 1, &ImageOptions::default().with_block_size(5000)).is_err());
+    }
+
+    #[test]
+    fn test_rejects_corrupt_images() {
+        let data = archive_to_image(&archive(&sample_tree()), 1, &small_blocks()).unwrap();
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(Image::parse(&bad_magic).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        assert!(Image::parse(&data[..data.len() - 1]).is_err());
+
+        let mut bad_offset = data.clone();
+        bad_offset[44..52].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(Image::parse(&bad_offset).is_err());
+    }
+}
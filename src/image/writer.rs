@@ -0,0 +1,282 @@
+//! Image writer
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path};
+
+use super::{
+    Inode, InodeKind, Superblock, BLOCK_ENTRY_SIZE, BLOCK_RAW, DEFAULT_BLOCK_SIZE, DIR_ENTRY_SIZE,
+    IMAGE_VERSION, MAX_BLOCK_SIZE, MIN_BLOCK_SIZE, ROOT_INODE, SUPERBLOCK_SIZE,
+};
+use crate::archive::file_entry::FileType;
+use crate::archive::FileEntry;
+use crate::compression::compress_zstd;
+use crate::DirectoryCompressor;
+
+/// Settings for writing an image
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    /// Uncompressed size of each data block
+    pub block_size: u32,
+
+    /// Zstd level for data blocks
+    pub level: i32,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self { block_size: DEFAULT_BLOCK_SIZE, level: crate::DEFAULT_COMPRESSION_LEVEL }
+    }
+}
+
+impl ImageOptions {
+    pub fn with_block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&self.block_size) || !self.block_size.is_power_of_two() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Block size must be a power of two between {} and {} bytes", MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Convert a directory archive into an image
+pub fn archive_to_image(archive_data: &[u8], threads: usize, options: &ImageOptions) -> Result<Vec<u8>> {
+    let (manifest, blob) = DirectoryCompressor::read_archive(archive_data, threads)?;
+    write_image(&manifest.entries, &blob, options)
+}
+
+/// Write an image of `entries`, whose file contents are in `blob`
+///
+/// Parent directories missing from `entries` are added with mode 0755.
+pub fn write_image(entries: &[FileEntry], blob: &[u8], options: &ImageOptions) -> Result<Vec<u8>> {
+    options.validate()?;
+
+    let mut root = Node::directory(None);
+    for entry in entries {
+        root.insert(entry, blob)?;
+    }
+
+    let mut tables = Tables::default();
+    tables.layout(&root);
+    if tables.strings.len() > u32::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "Names, link targets and xattrs exceed the 4 GB string table limit"));
+    }
+
+    let mut image = vec![0u8; SUPERBLOCK_SIZE];
+    let block_table = write_blocks(&mut image, &tables.data, options)?;
+
+    let block_table_offset = image.len() as u64;
+    image.extend_from_slice(&block_table);
+
+    let inode_table_offset = image.len() as u64;
+    for inode in &tables.inodes {
+        inode.encode(&mut image);
+    }
+
+    let directory_table_offset = image.len() as u64;
+    image.extend_from_slice(&tables.directory);
+
+    let xattr_table_offset = image.len() as u64;
+    image.extend_from_slice(&tables.xattrs);
+
+    let string_table_offset = image.len() as u64;
+    image.extend_from_slice(&tables.strings);
+
+    let superblock = Superblock {
+        version: IMAGE_VERSION,
+        block_size: options.block_size,
+        inode_count: tables.inodes.len() as u32,
+        block_count: (block_table.len() / BLOCK_ENTRY_SIZE) as u32,
+        dir_entry_count: (tables.directory.len() / DIR_ENTRY_SIZE) as u32,
+        xattr_count: (tables.xattrs.len() / super::XATTR_ENTRY_SIZE) as u32,
+        data_size: tables.data.len() as u64,
+        block_table_offset,
+        inode_table_offset,
+        directory_table_offset,
+        xattr_table_offset,
+        string_table_offset,
+        string_table_size: tables.strings.len() as u64,
+    };
+    image[..SUPERBLOCK_SIZE].copy_from_slice(&superblock.encode());
+
+    Ok(image)
+}
+
+/// Compress the data stream block by block, returning the block table
+fn write_blocks(image: &mut Vec<u8>, data: &[u8], options: &ImageOptions) -> Result<Vec<u8>> {
+    let mut block_table = Vec::new();
+    for block in data.chunks(options.block_size as usize) {
+        let compressed = compress_zstd(block, options.level)?;
+        let (stored, flags) = if compressed.len() < block.len() {
+            (compressed.as_slice(), 0)
+        } else {
+            (block, BLOCK_RAW)
+        };
+
+        block_table.extend_from_slice(&(image.len() as u64).to_be_bytes());
+        block_table.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+        block_table.extend_from_slice(&flags.to_be_bytes());
+        image.extend_from_slice(stored);
+    }
+    Ok(block_table)
+}
+
+/// In-memory tree assembled from manifest entries
+struct Node<'a> {
+    entry: Option<&'a FileEntry>,
+    contents: &'a [u8],
+    children: BTreeMap<OsString, Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn directory(entry: Option<&'a FileEntry>) -> Self {
+        Self { entry, contents: &[], children: BTreeMap::new() }
+    }
+
+    fn insert(&mut self, entry: &'a FileEntry, blob: &'a [u8]) -> Result<()> {
+        let mut names = Vec::new();
+        for component in entry.path.components() {
+            match component {
+                Component::Normal(name) => names.push(name.to_os_string()),
+                Component::CurDir => {}
+                _ => return Err(invalid_path(&entry.path)),
+            }
+        }
+        let name = names.pop().ok_or_else(|| invalid_path(&entry.path))?;
+
+        let mut directory = self;
+        for parent in names {
+            directory = directory.children.entry(parent).or_insert_with(|| Node::directory(None));
+            if directory.entry.is_some_and(|e| e.file_type != FileType::Directory) {
+                return Err(invalid_path(&entry.path));
+            }
+        }
+
+        let contents = match entry.file_type {
+            FileType::Regular => entry.data(blob)?,
+            _ => &[],
+        };
+        let node = directory.children.entry(name).or_insert_with(|| Node::directory(None));
+        if entry.file_type != FileType::Directory && !node.children.is_empty() {
+            return Err(invalid_path(&entry.path));
+        }
+        node.entry = Some(entry);
+        node.contents = contents;
+        Ok(())
+    }
+
+    fn file_type(&self) -> FileType {
+        self.entry.map_or(FileType::Directory, |e| e.file_type)
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    inodes: Vec<Inode>,
+    directory: Vec<u8>,
+    xattrs: Vec<u8>,
+    strings: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Tables {
+    /// Number inodes breadth-first, so every directory's children are contiguous
+    fn layout(&mut self, root: &Node) {
+        let mut queue = std::collections::VecDeque::from([(root, ROOT_INODE)]);
+        let root_inode = self.inode(root, ROOT_INODE);
+        self.inodes.push(root_inode);
+
+        while let Some((node, ino)) = queue.pop_front() {
+            if node.file_type() != FileType::Directory {
+                continue;
+            }
+
+            let first_entry = (self.directory.len() / DIR_ENTRY_SIZE) as u32;
+            for (name, child) in &node.children {
+                let child_ino = self.inodes.len() as u32;
+                let name_offset = self.string(name.as_encoded_bytes());
+
+                self.directory.extend_from_slice(&(name_offset as u32).to_be_bytes());
+                self.directory.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                self.directory.extend_from_slice(&child_ino.to_be_bytes());
+                self.directory.extend_from_slice(&[0; 4]);
+
+                let inode = self.inode(child, ino);
+                self.inodes.push(inode);
+                queue.push_back((child, child_ino));
+            }
+
+            self.inodes[ino as usize].kind = InodeKind::Directory { first_entry, entry_count: node.children.len() as u32 };
+        }
+    }
+
+    fn inode(&mut self, node: &Node, parent: u32) -> Inode {
+        let (kind, type_bits) = match node.file_type() {
+            FileType::Directory => (InodeKind::Directory { first_entry: 0, entry_count: 0 }, 0o040000),
+            FileType::Regular => {
+                let data_offset = self.data.len() as u64;
+                self.data.extend_from_slice(node.contents);
+                (InodeKind::File { size: node.contents.len() as u64, data_offset }, 0o100000)
+            }
+            FileType::Symlink => {
+                let target = node.entry.and_then(|e| e.symlink_target.as_deref()).unwrap_or(Path::new(""));
+                let target = target.as_os_str().as_encoded_bytes();
+                let target_offset = self.string(target);
+                (InodeKind::Symlink { target_offset, target_len: target.len() as u32 }, 0o120000)
+            }
+        };
+
+        let first_xattr = (self.xattrs.len() / super::XATTR_ENTRY_SIZE) as u32;
+        let mut xattrs = node.entry.map(|e| e.xattrs.clone()).unwrap_or_default();
+        xattrs.sort();
+        for (name, value) in &xattrs {
+            let name_offset = self.string(name.as_bytes());
+            let value_offset = self.string(value);
+            for field in [name_offset as u32, name.len() as u32, value_offset as u32, value.len() as u32] {
+                self.xattrs.extend_from_slice(&field.to_be_bytes());
+            }
+        }
+
+        let (mode, uid, gid, mtime) = match node.entry {
+            Some(entry) => (entry.mode & 0o7777, entry.uid, entry.gid, entry.mtime.timestamp()),
+            None => (0o755, 0, 0, 0),
+        };
+
+        Inode {
+            kind,
+            mode: type_bits | mode,
+            uid,
+            gid,
+            mtime,
+            parent,
+            first_xattr,
+            xattr_count: xattrs.len() as u32,
+        }
+    }
+
+    fn string(&mut self, bytes: &[u8]) -> u64 {
+        let offset = self.strings.len() as u64;
+        self.strings.extend_from_slice(bytes);
+        offset
+    }
+}
+
+fn invalid_path(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("{}: entry path must be relative, normalized and below a directory", path.display())
+    )
+}
@@ -1,91 +1,201 @@
+pub mod error;
 pub mod format;
 pub mod compression;
 pub mod verification;
+pub mod encoding;
 pub mod archive;
 pub mod platform;
+pub mod config;
+pub mod armor;
+pub mod bench;
+pub mod cache;
+pub mod clip;
+pub mod convert;
+pub mod image;
+pub mod progress;
+pub mod stream;
+pub mod selftest;
+pub mod testing;
+pub mod tune;
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
 
-use std::io::{Write, Read, Result, Error, ErrorKind};
+use std::io::{Write, Read, Error, ErrorKind};
 use std::fs::File;
 use std::path::Path;
 
-pub use format::{GlifHeader, GlifSidecar};
+use compression::fallback;
+use sha2::Digest;
+use error::Result;
+use format::ChunkTable;
+
+pub use error::GlifError;
+pub use config::{CompressionConfig, CompressionConfigBuilder, CompressionLevel, ConfigError, DecompressionConfig, VerifyPolicy};
+pub use format::{inspect, read_sidecar_file, GlifHeader, GlifInfo, GlifSidecar, PeekInfo};
 pub use compression::{compress_zstd_multithreaded, decompress_lz4_multithreaded, CHUNK_SIZE, DEFAULT_COMPRESSION_LEVEL};
+pub use compression::{Codec, CompressionReport, MemoryEstimate, StageTimings};
 pub use verification::{calculate_sha256, verify_sha256, hex_encode, hex_decode};
-pub use archive::{ArchiveManifest, FileEntry, DirectoryCompressor};
-pub use archive::directory_compressor::DirectoryCompressionConfig;
-
-/// Configuration for compression
-#[derive(Debug, Clone)]
-pub struct CompressionConfig {
-    pub level: i32,
-    pub threads: usize,
-    pub use_lz4_decompression: bool,
-    pub deterministic: bool,
-}
-
-impl Default for CompressionConfig {
-    fn default() -> Self {
-        Self {
-            level: DEFAULT_COMPRESSION_LEVEL,
-            threads: num_cpus::get(),
-            use_lz4_decompression: true,
-            deterministic: true,
-        }
-    }
+#[cfg(feature = "cli")]
+pub use progress::ConsoleProgress;
+pub use progress::{ItemProgress, NoProgress, ProgressReporter};
+pub use stream::{GlifDecoder, GlifEncoder, RangeDecoder};
+pub use verification::{VerificationRecord, ArchiveHealth, scan_archives};
+pub use verification::{SweepReport, SweepOutcome, SweepStatus, sweep_archives, verify_archive_file, verify_archive_file_at, VerifyLevel};
+pub use archive::{ArchiveManifest, ManifestFormat, FileEntry, DirectoryCompressor, ExtractionReport, GlifArchive, ArchiveWriter, EntryReader, TimestampPolicy, UnsupportedPolicy, NamePolicy};
+pub use archive::directory_compressor::{DirectoryCompressionConfig, ExtractOptions};
+
+/// Compress data and create a GLIF archive
+pub fn compress(data: &[u8], config: &CompressionConfig) -> Result<Vec<u8>> {
+    compress_with_report(data, config).map(|(archive, _)| archive)
 }
 
-impl CompressionConfig {
-    pub fn new(level: i32, threads: usize) -> Self {
-        Self {
-            level,
-            threads,
-            use_lz4_decompression: true,
-            deterministic: true,
-        }
-    }
+/// Compress data and report sizes, per-stage timings and codec alongside the archive
+pub fn compress_with_report(data: &[u8], config: &CompressionConfig) -> Result<(Vec<u8>, CompressionReport)> {
+    compress_annotated(data, config, |_| {})
+}
 
-    pub fn fast() -> Self {
-        Self {
-            level: 3,
-            threads: num_cpus::get(),
-            use_lz4_decompression: true,
-            deterministic: true,
-        }
-    }
+/// Compress data, letting `annotate` add details to the sidecar before it is written
+pub(crate) fn compress_annotated<F>(data: &[u8], config: &CompressionConfig, annotate: F) -> Result<(Vec<u8>, CompressionReport)>
+where
+    F: FnOnce(&mut GlifSidecar),
+{
+    let mut stopwatch = compression::Stopwatch::start();
+    let mut stages = StageTimings::default();
+    config.validate()?;
+    let threads = compression::resolve_threads(config.threads);
 
-    pub fn balanced() -> Self {
-        Self::default()
-    }
+    // Calculate SHA256 of uncompressed data
+    stopwatch.lap();
+    let payload_hash = calculate_sha256(data);
+    stages.payload_hash = stopwatch.lap();
 
-    pub fn high_compression() -> Self {
-        Self {
-            level: 16,
-            threads: num_cpus::get(),
-            use_lz4_decompression: false,
-            deterministic: true,
+    // Compress data using Zstd
+    let workers = compression::chunks_within_budget(threads, config.max_in_flight);
+    let compressed_data = match config.install(|| compression::compress_zstd_chunked(data, config.level, config.chunk_size, workers)) {
+        // Chunks compress the same on any number of threads, so the archive doesn't change
+        Err(e) if workers > 1 && fallback::is_memory_pressure(&e) => {
+            fallback::report_downgrade("compression", &e.to_string());
+            config.install(|| compression::compress_zstd_chunked(data, config.level, config.chunk_size, 1))?
         }
+        result => result?,
+    };
+    stages.zstd = stopwatch.lap();
+
+    let (archive_data, decompression_mode, chunk_table) = apply_lz4_layer(compressed_data, config, threads)?;
+    if config.use_lz4_decompression {
+        stages.lz4 = stopwatch.lap();
     }
-}
 
-/// Compress data and create a GLIF archive
-pub fn compress(data: &[u8], config: &CompressionConfig) -> Result<Vec<u8>> {
-    // Calculate SHA256 of uncompressed data
-    let payload_hash = calculate_sha256(data);
+    let (result, decompression_mode) = finish_archive(
+        data.len() as u64,
+        payload_hash,
+        archive_data,
+        decompression_mode,
+        chunk_table,
+        config,
+        threads,
+        annotate,
+    )?;
+    stages.assemble = stopwatch.lap();
 
-    // Compress data using Zstd
-    let compressed_data = compress_zstd_multithreaded(data, config.level, config.threads)?;
+    let report = CompressionReport::new(
+        data.len() as u64,
+        result.len() as u64,
+        stopwatch.elapsed(),
+        compression::framing::chunk_count(data.len(), config.chunk_size),
+        Codec::from_decompression_mode(decompression_mode),
+    ).with_stages(stages);
 
-    // If using LZ4 decompression mode, we need to recompress with LZ4
-    let (archive_data, decompression_mode) = if config.use_lz4_decompression {
-        let lz4_compressed = compression::compress_lz4_multithreaded(&compressed_data, config.threads)?;
-        (lz4_compressed, 0u32)
+    Ok((result, report))
+}
+
+/// Build an archive around a framed Zstd stream: the optional LZ4 layer,
+/// then the header and sidecar; returns the archive and its decompression mode
+pub(crate) fn assemble_archive<F>(
+    payload_size: u64,
+    payload_hash: [u8; 32],
+    compressed_data: Vec<u8>,
+    config: &CompressionConfig,
+    threads: usize,
+    annotate: F,
+) -> Result<(Vec<u8>, u32)>
+where
+    F: FnOnce(&mut GlifSidecar),
+{
+    let (archive_data, decompression_mode, chunk_table) = apply_lz4_layer(compressed_data, config, threads)?;
+    finish_archive(payload_size, payload_hash, archive_data, decompression_mode, chunk_table, config, threads, annotate)
+}
+
+/// Wrap a framed Zstd stream in the LZ4 layer if the config asks for it;
+/// returns the archive data, its decompression mode and its chunk table
+fn apply_lz4_layer(compressed_data: Vec<u8>, config: &CompressionConfig, threads: usize) -> Result<(Vec<u8>, u32, ChunkTable)> {
+    if config.use_lz4_decompression {
+        let workers = compression::chunks_within_budget(threads, config.max_in_flight);
+        let lz4_compressed = config.install(|| compression::compress_lz4_chunked(&compressed_data, config.chunk_size, workers))?;
+        let chunk_table = ChunkTable::from_frames(&compressed_data, Some(&lz4_compressed))?;
+        Ok((lz4_compressed, 0, chunk_table))
     } else {
-        (compressed_data, 1u32)
+        let chunk_table = ChunkTable::from_frames(&compressed_data, None)?;
+        Ok((compressed_data, 1, chunk_table))
+    }
+}
+
+/// Hash the archive data and put the header and sidecar in front of it
+#[allow(clippy::too_many_arguments)]
+fn finish_archive<F>(
+    payload_size: u64,
+    payload_hash: [u8; 32],
+    archive_data: Vec<u8>,
+    decompression_mode: u32,
+    chunk_table: ChunkTable,
+    config: &CompressionConfig,
+    threads: usize,
+    annotate: F,
+) -> Result<(Vec<u8>, u32)>
+where
+    F: FnOnce(&mut GlifSidecar),
+{
+    let block_size = verification::localize::integrity_block_size(archive_data.len());
+    let summary = ArchiveSummary {
+        payload_size,
+        payload_hash,
+        archive_size: archive_data.len() as u64,
+        archive_hash: calculate_sha256(&archive_data),
+        decompression_mode,
+        block_size,
+        block_hashes: verification::localize::block_hashes(&archive_data, block_size),
+        chunk_table,
     };
 
-    // Calculate SHA256 of compressed data
-    let archive_hash = calculate_sha256(&archive_data);
+    let mut result = archive_prefix(summary, config, threads, annotate)?;
+    result.write_all(&archive_data)?;
+
+    Ok((result, decompression_mode))
+}
 
+/// What the header and sidecar record about a payload and its archive data
+pub(crate) struct ArchiveSummary {
+    pub payload_size: u64,
+    pub payload_hash: [u8; 32],
+    pub archive_size: u64,
+    pub archive_hash: [u8; 32],
+    pub decompression_mode: u32,
+    pub block_size: usize,
+    pub block_hashes: Vec<String>,
+    pub chunk_table: ChunkTable,
+}
+
+/// Serialize the header and sidecar that precede the archive data
+pub(crate) fn archive_prefix<F>(
+    summary: ArchiveSummary,
+    config: &CompressionConfig,
+    threads: usize,
+    annotate: F,
+) -> Result<Vec<u8>>
+where
+    F: FnOnce(&mut GlifSidecar),
+{
     // Create sidecar metadata
     let timestamp = if config.deterministic {
         Some("2025-01-01T00:00:00.000000000+00:00".to_string())
@@ -93,19 +203,31 @@ pub fn compress(data: &[u8], config: &CompressionConfig) -> Result<Vec<u8>> {
         None
     };
 
-    let sidecar = format::GlifSidecar::new_with_timestamp(
-        data.len() as u64,
-        archive_data.len() as u64,
-        &payload_hash,
-        &archive_hash,
-        config.level as u32,
-        config.threads as u32,
-        decompression_mode,
+    let mut sidecar = format::GlifSidecar::new_with_timestamp(
+        summary.payload_size,
+        summary.archive_size,
+        &summary.payload_hash,
+        &summary.archive_hash,
+        config.level,
+        threads as u32,
+        summary.decompression_mode,
         timestamp,
     );
 
+    let memory = MemoryEstimate::new(config.level, summary.payload_size as usize, threads);
+    sidecar.archive.window_size = Some(memory.window_size);
+    sidecar.archive.decompression_memory = Some(memory.decompression);
+
+    sidecar.archive.integrity_block_size = Some(summary.block_size as u64);
+    sidecar.archive.block_hashes = Some(summary.block_hashes);
+    sidecar.metadata.sensitive = config.sensitive;
+    sidecar.icon = config.icon.clone();
+    annotate(&mut sidecar);
+
     let sidecar_json = sidecar.to_json()?;
-    let sidecar_size = sidecar_json.len() as u16;
+    let sidecar_size = u32::try_from(sidecar_json.len()).ok().filter(|&size| size <= format::MAX_SIDECAR_SIZE).ok_or_else(|| GlifError::InvalidInput(
+        format!("Sidecar is {} bytes, more than the header can record", sidecar_json.len())
+    ))?;
 
     // Create header
     let header_timestamp = if config.deterministic {
@@ -114,35 +236,49 @@ pub fn compress(data: &[u8], config: &CompressionConfig) -> Result<Vec<u8>> {
         None
     };
 
-    let header = format::GlifHeader::new_with_timestamp(
-        data.len() as u64,
-        archive_data.len() as u64,
-        payload_hash,
-        archive_hash,
-        config.level as u32,
-        decompression_mode,
-        config.threads as u32,
+    let mut header = format::GlifHeader::new_with_timestamp(
+        summary.payload_size,
+        summary.archive_size,
+        summary.payload_hash,
+        summary.archive_hash,
+        config.level,
+        summary.decompression_mode,
+        threads as u32,
         sidecar_size,
         header_timestamp,
     );
+    // Archives with too many chunks to list in the header go without; readers walk the framing instead
+    header.set_chunk_table(&summary.chunk_table);
 
-    // Build final archive
     let mut result = Vec::new();
     header.write(&mut result)?;
     result.write_all(sidecar_json.as_bytes())?;
-    result.write_all(&archive_data)?;
 
     Ok(result)
 }
 
 /// Decompress a GLIF archive
+/// A thread count of 0 uses all available cores
 pub fn decompress(archive: &[u8], threads: usize) -> Result<Vec<u8>> {
-    // Parse header
-    let mut cursor = std::io::Cursor::new(archive);
-    let header = GlifHeader::read(&mut cursor)?;
+    decompress_with(archive, &DecompressionConfig::new().with_threads(threads))
+}
+
+/// Decompress a GLIF archive using as many threads as the archive can use
+pub fn decompress_auto(archive: &[u8]) -> Result<Vec<u8>> {
+    decompress_with(archive, &DecompressionConfig::default())
+}
+
+/// Decompress a GLIF archive with explicit decompression settings
+pub fn decompress_with(archive: &[u8], config: &DecompressionConfig) -> Result<Vec<u8>> {
+    decompress_with_progress(archive, config, &mut NoProgress)
+}
 
-    // Read sidecar (not currently used but needed to advance cursor position)
-    let _sidecar = GlifSidecar::read(&mut cursor, header.sidecar_size)?;
+/// Decompress a GLIF archive, reporting each stage: header, sidecar,
+/// archive hash, decompress and payload hash (the hash stages only when
+/// `config.verify` checks them)
+pub fn decompress_with_progress(archive: &[u8], config: &DecompressionConfig, progress: &mut dyn ProgressReporter) -> Result<Vec<u8>> {
+    let mut cursor = std::io::Cursor::new(archive);
+    let (header, sidecar) = read_header_and_sidecar(&mut cursor, progress)?;
 
     // Get current position (start of compressed data)
     let header_and_sidecar_size = cursor.position() as usize;
@@ -150,56 +286,288 @@ pub fn decompress(archive: &[u8], threads: usize) -> Result<Vec<u8>> {
     // Extract compressed data
     let compressed_data = &archive[header_and_sidecar_size..];
 
+    // Drop to one thread if decompressing in parallel would exceed the
+    // memory ceiling or what is available, and refuse only if even that
+    // can't fit the archive's recorded window
+    let payload_size = header.payload_size;
+    let window_size = sidecar.archive.window_size.unwrap_or_else(|| {
+        1 << compression::memory::window_log(sidecar.archive.compression_level, payload_size as usize)
+    });
+    let ceiling = match (config.max_memory, platform::available_memory()) {
+        (Some(limit), Some(available)) => Some(limit.min(available)),
+        (limit, available) => limit.or(available),
+    };
+    let threads = fallback::threads_within(config.threads_for(payload_chunk_count(&header, compressed_data)), ceiling, |threads| {
+        compression::memory::decompression_memory(payload_size, window_size, threads)
+    }, "decompression");
+    if sidecar.archive.window_size.is_some() {
+        check_available_memory(compression::memory::decompression_memory(payload_size, window_size, threads))?;
+    }
+
     // Verify archive hash
-    verify_sha256(compressed_data, &header.archive_hash)?;
+    if config.verify.checks_archive() {
+        verify_archive_data(compressed_data, header_and_sidecar_size as u64, &header, &sidecar, progress)?;
+    }
 
     // Decompress based on mode
-    let decompressed_data = if header.decompression_mode == 0 {
+    progress.start_stage("decompress", 0);
+    let decompressed = if header.decompression_mode == 0 {
         // LZ4 mode
-        let lz4_decompressed = compression::decompress_lz4_multithreaded(compressed_data, threads)?;
-        // The LZ4 layer decompresses to Zstd-compressed data
-        compression::decompress_zstd_multithreaded(&lz4_decompressed, threads)?
+        check_framing(&header, compressed_data)?;
+        compression::decompress_lz4_multithreaded(compressed_data, threads).and_then(|lz4_decompressed| {
+            // The LZ4 layer decompresses to Zstd-compressed data
+            check_framing(&header, &lz4_decompressed)?;
+            compression::decompress_zstd_multithreaded(&lz4_decompressed, threads)
+        })
     } else {
         // Zstd-only mode
-        compression::decompress_zstd_multithreaded(compressed_data, threads)?
+        check_framing(&header, compressed_data)?;
+        compression::decompress_zstd_multithreaded(compressed_data, threads)
+    };
+    let decompressed_data = match decompressed {
+        Err(e) if threads > 1 && fallback::is_memory_pressure(&e) => {
+            // Stream one chunk at a time instead; the hashes are still checked below
+            fallback::report_downgrade("decompression", &e.to_string());
+            let single = DecompressionConfig::new().with_threads(1).with_verify_hashes(false);
+            let mut data = Vec::new();
+            GlifDecoder::with_config(archive, &single)?.read_to_end(&mut data)?;
+            data
+        }
+        result => result?,
     };
 
+    progress.finish_stage();
+
     // Verify payload hash
-    verify_sha256(&decompressed_data, &header.payload_hash)?;
+    if config.verify.checks_payload() {
+        progress.start_stage("payload hash", decompressed_data.len() as u64);
+        let payload_hash = verification::calculate_sha256_with_progress(&decompressed_data, progress);
+        verification::sha256::check_sha256(&payload_hash, &header.payload_hash)?;
+        progress.finish_stage();
+    }
 
     // Verify size matches
     if decompressed_data.len() != header.payload_size as usize {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            format!(
-                "Decompressed size mismatch: expected {}, got {}",
-                header.payload_size,
-                decompressed_data.len()
-            )
-        ));
+        return Err(GlifError::SizeMismatch {
+            expected: header.payload_size,
+            actual: decompressed_data.len() as u64,
+        });
     }
 
     Ok(decompressed_data)
 }
 
+/// Decompress a GLIF archive into `out`, returning the payload size
+///
+/// `out` must hold at least `header.payload_size` bytes; anything after the
+/// payload is left untouched, so one buffer can be reused across archives.
+/// A thread count of 0 uses all available cores.
+pub fn decompress_into(archive: &[u8], out: &mut [u8], threads: usize) -> Result<usize> {
+    decompress_into_with(archive, out, &DecompressionConfig::new().with_threads(threads))
+}
+
+/// Decompress a GLIF archive into `out` with explicit decompression settings
+///
+/// Chunks are decompressed straight into their place in `out`. Format v1.0
+/// archives don't record chunk sizes, so their payload is decompressed
+/// first and then copied in.
+pub fn decompress_into_with(archive: &[u8], out: &mut [u8], config: &DecompressionConfig) -> Result<usize> {
+    let mut cursor = std::io::Cursor::new(archive);
+    let (header, sidecar) = read_header_and_sidecar(&mut cursor, &mut NoProgress)?;
+
+    let payload_size = header.payload_size as usize;
+    if out.len() < payload_size {
+        return Err(GlifError::InvalidInput(format!(
+            "Output buffer holds {} bytes but the payload is {} bytes",
+            out.len(), payload_size
+        )));
+    }
+    let out = &mut out[..payload_size];
+
+    let header_and_sidecar_size = cursor.position() as usize;
+    let compressed_data = &archive[header_and_sidecar_size..];
+    if config.verify.checks_archive() {
+        verify_archive_data(compressed_data, header_and_sidecar_size as u64, &header, &sidecar, &mut NoProgress)?;
+    }
+
+    let threads = config.threads_for(payload_chunk_count(&header, compressed_data));
+    check_framing(&header, compressed_data)?;
+    let lz4_decompressed;
+    let zstd_data = if header.decompression_mode == 0 {
+        // The LZ4 layer decompresses to Zstd-compressed data
+        lz4_decompressed = compression::decompress_lz4_multithreaded(compressed_data, threads)?;
+        check_framing(&header, &lz4_decompressed)?;
+        &lz4_decompressed[..]
+    } else {
+        compressed_data
+    };
+
+    let chunks = if compression::framing::is_framed(zstd_data) {
+        compression::framing::read_frames(zstd_data)?
+    } else {
+        Vec::new()
+    };
+    if !chunks.is_empty() && chunks.iter().all(|chunk| chunk.uncompressed_size.is_some()) {
+        compression::framing::decompress_chunks_into(&chunks, out, threads, compression::decompress_zstd_into)?;
+    } else {
+        let payload = compression::decompress_zstd_multithreaded(zstd_data, threads)?;
+        if payload.len() != payload_size {
+            return Err(GlifError::SizeMismatch { expected: header.payload_size, actual: payload.len() as u64 });
+        }
+        out.copy_from_slice(&payload);
+    }
+
+    if config.verify.checks_payload() {
+        let payload_hash = verification::calculate_sha256(out);
+        verification::sha256::check_sha256(&payload_hash, &header.payload_hash)?;
+    }
+
+    Ok(payload_size)
+}
+
+/// Read and check the header and sidecar, reporting each as a stage
+fn read_header_and_sidecar<R: Read>(reader: &mut R, progress: &mut dyn ProgressReporter) -> Result<(GlifHeader, GlifSidecar)> {
+    progress.start_stage("header", 0);
+    let header = GlifHeader::read(reader)?;
+    progress.finish_stage();
+
+    progress.start_stage("sidecar", 0);
+    let sidecar = GlifSidecar::read(reader, header.sidecar_size)?;
+    sidecar.check_reader_compatibility()?;
+    sidecar.check_hashes()?;
+    progress.finish_stage();
+
+    Ok((header, sidecar))
+}
+
+/// Verify the archive hash, localizing any damage using the sidecar's block hashes
+fn verify_archive_data(
+    data: &[u8],
+    data_offset: u64,
+    header: &GlifHeader,
+    sidecar: &GlifSidecar,
+    progress: &mut dyn ProgressReporter,
+) -> Result<()> {
+    progress.start_stage("archive hash", data.len() as u64);
+    let actual = verification::calculate_sha256_with_progress(data, progress);
+    if actual == header.archive_hash {
+        progress.finish_stage();
+        return Ok(());
+    }
+
+    let regions = match (sidecar.archive.integrity_block_size, &sidecar.archive.block_hashes) {
+        (Some(block_size), Some(hashes)) if block_size > 0 => {
+            verification::localize::locate_corruption(data, block_size as usize, hashes)
+        }
+        _ => Vec::new(),
+    };
+
+    let mut error = verification::CorruptionError {
+        expected: hex_encode(&header.archive_hash),
+        actual: hex_encode(&actual),
+        regions,
+        data_offset,
+        payload_range: None,
+        affected_entries: Vec::new(),
+    };
+    let chunk_size = header.payload_size.div_ceil(payload_chunk_count(header, data) as u64).max(1);
+    error.estimate_payload_range(header.archive_size, header.payload_size, chunk_size);
+
+    Err(error.into())
+}
+
+/// Number of chunks the payload in `data` was compressed in: the Zstd
+/// chunks in the header's chunk table, else the count in the framing of
+/// `data`, else format v1.0's fixed chunk size
+pub(crate) fn payload_chunk_count(header: &GlifHeader, data: &[u8]) -> usize {
+    match header.chunk_table() {
+        Ok(Some(table)) => table.zstd.len(),
+        _ => compression::framing::framed_chunk_count(data)
+            .unwrap_or_else(|| compression::framing::chunk_count(header.payload_size as usize, CHUNK_SIZE)),
+    }
+}
+
+/// Require chunk framing for formats that always write it
+fn check_framing(header: &GlifHeader, data: &[u8]) -> Result<()> {
+    if header.is_framed() && !compression::framing::is_framed(data) {
+        return Err(compression::FramingError::NotFramed.into());
+    }
+    Ok(())
+}
+
+/// Fail with `OutOfMemory` if the platform reports less memory than required
+fn check_available_memory(required: u64) -> Result<()> {
+    match platform::available_memory() {
+        Some(available) if required > available => Err(Error::new(
+            ErrorKind::OutOfMemory,
+            format!(
+                "Archive needs ~{} MiB to decompress but only {} MiB is available; try fewer threads",
+                required.div_ceil(1024 * 1024),
+                available / (1024 * 1024)
+            )
+        ).into()),
+        _ => Ok(()),
+    }
+}
+
 /// Compress a file and save as GLIF archive
+///
+/// The file is streamed through a `GlifEncoder` rather than read whole:
+/// it is hashed and compressed a batch of chunks at a time, and compressed
+/// chunks are spilled next to the output until the header can be written.
+/// The archive is identical to `compress()` output for the file's contents.
+///
+/// The input is only read forward, never sought or sized up front, so
+/// pipes, `/dev/stdin` and process substitution work within the same
+/// bounded memory as regular files.
 pub fn compress_file<P: AsRef<Path>, Q: AsRef<Path>>(
     input_path: P,
     output_path: Q,
     config: &CompressionConfig,
 ) -> Result<()> {
-    // Read input file
-    let mut file = File::open(input_path)?;
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)?;
+    let output_path = output_path.as_ref();
+    let mut input = File::open(input_path)?;
 
-    // Compress
-    let compressed = compress(&data, config)?;
+    let output = std::io::BufWriter::new(File::create(output_path)?);
+    let written = GlifEncoder::new(output, config).and_then(|encoder| {
+        let mut encoder = encoder.with_spill_dir(stream::spill_dir_for(output_path));
+        std::io::copy(&mut input, &mut encoder)?;
+        encoder.finish()
+    });
 
-    // Write output file
-    let mut output = File::create(output_path)?;
-    output.write_all(&compressed)?;
+    if let Err(e) = written {
+        // Don't leave a truncated archive behind
+        let _ = std::fs::remove_file(output_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Compress a file into a standard `.zst` file with no GLIF header, for
+/// systems that only understand zstd
+///
+/// Chunks are compressed in parallel as for a GLIF archive, each into its
+/// own zstd frame; there is no sidecar, so nothing beyond zstd's frame
+/// checksums is recorded.
+pub fn compress_file_to_zst<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    config: &CompressionConfig,
+) -> Result<()> {
+    config.validate()?;
+    let output_path = output_path.as_ref();
+    let input = File::open(input_path)?;
 
+    let output = std::io::BufWriter::new(File::create(output_path)?);
+    let written = config.install(|| compression::raw::compress_zstd_stream(
+        input, output, config.level, config.chunk_size, config.chunks_in_flight(), config.effective_threads()
+    ));
+
+    if let Err(e) = written {
+        // Don't leave a truncated file behind
+        let _ = std::fs::remove_file(output_path);
+        return Err(e);
+    }
     Ok(())
 }
 
@@ -209,40 +577,134 @@ pub fn decompress_file<P: AsRef<Path>, Q: AsRef<Path>>(
     output_path: Q,
     threads: usize,
 ) -> Result<()> {
-    // Read archive file
-    let mut file = File::open(input_path)?;
-    let mut archive = Vec::new();
-    file.read_to_end(&mut archive)?;
+    decompress_file_with(input_path, output_path, &DecompressionConfig::new().with_threads(threads))
+}
+
+/// Decompress a GLIF archive file with explicit decompression settings
+pub fn decompress_file_with<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    config: &DecompressionConfig,
+) -> Result<()> {
+    // Read archive file, decoding ASCII armor if present
+    let archive = armor::read_archive_file(input_path.as_ref())?;
 
     // Decompress
-    let decompressed = decompress(&archive, threads)?;
+    let decompressed = decompress_with(&archive, config)?;
 
-    // Write output file
+    // Write output file, reserving its space first (a hint that may be unsupported)
     let mut output = File::create(output_path)?;
+    let _ = platform::preallocate(&output, decompressed.len() as u64);
     output.write_all(&decompressed)?;
 
     Ok(())
 }
 
+/// Decompress the GLIF archive read from `reader` into `writer`, returning
+/// the number of payload bytes written
+///
+/// Up to `threads` chunks (0: auto-detect) are decompressed at a time, so the
+/// payload never has to fit in memory. The hashes are checked once the last
+/// chunk is read: on a mismatch `writer` has already received the payload
+/// and the caller must discard it.
+pub fn decompress_to_writer<R: Read, W: Write>(reader: R, writer: W, threads: usize) -> Result<u64> {
+    decompress_to_writer_with(reader, writer, &DecompressionConfig::new().with_threads(threads))
+}
+
+/// Decompress the GLIF archive read from `reader` into `writer` with
+/// explicit decompression settings
+pub fn decompress_to_writer_with<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    config: &DecompressionConfig,
+) -> Result<u64> {
+    let mut decoder = GlifDecoder::with_config(reader, config)?;
+    let written = std::io::copy(&mut decoder, &mut writer)?;
+    writer.flush()?;
+    Ok(written)
+}
+
 /// Verify a GLIF archive without decompressing
 pub fn verify_archive(archive: &[u8]) -> Result<GlifSidecar> {
+    verify_archive_with_progress(archive, &mut NoProgress)
+}
+
+/// Verify a GLIF archive as thoroughly as `level` asks
+pub fn verify_archive_with_level(archive: &[u8], level: VerifyLevel) -> Result<GlifSidecar> {
+    match level {
+        VerifyLevel::Structural => verify_structure(archive),
+        VerifyLevel::Archive => verify_archive_hash(archive),
+        VerifyLevel::Full => {
+            verify_archive_hash(archive)?;
+            verify_payload_hash(archive)
+        }
+    }
+}
+
+/// Check the header, sidecar and archive data length, without hashing anything
+pub fn verify_structure(archive: &[u8]) -> Result<GlifSidecar> {
     let mut cursor = std::io::Cursor::new(archive);
-    let header = GlifHeader::read(&mut cursor)?;
-    let sidecar = GlifSidecar::read(&mut cursor, header.sidecar_size)?;
+    let (header, sidecar) = read_header_and_sidecar(&mut cursor, &mut NoProgress)?;
+    let data = &archive[cursor.position() as usize..];
+    if (data.len() as u64) < header.archive_size {
+        return Err(GlifError::Truncated(format!(
+            "Archive data ends after {} of {} bytes",
+            data.len(),
+            header.archive_size
+        )));
+    }
+    check_framing(&header, data)?;
+    Ok(sidecar)
+}
+
+/// Check the archive hash of the compressed data, without decompressing
+///
+/// The same check as `verify_archive`: it catches damage to the archive
+/// file, but not a payload that never matched its header.
+pub fn verify_archive_hash(archive: &[u8]) -> Result<GlifSidecar> {
+    verify_archive(archive)
+}
+
+/// Decompress the archive and check its payload hash and size, without
+/// checking the archive hash
+///
+/// The payload is streamed through the hash rather than held in memory.
+pub fn verify_payload_hash(archive: &[u8]) -> Result<GlifSidecar> {
+    let mut decoder = GlifDecoder::with_config(archive, &DecompressionConfig::new().with_verify(VerifyPolicy::Trusted))?;
+    let mut hasher = sha2::Sha256::new();
+    let size = std::io::copy(&mut decoder, &mut hasher)?;
+
+    let header = decoder.header();
+    if size != header.payload_size {
+        return Err(GlifError::SizeMismatch { expected: header.payload_size, actual: size });
+    }
+    verification::sha256::check_sha256(&hasher.finalize().into(), &header.payload_hash)?;
+    Ok(decoder.sidecar().clone())
+}
+
+/// Verify a GLIF archive without decompressing, reporting each stage:
+/// header, sidecar and archive hash
+pub fn verify_archive_with_progress(archive: &[u8], progress: &mut dyn ProgressReporter) -> Result<GlifSidecar> {
+    let mut cursor = std::io::Cursor::new(archive);
+    let (header, sidecar) = read_header_and_sidecar(&mut cursor, progress)?;
 
     // Get compressed data position
     let header_and_sidecar_size = cursor.position() as usize;
     let compressed_data = &archive[header_and_sidecar_size..];
 
     // Verify archive hash
-    verify_sha256(compressed_data, &header.archive_hash)?;
+    verify_archive_data(compressed_data, header_and_sidecar_size as u64, &header, &sidecar, progress)?;
 
     Ok(sidecar)
 }
 
 // Helper function to get number of CPUs (we'll use rayon's default if num_cpus isn't available)
-mod num_cpus {
+pub(crate) mod num_cpus {
+    /// Available parallelism, or 1 when built without the `parallel` feature
     pub fn get() -> usize {
+        if !cfg!(feature = "parallel") {
+            return 1;
+        }
         std::thread::available_parallelism()
             .map(|n| n.get())
             .unwrap_or(8)
@@ -275,6 +737,225 @@ mod tests {
         assert_eq!(result1, result2, "Compression not deterministic");
     }
 
+    #[test]
+    fn test_corruption_is_localized() {
+        let data = b"Localize the damage".repeat(100);
+        let mut archive = compress(&data, &CompressionConfig::default()).unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 0xff;
+
+        let err = decompress(&archive, 1).unwrap_err();
+        let corruption = err.corruption().unwrap();
+        assert_eq!(corruption.regions.len(), 1);
+        assert_eq!(corruption.regions[0].block, 0);
+        assert_eq!(corruption.payload_range, Some((0, data.len() as u64)));
+        assert!(err.to_string().contains("Damaged blocks"));
+    }
+
+    #[test]
+    fn test_corruption_is_localized_to_small_chunks() {
+        let chunk_size = compression::MIN_CHUNK_SIZE;
+        let data = testing::data::generate(testing::data::DataKind::Random, 3 * chunk_size, 7);
+        let config = CompressionConfig::builder().chunk_size(chunk_size).use_lz4_decompression(false).build().unwrap();
+        let mut archive = compress(&data, &config).unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 0xff;
+
+        let err = decompress(&archive, 1).unwrap_err();
+        let corruption = err.corruption().unwrap();
+        assert_eq!(corruption.payload_range, Some((2 * chunk_size as u64, data.len() as u64)));
+    }
+
+    #[test]
+    fn test_small_archives_are_framed() {
+        for use_lz4_decompression in [true, false] {
+            let config = CompressionConfig { use_lz4_decompression, ..CompressionConfig::default() };
+            let archive = compress(b"tiny", &config).unwrap();
+
+            let mut cursor = std::io::Cursor::new(&archive);
+            let header = GlifHeader::read(&mut cursor).unwrap();
+            GlifSidecar::read(&mut cursor, header.sidecar_size).unwrap();
+            let data = &archive[cursor.position() as usize..];
+
+            assert!(header.is_framed());
+            assert_eq!(compression::framing::read_frames(data).unwrap().len(), 1);
+            assert_eq!(decompress(&archive, 1).unwrap(), b"tiny");
+        }
+    }
+
+    #[test]
+    fn test_compress_with_report() {
+        let data = b"Report the compression ratio".repeat(100);
+        for use_lz4_decompression in [true, false] {
+            let config = CompressionConfig { use_lz4_decompression, ..CompressionConfig::default() };
+            let (archive, report) = compress_with_report(&data, &config).unwrap();
+
+            assert_eq!(archive, compress(&data, &config).unwrap());
+            assert_eq!(report.input_bytes, data.len() as u64);
+            assert_eq!(report.output_bytes, archive.len() as u64);
+            assert_eq!(report.ratio, archive.len() as f64 / data.len() as f64);
+            assert_eq!(report.chunks, 1);
+            assert_eq!(report.codec.decompression_mode(), if use_lz4_decompression { 0 } else { 1 });
+            assert!(report.stages.total() <= report.elapsed);
+            assert!(report.stages.zstd > std::time::Duration::ZERO);
+            assert_eq!(report.stages.lz4 > std::time::Duration::ZERO, use_lz4_decompression);
+        }
+    }
+
+    #[test]
+    fn test_sensitive_flag() {
+        let plain = verify_archive(&compress(b"config", &CompressionConfig::default()).unwrap()).unwrap();
+        assert!(!plain.metadata.sensitive);
+        assert!(!plain.to_json().unwrap().contains("sensitive"));
+
+        let config = CompressionConfig::builder().sensitive(true).build().unwrap();
+        let archive = compress(b"config", &config).unwrap();
+        assert!(verify_archive(&archive).unwrap().metadata.sensitive);
+        assert_eq!(decompress(&archive, 1).unwrap(), b"config");
+    }
+
+    #[test]
+    fn test_sidecar_over_64k() {
+        let signature = "ab".repeat(50_000);
+        let (archive, _) = compress_annotated(b"signed", &CompressionConfig::default(), |sidecar| {
+            sidecar.cryptography.signature = Some(signature.clone());
+        }).unwrap();
+
+        let header = format::GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+        assert!(header.sidecar_size > u16::MAX as u32);
+        assert_eq!(verify_archive(&archive).unwrap().cryptography.signature, Some(signature));
+        assert_eq!(decompress(&archive, 1).unwrap(), b"signed");
+        let info = format::GlifHeader::peek(&archive).unwrap();
+        assert!(info.is_complete());
+    }
+
+    #[test]
+    fn test_verification_progress_stages() {
+        use crate::progress::tests::RecordingProgress;
+
+        let data = b"staged verification".repeat(1000);
+        let archive = compress(&data, &CompressionConfig::default()).unwrap();
+        let header = GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+        let stream_size = header.archive_size;
+
+        let mut progress = RecordingProgress::default();
+        verify_archive_with_progress(&archive, &mut progress).unwrap();
+        assert_eq!(progress.events, [
+            "start header 0".to_string(), "finish".to_string(),
+            "start sidecar 0".to_string(), "finish".to_string(),
+            format!("start archive hash {}", stream_size), format!("advance {}", stream_size), "finish".to_string(),
+        ]);
+
+        let mut progress = RecordingProgress::default();
+        let config = DecompressionConfig::new().with_threads(1);
+        assert_eq!(decompress_with_progress(&archive, &config, &mut progress).unwrap(), data);
+        assert_eq!(progress.events[7..], [
+            "start decompress 0".to_string(), "finish".to_string(),
+            format!("start payload hash {}", data.len()), format!("advance {}", data.len()), "finish".to_string(),
+        ]);
+
+        // A failing stage is started but never finished
+        let mut corrupt = archive.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        let mut progress = RecordingProgress::default();
+        assert!(verify_archive_with_progress(&corrupt, &mut progress).is_err());
+        assert_eq!(progress.events.last().unwrap(), &format!("advance {}", stream_size));
+    }
+
+    #[test]
+    fn test_decompress_without_hash_checks() {
+        let data = b"trusted local archive".repeat(1000);
+        let archive = compress(&data, &CompressionConfig::default()).unwrap();
+
+        // Wrong hashes under a valid header checksum
+        let mut header = GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+        header.payload_hash = [0; 32];
+        header.archive_hash = [0; 32];
+        let mut tampered = Vec::new();
+        header.write(&mut tampered).unwrap();
+        tampered.extend_from_slice(&archive[tampered.len()..]);
+
+        let config = DecompressionConfig::new().with_threads(1);
+        assert!(decompress_with(&tampered, &config).is_err());
+        let config = config.with_verify_hashes(false);
+        assert_eq!(decompress_with(&tampered, &config).unwrap(), data);
+
+        // Each policy checks only its own hash, in memory and when streaming
+        for (wrong_archive_hash, passes, fails) in [(false, VerifyPolicy::Archive, VerifyPolicy::Payload), (true, VerifyPolicy::Payload, VerifyPolicy::Archive)] {
+            let mut header = GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+            if wrong_archive_hash {
+                header.archive_hash = [0; 32];
+            } else {
+                header.payload_hash = [0; 32];
+            }
+            let mut one_wrong = Vec::new();
+            header.write(&mut one_wrong).unwrap();
+            one_wrong.extend_from_slice(&archive[one_wrong.len()..]);
+
+            assert_eq!(decompress_with(&one_wrong, &config.clone().with_verify(passes)).unwrap(), data);
+            assert!(decompress_with(&one_wrong, &config.clone().with_verify(fails)).is_err());
+            assert!(decompress_to_writer_with(&one_wrong[..], std::io::sink(), &config.clone().with_verify(fails)).is_err());
+            assert!(decompress_with(&one_wrong, &config.clone().with_verify(VerifyPolicy::Full)).is_err());
+        }
+        assert_eq!("none".parse::<VerifyPolicy>().unwrap(), VerifyPolicy::Trusted);
+
+        // The header checksum is still checked
+        let mut corrupt = tampered.clone();
+        corrupt[20] ^= 0xff;
+        assert!(decompress_with(&corrupt, &config).is_err());
+    }
+
+    #[test]
+    fn test_decompress_into() {
+        let data: Vec<u8> = (0..5 * compression::MIN_CHUNK_SIZE / 2).map(|i| (i % 251) as u8).collect();
+        let mut out = vec![0xaa; data.len() + 100];
+
+        for use_lz4 in [true, false] {
+            let config = CompressionConfig::builder()
+                .use_lz4_decompression(use_lz4)
+                .chunk_size(compression::MIN_CHUNK_SIZE)
+                .build()
+                .unwrap();
+            let archive = compress(&data, &config).unwrap();
+
+            for threads in [1, 4] {
+                out.fill(0xaa);
+                assert_eq!(decompress_into(&archive, &mut out, threads).unwrap(), data.len());
+                assert_eq!(&out[..data.len()], &data[..]);
+                assert!(out[data.len()..].iter().all(|&b| b == 0xaa));
+            }
+
+            let too_small = &mut out[..data.len() - 1];
+            assert!(matches!(decompress_into(&archive, too_small, 1), Err(GlifError::InvalidInput(_))));
+        }
+
+        // The same buffer is reused for a smaller archive
+        let small = b"reused buffer".repeat(10);
+        let archive = compress(&small, &CompressionConfig::default()).unwrap();
+        assert_eq!(decompress_into(&archive, &mut out, 0).unwrap(), small.len());
+        assert_eq!(&out[..small.len()], &small[..]);
+
+        // A wrong payload hash is caught unless the policy skips it
+        let mut header = GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+        header.payload_hash = [0; 32];
+        let mut tampered = Vec::new();
+        header.write(&mut tampered).unwrap();
+        tampered.extend_from_slice(&archive[tampered.len()..]);
+        assert!(decompress_into(&tampered, &mut out, 1).is_err());
+        let trusted = DecompressionConfig::new().with_verify(VerifyPolicy::Archive);
+        assert_eq!(decompress_into_with(&tampered, &mut out, &trusted).unwrap(), small.len());
+    }
+
+    #[test]
+    fn test_decompress_auto() {
+        let data = b"Auto-detected decompression threads".repeat(50);
+        let compressed = compress(&data, &CompressionConfig::default()).unwrap();
+
+        assert_eq!(decompress_auto(&compressed).unwrap(), data);
+        assert_eq!(decompress(&compressed, 0).unwrap(), data);
+    }
+
     #[test]
     fn test_verify_archive() {
         let data = b"Test data for archive verification";
@@ -286,6 +967,111 @@ mod tests {
         assert_eq!(sidecar.payload.size, data.len() as u64);
     }
 
+    #[test]
+    fn test_verify_levels() {
+        let data = b"Each verify level checks more ".repeat(100);
+        let archive = compress(&data, &CompressionConfig::default()).unwrap();
+        for level in VerifyLevel::ALL {
+            assert_eq!(level.name().parse::<VerifyLevel>().unwrap(), level);
+            assert_eq!(verify_archive_with_level(&archive, level).unwrap().payload.size, data.len() as u64);
+        }
+        assert_eq!(verify_payload_hash(&archive).unwrap().payload.size, data.len() as u64);
+
+        // A header whose payload hash is wrong still passes the archive hash check
+        let mut cursor = std::io::Cursor::new(&archive[..]);
+        let mut header = GlifHeader::read(&mut cursor).unwrap();
+        header.payload_hash[0] ^= 0xFF;
+        let mut rehashed = Vec::new();
+        header.write(&mut rehashed).unwrap();
+        rehashed.extend_from_slice(&archive[cursor.position() as usize..]);
+        assert!(verify_archive_hash(&rehashed).is_ok());
+        assert!(matches!(verify_payload_hash(&rehashed), Err(GlifError::HashMismatch { .. })));
+        assert!(verify_archive_with_level(&rehashed, VerifyLevel::Full).is_err());
+
+        // Damaged data is structurally fine, but truncated data is not
+        let mut damaged = archive.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 0xFF;
+        assert!(verify_structure(&damaged).is_ok());
+        assert!(verify_archive_hash(&damaged).is_err());
+        assert!(matches!(verify_structure(&archive[..archive.len() - 1]), Err(GlifError::Truncated(_))));
+    }
+
+    #[test]
+    fn test_compress_file_matches_compress() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("input");
+        let data = b"compress_file under a budget ".repeat(4000);
+        std::fs::write(&input, &data).unwrap();
+
+        let config = CompressionConfig::default();
+        let bounded = CompressionConfig::builder()
+            .max_memory(compression::memory::chunk_compression_memory(config.level, config.chunk_size))
+            .build()
+            .unwrap();
+        compress_file(&input, dir.path().join("whole.glif"), &config).unwrap();
+        compress_file(&input, dir.path().join("bounded.glif"), &bounded).unwrap();
+
+        let archive = compress(&data, &config).unwrap();
+        assert_eq!(std::fs::read(dir.path().join("whole.glif")).unwrap(), archive);
+        assert_eq!(std::fs::read(dir.path().join("bounded.glif")).unwrap(), archive);
+
+        // Spill files are gone, and a failed run leaves no output behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+        assert!(compress_file(dir.path().join("missing"), dir.path().join("missing.glif"), &config).is_err());
+        assert!(compress_file(&input, dir.path().join("bad.glif"), &CompressionConfig::new(99, 1)).is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compress_file_from_pipe() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let fifo = dir.path().join("fifo");
+        let made = std::process::Command::new("mkfifo").arg(&fifo).status();
+        if !made.is_ok_and(|status| status.success()) {
+            return;
+        }
+
+        // A pipe can't be sought or sized, so this only works if the input is streamed
+        let data: Vec<u8> = (0..5 * compression::MIN_CHUNK_SIZE / 2).map(|i| (i % 249) as u8).collect();
+        let writer = std::thread::spawn({
+            let (fifo, data) = (fifo.clone(), data.clone());
+            move || std::fs::write(fifo, data)
+        });
+        let config = CompressionConfig::builder().chunk_size(compression::MIN_CHUNK_SIZE).build().unwrap();
+        let output = dir.path().join("piped.glif");
+        compress_file(&fifo, &output, &config).unwrap();
+        writer.join().unwrap().unwrap();
+
+        assert_eq!(std::fs::read(&output).unwrap(), compress(&data, &config).unwrap());
+    }
+
+    #[test]
+    fn test_decompress_to_writer() {
+        let data: Vec<u8> = (0..7 * compression::MIN_CHUNK_SIZE / 2).map(|i| (i % 251) as u8).collect();
+
+        for use_lz4_decompression in [true, false] {
+            let config = CompressionConfig::builder()
+                .chunk_size(compression::MIN_CHUNK_SIZE)
+                .use_lz4_decompression(use_lz4_decompression)
+                .build()
+                .unwrap();
+            let archive = compress(&data, &config).unwrap();
+
+            for threads in [1, 3] {
+                let mut output = Vec::new();
+                assert_eq!(decompress_to_writer(&archive[..], &mut output, threads).unwrap(), data.len() as u64);
+                assert_eq!(output, data);
+            }
+
+            let mut damaged = archive.clone();
+            let last = damaged.len() - 1;
+            damaged[last] ^= 0xFF;
+            assert!(decompress_to_writer(&damaged[..], std::io::sink(), 3).is_err());
+        }
+    }
+
     #[test]
     fn test_large_data_compression() {
         // Test with 10 MB of data
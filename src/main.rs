@@ -1,10 +1,21 @@
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use glifzip::progress::color::{column_width, pad};
+use glifzip::progress::{paint, Style};
 
 #[derive(Parser)]
 #[command(name = "glifzip")]
 #[command(about = "High-performance compression engine for GlyphOS", long_about = None)]
 struct Cli {
+    /// Plain sequential text for screen readers: no progress bars, spinners or colors
+    #[arg(long, global = true)]
+    plain: bool,
+
+    /// Color results: auto (when stdout is a terminal and NO_COLOR is unset), always or never
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto")]
+    color: glifzip::progress::ColorChoice,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -20,14 +31,22 @@ enum Commands {
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Compression level (1-22, default: 8)
-        #[arg(short, long, default_value = "8")]
-        level: i32,
+        /// Compression level (1-22 or max, or negative for fast mode, default: 8 or the preset's)
+        #[arg(short, long, allow_negative_numbers = true)]
+        level: Option<glifzip::CompressionLevel>,
 
-        /// Number of threads (default: auto-detect)
+        /// Number of threads (0 or omitted: auto-detect, or the preset's)
         #[arg(short, long)]
         threads: Option<usize>,
 
+        /// Start from named settings: fast, balanced, high, or tuned (saved by `glifzip tune`); --level and --threads override them
+        #[arg(long, conflicts_with = "profile")]
+        preset: Option<glifzip::tune::Preset>,
+
+        /// Start from a profile under `profiles` in the user config file (~/.config/glifzip/config.json); other options override or add to it
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
         /// Compress directory recursively
         #[arg(short, long)]
         recursive: bool,
@@ -40,9 +59,81 @@ enum Commands {
         #[arg(short = 'x', long = "exclude")]
         exclude: Vec<String>,
 
+        /// Skip cache directories (CACHEDIR.TAG, .cache, target, node_modules, Library/Caches)
+        #[arg(long)]
+        exclude_caches: bool,
+
+        /// Skip items excluded from Time Machine backups (macOS)
+        #[arg(long)]
+        respect_tm_excludes: bool,
+
         /// Disable progress bar
         #[arg(long)]
         no_progress: bool,
+
+        /// Show a desktop notification when finished
+        #[arg(long)]
+        notify: bool,
+
+        /// Write the archive as ASCII-armored text for email or chat (archives up to 1 MiB)
+        #[arg(long)]
+        armor: bool,
+
+        /// Write a standard .zst file with no GLIF header, for tools that only understand zstd (files only)
+        #[arg(long, conflicts_with = "armor")]
+        raw_zst: bool,
+
+        /// Mark the archive as holding sensitive data
+        #[arg(long)]
+        sensitive: bool,
+
+        /// Embed a preview image (PNG, JPEG or ICNS, up to 32 KiB) in the archive; on macOS it also becomes the file's Finder icon
+        #[arg(long, value_name = "IMAGE")]
+        icon: Option<PathBuf>,
+
+        /// Mark the archive as sensitive and protect the output file (owner-only; on macOS also excluded from Time Machine and Spotlight)
+        #[arg(long)]
+        set_protected: bool,
+
+        /// Archive what can be read from a failing disk: zero-fill unreadable ranges and skip unreadable paths, recording both in the manifest (directories only)
+        #[arg(long)]
+        salvage: bool,
+
+        /// Skip per-file hashes for speed; add them later with `hash-backfill` (directories only)
+        #[arg(long)]
+        no_hash: bool,
+
+        /// Cap compression memory (e.g. 512M, 2G) by streaming the input and limiting chunks in flight
+        #[arg(long, value_name = "SIZE", value_parser = glifzip::testing::data::parse_size)]
+        max_memory: Option<u64>,
+
+        /// Hold at most this many chunk buffers at once (default: two per thread)
+        #[arg(long, value_name = "CHUNKS")]
+        max_in_flight: Option<usize>,
+
+        /// Reuse compressed chunks of unchanged files from earlier runs, cached in DIR (default: the `chunks` directory of the cache root) (directories only)
+        #[arg(long, value_name = "DIR", num_args = 0..=1)]
+        chunk_cache: Option<Option<PathBuf>>,
+
+        /// Write a journal of the archived files (path, size, mtime, hash) to PATH for a later --since (directories only)
+        #[arg(long, value_name = "PATH", conflicts_with = "armor")]
+        journal: Option<PathBuf>,
+
+        /// Copy files unchanged since JOURNAL was written from its archive instead of reading and hashing them; a missing JOURNAL means a full run (directories only)
+        #[arg(long, value_name = "JOURNAL")]
+        since: Option<PathBuf>,
+
+        /// What to do with timestamps before 1970 or in the future: keep, clamp (to 1970 or the time of archiving) or error (directories only)
+        #[arg(long, value_name = "POLICY")]
+        timestamps: Option<glifzip::TimestampPolicy>,
+
+        /// Record timestamps in whole seconds (directories only)
+        #[arg(long)]
+        whole_seconds: bool,
+
+        /// Manifest encoding: json, or cbor for a manifest about half the size that needs glifzip 1.2.0 or later to read (directories only)
+        #[arg(long, value_name = "FORMAT")]
+        manifest_format: Option<glifzip::ManifestFormat>,
     },
 
     /// Extract a GLIF archive
@@ -51,10 +142,14 @@ enum Commands {
         input: PathBuf,
 
         /// Output file or directory path
-        #[arg(short, long)]
-        output: PathBuf,
+        #[arg(short, long, required_unless_present = "to_null", conflicts_with = "to_null")]
+        output: Option<PathBuf>,
+
+        /// Run the whole extraction (decompression, hash and metadata checks) without writing anything
+        #[arg(long)]
+        to_null: bool,
 
-        /// Number of threads (default: auto-detect)
+        /// Number of threads (0 or omitted: auto-detect)
         #[arg(short, long)]
         threads: Option<usize>,
 
@@ -65,12 +160,132 @@ enum Commands {
         /// Disable progress bar
         #[arg(long)]
         no_progress: bool,
+
+        /// Write a JSON report of every entry's outcome to this path
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+
+        /// Skip SHA256 checks for trusted local archives (the header and sidecar are still validated); same as --verify none
+        #[arg(long, conflicts_with = "verify")]
+        no_verify: bool,
+
+        /// Which SHA256 hashes to check: full, archive (compressed data only), payload (decompressed data and files only) or none
+        #[arg(long, value_name = "POLICY", default_value = "full")]
+        verify: glifzip::VerifyPolicy,
+
+        /// Hold at most this many chunk buffers at once (default: two per thread)
+        #[arg(long, value_name = "CHUNKS")]
+        max_in_flight: Option<usize>,
+
+        /// Decompress on a single thread when more threads would need over SIZE (e.g. 512M, 2G)
+        #[arg(long, value_name = "SIZE", value_parser = glifzip::testing::data::parse_size)]
+        max_memory: Option<u64>,
+
+        /// Base archive holding files the archive references; repeat for every archive in the chain (directory archives only)
+        #[arg(long, value_name = "ARCHIVE")]
+        base: Vec<PathBuf>,
+
+        /// Only extract files of this MIME type, e.g. image/png or image/*; repeat to allow several (directory archives only)
+        #[arg(long, value_name = "TYPE")]
+        include_type: Vec<String>,
+
+        /// On targets without symlinks or permissions (FAT, exFAT, SMB): fail the symlinks, write them as text files holding their target, or skip them; text and skip leave permissions unset quietly (directory archives only)
+        #[arg(long, value_name = "POLICY", default_value = "fail")]
+        unsupported: glifzip::UnsupportedPolicy,
+
+        /// Entry names Windows can't store (containing : * ? and the like, ending in a dot or space, or reserved like CON): keep them, replace them with safe names, or fail them (directory archives only)
+        #[arg(long, value_name = "POLICY", default_value = "keep")]
+        names: glifzip::NamePolicy,
+
+        /// Extract this member of a file holding several archives: its name, or #N by position (see `glifzip members`)
+        #[arg(long, value_name = "MEMBER")]
+        member: Option<String>,
+
+        /// Show a desktop notification when finished
+        #[arg(long)]
+        notify: bool,
     },
 
-    /// Verify a GLIF archive
+    /// Verify a GLIF archive, or every archive under a directory
     Verify {
-        /// GLIF archive to verify
+        /// GLIF archive to verify, or a directory with --recursive
         input: PathBuf,
+
+        /// Also decompress the archive and check the payload hash (same as --level full)
+        #[arg(long, alias = "deep", conflicts_with = "level")]
+        full: bool,
+
+        /// How thoroughly to check: structural (header and sidecar only), archive (archive hash) or full (payload hash too); --recursive sweeps check at least the archive hash
+        #[arg(long, value_name = "LEVEL", default_value = "archive")]
+        level: glifzip::VerifyLevel,
+
+        /// Verify every .glif archive under the input directory
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Write a JSON report of a --recursive sweep to this path
+        #[arg(long, requires = "recursive")]
+        report: Option<PathBuf>,
+
+        /// Number of threads for --full (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Show archive header, format and version information
+    Info {
+        /// GLIF archive to inspect
+        input: PathBuf,
+
+        /// Write the archive's embedded icon to the --output path instead of showing the info
+        #[arg(long, requires = "output")]
+        icon: bool,
+
+        /// Where to write the icon
+        #[arg(short, long, requires = "icon")]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the content address of a directory or GLIF archive
+    Hash {
+        /// Directory or GLIF archive to hash
+        input: PathBuf,
+
+        /// Exclude patterns when hashing a directory (can be specified multiple times)
+        #[arg(short, long)]
+        exclude: Vec<String>,
+    },
+
+    /// Add per-file hashes to a directory archive created with --no-hash
+    HashBackfill {
+        /// Directory archive to update in place
+        archive: PathBuf,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Summarize verification status of the archives in a directory
+    Status {
+        /// Directory containing GLIF archives
+        directory: PathBuf,
+
+        /// Report archives not verified within this many days
+        #[arg(long, default_value = "30")]
+        max_age_days: i64,
+    },
+
+    /// Write the golden corpus for this version (development use)
+    #[command(hide = true)]
+    MakeCorpus {
+        /// Corpus root; archives go in a `v<version>` subdirectory
+        #[arg(long, default_value = glifzip::testing::corpus::CORPUS_DIR)]
+        dir: PathBuf,
+
+        /// Replace an existing corpus for this version
+        #[arg(long)]
+        force: bool,
     },
 
     /// List contents of a GLIF archive
@@ -81,129 +296,1348 @@ enum Commands {
         /// Show detailed information
         #[arg(short, long)]
         verbose: bool,
+
+        /// Export every entry's metadata as a table (csv or parquet) instead of listing
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<glifzip::convert::TableFormat>,
+
+        /// Write the exported table to this path instead of stdout
+        #[arg(short, long, requires = "format")]
+        output: Option<PathBuf>,
+
+        /// Show the first line of each small text file under its entry
+        #[arg(long, conflicts_with = "format")]
+        preview: bool,
+    },
+
+    /// List the archives in a file holding several, one after another
+    Members {
+        /// File to list
+        input: PathBuf,
+    },
+
+    /// Append an archive to a file as a new member, leaving the archives already there untouched
+    Append {
+        /// File to append to; it must already hold one or more archives
+        container: PathBuf,
+
+        /// Archive to append
+        archive: PathBuf,
+
+        /// Name of the new member (default: the archive's file name without its extension)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Compare the entries of two directory archives
+    Compare {
+        /// Older archive
+        old: PathBuf,
+
+        /// Newer archive
+        new: PathBuf,
+
+        /// Output format: text, or html for a standalone report
+        #[arg(long, default_value = "text")]
+        format: glifzip::archive::diff::DiffFormat,
+
+        /// Write the report to this path instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check or flatten a chain of incremental archives
+    Chain {
+        #[command(subcommand)]
+        action: ChainCommands,
+    },
+
+    /// Install Finder Quick Actions and register the .glif file type (macOS)
+    InstallIntegration,
+
+    /// Manage scheduled backups (launchd on macOS, systemd on Linux)
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleCommands,
+    },
+
+    /// Convert a directory archive into an OCI image layer (tar+gzip)
+    ToOciLayer {
+        /// GLIF directory archive
+        input: PathBuf,
+
+        /// Output layer path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Convert an OCI image layer (tar or tar+gzip) into a directory archive
+    FromOciLayer {
+        /// OCI layer tarball
+        input: PathBuf,
+
+        /// Output GLIF archive path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compression level
+        #[arg(short, long, default_value = "8", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Drop whiteout entries instead of refusing layers that contain them
+        #[arg(long)]
+        drop_whiteouts: bool,
+    },
+
+    /// Convert a directory archive into a newc cpio stream (e.g. for initramfs)
+    ToCpio {
+        /// GLIF directory archive
+        input: PathBuf,
+
+        /// Output cpio path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compress the stream with Zstd (optionally at the given level)
+        #[arg(long, value_name = "LEVEL", num_args = 0..=1, default_missing_value = "19", allow_negative_numbers = true)]
+        zstd: Option<i32>,
+
+        /// Record all entries as owned by root
+        #[arg(long)]
+        root_owner: bool,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Convert a directory archive into a read-only image with random access
+    ToImage {
+        /// GLIF directory archive
+        input: PathBuf,
+
+        /// Output image path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Uncompressed block size in KB (power of two, 4 to 16384)
+        #[arg(long, default_value = "128")]
+        block_size_kb: u32,
+
+        /// Compression level for data blocks
+        #[arg(short, long, default_value = "8", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Compress a small file onto the clipboard as base64 text
+    Clip {
+        /// File to copy
+        input: PathBuf,
+
+        /// Compression level
+        #[arg(short, long, default_value = "19", allow_negative_numbers = true)]
+        level: i32,
+    },
+
+    /// Restore a file from base64 text on the clipboard
+    Unclip {
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+
+    /// Store and restore directories in a local build cache
+    Cache {
+        /// Cache root (default: $GLIFZIP_CACHE_DIR, $XDG_CACHE_HOME/glifzip or ~/.cache/glifzip)
+        #[arg(long, global = true)]
+        root: Option<PathBuf>,
+
+        #[command(subcommand)]
+        action: CacheCommands,
+    },
+
+    /// Check this installation: codec round trips, hash verification and filesystem metadata
+    Selftest,
+
+    /// Benchmark compression on this machine, optionally against gzip, zstd and zip
+    Bench {
+        /// File to benchmark on (default: generated data, see --type and --size)
+        input: Option<PathBuf>,
+
+        /// Kind of generated data: text, random, zeros or code
+        #[arg(long = "type", default_value = "text")]
+        kind: glifzip::testing::data::DataKind,
+
+        /// Size of generated data, with an optional K, M, G or T suffix
+        #[arg(long, default_value = "256M", value_parser = glifzip::testing::data::parse_size)]
+        size: u64,
+
+        /// Compression level
+        #[arg(short, long, default_value = "8", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Also run the system's gzip, zstd and zip on the same data
+        #[arg(long)]
+        compare: bool,
+    },
+
+    /// Calibrate chunk size, threads and the LZ4 layer on this machine and save the fastest settings as the `tuned` preset
+    Tune {
+        /// File to calibrate on (default: generated data, see --type and --size)
+        input: Option<PathBuf>,
+
+        /// Kind of generated data: text, random, zeros or code
+        #[arg(long = "type", default_value = "text")]
+        kind: glifzip::testing::data::DataKind,
+
+        /// Sample size (generated, or read from the start of the file), with an optional K, M, G or T suffix
+        #[arg(long, default_value = "64M", value_parser = glifzip::testing::data::parse_size)]
+        size: u64,
+
+        /// Compression level to tune for
+        #[arg(short, long, default_value = "8", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Most threads to try (0 or omitted: all cores)
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Print the fastest settings without saving them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Write seeded benchmark input data, identical to the benchmark suite's
+    GenTestdata {
+        /// Kind of data: text, random, zeros or code
+        #[arg(long = "type")]
+        kind: glifzip::testing::data::DataKind,
+
+        /// Size in bytes, with an optional K, M, G or T suffix (binary units)
+        #[arg(long, value_parser = glifzip::testing::data::parse_size)]
+        size: u64,
+
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Seed for random data (default: the benchmark suite's seed)
+        #[arg(long, default_value_t = glifzip::testing::data::DEFAULT_SEED)]
+        seed: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Archive a directory into the cache
+    Put {
+        /// Directory to cache
+        input: PathBuf,
+
+        /// Cache key (default: the directory's content address)
+        #[arg(long)]
+        key: Option<String>,
+
+        /// Compression level
+        #[arg(short, long, default_value = "3", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Exclude patterns (glob style, can be used multiple times)
+        #[arg(short = 'x', long = "exclude")]
+        exclude: Vec<String>,
+
+        /// Evict least recently used entries beyond this total size
+        #[arg(long, value_name = "MB", default_value_t = glifzip::cache::DEFAULT_MAX_CACHE_SIZE / (1024 * 1024))]
+        max_size_mb: u64,
+    },
+
+    /// Extract a cached directory; exits with an error on a cache miss
+    Get {
+        /// Cache key
+        key: String,
+
+        /// Output directory
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
     },
 }
 
+#[derive(Subcommand)]
+enum ChainCommands {
+    /// Check that every reference in the chain resolves to an earlier archive in it
+    Verify {
+        /// Directory archives, the full one first and each incremental after those it references
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+    },
+
+    /// Write the last archive of the chain as a standalone archive, with referenced contents copied in
+    Flatten {
+        /// Directory archives, the full one first and each incremental after those it references
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+
+        /// Output GLIF archive path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compression level
+        #[arg(short, long, default_value = "8", allow_negative_numbers = true)]
+        level: i32,
+
+        /// Number of threads (0 or omitted: auto-detect)
+        #[arg(short, long)]
+        threads: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// Install a daily job running glifzip with the given arguments
+    Install {
+        /// Backup profile name
+        profile: String,
+
+        /// Time of day to run (HH:MM, 24-hour)
+        #[arg(long, value_name = "HH:MM")]
+        daily: String,
+
+        /// glifzip arguments to run, e.g. `-- create ~/Documents -o /backups/docs.glif`
+        #[arg(last = true, required = true)]
+        args: Vec<String>,
+    },
+
+    /// List scheduled backup profiles
+    List,
+
+    /// Remove the scheduled job for a profile
+    Remove {
+        /// Backup profile name
+        profile: String,
+    },
+}
+
+/// Total size of a file, or of all regular files under a directory
+fn input_size(path: &std::path::Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Report for a single-file archive, which has exactly one entry
+fn single_file_report(input: &Path, output: &Path, result: glifzip::error::Result<()>) -> glifzip::ExtractionReport {
+    use glifzip::archive::{EntryOutcome, EntryStatus};
+
+    let mut report = glifzip::ExtractionReport::new(input, output);
+    let path = output.file_name().map(PathBuf::from).unwrap_or_else(|| output.to_path_buf());
+    report.record(match result {
+        Ok(()) => EntryOutcome { path, status: EntryStatus::Extracted, renamed_to: None, reason: None, warnings: Vec::new() },
+        Err(e) => EntryOutcome { path, status: EntryStatus::Failed, renamed_to: None, reason: Some(e.to_string()), warnings: Vec::new() },
+    });
+    report
+}
+
+/// Peek at an archive, reading only as much of the file as the header, sidecar
+/// and any manifest need; works on partially downloaded files
+fn peek_file(path: &Path) -> std::io::Result<(glifzip::PeekInfo, u64)> {
+    let file = std::fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+    Ok((glifzip::format::peek_reader(std::io::BufReader::new(file))?, file_size))
+}
+
+/// Content address of a directory, or the one a GLIF archive was created from
+///
+/// Directory archives hash their manifest, so they match the directory they
+/// were made from; single-file archives report their payload hash.
+fn content_address(input: &Path, exclude: Vec<String>) -> std::io::Result<String> {
+    if input.is_dir() {
+        let config = glifzip::DirectoryCompressionConfig::default()
+            .with_exclude_patterns(exclude)
+            .with_progress(false);
+        let address = glifzip::DirectoryCompressor::new(config)?.content_address(input)?;
+        return Ok(glifzip::archive::format_content_address(&address));
+    }
+
+    let archive = std::fs::read(input)?;
+    let mut cursor = std::io::Cursor::new(&archive);
+    if glifzip::ArchiveManifest::read(&mut cursor).is_ok() {
+        cursor.set_position(0);
+        let manifest = glifzip::ArchiveManifest::read_checked(&mut cursor)?;
+        return Ok(glifzip::archive::format_content_address(&manifest.content_address()?));
+    }
+
+    cursor.set_position(0);
+    let header = glifzip::GlifHeader::read(&mut cursor).map_err(|_| std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{} is not a directory or GLIF archive", input.display())
+    ))?;
+    Ok(glifzip::GlifSidecar::read(&mut cursor, header.sidecar_size)?.payload.hash)
+}
+
+/// Print what a salvage-mode archive is missing, read back from its manifest
+/// Print `label: value` lines with the values aligned and the labels dimmed
+fn print_fields(fields: &[(&str, String)]) {
+    let width = column_width(fields.iter().map(|(label, _)| label)) + 1;
+    for (label, value) in fields {
+        println!("  {} {}", paint(pad(format!("{}:", label), width, false), Style::Dim), value);
+    }
+}
+
+/// Print a line per manifest entry in aligned columns: type, size and path,
+/// with mode, owner, modification time, MIME type and compression ratio too
+/// if `verbose`
+/// With `archive`, the first line of each small text file is shown under its entry
+fn print_entries(manifest: &glifzip::ArchiveManifest, verbose: bool, mut archive: Option<&mut glifzip::GlifArchive<std::fs::File>>) {
+    use glifzip::archive::file_entry::FileType;
+
+    let size_width = column_width(manifest.entries.iter().map(|entry| entry.size));
+    let owner_width = column_width(manifest.entries.iter().map(|entry| format!("{}:{}", entry.uid, entry.gid)));
+    let mime_width = column_width(manifest.entries.iter().map(|entry| entry.mime_type.as_deref().unwrap_or("-")));
+    let ratio = |entry: &glifzip::FileEntry| entry.compression_ratio().map_or("-".to_string(), |ratio| format!("{:.1}%", ratio));
+    let ratio_width = column_width(manifest.entries.iter().map(ratio));
+    for entry in &manifest.entries {
+        let kind = match entry.file_type {
+            FileType::Regular => "f",
+            FileType::Directory => "d",
+            FileType::Symlink => "l",
+        };
+        let mut line = format!("  {} {}", paint(kind, Style::Dim), paint(pad(entry.size, size_width, true), Style::Dim));
+        if verbose {
+            line += &format!(
+                " {}",
+                paint(format!(
+                    "{:04o} {} {} {} {}",
+                    entry.mode & 0o7777,
+                    pad(format!("{}:{}", entry.uid, entry.gid), owner_width, false),
+                    entry.mtime.format("%Y-%m-%d %H:%M"),
+                    pad(entry.mime_type.as_deref().unwrap_or("-"), mime_width, false),
+                    pad(ratio(entry), ratio_width, true)
+                ), Style::Dim)
+            );
+        }
+        line += &format!(" {}", entry.path.display());
+        if let Some(target) = &entry.symlink_target {
+            line += &format!(" {}", paint(format!("-> {}", target.display()), Style::Dim));
+        }
+        println!("{}", line);
+
+        if let Some(archive) = archive.as_deref_mut().filter(|_| entry.file_type == FileType::Regular) {
+            let preview = match archive.preview_entry(&entry.path) {
+                Ok(Some(text)) => text,
+                Ok(None) => continue,
+                Err(e) => format!("(preview failed: {})", e),
+            };
+            println!("      {}", paint(preview, Style::Dim));
+        }
+    }
+}
+
+fn print_salvage_summary(archive: &Path) -> std::io::Result<()> {
+    let manifest = glifzip::ArchiveManifest::read_checked(&mut std::io::BufReader::new(std::fs::File::open(archive)?))?;
+
+    let damaged: Vec<_> = manifest.damaged_entries().collect();
+    for entry in &damaged {
+        eprintln!(
+            "Warning: {}: {} bytes unreadable, stored as zeros",
+            entry.path.display(),
+            glifzip::archive::salvage::gap_bytes(&entry.gaps)
+        );
+    }
+    for path in &manifest.skipped {
+        eprintln!("Warning: {}: unreadable, skipped", path.display());
+    }
+
+    if damaged.is_empty() && manifest.skipped.is_empty() {
+        println!("Salvage: every file was read in full");
+    } else {
+        println!(
+            "Salvage: {} damaged file(s), {} skipped path(s); details are recorded in the archive manifest",
+            damaged.len(),
+            manifest.skipped.len()
+        );
+    }
+    Ok(())
+}
+
+fn mib(bytes: u64) -> u64 {
+    bytes.div_ceil(1024 * 1024)
+}
+
 fn main() -> std::io::Result<()> {
     let cli = Cli::parse();
+    glifzip::progress::set_plain(cli.plain);
+    glifzip::progress::set_color(cli.color);
+    glifzip::compression::fallback::set_downgrade_hook(Some(|operation, reason| {
+        eprintln!("glifzip: warning: {} falling back to a single thread: {}", operation, reason);
+    }));
+
+    // Description of the operation for the completion notification, if requested
+    let notification = match &cli.command {
+        Commands::Create { output, notify: true, .. } => Some(format!("Compression of {}", output.display())),
+        Commands::Extract { input, notify: true, .. } => Some(format!("Extraction of {}", input.display())),
+        _ => None,
+    };
 
     let result = match cli.command {
-        Commands::Create { input, output, level, threads, recursive, verbose, exclude, no_progress } => {
-            let threads = threads.unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(8));
-
-            if recursive || input.is_dir() {
-                // Directory compression mode
-                let compression_config = glifzip::CompressionConfig::new(level, threads);
-                let dir_config = glifzip::DirectoryCompressionConfig::new(compression_config)
-                    .with_exclude_patterns(exclude)
-                    .with_verbose(verbose)
-                    .with_progress(!no_progress);
+        Commands::Create { input, output, level, threads, preset, profile, recursive, verbose, exclude, exclude_caches, respect_tm_excludes, no_progress, armor, sensitive, icon, set_protected, salvage, no_hash, max_memory, max_in_flight, chunk_cache, journal, since, timestamps, whole_seconds, manifest_format, raw_zst, .. } => {
+            profile.as_deref().map(glifzip::profile::load_user_profile).transpose()
+                .and_then(|profile| {
+                    let base = match (&profile, preset) {
+                        (Some(profile), _) => profile.compression.clone(),
+                        (None, Some(preset)) => preset.config()?,
+                        (None, None) => glifzip::CompressionConfig::default(),
+                    };
+                    let mut builder = glifzip::CompressionConfig::builder()
+                        .level(level.map(i32::from).unwrap_or(base.level))
+                        .threads(threads.unwrap_or(base.threads))
+                        .chunk_size(base.chunk_size)
+                        .use_lz4_decompression(base.use_lz4_decompression)
+                        .deterministic(base.deterministic)
+                        .sensitive(sensitive || set_protected || base.sensitive);
+                    if let Some(bytes) = max_memory.or(base.max_memory) {
+                        builder = builder.max_memory(bytes);
+                    }
+                    if let Some(budget) = max_in_flight.or(base.max_in_flight) {
+                        builder = builder.max_in_flight(budget);
+                    }
+                    if let Some(path) = &icon {
+                        builder = builder.icon(glifzip::format::IconInfo::from_file(path)?);
+                    }
+                    Ok((builder.build()?, profile.unwrap_or_default()))
+                })
+                .and_then(|(config, profile)| {
+                    let (level, threads) = (config.level, config.threads);
 
-                if verbose {
-                    println!("Compressing directory {} to {} (level={}, threads={})",
-                             input.display(), output.display(), level, threads);
-                }
+                    if glifzip::compression::is_ultra_level(config.level) {
+                        let estimate = config.memory_estimate(input_size(&input) as usize);
+                        eprintln!(
+                            "Warning: level {} is an ultra level and needs ~{} MiB to compress and ~{} MiB to decompress ({} MiB window)",
+                            config.level,
+                            mib(estimate.compression),
+                            mib(estimate.decompression),
+                            mib(estimate.window_size)
+                        );
+                    }
 
-                let compressor = glifzip::DirectoryCompressor::new(dir_config)?;
-                compressor.compress_directory(&input, &output)
-            } else {
-                // Single file compression mode
-                let config = glifzip::CompressionConfig::new(level, threads);
+                    if raw_zst && (recursive || input.is_dir()) {
+                        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "--raw-zst is only supported for files"))
+                    } else if recursive || input.is_dir() {
+                        // Directory compression mode
+                        let mut dir_config = glifzip::DirectoryCompressionConfig {
+                            compression: config,
+                            exclude_patterns: [profile.exclude_patterns, exclude].concat(),
+                            exclude_caches: exclude_caches || profile.exclude_caches,
+                            respect_tm_excludes: respect_tm_excludes || profile.respect_tm_excludes,
+                            verbose: verbose || profile.verbose,
+                            show_progress: !no_progress && profile.show_progress,
+                            salvage: salvage || profile.salvage,
+                            hash_files: !no_hash && profile.hash_files,
+                            timestamp_policy: timestamps.unwrap_or(profile.timestamp_policy),
+                            whole_second_timestamps: whole_seconds || profile.whole_second_timestamps,
+                            manifest_format: manifest_format.unwrap_or(profile.manifest_format),
+                            ..profile
+                        };
+                        if let Some(root) = chunk_cache {
+                            let root = match root {
+                                Some(root) => root,
+                                None => glifzip::cache::chunks::ChunkCache::default_root()?,
+                            };
+                            dir_config = dir_config.with_chunk_cache(glifzip::cache::chunks::ChunkCache::new(root));
+                        }
+                        if let Some(path) = since {
+                            dir_config = dir_config.with_since(path);
+                        }
+                        if let Some(path) = journal {
+                            dir_config = dir_config.with_journal(path);
+                        }
+                        let salvage = dir_config.salvage;
 
-                if verbose {
-                    println!("Compressing {} to {} (level={}, threads={})",
-                             input.display(), output.display(), level, threads);
-                }
+                        if verbose {
+                            println!("Compressing directory {} to {} (level={}, threads={})",
+                                     input.display(), output.display(), level, threads);
+                        }
 
-                glifzip::compress_file(&input, &output, &config)
-            }
+                        let compressor = glifzip::DirectoryCompressor::new(dir_config)?;
+                        compressor.compress_directory(&input, &output)?;
+                        if salvage {
+                            print_salvage_summary(&output)?;
+                        }
+                        Ok(())
+                    } else if salvage || no_hash || chunk_cache.is_some() || journal.is_some() || since.is_some() || timestamps.is_some() || whole_seconds {
+                        let flag = if salvage {
+                            "--salvage"
+                        } else if no_hash {
+                            "--no-hash"
+                        } else if chunk_cache.is_some() {
+                            "--chunk-cache"
+                        } else if journal.is_some() {
+                            "--journal"
+                        } else if since.is_some() {
+                            "--since"
+                        } else if timestamps.is_some() {
+                            "--timestamps"
+                        } else {
+                            "--whole-seconds"
+                        };
+                        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} is only supported for directories", flag)))
+                    } else {
+                        // Single file compression mode
+                        if verbose {
+                            println!("Compressing {} to {} (level={}, threads={})",
+                                     input.display(), output.display(), level, threads);
+                        }
+
+                        if raw_zst {
+                            glifzip::compress_file_to_zst(&input, &output, &config).map_err(std::io::Error::from)
+                        } else {
+                            glifzip::compress_file(&input, &output, &config).map_err(std::io::Error::from)
+                        }
+                    }
+                })
+                .and_then(|()| {
+                    if !armor {
+                        return Ok(());
+                    }
+                    glifzip::armor::armor_file(&output).inspect_err(|_| {
+                        // Don't leave a binary archive where armored text was asked for
+                        let _ = std::fs::remove_file(&output);
+                    })
+                })
+                .and_then(|()| {
+                    if let Some(path) = &icon {
+                        if let Err(e) = glifzip::platform::set_file_icon(&output, &std::fs::read(path)?) {
+                            eprintln!("Warning: could not set the file icon: {}", e);
+                        }
+                    }
+                    if !set_protected {
+                        return Ok(());
+                    }
+                    glifzip::platform::protect_file(&output)?;
+                    if glifzip::platform::is_disk_encrypted() == Some(false) {
+                        eprintln!("Warning: FileVault is off; {} is not encrypted at rest", output.display());
+                    }
+                    Ok(())
+                })
         }
 
-        Commands::Extract { input, output, threads, verbose, no_progress } => {
-            let threads = threads.unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(8));
+        Commands::Extract { input, output, to_null, threads, verbose, no_progress, report, no_verify, verify, max_in_flight, max_memory, base, include_type, unsupported, names, member, .. } => {
+            let verify = if no_verify { glifzip::VerifyPolicy::Trusted } else { verify };
+            // 0 lets decompression pick a thread count from the archive's chunk count
+            let threads = threads.unwrap_or(0);
+            let threads_label = if threads == 0 { "auto".to_string() } else { threads.to_string() };
+            let output = output.unwrap_or_else(|| PathBuf::from("/dev/null"));
 
             // Try to read the archive to determine if it's a directory archive
-            let archive_data = std::fs::read(&input)?;
+            let archive_data = glifzip::armor::read_archive_file(&input)?;
+
+            // A file of several archives needs --member to pick one
+            let members = glifzip::format::list_members(&mut std::io::Cursor::new(&archive_data));
+            let selected = match (&member, members) {
+                (Some(label), members) => members.and_then(|members| {
+                    let found = glifzip::format::members::find_member(&members, label)?;
+                    Ok(glifzip::format::members::member_data(&archive_data, found)?.to_vec())
+                }),
+                (None, Ok(members)) if members.len() > 1 => Err(glifzip::GlifError::InvalidInput(format!(
+                    "{} holds {} archives; pick one with --member (glifzip members lists them)", input.display(), members.len()
+                ))),
+                (None, _) => Ok(archive_data),
+            };
+            let (archive_data, selection_error) = match selected {
+                Ok(archive_data) => (archive_data, None),
+                Err(e) => (Vec::new(), Some(e)),
+            };
             let mut cursor = std::io::Cursor::new(&archive_data);
 
             // Try to read as directory archive first
-            if let Ok(_manifest) = glifzip::ArchiveManifest::read(&mut cursor) {
+            let extraction = if let Some(e) = selection_error {
+                Err(e)
+            } else if let Ok(_manifest) = glifzip::ArchiveManifest::read(&mut cursor) {
                 // Directory archive
                 if verbose {
                     println!("Extracting directory archive {} to {} (threads={})",
-                             input.display(), output.display(), threads);
+                             input.display(), output.display(), threads_label);
+                }
+
+                let options = glifzip::ExtractOptions::new()
+                    .with_threads(threads)
+                    .with_verbose(verbose)
+                    .with_progress(!no_progress)
+                    .with_verify(verify)
+                    .with_max_in_flight(max_in_flight.unwrap_or(0))
+                    .with_max_memory(max_memory.unwrap_or(0))
+                    .with_bases(base)
+                    .with_include_types(include_type)
+                    .with_unsupported(unsupported)
+                    .with_names(names);
+                if member.is_some() {
+                    let report = glifzip::ExtractionReport::new(&input, &output);
+                    if to_null {
+                        glifzip::DirectoryCompressor::extract_into(&archive_data, &mut glifzip::archive::NullSink::new(), report, &options)
+                    } else {
+                        glifzip::DirectoryCompressor::extract_into(&archive_data, &mut glifzip::archive::DiskSink::new(output.clone()), report, &options)
+                    }
+                } else if to_null {
+                    glifzip::DirectoryCompressor::extract_directory_to_null(&input, &options)
+                } else {
+                    glifzip::DirectoryCompressor::extract_directory_with_options(&input, &output, &options)
+                }
+            } else if let Some(format) = glifzip::compression::raw::RawFormat::detect(&archive_data) {
+                // Plain .zst or .lz4 stream
+                if verbose {
+                    println!("Extracting {} stream {} to {} (threads={})",
+                             format, input.display(), output.display(), threads_label);
                 }
 
-                glifzip::DirectoryCompressor::extract_directory(
-                    &input,
-                    &output,
-                    threads,
-                    verbose,
-                    !no_progress
-                )
+                let result = glifzip::compression::raw::decompress_raw(&archive_data, threads).and_then(|data| {
+                    if !to_null {
+                        std::fs::write(&output, data)?;
+                    }
+                    Ok(())
+                });
+                Ok(single_file_report(&input, &output, result))
             } else {
                 // Single file archive
                 if verbose {
                     println!("Extracting {} to {} (threads={})",
-                             input.display(), output.display(), threads);
+                             input.display(), output.display(), threads_label);
+                }
+
+                let config = glifzip::DecompressionConfig::new()
+                    .with_threads(threads)
+                    .with_verify(verify)
+                    .with_max_in_flight(max_in_flight.unwrap_or(0))
+                    .with_max_memory(max_memory.unwrap_or(0));
+                let result = if to_null {
+                    glifzip::decompress_to_writer_with(archive_data.as_slice(), std::io::sink(), &config).map(|_| ())
+                } else if member.is_some() {
+                    std::fs::File::create(&output).map_err(glifzip::GlifError::from).and_then(|file| {
+                        glifzip::decompress_to_writer_with(archive_data.as_slice(), std::io::BufWriter::new(file), &config).map(|_| ())
+                    })
+                } else {
+                    glifzip::decompress_file_with(&input, &output, &config)
+                };
+                Ok(single_file_report(&input, &output, result))
+            };
+
+            let extraction = extraction
+                .unwrap_or_else(|e| glifzip::ExtractionReport::failed(&input, &output, &e));
+            if to_null && extraction.is_complete() {
+                println!("Checked {} entries without writing them", extraction.extracted);
+            }
+            let renamed = extraction.renamed().count();
+            if renamed > 0 && !verbose {
+                println!("Renamed {} entries Windows can't store; --report lists the new names", renamed);
+            }
+
+            if let Some(report_path) = report {
+                extraction.write_json(&report_path)?;
+                if verbose {
+                    println!("Wrote extraction report to {}", report_path.display());
                 }
+            }
+
+            extraction.into_result().map_err(std::io::Error::from)
+        }
+
+        Commands::Verify { input, full, level, recursive, report, threads } => {
+            let level = if full { glifzip::VerifyLevel::Full } else { level };
+            let full = level == glifzip::VerifyLevel::Full;
+            if recursive {
+                println!("Verifying archives under {}...", input.display());
+                let mut progress = glifzip::ConsoleProgress::without_stage_lines();
+                let sweep = glifzip::sweep_archives(&input, full, threads.unwrap_or(0), &mut progress, |outcome| {
+                    match &outcome.error {
+                        None => println!("  {}  {}", paint(pad("ok", 6, false), Style::Ok), outcome.path.display()),
+                        Some(e) => println!("  {}  {}: {}", paint("FAILED", Style::Error), outcome.path.display(), e),
+                    }
+                });
 
-                glifzip::decompress_file(&input, &output, threads)
+                sweep.and_then(|sweep| {
+                    println!("Verified {} of {} archives", sweep.verified, sweep.archives.len());
+                    if let Some(report_path) = &report {
+                        sweep.write_json(report_path)?;
+                        println!("Report written to {}", report_path.display());
+                    }
+                    if sweep.is_clean() {
+                        Ok(())
+                    } else {
+                        Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("{} archive(s) failed verification", sweep.failed)
+                        ).into())
+                    }
+                }).map_err(std::io::Error::from)
+            } else {
+                println!("Verifying {}...", input.display());
+
+                let mut progress = glifzip::ConsoleProgress::new();
+                glifzip::verify_archive_file_at(&input, level, threads.unwrap_or(0), &mut progress)
+                    .and_then(|sidecar| {
+                        // A structural check hashes nothing, so it doesn't count as a verification
+                        if level >= glifzip::VerifyLevel::Archive {
+                            let size = std::fs::metadata(&input)?.len();
+                            let record = glifzip::VerificationRecord::now(size, sidecar.archive.hash.clone());
+                            if let Err(e) = record.save(&input) {
+                                eprintln!("Warning: could not record verification: {}", e);
+                            }
+                        }
+                        Ok(sidecar)
+                    })
+                    .map(|sidecar| {
+                        println!("{}", paint("Archive verified successfully!", Style::Ok));
+                        print_fields(&[
+                            ("Payload size", format!("{} bytes", sidecar.payload.size)),
+                            ("Archive size", format!("{} bytes", sidecar.archive.size)),
+                            ("Compression ratio", format!("{:.2}%", sidecar.payload.compression_ratio * 100.0)),
+                            ("Compression level", sidecar.archive.compression_level.to_string()),
+                            ("Threads used", sidecar.archive.threads.to_string()),
+                        ]);
+                    })
+                    .map_err(std::io::Error::from)
             }
         }
 
-        Commands::Verify { input } => {
-            println!("Verifying {}...", input.display());
+        Commands::Info { input, icon: true, output: Some(output) } => {
+            peek_file(&input).and_then(|(info, _)| {
+                let icon = info.sidecar.and_then(|sidecar| sidecar.icon).ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} has no icon", input.display())
+                ))?;
+                std::fs::write(&output, icon.bytes()?)?;
+                println!("Wrote {} icon to {}", icon.media_type, output.display());
+                Ok(())
+            })
+        }
+
+        Commands::Info { input, .. } => {
+            peek_file(&input).map(|(info, file_size)| {
+                println!("Archive: {}", input.display());
+                if info.manifest_size.is_some() || info.header.is_some() {
+                    println!("  Type: {}", if info.manifest_size.is_some() { "directory" } else { "file" });
+                }
+
+                let (Some(header), Some(sidecar)) = (&info.header, &info.sidecar) else {
+                    println!("  Incomplete: {} more bytes needed to read the archive info", info.bytes_needed);
+                    return;
+                };
 
+                println!("  Format: {} (header v{})", sidecar.format, glifzip::format::header::format_version(header.format_version));
+                println!("  Created by: glifzip {}", sidecar.metadata.glifzip_version.as_deref().unwrap_or("unknown"));
+                println!("  Requires: glifzip >= {}", sidecar.metadata.min_reader_version.as_deref().unwrap_or("1.0.0"));
+                println!("  Created: {}", sidecar.metadata.created);
+                println!("  Platform: {}/{}", sidecar.metadata.source_platform, sidecar.metadata.source_architecture);
+                println!("  Payload size: {} bytes", header.payload_size);
+                println!("  Archive size: {} bytes", header.archive_size);
+                println!("  Compression level: {}", header.compression_level);
+                println!("  Decompression: {}", sidecar.archive.decompressed_with);
+                if let Some(tree_hash) = &sidecar.payload.tree_hash {
+                    println!("  Tree hash: {}", tree_hash);
+                }
+                if let Some(content_address) = &sidecar.payload.content_address {
+                    println!("  Content address: {}", content_address);
+                }
+                if sidecar.metadata.sensitive {
+                    println!("  Sensitive: yes");
+                }
+                if let Some(icon) = &sidecar.icon {
+                    println!("  Icon: {}", icon.media_type);
+                }
+                if let Some(window_size) = sidecar.archive.window_size {
+                    println!("  Window size: {} bytes", window_size);
+                }
+                if let Some(expected) = info.expected_size().filter(|&expected| file_size < expected) {
+                    println!("  Downloaded: {} of {} bytes ({:.1}%)", file_size, expected, file_size as f64 * 100.0 / expected as f64);
+                }
+                if let Err(e) = sidecar.check_reader_compatibility() {
+                    println!("  Warning: {}", e);
+                }
+            })
+        }
+
+        Commands::Hash { input, exclude } => {
+            content_address(&input, exclude).map(|address| println!("{}  {}", address, input.display()))
+        }
+
+        Commands::HashBackfill { archive, threads } => {
+            glifzip::archive::backfill_file(&archive, threads.unwrap_or(0)).map(|hashed| match hashed {
+                0 => println!("{}: every file is already hashed", archive.display()),
+                n => println!("{}: added hashes for {} file(s)", archive.display(), n),
+            }).map_err(std::io::Error::from)
+        }
+
+        Commands::Status { directory, max_age_days } => {
+            glifzip::scan_archives(&directory).map(|archives| {
+                let now = chrono::Utc::now();
+                let max_age = chrono::Duration::days(max_age_days);
+                let mut stale = 0;
+
+                for archive in &archives {
+                    let verified = match archive.last_verified {
+                        Some(time) => format!("verified {} days ago", (now - time).num_days()),
+                        None => "never verified".to_string(),
+                    };
+                    let flag = if archive.is_stale(max_age, now) {
+                        stale += 1;
+                        "STALE"
+                    } else {
+                        "OK"
+                    };
+                    println!("{:<5} {} ({} bytes, {})", flag, archive.path.display(), archive.size, verified);
+                }
+
+                println!("\n{} archives, {} not verified in the last {} days", archives.len(), stale, max_age_days);
+            }).map_err(std::io::Error::from)
+        }
+
+        Commands::MakeCorpus { dir, force } => {
+            let version_dir = dir.join(format!("v{}", glifzip::format::GLIFZIP_VERSION));
+
+            if version_dir.exists() && !force {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists; released corpora must not change (use --force to replace)", version_dir.display())
+                ))
+            } else {
+                glifzip::testing::corpus::write_corpus(&version_dir).map(|written| {
+                    for path in &written {
+                        println!("Wrote {}", path.display());
+                    }
+                })
+            }
+        }
+
+        Commands::List { input, format: Some(format), output, .. } => {
             std::fs::read(&input)
-                .and_then(|archive| glifzip::verify_archive(&archive))
-                .map(|sidecar| {
-                    println!("Archive verified successfully!");
-                    println!("  Payload size: {} bytes", sidecar.payload.size);
-                    println!("  Archive size: {} bytes", sidecar.archive.size);
-                    println!("  Compression ratio: {:.2}%", sidecar.payload.compression_ratio * 100.0);
-                    println!("  Compression level: {}", sidecar.archive.compression_level);
-                    println!("  Threads used: {}", sidecar.archive.threads);
+                .and_then(|archive_data| {
+                    let manifest = glifzip::ArchiveManifest::read_checked(&mut std::io::Cursor::new(&archive_data))?;
+                    match &output {
+                        Some(path) => {
+                            let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                            glifzip::convert::export_manifest(&manifest, format, file)?;
+                            println!("Wrote {} entries to {} ({})", manifest.entries.len(), path.display(), format);
+                            Ok(())
+                        }
+                        None => glifzip::convert::export_manifest(&manifest, format, std::io::stdout().lock()),
+                    }
                 })
         }
 
-        Commands::List { input, verbose } => {
+        Commands::List { input, verbose, preview, .. } => {
             println!("Listing contents of {}...", input.display());
 
             std::fs::read(&input)
                 .and_then(|archive_data| {
-                    let mut cursor = std::io::Cursor::new(&archive_data);
-                    let manifest = glifzip::ArchiveManifest::read(&mut cursor)?;
+                    let manifest = glifzip::ArchiveManifest::read_checked(&mut std::io::Cursor::new(&archive_data))?;
 
                     println!("Archive: {}", input.display());
                     println!("Files: {}", manifest.file_count);
                     println!("Total size: {} bytes", manifest.total_size);
                     println!("Base directory: {}", manifest.base_directory.display());
                     println!("\nContents:");
+                    let mut archive = if preview { Some(glifzip::GlifArchive::open(&input)?) } else { None };
+                    print_entries(&manifest, verbose, archive.as_mut());
+
+                    Ok(())
+                })
+        }
+
+        Commands::Members { input } => {
+            std::fs::File::open(&input).map_err(glifzip::GlifError::from).and_then(|mut file| {
+                let members = glifzip::format::list_members(&mut std::io::BufReader::new(&mut file))?;
+                println!("{}: {} member(s)", input.display(), members.len());
+                for member in &members {
+                    println!("  {:<5} {:<20} {:<9} offset {:>12}  {:>12} bytes ({} bytes uncompressed)",
+                             format!("#{}", member.index),
+                             member.name.as_deref().unwrap_or("-"),
+                             if member.is_directory { "directory" } else { "file" },
+                             member.offset, member.size, member.payload_size);
+                }
+                Ok(())
+            }).map_err(std::io::Error::from)
+        }
 
-                    for file_info in manifest.list_files() {
-                        println!("  {}", file_info);
+        Commands::Append { container, archive, name } => {
+            let name = name.or_else(|| archive.file_stem().map(|stem| stem.to_string_lossy().into_owned()));
+            std::fs::read(&archive).map_err(glifzip::GlifError::from)
+                .and_then(|data| glifzip::format::append_member(&container, &data, name.as_deref()))
+                .map(|member| println!("Appended {} to {} as member {} ({} bytes at offset {})",
+                                       archive.display(), container.display(), member.label(), member.size, member.offset))
+                .map_err(std::io::Error::from)
+        }
+
+        Commands::Compare { old, new, format, output } => {
+            let read_manifest = |path: &PathBuf| -> std::io::Result<glifzip::ArchiveManifest> {
+                let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+                glifzip::ArchiveManifest::read_checked(&mut reader)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+            };
+            read_manifest(&old).and_then(|old_manifest| {
+                let diff = glifzip::archive::diff_manifests(&old_manifest, &read_manifest(&new)?);
+                let report = match format {
+                    glifzip::archive::diff::DiffFormat::Text if output.is_none() => {
+                        glifzip::archive::diff::render_console(&diff, glifzip::progress::color::colors_enabled())
+                    }
+                    glifzip::archive::diff::DiffFormat::Text => glifzip::archive::diff::render_text(&diff),
+                    glifzip::archive::diff::DiffFormat::Html => {
+                        glifzip::archive::diff::render_html(&diff, &old.display().to_string(), &new.display().to_string())
                     }
+                };
+                match &output {
+                    Some(path) => {
+                        std::fs::write(path, report)?;
+                        println!("Wrote {} changes to {}", diff.changes.len(), path.display());
+                        Ok(())
+                    }
+                    None => {
+                        print!("{}", report);
+                        Ok(())
+                    }
+                }
+            })
+        }
 
-                    if verbose {
-                        println!("\nDetailed information:");
-                        for entry in &manifest.entries {
-                            println!("  {} ({} bytes, mode: {:o})",
-                                entry.path.display(),
-                                entry.size,
-                                entry.mode
-                            );
-                            if let Some(ref target) = entry.symlink_target {
-                                println!("    -> {}", target.display());
-                            }
+        Commands::Chain { action } => match action {
+            ChainCommands::Verify { archives } => {
+                glifzip::archive::verify_chain(&archives)
+                    .map_err(std::io::Error::from)
+                    .and_then(|report| {
+                        for link in &report.links {
+                            println!("  {}  {} entries, {} referenced", link.path.display(), link.entries, link.references);
+                        }
+                        for problem in &report.problems {
+                            println!("  {}  {}", paint("BROKEN", Style::Error), problem);
+                        }
+                        if report.is_valid() {
+                            println!("{}", paint("Chain is complete", Style::Ok));
+                            Ok(())
+                        } else {
+                            Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!("{} problem(s) in the chain", report.problems.len())
+                            ))
                         }
+                    })
+            }
+            ChainCommands::Flatten { archives, output, level, threads } => {
+                glifzip::CompressionConfig::builder()
+                    .level(level)
+                    .threads(threads.unwrap_or(0))
+                    .build()
+                    .map_err(std::io::Error::from)
+                    .and_then(|config| {
+                        let dir_config = glifzip::DirectoryCompressionConfig::new(config).with_progress(false);
+                        let archive = glifzip::archive::flatten_chain(&archives, dir_config)?;
+                        std::fs::write(&output, &archive)?;
+                        println!("Wrote {} ({} bytes)", output.display(), archive.len());
+                        Ok(())
+                    })
+            }
+        },
+
+        Commands::InstallIntegration => {
+            std::env::current_exe()
+                .and_then(|exe| glifzip::platform::install_desktop_integration(&exe))
+                .map(|installed| {
+                    for path in installed {
+                        println!("Installed {}", path.display());
+                    }
+                    println!("Registered .glif file type with Launch Services");
+                })
+        }
+
+        Commands::Schedule { action } => match action {
+            ScheduleCommands::Install { profile, daily, args } => {
+                glifzip::platform::schedule::DailyTime::parse(&daily)
+                    .and_then(|time| {
+                        let exe = std::env::current_exe()?;
+                        glifzip::platform::schedule::ScheduledJob::new(&profile, time, exe, args)
+                    })
+                    .and_then(|job| {
+                        let written = job.install()?;
+                        for path in written {
+                            println!("Wrote {}", path.display());
+                        }
+                        println!("Scheduled '{}' daily at {}", job.profile, job.time);
+                        Ok(())
+                    })
+            }
+            ScheduleCommands::List => {
+                glifzip::platform::schedule::list().map(|profiles| {
+                    if profiles.is_empty() {
+                        println!("No scheduled backups");
+                    }
+                    for profile in profiles {
+                        println!("{}", profile);
+                    }
+                })
+            }
+            ScheduleCommands::Remove { profile } => {
+                glifzip::platform::schedule::remove(&profile)
+                    .map(|_| println!("Removed scheduled backup '{}'", profile))
+            }
+        },
+
+        Commands::ToOciLayer { input, output, threads } => {
+            std::fs::read(&input)
+                .and_then(|archive| glifzip::convert::to_oci_layer(&archive, threads.unwrap_or(0)))
+                .and_then(|layer| {
+                    std::fs::write(&output, &layer.data)?;
+                    println!("Wrote {} ({})", output.display(), glifzip::convert::oci::OCI_LAYER_MEDIA_TYPE);
+                    println!("  Digest: {}", layer.digest);
+                    println!("  DiffID: {}", layer.diff_id);
+                    println!("  Size: {} bytes", layer.data.len());
+                    Ok(())
+                })
+        }
+
+        Commands::FromOciLayer { input, output, level, drop_whiteouts } => {
+            glifzip::CompressionConfig::builder()
+                .level(level)
+                .build()
+                .map_err(std::io::Error::from)
+                .and_then(|config| {
+                    let compressor = glifzip::DirectoryCompressor::new(
+                        glifzip::DirectoryCompressionConfig::new(config).with_progress(false)
+                    )?;
+                    let layer = std::fs::read(&input)?;
+                    let (archive, tree) = glifzip::convert::from_oci_layer(&layer, &compressor, drop_whiteouts)?;
+                    std::fs::write(&output, archive)?;
+
+                    println!("Wrote {} ({} entries)", output.display(), tree.len());
+                    for path in &tree.whiteouts {
+                        println!("  Dropped whiteout for {}", path.display());
                     }
+                    Ok(())
+                })
+        }
+
+        Commands::ToCpio { input, output, zstd, root_owner, threads } => {
+            let mut options = glifzip::convert::CpioOptions::default().with_root_owner(root_owner);
+            options.zstd_level = zstd;
+
+            std::fs::read(&input)
+                .and_then(|archive| glifzip::convert::to_cpio(&archive, threads.unwrap_or(0), &options))
+                .and_then(|cpio| {
+                    std::fs::write(&output, &cpio)?;
+                    println!("Wrote {} ({} bytes{})", output.display(), cpio.len(), if zstd.is_some() { ", zstd" } else { "" });
+                    Ok(())
+                })
+        }
+
+        Commands::ToImage { input, output, block_size_kb, level, threads } => {
+            let options = glifzip::image::ImageOptions::default()
+                .with_block_size(block_size_kb.saturating_mul(1024))
+                .with_level(level);
+
+            std::fs::read(&input)
+                .and_then(|archive| glifzip::image::archive_to_image(&archive, threads.unwrap_or(0), &options))
+                .and_then(|image| {
+                    let superblock = *glifzip::image::Image::parse(&image)?.superblock();
+                    std::fs::write(&output, &image)?;
+                    println!(
+                        "Wrote {} ({} bytes, {} inodes, {} blocks)",
+                        output.display(), image.len(), superblock.inode_count, superblock.block_count
+                    );
+                    Ok(())
+                })
+        }
 
+        Commands::Clip { input, level } => {
+            glifzip::CompressionConfig::builder()
+                .level(level)
+                .build()
+                .map_err(std::io::Error::from)
+                .and_then(|config| {
+                    let data = std::fs::read(&input)?;
+                    let text = glifzip::clip::encode_clip(&data, &config)?;
+                    glifzip::platform::copy_to_clipboard(&text)?;
+                    println!("Copied {} ({} bytes as {} characters) to the clipboard", input.display(), data.len(), text.len());
                     Ok(())
                 })
         }
+
+        Commands::Unclip { output, threads } => {
+            glifzip::platform::paste_from_clipboard()
+                .and_then(|text| glifzip::clip::decode_clip(&text, threads.unwrap_or(0)))
+                .and_then(|data| {
+                    std::fs::write(&output, &data)?;
+                    println!("Wrote {} ({} bytes)", output.display(), data.len());
+                    Ok(())
+                })
+        }
+
+        Commands::Cache { root, action } => {
+            root.map(Ok).unwrap_or_else(glifzip::cache::ArchiveCache::default_root)
+                .map(glifzip::cache::ArchiveCache::new)
+                .and_then(|cache| match action {
+                    CacheCommands::Put { input, key, level, exclude, max_size_mb } => {
+                        glifzip::CompressionConfig::builder()
+                            .level(level)
+                            .build()
+                            .map_err(std::io::Error::from)
+                            .and_then(|config| {
+                                let dir_config = glifzip::DirectoryCompressionConfig::new(config)
+                                    .with_exclude_patterns(exclude)
+                                    .with_progress(false);
+                                cache.with_max_size(max_size_mb * 1024 * 1024)
+                                    .put(&input, key.as_deref(), dir_config)
+                            })
+                            .map(|entry| println!("Cached {} as {} ({} bytes)", input.display(), entry.key, entry.size))
+                    }
+                    CacheCommands::Get { key, output, threads } => {
+                        cache.get(&key, &output, threads.unwrap_or(0))
+                            .map(|entry| println!("Restored {} to {}", entry.key, output.display()))
+                    }
+                })
+        }
+
+        Commands::Selftest => {
+            println!("glifzip {} self-test ({} {}, {} CPUs)\n",
+                glifzip::format::GLIFZIP_VERSION,
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                num_cpus::get()
+            );
+
+            let report = glifzip::selftest::run_selftest(|check| {
+                println!("{:<7} {} ({:.2}s)", check.status.to_string(), check.name, check.elapsed.as_secs_f64());
+                if let Some(ref detail) = check.detail {
+                    println!("        {}", detail);
+                }
+            });
+
+            println!("\n{} passed, {} failed, {} skipped", report.passed(), report.failed(), report.skipped());
+            if report.is_ok() {
+                Ok(())
+            } else {
+                Err(std::io::Error::other(format!("{} self-test check(s) failed", report.failed())))
+            }
+        }
+
+        Commands::Bench { input, kind, size, level, threads, compare } => {
+            let data = match input {
+                Some(ref path) => std::fs::read(path),
+                None => usize::try_from(size)
+                    .map(|size| glifzip::testing::data::generate(kind, size, glifzip::testing::data::DEFAULT_SEED))
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "--size is too large for this platform")),
+            };
+            data.and_then(|data| {
+                let config = glifzip::CompressionConfig::builder()
+                    .level(level)
+                    .threads(threads.unwrap_or(0))
+                    .build()
+                    .map_err(std::io::Error::from)?;
+                match input {
+                    Some(ref path) => println!("Benchmarking {} ({} bytes)", path.display(), data.len()),
+                    None => println!("Benchmarking {} bytes of generated {} data", data.len(), kind),
+                }
+
+                let tools: &[glifzip::bench::ExternalTool] = if compare { &glifzip::bench::ExternalTool::ALL } else { &[] };
+                let results = glifzip::bench::compare(&data, &config, tools, |name, result| match result {
+                    Ok(_) => println!("  {} done", name),
+                    Err(e) => eprintln!("  {} failed: {}", name, e),
+                })?;
+                if compare && results.len() < tools.len() + 1 {
+                    println!("  (tools that are not installed were skipped)");
+                }
+
+                println!("\n{}", glifzip::bench::render_table(&results));
+                Ok(())
+            })
+        }
+        Commands::Tune { input, kind, size, level, threads, dry_run } => {
+            let sample = match input {
+                Some(ref path) => std::fs::File::open(path).and_then(|file| {
+                    use std::io::Read;
+                    let mut data = Vec::new();
+                    file.take(size).read_to_end(&mut data)?;
+                    Ok(data)
+                }),
+                None => usize::try_from(size)
+                    .map(|size| glifzip::testing::data::generate(kind, size, glifzip::testing::data::DEFAULT_SEED))
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "--size is too large for this platform")),
+            };
+            sample.and_then(|sample| {
+                let configs = glifzip::tune::candidates(level, sample.len(), threads.unwrap_or(0));
+                match input {
+                    Some(ref path) => println!("Calibrating {} settings on {} bytes of {}", configs.len(), sample.len(), path.display()),
+                    None => println!("Calibrating {} settings on {} bytes of generated {} data", configs.len(), sample.len(), kind),
+                }
+
+                let describe = |config: &glifzip::CompressionConfig| format!(
+                    "{:>4} MiB chunks, {:>3} threads, {}",
+                    config.chunk_size >> 20,
+                    config.threads,
+                    if config.use_lz4_decompression { "lz4 " } else { "zstd" }
+                );
+                let calibrations = glifzip::tune::calibrate(&sample, &configs, |calibration| {
+                    println!(
+                        "  {}: compress {:>8.1} MB/s, decompress {:>8.1} MB/s",
+                        describe(&calibration.config),
+                        calibration.result.compress_throughput() / 1e6,
+                        calibration.result.decompress_throughput() / 1e6
+                    );
+                })?;
+
+                let best = glifzip::tune::best(&calibrations)
+                    .ok_or_else(|| std::io::Error::other("no settings to calibrate"))?;
+                println!("\nFastest: {}", describe(&best.config).trim());
+                if dry_run {
+                    return Ok(());
+                }
+
+                let path = glifzip::tune::user_config_path()?;
+                glifzip::tune::TunedSettings::from_calibration(best).save(&path)?;
+                println!("Saved to {}; compress with them using `glifzip create --preset tuned`", path.display());
+                Ok(())
+            })
+        }
+
+        Commands::GenTestdata { kind, size, output, seed } => {
+            std::fs::File::create(&output)
+                .and_then(|file| {
+                    let mut writer = std::io::BufWriter::new(file);
+                    std::io::copy(&mut glifzip::testing::data::TestData::new(kind, size, seed), &mut writer)?;
+                    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+                })
+                .map(|()| println!("Wrote {} bytes of {} data to {} (seed {})", size, kind, output.display(), seed))
+        }
     };
 
+    if let Some(operation) = notification {
+        let message = match &result {
+            Ok(()) => format!("{} completed", operation),
+            Err(e) => format!("{} failed: {}", operation, e),
+        };
+        if let Err(e) = glifzip::platform::notify("GLifzip", &message) {
+            eprintln!("Warning: could not show notification: {}", e);
+        }
+    }
+
     if let Err(e) = result {
         eprintln!("Error: {}", e);
         std::process::exit(1);
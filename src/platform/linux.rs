@@ -0,0 +1,147 @@
+//! Linux-specific functionality for GLifzip
+//!
+//! This module provides integration with common Linux desktop services:
+//! - Desktop notifications over D-Bus (org.freedesktop.Notifications)
+//! - Clipboard access through wl-clipboard, xclip or xsel
+//! - Read-ahead hints and preallocation through `posix_fadvise` and `fallocate`
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::process::{Command, Stdio};
+
+/// Clipboard tools tried in order: (copy command, paste command)
+const CLIPBOARD_TOOLS: &[(&[&str], &[&str])] = &[
+    (&["wl-copy"], &["wl-paste", "--no-newline"]),
+    (&["xclip", "-selection", "clipboard"], &["xclip", "-selection", "clipboard", "-o"]),
+    (&["xsel", "--clipboard", "--input"], &["xsel", "--clipboard", "--output"]),
+];
+
+/// Send a desktop notification over the session D-Bus
+///
+/// Uses `notify-send` when available and falls back to calling the
+/// freedesktop notification service directly through `gdbus`.
+pub fn send_notification(title: &str, message: &str) -> Result<()> {
+    if let Ok(status) = Command::new("notify-send")
+        .args(["--app-name=glifzip", title, message])
+        .status()
+    {
+        if status.success() {
+            return Ok(());
+        }
+    }
+
+    let status = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest=org.freedesktop.Notifications",
+            "--object-path=/org/freedesktop/Notifications",
+            "--method=org.freedesktop.Notifications.Notify",
+            "glifzip",
+            "0",
+            "",
+            title,
+            message,
+            "[]",
+            "{}",
+            "-1",
+        ])
+        .output()?
+        .status;
+
+    if !status.success() {
+        return Err(Error::other(format!("gdbus exited with {}", status)));
+    }
+
+    Ok(())
+}
+
+/// Place text on the clipboard using the first available clipboard tool
+pub fn set_clipboard(text: &str) -> Result<()> {
+    for (copy, _) in CLIPBOARD_TOOLS {
+        let Ok(mut child) = Command::new(copy[0]).args(&copy[1..]).stdin(Stdio::piped()).spawn() else {
+            continue;
+        };
+        // A tool that cannot reach its display server may exit before reading
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        if child.wait()?.success() && written {
+            return Ok(());
+        }
+    }
+    Err(no_clipboard_tool())
+}
+
+/// Read text from the clipboard using the first available clipboard tool
+pub fn get_clipboard() -> Result<String> {
+    for (_, paste) in CLIPBOARD_TOOLS {
+        let Ok(output) = Command::new(paste[0]).args(&paste[1..]).stderr(Stdio::null()).output() else {
+            continue;
+        };
+        if output.status.success() {
+            return String::from_utf8(output.stdout)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Clipboard does not hold text"));
+        }
+    }
+    Err(no_clipboard_tool())
+}
+
+fn no_clipboard_tool() -> Error {
+    Error::new(ErrorKind::NotFound, "No usable clipboard tool found (install wl-clipboard, xclip or xsel)")
+}
+
+/// Tell the kernel the whole file will be read sequentially, so it reads
+/// ahead aggressively and starts fetching it into the page cache now
+pub fn advise_sequential(file: &File) -> Result<()> {
+    #[cfg(target_pointer_width = "64")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        const POSIX_FADV_SEQUENTIAL: i32 = 2;
+        const POSIX_FADV_WILLNEED: i32 = 3;
+
+        extern "C" {
+            fn posix_fadvise(fd: i32, offset: i64, len: i64, advice: i32) -> i32;
+        }
+
+        for advice in [POSIX_FADV_SEQUENTIAL, POSIX_FADV_WILLNEED] {
+            // Returns the error number rather than setting errno; a length of 0 means to the end
+            let result = unsafe { posix_fadvise(file.as_raw_fd(), 0, 0, advice) };
+            if result != 0 {
+                return Err(Error::from_raw_os_error(result));
+            }
+        }
+    }
+
+    #[cfg(not(target_pointer_width = "64"))]
+    let _ = file;
+
+    Ok(())
+}
+
+/// Reserve `len` bytes of disk space for a file about to be written,
+/// extending it to that size; filesystems without support are left alone
+pub fn preallocate(file: &File, len: u64) -> Result<()> {
+    #[cfg(target_pointer_width = "64")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        const EOPNOTSUPP: i32 = 95;
+
+        extern "C" {
+            fn fallocate(fd: i32, mode: i32, offset: i64, len: i64) -> i32;
+        }
+
+        let len = i64::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidInput, "file too large to preallocate"))?;
+        if unsafe { fallocate(file.as_raw_fd(), 0, 0, len) } != 0 {
+            let error = Error::last_os_error();
+            if error.raw_os_error() != Some(EOPNOTSUPP) {
+                return Err(error);
+            }
+        }
+    }
+
+    #[cfg(not(target_pointer_width = "64"))]
+    let _ = (file, len);
+
+    Ok(())
+}
@@ -5,31 +5,297 @@
 //! - Extended attributes (xattr) for metadata preservation
 //! - Quarantine attribute handling for downloaded files
 //! - File type registration in Launch Services
+//! - Finder Quick Actions ("Compress with GLifzip" / "Extract here")
+//! - Read-ahead advice and preallocation through `fcntl`
 //! - Apple Silicon and Intel support
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use super::xml_escape;
+
+/// Uniform Type Identifier declared for .glif archives
+pub const GLIF_UTI: &str = "com.glyphos.glif";
+
+/// Bundle identifier used for the Launch Services registration bundle
+pub const BUNDLE_ID: &str = "com.glyphos.glifzip";
+
+const LSREGISTER: &str = "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
+
+/// Finder Quick Actions installed by `glifzip install-integration`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickAction {
+    /// "Compress with GLifzip" on any file or folder
+    Compress,
+    /// "Extract here" on .glif archives
+    Extract,
+}
+
+impl QuickAction {
+    pub const ALL: [QuickAction; 2] = [QuickAction::Compress, QuickAction::Extract];
+
+    /// Menu title shown in Finder
+    pub fn title(&self) -> &'static str {
+        match self {
+            QuickAction::Compress => "Compress with GLifzip",
+            QuickAction::Extract => "Extract here",
+        }
+    }
+
+    /// File types the action is offered for
+    fn send_file_type(&self) -> &'static str {
+        match self {
+            QuickAction::Compress => "public.item",
+            QuickAction::Extract => GLIF_UTI,
+        }
+    }
+
+    /// Shell script run by the workflow, receiving the selection as arguments
+    pub fn shell_command(&self, glifzip: &Path) -> String {
+        let exe = shell_quote(&glifzip.to_string_lossy());
+        match self {
+            QuickAction::Compress => format!(
+                "for f in \"$@\"; do {} create \"$f\" -o \"$f.glif\" --no-progress --notify; done",
+                exe
+            ),
+            QuickAction::Extract => format!(
+                "for f in \"$@\"; do {} extract \"$f\" -o \"${{f%.glif}}\" --no-progress --notify; done",
+                exe
+            ),
+        }
+    }
+
+    /// Generate the workflow's Contents/Info.plist (Services menu registration)
+    pub fn info_plist(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{title}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>{file_type}</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#,
+            title = xml_escape(self.title()),
+            file_type = self.send_file_type(),
+        )
+    }
+
+    /// Generate the workflow's Contents/document.wflow (a single "Run Shell Script" action)
+    pub fn document_wflow(&self, glifzip: &Path) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMParameterProperties</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<dict/>
+					<key>inputMethod</key>
+					<dict/>
+					<key>shell</key>
+					<dict/>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{command}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/bash</string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>{input_uuid}</string>
+				<key>OutputUUID</key>
+				<string>{output_uuid}</string>
+				<key>UUID</key>
+				<string>{uuid}</string>
+			</dict>
+		</dict>
+	</array>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceApplicationBundleID</key>
+		<string>com.apple.finder</string>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#,
+            command = xml_escape(&self.shell_command(glifzip)),
+            input_uuid = self.uuid(1),
+            output_uuid = self.uuid(2),
+            uuid = self.uuid(3),
+        )
+    }
+
+    /// Stable per-action UUIDs so regenerated workflows are byte-identical
+    fn uuid(&self, slot: u8) -> String {
+        let action = match self {
+            QuickAction::Compress => 1,
+            QuickAction::Extract => 2,
+        };
+        format!("6C1F0000-0000-4000-8000-0000000{:02X}{:03X}", action, slot)
+    }
+
+    /// Write the workflow bundle into `services_dir` and return its path
+    pub fn install(&self, glifzip: &Path, services_dir: &Path) -> std::io::Result<PathBuf> {
+        let bundle = services_dir.join(format!("{}.workflow", self.title()));
+        let contents = bundle.join("Contents");
+        std::fs::create_dir_all(&contents)?;
+        std::fs::write(contents.join("Info.plist"), self.info_plist())?;
+        std::fs::write(contents.join("document.wflow"), self.document_wflow(glifzip))?;
+        Ok(bundle)
+    }
+}
+
+/// Generate the Info.plist declaring the GLIF UTI for Launch Services
+pub fn uti_declaration_plist() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleIdentifier</key>
+	<string>{bundle_id}</string>
+	<key>CFBundleName</key>
+	<string>GLifzip</string>
+	<key>CFBundlePackageType</key>
+	<string>APPL</string>
+	<key>CFBundleShortVersionString</key>
+	<string>{version}</string>
+	<key>LSBackgroundOnly</key>
+	<true/>
+	<key>UTExportedTypeDeclarations</key>
+	<array>
+		<dict>
+			<key>UTTypeIdentifier</key>
+			<string>{uti}</string>
+			<key>UTTypeDescription</key>
+			<string>GLIF Archive</string>
+			<key>UTTypeConformsTo</key>
+			<array>
+				<string>public.data</string>
+				<string>public.archive</string>
+			</array>
+			<key>UTTypeTagSpecification</key>
+			<dict>
+				<key>public.filename-extension</key>
+				<array>
+					<string>glif</string>
+				</array>
+				<key>public.mime-type</key>
+				<string>application/x-glif</string>
+			</dict>
+		</dict>
+	</array>
+	<key>CFBundleDocumentTypes</key>
+	<array>
+		<dict>
+			<key>CFBundleTypeName</key>
+			<string>GLIF Archive</string>
+			<key>CFBundleTypeRole</key>
+			<string>Viewer</string>
+			<key>LSHandlerRank</key>
+			<string>Owner</string>
+			<key>LSItemContentTypes</key>
+			<array>
+				<string>{uti}</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#,
+        bundle_id = BUNDLE_ID,
+        version = env!("CARGO_PKG_VERSION"),
+        uti = GLIF_UTI,
+    )
+}
+
 /// Register GLIF file type with macOS Finder and Launch Services
 /// This allows Finder to recognize .glif files and associate them with glifzip
+///
+/// Writes a declaration-only bundle to ~/Applications/GLifzip.app exporting
+/// the `com.glyphos.glif` UTI and registers it with `lsregister`.
 pub fn register_file_type() -> std::io::Result<()> {
     #[cfg(target_os = "macos")]
     {
-        // Try to get the app bundle path
-        let bundle_id = "com.glyphos.glifzip";
-        let app_name = "GLifzip";
-
-        // Use LaunchServices to register the file type
-        let output = Command::new("duti")
-            .args(&["--set", bundle_id, "com.glif", "all"])
-            .output();
-
-        // If duti is not available, try using macOS native commands
-        if output.is_err() {
-            let _ = Command::new("defaults")
-                .args(&["write", "com.apple.LaunchServices/com.apple.LaunchServices.secure", "LSHandlers", "-array-add",
-                    &format!("{{ LSHandlerContentType = com.glif; LSHandlerRoleAll = {}; }}", bundle_id)])
-                .output();
+        let bundle = home_dir()?.join("Applications").join("GLifzip.app");
+        let contents = bundle.join("Contents");
+        std::fs::create_dir_all(&contents)?;
+        std::fs::write(contents.join("Info.plist"), uti_declaration_plist())?;
+
+        let status = Command::new(LSREGISTER)
+            .arg("-f")
+            .arg(&bundle)
+            .status()?;
+
+        if !status.success() {
+            return Err(std::io::Error::other(format!("lsregister exited with {}", status)));
         }
 
         Ok(())
@@ -41,6 +307,39 @@ pub fn register_file_type() -> std::io::Result<()> {
     }
 }
 
+/// Install Finder Quick Actions and register the GLIF UTI
+/// Returns the paths of the installed workflow bundles
+pub fn install_integration(glifzip: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let services_dir = home_dir()?.join("Library").join("Services");
+
+    let mut installed = Vec::new();
+    for action in QuickAction::ALL {
+        installed.push(action.install(glifzip, &services_dir)?);
+    }
+
+    register_file_type()?;
+
+    // Ask the services menu to pick up the new workflows
+    #[cfg(target_os = "macos")]
+    {
+        let _ = Command::new("/System/Library/CoreServices/pbs").arg("-update").status();
+    }
+
+    Ok(installed)
+}
+
+/// Single-quote a word for `/bin/sh`; nothing inside single quotes is
+/// special, so an embedded `'` closes the quote, is escaped, and reopens it
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn home_dir() -> std::io::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "HOME is not set"))
+}
+
 /// Get extended attributes from a file (macOS xattr)
 /// These are metadata attributes stored with files
 pub fn get_extended_attributes(path: &Path) -> std::io::Result<Vec<(String, Vec<u8>)>> {
@@ -154,6 +453,120 @@ pub fn set_extended_attributes(path: &Path, attrs: &[(String, Vec<u8>)]) -> std:
     }
 }
 
+/// Extended attribute set by `tmutil addexclusion` on excluded items
+pub const TIME_MACHINE_EXCLUDE_XATTR: &str = "com.apple.metadata:com_apple_backup_excludeItem";
+
+/// Check whether a file or directory is excluded from Time Machine backups
+pub fn is_time_machine_excluded(path: &Path) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        use std::ffi::CString;
+        use std::os::raw::c_char;
+
+        const XATTR_NOFOLLOW: i32 = 0x0001;
+
+        extern "C" {
+            fn getxattr(path: *const c_char, name: *const c_char, value: *mut u8, size: usize, position: u32, options: i32) -> isize;
+        }
+
+        let (Ok(path_cstr), Ok(name_cstr)) = (
+            CString::new(path.to_string_lossy().as_bytes()),
+            CString::new(TIME_MACHINE_EXCLUDE_XATTR),
+        ) else {
+            return false;
+        };
+
+        // Only the presence of the attribute matters, not its value
+        let size = unsafe {
+            getxattr(path_cstr.as_ptr(), name_cstr.as_ptr(), std::ptr::null_mut(), 0, 0, XATTR_NOFOLLOW)
+        };
+        size >= 0
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Apply protection hints to a file holding sensitive data
+///
+/// Excludes the file from Time Machine (backup volumes are not necessarily
+/// encrypted the way a FileVault startup disk is) and hides it from Spotlight.
+pub fn set_file_protection(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let status = Command::new("tmutil").arg("addexclusion").arg(path).status()?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!("tmutil addexclusion failed for {}", path.display())));
+        }
+        set_extended_attributes(path, &[(SPOTLIGHT_EXCLUDE_XATTR.to_string(), b"1".to_vec())])
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Show `image` (PNG, JPEG or ICNS data) as the file's custom Finder icon
+///
+/// Uses `NSWorkspace setIcon:forFile:options:` through AppleScriptObjC.
+pub fn set_finder_icon(path: &Path, image: &[u8]) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        // NSWorkspace resolves relative paths against its own working directory
+        let path = std::fs::canonicalize(path)?;
+        let script = format!(
+            "use framework \"AppKit\"\n\
+             set imageData to current application's NSData's alloc()'s initWithBase64EncodedString:\"{}\" options:0\n\
+             set icon to current application's NSImage's alloc()'s initWithData:imageData\n\
+             if icon is missing value then error \"not an image\"\n\
+             if not (current application's NSWorkspace's sharedWorkspace()'s setIcon:icon forFile:\"{}\" options:0) then error \"setIcon failed\"",
+            crate::encoding::base64_encode(image),
+            applescript_escape(&path.to_string_lossy())
+        );
+
+        let status = Command::new("osascript").arg("-e").arg(script).status()?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!("Could not set the Finder icon of {}", path.display())));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (path, image);
+        Ok(())
+    }
+}
+
+/// Extended attribute keeping a file's contents out of the Spotlight index
+pub const SPOTLIGHT_EXCLUDE_XATTR: &str = "com.apple.metadata:com_apple_mdimporter_excludeItem";
+
+/// Whether FileVault is enabled on the startup disk, if `fdesetup` can tell
+pub fn is_filevault_enabled() -> Option<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        let output = Command::new("fdesetup").arg("status").output().ok()?;
+        let status = String::from_utf8_lossy(&output.stdout);
+        if status.contains("FileVault is On") {
+            Some(true)
+        } else if status.contains("FileVault is Off") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        None
+    }
+}
+
 /// Get macOS quarantine attribute status
 /// Files downloaded from the internet have the quarantine attribute set
 pub fn get_quarantine_status(path: &Path) -> std::io::Result<bool> {
@@ -204,6 +617,174 @@ pub fn set_quarantine_status(path: &Path, quarantined: bool) -> std::io::Result<
     }
 }
 
+/// Post a Notification Center banner via AppleScript
+pub fn post_notification(title: &str, message: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            applescript_escape(message),
+            applescript_escape(title)
+        );
+
+        let status = Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .status()?;
+
+        if !status.success() {
+            return Err(std::io::Error::other(format!("osascript exited with {}", status)));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (title, message);
+        Ok(())
+    }
+}
+
+/// Escape a string for use inside an AppleScript string literal
+#[cfg(target_os = "macos")]
+fn applescript_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Place text on the general pasteboard with `pbcopy`
+pub fn set_clipboard(text: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut child = Command::new("pbcopy").stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!("pbcopy exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = text;
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Clipboard access is not available on this platform"))
+    }
+}
+
+/// Read text from the general pasteboard with `pbpaste`
+pub fn get_clipboard() -> std::io::Result<String> {
+    #[cfg(target_os = "macos")]
+    {
+        let output = Command::new("pbpaste").output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!("pbpaste exited with {}", output.status)));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Clipboard does not hold text"))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Clipboard access is not available on this platform"))
+    }
+}
+
+/// Ask the kernel to read the whole file ahead (`F_RDADVISE`) since it
+/// will be read sequentially
+pub fn advise_sequential(file: &std::fs::File) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        const F_RDADVISE: i32 = 44;
+
+        #[repr(C)]
+        struct Radvisory {
+            ra_offset: i64,
+            ra_count: i32,
+        }
+
+        extern "C" {
+            fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+        }
+
+        let advice = Radvisory {
+            ra_offset: 0,
+            ra_count: file.metadata()?.len().min(i32::MAX as u64) as i32,
+        };
+        if unsafe { fcntl(file.as_raw_fd(), F_RDADVISE, &advice as *const Radvisory) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = file;
+        Ok(())
+    }
+}
+
+/// Reserve `len` bytes of disk space for a file about to be written
+/// (`F_PREALLOCATE`), contiguously if possible
+pub fn preallocate(file: &std::fs::File, len: u64) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        const F_PREALLOCATE: i32 = 42;
+        const F_ALLOCATECONTIG: u32 = 0x2;
+        const F_ALLOCATEALL: u32 = 0x4;
+        const F_PEOFPOSMODE: i32 = 3;
+
+        #[repr(C)]
+        struct Fstore {
+            fst_flags: u32,
+            fst_posmode: i32,
+            fst_offset: i64,
+            fst_length: i64,
+            fst_bytesalloc: i64,
+        }
+
+        extern "C" {
+            fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+        }
+
+        let length = i64::try_from(len)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "file too large to preallocate"))?;
+        let mut store = Fstore {
+            fst_flags: F_ALLOCATECONTIG | F_ALLOCATEALL,
+            fst_posmode: F_PEOFPOSMODE,
+            fst_offset: 0,
+            fst_length: length,
+            fst_bytesalloc: 0,
+        };
+
+        unsafe {
+            if fcntl(file.as_raw_fd(), F_PREALLOCATE, &mut store as *mut Fstore) == -1 {
+                // Fall back to space that need not be contiguous
+                store.fst_flags = F_ALLOCATEALL;
+                if fcntl(file.as_raw_fd(), F_PREALLOCATE, &mut store as *mut Fstore) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (file, len);
+        Ok(())
+    }
+}
+
 /// Get macOS version to determine feature availability
 pub fn get_macos_version() -> std::io::Result<(u32, u32, u32)> {
     #[cfg(target_os = "macos")]
@@ -245,12 +826,6 @@ pub fn is_apple_silicon() -> bool {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_register_file_type() {
-        // Should not panic
-        let _ = register_file_type();
-    }
-
     #[test]
     fn test_macos_version() {
         #[cfg(target_os = "macos")]
@@ -261,11 +836,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quick_action_workflow_generation() {
+        let glifzip = Path::new("/usr/local/bin/glifzip");
+
+        let plist = QuickAction::Extract.info_plist();
+        assert!(plist.contains("Extract here"));
+        assert!(plist.contains(GLIF_UTI));
+
+        let wflow = QuickAction::Compress.document_wflow(glifzip);
+        assert!(wflow.contains("'/usr/local/bin/glifzip' create"));
+        assert_eq!(wflow, QuickAction::Compress.document_wflow(glifzip));
+    }
+
+    #[test]
+    fn test_quick_action_quotes_glifzip_path() {
+        let glifzip = Path::new("/opt/it's \"$(x)\" `y` \\z/glifzip");
+
+        let command = QuickAction::Extract.shell_command(glifzip);
+        assert!(command.starts_with(
+            "for f in \"$@\"; do '/opt/it'\\''s \"$(x)\" `y` \\z/glifzip' extract"
+        ));
+    }
+
+    #[test]
+    fn test_uti_declaration_plist() {
+        let plist = uti_declaration_plist();
+        assert!(plist.contains("<string>glif</string>"));
+        assert!(plist.contains(BUNDLE_ID));
+    }
+
     #[test]
     fn test_is_apple_silicon() {
         let _ = is_apple_silicon();
     }
 
+    #[test]
+    fn test_time_machine_exclusion() {
+        use std::env::temp_dir;
+
+        let temp_file = temp_dir().join("test_tm_exclude.glif");
+        if std::fs::File::create(&temp_file).is_ok() {
+            assert!(!is_time_machine_excluded(&temp_file));
+
+            let tagged = Command::new("xattr")
+                .args(["-w", TIME_MACHINE_EXCLUDE_XATTR, "com.apple.backupd"])
+                .arg(&temp_file)
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if tagged {
+                assert!(is_time_machine_excluded(&temp_file));
+            }
+
+            let _ = std::fs::remove_file(&temp_file);
+        }
+    }
+
     #[test]
     fn test_quarantine_status() {
         use std::fs::File;
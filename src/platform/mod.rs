@@ -2,12 +2,18 @@
 //!
 //! This module provides cross-platform abstractions for OS-specific features:
 //! - macOS/Apple: Finder integration, file associations, extended attributes
-//! - Linux: Standard file operations
+//! - Linux: Standard file operations, D-Bus desktop notifications, clipboard tools,
+//!   read-ahead and preallocation hints
 //! - Windows: File association, context menu integration
 
 #[cfg(target_os = "macos")]
 pub mod macos;
 
+#[cfg(target_os = "linux")]
+pub mod linux;
+
+pub mod schedule;
+
 #[cfg(not(target_os = "macos"))]
 pub mod macos {
     // Stub for non-macOS platforms
@@ -30,6 +36,49 @@ pub mod macos {
     pub fn set_quarantine_status(_path: &std::path::Path, _quarantined: bool) -> std::io::Result<()> {
         Ok(())
     }
+
+    pub fn post_notification(_title: &str, _message: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn is_time_machine_excluded(_path: &std::path::Path) -> bool {
+        false
+    }
+
+    pub fn set_file_protection(_path: &std::path::Path) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn is_filevault_enabled() -> Option<bool> {
+        None
+    }
+
+    pub fn set_finder_icon(_path: &std::path::Path, _image: &[u8]) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn set_clipboard(_text: &str) -> std::io::Result<()> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Clipboard access is not available on this platform"))
+    }
+
+    pub fn get_clipboard() -> std::io::Result<String> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Clipboard access is not available on this platform"))
+    }
+
+    pub fn advise_sequential(_file: &std::fs::File) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn preallocate(_file: &std::fs::File, _len: u64) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub fn install_integration(_glifzip: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Finder integration is only available on macOS",
+        ))
+    }
 }
 
 /// Register GLIF file type with the operating system
@@ -37,6 +86,11 @@ pub fn register_glif_filetype() -> std::io::Result<()> {
     macos::register_file_type()
 }
 
+/// Install desktop integration (Finder Quick Actions and UTI registration on macOS)
+pub fn install_desktop_integration(glifzip: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+    macos::install_integration(glifzip)
+}
+
 /// Get extended file attributes (macOS xattr)
 pub fn get_file_attributes(path: &std::path::Path) -> std::io::Result<Vec<(String, Vec<u8>)>> {
     macos::get_extended_attributes(path)
@@ -47,6 +101,60 @@ pub fn set_file_attributes(path: &std::path::Path, attrs: &[(String, Vec<u8>)])
     macos::set_extended_attributes(path, attrs)
 }
 
+/// Check if a path is excluded from Time Machine backups (always false off macOS)
+pub fn is_backup_excluded(path: &std::path::Path) -> bool {
+    macos::is_time_machine_excluded(path)
+}
+
+/// Protect a file holding sensitive data
+/// Restricts it to its owner on Unix and applies backup and indexing exclusions on macOS
+pub fn protect_file(path: &std::path::Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    macos::set_file_protection(path)
+}
+
+/// Show `image` as the file's icon (a custom Finder icon on macOS; a no-op elsewhere)
+pub fn set_file_icon(path: &std::path::Path, image: &[u8]) -> std::io::Result<()> {
+    macos::set_finder_icon(path, image)
+}
+
+/// Whether the disk holding user data is encrypted at rest (FileVault on macOS), if known
+pub fn is_disk_encrypted() -> Option<bool> {
+    macos::is_filevault_enabled()
+}
+
+/// Memory available to the process in bytes, if the OS reports it
+/// Uses MemAvailable on Linux and physical memory on macOS
+pub fn available_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        meminfo.lines()
+            .find_map(|line| line.strip_prefix("MemAvailable:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("sysctl")
+            .args(["-n", "hw.memsize"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        None
+    }
+}
+
 /// Check if file has macOS quarantine attribute
 pub fn is_quarantined(path: &std::path::Path) -> std::io::Result<bool> {
     macos::get_quarantine_status(path)
@@ -57,15 +165,177 @@ pub fn set_quarantine(path: &std::path::Path, quarantined: bool) -> std::io::Res
     macos::set_quarantine_status(path, quarantined)
 }
 
+/// Place text on the system clipboard
+/// Uses pbcopy on macOS and wl-copy, xclip or xsel on Linux
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::set_clipboard(text)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        macos::set_clipboard(text)
+    }
+}
+
+/// Read text from the system clipboard
+pub fn paste_from_clipboard() -> std::io::Result<String> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::get_clipboard()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        macos::get_clipboard()
+    }
+}
+
+/// Hint that a file is about to be read sequentially from start to end
+/// Uses `posix_fadvise` on Linux and `F_RDADVISE` on macOS; a no-op elsewhere
+pub fn advise_sequential_read(file: &std::fs::File) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::advise_sequential(file)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        macos::advise_sequential(file)
+    }
+}
+
+/// Reserve disk space for a file about to be written with `len` bytes,
+/// reducing fragmentation; uses `fallocate` on Linux and `F_PREALLOCATE` on macOS
+pub fn preallocate(file: &std::fs::File, len: u64) -> std::io::Result<()> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::preallocate(file, len)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        macos::preallocate(file, len)
+    }
+}
+
+/// Show a native desktop notification
+/// Uses Notification Center on macOS and D-Bus on Linux; a no-op elsewhere
+pub fn notify(title: &str, message: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::send_notification(title, message)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        macos::post_notification(title, message)
+    }
+}
+
+/// Mode, owner and group of `metadata` as Unix records them
+///
+/// Elsewhere the mode is made up from the file type and read-only flag,
+/// owned by 0:0.
+pub fn unix_metadata(metadata: &std::fs::Metadata) -> (u32, u32, u32) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        (metadata.permissions().mode(), metadata.uid(), metadata.gid())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let (type_bits, permissions) = if metadata.is_symlink() {
+            (0o120000, 0o777)
+        } else if metadata.is_dir() {
+            (0o040000, 0o755)
+        } else {
+            (0o100000, 0o644)
+        };
+        let permissions = if metadata.permissions().readonly() { permissions & !0o222 } else { permissions };
+        (type_bits | permissions, 0, 0)
+    }
+}
+
+/// Set the permission bits of `path` to those of `mode`
+/// Off Unix only whether the owner may write is kept, as the read-only flag
+pub fn set_mode(path: &std::path::Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        std::fs::set_permissions(path, permissions)
+    }
+}
+
+/// Create a symlink at `link` pointing to `target`
+/// Fails with `Unsupported` on platforms without Unix symlinks
+pub fn symlink(target: &std::path::Path, link: &std::path::Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (target, link);
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Symlinks are not supported on this platform"))
+    }
+}
+
+/// OS string of a name stored as bytes in an archive or image
+/// Unix takes any bytes; elsewhere they must be UTF-8
+pub fn os_str_from_bytes(bytes: &[u8]) -> std::io::Result<&std::ffi::OsStr> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(std::ffi::OsStr::from_bytes(bytes))
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes)
+            .map(std::ffi::OsStr::new)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Name is not UTF-8: {}", e)))
+    }
+}
+
+/// Escape text for an XML element or attribute value, as in plists and workflows
+pub(crate) fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
+    #[ignore = "installs a bundle under ~/Applications and runs lsregister on macOS"]
     fn test_register_filetype() {
         assert!(register_glif_filetype().is_ok());
     }
 
+    #[test]
+    fn test_available_memory() {
+        #[cfg(target_os = "linux")]
+        assert!(available_memory().unwrap_or(0) > 0);
+    }
+
     #[test]
     fn test_file_attributes() {
         use std::env::temp_dir;
@@ -80,4 +350,40 @@ mod tests {
 
         let _ = std::fs::remove_file(&temp_file);
     }
+
+    #[test]
+    fn test_protect_file() {
+        let temp_file = std::env::temp_dir().join(format!("test_protect_{}.glif", std::process::id()));
+        std::fs::write(&temp_file, b"secret").unwrap();
+
+        protect_file(&temp_file).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&temp_file).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let _ = std::fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_access_hints() {
+        use std::io::{Read, Write};
+
+        let temp_file = std::env::temp_dir().join(format!("test_hints_{}.glif", std::process::id()));
+        let data = vec![7u8; 100_000];
+
+        let mut file = std::fs::File::create(&temp_file).unwrap();
+        preallocate(&file, data.len() as u64).unwrap();
+        file.write_all(&data).unwrap();
+        drop(file);
+
+        let mut file = std::fs::File::open(&temp_file).unwrap();
+        advise_sequential_read(&file).unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        let _ = std::fs::remove_file(&temp_file);
+    }
 }
@@ -0,0 +1,387 @@
+//! Scheduled backup jobs
+//!
+//! Generates and loads a launchd agent (macOS) or a systemd user timer
+//! (Linux) that runs glifzip daily at a fixed time:
+//! - macOS: ~/Library/LaunchAgents/com.glyphos.glifzip.<profile>.plist
+//! - Linux: ~/.config/systemd/user/glifzip-<profile>.{service,timer}
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::xml_escape;
+
+/// launchd label prefix for scheduled jobs
+pub const LAUNCHD_LABEL_PREFIX: &str = "com.glyphos.glifzip.";
+
+/// systemd unit name prefix for scheduled jobs
+pub const SYSTEMD_UNIT_PREFIX: &str = "glifzip-";
+
+/// Time of day at which a job runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl DailyTime {
+    /// Parse a "HH:MM" 24-hour time
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid time '{}': expected HH:MM (24-hour)", s)
+        );
+
+        let (hour, minute) = s.split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+
+        Ok(Self { hour, minute })
+    }
+}
+
+impl std::fmt::Display for DailyTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// A backup profile scheduled to run daily
+#[derive(Debug, Clone)]
+pub struct ScheduledJob {
+    /// Profile name, used to derive the launchd label / systemd unit name
+    pub profile: String,
+
+    /// Time of day to run
+    pub time: DailyTime,
+
+    /// Path to the glifzip executable
+    pub program: PathBuf,
+
+    /// Arguments passed to glifzip when the job runs
+    pub args: Vec<String>,
+}
+
+impl ScheduledJob {
+    pub fn new(profile: &str, time: DailyTime, program: PathBuf, args: Vec<String>) -> Result<Self> {
+        validate_profile_name(profile)?;
+        Ok(Self {
+            profile: profile.to_string(),
+            time,
+            program,
+            args,
+        })
+    }
+
+    /// launchd label for this job
+    pub fn launchd_label(&self) -> String {
+        format!("{}{}", LAUNCHD_LABEL_PREFIX, self.profile)
+    }
+
+    /// systemd unit base name for this job (without suffix)
+    pub fn systemd_unit(&self) -> String {
+        format!("{}{}", SYSTEMD_UNIT_PREFIX, self.profile)
+    }
+
+    /// Generate the launchd agent plist
+    pub fn launchd_plist(&self) -> String {
+        let mut arguments = format!("\t\t<string>{}</string>\n", xml_escape(&self.program.to_string_lossy()));
+        for arg in &self.args {
+            arguments.push_str(&format!("\t\t<string>{}</string>\n", xml_escape(arg)));
+        }
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>{label}</string>
+	<key>ProgramArguments</key>
+	<array>
+{arguments}	</array>
+	<key>StartCalendarInterval</key>
+	<dict>
+		<key>Hour</key>
+		<integer>{hour}</integer>
+		<key>Minute</key>
+		<integer>{minute}</integer>
+	</dict>
+	<key>LowPriorityIO</key>
+	<true/>
+	<key>Nice</key>
+	<integer>10</integer>
+</dict>
+</plist>
+"#,
+            label = self.launchd_label(),
+            arguments = arguments,
+            hour = self.time.hour,
+            minute = self.time.minute,
+        )
+    }
+
+    /// Generate the systemd service unit
+    pub fn systemd_service(&self) -> String {
+        let mut exec = systemd_quote(&self.program.to_string_lossy());
+        for arg in &self.args {
+            exec.push(' ');
+            exec.push_str(&systemd_quote(arg));
+        }
+
+        format!(
+            "[Unit]\n\
+             Description=glifzip backup profile '{profile}'\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart={exec}\n\
+             Nice=10\n\
+             IOSchedulingClass=idle\n",
+            profile = self.profile,
+            exec = exec,
+        )
+    }
+
+    /// Generate the systemd timer unit
+    pub fn systemd_timer(&self) -> String {
+        format!(
+            "[Unit]\n\
+             Description=Daily glifzip backup profile '{profile}'\n\
+             \n\
+             [Timer]\n\
+             OnCalendar=*-*-* {time}:00\n\
+             Persistent=true\n\
+             \n\
+             [Install]\n\
+             WantedBy=timers.target\n",
+            profile = self.profile,
+            time = self.time,
+        )
+    }
+
+    /// Write and load the job for the current platform
+    /// Returns the paths of the files written
+    pub fn install(&self) -> Result<Vec<PathBuf>> {
+        if cfg!(target_os = "macos") {
+            let dir = launch_agents_dir()?;
+            fs::create_dir_all(&dir)?;
+            let plist = dir.join(format!("{}.plist", self.launchd_label()));
+            fs::write(&plist, self.launchd_plist())?;
+
+            // Reload if a previous version of the job is loaded
+            let _ = Command::new("launchctl").arg("unload").arg(&plist).output();
+            run("launchctl", &["load", "-w", &plist.to_string_lossy()])?;
+
+            Ok(vec![plist])
+        } else if cfg!(target_os = "linux") {
+            let dir = systemd_user_dir()?;
+            fs::create_dir_all(&dir)?;
+            let unit = self.systemd_unit();
+            let service = dir.join(format!("{}.service", unit));
+            let timer = dir.join(format!("{}.timer", unit));
+            fs::write(&service, self.systemd_service())?;
+            fs::write(&timer, self.systemd_timer())?;
+
+            run("systemctl", &["--user", "daemon-reload"])?;
+            run("systemctl", &["--user", "enable", "--now", &format!("{}.timer", unit)])?;
+
+            Ok(vec![service, timer])
+        } else {
+            Err(unsupported())
+        }
+    }
+}
+
+/// List the profiles that currently have a scheduled job installed
+pub fn list() -> Result<Vec<String>> {
+    let (dir, prefix, suffix) = if cfg!(target_os = "macos") {
+        (launch_agents_dir()?, LAUNCHD_LABEL_PREFIX, ".plist")
+    } else if cfg!(target_os = "linux") {
+        (systemd_user_dir()?, SYSTEMD_UNIT_PREFIX, ".timer")
+    } else {
+        return Err(unsupported());
+    };
+
+    Ok(profiles_in(&dir, prefix, suffix))
+}
+
+/// Unload and remove the scheduled job for a profile
+pub fn remove(profile: &str) -> Result<()> {
+    validate_profile_name(profile)?;
+
+    let not_scheduled = || Error::new(
+        ErrorKind::NotFound,
+        format!("No scheduled job for profile '{}'", profile)
+    );
+
+    if cfg!(target_os = "macos") {
+        let plist = launch_agents_dir()?.join(format!("{}{}.plist", LAUNCHD_LABEL_PREFIX, profile));
+        if !plist.exists() {
+            return Err(not_scheduled());
+        }
+        let _ = Command::new("launchctl").arg("unload").arg("-w").arg(&plist).output();
+        fs::remove_file(&plist)?;
+        Ok(())
+    } else if cfg!(target_os = "linux") {
+        let dir = systemd_user_dir()?;
+        let unit = format!("{}{}", SYSTEMD_UNIT_PREFIX, profile);
+        let timer = dir.join(format!("{}.timer", unit));
+        if !timer.exists() {
+            return Err(not_scheduled());
+        }
+        let _ = Command::new("systemctl")
+            .args(["--user", "disable", "--now", &format!("{}.timer", unit)])
+            .output();
+        fs::remove_file(&timer)?;
+        let _ = fs::remove_file(dir.join(format!("{}.service", unit)));
+        let _ = Command::new("systemctl").args(["--user", "daemon-reload"]).output();
+        Ok(())
+    } else {
+        Err(unsupported())
+    }
+}
+
+fn profiles_in(dir: &Path, prefix: &str, suffix: &str) -> Vec<String> {
+    let mut profiles: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter_map(|name| {
+                    name.strip_prefix(prefix)
+                        .and_then(|rest| rest.strip_suffix(suffix))
+                        .map(|profile| profile.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles
+}
+
+fn validate_profile_name(profile: &str) -> Result<()> {
+    let valid = !profile.is_empty()
+        && profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid profile name '{}': use letters, digits, '-' and '_'", profile)
+        ))
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))
+}
+
+fn launch_agents_dir() -> Result<PathBuf> {
+    Ok(home_dir()?.join("Library").join("LaunchAgents"))
+}
+
+fn systemd_user_dir() -> Result<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".config"),
+    };
+    Ok(config.join("systemd").join("user"))
+}
+
+fn unsupported() -> Error {
+    Error::new(ErrorKind::Unsupported, "Scheduling is only supported on macOS (launchd) and Linux (systemd)")
+}
+
+/// Quote a word of an `ExecStart=` line; systemd reads C escapes inside
+/// double quotes and expands `%` specifiers and `$` variables everywhere
+fn systemd_quote(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '%' | '$')) {
+        return s.to_string();
+    }
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\'', "\\'")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> ScheduledJob {
+        ScheduledJob::new(
+            "documents",
+            DailyTime::parse("02:00").unwrap(),
+            PathBuf::from("/usr/local/bin/glifzip"),
+            vec!["create".to_string(), "/home/user/My Documents".to_string(), "-o".to_string(), "/backups/docs.glif".to_string()],
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_daily_time_parse() {
+        assert_eq!(DailyTime::parse("02:00").unwrap(), DailyTime { hour: 2, minute: 0 });
+        assert_eq!(DailyTime::parse("23:59").unwrap().to_string(), "23:59");
+        assert!(DailyTime::parse("24:00").is_err());
+        assert!(DailyTime::parse("2pm").is_err());
+    }
+
+    #[test]
+    fn test_profile_name_validation() {
+        assert!(ScheduledJob::new("../evil", DailyTime { hour: 0, minute: 0 }, PathBuf::new(), vec![]).is_err());
+        assert!(ScheduledJob::new("", DailyTime { hour: 0, minute: 0 }, PathBuf::new(), vec![]).is_err());
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let plist = job().launchd_plist();
+        assert!(plist.contains("<string>com.glyphos.glifzip.documents</string>"));
+        assert!(plist.contains("<string>/home/user/My Documents</string>"));
+        assert!(plist.contains("<key>Hour</key>\n\t\t<integer>2</integer>"));
+    }
+
+    #[test]
+    fn test_systemd_units() {
+        let job = job();
+        let service = job.systemd_service();
+        assert!(service.contains("ExecStart=/usr/local/bin/glifzip create \"/home/user/My Documents\" -o /backups/docs.glif"));
+
+        let timer = job.systemd_timer();
+        assert!(timer.contains("OnCalendar=*-*-* 02:00:00"));
+        assert_eq!(job.systemd_unit(), "glifzip-documents");
+    }
+
+    #[test]
+    fn test_systemd_quote() {
+        assert_eq!(systemd_quote("/backups/docs.glif"), "/backups/docs.glif");
+        assert_eq!(systemd_quote("/home/user/Bob's Files"), "\"/home/user/Bob\\'s Files\"");
+        assert_eq!(systemd_quote("it's"), "\"it\\'s\"");
+        assert_eq!(systemd_quote("C:\\backup"), "\"C:\\\\backup\"");
+        assert_eq!(systemd_quote("100%$HOME"), "\"100%%$$HOME\"");
+        assert_eq!(systemd_quote(""), "\"\"");
+    }
+}
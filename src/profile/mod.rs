@@ -0,0 +1,108 @@
+//! Named settings profiles
+//!
+//! Profiles live under the `profiles` key of the user config file (the
+//! file `glifzip tune` writes, see `tune::user_config_path`), each a
+//! `DirectoryCompressionConfig` object whose missing fields take their
+//! defaults:
+//!
+//! ```json
+//! {"profiles": {"backup-nightly": {"compression": {"level": 19, "threads": 4}, "exclude_caches": true}}}
+//! ```
+//!
+//! `glifzip create --profile backup-nightly` starts from a profile's
+//! settings; single files use only its `compression` settings.
+
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::tune::{read_user_config, user_config_path};
+use crate::DirectoryCompressionConfig;
+
+/// Key of the profiles in the user config file
+pub const PROFILES_KEY: &str = "profiles";
+
+/// Names of the profiles in the user config file at `path`
+pub fn profile_names(path: &Path) -> Result<Vec<String>> {
+    let config = read_user_config(path)?.unwrap_or_default();
+    match config.get(PROFILES_KEY) {
+        None => Ok(Vec::new()),
+        Some(serde_json::Value::Object(profiles)) => Ok(profiles.keys().cloned().collect()),
+        Some(_) => Err(invalid_profiles(path, format!("`{}` must be an object", PROFILES_KEY))),
+    }
+}
+
+/// Read and validate the profile `name` from the user config file at `path`
+pub fn load_profile(path: &Path, name: &str) -> Result<DirectoryCompressionConfig> {
+    let mut config = read_user_config(path)?.unwrap_or_default();
+    let profile = match config.get_mut(PROFILES_KEY) {
+        Some(serde_json::Value::Object(profiles)) => profiles.remove(name),
+        Some(_) => return Err(invalid_profiles(path, format!("`{}` must be an object", PROFILES_KEY))),
+        None => None,
+    };
+
+    let profile = profile.ok_or_else(|| {
+        let names = profile_names(path).unwrap_or_default();
+        let available = if names.is_empty() { "none".to_string() } else { names.join(", ") };
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no profile '{}' in {} (available: {})", name, path.display(), available)
+        )
+    })?;
+
+    let profile: DirectoryCompressionConfig = serde_json::from_value(profile)
+        .map_err(|e| invalid_profiles(path, format!("profile '{}': {}", name, e)))?;
+    profile.compression.validate()
+        .map_err(|e| invalid_profiles(path, format!("profile '{}': {}", name, e)))?;
+    Ok(profile)
+}
+
+/// Read the profile `name` from the user config file
+pub fn load_user_profile(name: &str) -> Result<DirectoryCompressionConfig> {
+    load_profile(&user_config_path()?, name)
+}
+
+fn invalid_profiles(path: &Path, message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_load_profile() {
+        crate::testing::with_temp_dir("profile", |dir| {
+            let path = dir.join("config.json");
+            assert!(profile_names(&path)?.is_empty());
+
+            fs::write(&path, br#"{
+                "tuned": {"ignored": true},
+                "profiles": {
+                    "backup-nightly": {
+                        "compression": {"level": 19, "threads": 4, "max_memory": 1073741824},
+                        "exclude_patterns": ["*.tmp"],
+                        "exclude_caches": true,
+                        "chunk_cache": {"root": "/var/cache/glifzip"}
+                    },
+                    "broken": {"compression": {"level": 99}}
+                }
+            }"#)?;
+            assert_eq!(profile_names(&path)?, ["backup-nightly", "broken"]);
+
+            let profile = load_profile(&path, "backup-nightly")?;
+            assert_eq!((profile.compression.level, profile.compression.threads), (19, 4));
+            assert_eq!(profile.compression.max_memory, Some(1 << 30));
+            assert_eq!(profile.compression.chunk_size, crate::CHUNK_SIZE);
+            assert_eq!(profile.exclude_patterns, ["*.tmp"]);
+            assert!(profile.exclude_caches && profile.hash_files && !profile.salvage);
+            assert_eq!(profile.chunk_cache.unwrap().root(), Path::new("/var/cache/glifzip"));
+
+            let err = load_profile(&path, "weekly").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::NotFound);
+            assert!(err.to_string().contains("available: backup-nightly, broken"));
+            assert_eq!(load_profile(&path, "broken").unwrap_err().kind(), ErrorKind::InvalidData);
+            Ok(())
+        }).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+//! Colored console output
+//!
+//! `glifzip --color auto|always|never` picks whether results are colored:
+//! green for what checked out, red for failures, dimmed metadata. `auto`
+//! colors only when stdout is a terminal and `NO_COLOR` is unset or empty;
+//! plain mode never colors. Text is padded with `pad` before it is painted,
+//! so escape codes don't upset column alignment.
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use super::is_plain;
+
+/// When to color console output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub const ALL: [ColorChoice; 3] = [ColorChoice::Auto, ColorChoice::Always, ColorChoice::Never];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ColorChoice::ALL.into_iter()
+            .find(|choice| choice.name() == s)
+            .ok_or_else(|| format!("unknown color choice '{}' (expected auto, always or never)", s))
+    }
+}
+
+/// How a piece of output is painted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Passed checks and added entries
+    Ok,
+    /// Failures and removed entries
+    Error,
+    /// Warnings and modified entries
+    Warning,
+    /// Metadata next to the main value
+    Dim,
+    Bold,
+}
+
+impl Style {
+    fn code(&self) -> &'static str {
+        match self {
+            Style::Ok => "32",
+            Style::Error => "31",
+            Style::Warning => "33",
+            Style::Dim => "2",
+            Style::Bold => "1",
+        }
+    }
+}
+
+static COLOR: AtomicU8 = AtomicU8::new(0);
+
+/// Choose when console output is colored, including progress bars on stderr
+pub fn set_color(choice: ColorChoice) {
+    COLOR.store(choice as u8, Ordering::Relaxed);
+    #[cfg(feature = "cli")]
+    console::set_colors_enabled_stderr(match choice {
+        ColorChoice::Auto => console::colors_enabled_stderr() && !no_color(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    } && !is_plain());
+}
+
+/// The choice made with `set_color`
+pub fn color_choice() -> ColorChoice {
+    ColorChoice::ALL[COLOR.load(Ordering::Relaxed) as usize]
+}
+
+/// Whether stdout is colored
+pub fn colors_enabled() -> bool {
+    !is_plain() && match color_choice() {
+        ColorChoice::Auto => !no_color() && std::io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+/// `NO_COLOR` is set to a non-empty value (https://no-color.org)
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// `text` in `style` if colors are enabled
+pub fn paint<T: fmt::Display>(text: T, style: Style) -> String {
+    paint_if(colors_enabled(), text, style)
+}
+
+/// `text` in `style` if `enabled`, for output rendered before it is printed
+pub fn paint_if<T: fmt::Display>(enabled: bool, text: T, style: Style) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` left-aligned (right-aligned if `right`) in a column `width` characters wide
+pub fn pad<T: fmt::Display>(text: T, width: usize, right: bool) -> String {
+    if right {
+        format!("{:>width$}", text.to_string())
+    } else {
+        format!("{:<width$}", text.to_string())
+    }
+}
+
+/// Width of the widest of `values` as displayed
+pub fn column_width<T: fmt::Display>(values: impl IntoIterator<Item = T>) -> usize {
+    values.into_iter().map(|value| value.to_string().chars().count()).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice_and_paint() {
+        for choice in ColorChoice::ALL {
+            assert_eq!(choice.name().parse::<ColorChoice>().unwrap(), choice);
+        }
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+
+        assert_eq!(paint_if(true, "ok", Style::Ok), "\x1b[32mok\x1b[0m");
+        assert_eq!(paint_if(false, "ok", Style::Ok), "ok");
+
+        // Padding happens before painting, so columns line up either way
+        assert_eq!(pad(42, 5, true), "   42");
+        assert_eq!(pad("ab", 4, false), "ab  ");
+        assert_eq!(column_width([7, 1234, 56]), 4);
+    }
+}
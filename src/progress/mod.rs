@@ -0,0 +1,261 @@
+//! Progress reporting for long-running operations
+//!
+//! Operations such as verification run as a sequence of named stages
+//! ("header", "archive hash", ...). Stages that process data report how
+//! many of their bytes are done, so multi-gigabyte archives show steady
+//! progress instead of appearing to hang.
+//!
+//! Per-item work, such as the files `DirectoryCompressor` archives or
+//! extracts, is reported through `ItemProgress` instead.
+//!
+//! All progress bars and spinners are created through `bar` and
+//! `spinner`, so `set_plain` (`glifzip --plain`) can replace them with
+//! plain sequential lines that screen readers announce in order.
+//! Results printed to stdout are colored through `color`, which plain
+//! mode also turns off. Drawing bars takes the `cli` feature; library-only
+//! builds report progress only to the traits' own implementations.
+
+pub mod color;
+
+pub use color::{paint, set_color, ColorChoice, Style};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "cli")]
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Template of item-count bars, e.g. files archived or extracted
+pub const ITEM_BAR_TEMPLATE: &str = "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}";
+
+/// Template of byte-count bars for the stages of `ConsoleProgress`
+pub const BYTE_BAR_TEMPLATE: &str = "  {msg} [{bar:40.cyan/blue}] {percent}% ({bytes}/{total_bytes}, {eta})";
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switch console output to plain sequential text: no progress bars, spinners or colors
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// Whether console output is plain text
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// A progress bar to `total` drawn with `template`; hidden in plain mode
+#[cfg(feature = "cli")]
+pub fn bar(total: u64, template: &str) -> ProgressBar {
+    if is_plain() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total);
+    bar.set_style(ProgressStyle::default_bar().template(template).unwrap().progress_chars("#>-"));
+    bar
+}
+
+/// A spinner for unmeasured work; hidden in plain mode
+#[cfg(feature = "cli")]
+pub fn spinner() -> ProgressBar {
+    if is_plain() {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new_spinner()
+}
+
+/// Receives stage-by-stage progress
+pub trait ProgressReporter {
+    /// A stage started; `total` is the bytes it will process, or 0 if it is not measured
+    fn start_stage(&mut self, stage: &str, total: u64);
+
+    /// `done` bytes of the current stage are complete
+    fn advance(&mut self, done: u64);
+
+    /// The current stage completed successfully
+    fn finish_stage(&mut self);
+}
+
+/// Receives item-by-item progress, such as files archived or extracted
+pub trait ItemProgress {
+    /// Work on the item called `name` started
+    fn start_item(&mut self, name: &str);
+
+    /// The current item is done, successfully or not
+    fn finish_item(&mut self);
+
+    /// All items are done
+    fn finish(&mut self);
+}
+
+/// Reporter that ignores all progress
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn start_stage(&mut self, _stage: &str, _total: u64) {}
+
+    fn advance(&mut self, _done: u64) {}
+
+    fn finish_stage(&mut self) {}
+}
+
+impl ItemProgress for NoProgress {
+    fn start_item(&mut self, _name: &str) {}
+
+    fn finish_item(&mut self) {}
+
+    fn finish(&mut self) {}
+}
+
+/// Progress over `total` items for the console: an item bar with the `cli`
+/// feature if `show` is set, otherwise nothing
+pub fn item_progress(total: u64, show: bool) -> Box<dyn ItemProgress> {
+    #[cfg(feature = "cli")]
+    if show {
+        return Box::new(bar(total, ITEM_BAR_TEMPLATE));
+    }
+    let _ = (total, show);
+    Box::new(NoProgress)
+}
+
+#[cfg(feature = "cli")]
+impl ItemProgress for ProgressBar {
+    fn start_item(&mut self, name: &str) {
+        self.set_message(name.to_string());
+    }
+
+    fn finish_item(&mut self) {
+        self.inc(1);
+    }
+
+    fn finish(&mut self) {
+        self.finish_with_message("Done");
+    }
+}
+
+/// Reporter for terminals: a progress bar for measured stages and a line per completed stage
+///
+/// Bars are drawn on stderr and hidden when it is not a terminal; the
+/// completed-stage lines always go to stdout. In plain mode each stage
+/// instead prints a line when it starts.
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub struct ConsoleProgress {
+    current: Option<(String, ProgressBar)>,
+    stage_lines: bool,
+}
+
+#[cfg(feature = "cli")]
+impl Default for ConsoleProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ConsoleProgress {
+    pub fn new() -> Self {
+        Self { current: None, stage_lines: true }
+    }
+
+    /// Show progress bars without the completed-stage lines, e.g. when
+    /// the caller prints its own line per item
+    pub fn without_stage_lines() -> Self {
+        Self { current: None, stage_lines: false }
+    }
+
+    fn clear(&mut self) {
+        if let Some((_, bar)) = self.current.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ProgressReporter for ConsoleProgress {
+    fn start_stage(&mut self, stage: &str, total: u64) {
+        self.clear();
+
+        if is_plain() && self.stage_lines {
+            println!("  {}...", stage);
+        }
+        let bar = if total > 0 { bar(total, BYTE_BAR_TEMPLATE) } else { spinner() };
+        bar.set_message(stage.to_string());
+        self.current = Some((stage.to_string(), bar));
+    }
+
+    fn advance(&mut self, done: u64) {
+        if let Some((_, ref bar)) = self.current {
+            bar.set_position(done);
+        }
+    }
+
+    fn finish_stage(&mut self) {
+        if let Some((stage, bar)) = self.current.take() {
+            bar.finish_and_clear();
+            if self.stage_lines {
+                println!("  {}: {}", stage, paint("ok", Style::Ok));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl Drop for ConsoleProgress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Records every call, for checking what an operation reports
+    #[derive(Debug, Default)]
+    pub(crate) struct RecordingProgress {
+        pub events: Vec<String>,
+    }
+
+    impl ProgressReporter for RecordingProgress {
+        fn start_stage(&mut self, stage: &str, total: u64) {
+            self.events.push(format!("start {} {}", stage, total));
+        }
+
+        fn advance(&mut self, done: u64) {
+            self.events.push(format!("advance {}", done));
+        }
+
+        fn finish_stage(&mut self) {
+            self.events.push("finish".to_string());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_console_progress_stages() {
+        let mut progress = ConsoleProgress::new();
+        progress.start_stage("archive hash", 100);
+        progress.advance(50);
+        progress.finish_stage();
+        assert!(progress.current.is_none());
+
+        // An unfinished stage is cleared when the next one starts
+        progress.start_stage("header", 0);
+        progress.start_stage("sidecar", 0);
+        assert_eq!(progress.current.as_ref().unwrap().0, "sidecar");
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_plain_mode_hides_bars() {
+        set_plain(true);
+        assert!(bar(10, ITEM_BAR_TEMPLATE).is_hidden() && spinner().is_hidden());
+
+        let mut progress = ConsoleProgress::new();
+        progress.start_stage("archive hash", 100);
+        assert!(progress.current.as_ref().unwrap().1.is_hidden());
+        progress.finish_stage();
+        set_plain(false);
+        assert!(!is_plain());
+    }
+}
@@ -0,0 +1,258 @@
+//! Python bindings (the `python` feature)
+//!
+//! A PyO3 extension module named `glifzip`, so data pipelines can read and
+//! write archives without shelling out to the CLI:
+//!
+//! ```python
+//! import glifzip
+//! archive = glifzip.compress_bytes(b"payload", level=8)
+//! assert glifzip.decompress_bytes(archive) == b"payload"
+//! glifzip.compress_directory("data", "data.glif")
+//! manifest = glifzip.ArchiveManifest.read("data.glif")
+//! print([entry.path for entry in manifest.entries])
+//! ```
+//!
+//! The GIL is released while compressing and decompressing. Invalid
+//! arguments raise `ValueError`; everything else raises `OSError` (or the
+//! subclass matching the error, e.g. `FileNotFoundError`).
+
+// The pyfunction and pymethods macros convert results into `PyResult` themselves
+#![allow(clippy::useless_conversion)]
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::archive::file_entry::FileType;
+use crate::archive::{self, DirectoryCompressor};
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::{CompressionConfig, DecompressionConfig, GlifError, DEFAULT_COMPRESSION_LEVEL};
+
+/// Raise invalid arguments as `ValueError` and everything else as `OSError`
+fn to_py_err(error: GlifError) -> PyErr {
+    match error {
+        GlifError::Config(_) | GlifError::InvalidInput(_) => PyValueError::new_err(error.to_string()),
+        e => std::io::Error::from(e).into(),
+    }
+}
+
+fn compression_config(level: i32, threads: usize) -> PyResult<CompressionConfig> {
+    CompressionConfig::builder()
+        .level(level)
+        .threads(threads)
+        .build()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Compress `data` into a GLIF archive; `threads=0` uses all cores
+#[pyfunction]
+#[pyo3(signature = (data, level = DEFAULT_COMPRESSION_LEVEL, threads = 0))]
+fn compress_bytes<'py>(py: Python<'py>, data: &[u8], level: i32, threads: usize) -> PyResult<Bound<'py, PyBytes>> {
+    let config = compression_config(level, threads)?;
+    let archive = py.allow_threads(|| crate::compress(data, &config)).map_err(to_py_err)?;
+    Ok(PyBytes::new_bound(py, &archive))
+}
+
+/// Decompress a GLIF archive, checking its hashes unless `verify=False`
+#[pyfunction]
+#[pyo3(signature = (archive, threads = 0, verify = true))]
+fn decompress_bytes<'py>(py: Python<'py>, archive: &[u8], threads: usize, verify: bool) -> PyResult<Bound<'py, PyBytes>> {
+    let config = DecompressionConfig::new().with_threads(threads).with_verify_hashes(verify);
+    let payload = py.allow_threads(|| crate::decompress_with(archive, &config)).map_err(to_py_err)?;
+    Ok(PyBytes::new_bound(py, &payload))
+}
+
+/// Compress the directory at `directory` into a GLIF archive at `output`
+#[pyfunction]
+#[pyo3(signature = (directory, output, level = DEFAULT_COMPRESSION_LEVEL, threads = 0, exclude = Vec::new()))]
+fn compress_directory(
+    py: Python<'_>,
+    directory: PathBuf,
+    output: PathBuf,
+    level: i32,
+    threads: usize,
+    exclude: Vec<String>,
+) -> PyResult<()> {
+    let config = DirectoryCompressionConfig::new(compression_config(level, threads)?)
+        .with_exclude_patterns(exclude)
+        .with_progress(false);
+    py.allow_threads(|| {
+        DirectoryCompressor::new(config)?.compress_directory(&directory, &output)
+    }).map_err(to_py_err)
+}
+
+/// The manifest of a directory archive
+#[pyclass(name = "ArchiveManifest", module = "glifzip", frozen)]
+struct PyArchiveManifest {
+    manifest: archive::ArchiveManifest,
+}
+
+#[pymethods]
+impl PyArchiveManifest {
+    /// Read the manifest at the start of the directory archive at `path`, without decompressing it
+    #[staticmethod]
+    fn read(path: PathBuf) -> PyResult<Self> {
+        let file = File::open(&path)?;
+        let manifest = archive::ArchiveManifest::read_checked(&mut BufReader::new(file)).map_err(to_py_err)?;
+        Ok(Self { manifest })
+    }
+
+    /// Read the manifest of a directory archive held in memory
+    #[staticmethod]
+    fn from_bytes(archive: &[u8]) -> PyResult<Self> {
+        let manifest = archive::ArchiveManifest::read_checked(&mut &archive[..]).map_err(to_py_err)?;
+        Ok(Self { manifest })
+    }
+
+    #[getter]
+    fn entries(&self) -> Vec<PyFileEntry> {
+        self.manifest.entries.iter().cloned().map(|entry| PyFileEntry { entry }).collect()
+    }
+
+    #[getter]
+    fn file_count(&self) -> usize {
+        self.manifest.file_count
+    }
+
+    /// Bytes of file contents
+    #[getter]
+    fn total_size(&self) -> u64 {
+        self.manifest.total_size
+    }
+
+    #[getter]
+    fn created_at(&self) -> &str {
+        &self.manifest.created_at
+    }
+
+    #[getter]
+    fn creator(&self) -> &str {
+        &self.manifest.creator
+    }
+
+    #[getter]
+    fn base_directory(&self) -> PathBuf {
+        self.manifest.base_directory.clone()
+    }
+
+    /// Paths left out because they could not be read
+    #[getter]
+    fn skipped(&self) -> Vec<PathBuf> {
+        self.manifest.skipped.clone()
+    }
+
+    /// The entry at `path`, or None
+    fn find(&self, path: PathBuf) -> Option<PyFileEntry> {
+        self.manifest.find_entry(&path).cloned().map(|entry| PyFileEntry { entry })
+    }
+
+    /// Paths of all entries, sorted
+    fn paths(&self) -> Vec<PathBuf> {
+        self.manifest.sorted_entries().into_iter().map(|entry| entry.path.clone()).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.manifest.entries.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<ArchiveManifest {} entries, {} bytes>", self.manifest.entries.len(), self.manifest.total_size)
+    }
+}
+
+/// An entry of a directory archive's manifest
+#[pyclass(name = "FileEntry", module = "glifzip", frozen)]
+struct PyFileEntry {
+    entry: archive::FileEntry,
+}
+
+#[pymethods]
+impl PyFileEntry {
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.entry.path.clone()
+    }
+
+    /// "file", "directory" or "symlink"
+    #[getter]
+    fn kind(&self) -> &'static str {
+        match self.entry.file_type {
+            FileType::Regular => "file",
+            FileType::Directory => "directory",
+            FileType::Symlink => "symlink",
+        }
+    }
+
+    #[getter]
+    fn size(&self) -> u64 {
+        self.entry.size
+    }
+
+    #[getter]
+    fn mode(&self) -> u32 {
+        self.entry.mode
+    }
+
+    #[getter]
+    fn uid(&self) -> u32 {
+        self.entry.uid
+    }
+
+    #[getter]
+    fn gid(&self) -> u32 {
+        self.entry.gid
+    }
+
+    /// Modification time as an RFC 3339 string
+    #[getter]
+    fn mtime(&self) -> String {
+        self.entry.mtime.to_rfc3339()
+    }
+
+    #[getter]
+    fn symlink_target(&self) -> Option<PathBuf> {
+        self.entry.symlink_target.clone()
+    }
+
+    /// MIME type detected from the contents of a regular file
+    #[getter]
+    fn mime_type(&self) -> Option<&str> {
+        self.entry.mime_type.as_deref()
+    }
+
+    /// Compressed bytes attributed to a regular file's contents
+    #[getter]
+    fn compressed_size(&self) -> Option<u64> {
+        self.entry.compressed_size
+    }
+
+    /// Hex SHA256 of the contents, or "unhashed"
+    #[getter]
+    fn sha256(&self) -> &str {
+        &self.entry.sha256
+    }
+
+    #[getter]
+    fn is_hashed(&self) -> bool {
+        self.entry.is_hashed()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<FileEntry {} {} ({} bytes)>", self.kind(), self.entry.path.display(), self.entry.size)
+    }
+}
+
+#[pymodule]
+fn glifzip(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add_function(wrap_pyfunction!(compress_bytes, module)?)?;
+    module.add_function(wrap_pyfunction!(decompress_bytes, module)?)?;
+    module.add_function(wrap_pyfunction!(compress_directory, module)?)?;
+    module.add_class::<PyArchiveManifest>()?;
+    module.add_class::<PyFileEntry>()?;
+    Ok(())
+}
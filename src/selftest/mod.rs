@@ -0,0 +1,321 @@
+//! Installation self-test
+//!
+//! Runs a fixed battery of checks against this build: compression round
+//! trips across codecs, levels and thread counts, the streaming API, hash
+//! and checksum verification of deliberately damaged archives, and a
+//! directory round trip through a temporary directory to confirm the
+//! platform preserves contents, permissions, timestamps and symlinks. Used
+//! by `glifzip selftest` to diagnose broken installs before filing bugs.
+
+use std::fmt;
+use std::fs;
+use std::io::{Cursor, Error, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::format::GlifHeader;
+use crate::testing::{random_tree, with_temp_dir, MemoryFs};
+use crate::{CompressionConfig, DirectoryCompressor, GlifDecoder, GlifEncoder, GlifError};
+
+/// Levels covering negative, fast, default, high and ultra parameter sets
+pub const SELFTEST_LEVELS: &[i32] = &[-5, 1, 8, 19, 22];
+
+/// Thread counts exercised by the round trips
+pub const SELFTEST_THREADS: &[usize] = &[1, 4];
+
+/// Size of the generated round-trip input
+const SELFTEST_DATA_SIZE: usize = 1024 * 1024;
+
+/// Modification time given to files in the metadata round trip
+const FIXTURE_MTIME: i64 = 1_735_689_600; // 2025-01-01T00:00:00Z
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not applicable on this platform
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Passed => write!(f, "ok"),
+            CheckStatus::Failed => write!(f, "FAILED"),
+            CheckStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Result of one self-test check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    /// Why the check failed or was skipped
+    pub detail: Option<String>,
+    pub elapsed: Duration,
+}
+
+/// Results of a self-test run, in the order the checks ran
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> usize {
+        self.count(CheckStatus::Passed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(CheckStatus::Failed)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(CheckStatus::Skipped)
+    }
+
+    /// Whether no check failed
+    pub fn is_ok(&self) -> bool {
+        self.failed() == 0
+    }
+
+    fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+}
+
+/// Run every check, calling `on_check` as each one finishes
+pub fn run_selftest(mut on_check: impl FnMut(&CheckResult)) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    let mut record = |result: CheckResult| {
+        on_check(&result);
+        report.checks.push(result);
+    };
+
+    let data = selftest_data(SELFTEST_DATA_SIZE);
+    for &level in SELFTEST_LEVELS {
+        for use_lz4_decompression in [true, false] {
+            for &threads in SELFTEST_THREADS {
+                let config = CompressionConfig {
+                    level,
+                    threads,
+                    use_lz4_decompression,
+                    deterministic: true,
+                    ..CompressionConfig::default()
+                };
+                let codec = if use_lz4_decompression { "lz4+zstd" } else { "zstd" };
+                let name = format!("roundtrip {} level {} threads {}", codec, level, threads);
+                record(run_check(name, || check_roundtrip(&data, &config)));
+            }
+        }
+    }
+
+    record(run_check("roundtrip empty input".to_string(), || check_roundtrip(&[], &CompressionConfig::default())));
+    record(run_check("deterministic output".to_string(), || check_deterministic(&data)));
+    record(run_check("streaming encoder and decoder".to_string(), || check_streaming(&data)));
+    record(run_check("header checksum detects damage".to_string(), || check_header_checksum(&data)));
+    record(run_check("archive hash detects damage".to_string(), || check_archive_hash(&data)));
+    record(run_check("payload hash detects mismatch".to_string(), || check_payload_hash(&data)));
+    record(run_check("directory archive roundtrip".to_string(), check_tree_roundtrip));
+    record(run_check("filesystem metadata roundtrip".to_string(), || with_temp_dir("selftest", |dir| check_metadata_roundtrip(dir, false))));
+
+    if cfg!(target_os = "macos") {
+        record(run_check("extended attribute roundtrip".to_string(), || with_temp_dir("selftest", |dir| check_metadata_roundtrip(dir, true))));
+    } else {
+        record(CheckResult {
+            name: "extended attribute roundtrip".to_string(),
+            status: CheckStatus::Skipped,
+            detail: Some("extended attributes are only archived on macOS".to_string()),
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    report
+}
+
+fn run_check(name: String, check: impl FnOnce() -> Result<()>) -> CheckResult {
+    let started = Instant::now();
+    let outcome = check();
+    let (status, detail) = match outcome {
+        Ok(()) => (CheckStatus::Passed, None),
+        Err(e) => (CheckStatus::Failed, Some(e.to_string())),
+    };
+    CheckResult { name, status, detail, elapsed: started.elapsed() }
+}
+
+fn check_roundtrip(data: &[u8], config: &CompressionConfig) -> Result<()> {
+    let archive = crate::compress(data, config)?;
+    crate::verify_archive(&archive)?;
+    let restored = crate::decompress(&archive, config.threads)?;
+    expect(restored == data, || format!("restored {} bytes differ from the {} input bytes", restored.len(), data.len()))
+}
+
+fn check_deterministic(data: &[u8]) -> Result<()> {
+    let config = CompressionConfig::default();
+    let first = crate::compress(data, &config)?;
+    let second = crate::compress(data, &config)?;
+    expect(first == second, || "two deterministic compressions of the same input differ".to_string())
+}
+
+fn check_streaming(data: &[u8]) -> Result<()> {
+    let config = CompressionConfig::default();
+    let mut encoder = GlifEncoder::new(Vec::new(), &config)?;
+    for piece in data.chunks(64 * 1024 + 7) {
+        encoder.write_all(piece)?;
+    }
+    let archive = encoder.finish()?;
+    expect(archive == crate::compress(data, &config)?, || "streamed archive differs from compress() output".to_string())?;
+
+    let mut restored = Vec::new();
+    GlifDecoder::new(Cursor::new(&archive))?.read_to_end(&mut restored)?;
+    expect(restored == data, || "streamed decompression differs from the input".to_string())
+}
+
+fn check_header_checksum(data: &[u8]) -> Result<()> {
+    let mut archive = crate::compress(data, &CompressionConfig::default())?;
+    // Payload size field, right after the magic number and version
+    archive[10] ^= 0x01;
+    expect_error(crate::decompress(&archive, 1), |e| matches!(e, GlifError::ChecksumMismatch { .. }))
+}
+
+fn check_archive_hash(data: &[u8]) -> Result<()> {
+    let mut archive = crate::compress(data, &CompressionConfig::default())?;
+    let last = archive.len() - 1;
+    archive[last] ^= 0xff;
+    expect_error(crate::decompress(&archive, 1), |e| e.corruption().is_some())
+}
+
+fn check_payload_hash(data: &[u8]) -> Result<()> {
+    let mut archive = crate::compress(data, &CompressionConfig::default())?;
+    let mut header = GlifHeader::read(&mut &archive[..])?;
+    header.payload_hash[0] ^= 0xff;
+    let mut rewritten = Vec::with_capacity(header.size());
+    header.write(&mut rewritten)?;
+    archive[..rewritten.len()].copy_from_slice(&rewritten);
+    expect_error(crate::decompress(&archive, 1), |e| matches!(e, GlifError::HashMismatch { .. }))
+}
+
+fn check_tree_roundtrip() -> Result<()> {
+    let tree = random_tree(0x5e1f, 40);
+    let config = DirectoryCompressionConfig::default().with_progress(false);
+    let archive = DirectoryCompressor::new(config)?.compress_source(&tree, PathBuf::from("selftest"))?;
+
+    let mut extracted = MemoryFs::new();
+    DirectoryCompressor::extract_to_sink(&archive, &mut extracted, 0)?.into_result()?;
+    let differences = tree.differences(&extracted);
+    expect(differences.is_empty(), || format!("extracted tree differs at {:?}", differences))
+}
+
+/// Archive a small tree from disk, extract it again and compare what the
+/// platform reports for both copies
+fn check_metadata_roundtrip(dir: &Path, xattrs: bool) -> Result<()> {
+    let source = dir.join("source");
+    fs::create_dir_all(source.join("nested"))?;
+    let files: [(&str, &[u8], u32); 3] = [
+        ("plain.txt", b"glifzip self-test\n", 0o644),
+        ("script.sh", b"#!/bin/sh\necho ok\n", 0o755),
+        ("nested/private.dat", &[0, 1, 2, 3, 255], 0o600),
+    ];
+    for (path, contents, mode) in files {
+        let path = source.join(path);
+        fs::write(&path, contents)?;
+        crate::platform::set_mode(&path, mode)?;
+        let mtime = filetime::FileTime::from_unix_time(FIXTURE_MTIME, 0);
+        filetime::set_file_times(&path, mtime, mtime)?;
+        if xattrs {
+            crate::platform::set_file_attributes(&path, &[("com.glifzip.selftest".to_string(), b"1".to_vec())])?;
+        }
+    }
+    crate::platform::symlink(Path::new("plain.txt"), &source.join("link"))?;
+
+    let archive = dir.join("selftest.glif");
+    let config = DirectoryCompressionConfig::default().with_progress(false);
+    DirectoryCompressor::new(config)?.compress_directory(&source, &archive)?;
+    let output = dir.join("output");
+    DirectoryCompressor::extract_directory(&archive, &output, 0, false, false)?;
+
+    for (path, contents, mode) in files {
+        let restored = output.join(path);
+        expect(fs::read(&restored)? == contents, || format!("{}: contents differ", path))?;
+
+        let metadata = fs::metadata(&restored)?;
+        let restored_mode = crate::platform::unix_metadata(&metadata).0 & 0o7777;
+        expect(restored_mode == mode, || format!("{}: mode {:o}, expected {:o}", path, restored_mode, mode))?;
+        let mtime = filetime::FileTime::from_last_modification_time(&metadata).unix_seconds();
+        expect(mtime == FIXTURE_MTIME, || format!("{}: modification time {}, expected {}", path, mtime, FIXTURE_MTIME))?;
+
+        if xattrs {
+            let attributes = crate::platform::get_file_attributes(&restored)?;
+            let restored_xattr = attributes.iter().any(|(name, value)| name == "com.glifzip.selftest" && value == b"1");
+            expect(restored_xattr, || format!("{}: extended attribute was not restored", path))?;
+        }
+    }
+
+    let target = fs::read_link(output.join("link"))?;
+    expect(target == Path::new("plain.txt"), || format!("link: points to {}, expected plain.txt", target.display()))
+}
+
+fn expect(condition: bool, message: impl FnOnce() -> String) -> Result<()> {
+    if condition {
+        Ok(())
+    } else {
+        Err(Error::other(message()))
+    }
+}
+
+/// Require `result` to fail with an error accepted by `is_expected`
+fn expect_error<T>(result: crate::error::Result<T>, is_expected: impl FnOnce(&GlifError) -> bool) -> Result<()> {
+    match result {
+        Ok(_) => Err(Error::other("damaged archive was accepted")),
+        Err(e) if is_expected(&e) => Ok(()),
+        Err(e) => Err(Error::other(format!("damaged archive failed with an unexpected error: {}", e))),
+    }
+}
+
+/// Half text, half pseudo-random bytes, so both compressible and
+/// incompressible chunks are exercised
+fn selftest_data(len: usize) -> Vec<u8> {
+    let mut data = b"glifzip self-test line, repeated so that it compresses well\n"
+        .repeat(len / 120 + 1);
+    data.truncate(len / 2);
+
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    while data.len() < len {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        data.push(state as u8);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_passes() {
+        let mut seen = 0;
+        let report = run_selftest(|_| seen += 1);
+
+        let failures: Vec<_> = report.checks.iter()
+            .filter(|c| c.status == CheckStatus::Failed)
+            .map(|c| format!("{}: {}", c.name, c.detail.as_deref().unwrap_or_default()))
+            .collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(seen, report.checks.len());
+        assert_eq!(report.passed() + report.skipped(), report.checks.len());
+    }
+
+    #[test]
+    fn test_failed_check_is_reported() {
+        let result = run_check("broken".to_string(), || expect(false, || "boom".to_string()));
+        assert_eq!(result.status, CheckStatus::Failed);
+        assert_eq!(result.detail.as_deref(), Some("boom"));
+        assert!(expect_error(Ok(()), |_| true).is_err());
+    }
+}
@@ -0,0 +1,638 @@
+//! Streaming compression and decompression
+//!
+//! `GlifEncoder` compresses data as it is written instead of requiring the
+//! whole payload in memory. Input is buffered one chunk per thread and
+//! compressed in the same `chunk_size` pieces as `compress()`, so the
+//! archive is identical to compressing the payload in one call.
+//!
+//! The header precedes the compressed stream and records its size and
+//! hash, so compressed chunks are held until `finish()` writes the archive:
+//! memory use is the compressed size plus up to `threads` chunks of input.
+//! With `CompressionConfig::max_memory` set, fewer chunks are compressed at
+//! once if needed and compressed chunks are spilled to a temporary file
+//! instead, so memory use no longer grows with the payload; `with_spill_dir`
+//! spills without limiting concurrency.
+//!
+//! `GlifDecoder` reads an archive from any `Read`, decompressing one chunk
+//! at a time, or a batch of up to one per thread within
+//! `DecompressionConfig::max_in_flight` when given threads. The archive and payload hashes are computed as data passes
+//! through and checked when the payload is exhausted, so a mismatch is
+//! reported by the final read rather than up front.
+
+pub mod range;
+pub(crate) mod spill;
+
+pub use range::RangeDecoder;
+
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Take, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::compression::{self, framing, framing::FrameReader, CHUNK_SIZE};
+use crate::format::ChunkTable;
+use crate::progress::NoProgress;
+use crate::verification::localize::{integrity_block_size, BlockHasher};
+use crate::verification::{self, CorruptionError};
+use crate::{ArchiveSummary, CompressionConfig, DecompressionConfig, GlifHeader, GlifSidecar};
+use spill::FrameSpill;
+
+/// Compresses everything written to it into a GLIF archive written to `W`
+///
+/// Nothing reaches the inner writer until `finish()`, which must be called
+/// to produce the archive; dropping the encoder discards the data.
+pub struct GlifEncoder<W: Write> {
+    writer: W,
+    config: CompressionConfig,
+    threads: usize,
+    /// Chunks compressed at once; fewer than `threads` under a tight memory budget
+    chunks_in_flight: usize,
+    chunk_size: usize,
+    pending: Vec<u8>,
+    chunk_sizes: Vec<usize>,
+    compressed_chunks: Vec<Vec<u8>>,
+    /// Compressed chunks, when spilling to disk
+    spill: Option<FrameSpill>,
+    spill_dir: Option<PathBuf>,
+    payload_hasher: Sha256,
+    payload_size: u64,
+}
+
+impl<W: Write> GlifEncoder<W> {
+    /// Create an encoder writing an archive with the given settings to `writer`
+    pub fn new(writer: W, config: &CompressionConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            writer,
+            config: config.clone(),
+            threads: compression::resolve_threads(config.threads),
+            chunks_in_flight: config.chunks_in_flight(),
+            chunk_size: config.chunk_size,
+            pending: Vec::new(),
+            chunk_sizes: Vec::new(),
+            compressed_chunks: Vec::new(),
+            spill: None,
+            spill_dir: None,
+            payload_hasher: Sha256::new(),
+            payload_size: 0,
+        })
+    }
+
+    /// Spill compressed chunks to a temporary file in `dir` instead of
+    /// holding them in memory until `finish()`
+    ///
+    /// With `max_memory` set they are spilled anyway, by default to the
+    /// system temporary directory. The directory needs room for about the
+    /// compressed size, or twice that with the LZ4 layer.
+    pub fn with_spill_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    fn spills(&self) -> bool {
+        self.config.max_memory.is_some() || self.spill_dir.is_some()
+    }
+
+    fn spill_dir(&self) -> PathBuf {
+        self.spill_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// Bytes written to the encoder so far
+    pub fn payload_size(&self) -> u64 {
+        self.payload_size
+    }
+
+    /// The inner writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Compress the remaining input, write the archive and return the inner writer
+    pub fn finish(self) -> Result<W> {
+        self.finish_annotated(|_| {})
+    }
+
+    /// Finish the archive, letting `annotate` add details to the sidecar
+    pub(crate) fn finish_annotated<F>(mut self, annotate: F) -> Result<W>
+    where
+        F: FnOnce(&mut GlifSidecar),
+    {
+        // Empty input is framed as a single empty chunk, as by compress()
+        if !self.pending.is_empty() || self.chunk_sizes.is_empty() {
+            self.compress_pending()?;
+        }
+
+        if let Some(spill) = self.spill.take() {
+            return self.finish_spilled(spill, annotate);
+        }
+
+        let compressed_data = framing::frame_chunks(&self.chunk_sizes, &self.compressed_chunks);
+        self.compressed_chunks = Vec::new();
+
+        let payload_hash: [u8; 32] = self.payload_hasher.finalize_reset().into();
+        let (archive, _) = crate::assemble_archive(
+            self.payload_size,
+            payload_hash,
+            compressed_data,
+            &self.config,
+            self.threads,
+            annotate,
+        )?;
+
+        self.writer.write_all(&archive)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Write the archive around compressed chunks held in `zstd`, reading
+    /// the spilled data once to hash it and once to copy it out
+    fn finish_spilled<F>(mut self, mut zstd: FrameSpill, annotate: F) -> Result<W>
+    where
+        F: FnOnce(&mut GlifSidecar),
+    {
+        let (mut archive_data, decompression_mode, chunk_table) = if self.config.use_lz4_decompression {
+            let lz4 = self.lz4_layer(&mut zstd)?;
+            let chunk_table = ChunkTable { zstd: zstd.locations(), lz4: lz4.locations() };
+            (lz4, 0, chunk_table)
+        } else {
+            let chunk_table = ChunkTable { zstd: zstd.locations(), lz4: Vec::new() };
+            (zstd, 1, chunk_table)
+        };
+
+        let block_size = integrity_block_size(archive_data.len() as usize);
+        let mut archive_hasher = Sha256::new();
+        let mut block_hasher = BlockHasher::new(block_size);
+        let mut reader = archive_data.reader()?;
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            match reader.read(&mut buffer)? {
+                0 => break,
+                read => {
+                    archive_hasher.update(&buffer[..read]);
+                    block_hasher.update(&buffer[..read]);
+                }
+            }
+        }
+
+        let summary = ArchiveSummary {
+            payload_size: self.payload_size,
+            payload_hash: self.payload_hasher.finalize_reset().into(),
+            archive_size: archive_data.len(),
+            archive_hash: archive_hasher.finalize().into(),
+            decompression_mode,
+            block_size,
+            block_hashes: block_hasher.finish(),
+            chunk_table,
+        };
+        let prefix = crate::archive_prefix(summary, &self.config, self.threads, annotate)?;
+
+        self.writer.write_all(&prefix)?;
+        std::io::copy(&mut archive_data.reader()?, &mut self.writer)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// LZ4-compress the framed Zstd stream in `zstd` into a new spill, in
+    /// the same chunks as `compress_lz4_multithreaded`
+    fn lz4_layer(&self, zstd: &mut FrameSpill) -> Result<FrameSpill> {
+        let mut lz4 = FrameSpill::create(&self.spill_dir())?;
+        let mut reader = zstd.reader()?;
+        let mut batch = Vec::new();
+        loop {
+            batch.clear();
+            (&mut reader).take((self.chunk_size * self.chunks_in_flight) as u64).read_to_end(&mut batch)?;
+            if batch.is_empty() {
+                return Ok(lz4);
+            }
+
+            let chunks: Vec<&[u8]> = batch.chunks(self.chunk_size).collect();
+            let in_flight = self.chunks_in_flight;
+            let compressed = self.config.install(|| framing::compress_chunks(&chunks, in_flight, compression::compress_lz4))?;
+            for (chunk, data) in chunks.iter().zip(&compressed) {
+                lz4.push(chunk.len(), data)?;
+            }
+        }
+    }
+
+    /// Compress the buffered input as whole chunks (the last one may be short)
+    fn compress_pending(&mut self) -> Result<()> {
+        let chunks: Vec<&[u8]> = if self.pending.is_empty() {
+            vec![&self.pending[..]]
+        } else {
+            self.pending.chunks(self.chunk_size).collect()
+        };
+
+        let (level, in_flight) = (self.config.level, self.chunks_in_flight);
+        let compressed = self.config.install(|| {
+            framing::compress_chunks(&chunks, in_flight, |chunk| compression::compress_zstd(chunk, level))
+        })?;
+        self.chunk_sizes.extend(chunks.iter().map(|chunk| chunk.len()));
+
+        if self.spills() {
+            let spill = match self.spill.take() {
+                Some(spill) => spill,
+                None => FrameSpill::create(&self.spill_dir())?,
+            };
+            let spill = self.spill.insert(spill);
+            for (chunk, data) in chunks.iter().zip(&compressed) {
+                spill.push(chunk.len(), data)?;
+            }
+        } else {
+            self.compressed_chunks.extend(compressed);
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for GlifEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let batch_size = self.chunk_size * self.chunks_in_flight;
+        let accepted = buf.len().min(batch_size - self.pending.len());
+        if self.pending.capacity() < batch_size {
+            // Exactly one batch, rather than whatever doubling would reach
+            self.pending.reserve_exact(batch_size - self.pending.len());
+        }
+
+        self.pending.extend_from_slice(&buf[..accepted]);
+        self.payload_hasher.update(&buf[..accepted]);
+        self.payload_size += accepted as u64;
+
+        if self.pending.len() == batch_size {
+            self.compress_pending()?;
+        }
+        Ok(accepted)
+    }
+
+    /// Nothing can be written before `finish()`, so flushing is a no-op
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Spill directory for an archive written to `output_path`: its own
+/// directory, which has room for the archive and is rarely RAM-backed
+pub(crate) fn spill_dir_for(output_path: &Path) -> PathBuf {
+    match output_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Decompresses a GLIF archive read from `R`
+///
+/// The header and sidecar are read by `new()`; the payload is then
+/// decompressed as it is read. Reading past the end of the payload checks
+/// its size and (unless disabled) the archive and payload hashes, failing
+/// that read with `InvalidData` if any of them differ.
+pub struct GlifDecoder<R: Read> {
+    header: GlifHeader,
+    sidecar: GlifSidecar,
+    payload: Payload<R>,
+    payload_hasher: Option<Sha256>,
+    payload_read: u64,
+    state: DecodeState,
+}
+
+/// Where decompressed payload bytes come from
+enum Payload<R> {
+    Zstd(FrameReader<HashingReader<Take<R>>>),
+    /// An LZ4 layer over the framed Zstd stream
+    Lz4(FrameReader<FrameReader<HashingReader<Take<R>>>>),
+    /// Format v1.0 streams, which lack per-chunk sizes, decompressed whole
+    Buffered(HashingReader<Take<R>>, Cursor<Vec<u8>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Reading,
+    Finished,
+    Failed,
+}
+
+impl<R: Read> GlifDecoder<R> {
+    /// Read the header and sidecar of the archive in `reader`, verifying
+    /// hashes and decompressing one chunk at a time
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_config(reader, &DecompressionConfig::new().with_threads(1))
+    }
+
+    /// Read the header and sidecar of the archive in `reader` with explicit
+    /// settings; each thread holds one chunk in memory
+    pub fn with_config(mut reader: R, config: &DecompressionConfig) -> Result<Self> {
+        let (header, sidecar) = crate::read_header_and_sidecar(&mut reader, &mut NoProgress)?;
+
+        let mut source = HashingReader {
+            inner: reader.take(header.archive_size),
+            hasher: config.verify.checks_archive().then(Sha256::new),
+        };
+        let lz4 = header.decompression_mode == 0;
+
+        let payload = if !header.is_framed() {
+            let threads = config.threads_for(framing::chunk_count(header.payload_size as usize, CHUNK_SIZE));
+            let mut data = Vec::new();
+            source.read_to_end(&mut data)?;
+            if lz4 {
+                data = compression::decompress_lz4_multithreaded(&data, threads)?;
+            }
+            let decompressed = compression::decompress_zstd_multithreaded(&data, threads)?;
+            Payload::Buffered(source, Cursor::new(decompressed))
+        } else {
+            let threads = config.chunks_in_flight();
+            let decompress_zstd: fn(&[u8], Option<usize>) -> crate::error::Result<Vec<u8>> =
+                |chunk, _| compression::decompress_zstd(chunk);
+            if lz4 {
+                // Both layers hold a batch of chunks, so they split the in-flight budget
+                let threads = compression::chunks_within_budget(threads, config.max_in_flight.map(|budget| budget / 2));
+                let lz4_stream = FrameReader::new(source, compression::decompress_lz4).with_threads(threads);
+                Payload::Lz4(FrameReader::new(lz4_stream, decompress_zstd).with_threads(threads))
+            } else {
+                Payload::Zstd(FrameReader::new(source, decompress_zstd).with_threads(threads))
+            }
+        };
+
+        Ok(Self {
+            header,
+            sidecar,
+            payload,
+            payload_hasher: config.verify.checks_payload().then(Sha256::new),
+            payload_read: 0,
+            state: DecodeState::Reading,
+        })
+    }
+
+    /// The archive header
+    pub fn header(&self) -> &GlifHeader {
+        &self.header
+    }
+
+    /// The archive sidecar
+    pub fn sidecar(&self) -> &GlifSidecar {
+        &self.sidecar
+    }
+
+    /// Payload bytes read so far
+    pub fn payload_read(&self) -> u64 {
+        self.payload_read
+    }
+
+    fn read_payload(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = match &mut self.payload {
+            Payload::Zstd(reader) => reader.read(buf)?,
+            Payload::Lz4(reader) => reader.read(buf)?,
+            Payload::Buffered(_, data) => data.read(buf)?,
+        };
+
+        if read == 0 && !buf.is_empty() {
+            self.verify()?;
+            self.state = DecodeState::Finished;
+            return Ok(0);
+        }
+
+        if let Some(hasher) = &mut self.payload_hasher {
+            hasher.update(&buf[..read]);
+        }
+        self.payload_read += read as u64;
+        Ok(read)
+    }
+
+    /// Check the archive hash, payload hash and payload size, in that order
+    fn verify(&mut self) -> Result<()> {
+        let source = match &mut self.payload {
+            Payload::Zstd(reader) => reader.get_mut(),
+            Payload::Lz4(reader) => reader.get_mut().get_mut(),
+            Payload::Buffered(source, _) => source,
+        };
+
+        if let Some(hasher) = source.hasher.take() {
+            let actual: [u8; 32] = hasher.finalize().into();
+            if actual != self.header.archive_hash {
+                // Block hashes need the archive data, so damage isn't localized here
+                return Err(CorruptionError {
+                    expected: verification::hex_encode(&self.header.archive_hash),
+                    actual: verification::hex_encode(&actual),
+                    regions: Vec::new(),
+                    data_offset: (self.header.size() + self.header.sidecar_size as usize) as u64,
+                    payload_range: None,
+                    affected_entries: Vec::new(),
+                }.into_io_error());
+            }
+        }
+
+        if let Some(hasher) = self.payload_hasher.take() {
+            let actual: [u8; 32] = hasher.finalize().into();
+            verification::sha256::check_sha256(&actual, &self.header.payload_hash)?;
+        }
+
+        if self.payload_read != self.header.payload_size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Decompressed size mismatch: expected {}, got {}",
+                    self.header.payload_size,
+                    self.payload_read
+                )
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for GlifDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.state {
+            DecodeState::Finished => return Ok(0),
+            DecodeState::Failed => {
+                return Err(Error::new(ErrorKind::InvalidData, "archive failed to decompress or verify"));
+            }
+            DecodeState::Reading => {}
+        }
+
+        let result = self.read_payload(buf);
+        if result.is_err() {
+            self.state = DecodeState::Failed;
+        }
+        result
+    }
+}
+
+/// Hashes everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Option<Sha256>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CHUNK_SIZE;
+
+    #[test]
+    fn test_encoder_matches_compress() {
+        let data = b"streamed into the encoder ".repeat(5000);
+        let config = CompressionConfig::builder().deterministic(true).threads(2).build().unwrap();
+
+        let mut encoder = GlifEncoder::new(Vec::new(), &config).unwrap();
+        for piece in data.chunks(777) {
+            encoder.write_all(piece).unwrap();
+        }
+        assert_eq!(encoder.payload_size(), data.len() as u64);
+        let archive = encoder.finish().unwrap();
+
+        assert_eq!(archive, crate::compress(&data, &config).unwrap());
+        assert_eq!(crate::decompress(&archive, 1).unwrap(), data);
+    }
+
+    #[test]
+    fn test_encoder_across_chunks() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        let config = CompressionConfig::builder().threads(3).build().unwrap();
+
+        let mut encoder = GlifEncoder::new(Vec::new(), &config).unwrap();
+        encoder.chunk_size = 4096;
+        std::io::copy(&mut &data[..], &mut encoder).unwrap();
+        let archive = encoder.finish().unwrap();
+
+        let header = crate::GlifHeader::read(&mut std::io::Cursor::new(&archive)).unwrap();
+        assert_eq!(header.payload_size, data.len() as u64);
+        assert_eq!(crate::decompress(&archive, 2).unwrap(), data);
+    }
+
+    #[test]
+    fn test_encoder_empty_and_lz4() {
+        let config = CompressionConfig::builder().deterministic(true).build().unwrap();
+        let archive = GlifEncoder::new(Vec::new(), &config).unwrap().finish().unwrap();
+        assert_eq!(archive, crate::compress(b"", &config).unwrap());
+
+        let config = CompressionConfig::builder().use_lz4_decompression(true).build().unwrap();
+        let mut encoder = GlifEncoder::new(Vec::new(), &config).unwrap();
+        encoder.write_all(b"lz4 streamed payload").unwrap();
+        let archive = encoder.finish().unwrap();
+        assert_eq!(crate::decompress(&archive, 1).unwrap(), b"lz4 streamed payload");
+    }
+
+    #[test]
+    fn test_bounded_encoder_matches_compress() {
+        let data = b"spilled to disk under a memory budget ".repeat(3000);
+        let budget = compression::memory::chunk_compression_memory(8, CHUNK_SIZE);
+
+        for use_lz4 in [false, true] {
+            let config = CompressionConfig::builder().use_lz4_decompression(use_lz4).max_memory(budget).build().unwrap();
+            assert_eq!(config.chunks_in_flight(), 1);
+
+            let mut encoder = GlifEncoder::new(Vec::new(), &config).unwrap();
+            encoder.write_all(&data).unwrap();
+            let archive = encoder.finish().unwrap();
+            assert_eq!(archive, crate::compress(&data, &config).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_bounded_encoder_across_chunks() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        let budget = 2 * compression::memory::chunk_compression_memory(8, CHUNK_SIZE);
+
+        for use_lz4 in [false, true] {
+            let config = CompressionConfig::builder()
+                .threads(4)
+                .use_lz4_decompression(use_lz4)
+                .max_memory(budget)
+                .build()
+                .unwrap();
+            let mut encoder = GlifEncoder::new(Vec::new(), &config).unwrap();
+            assert_eq!(encoder.chunks_in_flight, 2);
+            encoder.chunk_size = 4096;
+            std::io::copy(&mut &data[..], &mut encoder).unwrap();
+            let archive = encoder.finish().unwrap();
+
+            crate::verify_archive(&archive).unwrap();
+            assert_eq!(crate::decompress(&archive, 2).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decoder_streams_payload() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+        for use_lz4 in [false, true] {
+            let config = CompressionConfig::builder().use_lz4_decompression(use_lz4).build().unwrap();
+            let archive = crate::compress(&data, &config).unwrap();
+
+            let mut decoder = GlifDecoder::new(&archive[..]).unwrap();
+            assert_eq!(decoder.header().payload_size, data.len() as u64);
+
+            let mut output = Vec::new();
+            let mut buf = [0u8; 1000];
+            loop {
+                match decoder.read(&mut buf).unwrap() {
+                    0 => break,
+                    read => output.extend_from_slice(&buf[..read]),
+                }
+            }
+            assert_eq!(output, data);
+            assert_eq!(decoder.payload_read(), data.len() as u64);
+            assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn test_decoder_reports_hash_mismatch_on_final_read() {
+        let data = b"payload checked at the end of the stream ".repeat(100);
+        let mut archive = crate::compress(&data, &CompressionConfig::default()).unwrap();
+
+        // Corrupt the recorded payload hash; the header checksum covers it, so patch that too
+        let mut header = GlifHeader::read(&mut Cursor::new(&archive)).unwrap();
+        header.payload_hash[0] ^= 0xff;
+        let mut patched = Vec::new();
+        header.write(&mut patched).unwrap();
+        archive[..patched.len()].copy_from_slice(&patched);
+
+        let mut decoder = GlifDecoder::new(&archive[..]).unwrap();
+        let mut output = vec![0u8; data.len()];
+        decoder.read_exact(&mut output).unwrap();
+        assert_eq!(output, data);
+
+        let err = decoder.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("SHA256 hash mismatch"));
+        assert!(decoder.read(&mut [0u8; 16]).is_err());
+
+        let config = DecompressionConfig::new().with_verify_hashes(false);
+        let mut output = Vec::new();
+        GlifDecoder::with_config(&archive[..], &config).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn test_decoder_rejects_corrupt_archive_data() {
+        let data = b"archive bytes hashed as they stream in ".repeat(100);
+        let archive = crate::compress(&data, &CompressionConfig::default()).unwrap();
+
+        let mut header = GlifHeader::read(&mut Cursor::new(&archive)).unwrap();
+        header.archive_hash[0] ^= 0xff;
+        let mut patched = Vec::new();
+        header.write(&mut patched).unwrap();
+        patched.extend_from_slice(&archive[header.size()..]);
+
+        let err = GlifDecoder::new(&patched[..]).unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+        let corruption = CorruptionError::from_io_error(&err).unwrap();
+        assert_eq!(corruption.data_offset, (header.size() + header.sidecar_size as usize) as u64);
+
+        let mut damaged = archive.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 0x01;
+        assert!(GlifDecoder::new(&damaged[..]).unwrap().read_to_end(&mut Vec::new()).is_err());
+        assert!(GlifDecoder::new(&archive[..archive.len() - 1]).unwrap().read_to_end(&mut Vec::new()).is_err());
+    }
+}
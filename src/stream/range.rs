@@ -0,0 +1,351 @@
+//! Random access to archive payloads
+//!
+//! `RangeDecoder` parses the header and sidecar of an archive in a seekable
+//! source once and locates its compressed chunks, so any byte range of the
+//! payload is read by decompressing only the chunks it lies in. The most
+//! recently decompressed chunk is kept, since neighbouring reads usually
+//! share one.
+//!
+//! Chunks are located from the header's chunk table when it has one (see
+//! `format::chunk_table`), and otherwise by walking the chunk framing. In
+//! LZ4 mode the framed Zstd stream is itself framed in LZ4 chunks, and both
+//! layers are indexed: walking the inner framing decompresses the LZ4
+//! chunks holding its chunk headers, but no Zstd chunks. Format v1.0
+//! streams lack per-chunk sizes and are decompressed whole on first access.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::compression::framing::{CHUNK_HEADER_SIZE, FRAME_HEADER_SIZE, FRAME_MAGIC, MAX_FRAME_CHUNK_SIZE};
+use crate::compression::{self, FramingError};
+use crate::error::{GlifError, Result};
+use crate::format::chunk_table::{self, ChunkLocation};
+use crate::format::{GlifHeader, GlifSidecar};
+
+/// An archive opened for reading byte ranges of its payload
+pub struct RangeDecoder<R> {
+    source: R,
+    header: GlifHeader,
+    sidecar: GlifSidecar,
+    /// Offset of the GLIF header in the source
+    stream_offset: u64,
+    /// Offset of the compressed stream in the source
+    data_offset: u64,
+    pub(crate) layout: Layout,
+}
+
+/// How payload bytes are located
+pub(crate) enum Layout {
+    /// Framed Zstd chunks
+    Zstd(ChunkIndex),
+    /// Framed Zstd chunks within the stream of framed LZ4 chunks
+    Lz4 { lz4: ChunkIndex, zstd: ChunkIndex },
+    /// The whole payload, once something has been read
+    Whole(Option<Vec<u8>>),
+}
+
+/// Chunk locations of a framed stream and its most recently decompressed chunk
+pub(crate) struct ChunkIndex {
+    pub(crate) chunks: Vec<ChunkLocation>,
+    decompress: fn(&[u8], Option<usize>) -> Result<Vec<u8>>,
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+impl RangeDecoder<File> {
+    /// Open an archive file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(open_unarmored(path.as_ref())?)
+    }
+}
+
+impl<R: Read + Seek> RangeDecoder<R> {
+    /// Parse the header and sidecar of the archive at the current position
+    /// of `source` and locate its compressed chunks
+    pub fn new(mut source: R) -> Result<Self> {
+        let stream_offset = source.stream_position()?;
+        let header = GlifHeader::read(&mut source)?;
+        let sidecar = GlifSidecar::read(&mut source, header.sidecar_size)?;
+        sidecar.check_reader_compatibility()?;
+        sidecar.check_hashes()?;
+        let data_offset = source.stream_position()?;
+
+        let source_len = source.seek(SeekFrom::End(0))?;
+        if data_offset.saturating_add(header.archive_size) > source_len {
+            return Err(GlifError::Truncated(
+                format!("Archive data is truncated: expected {} bytes, found {}", header.archive_size, source_len - data_offset)
+            ));
+        }
+
+        let layout = match header.chunk_table()? {
+            Some(table) if header.decompression_mode == 0 => Layout::Lz4 {
+                lz4: ChunkIndex::new(table.lz4, compression::decompress_lz4),
+                zstd: ChunkIndex::new(table.zstd, decompress_zstd_chunk),
+            },
+            Some(table) => Layout::Zstd(ChunkIndex::new(table.zstd, decompress_zstd_chunk)),
+            None => {
+                let mut read_data = |offset: u64, len: u64| read_at(&mut source, data_offset + offset, len);
+                if !header.is_framed() {
+                    Layout::Whole(None)
+                } else if header.decompression_mode == 0 {
+                    let mut lz4 = ChunkIndex::build(header.archive_size, compression::decompress_lz4, &mut read_data)?;
+                    let zstd = ChunkIndex::build(lz4.output_size(), decompress_zstd_chunk, &mut |offset, len| {
+                        lz4.read_range(offset, len, &mut read_data)
+                    })?;
+                    Layout::Lz4 { lz4, zstd }
+                } else {
+                    Layout::Zstd(ChunkIndex::build(header.archive_size, decompress_zstd_chunk, &mut read_data)?)
+                }
+            }
+        };
+
+        let indexed_size = match &layout {
+            Layout::Zstd(zstd) | Layout::Lz4 { zstd, .. } => zstd.output_size(),
+            Layout::Whole(_) => header.payload_size,
+        };
+        if indexed_size != header.payload_size {
+            return Err(GlifError::Malformed(
+                format!("Chunks hold {} bytes but the header records a {}-byte payload", indexed_size, header.payload_size)
+            ));
+        }
+
+        Ok(Self {
+            source,
+            header,
+            sidecar,
+            stream_offset,
+            data_offset,
+            layout,
+        })
+    }
+
+    pub fn header(&self) -> &GlifHeader {
+        &self.header
+    }
+
+    pub fn sidecar(&self) -> &GlifSidecar {
+        &self.sidecar
+    }
+
+    /// Read `len` payload bytes starting at `offset`
+    ///
+    /// The bytes are not checked against the payload hash, which covers
+    /// the whole payload; each chunk is checked against its recorded size.
+    pub fn read_range(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let end = offset.checked_add(len).filter(|&end| end <= self.header.payload_size).ok_or_else(|| GlifError::InvalidInput(
+            format!("Range {}+{} is outside the {}-byte payload", offset, len, self.header.payload_size)
+        ))?;
+
+        let source = &mut self.source;
+        let data_offset = self.data_offset;
+        let mut read_data = |offset: u64, len: u64| read_at(source, data_offset + offset, len);
+
+        match &mut self.layout {
+            Layout::Zstd(zstd) => zstd.read_range(offset, len, &mut read_data),
+            Layout::Lz4 { lz4, zstd } => zstd.read_range(offset, len, &mut |offset, len| {
+                lz4.read_range(offset, len, &mut read_data)
+            }),
+            Layout::Whole(payload) => {
+                if payload.is_none() {
+                    let stream_size = data_offset - self.stream_offset + self.header.archive_size;
+                    let stream = read_at(source, self.stream_offset, stream_size)?;
+                    *payload = Some(crate::decompress_auto(&stream)?);
+                }
+                let payload = payload.as_deref().unwrap_or_default();
+                Ok(payload[offset as usize..end as usize].to_vec())
+            }
+        }
+    }
+}
+
+impl ChunkIndex {
+    fn new(chunks: Vec<ChunkLocation>, decompress: fn(&[u8], Option<usize>) -> Result<Vec<u8>>) -> Self {
+        Self { chunks, decompress, cached: None }
+    }
+
+    /// Index the framed stream of `stream_len` bytes that `read` reads from,
+    /// validating the chunk table as `framing::read_frames` does
+    fn build(
+        stream_len: u64,
+        decompress: fn(&[u8], Option<usize>) -> Result<Vec<u8>>,
+        read: &mut dyn FnMut(u64, u64) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        let mut read_checked = |chunk: usize, offset: u64, len: u64| {
+            let available = stream_len.saturating_sub(offset);
+            if len > available {
+                return Err(GlifError::from(FramingError::Truncated {
+                    chunk,
+                    offset: offset as usize,
+                    needed: len.min(usize::MAX as u64) as usize,
+                    available: available as usize,
+                }));
+            }
+            read(offset, len)
+        };
+
+        let frame_header = read_checked(0, 0, FRAME_HEADER_SIZE as u64)?;
+        if &frame_header[..4] != FRAME_MAGIC {
+            return Err(FramingError::NotFramed.into());
+        }
+        let count = u32::from_be_bytes([frame_header[4], frame_header[5], frame_header[6], frame_header[7]]) as usize;
+        if count == 0 {
+            return Err(FramingError::NoChunks.into());
+        }
+
+        let mut chunks = Vec::with_capacity(count.min(stream_len as usize / CHUNK_HEADER_SIZE));
+        let mut position = FRAME_HEADER_SIZE as u64;
+        let mut output_offset = 0;
+        for chunk in 0..count {
+            let chunk_header = read_checked(chunk, position, CHUNK_HEADER_SIZE as u64)?;
+            let mut size = [0u8; 8];
+            size.copy_from_slice(&chunk_header[..8]);
+            let uncompressed_size = u64::from_be_bytes(size);
+            if uncompressed_size > MAX_FRAME_CHUNK_SIZE as u64 {
+                return Err(FramingError::ChunkTooLarge { chunk, size: uncompressed_size }.into());
+            }
+            size.copy_from_slice(&chunk_header[8..]);
+            let compressed_size = u64::from_be_bytes(size);
+
+            let stream_offset = position + CHUNK_HEADER_SIZE as u64;
+            if compressed_size > stream_len - stream_offset {
+                return Err(FramingError::Truncated {
+                    chunk,
+                    offset: stream_offset as usize,
+                    needed: compressed_size.min(usize::MAX as u64) as usize,
+                    available: (stream_len - stream_offset) as usize,
+                }.into());
+            }
+
+            chunks.push(ChunkLocation { output_offset, uncompressed_size, stream_offset, compressed_size });
+            output_offset += uncompressed_size;
+            position = stream_offset + compressed_size;
+        }
+
+        if position != stream_len {
+            return Err(FramingError::TrailingData { bytes: (stream_len - position) as usize }.into());
+        }
+
+        Ok(Self::new(chunks, decompress))
+    }
+
+    /// Total decompressed size of the stream
+    fn output_size(&self) -> u64 {
+        chunk_table::output_size(&self.chunks)
+    }
+
+    /// Decompressed bytes `offset..offset + len`, decompressing only the
+    /// chunks they lie in
+    fn read_range(&mut self, offset: u64, len: u64, read: &mut dyn FnMut(u64, u64) -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+        let end = offset + len;
+        let mut data = Vec::with_capacity(len as usize);
+        // First chunk that ends past `offset`
+        let first = self.chunks.partition_point(|c| c.output_offset + c.uncompressed_size <= offset);
+
+        for (index, chunk) in self.chunks.iter().enumerate().skip(first) {
+            if chunk.output_offset >= end {
+                break;
+            }
+
+            if self.cached.as_ref().map(|(i, _)| *i) != Some(index) {
+                let compressed = read(chunk.stream_offset, chunk.compressed_size)?;
+                let expected = chunk.uncompressed_size as usize;
+                let output = (self.decompress)(&compressed, Some(expected))?;
+                if output.len() != expected {
+                    return Err(FramingError::SizeMismatch { chunk: index, expected, actual: output.len() }.into());
+                }
+                self.cached = Some((index, output));
+            }
+            let chunk_data = self.cached.as_ref().map(|(_, data)| data.as_slice()).unwrap_or_default();
+
+            let start = offset.max(chunk.output_offset) - chunk.output_offset;
+            let stop = end.min(chunk.output_offset + chunk.uncompressed_size) - chunk.output_offset;
+            data.extend_from_slice(&chunk_data[start as usize..stop as usize]);
+        }
+
+        if (data.len() as u64) < len {
+            return Err(GlifError::Truncated("Read past the end of the decompressed stream".to_string()));
+        }
+        Ok(data)
+    }
+}
+
+/// Open the file at `path`, refusing ASCII-armored archives, which can't be read in place
+pub(crate) fn open_unarmored(path: &Path) -> Result<File> {
+    let mut file = File::open(path)?;
+
+    let mut prefix = Vec::new();
+    (&mut file).take(64).read_to_end(&mut prefix)?;
+    if crate::armor::is_armored(&prefix) {
+        return Err(GlifError::InvalidInput(
+            format!("{} is ASCII-armored; dearmor it and use a Cursor over the result", path.display())
+        ));
+    }
+
+    file.rewind()?;
+    Ok(file)
+}
+
+fn decompress_zstd_chunk(data: &[u8], _size: Option<usize>) -> Result<Vec<u8>> {
+    compression::decompress_zstd(data)
+}
+
+/// Read exactly `len` bytes at `offset` in `source`
+fn read_at<R: Read + Seek>(source: &mut R, offset: u64, len: u64) -> Result<Vec<u8>> {
+    source.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    source.by_ref().take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(GlifError::Truncated("Archive ended unexpectedly".to_string()));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::format::header::TLV_CHUNK_TABLE;
+    use crate::CompressionConfig;
+
+    #[test]
+    fn test_read_ranges_with_and_without_chunk_table() {
+        let data: Vec<u8> = (0..5 * compression::MIN_CHUNK_SIZE as u32 / 2).map(|i| (i * 7 % 251) as u8).collect();
+
+        for use_lz4 in [false, true] {
+            let config = CompressionConfig::builder()
+                .chunk_size(compression::MIN_CHUNK_SIZE)
+                .use_lz4_decompression(use_lz4)
+                .build()
+                .unwrap();
+            let archive = crate::compress(&data, &config).unwrap();
+            let mut decoder = RangeDecoder::new(Cursor::new(&archive)).unwrap();
+            let table = decoder.header().chunk_table().unwrap().unwrap();
+            assert_eq!(table.zstd.len(), 3);
+            assert_eq!(table.lz4.is_empty(), !use_lz4);
+
+            // Without the table, the framing is walked to the same chunks
+            let mut header = decoder.header().clone();
+            header.fields.retain(|field| field.field_type != TLV_CHUNK_TABLE);
+            let mut untabled = Vec::new();
+            header.write(&mut untabled).unwrap();
+            untabled.extend_from_slice(&archive[decoder.header().size()..]);
+            let mut walked = RangeDecoder::new(Cursor::new(&untabled)).unwrap();
+            match (&decoder.layout, &walked.layout) {
+                (Layout::Zstd(a), Layout::Zstd(b)) => assert_eq!(a.chunks, b.chunks),
+                (Layout::Lz4 { lz4: a, zstd: b }, Layout::Lz4 { lz4: c, zstd: d }) => {
+                    assert_eq!((&a.chunks, &b.chunks), (&c.chunks, &d.chunks));
+                }
+                _ => panic!("archives were indexed differently"),
+            }
+
+            let boundary = compression::MIN_CHUNK_SIZE as u64;
+            for (offset, len) in [(0, 10), (boundary - 5, 10), (2 * boundary, boundary / 2), (data.len() as u64, 0)] {
+                let expected = &data[offset as usize..(offset + len) as usize];
+                assert_eq!(decoder.read_range(offset, len).unwrap(), expected);
+                assert_eq!(walked.read_range(offset, len).unwrap(), expected);
+            }
+            assert!(decoder.read_range(data.len() as u64 - 1, 2).is_err());
+        }
+    }
+}
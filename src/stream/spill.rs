@@ -0,0 +1,107 @@
+//! Framed streams held on disk
+//!
+//! Under a memory budget, compressed chunks are written to a temporary
+//! file as they are produced instead of being kept until the archive
+//! header can be written. The file is unlinked as soon as it is created,
+//! so it disappears with its handle even if the process is killed.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Take, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::compression::framing::{FRAME_HEADER_SIZE, FRAME_MAGIC};
+use crate::error::Result;
+use crate::format::ChunkLocation;
+
+/// Distinguishes spill files created by one process
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Create a temporary file in `dir` that is already unlinked
+pub(crate) fn anonymous_file(dir: &Path) -> Result<File> {
+    let path = dir.join(format!(
+        ".glifzip-spill-{}-{}",
+        std::process::id(),
+        SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+/// A framed stream written chunk by chunk to an anonymous temporary file
+pub(crate) struct FrameSpill {
+    file: File,
+    /// Uncompressed and compressed size of each chunk
+    sizes: Vec<(u64, u64)>,
+    len: u64,
+}
+
+impl FrameSpill {
+    /// Start an empty framed stream in a new file in `dir`
+    pub(crate) fn create(dir: &Path) -> Result<Self> {
+        let mut file = anonymous_file(dir)?;
+
+        // The chunk count is filled in by `reader()`
+        file.write_all(FRAME_MAGIC)?;
+        file.write_all(&0u32.to_be_bytes())?;
+        Ok(Self { file, sizes: Vec::new(), len: FRAME_HEADER_SIZE as u64 })
+    }
+
+    /// Append a compressed chunk and its sizes
+    pub(crate) fn push(&mut self, uncompressed_size: usize, data: &[u8]) -> Result<()> {
+        self.file.write_all(&(uncompressed_size as u64).to_be_bytes())?;
+        self.file.write_all(&(data.len() as u64).to_be_bytes())?;
+        self.file.write_all(data)?;
+        self.sizes.push((uncompressed_size as u64, data.len() as u64));
+        self.len += 16 + data.len() as u64;
+        Ok(())
+    }
+
+    /// Size of the framed stream
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Where each chunk pushed so far lies in the framed stream
+    pub(crate) fn locations(&self) -> Vec<ChunkLocation> {
+        ChunkLocation::from_sizes(self.sizes.iter().copied())
+    }
+
+    /// Record the chunk count and read the framed stream from the start
+    ///
+    /// Chunks pushed afterwards are appended after the stream read so far.
+    pub(crate) fn reader(&mut self) -> Result<Take<&mut File>> {
+        self.file.seek(SeekFrom::Start(FRAME_MAGIC.len() as u64))?;
+        self.file.write_all(&(self.sizes.len() as u32).to_be_bytes())?;
+        self.file.seek(SeekFrom::Start(0))?;
+        let len = self.len;
+        Ok((&mut self.file).take(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::framing::{frame_chunks, read_frames};
+
+    #[test]
+    fn test_spilled_stream_matches_frame_chunks() {
+        crate::testing::with_temp_dir("spill", |dir| {
+            let mut spill = FrameSpill::create(dir).unwrap();
+            spill.push(10, b"first").unwrap();
+            spill.push(20, b"second chunk").unwrap();
+
+            // The file was unlinked on creation
+            assert_eq!(fs::read_dir(dir)?.count(), 0);
+
+            let mut framed = Vec::new();
+            spill.reader().unwrap().read_to_end(&mut framed)?;
+            assert_eq!(framed.len() as u64, spill.len());
+            assert_eq!(framed, frame_chunks(&[10, 20], &[b"first".to_vec(), b"second chunk".to_vec()]));
+            assert_eq!(read_frames(&framed).unwrap().len(), 2);
+            assert_eq!(spill.locations(), crate::format::ChunkTable::from_frames(&framed, None).unwrap().zstd);
+            Ok(())
+        }).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+//! Golden archive corpus
+//!
+//! `tests/corpus/v<version>/` holds archives written by each released
+//! glifzip version from the inputs defined here. The corpus tests check
+//! that the current reader still extracts all of them bit-identically.
+//!
+//! The inputs must never change: add new cases instead of editing
+//! existing ones, since archives from older versions can't be regenerated.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use chrono::Duration;
+
+use super::{fixture_mtime, MemoryFs, TreeBuilder, XorShift};
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::{CompressionConfig, DirectoryCompressor};
+
+/// Default location of the corpus, relative to the crate root
+pub const CORPUS_DIR: &str = "tests/corpus";
+
+/// Extension of corpus archives
+pub const CORPUS_EXTENSION: &str = "glif";
+
+/// What a corpus archive contains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorpusInput {
+    File(Vec<u8>),
+    Tree(MemoryFs),
+}
+
+/// A named input and the settings its archive is written with
+#[derive(Debug, Clone)]
+pub struct CorpusCase {
+    pub name: String,
+    pub input: CorpusInput,
+    pub level: i32,
+    pub use_lz4_decompression: bool,
+}
+
+impl CorpusCase {
+    fn new(name: String, input: CorpusInput, level: i32, use_lz4_decompression: bool) -> Self {
+        Self { name, input, level, use_lz4_decompression }
+    }
+
+    /// Compression settings; single-threaded and deterministic for stable output
+    pub fn config(&self) -> CompressionConfig {
+        CompressionConfig {
+            level: self.level,
+            threads: 1,
+            use_lz4_decompression: self.use_lz4_decompression,
+            deterministic: true,
+            ..CompressionConfig::default()
+        }
+    }
+
+    /// Build this case's archive with the current version
+    pub fn archive(&self) -> Result<Vec<u8>> {
+        match &self.input {
+            CorpusInput::File(data) => Ok(crate::compress(data, &self.config())?),
+            CorpusInput::Tree(tree) => {
+                let config = DirectoryCompressionConfig::new(self.config()).with_progress(false);
+                Ok(DirectoryCompressor::new(config)?.compress_source(tree, PathBuf::from(&self.name))?)
+            }
+        }
+    }
+
+    /// Check that `archive` extracts to exactly this case's input
+    pub fn check(&self, archive: &[u8]) -> Result<()> {
+        match &self.input {
+            CorpusInput::File(expected) => {
+                let data = crate::decompress(archive, 0)?;
+                if &data != expected {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{}: extracted {} bytes differ from the expected {} bytes", self.name, data.len(), expected.len())
+                    ));
+                }
+            }
+            CorpusInput::Tree(expected) => {
+                let mut extracted = MemoryFs::new();
+                DirectoryCompressor::extract_to_sink(archive, &mut extracted, 0)?.into_result()?;
+
+                let differences = expected.differences(&extracted);
+                if !differences.is_empty() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{}: extracted tree differs at {:?}", self.name, differences)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All corpus cases, in a stable order
+pub fn corpus_cases() -> Vec<CorpusCase> {
+    let mut cases = Vec::new();
+    for (suffix, lz4) in [("lz4", true), ("zstd", false)] {
+        let name = |base: &str| format!("{}-{}", base, suffix);
+        cases.push(CorpusCase::new(name("empty"), CorpusInput::File(Vec::new()), 8, lz4));
+        cases.push(CorpusCase::new(name("text"), CorpusInput::File(corpus_text()), 8, lz4));
+        cases.push(CorpusCase::new(name("random"), CorpusInput::File(corpus_random(16 * 1024)), 8, lz4));
+        cases.push(CorpusCase::new(name("text-level19"), CorpusInput::File(corpus_text()), 19, lz4));
+        cases.push(CorpusCase::new(name("tree"), CorpusInput::Tree(corpus_tree()), 8, lz4));
+    }
+    cases
+}
+
+/// Look up a case by archive name (file stem)
+pub fn find_case(name: &str) -> Option<CorpusCase> {
+    corpus_cases().into_iter().find(|case| case.name == name)
+}
+
+/// Write every case's archive into `directory`, returning the paths written
+pub fn write_corpus(directory: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(directory)?;
+
+    corpus_cases().iter()
+        .map(|case| {
+            let path = directory.join(format!("{}.{}", case.name, CORPUS_EXTENSION));
+            fs::write(&path, case.archive()?)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Versioned corpus directories (`v<version>`) below `root`, sorted
+pub fn corpus_versions(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut versions: Vec<PathBuf> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir() && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('v')))
+        .collect();
+    versions.sort();
+    Ok(versions)
+}
+
+fn corpus_text() -> Vec<u8> {
+    (0..200)
+        .map(|i| format!("{:04} The GLIF corpus keeps every released archive format readable.\n", i))
+        .collect::<String>()
+        .into_bytes()
+}
+
+fn corpus_random(len: usize) -> Vec<u8> {
+    let mut rng = XorShift::new(0x474c_4946);
+    (0..len).map(|_| rng.next() as u8).collect()
+}
+
+/// Fixed tree covering nested directories, modes, times, symlinks and empty entries
+///
+/// No xattrs, so the tree can be reproduced on any platform.
+pub fn corpus_tree() -> MemoryFs {
+    let day = |n: i64| fixture_mtime() - Duration::days(n);
+
+    TreeBuilder::new()
+        .file("README.txt", corpus_text())
+        .mtime("README.txt", day(10))
+        .file("bin/run.sh", "#!/bin/sh\necho run\n")
+        .mode("bin/run.sh", 0o755)
+        .mtime("bin/run.sh", day(9))
+        .mtime("bin", day(8))
+        .file("src/main.rs", "fn main() {}\n")
+        .file("src/data.bin", corpus_random(4096))
+        .mode("src/data.bin", 0o600)
+        .mtime("src/data.bin", day(7))
+        .mtime("src", day(6))
+        .dir("empty")
+        .mode("empty", 0o700)
+        .file("empty.txt", "")
+        .symlink("latest", "src/main.rs")
+        .build()
+}
@@ -0,0 +1,219 @@
+//! Benchmark input data
+//!
+//! The generators behind the benchmark suite, seeded so that every run and
+//! every machine produces the same bytes. `glifzip gen-testdata` writes
+//! them to a file, letting users benchmark their own hardware on the same
+//! inputs as published results.
+//!
+//! ```
+//! use std::io::Read;
+//! use glifzip::testing::data::{DataKind, TestData, DEFAULT_SEED};
+//!
+//! let mut text = Vec::new();
+//! TestData::new(DataKind::Text, 4096, DEFAULT_SEED).read_to_end(&mut text).unwrap();
+//! assert_eq!(text.len(), 4096);
+//! ```
+
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+/// Seed used by the benchmark suite and by `gen-testdata` unless overridden
+pub const DEFAULT_SEED: u64 = 0x5EED_61F2;
+
+const TEXT_PATTERN: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. \
+                              Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. \
+                              Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris. ";
+
+const CODE_PATTERNS: [&[u8]; 5] = [
+    b"fn main() {\n    println!(\"Hello, world!\");\n}\n\n",
+    b"pub struct MyStruct {\n    field1: u32,\n    field2: String,\n}\n\n",
+    b"impl MyTrait for MyStruct {\n    fn method(&self) -> bool {\n        true\n    }\n}\n\n",
+    b"use std::collections::HashMap;\nuse std::io::{Read, Write};\n\n",
+    b"#[derive(Debug, Clone, PartialEq)]\npub enum MyEnum {\n    Variant1,\n    Variant2(u32),\n}\n\n",
+];
+
+/// Kind of generated data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataKind {
+    /// Repeated English-like prose, highly compressible
+    Text,
+    /// Uniformly random bytes, incompressible
+    Random,
+    /// All zero bytes
+    Zeros,
+    /// Repeated Rust source snippets
+    Code,
+}
+
+impl DataKind {
+    pub const ALL: [DataKind; 4] = [DataKind::Text, DataKind::Random, DataKind::Zeros, DataKind::Code];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataKind::Text => "text",
+            DataKind::Random => "random",
+            DataKind::Zeros => "zeros",
+            DataKind::Code => "code",
+        }
+    }
+}
+
+impl fmt::Display for DataKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DataKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DataKind::ALL.into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown data type '{}' (expected text, random, zeros or code)", s))
+    }
+}
+
+/// A reader producing `size` bytes of generated data
+///
+/// Random data depends on the seed; the patterned kinds are the same for
+/// every seed.
+pub struct TestData {
+    kind: DataKind,
+    remaining: u64,
+    /// Offset into the current pattern
+    position: usize,
+    /// Index of the current pattern
+    pattern: usize,
+    /// Bytes produced so far
+    offset: u64,
+    seed: u64,
+}
+
+impl TestData {
+    pub fn new(kind: DataKind, size: u64, seed: u64) -> Self {
+        Self { kind, remaining: size, position: 0, pattern: 0, offset: 0, seed }
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        match self.kind {
+            DataKind::Zeros => buf.fill(0),
+            DataKind::Random => {
+                // Each 8-byte word is a function of its index, so the output
+                // does not depend on how reads are split
+                let mut word = 0;
+                for (i, byte) in buf.iter_mut().enumerate() {
+                    let pos = self.offset + i as u64;
+                    if i == 0 || pos.is_multiple_of(8) {
+                        word = splitmix64(self.seed.wrapping_add(pos / 8));
+                    }
+                    *byte = (word >> (8 * (pos % 8))) as u8;
+                }
+            }
+            DataKind::Text => self.fill_patterns(buf, &[TEXT_PATTERN]),
+            DataKind::Code => self.fill_patterns(buf, &CODE_PATTERNS),
+        }
+        self.offset += buf.len() as u64;
+    }
+
+    /// Fill `buf` from a cycle of patterns, continuing where the last call stopped
+    fn fill_patterns(&mut self, buf: &mut [u8], patterns: &[&[u8]]) {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let current = &patterns[self.pattern][self.position..];
+            let n = current.len().min(buf.len() - filled);
+            buf[filled..filled + n].copy_from_slice(&current[..n]);
+            filled += n;
+            self.position += n;
+            if self.position == patterns[self.pattern].len() {
+                self.position = 0;
+                self.pattern = (self.pattern + 1) % patterns.len();
+            }
+        }
+    }
+}
+
+impl Read for TestData {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        self.fill(&mut buf[..n]);
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Generate `size` bytes of `kind` data in memory
+pub fn generate(kind: DataKind, size: usize, seed: u64) -> Vec<u8> {
+    let mut data = vec![0; size];
+    TestData::new(kind, size as u64, seed).fill(&mut data);
+    data
+}
+
+/// Parse a byte size with an optional binary suffix (`K`, `M`, `G`, `T`),
+/// such as `512K` or `1G`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let shift = match s[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        suffix => return Err(format!("unknown size suffix '{}' in '{}'", suffix, s)),
+    };
+    let value: u64 = digits.parse().map_err(|_| format!("invalid size '{}'", s))?;
+    value.checked_mul(1 << shift).ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamed_matches_generated() {
+        for kind in DataKind::ALL {
+            let expected = generate(kind, 100_003, DEFAULT_SEED);
+            let mut streamed = Vec::new();
+            let mut reader = TestData::new(kind, expected.len() as u64, DEFAULT_SEED);
+            let mut buf = [0; 333];
+            loop {
+                let n = reader.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                streamed.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(streamed, expected, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn test_generators_are_seeded() {
+        assert_eq!(generate(DataKind::Random, 4096, 1), generate(DataKind::Random, 4096, 1));
+        assert_ne!(generate(DataKind::Random, 4096, 1), generate(DataKind::Random, 4096, 2));
+        assert_eq!(generate(DataKind::Code, 4096, 1), generate(DataKind::Code, 4096, 2));
+        assert!(generate(DataKind::Zeros, 64, 1).iter().all(|&b| b == 0));
+        assert!(generate(DataKind::Text, 200, 1).starts_with(b"Lorem ipsum"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1234"), Ok(1234));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert_eq!(parse_size("2mb"), Ok(2 << 20));
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("G").is_err());
+        assert_eq!("code".parse(), Ok(DataKind::Code));
+        assert!("binary".parse::<DataKind>().is_err());
+    }
+}
@@ -0,0 +1,520 @@
+//! Test support: an in-memory filesystem and fixture builders
+//!
+//! `MemoryFs` implements both `FileSource` and `FileSystemSink`, so archive
+//! logic can be exercised without temporary directories or platform quirks
+//! such as umask, timestamp granularity or missing xattr support.
+//!
+//! ```
+//! use glifzip::testing::{random_tree, MemoryFs};
+//! use glifzip::{DirectoryCompressor, DirectoryCompressionConfig};
+//!
+//! let tree = random_tree(7, 20);
+//! let config = DirectoryCompressionConfig::default().with_progress(false);
+//! let archive = DirectoryCompressor::new(config).unwrap()
+//!     .compress_source(&tree, "fixture".into()).unwrap();
+//!
+//! let mut extracted = MemoryFs::new();
+//! DirectoryCompressor::extract_to_sink(&archive, &mut extracted, 1).unwrap();
+//! assert_eq!(extracted, tree);
+//! ```
+//!
+//! With the `proptest` feature, `property` adds roundtrip properties and
+//! strategies for arbitrary data and trees.
+
+pub mod corpus;
+pub mod data;
+
+#[cfg(any(test, feature = "proptest"))]
+pub mod property;
+
+#[cfg(any(test, feature = "proptest"))]
+pub use property::roundtrip_property;
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::archive::external::archive_id;
+use crate::archive::file_entry::FileType;
+use crate::archive::{ArchiveWriter, DirectoryCompressor, ExternalRef, FileEntry, FileSource, FileSystemSink, GlifArchive};
+use crate::verification::{calculate_sha256, hex_encode};
+
+/// Modification time given to fixture nodes (matches deterministic builds)
+pub const FIXTURE_MTIME: i64 = 1_735_689_600; // 2025-01-01T00:00:00Z
+
+/// Contents of an in-memory node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    File(Vec<u8>),
+    Directory,
+    Symlink(PathBuf),
+}
+
+/// A file, directory or symlink in a `MemoryFs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryNode {
+    pub kind: NodeKind,
+    pub mode: u32,
+    pub mtime: DateTime<Utc>,
+    pub xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryNode {
+    fn new(kind: NodeKind, mode: u32) -> Self {
+        Self {
+            kind,
+            mode,
+            mtime: fixture_mtime(),
+            xattrs: BTreeMap::new(),
+        }
+    }
+}
+
+/// In-memory tree of files keyed by relative path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFs {
+    nodes: BTreeMap<PathBuf, MemoryNode>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up a node by relative path
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&MemoryNode> {
+        self.nodes.get(path.as_ref())
+    }
+
+    /// Contents of a regular file
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&[u8]> {
+        match self.get(path).map(|node| &node.kind) {
+            Some(NodeKind::File(data)) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Relative paths of all nodes in archive order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.nodes.keys().map(PathBuf::as_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Paths whose nodes differ between the two trees or exist in only one
+    pub fn differences(&self, other: &MemoryFs) -> Vec<PathBuf> {
+        let paths: std::collections::BTreeSet<&PathBuf> = self.nodes.keys()
+            .chain(other.nodes.keys())
+            .filter(|path| self.nodes.get(*path) != other.nodes.get(*path))
+            .collect();
+        paths.into_iter().cloned().collect()
+    }
+
+    /// Insert a node, creating missing parent directories
+    pub fn insert(&mut self, path: impl AsRef<Path>, node: MemoryNode) -> Result<()> {
+        let path = normalize(path.as_ref())?;
+        self.create_parents(&path)?;
+        self.nodes.insert(path, node);
+        Ok(())
+    }
+
+    fn create_parents(&mut self, path: &Path) -> Result<()> {
+        for parent in path.ancestors().skip(1).filter(|p| !p.as_os_str().is_empty()) {
+            match self.nodes.get(parent).map(|node| &node.kind) {
+                Some(NodeKind::Directory) => break,
+                Some(_) => return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} is not a directory", parent.display())
+                )),
+                None => {
+                    self.nodes.insert(parent.to_path_buf(), MemoryNode::new(NodeKind::Directory, 0o40755));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&self, path: &Path) -> Result<&MemoryNode> {
+        self.nodes.get(path).ok_or_else(|| Error::new(
+            ErrorKind::NotFound,
+            format!("{} not found", path.display())
+        ))
+    }
+}
+
+impl FileSource for MemoryFs {
+    fn paths(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.nodes.keys().cloned().collect())
+    }
+
+    fn entry(&self, path: &Path) -> Result<FileEntry> {
+        let node = self.node(path)?;
+
+        let (file_type, size, symlink_target) = match &node.kind {
+            NodeKind::File(data) => (FileType::Regular, data.len() as u64, None),
+            NodeKind::Directory => (FileType::Directory, 0, None),
+            NodeKind::Symlink(target) => (FileType::Symlink, 0, Some(target.clone())),
+        };
+
+        Ok(FileEntry {
+            path: path.to_path_buf(),
+            file_type,
+            size,
+            mode: node.mode,
+            uid: 0,
+            gid: 0,
+            mtime: node.mtime,
+            atime: node.mtime,
+            symlink_target,
+            data_offset: 0,
+            sha256: String::new(),
+            xattrs: node.xattrs.clone().into_iter().collect(),
+            gaps: Vec::new(),
+            external: None,
+            mime_type: None,
+            compressed_size: None,
+        })
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        match &self.node(path)?.kind {
+            NodeKind::File(data) => Ok(data.clone()),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a regular file", path.display())
+            )),
+        }
+    }
+}
+
+impl FileSystemSink for MemoryFs {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        match self.nodes.get(&path).map(|node| &node.kind) {
+            Some(NodeKind::Directory) => Ok(()),
+            Some(_) => Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} exists and is not a directory", path.display())
+            )),
+            None => self.insert(path, MemoryNode::new(NodeKind::Directory, 0o40755)),
+        }
+    }
+
+    fn write_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        let path = normalize(path)?;
+        if let Some(NodeKind::Directory) = self.nodes.get(&path).map(|node| &node.kind) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is a directory", path.display())
+            ));
+        }
+        self.insert(path, MemoryNode::new(NodeKind::File(data.to_vec()), 0o100644))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        if self.nodes.contains_key(&path) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display())
+            ));
+        }
+        self.insert(path, MemoryNode::new(NodeKind::Symlink(target.to_path_buf()), 0o120777))
+    }
+
+    fn restore_metadata(&mut self, entry: &FileEntry) -> Result<()> {
+        let path = normalize(&entry.path)?;
+        let node = self.nodes.get_mut(&path).ok_or_else(|| Error::new(
+            ErrorKind::NotFound,
+            format!("{} not found", path.display())
+        ))?;
+
+        node.mode = entry.mode;
+        node.mtime = entry.mtime;
+        node.xattrs = entry.xattrs.iter().cloned().collect();
+        Ok(())
+    }
+}
+
+/// Reject absolute paths and `..` so a sink can never escape its root
+fn normalize(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a relative path inside the tree", path.display())
+            )),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "Empty path"));
+    }
+    Ok(normalized)
+}
+
+fn fixture_mtime() -> DateTime<Utc> {
+    DateTime::from_timestamp(FIXTURE_MTIME, 0).unwrap_or_default()
+}
+
+/// Builder for fixture trees
+///
+/// Paths must be relative; parent directories are created as needed.
+/// Panics on invalid paths, as fixtures are test code.
+#[derive(Debug, Clone, Default)]
+pub struct TreeBuilder {
+    fs: MemoryFs,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(mut self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.add(path, NodeKind::File(data.into()), 0o100644);
+        self
+    }
+
+    pub fn dir(mut self, path: &str) -> Self {
+        self.add(path, NodeKind::Directory, 0o40755);
+        self
+    }
+
+    pub fn symlink(mut self, path: &str, target: &str) -> Self {
+        self.add(path, NodeKind::Symlink(PathBuf::from(target)), 0o120777);
+        self
+    }
+
+    /// Set the permission bits of an existing node, keeping its type bits
+    pub fn mode(mut self, path: &str, permissions: u32) -> Self {
+        let node = self.node_mut(path);
+        node.mode = (node.mode & !0o7777) | (permissions & 0o7777);
+        self
+    }
+
+    /// Set the modification time of an existing node
+    pub fn mtime(mut self, path: &str, mtime: DateTime<Utc>) -> Self {
+        self.node_mut(path).mtime = mtime;
+        self
+    }
+
+    /// Add an extended attribute to an existing node
+    pub fn xattr(mut self, path: &str, name: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.node_mut(path).xattrs.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn build(self) -> MemoryFs {
+        self.fs
+    }
+
+    fn add(&mut self, path: &str, kind: NodeKind, mode: u32) {
+        self.fs.insert(path, MemoryNode::new(kind, mode))
+            .unwrap_or_else(|e| panic!("invalid fixture path {}: {}", path, e));
+    }
+
+    fn node_mut(&mut self, path: &str) -> &mut MemoryNode {
+        self.fs.nodes.get_mut(Path::new(path))
+            .unwrap_or_else(|| panic!("fixture path {} has not been added", path))
+    }
+}
+
+/// Directory archive of `tree`, recorded as the directory "tree"
+///
+/// Panics if compression fails, as fixtures are test code.
+pub fn archive(tree: &MemoryFs) -> Vec<u8> {
+    archive_with(tree, DirectoryCompressionConfig::default())
+}
+
+/// Directory archive of `tree` compressed with `config`, without progress output
+pub fn archive_with(tree: &MemoryFs, config: DirectoryCompressionConfig) -> Vec<u8> {
+    DirectoryCompressor::new(config.with_progress(false))
+        .and_then(|compressor| compressor.compress_source(tree, PathBuf::from("tree")))
+        .unwrap_or_else(|e| panic!("could not archive fixture tree: {}", e))
+}
+
+/// An `ArchiveWriter` with default settings and no progress output
+pub fn archive_writer() -> ArchiveWriter {
+    ArchiveWriter::new(DirectoryCompressionConfig::default().with_progress(false))
+        .unwrap_or_else(|e| panic!("could not create archive writer: {}", e))
+}
+
+/// Reference to `data` as stored in the directory archive at `archive`
+///
+/// Panics if the archive can't be opened.
+pub fn reference_to(archive: &Path, data: &[u8]) -> ExternalRef {
+    let header = GlifArchive::open(archive)
+        .unwrap_or_else(|e| panic!("could not open {}: {}", archive.display(), e))
+        .header()
+        .clone();
+    ExternalRef { archive_id: archive_id(&header), entry_hash: hex_encode(&calculate_sha256(data)) }
+}
+
+/// Run `f` in a fresh temporary directory named after `label`, removing it afterwards
+pub(crate) fn with_temp_dir<T>(label: &str, f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let dir = std::env::temp_dir().join(format!("glifzip-{}-{}-{}", label, std::process::id(), nanos));
+    std::fs::create_dir_all(&dir)?;
+    let outcome = f(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    outcome
+}
+
+/// Deterministic pseudo-random tree with about `entries` nodes
+///
+/// Mixes nested directories, files of varied size and compressibility,
+/// relative symlinks, unusual permissions, spread-out timestamps and
+/// xattrs. The same seed always produces the same tree.
+pub fn random_tree(seed: u64, entries: usize) -> MemoryFs {
+    let mut rng = XorShift::new(seed);
+    let mut builder = TreeBuilder::new();
+    let mut directories = vec![String::new()];
+    let mut files = Vec::new();
+
+    for i in 0..entries {
+        let parent = directories[rng.below(directories.len())].clone();
+        let join = |name: String| if parent.is_empty() { name } else { format!("{}/{}", parent, name) };
+
+        let path = match rng.below(10) {
+            0..=1 => {
+                let path = join(format!("dir{}", i));
+                builder = builder.dir(&path);
+                directories.push(path.clone());
+                path
+            }
+            2 if !files.is_empty() => {
+                let target: &String = &files[rng.below(files.len())];
+                let path = join(format!("link{}", i));
+                builder = builder.symlink(&path, &relative_target(&path, target));
+                // Symlink metadata is not restored on extraction
+                continue;
+            }
+            _ => {
+                let path = join(format!("file{}.dat", i));
+                let data = rng.contents();
+                builder = builder.file(&path, data);
+                files.push(path.clone());
+                path
+            }
+        };
+
+        if rng.below(4) == 0 {
+            builder = builder.mode(&path, [0o600, 0o700, 0o444, 0o750][rng.below(4)]);
+        }
+        if rng.below(4) == 0 {
+            builder = builder.xattr(&path, "user.glifzip.test", format!("value{}", i));
+        }
+        builder = builder.mtime(&path, fixture_mtime() - Duration::seconds(rng.below(86_400 * 365) as i64));
+    }
+
+    builder.build()
+}
+
+/// Path from the directory containing `link` to `target`
+fn relative_target(link: &str, target: &str) -> String {
+    let depth = link.matches('/').count();
+    format!("{}{}", "../".repeat(depth), target)
+}
+
+/// Small deterministic PRNG so fixtures don't need an extra dependency
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    /// File contents: empty, repetitive text or random bytes
+    fn contents(&mut self) -> Vec<u8> {
+        match self.below(4) {
+            0 => Vec::new(),
+            1 => b"glifzip fixture line\n".repeat(1 + self.below(200)),
+            _ => (0..self.below(8192)).map(|_| self.next() as u8).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::directory_compressor::DirectoryCompressionConfig;
+    use crate::DirectoryCompressor;
+
+    fn roundtrip(tree: &MemoryFs) -> MemoryFs {
+        let config = DirectoryCompressionConfig::default().with_progress(false);
+        let archive = DirectoryCompressor::new(config).unwrap()
+            .compress_source(tree, PathBuf::from("fixture")).unwrap();
+
+        let mut extracted = MemoryFs::new();
+        let report = DirectoryCompressor::extract_to_sink(&archive, &mut extracted, 1).unwrap();
+        assert!(report.is_complete());
+        extracted
+    }
+
+    #[test]
+    fn test_builder_roundtrip() {
+        let tree = TreeBuilder::new()
+            .file("docs/readme.txt", "hello")
+            .mode("docs/readme.txt", 0o600)
+            .xattr("docs/readme.txt", "user.tag", "red")
+            .symlink("docs/latest", "readme.txt")
+            .dir("empty")
+            .build();
+
+        // The parent directory is created implicitly
+        assert!(matches!(tree.get("docs").unwrap().kind, NodeKind::Directory));
+
+        let extracted = roundtrip(&tree);
+        assert_eq!(extracted, tree);
+        assert_eq!(extracted.file("docs/readme.txt"), Some(&b"hello"[..]));
+        assert_eq!(extracted.get("docs/readme.txt").unwrap().mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_random_tree_is_deterministic() {
+        let tree = random_tree(42, 60);
+        assert_eq!(tree, random_tree(42, 60));
+        assert_ne!(tree, random_tree(43, 60));
+        assert!(tree.paths().any(|p| matches!(tree.get(p).unwrap().kind, NodeKind::Symlink(_))));
+
+        assert_eq!(roundtrip(&tree), tree);
+    }
+
+    #[test]
+    fn test_sink_rejects_escaping_paths() {
+        let mut fs = MemoryFs::new();
+        assert!(fs.write_file(Path::new("../outside"), b"x").is_err());
+        assert!(fs.write_file(Path::new("/abs"), b"x").is_err());
+
+        fs.write_file(Path::new("a/b"), b"x").unwrap();
+        assert!(fs.write_file(Path::new("a/b/c"), b"x").is_err());
+        assert!(fs.write_file(Path::new("a"), b"x").is_err());
+    }
+}
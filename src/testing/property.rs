@@ -0,0 +1,170 @@
+//! Property-based roundtrip checks (enabled by the `proptest` feature)
+//!
+//! `roundtrip_property` compresses every generated input with each
+//! codec/mode combination from `codec_configs` and checks that it
+//! decompresses to the same bytes. The strategies here can also be
+//! combined with `proptest!` in downstream test suites.
+
+use std::path::PathBuf;
+
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+
+use super::{random_tree, MemoryFs};
+use crate::archive::directory_compressor::DirectoryCompressionConfig;
+use crate::{CompressionConfig, DirectoryCompressor, CHUNK_SIZE};
+
+/// Cases run by `roundtrip_property` (each case tries every codec configuration)
+pub const DEFAULT_CASES: u32 = 64;
+
+/// Levels covering negative, default, high and ultra parameter sets
+pub const ROUNDTRIP_LEVELS: &[i32] = &[-5, 1, 3, 19, 22];
+
+/// Every codec/mode combination exercised by the roundtrip property
+pub fn codec_configs() -> Vec<CompressionConfig> {
+    let mut configs = Vec::new();
+    for &level in ROUNDTRIP_LEVELS {
+        for use_lz4_decompression in [true, false] {
+            for threads in [1, 4] {
+                configs.push(CompressionConfig {
+                    level,
+                    threads,
+                    use_lz4_decompression,
+                    deterministic: true,
+                    ..CompressionConfig::default()
+                });
+            }
+        }
+    }
+    configs
+}
+
+/// Check that `data` survives compression and decompression with `config`
+pub fn check_roundtrip(data: &[u8], config: &CompressionConfig) -> Result<(), TestCaseError> {
+    let archive = crate::compress(data, config)
+        .map_err(|e| TestCaseError::fail(format!("compress failed with {:?}: {}", config, e)))?;
+    let restored = crate::decompress(&archive, config.threads)
+        .map_err(|e| TestCaseError::fail(format!("decompress failed with {:?}: {}", config, e)))?;
+
+    prop_assert_eq!(restored.len(), data.len(), "length changed with {:?}", config);
+    prop_assert!(restored == data, "contents changed with {:?}", config);
+    Ok(())
+}
+
+/// Check that every input from `strategy` roundtrips under all `codec_configs`
+pub fn roundtrip_property<S>(strategy: S) -> Result<(), TestError<Vec<u8>>>
+where
+    S: Strategy<Value = Vec<u8>>,
+{
+    roundtrip_property_with(Config::with_cases(DEFAULT_CASES), strategy)
+}
+
+/// `roundtrip_property` with an explicit proptest configuration
+pub fn roundtrip_property_with<S>(config: Config, strategy: S) -> Result<(), TestError<Vec<u8>>>
+where
+    S: Strategy<Value = Vec<u8>>,
+{
+    let configs = codec_configs();
+    TestRunner::new(config).run(&strategy, |data| {
+        configs.iter().try_for_each(|config| check_roundtrip(&data, config))
+    })
+}
+
+/// Arbitrary byte strings up to `max_len`, biased towards compressible data
+pub fn arb_data(max_len: usize) -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..=max_len),
+        (prop::collection::vec(any::<u8>(), 1..=16), 0..=max_len)
+            .prop_map(|(pattern, len)| pattern.iter().copied().cycle().take(len).collect()),
+    ]
+}
+
+/// Arbitrary file trees with up to `max_entries` nodes
+pub fn arb_tree(max_entries: usize) -> impl Strategy<Value = MemoryFs> {
+    (any::<u64>(), 0..=max_entries).prop_map(|(seed, entries)| random_tree(seed, entries))
+}
+
+/// Sizes within `max_offset` bytes of the first chunk boundaries
+pub fn chunk_boundary_sizes(max_offset: usize) -> impl Strategy<Value = usize> {
+    let max_offset = max_offset.min(CHUNK_SIZE - 1) as i64;
+    (1..=2usize, -max_offset..=max_offset)
+        .prop_map(|(chunks, offset)| (chunks * CHUNK_SIZE).saturating_add_signed(offset as isize))
+}
+
+/// Cheap data of `len` bytes: zeros with a seeded marker every 4 KB
+///
+/// Large enough inputs to cross chunk boundaries are mostly zeros so they
+/// compress quickly, while the markers catch reordered or dropped chunks.
+pub fn sparse_data(len: usize, seed: u64) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    for (i, byte) in data.iter_mut().enumerate().step_by(4096) {
+        *byte = (seed.wrapping_add(i as u64 / 4096) % 251) as u8 + 1;
+    }
+    data
+}
+
+/// Check that a tree survives archiving and extraction unchanged
+pub fn check_tree_roundtrip(tree: &MemoryFs, config: &CompressionConfig) -> Result<(), TestCaseError> {
+    let config = DirectoryCompressionConfig::new(config.clone()).with_progress(false);
+    let archive = DirectoryCompressor::new(config)
+        .and_then(|compressor| compressor.compress_source(tree, PathBuf::from("tree")))
+        .map_err(|e| TestCaseError::fail(format!("archiving failed: {}", e)))?;
+
+    let mut extracted = MemoryFs::new();
+    let report = DirectoryCompressor::extract_to_sink(&archive, &mut extracted, 1)
+        .map_err(|e| TestCaseError::fail(format!("extraction failed: {}", e)))?;
+
+    prop_assert!(report.is_complete(), "incomplete extraction: {:?}", report);
+    prop_assert_eq!(&extracted, tree);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arbitrary_data_roundtrips() {
+        roundtrip_property_with(Config::with_cases(16), arb_data(64 * 1024)).unwrap();
+    }
+
+    #[test]
+    fn test_empty_and_tiny_inputs_roundtrip() {
+        roundtrip_property_with(Config::with_cases(16), prop::collection::vec(any::<u8>(), 0..=4)).unwrap();
+    }
+
+    #[test]
+    fn test_codec_configs_are_valid() {
+        for config in codec_configs() {
+            config.validate().unwrap();
+        }
+    }
+
+    proptest! {
+        #![proptest_config(Config::with_cases(32))]
+
+        #[test]
+        fn test_arbitrary_trees_roundtrip(tree in arb_tree(40), lz4 in any::<bool>()) {
+            let config = CompressionConfig { use_lz4_decompression: lz4, ..CompressionConfig::fast() };
+            check_tree_roundtrip(&tree, &config)?;
+        }
+    }
+
+    proptest! {
+        // Each case compresses over 128 MB, so keep the count low
+        #![proptest_config(Config::with_cases(3))]
+
+        #[test]
+        fn test_chunk_boundary_sizes_roundtrip(len in chunk_boundary_sizes(4096), seed in any::<u64>(), lz4 in any::<bool>()) {
+            let data = sparse_data(len, seed);
+            // Multithreaded so data over one chunk uses the chunked framing
+            let config = CompressionConfig { level: 1, threads: 2, use_lz4_decompression: lz4, ..CompressionConfig::default() };
+            let archive = crate::compress(&data, &config).unwrap();
+
+            // Framed archives must decompress with any thread count
+            for threads in [1, 2] {
+                prop_assert!(crate::decompress(&archive, threads).unwrap() == data, "threads={}", threads);
+            }
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Calibration of compression settings for this machine
+//!
+//! `glifzip tune` compresses and decompresses sample data with each
+//! combination of chunk size, thread count and LZ4 layer, and saves the
+//! fastest combination to the user config file. `glifzip create --preset
+//! tuned` then compresses with those settings.
+//!
+//! The user config file is JSON at `$GLIFZIP_CONFIG`,
+//! `$XDG_CONFIG_HOME/glifzip/config.json` or `~/.config/glifzip/config.json`.
+//! Tuning only rewrites its `tuned` key; other keys are kept.
+
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bench::{bench_glifzip, BenchResult};
+use crate::compression::{resolve_threads, CHUNK_SIZE};
+use crate::CompressionConfig;
+
+/// Environment variable overriding the user config file path
+pub const CONFIG_PATH_ENV: &str = "GLIFZIP_CONFIG";
+
+/// Chunk sizes tried by the calibration
+pub const CHUNK_SIZE_CANDIDATES: [usize; 4] = [4 << 20, 16 << 20, 64 << 20, CHUNK_SIZE];
+
+/// Key of the tuned settings in the user config file
+const TUNED_KEY: &str = "tuned";
+
+/// User config file in `$GLIFZIP_CONFIG`, `$XDG_CONFIG_HOME/glifzip` or `~/.config/glifzip`
+pub fn user_config_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_PATH_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config"))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "HOME is not set"))?,
+    };
+    Ok(config.join("glifzip").join("config.json"))
+}
+
+/// Settings chosen by `glifzip tune`, stored in the user config file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TunedSettings {
+    pub level: i32,
+    pub threads: usize,
+    pub chunk_size: usize,
+    pub use_lz4_decompression: bool,
+
+    /// When the calibration ran (RFC 3339)
+    pub tuned_at: String,
+
+    /// Size of the sample and throughput measured on it, in bytes per second
+    pub sample_bytes: u64,
+    pub compress_throughput: f64,
+    pub decompress_throughput: f64,
+}
+
+impl TunedSettings {
+    /// Settings of a calibration run
+    pub fn from_calibration(calibration: &Calibration) -> Self {
+        let config = &calibration.config;
+        let result = &calibration.result;
+        Self {
+            level: config.level,
+            threads: config.threads,
+            chunk_size: config.chunk_size,
+            use_lz4_decompression: config.use_lz4_decompression,
+            tuned_at: chrono::Utc::now().to_rfc3339(),
+            sample_bytes: result.input_bytes,
+            compress_throughput: result.compress_throughput(),
+            decompress_throughput: result.decompress_throughput(),
+        }
+    }
+
+    /// Compression configuration with these settings
+    pub fn config(&self) -> Result<CompressionConfig> {
+        Ok(CompressionConfig::builder()
+            .level(self.level)
+            .threads(self.threads)
+            .chunk_size(self.chunk_size)
+            .use_lz4_decompression(self.use_lz4_decompression)
+            .build()?)
+    }
+
+    /// Read the tuned settings from the user config file at `path`, if any
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let mut config = match read_user_config(path)? {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        config.remove(TUNED_KEY)
+            .map(|tuned| serde_json::from_value(tuned).map_err(|e| invalid_config(path, e)))
+            .transpose()
+    }
+
+    /// Write these settings to the user config file at `path`, keeping its other keys
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut config = read_user_config(path)?.unwrap_or_default();
+        config.insert(TUNED_KEY.to_string(), serde_json::to_value(self)?);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut json = serde_json::to_vec_pretty(&config)?;
+        json.push(b'\n');
+        fs::write(path, json)
+    }
+}
+
+pub(crate) fn read_user_config(path: &Path) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map(Some).map_err(|e| invalid_config(path, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn invalid_config(path: &Path, e: serde_json::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: invalid config file: {}", path.display(), e))
+}
+
+/// Named compression settings for `glifzip create --preset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Fast,
+    Balanced,
+    High,
+    /// The settings saved by `glifzip tune`
+    Tuned,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Fast, Preset::Balanced, Preset::High, Preset::Tuned];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Fast => "fast",
+            Preset::Balanced => "balanced",
+            Preset::High => "high",
+            Preset::Tuned => "tuned",
+        }
+    }
+
+    /// Compression configuration of the preset; `Tuned` reads the user config file
+    pub fn config(&self) -> Result<CompressionConfig> {
+        match self {
+            Preset::Fast => Ok(CompressionConfig::fast()),
+            Preset::Balanced => Ok(CompressionConfig::balanced()),
+            Preset::High => Ok(CompressionConfig::high_compression()),
+            Preset::Tuned => {
+                let path = user_config_path()?;
+                TunedSettings::load(&path)?
+                    .ok_or_else(|| Error::new(
+                        ErrorKind::NotFound,
+                        format!("no tuned settings in {}; run `glifzip tune` first", path.display())
+                    ))?
+                    .config()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Preset::ALL.into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| format!("unknown preset '{}' (expected fast, balanced, high or tuned)", s))
+    }
+}
+
+/// One combination of settings and how it performed on the sample
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub config: CompressionConfig,
+    pub result: BenchResult,
+}
+
+impl Calibration {
+    /// Time to compress and then decompress the sample; lower is better
+    pub fn score(&self) -> Duration {
+        self.result.compress_time + self.result.decompress_time
+    }
+}
+
+/// Configurations to try at `level` on a sample of `sample_len` bytes
+///
+/// Chunk sizes beyond the first that covers the whole sample all give one
+/// chunk, so they are left out. Thread counts are 1, half and all of
+/// `threads` (0: all cores).
+pub fn candidates(level: i32, sample_len: usize, threads: usize) -> Vec<CompressionConfig> {
+    let max_threads = resolve_threads(threads);
+    let mut thread_counts = vec![1, max_threads.div_ceil(2), max_threads];
+    thread_counts.dedup();
+
+    let single_chunk = CHUNK_SIZE_CANDIDATES.iter().position(|&size| size >= sample_len);
+    let chunk_sizes = match single_chunk {
+        Some(last) => &CHUNK_SIZE_CANDIDATES[..=last],
+        None => &CHUNK_SIZE_CANDIDATES[..],
+    };
+
+    let mut configs = Vec::new();
+    for &chunk_size in chunk_sizes {
+        for &threads in &thread_counts {
+            for use_lz4_decompression in [true, false] {
+                configs.push(CompressionConfig {
+                    level,
+                    threads,
+                    chunk_size,
+                    use_lz4_decompression,
+                    ..CompressionConfig::default()
+                });
+            }
+        }
+    }
+    configs
+}
+
+/// Compress and decompress `sample` with each configuration
+///
+/// `on_result` sees each run as it finishes.
+pub fn calibrate(
+    sample: &[u8],
+    configs: &[CompressionConfig],
+    mut on_result: impl FnMut(&Calibration),
+) -> Result<Vec<Calibration>> {
+    configs.iter()
+        .map(|config| {
+            let calibration = Calibration { config: config.clone(), result: bench_glifzip(sample, config)? };
+            on_result(&calibration);
+            Ok(calibration)
+        })
+        .collect()
+}
+
+/// The fastest calibration run
+pub fn best(calibrations: &[Calibration]) -> Option<&Calibration> {
+    calibrations.iter().min_by_key(|calibration| calibration.score())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let configs = candidates(8, 10 << 20, 4);
+        // 4 MiB and 16 MiB chunks, 1, 2 and 4 threads, with and without LZ4
+        assert_eq!(configs.len(), 2 * 3 * 2);
+        assert!(configs.iter().all(|config| config.level == 8 && config.validate().is_ok()));
+        assert_eq!(configs.last().unwrap().chunk_size, 16 << 20);
+
+        assert_eq!(candidates(8, 1 << 30, 1).len(), CHUNK_SIZE_CANDIDATES.len() * 2);
+    }
+
+    #[test]
+    fn test_calibrate_picks_fastest() {
+        let sample = crate::testing::data::generate(crate::testing::data::DataKind::Text, 1 << 20, 1);
+        let configs = candidates(1, sample.len(), 2);
+        let mut seen = 0;
+        let calibrations = calibrate(&sample, &configs, |_| seen += 1).unwrap();
+        assert_eq!(seen, configs.len());
+
+        let fastest = best(&calibrations).unwrap();
+        assert!(calibrations.iter().all(|calibration| fastest.score() <= calibration.score()));
+
+        let tuned = TunedSettings::from_calibration(fastest);
+        assert_eq!(tuned.sample_bytes, 1 << 20);
+        let config = tuned.config().unwrap();
+        assert_eq!(config.chunk_size, fastest.config.chunk_size);
+        assert_eq!(config.use_lz4_decompression, fastest.config.use_lz4_decompression);
+    }
+
+    #[test]
+    fn test_save_keeps_other_keys() {
+        crate::testing::with_temp_dir("tune", |dir| {
+            let path = dir.join("glifzip").join("config.json");
+            assert_eq!(TunedSettings::load(&path)?, None);
+
+            let tuned = TunedSettings {
+                level: 8,
+                threads: 4,
+                chunk_size: 16 << 20,
+                use_lz4_decompression: false,
+                tuned_at: "2026-01-01T00:00:00+00:00".to_string(),
+                sample_bytes: 1 << 26,
+                compress_throughput: 1e9,
+                decompress_throughput: 4e9,
+            };
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, br#"{"other": {"kept": true}}"#)?;
+            tuned.save(&path)?;
+            assert_eq!(TunedSettings::load(&path)?, Some(tuned));
+
+            let config: serde_json::Value = serde_json::from_slice(&fs::read(&path)?)?;
+            assert_eq!(config["other"]["kept"], true);
+
+            fs::write(&path, b"not json")?;
+            assert_eq!(TunedSettings::load(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+            Ok(())
+        }).unwrap();
+
+        assert_eq!("tuned".parse(), Ok(Preset::Tuned));
+        assert!("slow".parse::<Preset>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Archive health tracking
+//!
+//! After a successful `verify`, a small JSON companion file
+//! (`<archive>.verified`) records when the archive was last verified.
+//! `scan_archives` reads these records back to report archives that have
+//! never been verified or have not been verified recently.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// Suffix appended to an archive path to form its verification record
+pub const VERIFICATION_RECORD_SUFFIX: &str = ".verified";
+
+/// Result of the last successful verification of an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationRecord {
+    /// When the archive was last verified (RFC 3339)
+    pub last_verified: DateTime<Utc>,
+
+    /// Archive file size at verification time
+    pub archive_size: u64,
+
+    /// Archive hash recorded in the header ("sha256:<hex>")
+    pub archive_hash: String,
+
+    /// glifzip version that performed the verification
+    pub verified_by: String,
+}
+
+impl VerificationRecord {
+    /// Create a record for an archive verified now
+    pub fn now(archive_size: u64, archive_hash: String) -> Self {
+        Self {
+            last_verified: Utc::now(),
+            archive_size,
+            archive_hash,
+            verified_by: format!("glifzip v{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// Path of the record companion file for an archive
+    pub fn path_for(archive: &Path) -> PathBuf {
+        let mut name = archive.as_os_str().to_os_string();
+        name.push(VERIFICATION_RECORD_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Write the record next to the archive
+    pub fn save(&self, archive: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        Ok(fs::write(Self::path_for(archive), json)?)
+    }
+
+    /// Load the record for an archive, if one exists
+    pub fn load(archive: &Path) -> Result<Option<Self>> {
+        match fs::read(Self::path_for(archive)) {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Verification state of a single archive
+#[derive(Debug, Clone)]
+pub struct ArchiveHealth {
+    /// Path to the archive
+    pub path: PathBuf,
+
+    /// Current archive size in bytes
+    pub size: u64,
+
+    /// Last modification time of the archive
+    pub modified: DateTime<Utc>,
+
+    /// Last verification, if the record still matches the archive on disk
+    pub last_verified: Option<DateTime<Utc>>,
+}
+
+impl ArchiveHealth {
+    /// Whether the archive needs verifying given a maximum acceptable age
+    pub fn is_stale(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        match self.last_verified {
+            Some(verified) => verified < self.modified || now - verified > max_age,
+            None => true,
+        }
+    }
+}
+
+/// Scan a directory (non-recursively) for .glif archives and their verification state
+pub fn scan_archives<P: AsRef<Path>>(directory: P) -> Result<Vec<ArchiveHealth>> {
+    let mut archives = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("glif") {
+            continue;
+        }
+
+        let metadata = fs::metadata(&path)?;
+        let modified = DateTime::from(metadata.modified()?);
+
+        // A record only counts if the archive hasn't been replaced since
+        let last_verified = VerificationRecord::load(&path)
+            .ok()
+            .flatten()
+            .filter(|record| record.archive_size == metadata.len())
+            .map(|record| record.last_verified);
+
+        archives.push(ArchiveHealth {
+            path,
+            size: metadata.len(),
+            modified,
+            last_verified,
+        });
+    }
+
+    archives.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(archives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = temp_dir.path().join("data.glif");
+        fs::write(&archive, b"archive").unwrap();
+
+        assert!(VerificationRecord::load(&archive).unwrap().is_none());
+
+        let record = VerificationRecord::now(7, "sha256:00".to_string());
+        record.save(&archive).unwrap();
+
+        assert!(temp_dir.path().join("data.glif.verified").exists());
+        let loaded = VerificationRecord::load(&archive).unwrap().unwrap();
+        assert_eq!(loaded.archive_size, 7);
+        assert_eq!(loaded.last_verified, record.last_verified);
+    }
+
+    #[test]
+    fn test_scan_archives() {
+        let temp_dir = TempDir::new().unwrap();
+        let verified = temp_dir.path().join("a.glif");
+        let unverified = temp_dir.path().join("b.glif");
+        let replaced = temp_dir.path().join("c.glif");
+        fs::write(&verified, b"aaaa").unwrap();
+        fs::write(&unverified, b"bbbb").unwrap();
+        fs::write(&replaced, b"cccc").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        VerificationRecord::now(4, String::new()).save(&verified).unwrap();
+        VerificationRecord::now(3, String::new()).save(&replaced).unwrap();
+
+        let health = scan_archives(temp_dir.path()).unwrap();
+        assert_eq!(health.len(), 3);
+        assert!(health[0].last_verified.is_some());
+        assert!(health[1].last_verified.is_none());
+        assert!(health[2].last_verified.is_none());
+
+        let now = Utc::now();
+        assert!(!health[0].is_stale(Duration::days(30), now));
+        assert!(health[0].is_stale(Duration::days(30), now + Duration::days(31)));
+        assert!(health[1].is_stale(Duration::days(30), now));
+    }
+}
@@ -0,0 +1,49 @@
+//! How thoroughly to verify an archive
+//!
+//! Each level includes the checks of the ones before it and costs more:
+//! `Structural` reads only the header and sidecar, `Archive` hashes the
+//! compressed data, and `Full` also decompresses it to check the payload
+//! hash (and, for directory archives, every file's hash).
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How thoroughly `verify_archive_with_level` checks an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum VerifyLevel {
+    /// Header checksum, sidecar and archive data length
+    Structural,
+    /// The archive hash of the compressed data, as `verify_archive` does
+    #[default]
+    Archive,
+    /// The payload hash and size too, which means decompressing
+    Full,
+}
+
+impl VerifyLevel {
+    pub const ALL: [VerifyLevel; 3] = [VerifyLevel::Structural, VerifyLevel::Archive, VerifyLevel::Full];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VerifyLevel::Structural => "structural",
+            VerifyLevel::Archive => "archive",
+            VerifyLevel::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for VerifyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for VerifyLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        VerifyLevel::ALL.into_iter()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| format!("unknown verify level '{}' (expected structural, archive or full)", s))
+    }
+}
@@ -0,0 +1,274 @@
+//! Corruption localization
+//!
+//! The archive data is split into fixed-size integrity blocks whose short
+//! hashes are stored in the sidecar. When the whole-archive SHA256 check
+//! fails, the blocks are re-hashed to find which byte ranges are damaged
+//! and which payload region (and, for directory archives, which entries)
+//! may be affected.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+use crate::archive::FileEntry;
+use super::{calculate_sha256, hex_encode};
+
+/// Smallest integrity block size (1 MB)
+pub const MIN_INTEGRITY_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of integrity blocks, bounding the sidecar size
+pub const MAX_INTEGRITY_BLOCKS: usize = 256;
+
+/// Hex characters kept per block hash (64 bits is ample for localization)
+const BLOCK_HASH_LEN: usize = 16;
+
+/// Maximum number of affected entries listed in an error message
+const MAX_LISTED_ENTRIES: usize = 20;
+
+/// Block size used for data of the given length
+pub fn integrity_block_size(len: usize) -> usize {
+    len.div_ceil(MAX_INTEGRITY_BLOCKS).max(MIN_INTEGRITY_BLOCK_SIZE)
+}
+
+/// Short hashes of each `block_size` block of `data`
+pub fn block_hashes(data: &[u8], block_size: usize) -> Vec<String> {
+    data.chunks(block_size.max(1))
+        .map(|block| hex_encode(&calculate_sha256(block))[..BLOCK_HASH_LEN].to_string())
+        .collect()
+}
+
+/// Computes `block_hashes` over data supplied in pieces
+#[derive(Clone)]
+pub struct BlockHasher {
+    block_size: usize,
+    block: Sha256,
+    filled: usize,
+    hashes: Vec<String>,
+}
+
+impl BlockHasher {
+    pub fn new(block_size: usize) -> Self {
+        Self { block_size: block_size.max(1), block: Sha256::new(), filled: 0, hashes: Vec::new() }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = data.len().min(self.block_size - self.filled);
+            self.block.update(&data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled == self.block_size {
+                self.finish_block();
+            }
+        }
+    }
+
+    /// Hashes of every block, including a final partial one
+    pub fn finish(mut self) -> Vec<String> {
+        if self.filled > 0 {
+            self.finish_block();
+        }
+        self.hashes
+    }
+
+    fn finish_block(&mut self) {
+        let digest: [u8; 32] = self.block.finalize_reset().into();
+        self.hashes.push(hex_encode(&digest)[..BLOCK_HASH_LEN].to_string());
+        self.filled = 0;
+    }
+}
+
+/// A damaged range of the archive data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptRegion {
+    /// Index of the integrity block
+    pub block: usize,
+    /// Start offset within the archive data
+    pub start: u64,
+    /// End offset (exclusive) within the archive data
+    pub end: u64,
+}
+
+/// Compare `data` against recorded block hashes and return the damaged blocks
+///
+/// A length mismatch marks everything from the first missing or extra block
+/// onward as damaged.
+pub fn locate_corruption(data: &[u8], block_size: usize, expected: &[String]) -> Vec<CorruptRegion> {
+    let actual = block_hashes(data, block_size);
+    let blocks = actual.len().max(expected.len());
+
+    (0..blocks)
+        .filter(|&i| actual.get(i) != expected.get(i))
+        .map(|i| {
+            let start = (i * block_size) as u64;
+            let end = (((i + 1) * block_size) as u64).min(data.len() as u64).max(start);
+            CorruptRegion { block: i, start, end }
+        })
+        .collect()
+}
+
+/// Error describing where an archive is damaged
+///
+/// Carried inside `std::io::Error` (kind `InvalidData`) so callers can
+/// downcast it to enrich the report, e.g. with affected entries.
+#[derive(Debug, Clone)]
+pub struct CorruptionError {
+    /// Expected archive hash (hex)
+    pub expected: String,
+    /// Calculated archive hash (hex)
+    pub actual: String,
+    /// Damaged regions, relative to the start of the archive data
+    pub regions: Vec<CorruptRegion>,
+    /// Offset of the archive data within the file
+    pub data_offset: u64,
+    /// Payload byte range that may be affected, if it could be determined
+    pub payload_range: Option<(u64, u64)>,
+    /// Directory archive entries whose data overlaps the affected payload range
+    pub affected_entries: Vec<PathBuf>,
+}
+
+impl CorruptionError {
+    /// Estimate the affected payload range from the damaged archive regions
+    ///
+    /// Compressed chunks map to `chunk_size` payload chunks in order, so
+    /// damage is mapped proportionally and widened to whole chunks.
+    pub fn estimate_payload_range(&mut self, archive_len: u64, payload_size: u64, chunk_size: u64) {
+        let (Some(first), Some(last)) = (self.regions.first(), self.regions.last()) else {
+            return;
+        };
+
+        let chunks = payload_size.div_ceil(chunk_size).max(1);
+        let archive_len = archive_len.max(1);
+        let first_chunk = (first.start * chunks / archive_len).min(chunks - 1);
+        let last_chunk = ((last.end.max(1) - 1) * chunks / archive_len).min(chunks - 1);
+
+        self.payload_range = Some((first_chunk * chunk_size, ((last_chunk + 1) * chunk_size).min(payload_size)));
+    }
+
+    /// Record the entries whose data overlaps the affected payload range
+    pub fn find_affected_entries(&mut self, entries: &[FileEntry]) {
+        let Some((start, end)) = self.payload_range else {
+            return;
+        };
+
+        self.affected_entries = entries.iter()
+            .filter(|e| e.external.is_none() && e.size > 0 && e.data_offset < end && e.data_offset + e.size > start)
+            .map(|e| e.path.clone())
+            .collect();
+    }
+
+    /// Wrap in an `std::io::Error`
+    pub fn into_io_error(self) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, self)
+    }
+
+    /// Extract a `CorruptionError` carried by an `std::io::Error`
+    pub fn from_io_error(error: &std::io::Error) -> Option<&CorruptionError> {
+        error.get_ref().and_then(|e| e.downcast_ref::<CorruptionError>())
+    }
+}
+
+impl fmt::Display for CorruptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SHA256 hash mismatch. Expected: {}, Got: {}", self.expected, self.actual)?;
+
+        if self.regions.is_empty() {
+            return write!(f, " (no per-block checksums recorded; damaged region unknown)");
+        }
+
+        write!(f, "\n  Damaged blocks:")?;
+        for region in &self.regions {
+            write!(
+                f,
+                "\n    block {}: bytes {}..{}",
+                region.block,
+                self.data_offset + region.start,
+                self.data_offset + region.end
+            )?;
+        }
+
+        if let Some((start, end)) = self.payload_range {
+            write!(f, "\n  Payload bytes possibly affected: {}..{}", start, end)?;
+        }
+
+        if !self.affected_entries.is_empty() {
+            write!(f, "\n  Entries possibly affected ({}):", self.affected_entries.len())?;
+            for path in self.affected_entries.iter().take(MAX_LISTED_ENTRIES) {
+                write!(f, "\n    {}", path.display())?;
+            }
+            if self.affected_entries.len() > MAX_LISTED_ENTRIES {
+                write!(f, "\n    ... and {} more", self.affected_entries.len() - MAX_LISTED_ENTRIES)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for CorruptionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integrity_block_size() {
+        assert_eq!(integrity_block_size(100), MIN_INTEGRITY_BLOCK_SIZE);
+        let large = 1024 * MIN_INTEGRITY_BLOCK_SIZE;
+        assert_eq!(integrity_block_size(large), large / MAX_INTEGRITY_BLOCKS);
+    }
+
+    #[test]
+    fn test_block_hasher_matches_block_hashes() {
+        let data: Vec<u8> = (0..10_500u32).map(|i| (i % 251) as u8).collect();
+        let mut hasher = BlockHasher::new(1000);
+        for piece in data.chunks(333) {
+            hasher.update(piece);
+        }
+        assert_eq!(hasher.finish(), block_hashes(&data, 1000));
+        assert!(BlockHasher::new(1000).finish().is_empty());
+    }
+
+    #[test]
+    fn test_locate_corruption() {
+        let mut data = vec![7u8; 10_000];
+        let hashes = block_hashes(&data, 1000);
+        assert_eq!(hashes.len(), 10);
+        assert!(locate_corruption(&data, 1000, &hashes).is_empty());
+
+        data[4500] ^= 0xff;
+        let regions = locate_corruption(&data, 1000, &hashes);
+        assert_eq!(regions, vec![CorruptRegion { block: 4, start: 4000, end: 5000 }]);
+
+        // Truncation damages the tail
+        let regions = locate_corruption(&data[..9500], 1000, &hashes);
+        assert_eq!(regions.last().unwrap().block, 9);
+    }
+
+    #[test]
+    fn test_affected_entries() {
+        let mut error = CorruptionError {
+            expected: String::new(),
+            actual: String::new(),
+            regions: vec![CorruptRegion { block: 1, start: 500, end: 600 }],
+            data_offset: 0,
+            payload_range: None,
+            affected_entries: Vec::new(),
+        };
+
+        // Two payload chunks of 100 bytes; damage in the second half of the archive
+        error.estimate_payload_range(1000, 200, 100);
+        assert_eq!(error.payload_range, Some((100, 200)));
+
+        let mut first = FileEntry::directory(PathBuf::from("a.txt"), 0o644, 0, 0);
+        first.size = 100;
+        let mut second = FileEntry::directory(PathBuf::from("b.txt"), 0o644, 0, 0);
+        second.size = 100;
+        second.data_offset = 100;
+
+        error.find_affected_entries(&[first, second]);
+        assert_eq!(error.affected_entries, vec![PathBuf::from("b.txt")]);
+        assert!(error.to_string().contains("b.txt"));
+    }
+}
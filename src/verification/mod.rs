@@ -1,3 +1,11 @@
 pub mod sha256;
+pub mod health;
+pub mod localize;
+pub mod sweep;
+pub mod level;
 
-pub use sha256::{calculate_sha256, verify_sha256, hex_encode, hex_decode};
+pub use sha256::{calculate_sha256, calculate_sha256_with_progress, verify_sha256, hex_encode, hex_decode};
+pub use health::{VerificationRecord, ArchiveHealth, scan_archives};
+pub use localize::{CorruptionError, CorruptRegion};
+pub use sweep::{SweepReport, SweepOutcome, SweepStatus, sweep_archives, verify_archive_file, verify_archive_file_at};
+pub use level::VerifyLevel;
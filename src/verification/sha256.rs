@@ -1,5 +1,7 @@
 use sha2::{Sha256, Digest};
-use std::io::{Result, Error, ErrorKind};
+
+use crate::error::{GlifError, Result};
+use crate::progress::ProgressReporter;
 
 pub fn calculate_sha256(data: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
@@ -10,44 +12,41 @@ pub fn calculate_sha256(data: &[u8]) -> [u8; 32] {
     hash
 }
 
-pub fn verify_sha256(data: &[u8], expected_hash: &[u8; 32]) -> Result<()> {
-    let calculated_hash = calculate_sha256(data);
+/// Bytes hashed between progress updates (16 MiB)
+pub const HASH_PROGRESS_STEP: usize = 16 * 1024 * 1024;
 
-    if &calculated_hash == expected_hash {
-        Ok(())
-    } else {
-        Err(Error::new(
-            ErrorKind::InvalidData,
-            format!(
-                "SHA256 hash mismatch. Expected: {}, Got: {}",
-                hex_encode(expected_hash),
-                hex_encode(&calculated_hash)
-            )
-        ))
+/// Calculate a SHA256 hash, reporting how many bytes are done as it goes
+pub fn calculate_sha256_with_progress(data: &[u8], progress: &mut dyn ProgressReporter) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut done = 0;
+    for chunk in data.chunks(HASH_PROGRESS_STEP) {
+        hasher.update(chunk);
+        done += chunk.len() as u64;
+        progress.advance(done);
     }
+    hasher.finalize().into()
 }
 
-pub fn hex_encode(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect()
-}
+pub fn verify_sha256(data: &[u8], expected_hash: &[u8; 32]) -> Result<()> {
+    let calculated_hash = calculate_sha256(data);
 
-pub fn hex_decode(s: &str) -> Result<[u8; 32]> {
-    if s.len() != 64 {
-        return Err(Error::new(
-            ErrorKind::InvalidInput,
-            format!("Invalid hex string length: {} (expected 64)", s.len())
-        ));
-    }
+    check_sha256(&calculated_hash, expected_hash)
+}
 
-    let mut bytes = [0u8; 32];
-    for i in 0..32 {
-        let hex_byte = &s[i*2..i*2+2];
-        bytes[i] = u8::from_str_radix(hex_byte, 16)
-            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+/// Compare an already calculated hash against the expected one
+pub fn check_sha256(calculated_hash: &[u8; 32], expected_hash: &[u8; 32]) -> Result<()> {
+    if calculated_hash == expected_hash {
+        Ok(())
+    } else {
+        Err(GlifError::HashMismatch {
+            expected: hex_encode(expected_hash),
+            actual: hex_encode(calculated_hash),
+        })
     }
-    Ok(bytes)
 }
 
+pub use crate::encoding::{hex_decode, hex_encode};
+
 #[cfg(test)]
 mod tests {
     use super::*;
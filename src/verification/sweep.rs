@@ -0,0 +1,316 @@
+//! Recursive verification sweeps
+//!
+//! Finds every `.glif` archive under a directory tree and verifies each
+//! one, continuing past failures so a single bad archive does not hide the
+//! state of the rest. The consolidated report can be written as JSON for
+//! backup monitoring. Successful verifications are recorded next to each
+//! archive, as a plain `verify` does.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::error::{GlifError, Result};
+use crate::format::{GlifHeader, GlifSidecar};
+use crate::progress::ProgressReporter;
+use crate::verification::{VerificationRecord, VerifyLevel};
+use crate::DecompressionConfig;
+
+/// Outcome of verifying a single archive in a sweep
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepStatus {
+    Verified,
+    Failed,
+}
+
+/// Per-archive record in a sweep report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepOutcome {
+    /// Path of the archive
+    pub path: PathBuf,
+
+    /// Archive file size in bytes (0 if it could not be read)
+    pub size: u64,
+
+    /// Whether the archive verified
+    pub status: SweepStatus,
+
+    /// Archive hash from the sidecar ("sha256:<hex>"), once verified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_hash: Option<String>,
+
+    /// Why verification failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Summary of a verification sweep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepReport {
+    /// Directory that was swept
+    pub root: PathBuf,
+
+    /// Whether archives were decompressed and their payloads checked
+    pub deep: bool,
+
+    /// When the sweep started
+    pub started: DateTime<Utc>,
+
+    /// Number of archives that verified
+    pub verified: usize,
+
+    /// Number of archives that failed
+    pub failed: usize,
+
+    /// Outcome for every archive found, in file name order
+    pub archives: Vec<SweepOutcome>,
+}
+
+impl SweepReport {
+    /// Whether every archive verified
+    pub fn is_clean(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Write the report as pretty-printed JSON
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        Ok(std::fs::write(path, json)?)
+    }
+}
+
+/// Find all .glif archives under a directory tree, in file name order
+///
+/// Directories that cannot be read are returned as errors alongside the
+/// archives so the caller can report them.
+pub fn find_archives<P: AsRef<Path>>(root: P) -> Vec<Result<PathBuf>> {
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => {
+                let is_archive = entry.file_type().is_file()
+                    && entry.path().extension().and_then(|e| e.to_str()) == Some("glif");
+                is_archive.then(|| Ok(entry.into_path()))
+            }
+            Err(e) => Some(Err(Error::from(e).into())),
+        })
+        .collect()
+}
+
+/// Verify one archive file, either a single-file or a directory archive
+///
+/// Armored archives are decoded first. With `deep`, the archive is
+/// decompressed and its payload hash checked, along with the hash of every
+/// file in a directory archive.
+pub fn verify_archive_file(path: &Path, deep: bool, threads: usize, progress: &mut dyn ProgressReporter) -> Result<GlifSidecar> {
+    let level = if deep { VerifyLevel::Full } else { VerifyLevel::Archive };
+    verify_archive_file_at(path, level, threads, progress)
+}
+
+/// Verify one archive file as thoroughly as `level` asks; `Full` also
+/// checks every file's hash in a directory archive
+pub fn verify_archive_file_at(path: &Path, level: VerifyLevel, threads: usize, progress: &mut dyn ProgressReporter) -> Result<GlifSidecar> {
+    let data = crate::armor::read_archive_file(path)?;
+    let info = GlifHeader::peek(&data)?;
+    let offset = info.header_offset()
+        .filter(|_| info.is_complete())
+        .ok_or_else(|| GlifError::Truncated("Archive is truncated before the end of its sidecar".to_string()))?;
+    let stream = &data[offset as usize..];
+    if level != VerifyLevel::Structural {
+        if let Some(header) = &info.header {
+            crate::archive::check_manifest_hash(&data[..offset as usize], header)?;
+        }
+    }
+
+    match level {
+        VerifyLevel::Structural => return crate::verify_structure(stream),
+        VerifyLevel::Archive => return crate::verify_archive_with_progress(stream, progress),
+        VerifyLevel::Full => {}
+    }
+
+    let config = DecompressionConfig::new().with_threads(threads);
+    let payload = crate::decompress_with_progress(stream, &config, progress)?;
+
+    if let Some(manifest) = &info.manifest {
+        progress.start_stage("file hashes", payload.len() as u64);
+        // Referenced contents are checked when their base archive is verified
+        for entry in manifest.entries.iter().filter(|entry| entry.external.is_none()) {
+            entry.data(&payload)?;
+            progress.advance(entry.data_offset + entry.size);
+        }
+        progress.finish_stage();
+    }
+
+    info.sidecar.ok_or_else(|| GlifError::Truncated("Archive sidecar is truncated".to_string()))
+}
+
+/// Verify every archive under `root`, calling `on_archive` as each one finishes
+pub fn sweep_archives<P: AsRef<Path>>(
+    root: P,
+    deep: bool,
+    threads: usize,
+    progress: &mut dyn ProgressReporter,
+    mut on_archive: impl FnMut(&SweepOutcome),
+) -> Result<SweepReport> {
+    let root = root.as_ref();
+    if !root.is_dir() {
+        return Err(Error::new(ErrorKind::NotFound, format!("{} is not a directory", root.display())).into());
+    }
+
+    let mut report = SweepReport {
+        root: root.to_path_buf(),
+        deep,
+        started: Utc::now(),
+        verified: 0,
+        failed: 0,
+        archives: Vec::new(),
+    };
+
+    for found in find_archives(root) {
+        let outcome = match found {
+            Ok(path) => verify_one(path, deep, threads, progress),
+            Err(e) => SweepOutcome {
+                path: root.to_path_buf(),
+                size: 0,
+                status: SweepStatus::Failed,
+                archive_hash: None,
+                error: Some(e.to_string()),
+            },
+        };
+
+        match outcome.status {
+            SweepStatus::Verified => report.verified += 1,
+            SweepStatus::Failed => report.failed += 1,
+        }
+        on_archive(&outcome);
+        report.archives.push(outcome);
+    }
+
+    Ok(report)
+}
+
+fn verify_one(path: PathBuf, deep: bool, threads: usize, progress: &mut dyn ProgressReporter) -> SweepOutcome {
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+    match verify_archive_file(&path, deep, threads, progress) {
+        Ok(sidecar) => {
+            let record = VerificationRecord::now(size, sidecar.archive.hash.clone());
+            // A missing record only means the next sweep re-verifies this archive
+            let _ = record.save(&path);
+            SweepOutcome {
+                path,
+                size,
+                status: SweepStatus::Verified,
+                archive_hash: Some(sidecar.archive.hash),
+                error: None,
+            }
+        }
+        Err(e) => SweepOutcome {
+            path,
+            size,
+            status: SweepStatus::Failed,
+            archive_hash: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use crate::CompressionConfig;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sweep_archives() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = CompressionConfig::default();
+
+        let archive = crate::compress(&b"sweep payload ".repeat(500), &config).unwrap();
+        fs::create_dir_all(root.join("daily/nested")).unwrap();
+        fs::write(root.join("good.glif"), &archive).unwrap();
+        fs::write(root.join("daily/nested/good.glif"), crate::armor::armor(&archive).unwrap()).unwrap();
+        fs::write(root.join("daily/notes.txt"), b"not an archive").unwrap();
+
+        let mut corrupt = archive.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        fs::write(root.join("daily/corrupt.glif"), &corrupt).unwrap();
+
+        let source = root.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), b"directory entry").unwrap();
+        crate::archive::DirectoryCompressor::new(crate::DirectoryCompressionConfig::new(config))
+            .unwrap()
+            .compress_directory(&source, root.join("daily/tree.glif"))
+            .unwrap();
+
+        let mut seen = 0;
+        let report = sweep_archives(root, true, 1, &mut NoProgress, |_| seen += 1).unwrap();
+        assert_eq!(seen, 4);
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.failed, 1);
+        assert!(!report.is_clean());
+
+        let failed: Vec<_> = report.archives.iter().filter(|a| a.status == SweepStatus::Failed).collect();
+        assert_eq!(failed[0].path, root.join("daily/corrupt.glif"));
+        assert!(failed[0].error.is_some());
+        assert!(VerificationRecord::load(&root.join("daily/tree.glif")).unwrap().is_some());
+        assert!(VerificationRecord::load(&root.join("daily/corrupt.glif")).unwrap().is_none());
+
+        let json_path = root.join("report.json");
+        report.write_json(&json_path).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&json_path).unwrap()).unwrap();
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["archives"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_verify_detects_manifest_damage() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tree.glif");
+        let tree = crate::testing::TreeBuilder::new().file("a.txt", "alpha").file("b.txt", "beta").build();
+        let archive = crate::archive::DirectoryCompressor::new(crate::DirectoryCompressionConfig::default().with_progress(false))
+            .unwrap()
+            .compress_source(&tree, std::path::PathBuf::from("tree"))
+            .unwrap();
+        assert!(GlifHeader::peek(&archive).unwrap().header.unwrap().manifest_hash().unwrap().is_some());
+        fs::write(&path, &archive).unwrap();
+        verify_archive_file_at(&path, VerifyLevel::Full, 1, &mut NoProgress).unwrap();
+
+        // A manifest rewritten in front of the original stream
+        let mut cursor = std::io::Cursor::new(&archive);
+        let mut manifest = crate::archive::ArchiveManifest::read(&mut cursor).unwrap();
+        manifest.entries[1].sha256 = "00".repeat(32);
+        let mut tampered = Vec::new();
+        manifest.write(&mut tampered).unwrap();
+        tampered.extend_from_slice(&archive[cursor.position() as usize..]);
+        fs::write(&path, &tampered).unwrap();
+        let error = verify_archive_file_at(&path, VerifyLevel::Archive, 1, &mut NoProgress).unwrap_err();
+        assert!(matches!(error, GlifError::ManifestHashMismatch { .. }), "{}", error);
+        verify_archive_file_at(&path, VerifyLevel::Structural, 1, &mut NoProgress).unwrap();
+
+        // Damage to the compressed data is reported as such
+        let mut corrupt = archive.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        fs::write(&path, &corrupt).unwrap();
+        let error = verify_archive_file_at(&path, VerifyLevel::Archive, 1, &mut NoProgress).unwrap_err();
+        assert!(error.corruption().is_some(), "{}", error);
+    }
+
+    #[test]
+    fn test_sweep_requires_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.glif");
+        fs::write(&file, b"x").unwrap();
+        assert!(sweep_archives(&file, false, 1, &mut NoProgress, |_| {}).is_err());
+    }
+}
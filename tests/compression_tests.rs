@@ -99,16 +99,14 @@ fn test_different_thread_counts() {
 #[test]
 fn test_random_data() {
     use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hash, Hasher};
+    use std::hash::BuildHasher;
 
     // Generate pseudo-random data (deterministic for testing)
     let mut data = Vec::with_capacity(10 * 1024 * 1024);
     let hasher_builder = RandomState::new();
 
     for i in 0..data.capacity() {
-        let mut hasher = hasher_builder.build_hasher();
-        i.hash(&mut hasher);
-        data.push((hasher.finish() % 256) as u8);
+        data.push((hasher_builder.hash_one(i) % 256) as u8);
     }
 
     let config = CompressionConfig::default();
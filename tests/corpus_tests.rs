@@ -0,0 +1,45 @@
+use glifzip::testing::corpus::{corpus_cases, corpus_versions, find_case, write_corpus, CORPUS_DIR, CORPUS_EXTENSION};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn corpus_archives(directory: &Path) -> Vec<PathBuf> {
+    let mut archives: Vec<PathBuf> = fs::read_dir(directory).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == CORPUS_EXTENSION))
+        .collect();
+    archives.sort();
+    archives
+}
+
+#[test]
+fn test_released_corpus_extracts_identically() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join(CORPUS_DIR);
+    let versions = corpus_versions(&root).unwrap();
+    assert!(!versions.is_empty(), "no corpus versions in {}", root.display());
+
+    for version in versions {
+        let archives = corpus_archives(&version);
+        assert!(!archives.is_empty(), "no archives in {}", version.display());
+
+        for archive in archives {
+            let name = archive.file_stem().unwrap().to_string_lossy();
+            let case = find_case(&name)
+                .unwrap_or_else(|| panic!("{} has no matching corpus case", archive.display()));
+
+            case.check(&fs::read(&archive).unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", archive.display(), e));
+        }
+    }
+}
+
+#[test]
+fn test_current_version_writes_full_corpus() {
+    let temp_dir = TempDir::new().unwrap();
+    let written = write_corpus(temp_dir.path()).unwrap();
+    assert_eq!(written.len(), corpus_cases().len());
+
+    for (path, case) in written.iter().zip(corpus_cases()) {
+        case.check(&fs::read(path).unwrap()).unwrap();
+    }
+}
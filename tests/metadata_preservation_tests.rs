@@ -1,7 +1,7 @@
 use glifzip::{DirectoryCompressor, DirectoryCompressionConfig, CompressionConfig, FileEntry};
 use std::fs::{self, File};
 use std::io::Write;
-use std::os::unix::fs::{PermissionsExt, MetadataExt};
+use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use tempfile::TempDir;
 use std::time::SystemTime;